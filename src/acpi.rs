@@ -0,0 +1,396 @@
+use config_table::{find_config_table, EFI_ACPI_20_TABLE_GUID, EFI_ACPI_TABLE_GUID};
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec};
+use core::{slice, fmt};
+use {Result, EfiErrorKind};
+
+// ACPI tables as published by the firmware. The RSDP comes from the configuration table and points
+// to the XSDT (or, on ACPI 1.0 systems, the RSDT) which lists the physical addresses of all other
+// tables except the DSDT and FACS, which hang off the FADT. Boot services memory is identity mapped
+// so physical addresses can be read directly.
+
+pub const FADT: [u8; 4] = *b"FACP";
+pub const MADT: [u8; 4] = *b"APIC";
+pub const MCFG: [u8; 4] = *b"MCFG";
+pub const SRAT: [u8; 4] = *b"SRAT";
+pub const SLIT: [u8; 4] = *b"SLIT";
+pub const HPET: [u8; 4] = *b"HPET";
+pub const BGRT: [u8; 4] = *b"BGRT";
+pub const DSDT: [u8; 4] = *b"DSDT";
+pub const SSDT: [u8; 4] = *b"SSDT";
+pub const FACS: [u8; 4] = *b"FACS";
+pub const FPDT: [u8; 4] = *b"FPDT";
+
+const RSDP_SIGNATURE: &[u8] = b"RSD PTR ";
+const RSDP_V1_SIZE: usize = 20;
+const RSDP_V2_SIZE: usize = 36;
+
+/// The Root System Description Pointer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rsdp {
+    pub oem_id: [u8; 6],
+    pub revision: u8,
+    pub rsdt_address: u32,
+    /// Only present from ACPI 2.0 on
+    pub xsdt_address: Option<u64>,
+}
+
+impl Rsdp {
+    /// Parses an RSDP, checking its signature and checksums
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < RSDP_V1_SIZE || &bytes[..8] != RSDP_SIGNATURE {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        if !checksum_valid(&bytes[..RSDP_V1_SIZE]) {
+            return Err(EfiErrorKind::CrcError.into());
+        }
+
+        let revision = bytes[15];
+        let xsdt_address = if revision >= 2 {
+            if bytes.len() < RSDP_V2_SIZE {
+                return Err(EfiErrorKind::BadBufferSize.into());
+            }
+
+            let length = LittleEndian::read_u32(&bytes[20..24]) as usize;
+            if length < RSDP_V2_SIZE || length > bytes.len() {
+                return Err(EfiErrorKind::BadBufferSize.into());
+            }
+
+            if !checksum_valid(&bytes[..length]) {
+                return Err(EfiErrorKind::CrcError.into());
+            }
+
+            nonzero(LittleEndian::read_u64(&bytes[24..32]))
+        } else {
+            None
+        };
+
+        let mut oem_id = [0; 6];
+        oem_id.copy_from_slice(&bytes[9..15]);
+        Ok(Self { oem_id, revision, rsdt_address: LittleEndian::read_u32(&bytes[16..20]), xsdt_address })
+    }
+}
+
+/// The header every system description table starts with
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: [u8; 4],
+    pub creator_revision: u32,
+}
+
+impl SdtHeader {
+    pub const SIZE: usize = 36;
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < Self::SIZE {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        let mut header = Self {
+            signature: [0; 4],
+            length: LittleEndian::read_u32(&bytes[4..8]),
+            revision: bytes[8],
+            checksum: bytes[9],
+            oem_id: [0; 6],
+            oem_table_id: [0; 8],
+            oem_revision: LittleEndian::read_u32(&bytes[24..28]),
+            creator_id: [0; 4],
+            creator_revision: LittleEndian::read_u32(&bytes[32..36]),
+        };
+        header.signature.copy_from_slice(&bytes[0..4]);
+        header.oem_id.copy_from_slice(&bytes[10..16]);
+        header.oem_table_id.copy_from_slice(&bytes[16..24]);
+        header.creator_id.copy_from_slice(&bytes[28..32]);
+        Ok(header)
+    }
+
+    /// The signature as text, e.g. "FACP"
+    pub fn signature_str(&self) -> String {
+        ascii_field(&self.signature)
+    }
+
+    pub fn oem_id_str(&self) -> String {
+        ascii_field(&self.oem_id)
+    }
+
+    pub fn oem_table_id_str(&self) -> String {
+        ascii_field(&self.oem_table_id)
+    }
+}
+
+/// A system description table in memory
+#[derive(Debug, Copy, Clone)]
+pub struct Table {
+    address: u64,
+    header: SdtHeader,
+    bytes: &'static [u8],
+}
+
+impl Table {
+    /// Reads the table at the given physical address
+    ///
+    /// # Safety
+    /// The address must point to a readable ACPI table that stays in place for the rest of the program
+    pub unsafe fn from_address(address: u64) -> Result<Self> {
+        if address == 0 {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        let header = SdtHeader::parse(slice::from_raw_parts(address as *const u8, SdtHeader::SIZE))?;
+        if (header.length as usize) < SdtHeader::SIZE {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        Ok(Self { address, header, bytes: slice::from_raw_parts(address as *const u8, header.length as usize) })
+    }
+
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    pub fn header(&self) -> &SdtHeader {
+        &self.header
+    }
+
+    pub fn signature(&self) -> [u8; 4] {
+        self.header.signature
+    }
+
+    /// The whole table including the header
+    pub fn bytes(&self) -> &'static [u8] {
+        self.bytes
+    }
+
+    /// The table's contents after the header
+    pub fn body(&self) -> &'static [u8] {
+        &self.bytes[SdtHeader::SIZE..]
+    }
+
+    pub fn checksum_valid(&self) -> bool {
+        checksum_valid(self.bytes)
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:#010x} {:6} (v{:02} {:6} {:8})",
+            self.header.signature_str(), self.address, self.header.length, self.header.revision,
+            self.header.oem_id_str(), self.header.oem_table_id_str())
+    }
+}
+
+/// The ACPI tables the firmware has published
+#[derive(Debug, Clone)]
+pub struct Acpi {
+    rsdp: Rsdp,
+    root: Table,
+    entries: Vec<u64>,
+}
+
+impl Acpi {
+    /// Locates the RSDP through the configuration table, preferring the ACPI 2.0 entry, and reads
+    /// the XSDT (or RSDT) it points to
+    pub fn new() -> Result<Self> {
+        let rsdp = find_config_table::<u8>(&EFI_ACPI_20_TABLE_GUID)
+            .or_else(|| find_config_table::<u8>(&EFI_ACPI_TABLE_GUID))
+            .ok_or(EfiErrorKind::NotFound)?;
+        unsafe { Self::from_rsdp(rsdp as u64) }
+    }
+
+    /// Reads the tables starting from an RSDP at the given physical address
+    ///
+    /// # Safety
+    /// The address must point to a readable RSDP whose tables stay in place for the rest of the program
+    pub unsafe fn from_rsdp(address: u64) -> Result<Self> {
+        let head = slice::from_raw_parts(address as *const u8, RSDP_V1_SIZE);
+        if &head[..8] != RSDP_SIGNATURE {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        // The ACPI 2.0 RSDP is always 36 bytes; its length field is checked against that by parse()
+        let size = if head[15] >= 2 { RSDP_V2_SIZE } else { RSDP_V1_SIZE };
+        let rsdp = Rsdp::parse(slice::from_raw_parts(address as *const u8, size))?;
+        let (root, entry_size) = match rsdp.xsdt_address {
+            Some(xsdt) => (Table::from_address(xsdt)?, 8),
+            None => (Table::from_address(rsdp.rsdt_address as u64)?, 4),
+        };
+
+        if !root.checksum_valid() {
+            return Err(EfiErrorKind::CrcError.into());
+        }
+
+        let entries = table_addresses(root.body(), entry_size);
+        Ok(Self { rsdp, root, entries })
+    }
+
+    pub fn rsdp(&self) -> &Rsdp {
+        &self.rsdp
+    }
+
+    /// The XSDT, or the RSDT if there isn't one
+    pub fn root(&self) -> &Table {
+        &self.root
+    }
+
+    /// Every table listed in the XSDT/RSDT plus the DSDT, in the order the firmware listed them.
+    /// Tables are returned even if their checksum is off; see `Table::checksum_valid()`.
+    pub fn tables(&self) -> Vec<Table> {
+        let mut tables = self.entries.iter()
+            .filter_map(|&address| unsafe { Table::from_address(address) }.ok())
+            .collect::<Vec<_>>();
+        if let Some(dsdt) = self.dsdt() {
+            tables.push(dsdt);
+        }
+        tables
+    }
+
+    /// The first table with the given signature and a valid checksum
+    pub fn find(&self, signature: [u8; 4]) -> Option<Table> {
+        self.find_all(signature).into_iter().next()
+    }
+
+    /// All tables with the given signature and a valid checksum, e.g. all the SSDTs
+    pub fn find_all(&self, signature: [u8; 4]) -> Vec<Table> {
+        self.tables().into_iter()
+            .filter(|t| t.signature() == signature && t.checksum_valid())
+            .collect()
+    }
+
+    /// The DSDT, found through the FADT
+    pub fn dsdt(&self) -> Option<Table> {
+        let fadt = self.entries.iter()
+            .filter_map(|&address| unsafe { Table::from_address(address) }.ok())
+            .find(|t| t.signature() == FADT)?;
+        dsdt_address(fadt.bytes()).and_then(|address| unsafe { Table::from_address(address) }.ok())
+    }
+}
+
+/// Whether the bytes of a table sum up to zero as they should
+pub fn checksum_valid(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) == 0
+}
+
+// The table addresses listed in the body of an XSDT (8 byte entries) or RSDT (4 byte entries)
+fn table_addresses(body: &[u8], entry_size: usize) -> Vec<u64> {
+    body.chunks(entry_size)
+        .filter(|e| e.len() == entry_size)
+        .map(|e| if entry_size == 8 { LittleEndian::read_u64(e) } else { LittleEndian::read_u32(e) as u64 })
+        .filter(|&address| address != 0)
+        .collect()
+}
+
+// The DSDT address from a FADT: X_DSDT at offset 140 if the table is long enough and it's set,
+// DSDT at offset 40 otherwise
+fn dsdt_address(fadt: &[u8]) -> Option<u64> {
+    if fadt.len() >= 148 {
+        let x_dsdt = LittleEndian::read_u64(&fadt[140..148]);
+        if x_dsdt != 0 {
+            return Some(x_dsdt);
+        }
+    }
+
+    if fadt.len() >= 44 {
+        nonzero(LittleEndian::read_u32(&fadt[40..44]) as u64)
+    } else {
+        None
+    }
+}
+
+fn nonzero(address: u64) -> Option<u64> {
+    if address == 0 { None } else { Some(address) }
+}
+
+fn ascii_field(bytes: &[u8]) -> String {
+    bytes.iter()
+        .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '?' })
+        .collect::<String>()
+        .trim_right()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fix_checksum(bytes: &mut [u8], at: usize, len: usize) {
+        bytes[at] = 0;
+        let sum = bytes[..len].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        bytes[at] = 0u8.wrapping_sub(sum);
+    }
+
+    fn rsdp(revision: u8) -> Vec<u8> {
+        let mut bytes = vec![0u8; RSDP_V2_SIZE];
+        bytes[..8].copy_from_slice(RSDP_SIGNATURE);
+        bytes[9..15].copy_from_slice(b"OEMID ");
+        bytes[15] = revision;
+        LittleEndian::write_u32(&mut bytes[16..20], 0x7fe1_0000);
+        LittleEndian::write_u32(&mut bytes[20..24], RSDP_V2_SIZE as u32);
+        LittleEndian::write_u64(&mut bytes[24..32], 0x7fe2_0000);
+        fix_checksum(&mut bytes, 8, RSDP_V1_SIZE);
+        fix_checksum(&mut bytes, 32, RSDP_V2_SIZE);
+        bytes
+    }
+
+    #[test]
+    fn rsdp_is_parsed_and_checked() {
+        let v2 = Rsdp::parse(&rsdp(2)).unwrap();
+        assert_eq!(v2.revision, 2);
+        assert_eq!(&v2.oem_id, b"OEMID ");
+        assert_eq!(v2.rsdt_address, 0x7fe1_0000);
+        assert_eq!(v2.xsdt_address, Some(0x7fe2_0000));
+
+        assert_eq!(Rsdp::parse(&rsdp(0)[..RSDP_V1_SIZE]).unwrap().xsdt_address, None);
+
+        let mut bad = rsdp(2);
+        bad[30] ^= 1;
+        assert_eq!(Rsdp::parse(&bad).unwrap_err().kind(), EfiErrorKind::CrcError);
+
+        let mut bad = rsdp(2);
+        bad[0] = b'X';
+        assert_eq!(Rsdp::parse(&bad).unwrap_err().kind(), EfiErrorKind::NotFound);
+    }
+
+    #[test]
+    fn headers_and_entries_are_read() {
+        let mut xsdt = vec![0u8; SdtHeader::SIZE];
+        xsdt[..4].copy_from_slice(b"XSDT");
+        xsdt[10..16].copy_from_slice(b"BOCHS ");
+        xsdt[16..24].copy_from_slice(b"BXPC    ");
+        for address in &[0x1000u64, 0, 0x2000] {
+            let mut entry = [0u8; 8];
+            LittleEndian::write_u64(&mut entry, *address);
+            xsdt.extend_from_slice(&entry);
+        }
+        let length = xsdt.len() as u32;
+        LittleEndian::write_u32(&mut xsdt[4..8], length);
+        fix_checksum(&mut xsdt, 9, length as usize);
+        assert!(checksum_valid(&xsdt));
+
+        let header = SdtHeader::parse(&xsdt).unwrap();
+        assert_eq!(header.signature_str(), "XSDT");
+        assert_eq!(header.oem_id_str(), "BOCHS");
+        assert_eq!(header.oem_table_id_str(), "BXPC");
+        assert_eq!(header.length, length);
+        assert_eq!(table_addresses(&xsdt[SdtHeader::SIZE..], 8), vec![0x1000, 0x2000]);
+        assert_eq!(table_addresses(&[0x00, 0x10, 0, 0, 0x00, 0x20, 0, 0], 4), vec![0x1000, 0x2000]);
+    }
+
+    #[test]
+    fn dsdt_prefers_the_64_bit_address() {
+        let mut fadt = vec![0u8; 148];
+        LittleEndian::write_u32(&mut fadt[40..44], 0x1000);
+        assert_eq!(dsdt_address(&fadt), Some(0x1000));
+        assert_eq!(dsdt_address(&fadt[..116]), Some(0x1000));
+        LittleEndian::write_u64(&mut fadt[140..148], 0x1_0000_0000);
+        assert_eq!(dsdt_address(&fadt), Some(0x1_0000_0000));
+        assert_eq!(dsdt_address(&[0u8; 148]), None);
+    }
+}
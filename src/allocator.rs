@@ -1,9 +1,18 @@
 
-use system_table;
+use boot_services;
 use alloc::allocator::{Alloc, AllocErr, Layout};
 use ffi::{EFI_SUCCESS, EFI_OUT_OF_RESOURCES, VOID, boot_services::EFI_MEMORY_TYPE};
 use core::ptr;
 
+// Runtime drivers must allocate from runtime memory or their heap disappears after ExitBootServices().
+// Boot service drivers allocate from boot services memory as the spec asks of them.
+#[cfg(not(any(feature = "runtime-driver", feature = "boot-driver")))]
+const POOL_TYPE: EFI_MEMORY_TYPE = EFI_MEMORY_TYPE::EfiLoaderData;
+#[cfg(all(feature = "boot-driver", not(feature = "runtime-driver")))]
+const POOL_TYPE: EFI_MEMORY_TYPE = EFI_MEMORY_TYPE::EfiBootServicesData;
+#[cfg(feature = "runtime-driver")]
+const POOL_TYPE: EFI_MEMORY_TYPE = EFI_MEMORY_TYPE::EfiRuntimeServicesData;
+
 pub struct EfiAllocator;
 
 unsafe impl<'a> Alloc for &'a EfiAllocator {
@@ -25,7 +34,7 @@ unsafe impl<'a> Alloc for &'a EfiAllocator {
         }
 
         let mut ptr = ptr::null() as *const VOID;
-        let status = ((*system_table().BootServices).AllocatePool)(EFI_MEMORY_TYPE::EfiLoaderData, layout.size(), &mut ptr);
+        let status = (boot_services().AllocatePool)(POOL_TYPE, layout.size(), &mut ptr);
         match status {
             EFI_SUCCESS => Ok(ptr as *mut u8),
             EFI_OUT_OF_RESOURCES => Err(AllocErr::Exhausted { request: layout }),
@@ -35,7 +44,7 @@ unsafe impl<'a> Alloc for &'a EfiAllocator {
 
     unsafe fn dealloc(&mut self, ptr: *mut u8, _layout: Layout) {
         // TODO: As mentioned above, stop ignoring layout::align() here
-        let status = ((*system_table().BootServices).FreePool)(ptr as *const VOID);
+        let status = (boot_services().FreePool)(ptr as *const VOID);
 
         if status != EFI_SUCCESS {
             panic!("UEFI FreePool returned an error");
@@ -0,0 +1,483 @@
+use console::{self, Console, ForeColor, BackColor, Position};
+use io::{self, Write};
+use alloc::Vec;
+use core::cmp;
+
+// Translates ANSI/VT100 escape sequences into SimpleTextOutput calls so that output meant for
+// terminals, e.g. colored log lines, shows up properly on the firmware console.
+// Supported are SGR colors (including bright, 256 color and 24 bit ones, approximated to the
+// 16 colors the console has), cursor movement and positioning, erasing and cursor visibility.
+// Other sequences are dropped.
+
+/// A writer that interprets ANSI escape sequences in the text written to it
+pub struct AnsiConsole {
+    console: Console,
+    parser: AnsiParser,
+    text: Vec<u8>,
+    attrs: Attributes,
+    saved_pos: Option<Position>,
+}
+
+impl AnsiConsole {
+    /// Wraps ConOut
+    pub fn new() -> Self {
+        Self::with_console(console::console())
+    }
+
+    pub fn with_console(console: Console) -> Self {
+        AnsiConsole { console, parser: AnsiParser::new(), text: Vec::new(), attrs: Attributes::default(), saved_pos: None }
+    }
+
+    pub fn into_inner(self) -> Console {
+        self.console
+    }
+
+    fn flush_text(&mut self) -> io::Result<()> {
+        if !self.text.is_empty() {
+            self.console.write_all(&self.text)?;
+            self.text.clear();
+        }
+
+        Ok(())
+    }
+
+    fn execute(&mut self, command: AnsiCommand) -> io::Result<()> {
+        self.flush_text()?;
+
+        // Consoles commonly don't support everything (e.g. hiding the cursor) and that shouldn't fail the write
+        let _ = match command {
+            AnsiCommand::SetGraphics(params) => {
+                self.attrs.apply_sgr(&params);
+                let (fore, back) = self.attrs.colors();
+                self.console.set_colors(ForeColor::from(fore as usize), BackColor::from((back as usize) << 4))
+            },
+            AnsiCommand::MoveTo { row, col } => self.move_to(row as i64, col as i64),
+            AnsiCommand::MoveBy { rows, cols } => {
+                let pos = self.console.cursor_pos();
+                self.move_to(pos.row as i64 + rows as i64, pos.col as i64 + cols as i64)
+            },
+            AnsiCommand::MoveToColumn(col) => {
+                let pos = self.console.cursor_pos();
+                self.move_to(pos.row as i64, col as i64)
+            },
+            AnsiCommand::EraseDisplay(mode) => self.erase_display(mode),
+            AnsiCommand::EraseLine(mode) => self.erase_line(mode),
+            AnsiCommand::SaveCursor => {
+                self.saved_pos = Some(self.console.cursor_pos());
+                Ok(())
+            },
+            AnsiCommand::RestoreCursor => match self.saved_pos {
+                Some(pos) => self.console.set_cursor_pos(pos),
+                None => Ok(())
+            },
+            AnsiCommand::ShowCursor(true) => self.console.enable_cursor(),
+            AnsiCommand::ShowCursor(false) => self.console.disable_cursor(),
+        };
+
+        Ok(())
+    }
+
+    // Moves the cursor clamped to the screen
+    fn move_to(&mut self, row: i64, col: i64) -> ::Result<()> {
+        let (columns, rows) = self.console.size()?;
+        let row = cmp::min(cmp::max(row, 0), rows as i64 - 1) as u32;
+        let col = cmp::min(cmp::max(col, 0), columns as i64 - 1) as u32;
+        self.console.set_cursor_pos(Position { row, col })
+    }
+
+    fn erase_display(&mut self, mode: u32) -> ::Result<()> {
+        match mode {
+            2 | 3 => {
+                let pos = self.console.cursor_pos();
+                self.console.clear_screen()?;
+                self.console.set_cursor_pos(pos) // Unlike ClearScreen() ANSI leaves the cursor where it was
+            },
+            _ => {
+                let pos = self.console.cursor_pos();
+                let (_, rows) = self.console.size()?;
+                let (first, last) = if mode == 0 { (pos.row + 1, rows) } else { (0, pos.row) };
+                self.erase_line(mode)?;
+                for row in first..last {
+                    self.blank(row, 0, None)?;
+                }
+                self.console.set_cursor_pos(pos)
+            }
+        }
+    }
+
+    fn erase_line(&mut self, mode: u32) -> ::Result<()> {
+        let pos = self.console.cursor_pos();
+        match mode {
+            0 => self.blank(pos.row, pos.col, None)?,
+            1 => self.blank(pos.row, 0, Some(pos.col + 1))?,
+            _ => self.blank(pos.row, 0, None)?,
+        }
+        self.console.set_cursor_pos(pos)
+    }
+
+    // Overwrites part of a row with spaces. Stops short of the last column since writing there scrolls some consoles.
+    fn blank(&mut self, row: u32, col: u32, end: Option<u32>) -> ::Result<()> {
+        let (columns, _) = self.console.size()?;
+        let end = cmp::min(end.unwrap_or(columns), columns.saturating_sub(1));
+        if col >= end {
+            return Ok(());
+        }
+
+        self.console.set_cursor_pos(Position { row, col })?;
+        let spaces = vec![b' '; (end - col) as usize];
+        self.console.write_all(&spaces).map_err(|_| ::EfiErrorKind::DeviceError)?;
+        Ok(())
+    }
+}
+
+impl io::Write for AnsiConsole {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            match self.parser.feed(byte) {
+                Parsed::Text(b) => self.text.push(b),
+                Parsed::Command(command) => self.execute(command)?,
+                Parsed::Pending => (),
+            }
+        }
+
+        self.flush_text()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_text()?;
+        self.console.flush()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AnsiCommand {
+    SetGraphics(Vec<u32>),
+    /// Zero based
+    MoveTo { row: u32, col: u32 },
+    MoveBy { rows: i32, cols: i32 },
+    MoveToColumn(u32),
+    EraseDisplay(u32),
+    EraseLine(u32),
+    SaveCursor,
+    RestoreCursor,
+    ShowCursor(bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Parsed {
+    Text(u8),
+    Command(AnsiCommand),
+    /// In the middle of an escape sequence
+    Pending,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ParserState {
+    Ground,
+    Escape,
+    Csi,
+    /// Operating system command e.g. setting the window title. Skipped.
+    Osc,
+}
+
+const ESC: u8 = 0x1B;
+const BEL: u8 = 0x07;
+const MAX_PARAMS: usize = 16;
+
+// Parses byte at a time so that sequences split across writes are handled
+struct AnsiParser {
+    state: ParserState,
+    params: Vec<u32>,
+    private: bool,
+}
+
+impl AnsiParser {
+    fn new() -> Self {
+        AnsiParser { state: ParserState::Ground, params: Vec::new(), private: false }
+    }
+
+    fn feed(&mut self, byte: u8) -> Parsed {
+        match self.state {
+            ParserState::Ground => {
+                if byte == ESC {
+                    self.state = ParserState::Escape;
+                    Parsed::Pending
+                } else {
+                    Parsed::Text(byte)
+                }
+            },
+            ParserState::Escape => {
+                self.state = ParserState::Ground;
+                match byte {
+                    b'[' => {
+                        self.state = ParserState::Csi;
+                        self.params.clear();
+                        self.private = false;
+                        Parsed::Pending
+                    },
+                    b']' => {
+                        self.state = ParserState::Osc;
+                        Parsed::Pending
+                    },
+                    b'7' => Parsed::Command(AnsiCommand::SaveCursor),
+                    b'8' => Parsed::Command(AnsiCommand::RestoreCursor),
+                    _ => Parsed::Pending, // Unsupported
+                }
+            },
+            ParserState::Osc => {
+                match byte {
+                    BEL => self.state = ParserState::Ground,
+                    ESC => self.state = ParserState::Escape, // Start of the ESC \ terminator. The backslash then gets dropped as unsupported.
+                    _ => (),
+                }
+                Parsed::Pending
+            },
+            ParserState::Csi => match byte {
+                b'0'...b'9' => {
+                    if self.params.is_empty() {
+                        self.params.push(0);
+                    }
+                    let last = self.params.last_mut().unwrap();
+                    *last = last.saturating_mul(10).saturating_add((byte - b'0') as u32);
+                    Parsed::Pending
+                },
+                b';' | b':' => {
+                    if self.params.is_empty() {
+                        self.params.push(0);
+                    }
+                    if self.params.len() < MAX_PARAMS {
+                        self.params.push(0);
+                    }
+                    Parsed::Pending
+                },
+                b'?' => {
+                    self.private = true;
+                    Parsed::Pending
+                },
+                0x20...0x2F | b'<' | b'=' | b'>' => Parsed::Pending, // Intermediate bytes. Nothing we support uses them.
+                0x40...0x7E => {
+                    self.state = ParserState::Ground;
+                    match self.command(byte) {
+                        Some(command) => Parsed::Command(command),
+                        None => Parsed::Pending,
+                    }
+                },
+                _ => { // Not a valid sequence. Give up on it.
+                    self.state = ParserState::Ground;
+                    Parsed::Pending
+                }
+            },
+        }
+    }
+
+    fn command(&self, final_byte: u8) -> Option<AnsiCommand> {
+        let param = |i: usize, default: u32| match self.params.get(i) {
+            Some(&0) | None => default,
+            Some(&p) => p,
+        };
+        let count = |default| cmp::min(param(0, default), i32::max_value() as u32) as i32;
+
+        if self.private {
+            return match (final_byte, self.params.get(0)) {
+                (b'h', Some(&25)) => Some(AnsiCommand::ShowCursor(true)),
+                (b'l', Some(&25)) => Some(AnsiCommand::ShowCursor(false)),
+                _ => None,
+            };
+        }
+
+        let command = match final_byte {
+            b'm' => AnsiCommand::SetGraphics(if self.params.is_empty() { vec![0] } else { self.params.clone() }),
+            b'H' | b'f' => AnsiCommand::MoveTo { row: param(0, 1) - 1, col: param(1, 1) - 1 },
+            b'A' => AnsiCommand::MoveBy { rows: -count(1), cols: 0 },
+            b'B' => AnsiCommand::MoveBy { rows: count(1), cols: 0 },
+            b'C' => AnsiCommand::MoveBy { rows: 0, cols: count(1) },
+            b'D' => AnsiCommand::MoveBy { rows: 0, cols: -count(1) },
+            b'G' => AnsiCommand::MoveToColumn(param(0, 1) - 1),
+            b'J' => AnsiCommand::EraseDisplay(self.params.get(0).cloned().unwrap_or(0)),
+            b'K' => AnsiCommand::EraseLine(self.params.get(0).cloned().unwrap_or(0)),
+            b's' => AnsiCommand::SaveCursor,
+            b'u' => AnsiCommand::RestoreCursor,
+            _ => return None,
+        };
+
+        Some(command)
+    }
+}
+
+// ANSI color numbers (black, red, green, yellow, blue, magenta, cyan, white) to the console's
+const ANSI_TO_EFI: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+const DEFAULT_FORE: u8 = 7; // Light gray
+const DEFAULT_BACK: u8 = 0;
+
+// The current SGR state with colors as console color numbers (0-15)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Attributes {
+    fore: u8,
+    back: u8,
+    bold: bool,
+    reverse: bool,
+}
+
+impl Default for Attributes {
+    fn default() -> Self {
+        Attributes { fore: DEFAULT_FORE, back: DEFAULT_BACK, bold: false, reverse: false }
+    }
+}
+
+impl Attributes {
+    fn apply_sgr(&mut self, params: &[u32]) {
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => *self = Attributes::default(),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                7 => self.reverse = true,
+                27 => self.reverse = false,
+                p @ 30...37 => self.fore = ANSI_TO_EFI[(p - 30) as usize],
+                39 => self.fore = DEFAULT_FORE,
+                p @ 40...47 => self.back = ANSI_TO_EFI[(p - 40) as usize],
+                49 => self.back = DEFAULT_BACK,
+                p @ 90...97 => self.fore = ANSI_TO_EFI[(p - 90) as usize] | 8,
+                p @ 100...107 => self.back = ANSI_TO_EFI[(p - 100) as usize] | 8,
+                p @ 38 | p @ 48 => {
+                    let (color, used) = extended_color(&params[i + 1..]);
+                    if let Some(color) = color {
+                        if p == 38 { self.fore = color } else { self.back = color }
+                    }
+                    i += used;
+                },
+                _ => (),
+            }
+            i += 1;
+        }
+    }
+
+    // The (fore, back) colors to set on the console. The background can only be one of the 8 dark colors.
+    fn colors(&self) -> (u8, u8) {
+        let mut fore = self.fore;
+        if self.bold && fore < 8 {
+            fore |= 8; // Bold is shown as bright as terminals usually do
+        }
+
+        if self.reverse {
+            (self.back, fore & 7)
+        } else {
+            (fore, self.back & 7)
+        }
+    }
+}
+
+// Parses the parameters after 38 or 48 i.e. "5;n" or "2;r;g;b". Returns the color and the number of parameters used.
+fn extended_color(params: &[u32]) -> (Option<u8>, usize) {
+    match params.get(0) {
+        Some(&5) => match params.get(1) {
+            Some(&n) => (Some(color_256_to_efi(n)), 2),
+            None => (None, 1),
+        },
+        Some(&2) if params.len() >= 4 => (Some(rgb_to_efi(params[1], params[2], params[3])), 4),
+        Some(_) => (None, params.len()),
+        None => (None, 0),
+    }
+}
+
+fn color_256_to_efi(n: u32) -> u8 {
+    match n {
+        0...7 => ANSI_TO_EFI[n as usize],
+        8...15 => ANSI_TO_EFI[(n - 8) as usize] | 8,
+        16...231 => { // 6x6x6 color cube
+            let n = n - 16;
+            let level = |v: u32| if v == 0 { 0 } else { 55 + v * 40 };
+            rgb_to_efi(level(n / 36), level(n / 6 % 6), level(n % 6))
+        },
+        _ => { // Grayscale ramp
+            let v = 8 + (cmp::min(n, 255) - 232) * 10;
+            rgb_to_efi(v, v, v)
+        }
+    }
+}
+
+// Picks the closest of the 16 console colors
+fn rgb_to_efi(r: u32, g: u32, b: u32) -> u8 {
+    let max = cmp::max(r, cmp::max(g, b));
+    if max < 64 {
+        return 0; // Black
+    }
+
+    let threshold = max / 2;
+    let ansi = (r > threshold) as usize | ((g > threshold) as usize) << 1 | ((b > threshold) as usize) << 2;
+    let color = ANSI_TO_EFI[ansi];
+    match (color, max > 191) {
+        (7, false) => 8,  // Dark gray rather than light gray
+        (0, _) => 0,
+        (c, true) => c | 8,
+        (c, false) => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> (Vec<u8>, Vec<AnsiCommand>) {
+        let mut parser = AnsiParser::new();
+        let (mut text, mut commands) = (Vec::new(), Vec::new());
+        for &b in bytes {
+            match parser.feed(b) {
+                Parsed::Text(b) => text.push(b),
+                Parsed::Command(c) => commands.push(c),
+                Parsed::Pending => (),
+            }
+        }
+        (text, commands)
+    }
+
+    #[test]
+    fn escape_sequences_are_parsed() {
+        let (text, commands) = parse(b"a\x1b[1;31mred\x1b[0m\x1b[2J\x1b[5;10H\x1b[3A\x1b[?25l\x1b[Kb");
+        assert_eq!(text, b"aredb");
+        assert_eq!(commands, vec![
+            AnsiCommand::SetGraphics(vec![1, 31]),
+            AnsiCommand::SetGraphics(vec![0]),
+            AnsiCommand::EraseDisplay(2),
+            AnsiCommand::MoveTo { row: 4, col: 9 },
+            AnsiCommand::MoveBy { rows: -3, cols: 0 },
+            AnsiCommand::ShowCursor(false),
+            AnsiCommand::EraseLine(0),
+        ]);
+
+        let (text, commands) = parse(b"\x1b[m\x1b[H\x1b]0;title\x07c\x1b]0;title\x1b\\d\x1b[1;2;3X");
+        assert_eq!(commands, vec![AnsiCommand::SetGraphics(vec![0]), AnsiCommand::MoveTo { row: 0, col: 0 }]);
+        assert_eq!(text, b"cd");
+    }
+
+    #[test]
+    fn sgr_sets_console_colors() {
+        let mut attrs = Attributes::default();
+        assert_eq!(attrs.colors(), (7, 0));
+        attrs.apply_sgr(&[31, 44]);
+        assert_eq!(attrs.colors(), (4, 1)); // Red on blue
+        attrs.apply_sgr(&[1]);
+        assert_eq!(attrs.colors(), (12, 1)); // Light red
+        attrs.apply_sgr(&[7]);
+        assert_eq!(attrs.colors(), (1, 4));
+        attrs.apply_sgr(&[0, 92, 103]);
+        assert_eq!(attrs.colors(), (10, 6)); // Light green on brown (bright backgrounds aren't available)
+        attrs.apply_sgr(&[38, 5, 196, 48, 2, 0, 0, 255]);
+        assert_eq!(attrs.colors(), (12, 1));
+        attrs.apply_sgr(&[39, 49]);
+        assert_eq!(attrs, Attributes::default());
+    }
+
+    #[test]
+    fn rgb_colors_are_approximated() {
+        assert_eq!(rgb_to_efi(0, 0, 0), 0);
+        assert_eq!(rgb_to_efi(255, 255, 255), 15);
+        assert_eq!(rgb_to_efi(128, 128, 128), 8);
+        assert_eq!(rgb_to_efi(0, 128, 0), 2);
+        assert_eq!(rgb_to_efi(255, 255, 0), 14); // Yellow
+        assert_eq!(color_256_to_efi(232), 0);
+        assert_eq!(color_256_to_efi(9), 12);
+    }
+}
@@ -0,0 +1,241 @@
+use image::LoadedImage;
+#[cfg(feature = "shell")] use shell::ShellParameters;
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec, vec};
+use {Result, EfiErrorKind};
+
+// Command line arguments of the running image, taken from its load options.
+//
+// When started from the shell the load options are the whole command line, so the first argument
+// is the name the image was run as. When started from a Boot#### option they're whatever text was
+// registered as the option's optional data, usually without a program name.
+
+/// The arguments the running image was started with. When started from the shell these are the
+/// arguments exactly as the shell split them. Otherwise the load options are split like the shell
+/// does: whitespace separates arguments, double quotes group them and `^` escapes the next character.
+pub fn args() -> Result<Args> {
+    #[cfg(feature = "shell")]
+    {
+        if let Ok(params) = ShellParameters::current() {
+            return Ok(Args { inner: params.argv().into_iter() });
+        }
+    }
+
+    let image = LoadedImage::current()?;
+    Ok(Args { inner: split_args(&decode_load_options(image.load_options())).into_iter() })
+}
+
+/// An iterator over command line arguments
+pub struct Args {
+    inner: vec::IntoIter<String>,
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Args {
+    fn next_back(&mut self) -> Option<String> {
+        self.inner.next_back()
+    }
+}
+
+impl ExactSizeIterator for Args {}
+
+// Load options are a UCS-2 string, possibly null terminated
+fn decode_load_options(options: &[u8]) -> String {
+    let chars = options.chunks(2)
+        .filter(|c| c.len() == 2)
+        .map(|c| LittleEndian::read_u16(c))
+        .take_while(|c| *c != 0)
+        .collect::<Vec<u16>>();
+    String::from_utf16_lossy(&chars)
+}
+
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false; // Distinguishes an empty quoted argument from no argument
+    let mut quoted = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '^' => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+                in_arg = true;
+            },
+            '"' => {
+                quoted = !quoted;
+                in_arg = true;
+            },
+            c if c.is_whitespace() && !quoted => {
+                if in_arg {
+                    args.push(current);
+                    current = String::new();
+                    in_arg = false;
+                }
+            },
+            c => {
+                current.push(c);
+                in_arg = true;
+            },
+        }
+    }
+
+    if in_arg {
+        args.push(current);
+    }
+
+    args
+}
+
+/// A minimal parser for `-x`/`--long` style flags. Each query removes what it matches so that
+/// whatever is left over at the end is either a positional argument or an unknown flag:
+///
+/// ```ignore
+/// let mut flags = Flags::new(args()?.skip(1));
+/// let verbose = flags.switch('v', "verbose");
+/// let server = flags.value('s', "server"); // -s x, --server x or --server=x
+/// let files = flags.finish()?; // Fails on unknown flags
+/// ```
+///
+/// Nothing after a `--` argument is taken to be a flag.
+pub struct Flags {
+    args: Vec<String>,
+}
+
+impl Flags {
+    pub fn new<I: IntoIterator<Item = String>>(args: I) -> Self {
+        Flags { args: args.into_iter().collect() }
+    }
+
+    /// Whether the flag is present. Repeats are allowed and all of them are removed.
+    pub fn switch(&mut self, short: char, long: &str) -> bool {
+        let mut found = false;
+        let mut i = 0;
+        while i < self.flag_end() {
+            if is_flag(&self.args[i], short, long) {
+                self.args.remove(i);
+                found = true;
+            } else {
+                i += 1;
+            }
+        }
+
+        found
+    }
+
+    /// The value given to a flag either as the next argument or after '=' with the long form.
+    /// If the flag is repeated the last value wins. A flag at the end with no value is left in
+    /// place so that `finish()` reports it.
+    pub fn value(&mut self, short: char, long: &str) -> Option<String> {
+        let mut value = None;
+        let mut i = 0;
+        while i < self.flag_end() {
+            let inline = {
+                let arg = &self.args[i];
+                let prefix = format!("--{}=", long);
+                if arg.starts_with(&prefix) { Some(arg[prefix.len()..].into()) } else { None }
+            };
+
+            if inline.is_some() {
+                value = inline;
+                self.args.remove(i);
+            } else if is_flag(&self.args[i], short, long) && i + 1 < self.flag_end() {
+                value = Some(self.args.remove(i + 1));
+                self.args.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        value
+    }
+
+    /// Returns the positional arguments, failing with `InvalidParameter` if any unrecognised flags remain
+    pub fn finish(self) -> Result<Vec<String>> {
+        let end = self.flag_end();
+        if self.args[..end].iter().any(|a| a.len() > 1 && a.starts_with('-')) {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let mut args = self.args;
+        if end < args.len() {
+            args.remove(end); // The "--"
+        }
+
+        Ok(args)
+    }
+
+    // The index of the "--" argument or the length if there isn't one
+    fn flag_end(&self) -> usize {
+        self.args.iter().position(|a| a == "--").unwrap_or(self.args.len())
+    }
+}
+
+fn is_flag(arg: &str, short: char, long: &str) -> bool {
+    if arg.starts_with("--") {
+        &arg[2..] == long
+    } else if arg.starts_with('-') {
+        let mut chars = arg[1..].chars();
+        chars.next() == Some(short) && chars.next().is_none()
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| String::from(*a)).collect()
+    }
+
+    #[test]
+    fn arguments_are_split_like_the_shell() {
+        assert_eq!(split_args("app.efi  -v \"fs0:\\My Files\" x"), strings(&["app.efi", "-v", "fs0:\\My Files", "x"]));
+        assert_eq!(split_args("a ^\"b^\" \"\" c^ d"), strings(&["a", "\"b\"", "", "c d"]));
+        assert!(split_args("   ").is_empty());
+    }
+
+    #[test]
+    fn load_options_are_decoded_up_to_the_terminator() {
+        let mut options = Vec::new();
+        for c in "x -y".encode_utf16().chain(Some(0)).chain("junk".encode_utf16()) {
+            options.push(c as u8);
+            options.push((c >> 8) as u8);
+        }
+        assert_eq!(decode_load_options(&options), "x -y");
+        assert_eq!(decode_load_options(&[]), "");
+    }
+
+    #[test]
+    fn flags_are_parsed() {
+        let mut flags = Flags::new(strings(&["-v", "in.txt", "--server=10.0.0.1", "-o", "out.txt", "--verbose", "--", "-x"]));
+        assert!(flags.switch('v', "verbose"));
+        assert!(!flags.switch('q', "quiet"));
+        assert_eq!(flags.value('s', "server"), Some("10.0.0.1".into()));
+        assert_eq!(flags.value('o', "output"), Some("out.txt".into()));
+        assert_eq!(flags.finish().unwrap(), strings(&["in.txt", "-x"]));
+
+        let mut flags = Flags::new(strings(&["--unknown", "x"]));
+        assert!(!flags.switch('u', "used"));
+        assert_eq!(flags.finish().unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+
+        let mut flags = Flags::new(strings(&["-o"]));
+        assert_eq!(flags.value('o', "output"), None);
+        assert!(flags.finish().is_err());
+    }
+}
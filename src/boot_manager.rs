@@ -0,0 +1,314 @@
+use ffi::runtime_services::EFI_GLOBAL_VARIABLE;
+use variables::{self, Attributes};
+use device_path::DevicePath;
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec};
+use {Result, EfiErrorKind};
+
+const LOAD_OPTION_ACTIVE: u32 = 0x00000001;
+const LOAD_OPTION_FORCE_RECONNECT: u32 = 0x00000002;
+const LOAD_OPTION_HIDDEN: u32 = 0x00000008;
+const LOAD_OPTION_CATEGORY: u32 = 0x00001F00;
+const LOAD_OPTION_CATEGORY_BOOT: u32 = 0x00000000;
+const LOAD_OPTION_CATEGORY_APP: u32 = 0x00000100;
+
+// Size of the fixed part of EFI_LOAD_OPTION i.e. Attributes (u32) and FilePathListLength (u16)
+const LOAD_OPTION_HEADER_SIZE: usize = 6;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadOptionCategory {
+    Boot,
+    App,
+    Other(u32),
+}
+
+/// A boot option as stored in a Boot#### variable (the EFI_LOAD_OPTION structure)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadOption {
+    attributes: u32,
+    description: String,
+    file_path_list: Vec<u8>,
+    optional_data: Vec<u8>,
+}
+
+impl LoadOption {
+    /// Creates a new active boot option which boots from the given path
+    pub fn new(description: &str, file_path: &DevicePath) -> Self {
+        Self { 
+            attributes: LOAD_OPTION_ACTIVE | LOAD_OPTION_CATEGORY_BOOT,
+            description: description.into(),
+            file_path_list: file_path.as_bytes().to_vec(),
+            optional_data: Vec::new()
+        }
+    }
+
+    /// Parses an EFI_LOAD_OPTION structure
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < LOAD_OPTION_HEADER_SIZE {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let attributes = LittleEndian::read_u32(&bytes[0..4]);
+        let file_path_list_len = LittleEndian::read_u16(&bytes[4..6]) as usize;
+
+        // The description is a null-terminated UCS-2 string
+        let mut description = Vec::new();
+        let mut offset = LOAD_OPTION_HEADER_SIZE;
+        loop {
+            if offset + 2 > bytes.len() {
+                return Err(EfiErrorKind::InvalidParameter.into()); // No null terminator
+            }
+
+            let c = LittleEndian::read_u16(&bytes[offset..offset + 2]);
+            offset += 2;
+            if c == 0 {
+                break;
+            }
+            description.push(c);
+        }
+
+        if offset + file_path_list_len > bytes.len() {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let file_path_list = bytes[offset..offset + file_path_list_len].to_vec();
+        let optional_data = bytes[offset + file_path_list_len..].to_vec();
+
+        Ok(Self { 
+            attributes,
+            description: String::from_utf16_lossy(&description), // Lossy because we don't want to fail on firmwares which put junk in here
+            file_path_list,
+            optional_data
+        })
+    }
+
+    /// Serializes this option into an EFI_LOAD_OPTION structure
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let description = self.description.encode_utf16().collect::<Vec<_>>();
+        let mut bytes = vec![0_u8; LOAD_OPTION_HEADER_SIZE + (description.len() + 1) * 2]; // + 1 for null terminator
+
+        LittleEndian::write_u32(&mut bytes[0..4], self.attributes);
+        LittleEndian::write_u16(&mut bytes[4..6], self.file_path_list.len() as u16);
+        for (i, c) in description.iter().enumerate() {
+            let offset = LOAD_OPTION_HEADER_SIZE + i * 2;
+            LittleEndian::write_u16(&mut bytes[offset..offset + 2], *c);
+        }
+
+        bytes.extend_from_slice(&self.file_path_list);
+        bytes.extend_from_slice(&self.optional_data);
+        bytes
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn set_description(&mut self, description: &str) {
+        self.description = description.into();
+    }
+
+    /// The first device path in this option's file path list. This is the path that the boot manager boots from.
+    pub fn device_path(&self) -> Result<DevicePath> {
+        DevicePath::from_bytes(&self.file_path_list)
+    }
+
+    pub fn set_device_path(&mut self, file_path: &DevicePath) {
+        self.file_path_list = file_path.as_bytes().to_vec();
+    }
+
+    /// The raw file path list. May contain more than one device path
+    pub fn file_path_list(&self) -> &[u8] {
+        &self.file_path_list
+    }
+
+    /// Binary data passed to the loaded image as its load options
+    pub fn optional_data(&self) -> &[u8] {
+        &self.optional_data
+    }
+
+    pub fn set_optional_data(&mut self, data: &[u8]) {
+        self.optional_data = data.to_vec();
+    }
+
+    pub fn attributes(&self) -> u32 {
+        self.attributes
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.attributes & LOAD_OPTION_ACTIVE != 0
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.set_attribute(LOAD_OPTION_ACTIVE, active);
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.attributes & LOAD_OPTION_HIDDEN != 0
+    }
+
+    pub fn set_hidden(&mut self, hidden: bool) {
+        self.set_attribute(LOAD_OPTION_HIDDEN, hidden);
+    }
+
+    pub fn force_reconnect(&self) -> bool {
+        self.attributes & LOAD_OPTION_FORCE_RECONNECT != 0
+    }
+
+    pub fn set_force_reconnect(&mut self, force_reconnect: bool) {
+        self.set_attribute(LOAD_OPTION_FORCE_RECONNECT, force_reconnect);
+    }
+
+    pub fn category(&self) -> LoadOptionCategory {
+        match self.attributes & LOAD_OPTION_CATEGORY {
+            LOAD_OPTION_CATEGORY_BOOT => LoadOptionCategory::Boot,
+            LOAD_OPTION_CATEGORY_APP => LoadOptionCategory::App,
+            c => LoadOptionCategory::Other(c),
+        }
+    }
+
+    fn set_attribute(&mut self, attribute: u32, value: bool) {
+        if value {
+            self.attributes |= attribute;
+        } else {
+            self.attributes &= !attribute;
+        }
+    }
+}
+
+/// A Boot#### variable along with its number
+#[derive(Debug, Clone)]
+pub struct BootEntry {
+    pub number: u16,
+    pub option: LoadOption,
+}
+
+fn boot_var_attributes() -> Attributes {
+    Attributes::NON_VOLATILE | Attributes::BOOTSERVICE_ACCESS | Attributes::RUNTIME_ACCESS
+}
+
+fn boot_var_name(number: u16) -> String {
+    format!("Boot{:04X}", number)
+}
+
+fn to_u16_list(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks(2)
+        .filter(|c| c.len() == 2)
+        .map(|c| LittleEndian::read_u16(c))
+        .collect()
+}
+
+fn from_u16_list(list: &[u16]) -> Vec<u8> {
+    let mut bytes = vec![0_u8; list.len() * 2];
+    for (i, n) in list.iter().enumerate() {
+        LittleEndian::write_u16(&mut bytes[i * 2..i * 2 + 2], *n);
+    }
+    bytes
+}
+
+/// The numbers of the boot entries in the order the boot manager tries them
+pub fn boot_order() -> Result<Vec<u16>> {
+    let order = variables::try_get("BootOrder", &EFI_GLOBAL_VARIABLE)?;
+    Ok(order.map(|o| to_u16_list(&o)).unwrap_or_else(Vec::new))
+}
+
+pub fn set_boot_order(order: &[u16]) -> Result<()> {
+    variables::set("BootOrder", &EFI_GLOBAL_VARIABLE, boot_var_attributes(), &from_u16_list(order))
+}
+
+/// All the boot entries listed in BootOrder, in that order.
+/// Entries listed in BootOrder that don't have a corresponding Boot#### variable are skipped.
+pub fn boot_entries() -> Result<Vec<BootEntry>> {
+    let mut entries = Vec::new();
+    for number in boot_order()? {
+        if let Some(bytes) = variables::try_get(&boot_var_name(number), &EFI_GLOBAL_VARIABLE)? {
+            entries.push(BootEntry { number, option: LoadOption::parse(&bytes)? });
+        }
+    }
+
+    Ok(entries)
+}
+
+pub fn boot_entry(number: u16) -> Result<LoadOption> {
+    let bytes = variables::get(&boot_var_name(number), &EFI_GLOBAL_VARIABLE)?;
+    LoadOption::parse(&bytes)
+}
+
+/// Creates or overwrites the Boot#### variable with the given number
+pub fn set_boot_entry(number: u16, option: &LoadOption) -> Result<()> {
+    variables::set(&boot_var_name(number), &EFI_GLOBAL_VARIABLE, boot_var_attributes(), &option.to_bytes())
+}
+
+/// Creates a new Boot#### variable using the first unused number and returns that number.
+/// The new entry is NOT added to BootOrder. Use `set_boot_order()` for that.
+pub fn create_boot_entry(option: &LoadOption) -> Result<u16> {
+    for number in 0..=0xFFFF_u16 {
+        if variables::try_get(&boot_var_name(number), &EFI_GLOBAL_VARIABLE)?.is_none() {
+            set_boot_entry(number, option)?;
+            return Ok(number);
+        }
+    }
+
+    Err(EfiErrorKind::OutOfResources.into())
+}
+
+/// Deletes the given Boot#### variable and removes it from BootOrder
+pub fn delete_boot_entry(number: u16) -> Result<()> {
+    let order = boot_order()?;
+    if order.contains(&number) {
+        let new_order = order.into_iter().filter(|n| *n != number).collect::<Vec<_>>();
+        set_boot_order(&new_order)?;
+    }
+
+    variables::delete(&boot_var_name(number), &EFI_GLOBAL_VARIABLE)
+}
+
+/// The boot entry that will be used on the next boot only, overriding BootOrder
+pub fn boot_next() -> Result<Option<u16>> {
+    let next = variables::try_get("BootNext", &EFI_GLOBAL_VARIABLE)?;
+    Ok(next.and_then(|n| to_u16_list(&n).first().cloned()))
+}
+
+pub fn set_boot_next(number: u16) -> Result<()> {
+    variables::set("BootNext", &EFI_GLOBAL_VARIABLE, boot_var_attributes(), &from_u16_list(&[number]))
+}
+
+pub fn clear_boot_next() -> Result<()> {
+    variables::delete("BootNext", &EFI_GLOBAL_VARIABLE)
+}
+
+/// The boot entry that was used to start the current boot
+pub fn boot_current() -> Result<u16> {
+    let current = variables::get("BootCurrent", &EFI_GLOBAL_VARIABLE)?;
+    to_u16_list(&current).first().cloned().ok_or_else(|| EfiErrorKind::NotFound.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Attributes: active, FilePathListLength: 4, Description: "ab", FilePathList: end node, OptionalData: [1, 2]
+    const OPTION: &[u8] = &[0x01, 0x00, 0x00, 0x00, 0x04, 0x00, b'a', 0x00, b'b', 0x00, 0x00, 0x00, 0x7f, 0xff, 0x04, 0x00, 0x01, 0x02];
+
+    #[test]
+    fn parse_load_option() {
+        let option = LoadOption::parse(OPTION).unwrap();
+        assert!(option.is_active());
+        assert!(!option.is_hidden());
+        assert_eq!(option.category(), LoadOptionCategory::Boot);
+        assert_eq!(option.description(), "ab");
+        assert_eq!(option.file_path_list(), &[0x7f, 0xff, 0x04, 0x00]);
+        assert_eq!(option.optional_data(), &[0x01, 0x02]);
+    }
+
+    #[test]
+    fn load_option_roundtrip() {
+        let option = LoadOption::parse(OPTION).unwrap();
+        assert_eq!(&option.to_bytes()[..], OPTION);
+    }
+
+    #[test]
+    fn parse_truncated_load_option() {
+        assert!(LoadOption::parse(&OPTION[..8]).is_err());
+        assert!(LoadOption::parse(&OPTION[..14]).is_err());
+    }
+}
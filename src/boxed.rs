@@ -8,7 +8,7 @@ use ffi::{
     EFI_SUCCESS,
     VOID,
 };
-use {system_table, Result};
+use {boot_services, Result};
 
 pub struct EfiBox<T>(Unique<T>);
 
@@ -16,7 +16,7 @@ impl<T> EfiBox<T> {
     #[inline]
     pub unsafe fn allocate(size: usize) -> Result<Self> {
         let mut ptr = ptr::null() as *const VOID;
-        let status = ((*system_table().BootServices).AllocatePool)(EFI_MEMORY_TYPE::EfiLoaderData, size, &mut ptr);
+        let status = (boot_services().AllocatePool)(EFI_MEMORY_TYPE::EfiLoaderData, size, &mut ptr);
         match status {
             EFI_SUCCESS => {
                 let unique = Unique::new_unchecked(ptr as *mut T);
@@ -46,7 +46,7 @@ impl<T> EfiBox<T> {
 
 impl<T> Drop for EfiBox<T> {
     fn drop(&mut self) {
-        unsafe { ((*system_table().BootServices).FreePool)(self.as_raw() as *const VOID) }; // No need to check status. Can't do anything if it fails.
+        unsafe { (boot_services().FreePool)(self.as_raw() as *const VOID) }; // No need to check status. Can't do anything if it fails.
     }
 }
 
@@ -0,0 +1,54 @@
+use core::cell::UnsafeCell;
+
+// Cells for globals. Boot services code runs on a single processor, so a global that is only ever written once
+// (typically at the entry point) and read afterwards needs no locking. Code running on other processors (e.g.
+// through MP services) must not initialize a cell, only read one that is already initialized.
+
+/// A cell that can be written only once. Unlike a `static mut` it can be read without `unsafe`.
+pub(crate) struct OnceCell<T> {
+    value: UnsafeCell<Option<T>>,
+}
+
+// See the comment at the top of the module. Holding raw pointers (the system table, handles) is the point of
+// these cells which is why T isn't required to be Sync. That is only sound because the type is crate internal.
+unsafe impl<T> Sync for OnceCell<T> {}
+
+impl<T> OnceCell<T> {
+    pub const fn new() -> Self {
+        OnceCell { value: UnsafeCell::new(None) }
+    }
+
+    /// The value if the cell has been initialized
+    pub fn get(&self) -> Option<&T> {
+        unsafe { (*self.value.get()).as_ref() }
+    }
+
+    /// Initializes the cell. Gives the value back if the cell is already initialized.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        if self.get().is_some() {
+            return Err(value);
+        }
+
+        unsafe { *self.value.get() = Some(value); }
+        Ok(())
+    }
+
+    // Replaces the value of an initialized cell. Any references obtained from get() must not be used afterwards.
+    pub(crate) unsafe fn replace(&self, value: T) {
+        *self.value.get() = Some(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OnceCell;
+
+    #[test]
+    fn cells_are_written_once() {
+        let cell = OnceCell::new();
+        assert_eq!(cell.get(), None);
+        assert_eq!(cell.set(1), Ok(()));
+        assert_eq!(cell.set(2), Err(2));
+        assert_eq!(cell.get(), Some(&1));
+    }
+}
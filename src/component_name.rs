@@ -0,0 +1,327 @@
+use ffi::{
+    component_name::{EFI_COMPONENT_NAME2_PROTOCOL, EFI_COMPONENT_NAME2_PROTOCOL_GUID},
+    boot_services::EFI_INTERFACE_TYPE,
+    EFI_HANDLE,
+    EFI_STATUS,
+    EFI_SUCCESS,
+    EFI_UNSUPPORTED,
+    EFI_INVALID_PARAMETER,
+    CHAR8,
+    CHAR16,
+    VOID,
+};
+use utils::{as_slice, to_null_terminated_utf16, split_languages, handles_by_protocol, open_protocol};
+use alloc::{String, Vec, boxed::Box, rc::Rc};
+use core::{ptr, slice, cell::RefCell};
+use {Result, EfiError, EfiErrorKind, boot_services, image_handle};
+
+// Human readable names of drivers and the controllers they manage (EFI_COMPONENT_NAME2_PROTOCOL),
+// as shown by e.g. the shell's drivers and devtree commands.
+
+/// Names a driver gives itself and its controllers, in one or more RFC 4646 languages such as "en-US".
+/// Controller names change as the driver starts and stops managing controllers so the table is shared
+/// between the installed protocol and the driver through an `Rc<RefCell<..>>`:
+///
+/// ```ignore
+/// let names = Rc::new(RefCell::new(NameTable::new().driver_name("en-US", "Example Disk Driver")));
+/// driver::keep_until_unload(ComponentName2::install(names.clone())?)?;
+/// // ...then in Driver::start()
+/// names.borrow_mut().set_controller_name(controller, None, "en-US", "Example Disk");
+/// ```
+#[derive(Debug, Default)]
+pub struct NameTable {
+    driver_names: Vec<(String, Vec<CHAR16>)>,
+    controller_names: Vec<ControllerName>,
+}
+
+#[derive(Debug)]
+struct ControllerName {
+    controller: EFI_HANDLE,
+    child: EFI_HANDLE, // Null for the controller itself
+    language: String,
+    name: Vec<CHAR16>,
+}
+
+impl NameTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the driver's name in a language. The languages the driver is named in are the ones the protocol supports.
+    pub fn driver_name(mut self, language: &str, name: &str) -> Self {
+        self.driver_names.push((language.into(), to_null_terminated_utf16(name)));
+        self
+    }
+
+    /// Sets the name of a controller, or of one of its children if `child` is given, replacing any previous one
+    pub fn set_controller_name(&mut self, controller: EFI_HANDLE, child: Option<EFI_HANDLE>, language: &str, name: &str) {
+        let child = child.unwrap_or(ptr::null());
+        self.controller_names.retain(|n| !(n.controller == controller && n.child == child && languages_match(&n.language, language)));
+        self.controller_names.push(ControllerName { controller, child, language: language.into(), name: to_null_terminated_utf16(name) });
+    }
+
+    /// Removes the names of a controller and all its children, for when the driver stops managing it
+    pub fn remove_controller(&mut self, controller: EFI_HANDLE) {
+        self.controller_names.retain(|n| n.controller != controller);
+    }
+
+    /// Removes the names of one child of a controller
+    pub fn remove_child(&mut self, controller: EFI_HANDLE, child: EFI_HANDLE) {
+        self.controller_names.retain(|n| !(n.controller == controller && n.child == child));
+    }
+
+    fn find_driver_name(&self, language: &str) -> Option<&[CHAR16]> {
+        self.driver_names.iter().find(|n| languages_match(&n.0, language)).map(|n| &n.1[..])
+    }
+
+    fn find_controller_name(&self, controller: EFI_HANDLE, child: EFI_HANDLE, language: &str) -> Option<&[CHAR16]> {
+        self.controller_names.iter()
+            .find(|n| n.controller == controller && n.child == child && languages_match(&n.language, language))
+            .map(|n| &n.name[..])
+    }
+
+    // The SupportedLanguages string, null terminated
+    fn supported_languages(&self) -> Vec<u8> {
+        let mut languages = Vec::new();
+        for (i, &(ref language, _)) in self.driver_names.iter().enumerate() {
+            if i > 0 {
+                languages.push(b';');
+            }
+            languages.extend_from_slice(language.as_bytes());
+        }
+        languages.push(0);
+        languages
+    }
+}
+
+// RFC 4646 language tags are case insensitive
+fn languages_match(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).all(|(a, b)| a.to_ascii_lowercase() == b.to_ascii_lowercase())
+}
+
+/// An EFI_COMPONENT_NAME2_PROTOCOL installed on the image handle and answering from a `NameTable`.
+/// Uninstalled when dropped.
+pub struct ComponentName2(Box<NameServer>);
+
+#[repr(C)] // repr C needed so that we can safely cast the protocol pointer back to this struct in the callbacks below
+struct NameServer {
+    proto: EFI_COMPONENT_NAME2_PROTOCOL,
+    table: Rc<RefCell<NameTable>>,
+    languages: Vec<u8>,
+}
+
+impl ComponentName2 {
+    pub fn install(table: Rc<RefCell<NameTable>>) -> Result<Self> {
+        let bs = boot_services();
+        let languages = table.borrow().supported_languages();
+        let server = Box::new(NameServer {
+            proto: EFI_COMPONENT_NAME2_PROTOCOL {
+                GetDriverName: get_driver_name_callback,
+                GetControllerName: get_controller_name_callback,
+                SupportedLanguages: languages.as_ptr() as *const CHAR8,
+            },
+            table,
+            languages,
+        });
+
+        let mut handle = image_handle();
+        unsafe {
+            ret_on_err!(((*bs).InstallProtocolInterface)(&mut handle, &EFI_COMPONENT_NAME2_PROTOCOL_GUID, EFI_INTERFACE_TYPE::EFI_NATIVE_INTERFACE, &server.proto as *const EFI_COMPONENT_NAME2_PROTOCOL as *const VOID));
+        }
+
+        Ok(ComponentName2(server))
+    }
+
+    pub fn table(&self) -> &Rc<RefCell<NameTable>> {
+        &self.0.table
+    }
+}
+
+impl Drop for ComponentName2 {
+    fn drop(&mut self) {
+        let bs = boot_services();
+        unsafe {
+            ((*bs).UninstallProtocolInterface)(image_handle(), &EFI_COMPONENT_NAME2_PROTOCOL_GUID, &self.0.proto as *const EFI_COMPONENT_NAME2_PROTOCOL as *const VOID); // TODO: Can't do anything if this fails. So we should log here
+        }
+    }
+}
+
+// The returned pointer stays valid until the table entry is replaced or removed
+fn lookup<F: Fn(&NameTable, &str) -> Option<*const CHAR16>>(this: *const EFI_COMPONENT_NAME2_PROTOCOL, language: *const CHAR8, name: *mut *const CHAR16, find: F) -> EFI_STATUS {
+    if this.is_null() || language.is_null() || name.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let server = unsafe { &*(this as *const NameServer) }; // Should be safe to do this cast since NameServer is marked repr C
+    let language = match unsafe { ascii_from_ptr(language) } {
+        Some(language) => language,
+        None => return EFI_INVALID_PARAMETER,
+    };
+
+    let table = match server.table.try_borrow() {
+        Ok(table) => table,
+        Err(_) => return EFI_UNSUPPORTED, // Being updated; can't happen unless called from within the driver itself
+    };
+
+    match find(&table, &language) {
+        Some(found) => {
+            unsafe { *name = found };
+            EFI_SUCCESS
+        },
+        None => EFI_UNSUPPORTED,
+    }
+}
+
+extern "win64" fn get_driver_name_callback(this: *const EFI_COMPONENT_NAME2_PROTOCOL, language: *const CHAR8, driver_name: *mut *const CHAR16) -> EFI_STATUS {
+    lookup(this, language, driver_name, |table, language| table.find_driver_name(language).map(|n| n.as_ptr()))
+}
+
+extern "win64" fn get_controller_name_callback(
+    this: *const EFI_COMPONENT_NAME2_PROTOCOL,
+    controller: EFI_HANDLE,
+    child: EFI_HANDLE,
+    language: *const CHAR8,
+    controller_name: *mut *const CHAR16
+) -> EFI_STATUS {
+    if controller.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    lookup(this, language, controller_name, |table, language| table.find_controller_name(controller, child, language).map(|n| n.as_ptr()))
+}
+
+unsafe fn ascii_from_ptr(s: *const CHAR8) -> Option<String> {
+    let mut len = 0;
+    while *s.offset(len as isize) != 0 {
+        len += 1;
+    }
+
+    let bytes = slice::from_raw_parts(s as *const u8, len);
+    if bytes.is_ascii() { Some(String::from_utf8_lossy(bytes).into_owned()) } else { None }
+}
+
+/// Another driver's EFI_COMPONENT_NAME2_PROTOCOL, for showing friendly names in diagnostics
+pub struct ComponentName {
+    handle: EFI_HANDLE,
+    protocol: *const EFI_COMPONENT_NAME2_PROTOCOL,
+}
+
+impl ComponentName {
+    /// Opens the protocol on a driver's image (or driver binding) handle
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol::<EFI_COMPONENT_NAME2_PROTOCOL>(handle, &EFI_COMPONENT_NAME2_PROTOCOL_GUID)?;
+
+        Ok(ComponentName { handle, protocol })
+    }
+
+    /// Opens the protocol of every driver that has one
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_COMPONENT_NAME2_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// The RFC 4646 languages the driver has names in
+    pub fn supported_languages(&self) -> Vec<String> {
+        let languages = unsafe { (*self.protocol).SupportedLanguages };
+        if languages.is_null() {
+            return Vec::new();
+        }
+
+        unsafe { ascii_from_ptr(languages) }.map_or(Vec::new(), |l| split_languages(&l))
+    }
+
+    /// The driver's name. If `language` is None English is preferred, falling back to whatever the driver has.
+    pub fn driver_name(&self, language: Option<&str>) -> Result<String> {
+        let language = self.language(language)?;
+        let mut name: *const CHAR16 = ptr::null();
+        unsafe {
+            ret_on_err!(((*self.protocol).GetDriverName)(self.protocol, language.as_ptr() as *const CHAR8, &mut name));
+        }
+
+        name_to_string(name)
+    }
+
+    /// The name the driver gives a controller it manages, or one of the controller's children
+    pub fn controller_name(&self, controller: EFI_HANDLE, child: Option<EFI_HANDLE>, language: Option<&str>) -> Result<String> {
+        let language = self.language(language)?;
+        let mut name: *const CHAR16 = ptr::null();
+        unsafe {
+            ret_on_err!(((*self.protocol).GetControllerName)(self.protocol, controller, child.unwrap_or(ptr::null()), language.as_ptr() as *const CHAR8, &mut name));
+        }
+
+        name_to_string(name)
+    }
+
+    // The language to ask in, null terminated
+    fn language(&self, language: Option<&str>) -> Result<Vec<u8>> {
+        let supported = self.supported_languages();
+        let mut language = match language {
+            Some(language) => String::from(language),
+            None => pick_language(&supported).ok_or_else(|| EfiError::from(EfiErrorKind::Unsupported))?,
+        }.into_bytes();
+        language.push(0);
+        Ok(language)
+    }
+}
+
+/// The name of a controller according to the first driver that has one for it
+pub fn controller_name(controller: EFI_HANDLE) -> Result<String> {
+    ComponentName::all()?.iter()
+        .filter_map(|c| c.controller_name(controller, None, None).ok())
+        .next()
+        .ok_or_else(|| EfiErrorKind::NotFound.into())
+}
+
+fn name_to_string(name: *const CHAR16) -> Result<String> {
+    if name.is_null() {
+        return Err(EfiErrorKind::NotFound.into());
+    }
+
+    Ok(String::from_utf16_lossy(unsafe { as_slice(name) }))
+}
+
+// Prefers English, then the first language
+fn pick_language(supported: &[String]) -> Option<String> {
+    supported.iter()
+        .find(|l| languages_match(l, "en") || l.to_ascii_lowercase().starts_with("en-"))
+        .or_else(|| supported.first())
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle(n: usize) -> EFI_HANDLE {
+        n as EFI_HANDLE
+    }
+
+    #[test]
+    fn names_are_looked_up_by_language() {
+        let mut table = NameTable::new().driver_name("en-US", "Disk Driver").driver_name("fr-FR", "Pilote de disque");
+        assert_eq!(table.supported_languages(), b"en-US;fr-FR\0".to_vec());
+        assert_eq!(table.find_driver_name("EN-us"), Some(&to_null_terminated_utf16("Disk Driver")[..]));
+        assert_eq!(table.find_driver_name("de-DE"), None);
+
+        table.set_controller_name(handle(1), None, "en-US", "Disk");
+        table.set_controller_name(handle(1), Some(handle(2)), "en-US", "Partition");
+        table.set_controller_name(handle(1), None, "en-US", "Renamed Disk");
+        assert_eq!(table.find_controller_name(handle(1), ptr::null(), "en-US"), Some(&to_null_terminated_utf16("Renamed Disk")[..]));
+        assert_eq!(table.find_controller_name(handle(1), handle(2), "en-US"), Some(&to_null_terminated_utf16("Partition")[..]));
+
+        table.remove_child(handle(1), handle(2));
+        assert_eq!(table.find_controller_name(handle(1), handle(2), "en-US"), None);
+        table.remove_controller(handle(1));
+        assert_eq!(table.find_controller_name(handle(1), ptr::null(), "en-US"), None);
+    }
+
+    #[test]
+    fn english_is_preferred() {
+        assert_eq!(pick_language(&split_languages("fr-FR;en-GB;en")), Some("en-GB".into()));
+        assert_eq!(pick_language(&split_languages("de;fr")), Some("de".into()));
+        assert_eq!(pick_language(&split_languages("")), None);
+    }
+}
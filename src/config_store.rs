@@ -0,0 +1,320 @@
+use variables::{self, Attributes};
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec};
+use {Result, Guid, EfiErrorKind};
+
+// Each key is stored as a header variable with the same name as the key. Small values are stored
+// inline in the header itself. Values too large for a single variable are split into chunk
+// variables named "<key>#<generation>#<index>" and the header records how many there are. Keys
+// therefore can't contain '#' themselves.
+//
+// Updates are atomic-ish: the new chunks are written under a fresh generation first and only
+// then the header (a single SetVariable() call) is switched over to them, after which the chunks
+// of the old generation are deleted. A reset in the middle therefore leaves either the old or
+// the new value readable, at worst with some orphaned chunks lying around.
+
+const FORMAT_VERSION: u8 = 1;
+const HEADER_SIZE: usize = 12;
+
+// Used if the firmware does not tell us the maximum variable size
+const DEFAULT_CHUNK_SIZE: usize = 1024;
+// Room left in a variable for its name and the firmware's own bookkeeping
+const VARIABLE_OVERHEAD: usize = 256;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+enum ValueType {
+    Bytes = 0,
+    Str = 1,
+    U64 = 2,
+    Bool = 3,
+}
+
+impl ValueType {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ValueType::Bytes),
+            1 => Some(ValueType::Str),
+            2 => Some(ValueType::U64),
+            3 => Some(ValueType::Bool),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Header {
+    value_type: ValueType,
+    generation: u8,
+    chunk_count: u16, // Zero means the value is inline after the header
+    total_len: u32,
+}
+
+impl Header {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        if bytes[0] != FORMAT_VERSION {
+            return Err(EfiErrorKind::IncompatibleVersion.into());
+        }
+
+        let value_type = ValueType::from_u8(bytes[1]).ok_or_else(|| ::EfiError::from(EfiErrorKind::VolumeCorrupted))?;
+        Ok(Header {
+            value_type,
+            generation: bytes[2],
+            chunk_count: LittleEndian::read_u16(&bytes[4..6]),
+            total_len: LittleEndian::read_u32(&bytes[8..12]),
+        })
+    }
+
+    fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0_u8; HEADER_SIZE];
+        bytes[0] = FORMAT_VERSION;
+        bytes[1] = self.value_type as u8;
+        bytes[2] = self.generation;
+        LittleEndian::write_u16(&mut bytes[4..6], self.chunk_count);
+        LittleEndian::write_u32(&mut bytes[8..12], self.total_len);
+        bytes
+    }
+}
+
+/// A typed key-value store kept in UEFI variables under a vendor GUID of the app's choosing.
+/// Values larger than the firmware's variable size limit are transparently split across multiple variables.
+pub struct ConfigStore {
+    vendor_guid: Guid,
+    attributes: Attributes,
+    chunk_size: Option<usize>,
+}
+
+impl ConfigStore {
+    /// Creates a store whose values persist across reboots and are accessible only before ExitBootServices()
+    pub fn new(vendor_guid: Guid) -> Self {
+        Self::with_attributes(vendor_guid, Attributes::NON_VOLATILE | Attributes::BOOTSERVICE_ACCESS)
+    }
+
+    pub fn with_attributes(vendor_guid: Guid, attributes: Attributes) -> Self {
+        ConfigStore { vendor_guid, attributes, chunk_size: None }
+    }
+
+    /// Overrides the size of the chunks large values are split into.
+    /// By default it is derived from the maximum variable size reported by the firmware.
+    /// A chunk is at least one byte so zero is treated as one.
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.chunk_size = Some(chunk_size.max(1));
+    }
+
+    pub fn get_bytes(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.get(key, ValueType::Bytes)
+    }
+
+    pub fn set_bytes(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.set(key, ValueType::Bytes, value)
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<Option<String>> {
+        match self.get(key, ValueType::Str)? {
+            Some(bytes) => String::from_utf8(bytes).map(Some).map_err(|_| EfiErrorKind::VolumeCorrupted.into()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_str(&self, key: &str, value: &str) -> Result<()> {
+        self.set(key, ValueType::Str, value.as_bytes())
+    }
+
+    pub fn get_u64(&self, key: &str) -> Result<Option<u64>> {
+        match self.get(key, ValueType::U64)? {
+            Some(ref bytes) if bytes.len() == 8 => Ok(Some(LittleEndian::read_u64(bytes))),
+            Some(_) => Err(EfiErrorKind::VolumeCorrupted.into()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_u64(&self, key: &str, value: u64) -> Result<()> {
+        let mut bytes = [0_u8; 8];
+        LittleEndian::write_u64(&mut bytes, value);
+        self.set(key, ValueType::U64, &bytes)
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        match self.get(key, ValueType::Bool)? {
+            Some(ref bytes) if bytes.len() == 1 => Ok(Some(bytes[0] != 0)),
+            Some(_) => Err(EfiErrorKind::VolumeCorrupted.into()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_bool(&self, key: &str, value: bool) -> Result<()> {
+        self.set(key, ValueType::Bool, &[value as u8])
+    }
+
+    pub fn contains(&self, key: &str) -> Result<bool> {
+        check_key(key)?;
+        Ok(variables::try_get(key, &self.vendor_guid)?.is_some())
+    }
+
+    /// Removes the key along with all of its chunks. Succeeds if the key does not exist.
+    pub fn remove(&self, key: &str) -> Result<()> {
+        check_key(key)?;
+        if let Some(bytes) = variables::try_get(key, &self.vendor_guid)? {
+            variables::delete(key, &self.vendor_guid)?; // Header first so that a half-removed value is never visible
+            if let Ok(header) = Header::parse(&bytes) {
+                self.delete_chunks(key, &header)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Fails with InvalidParameter if the value stored under the key is of a different type
+    fn get(&self, key: &str, value_type: ValueType) -> Result<Option<Vec<u8>>> {
+        check_key(key)?;
+        let header_bytes = match variables::try_get(key, &self.vendor_guid)? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+
+        let header = Header::parse(&header_bytes)?;
+        if header.value_type != value_type {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let value = if header.chunk_count == 0 {
+            header_bytes[HEADER_SIZE..].to_vec()
+        } else {
+            let mut value = Vec::with_capacity(header.total_len as usize);
+            for index in 0..header.chunk_count {
+                let chunk = variables::try_get(&chunk_name(key, header.generation, index), &self.vendor_guid)?
+                    .ok_or_else(|| ::EfiError::from(EfiErrorKind::VolumeCorrupted))?;
+                value.extend_from_slice(&chunk);
+            }
+            value
+        };
+
+        if value.len() != header.total_len as usize {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        Ok(Some(value))
+    }
+
+    fn set(&self, key: &str, value_type: ValueType, value: &[u8]) -> Result<()> {
+        check_key(key)?;
+        let old_header = match variables::try_get(key, &self.vendor_guid)? {
+            Some(bytes) => Header::parse(&bytes).ok(),
+            None => None,
+        };
+
+        let chunk_size = self.chunk_size();
+        let generation = old_header.map(|h| h.generation.wrapping_add(1)).unwrap_or(0);
+        let header = Header { value_type, generation, chunk_count: chunk_count(value.len(), chunk_size)?, total_len: value.len() as u32 };
+
+        let mut header_bytes = header.to_bytes().to_vec();
+        if header.chunk_count == 0 {
+            header_bytes.extend_from_slice(value);
+        } else {
+            for (index, chunk) in value.chunks(chunk_size).enumerate() {
+                variables::set(&chunk_name(key, generation, index as u16), &self.vendor_guid, self.attributes, chunk)?;
+            }
+        }
+
+        // This is the commit point. Until the header is written the old value remains intact.
+        variables::set(key, &self.vendor_guid, self.attributes, &header_bytes)?;
+
+        if let Some(old_header) = old_header {
+            self.delete_chunks(key, &old_header)?;
+        }
+
+        Ok(())
+    }
+
+    fn delete_chunks(&self, key: &str, header: &Header) -> Result<()> {
+        for index in 0..header.chunk_count {
+            variables::delete(&chunk_name(key, header.generation, index), &self.vendor_guid)?;
+        }
+
+        Ok(())
+    }
+
+    fn chunk_size(&self) -> usize {
+        if let Some(chunk_size) = self.chunk_size {
+            return chunk_size;
+        }
+
+        match variables::storage_info(self.attributes) {
+            Ok(info) if info.max_variable_size as usize > VARIABLE_OVERHEAD + HEADER_SIZE => info.max_variable_size as usize - VARIABLE_OVERHEAD,
+            _ => DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+// '#' separates the parts of chunk names so a key containing it could collide with another key's chunks
+fn check_key(key: &str) -> Result<()> {
+    if key.contains('#') {
+        return Err(EfiErrorKind::InvalidParameter.into());
+    }
+
+    Ok(())
+}
+
+// The number of chunks a value is split into, zero if it fits inline after the header
+fn chunk_count(value_len: usize, chunk_size: usize) -> Result<u16> {
+    if HEADER_SIZE + value_len <= chunk_size {
+        return Ok(0);
+    }
+
+    let count = (value_len + chunk_size - 1) / chunk_size;
+    if count > u16::max_value() as usize {
+        return Err(EfiErrorKind::BadBufferSize.into());
+    }
+
+    Ok(count as u16)
+}
+
+fn chunk_name(key: &str, generation: u8, index: u16) -> String {
+    format!("{}#{}#{}", key, generation, index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_roundtrip() {
+        let header = Header { value_type: ValueType::Str, generation: 7, chunk_count: 3, total_len: 70000 };
+        assert_eq!(Header::parse(&header.to_bytes()).unwrap(), header);
+    }
+
+    #[test]
+    fn header_with_unknown_version() {
+        let mut bytes = Header { value_type: ValueType::Bool, generation: 0, chunk_count: 0, total_len: 1 }.to_bytes();
+        bytes[0] = 2;
+        assert_eq!(Header::parse(&bytes).unwrap_err().kind(), EfiErrorKind::IncompatibleVersion);
+    }
+
+    #[test]
+    fn values_are_chunked() {
+        assert_eq!(chunk_count(0, 1024).unwrap(), 0);
+        assert_eq!(chunk_count(1024 - HEADER_SIZE, 1024).unwrap(), 0);
+        assert_eq!(chunk_count(1024 - HEADER_SIZE + 1, 1024).unwrap(), 1);
+        assert_eq!(chunk_count(1025, 1024).unwrap(), 2);
+        assert_eq!(chunk_count(0x10000, 1).unwrap_err().kind(), EfiErrorKind::BadBufferSize);
+        assert_eq!(chunk_name("boot", 3, 1), "boot#3#1");
+    }
+
+    #[test]
+    fn chunk_size_is_at_least_one() {
+        let mut store = ConfigStore::new(Guid(0, 0, 0, [0; 8]));
+        store.set_chunk_size(0);
+        assert_eq!(store.chunk_size(), 1);
+        assert_eq!(chunk_count(5, store.chunk_size()).unwrap(), 5);
+    }
+
+    #[test]
+    fn keys_cannot_contain_hash() {
+        assert!(check_key("boot.order").is_ok());
+        assert_eq!(check_key("boot#0#1").unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+    }
+}
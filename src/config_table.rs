@@ -0,0 +1,74 @@
+use ffi::{EFI_SYSTEM_TABLE, EFI_CONFIGURATION_TABLE};
+use core::slice;
+use {Guid, Void, system_table};
+
+pub use ffi::{EFI_ACPI_TABLE_GUID, EFI_ACPI_20_TABLE_GUID, SMBIOS_TABLE_GUID, SMBIOS3_TABLE_GUID, EFI_DTB_TABLE_GUID};
+
+// The configuration table is the list of (GUID, pointer) pairs the firmware uses to publish
+// things like the ACPI RSDP, the SMBIOS entry point and the device tree.
+
+/// Iterates over the (GUID, pointer) entries of the configuration table
+pub struct ConfigTableIter<'a> {
+    entries: slice::Iter<'a, EFI_CONFIGURATION_TABLE>,
+}
+
+impl<'a> Iterator for ConfigTableIter<'a> {
+    type Item = (Guid, *const Void);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|e| (e.VendorGuid, e.VendorTable as *const Void))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+pub(crate) fn entries(table: &EFI_SYSTEM_TABLE) -> ConfigTableIter {
+    let entries = if table.ConfigurationTable.is_null() {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(table.ConfigurationTable, table.NumberOfTableEntries) }
+    };
+
+    ConfigTableIter { entries: entries.iter() }
+}
+
+/// The (GUID, pointer) entries of the configuration table
+pub fn config_table() -> ConfigTableIter<'static> {
+    entries(system_table())
+}
+
+/// Looks up the configuration table entry with the given GUID, e.g.
+/// `find_config_table::<u8>(&SMBIOS3_TABLE_GUID)` for the SMBIOS 3 entry point.
+/// The pointer is handed out as is; what `T` it points to is up to the caller.
+pub fn find_config_table<T>(guid: &Guid) -> Option<*const T> {
+    config_table()
+        .find(|&(g, _)| g == *guid)
+        .map(|(_, table)| table as *const T)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{mem, ptr};
+    use alloc::Vec;
+
+    #[test]
+    fn entries_are_read_from_the_system_table() {
+        let smbios = 0x55u8;
+        let tables = [
+            EFI_CONFIGURATION_TABLE { VendorGuid: EFI_ACPI_20_TABLE_GUID, VendorTable: ptr::null() },
+            EFI_CONFIGURATION_TABLE { VendorGuid: SMBIOS3_TABLE_GUID, VendorTable: &smbios as *const u8 as *const () },
+        ];
+        let mut table: EFI_SYSTEM_TABLE = unsafe { mem::zeroed() };
+        assert_eq!(entries(&table).count(), 0);
+
+        table.ConfigurationTable = tables.as_ptr();
+        table.NumberOfTableEntries = tables.len();
+        let found = entries(&table).collect::<Vec<_>>();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].0, EFI_ACPI_20_TABLE_GUID);
+        assert_eq!(found[1], (SMBIOS3_TABLE_GUID, &smbios as *const u8 as *const Void));
+    }
+}
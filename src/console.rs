@@ -5,9 +5,46 @@ use ffi::{
         EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL, 
         EFI_KEY_DATA,
         EFI_INPUT_KEY,
+        CHAR_NULL,
+        CHAR_BACKSPACE,
+        CHAR_TAB,
+        CHAR_LINEFEED,
+        CHAR_CARRIAGE_RETURN,
+        SCAN_NULL,
+        SCAN_UP,
+        SCAN_DOWN,
+        SCAN_RIGHT,
+        SCAN_LEFT,
+        SCAN_HOME,
+        SCAN_END,
+        SCAN_INSERT,
+        SCAN_DELETE,
+        SCAN_PAGE_UP,
+        SCAN_PAGE_DOWN,
+        SCAN_F1,
+        SCAN_F10,
+        SCAN_F11,
+        SCAN_F12,
+        SCAN_ESC,
+        SCAN_F13,
+        SCAN_F24,
         EFI_SHIFT_STATE_VALID,
-        EFI_LEFT_CONTROL_PRESSED,
+        EFI_RIGHT_SHIFT_PRESSED,
+        EFI_LEFT_SHIFT_PRESSED,
         EFI_RIGHT_CONTROL_PRESSED,
+        EFI_LEFT_CONTROL_PRESSED,
+        EFI_RIGHT_ALT_PRESSED,
+        EFI_LEFT_ALT_PRESSED,
+        EFI_RIGHT_LOGO_PRESSED,
+        EFI_LEFT_LOGO_PRESSED,
+        EFI_MENU_KEY_PRESSED,
+        EFI_SYS_REQ_PRESSED,
+        EFI_KEY_TOGGLE_STATE,
+        EFI_TOGGLE_STATE_VALID,
+        EFI_KEY_STATE_EXPOSED,
+        EFI_SCROLL_LOCK_ACTIVE,
+        EFI_NUM_LOCK_ACTIVE,
+        EFI_CAPS_LOCK_ACTIVE,
         EFI_BLACK,
         EFI_BLUE,
         EFI_GREEN,
@@ -33,16 +70,22 @@ use ffi::{
         EFI_BACKGROUND_BROWN,
         EFI_BACKGROUND_LIGHTGRAY,
     }, 
+    boot_services::TPL_NOTIFY,
     IsSuccess, 
+    EFI_STATUS,
+    EFI_SUCCESS,
+    EFI_NOT_READY,
+    UINT32,
     UINTN,
+    VOID,
     TRUE,
     FALSE,
 };
-use core::cmp;
+use core::{cmp, char, ptr, mem, cell::RefCell};
 use io::{self, Write, Cursor, BufRead, BufReader, LineWriter};
-use ::Result;
-use system_table;
-use alloc::{Vec, String, str, fmt};
+use {Result, EfiErrorKind, WithWarning, to_res_with_warning};
+use {system_table, boot_services};
+use alloc::{Vec, String, str, fmt, boxed::Box};
 use TextInputProcolPtr;
 
 // TODO: This whole module has gotten ugly. Needs cleanup.
@@ -112,6 +155,277 @@ impl From<BackColor> for UINTN {
     }
 }
 
+/// A key press read from the console
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Tab,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Insert,
+    Delete,
+    PageUp,
+    PageDown,
+    /// F1 to F24
+    Function(u8),
+    /// A key with a scan code not covered above e.g. a media key
+    Other(u16),
+}
+
+impl Key {
+    // None for keystrokes that don't carry a key e.g. a lone press of a modifier
+    fn from_efi(key: &EFI_INPUT_KEY) -> Option<Self> {
+        let key = match key.ScanCode {
+            SCAN_NULL => match key.UnicodeChar {
+                CHAR_NULL => return None,
+                CHAR_CARRIAGE_RETURN | CHAR_LINEFEED => Key::Enter,
+                CHAR_BACKSPACE => Key::Backspace,
+                CHAR_TAB => Key::Tab,
+                ESC => Key::Escape, // Serial terminals often send ESC as a character rather than a scan code
+                c => Key::Char(char::from_u32(c as u32)?)
+            },
+            SCAN_UP => Key::Up,
+            SCAN_DOWN => Key::Down,
+            SCAN_RIGHT => Key::Right,
+            SCAN_LEFT => Key::Left,
+            SCAN_HOME => Key::Home,
+            SCAN_END => Key::End,
+            SCAN_INSERT => Key::Insert,
+            SCAN_DELETE => Key::Delete,
+            SCAN_PAGE_UP => Key::PageUp,
+            SCAN_PAGE_DOWN => Key::PageDown,
+            SCAN_ESC => Key::Escape,
+            SCAN_F1...SCAN_F10 => Key::Function((key.ScanCode - SCAN_F1 + 1) as u8),
+            SCAN_F11 => Key::Function(11),
+            SCAN_F12 => Key::Function(12),
+            SCAN_F13...SCAN_F24 => Key::Function((key.ScanCode - SCAN_F13 + 13) as u8),
+            scan_code => Key::Other(scan_code),
+        };
+
+        Some(key)
+    }
+
+    // None for keys that have no EFI encoding e.g. characters outside the BMP
+    fn to_efi(&self) -> Option<EFI_INPUT_KEY> {
+        let (scan_code, c) = match *self {
+            Key::Char(c) if (c as u32) < 0x10000 => (SCAN_NULL, c as u16),
+            Key::Char(_) => return None,
+            Key::Enter => (SCAN_NULL, CHAR_CARRIAGE_RETURN),
+            Key::Backspace => (SCAN_NULL, CHAR_BACKSPACE),
+            Key::Tab => (SCAN_NULL, CHAR_TAB),
+            Key::Escape => (SCAN_ESC, CHAR_NULL),
+            Key::Up => (SCAN_UP, CHAR_NULL),
+            Key::Down => (SCAN_DOWN, CHAR_NULL),
+            Key::Left => (SCAN_LEFT, CHAR_NULL),
+            Key::Right => (SCAN_RIGHT, CHAR_NULL),
+            Key::Home => (SCAN_HOME, CHAR_NULL),
+            Key::End => (SCAN_END, CHAR_NULL),
+            Key::Insert => (SCAN_INSERT, CHAR_NULL),
+            Key::Delete => (SCAN_DELETE, CHAR_NULL),
+            Key::PageUp => (SCAN_PAGE_UP, CHAR_NULL),
+            Key::PageDown => (SCAN_PAGE_DOWN, CHAR_NULL),
+            Key::Function(n @ 1...10) => (SCAN_F1 + n as u16 - 1, CHAR_NULL),
+            Key::Function(11) => (SCAN_F11, CHAR_NULL),
+            Key::Function(12) => (SCAN_F12, CHAR_NULL),
+            Key::Function(n @ 13...24) => (SCAN_F13 + n as u16 - 13, CHAR_NULL),
+            Key::Function(_) => return None,
+            Key::Other(scan_code) => (scan_code, CHAR_NULL),
+        };
+
+        Some(EFI_INPUT_KEY { ScanCode: scan_code, UnicodeChar: c })
+    }
+}
+
+flags! {
+    /// The modifier keys held down during a keystroke.
+    /// Can be combined using the `|` operator.
+    pub struct ShiftState(UINT32) ignoring EFI_SHIFT_STATE_VALID {
+        const RIGHT_SHIFT = EFI_RIGHT_SHIFT_PRESSED;
+        const LEFT_SHIFT = EFI_LEFT_SHIFT_PRESSED;
+        const RIGHT_CONTROL = EFI_RIGHT_CONTROL_PRESSED;
+        const LEFT_CONTROL = EFI_LEFT_CONTROL_PRESSED;
+        const RIGHT_ALT = EFI_RIGHT_ALT_PRESSED;
+        const LEFT_ALT = EFI_LEFT_ALT_PRESSED;
+        const RIGHT_LOGO = EFI_RIGHT_LOGO_PRESSED;
+        const LEFT_LOGO = EFI_LEFT_LOGO_PRESSED;
+        const MENU = EFI_MENU_KEY_PRESSED;
+        const SYS_REQ = EFI_SYS_REQ_PRESSED;
+    }
+}
+
+impl ShiftState {
+    /// Either Shift key is down
+    pub fn shift(&self) -> bool {
+        self.0 & (EFI_LEFT_SHIFT_PRESSED | EFI_RIGHT_SHIFT_PRESSED) != 0
+    }
+
+    /// Either Ctrl key is down
+    pub fn control(&self) -> bool {
+        self.0 & (EFI_LEFT_CONTROL_PRESSED | EFI_RIGHT_CONTROL_PRESSED) != 0
+    }
+
+    /// Either Alt key is down
+    pub fn alt(&self) -> bool {
+        self.0 & (EFI_LEFT_ALT_PRESSED | EFI_RIGHT_ALT_PRESSED) != 0
+    }
+}
+
+flags! {
+    /// The state of the lock keys.
+    /// Can be combined using the `|` operator.
+    pub struct ToggleState(EFI_KEY_TOGGLE_STATE) ignoring EFI_TOGGLE_STATE_VALID {
+        const SCROLL_LOCK = EFI_SCROLL_LOCK_ACTIVE;
+        const NUM_LOCK = EFI_NUM_LOCK_ACTIVE;
+        const CAPS_LOCK = EFI_CAPS_LOCK_ACTIVE;
+        /// Partial keystrokes (e.g. a lone press of Shift) are reported
+        const KEY_STATE_EXPOSED = EFI_KEY_STATE_EXPOSED;
+    }
+}
+
+/// A keystroke along with the state of the modifier and lock keys at the time
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// None for a partial keystroke i.e. one where only a modifier or lock key changed
+    pub key: Option<Key>,
+    /// None if the firmware doesn't report the modifier state
+    pub shift_state: Option<ShiftState>,
+    /// None if the firmware doesn't report the lock state
+    pub toggle_state: Option<ToggleState>,
+}
+
+impl KeyEvent {
+    fn from_efi(key_data: &EFI_KEY_DATA) -> Self {
+        let state = &key_data.KeyState;
+        let shift_state = if state.KeyShiftState & EFI_SHIFT_STATE_VALID != 0 {
+            Some(ShiftState::from_bits(state.KeyShiftState))
+        } else {
+            None
+        };
+
+        let toggle_state = if state.KeyToggleState & EFI_TOGGLE_STATE_VALID != 0 {
+            Some(ToggleState::from_bits(state.KeyToggleState))
+        } else {
+            None
+        };
+
+        Self { key: Key::from_efi(&key_data.Key), shift_state, toggle_state }
+    }
+}
+
+/// A key notification registered with `Console::register_key_notify()`.
+/// The notification is unregistered when this is dropped.
+pub struct KeyNotification {
+    input_ex: *mut EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL,
+    key: EFI_INPUT_KEY,
+    id: usize,
+}
+
+impl Drop for KeyNotification {
+    fn drop(&mut self) {
+        let removed = with_key_notify_fns(|fns| {
+            let id = self.id;
+            fns.iter().position(|f| f.id == id).map(|i| fns.remove(i))
+        });
+        drop(removed); // Freed here rather than at TPL_NOTIFY
+
+        let registrations = unsafe { KEY_REGISTRATIONS.get_or_insert_with(Vec::new) };
+        if let Some(handle) = release_key(registrations, self.input_ex, &self.key) {
+            unsafe {
+                ((*self.input_ex).UnregisterKeyNotify)(self.input_ex, handle);
+            }
+        }
+    }
+}
+
+struct KeyNotifyFn {
+    id: usize,
+    key_data: EFI_KEY_DATA,
+    callback: Box<FnMut()>,
+}
+
+// The firmware passes no context to key notification functions, so one notification function
+// is registered for every key and it finds the closures to call here by matching the keystroke.
+static mut KEY_NOTIFY_FNS: Option<Vec<KeyNotifyFn>> = None;
+static mut NEXT_KEY_NOTIFY_ID: usize = 0;
+
+// A key registered with the firmware. Keys are registered once, for any modifiers, however many closures there
+// are for them. Otherwise firmware that merges identical registrations (EDK2 hands back the same handle) would
+// have one KeyNotification's drop unregister all the others, and firmware that doesn't would run the closures
+// once per registration. The closures' own shift states are checked in key_notify_cb().
+struct KeyRegistration {
+    input_ex: *mut EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL,
+    key: EFI_INPUT_KEY,
+    handle: *mut VOID,
+    count: usize,
+}
+
+// Only touched at TPL_APPLICATION, never from key_notify_cb()
+static mut KEY_REGISTRATIONS: Option<Vec<KeyRegistration>> = None;
+
+fn same_key(a: &EFI_INPUT_KEY, b: &EFI_INPUT_KEY) -> bool {
+    a.ScanCode == b.ScanCode && a.UnicodeChar == b.UnicodeChar
+}
+
+// Takes another reference to the key's registration and returns its handle if the key is already registered
+fn acquire_key(registrations: &mut Vec<KeyRegistration>, input_ex: *mut EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL, key: &EFI_INPUT_KEY) -> Option<*mut VOID> {
+    let registration = registrations.iter_mut().find(|r| r.input_ex == input_ex && same_key(&r.key, key))?;
+    registration.count += 1;
+    Some(registration.handle)
+}
+
+// Drops a reference to the key's registration and returns its handle if that was the last one, for the caller
+// to unregister
+fn release_key(registrations: &mut Vec<KeyRegistration>, input_ex: *mut EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL, key: &EFI_INPUT_KEY) -> Option<*mut VOID> {
+    let i = registrations.iter().position(|r| r.input_ex == input_ex && same_key(&r.key, key))?;
+    registrations[i].count -= 1;
+    if registrations[i].count > 0 {
+        return None;
+    }
+
+    Some(registrations.remove(i).handle)
+}
+
+// Runs `f` on the registered closures with TPL raised so that key_notify_cb can't run in the middle
+fn with_key_notify_fns<R, F: FnOnce(&mut Vec<KeyNotifyFn>) -> R>(f: F) -> R {
+    unsafe {
+        let bs = boot_services();
+        let old_tpl = ((*bs).RaiseTPL)(TPL_NOTIFY);
+        let ret = f(KEY_NOTIFY_FNS.get_or_insert_with(Vec::new));
+        ((*bs).RestoreTPL)(old_tpl);
+        ret
+    }
+}
+
+extern "win64" fn key_notify_cb(key_data: *const EFI_KEY_DATA) -> EFI_STATUS {
+    unsafe {
+        if let Some(ref mut fns) = KEY_NOTIFY_FNS {
+            for f in fns.iter_mut().filter(|f| is_registered_key(&f.key_data, &*key_data)) {
+                (f.callback)();
+            }
+        }
+    }
+
+    EFI_SUCCESS
+}
+
+// Mirrors how the firmware decides whether a keystroke matches a registration: the shift state
+// only has to match if it was given at registration
+fn is_registered_key(registered: &EFI_KEY_DATA, pressed: &EFI_KEY_DATA) -> bool {
+    if registered.Key.ScanCode != pressed.Key.ScanCode || registered.Key.UnicodeChar != pressed.Key.UnicodeChar {
+        return false;
+    }
+
+    let registered_state = registered.KeyState.KeyShiftState;
+    registered_state & EFI_SHIFT_STATE_VALID == 0 || registered_state == pressed.KeyState.KeyShiftState
+}
+
 pub struct Console {
     pub input: TextInputProcolPtr,
     pub output: *const EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL,
@@ -121,6 +435,8 @@ pub struct Console {
 const LF: u16 = 10;
 const CR: u16 = 13;
 const BS: u16 = 8;
+const ESC: u16 = 0x1B;
+const SPACE: u16 = 0x20;
 
 impl Console {
     pub fn new(input: TextInputProcolPtr, output: *const EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL) -> Self {
@@ -156,6 +472,16 @@ impl Console {
         Ok(())
     }
 
+    pub fn is_cursor_visible(&self) -> bool {
+        unsafe { (*(*(*self).output).Mode).CursorVisible != FALSE }
+    }
+
+    /// The number of (columns, rows) of text that fit on the screen in the current mode
+    pub fn size(&self) -> Result<(u32, u32)> {
+        let mode = unsafe { (*(*(*self).output).Mode).Mode } as UINTN;
+        self.query_mode(mode)
+    }
+
     pub fn clear_screen(&mut self) -> Result<()> {
         unsafe {
             ret_on_err!(((*(*self).output).ClearScreen)(self.output));
@@ -171,6 +497,33 @@ impl Console {
         unsafe { (*(*(*self).output).Mode).MaxMode  as u32 } // Cast from i32 to u32 to is safe
     }
 
+    /// The number of the current text mode
+    pub fn mode(&self) -> u32 {
+        unsafe { (*(*(*self).output).Mode).Mode as u32 } // Cast from i32 to u32 to is safe
+    }
+
+    /// All text modes the console supports. Mode numbers the firmware reports as unsupported are skipped.
+    pub fn modes(&self) -> Vec<TextMode> {
+        let max_mode = unsafe { (*(*(*self).output).Mode).MaxMode } as u32;
+        (0..max_mode)
+            .filter_map(|number| self.query_mode(number as UINTN).ok().map(|(columns, rows)| TextMode { number, columns, rows }))
+            .collect()
+    }
+
+    /// Switches to the mode that fits the most text on the screen and returns it.
+    /// Note that switching modes clears the screen.
+    pub fn set_largest_mode(&mut self) -> Result<TextMode> {
+        let largest = self.modes().into_iter()
+            .max_by_key(|m| m.columns * m.rows)
+            .ok_or(EfiErrorKind::Unsupported)?;
+
+        if largest.number != self.mode() {
+            self.set_mode(largest.number)?;
+        }
+
+        Ok(largest)
+    }
+
     pub fn set_mode(&mut self, mode_number: u32) -> Result<()> {
         unsafe {
             ret_on_err!(((*(*self).output).SetMode)(self.output, mode_number as usize)); // TODO: Cast should be safe on patforms with 32 and 64 ptr widths. Do we need to worry about other platforms?
@@ -217,6 +570,15 @@ impl Console {
         Ok(())
     }
 
+    /// Sets both colors at once. Cheaper than calling `set_fore_color()` and `set_back_color()` separately.
+    pub fn set_colors(&mut self, fore_color: ForeColor, back_color: BackColor) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*(*self).output).SetAttribute)(self.output, usize::from(fore_color) | usize::from(back_color)));
+        }
+
+        Ok(())
+    }
+
     pub fn reset(&mut self, extended_verification: bool) -> Result<()> {
         unsafe {
             ret_on_err!(((*(*self).output).Reset)(self.output, if extended_verification { TRUE } else { FALSE }));
@@ -225,12 +587,181 @@ impl Console {
         Ok(())
     }
 
-    fn write_to_efi(&self, buf: &[u16]) -> Result<()> {
+    /// Waits for a key press and returns it. Keystrokes that don't make up a key (e.g. a lone Shift) are skipped.
+    pub fn read_key(&mut self) -> Result<Key> {
+        loop {
+            if let Some(key) = self.read_efi_key(true)?.and_then(|k| Key::from_efi(&k.Key)) {
+                return Ok(key);
+            }
+        }
+    }
+
+    /// Returns the next key press if there is one without waiting
+    pub fn try_read_key(&mut self) -> Result<Option<Key>> {
+        while let Some(key) = self.read_efi_key(false)? {
+            if let Some(key) = Key::from_efi(&key.Key) {
+                return Ok(Some(key));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Waits for a keystroke and returns it along with the modifier and lock state.
+    /// Unlike `read_key()` partial keystrokes are returned too if they've been enabled with `ToggleState::KEY_STATE_EXPOSED`.
+    /// Fails with `Unsupported` if the firmware doesn't provide the extended text input protocol.
+    pub fn read_key_ex(&mut self) -> Result<KeyEvent> {
+        self.input_ex()?;
+        loop {
+            if let Some(key_data) = self.read_efi_key(true)? {
+                return Ok(KeyEvent::from_efi(&key_data));
+            }
+        }
+    }
+
+    /// Returns the next keystroke along with the modifier and lock state if there is one without waiting.
+    /// Fails with `Unsupported` if the firmware doesn't provide the extended text input protocol.
+    pub fn try_read_key_ex(&mut self) -> Result<Option<KeyEvent>> {
+        self.input_ex()?;
+        Ok(self.read_efi_key(false)?.map(|k| KeyEvent::from_efi(&k)))
+    }
+
+    /// Sets the lock keys to the given state. Including `ToggleState::KEY_STATE_EXPOSED` enables partial keystrokes.
+    /// Fails with `Unsupported` if the firmware doesn't provide the extended text input protocol.
+    pub fn set_toggle_state(&mut self, toggle_state: ToggleState) -> Result<()> {
+        let input_ex = self.input_ex()?;
+        let state = toggle_state.bits() | EFI_TOGGLE_STATE_VALID;
+        unsafe {
+            ret_on_err!(((*input_ex).SetState)(input_ex, &state));
+        }
+
+        Ok(())
+    }
+
+    /// Calls `callback` whenever `key` is pressed, even while the application is busy elsewhere
+    /// e.g. to abort a long download on Ctrl+C. If `shift_state` is given the modifiers must match it exactly,
+    /// so Left Ctrl and Right Ctrl need separate registrations. Firmware usually reports Ctrl+C as `Key::Char('c')`.
+    /// The callback runs at TPL_NOTIFY from within the firmware's keyboard handling so it must be quick
+    /// and must not block or touch the console. It stays registered until the returned `KeyNotification` is dropped.
+    /// Fails with `Unsupported` if the firmware doesn't provide the extended text input protocol.
+    pub fn register_key_notify<F: FnMut() + 'static>(&mut self, key: Key, shift_state: Option<ShiftState>, callback: F) -> Result<KeyNotification> {
+        let input_ex = self.input_ex()?;
+        let mut key_data = EFI_KEY_DATA::default();
+        key_data.Key = match key.to_efi() {
+            Some(key) => key,
+            None => return Err(EfiErrorKind::InvalidParameter.into())
+        };
+
+        if let Some(shift_state) = shift_state {
+            key_data.KeyState.KeyShiftState = shift_state.bits() | EFI_SHIFT_STATE_VALID;
+        }
+
+        let registrations = unsafe { KEY_REGISTRATIONS.get_or_insert_with(Vec::new) };
+        if acquire_key(registrations, input_ex, &key_data.Key).is_none() {
+            let any_modifiers = EFI_KEY_DATA { Key: key_data.Key, ..EFI_KEY_DATA::default() };
+            let mut handle = ptr::null_mut();
+            unsafe {
+                ret_on_err!(((*input_ex).RegisterKeyNotify)(input_ex, &any_modifiers, key_notify_cb, &mut handle));
+            }
+            registrations.push(KeyRegistration { input_ex, key: key_data.Key, handle, count: 1 });
+        }
+
+        let callback = Box::new(callback);
+        let id = with_key_notify_fns(|fns| unsafe {
+            let id = NEXT_KEY_NOTIFY_ID;
+            NEXT_KEY_NOTIFY_ID += 1;
+            fns.push(KeyNotifyFn { id, key_data, callback });
+            id
+        });
+
+        Ok(KeyNotification { input_ex, key: key_data.Key, id })
+    }
+
+    /// Reads a line of text, echoing it as it's typed. Backspace deletes the last character and Escape clears the line.
+    /// Returns the line without the terminating newline once Enter is pressed.
+    pub fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+        loop {
+            match self.read_key()? {
+                Key::Enter => {
+                    self.write_to_efi(&[CR, LF, 0])?;
+                    return Ok(line);
+                },
+                Key::Backspace => {
+                    if line.pop().is_some() {
+                        self.write_to_efi(&[BS, SPACE, BS, 0])?; // Overwrite the character since BS only moves the cursor
+                    }
+                },
+                Key::Escape => {
+                    for _ in line.chars() {
+                        self.write_to_efi(&[BS, SPACE, BS, 0])?;
+                    }
+                    line.clear();
+                },
+                Key::Char(c) if (c as u32) < 0x10000 && !c.is_control() => { // UCS-2 can't show anything outside the BMP
+                    self.write_to_efi(&[c as u16, 0])?;
+                    line.push(c);
+                },
+                _ => ()
+            }
+        }
+    }
+
+    fn input_ex(&self) -> Result<*mut EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL> {
+        match self.input {
+            TextInputProcolPtr::InputEx(input_ex) => Ok(input_ex),
+            TextInputProcolPtr::Input(_) => Err(EfiErrorKind::Unsupported.into())
+        }
+    }
+
+    // Reads a keystroke from whichever input protocol we have. None if there's none pending and `wait` is false.
+    // With the plain input protocol the key state is left empty i.e. not valid.
+    fn read_efi_key(&self, wait: bool) -> Result<Option<EFI_KEY_DATA>> {
+        let mut evt_index: UINTN = 0;
+        let mut evt_list = match self.input {
+            TextInputProcolPtr::Input(input) => unsafe { [(*input).WaitForKey; 1] },
+            TextInputProcolPtr::InputEx(input_ex) => unsafe { [(*input_ex).WaitForKeyEx; 1] },
+        };
+
+        if wait {
+            unsafe {
+                ret_on_err!((boot_services().WaitForEvent)(evt_list.len(), evt_list.as_mut_ptr(), &mut evt_index));
+            }
+        }
+
+        let mut key_data = EFI_KEY_DATA::default();
+        let status = match self.input {
+            TextInputProcolPtr::Input(input) => unsafe { ((*input).ReadKeyStroke)(input, &mut key_data.Key) },
+            TextInputProcolPtr::InputEx(input_ex) => unsafe { ((*input_ex).ReadKeyStrokeEx)(input_ex, &mut key_data) },
+        };
+
+        match status {
+            EFI_NOT_READY => Ok(None),
+            s if IsSuccess(s) => Ok(Some(key_data)),
+            s => Err(s.into())
+        }
+    }
+
+    fn query_mode(&self, mode_number: UINTN) -> Result<(u32, u32)> {
+        let (mut columns, mut rows): (UINTN, UINTN) = (0, 0);
         unsafe {
-            let (ptr, _) = to_ptr(buf);
-            ret_on_err!(((*(*self).output).OutputString)(self.output, ptr));
-            Ok(())
+            ret_on_err!(((*(*self).output).QueryMode)(self.output, mode_number, &mut columns, &mut rows));
         }
+
+        Ok((columns as u32, rows as u32))
+    }
+
+    /// Writes the string as is i.e. without normalizing line endings.
+    /// Characters the device could not render are skipped and reported as `EfiWarning::UnknownGlyph`.
+    pub fn output_string(&mut self, s: &str) -> Result<WithWarning<()>> {
+        let buf = s.encode_utf16().chain(Some(0)).collect::<Vec<_>>();
+        self.write_to_efi(&buf)
+    }
+
+    fn write_to_efi(&self, buf: &[u16]) -> Result<WithWarning<()>> {
+        let (ptr, _) = to_ptr(buf);
+        let status = unsafe { ((*(*self).output).OutputString)(self.output, ptr) };
+        to_res_with_warning((), status)
     }
 
     fn read_from_efi(&self, buf: &mut [u16]) -> Result<usize> {
@@ -250,7 +781,7 @@ impl Console {
 
         while bytes_read < buf.len() {
             // TODO: For some reason we can't use ret_on_err here. Why?
-            let status = unsafe { ((*system_table().BootServices).WaitForEvent)(evt_list.len(), evt_list.as_mut_ptr(), &mut evt_index) };
+            let status = unsafe { (boot_services().WaitForEvent)(evt_list.len(), evt_list.as_mut_ptr(), &mut evt_index) };
             if !IsSuccess(status) {
                 return Err(status.into()); // TODO: Can we send some error text too with such errors
             }
@@ -314,7 +845,7 @@ impl Console {
 
         while bytes_read < buf.len() {
             // TODO: For some reason we can't use ret_on_err here. Why?
-            let status = unsafe { ((*system_table().BootServices).WaitForEvent)(evt_list.len(), evt_list.as_mut_ptr(), &mut evt_index) };
+            let status = unsafe { (boot_services().WaitForEvent)(evt_list.len(), evt_list.as_mut_ptr(), &mut evt_index) };
             if !IsSuccess(status) {
                 return Err(status.into()); // TODO: Can we send some error text too with such errors
             }
@@ -395,8 +926,8 @@ impl io::Write for Console {
 
         utf16_buf.push(0); // Appending the null terminator
 
-        self.write_to_efi(&utf16_buf)
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to write to EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL"))?; // TODO: Don't swallaow EFI status like this. Error handling in this whole crate needs fixing
+        // Characters the device can't show are skipped with EFI_WARN_UNKNOWN_GLYPH. The rest was written.
+        self.write_to_efi(&utf16_buf)?;
 
         Ok(utf8_buf.len())
     }
@@ -412,7 +943,7 @@ impl io::Read for Console {
         // Read more if the buffer is empty
         if self.utf8_buf.position() as usize == self.utf8_buf.get_ref().len() {
             let mut utf16_buf = vec![0u16; 0x1000];
-            let bytes_read = self.read_from_efi(&mut utf16_buf).map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to read from EFI_SIMPLE_TEXT_INPUT_PROTOCOL"))?; // TODO: again swallowing incoming efi status
+            let bytes_read = self.read_from_efi(&mut utf16_buf)?;
             utf16_buf.truncate(bytes_read as usize);
             // FIXME: what to do about this data that has already been read?
             let data = match String::from_utf16(&utf16_buf) {
@@ -428,12 +959,65 @@ impl io::Read for Console {
     }
 }
 
-// TODO: Implement StdErr
-pub struct StdIn(BufReader<Console>);
+// Where the standard streams go: the console, or a file the shell redirected them to
+enum Stream {
+    Console(Console),
+    #[cfg(all(feature = "shell", not(feature = "runtime-driver")))]
+    Shell(::shell::ShellFile),
+}
+
+impl io::Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Console(ref mut c) => c.read(buf),
+            #[cfg(all(feature = "shell", not(feature = "runtime-driver")))]
+            Stream::Shell(ref mut f) => f.read(buf),
+        }
+    }
+}
+
+impl io::Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Console(ref mut c) => c.write(buf),
+            #[cfg(all(feature = "shell", not(feature = "runtime-driver")))]
+            Stream::Shell(ref mut f) => f.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Stream::Console(ref mut c) => c.flush(),
+            #[cfg(all(feature = "shell", not(feature = "runtime-driver")))]
+            Stream::Shell(ref mut f) => f.flush(),
+        }
+    }
+}
+
+pub struct StdIn(BufReader<Stream>);
 
 impl StdIn {
-    fn new(c: Console) -> Self {
-        StdIn(BufReader::new(c))
+    fn new(s: Stream) -> Self {
+        StdIn(BufReader::new(s))
+    }
+
+    /// Waits for a key press and returns it. Note that this bypasses any text already buffered by `read()`.
+    /// Keys always come from the console, even when input has been redirected.
+    pub fn read_key(&mut self) -> Result<Key> {
+        match *self.0.get_mut() {
+            Stream::Console(ref mut c) => c.read_key(),
+            #[cfg(all(feature = "shell", not(feature = "runtime-driver")))]
+            _ => console().read_key(),
+        }
+    }
+
+    /// Returns the next key press if there is one without waiting
+    pub fn try_read_key(&mut self) -> Result<Option<Key>> {
+        match *self.0.get_mut() {
+            Stream::Console(ref mut c) => c.try_read_key(),
+            #[cfg(all(feature = "shell", not(feature = "runtime-driver")))]
+            _ => console().try_read_key(),
+        }
     }
 }
 
@@ -448,11 +1032,11 @@ impl BufRead for StdIn {
     fn consume(&mut self, n: usize) { self.0.consume(n) }
 }
 
-pub struct StdOut(LineWriter<Console>);
+pub struct StdOut(LineWriter<Stream>);
 
 impl StdOut {
-    fn new(c: Console) -> Self {
-        StdOut(LineWriter::new(c))
+    fn new(s: Stream) -> Self {
+        StdOut(LineWriter::new(s))
     }
 }
 
@@ -466,6 +1050,170 @@ impl io::Write for StdOut {
     }
 }
 
+/// Unbuffered output to the firmware's standard error console
+pub struct StdErr(Stream);
+
+impl io::Write for StdErr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+// So that the console can be used with write! from core and anything else that takes a fmt::Write
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+impl fmt::Write for StdOut {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+impl fmt::Write for StdErr {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+/// Duplicates everything written to it to a number of sinks e.g. ConOut, a serial port and a log file.
+/// A failing sink doesn't stop the others from being written; writes only fail if every sink fails.
+///
+/// Install it with `install()` to make it standard output for `print!`, `println!` and the logger's stdout target:
+///
+/// ```ignore
+/// Tee::new().console().serial(SerialPort::first()?).file(r"\EFI\app\log.txt")?.install();
+/// ```
+pub struct Tee {
+    sinks: Vec<Box<io::Write>>,
+}
+
+impl Tee {
+    pub fn new() -> Self {
+        Tee { sinks: Vec::new() }
+    }
+
+    /// Adds any writer as a sink
+    pub fn sink<W: io::Write + 'static>(mut self, writer: W) -> Self {
+        self.sinks.push(Box::new(writer));
+        self
+    }
+
+    /// Adds ConOut as a sink
+    pub fn console(self) -> Self {
+        self.sink(console())
+    }
+
+    /// Adds a serial port as a sink. Line feeds are sent as CR LF as terminals expect.
+    #[cfg(not(feature = "runtime-driver"))]
+    pub fn serial(self, port: ::serial::SerialPort) -> Self {
+        self.sink(CrLf(port))
+    }
+
+    /// Adds a file as a sink, appending to it. The file is created if it doesn't exist.
+    /// The path can be of any form accepted by `fs::open()`.
+    #[cfg(all(feature = "fs", not(feature = "runtime-driver")))]
+    pub fn file(self, path: &str) -> Result<Self> {
+        let mut file = ::fs::open(path, ::fs::OpenMode::CreateReadWrite, ::fs::FileAttributes::empty())?;
+        file.set_position(u64::max_value())?; // All ones is the end of the file as per the UEFI spec
+        Ok(self.sink(file))
+    }
+
+    /// Makes this standard output, replacing any previously installed tee which is returned
+    pub fn install(self) -> Option<Tee> {
+        unsafe { mem::replace(&mut STDOUT_TEE, Some(RefCell::new(self))).map(RefCell::into_inner) }
+    }
+
+    /// Stops using the installed tee as standard output and returns it e.g. so that its file can be closed
+    pub fn uninstall() -> Option<Tee> {
+        unsafe { STDOUT_TEE.take().map(RefCell::into_inner) }
+    }
+}
+
+impl io::Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut result = Ok(buf.len());
+        let mut any_ok = false;
+        for sink in self.sinks.iter_mut() {
+            match sink.write_all(buf) {
+                Ok(()) => any_ok = true,
+                Err(e) => result = Err(e),
+            }
+        }
+
+        if any_ok { Ok(buf.len()) } else { result }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        for sink in self.sinks.iter_mut() {
+            if let Err(e) = sink.flush() {
+                result = Err(e);
+            }
+        }
+
+        result
+    }
+}
+
+impl fmt::Write for Tee {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+// Translates LF to CR LF
+#[cfg(not(feature = "runtime-driver"))]
+struct CrLf<W: io::Write>(W);
+
+#[cfg(not(feature = "runtime-driver"))]
+impl<W: io::Write> io::Write for CrLf<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for (i, line) in buf.split(|b| *b == b'\n').enumerate() {
+            if i > 0 {
+                self.0.write_all(b"\r\n")?;
+            }
+            self.0.write_all(line)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+// Wrapped in a RefCell so that printing from within a sink (e.g. in an event notification) falls back to ConOut
+// instead of aliasing the tee
+static mut STDOUT_TEE: Option<RefCell<Tee>> = None;
+
+/// Writes to standard output i.e. the installed `Tee` if there is one, otherwise ConOut
+pub fn write_stdout(buf: &[u8]) -> io::Result<()> {
+    if let Some(ref tee) = unsafe { &STDOUT_TEE } {
+        if let Ok(mut tee) = tee.try_borrow_mut() {
+            return tee.write_all(buf);
+        }
+    }
+
+    stdout().write_all(buf)
+}
+
+/// A text mode of the console
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TextMode {
+    /// The number to pass to `Console::set_mode()`
+    pub number: u32,
+    pub columns: u32,
+    pub rows: u32,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Position {
     pub row: u32,
@@ -479,16 +1227,52 @@ pub fn console() -> Console {
 
 // TODO: Remove this uncessary SystemTable::new() business
 // Do we need this SystemTable type?
+/// Standard input. When started from the shell with input redirected from a file, reads the file.
 pub fn stdin() -> StdIn {
-    StdIn::new(console())
+    #[cfg(all(feature = "shell", not(feature = "runtime-driver")))]
+    {
+        if let Some(file) = ::shell::redirected_stdin() {
+            return StdIn::new(Stream::Shell(file));
+        }
+    }
+
+    StdIn::new(Stream::Console(console()))
 }
 
+/// Standard output. When started from the shell with output redirected to a file, writes the file.
 pub fn stdout() -> StdOut {
-    StdOut::new(console())
+    #[cfg(all(feature = "shell", not(feature = "runtime-driver")))]
+    {
+        if let Some(file) = ::shell::redirected_stdout() {
+            return StdOut::new(Stream::Shell(file));
+        }
+    }
+
+    StdOut::new(Stream::Console(console()))
+}
+
+/// The firmware's standard error console. Falls back to standard output if the firmware doesn't provide one.
+/// When started from the shell with standard error redirected to a file, writes the file.
+pub fn stderr() -> StdErr {
+    #[cfg(all(feature = "shell", not(feature = "runtime-driver")))]
+    {
+        if let Some(file) = ::shell::redirected_stderr() {
+            return StdErr(Stream::Shell(file));
+        }
+    }
+
+    let mut console = console();
+    let std_err = system_table().StdErr;
+    if !std_err.is_null() {
+        console.output = std_err;
+    }
+
+    StdErr(Stream::Console(console))
 }
 
 #[macro_export]
 macro_rules! println {
+    () => (print!("\n"));
     ($fmt:expr) => (print!(concat!($fmt, "\n")));
     ($fmt:expr, $($arg:tt)*) => (print!(concat!($fmt, "\n"), $($arg)*));
 }
@@ -498,9 +1282,31 @@ macro_rules! print {
     ($($arg:tt)*) => ($crate::console::print_args(format_args!($($arg)*)));
 }
 
+#[macro_export]
+macro_rules! eprintln {
+    () => (eprint!("\n"));
+    ($fmt:expr) => (eprint!(concat!($fmt, "\n")));
+    ($fmt:expr, $($arg:tt)*) => (eprint!(concat!($fmt, "\n"), $($arg)*));
+}
+
+#[macro_export]
+macro_rules! eprint {
+    ($($arg:tt)*) => ($crate::console::eprint_args(format_args!($($arg)*)));
+}
+
 // TODO: Call to stdout() creates a new StdOut obj everytime. Remove this extravagance.
 pub fn print_args(args: fmt::Arguments) {
-    return stdout().write_fmt(args).expect("Failed to write to stdout")
+    if let Some(ref tee) = unsafe { &STDOUT_TEE } {
+        if let Ok(mut tee) = tee.try_borrow_mut() {
+            return io::Write::write_fmt(&mut *tee, args).expect("Failed to write to stdout");
+        }
+    }
+
+    return io::Write::write_fmt(&mut stdout(), args).expect("Failed to write to stdout")
+}
+
+pub fn eprint_args(args: fmt::Arguments) {
+    io::Write::write_fmt(&mut stderr(), args).expect("Failed to write to stderr")
 }
 
 
@@ -518,3 +1324,96 @@ fn to_ptr<T>(slice: &[T]) -> (*const T, usize) {
 extern "rust-intrinsic" {
     fn transmute<T,U>(val: T) -> U;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(scan_code: u16, c: u16) -> Option<Key> {
+        Key::from_efi(&EFI_INPUT_KEY { ScanCode: scan_code, UnicodeChar: c })
+    }
+
+    #[test]
+    fn keys_are_decoded() {
+        assert_eq!(key(SCAN_NULL, 'a' as u16), Some(Key::Char('a')));
+        assert_eq!(key(SCAN_NULL, CHAR_CARRIAGE_RETURN), Some(Key::Enter));
+        assert_eq!(key(SCAN_NULL, CHAR_BACKSPACE), Some(Key::Backspace));
+        assert_eq!(key(SCAN_NULL, ESC), Some(Key::Escape));
+        assert_eq!(key(SCAN_ESC, CHAR_NULL), Some(Key::Escape));
+        assert_eq!(key(SCAN_UP, CHAR_NULL), Some(Key::Up));
+        assert_eq!(key(SCAN_F1, CHAR_NULL), Some(Key::Function(1)));
+        assert_eq!(key(SCAN_F10, CHAR_NULL), Some(Key::Function(10)));
+        assert_eq!(key(SCAN_F12, CHAR_NULL), Some(Key::Function(12)));
+        assert_eq!(key(SCAN_F24, CHAR_NULL), Some(Key::Function(24)));
+        assert_eq!(key(0x7F, CHAR_NULL), Some(Key::Other(0x7F)));
+        assert_eq!(key(SCAN_NULL, CHAR_NULL), None);
+        assert_eq!(key(SCAN_NULL, 0xD800), None); // Lone surrogate
+    }
+
+    #[test]
+    fn keys_round_trip_through_efi() {
+        let keys = [Key::Char('c'), Key::Enter, Key::Escape, Key::PageDown, Key::Function(1), Key::Function(11), Key::Function(24), Key::Other(0x7F)];
+        for k in keys.iter() {
+            assert_eq!(k.to_efi().and_then(|e| Key::from_efi(&e)), Some(*k));
+        }
+
+        assert!(Key::Function(0).to_efi().is_none());
+        assert!(Key::Function(25).to_efi().is_none());
+        assert!(Key::Char('\u{1F600}').to_efi().is_none());
+    }
+
+    fn key_data(c: char, shift_state: u32, toggle_state: u8) -> EFI_KEY_DATA {
+        let mut key_data = EFI_KEY_DATA::default();
+        key_data.Key.UnicodeChar = c as u16;
+        key_data.KeyState.KeyShiftState = shift_state;
+        key_data.KeyState.KeyToggleState = toggle_state;
+        key_data
+    }
+
+    #[test]
+    fn key_events_are_decoded() {
+        let event = KeyEvent::from_efi(&key_data('c', EFI_SHIFT_STATE_VALID | EFI_LEFT_CONTROL_PRESSED, EFI_TOGGLE_STATE_VALID | EFI_CAPS_LOCK_ACTIVE));
+        assert_eq!(event.key, Some(Key::Char('c')));
+        let shift_state = event.shift_state.unwrap();
+        assert_eq!(shift_state, ShiftState::LEFT_CONTROL);
+        assert!(shift_state.control() && !shift_state.shift() && !shift_state.alt());
+        assert_eq!(event.toggle_state, Some(ToggleState::CAPS_LOCK));
+
+        let event = KeyEvent::from_efi(&key_data('\0', EFI_LEFT_SHIFT_PRESSED, 0));
+        assert_eq!(event, KeyEvent { key: None, shift_state: None, toggle_state: None });
+    }
+
+    #[test]
+    fn registered_keys_are_matched() {
+        let ctrl_c = key_data('c', EFI_SHIFT_STATE_VALID | EFI_LEFT_CONTROL_PRESSED, 0);
+        let any_c = key_data('c', 0, 0);
+        assert!(is_registered_key(&ctrl_c, &ctrl_c));
+        assert!(is_registered_key(&any_c, &ctrl_c));
+        assert!(!is_registered_key(&ctrl_c, &key_data('c', EFI_SHIFT_STATE_VALID, 0)));
+        assert!(!is_registered_key(&ctrl_c, &key_data('d', EFI_SHIFT_STATE_VALID | EFI_LEFT_CONTROL_PRESSED, 0)));
+    }
+
+    #[test]
+    fn keys_are_registered_once() {
+        let input_ex = 0x1000 as *mut EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL;
+        let c = key_data('c', 0, 0).Key;
+        let d = key_data('d', 0, 0).Key;
+        let mut registrations = vec![KeyRegistration { input_ex, key: c, handle: 0x10 as *mut VOID, count: 1 }];
+        assert_eq!(acquire_key(&mut registrations, input_ex, &c), Some(0x10 as *mut VOID));
+        assert_eq!(acquire_key(&mut registrations, input_ex, &d), None);
+        assert_eq!(acquire_key(&mut registrations, 0x2000 as *mut _, &c), None);
+
+        assert_eq!(release_key(&mut registrations, input_ex, &c), None);
+        assert_eq!(release_key(&mut registrations, input_ex, &c), Some(0x10 as *mut VOID));
+        assert!(registrations.is_empty());
+        assert_eq!(release_key(&mut registrations, input_ex, &c), None);
+    }
+
+    #[test]
+    fn crlf_translates_line_feeds() {
+        let mut out = CrLf(Vec::new());
+        out.write_all(b"one\ntwo\n").unwrap();
+        out.write_all(b"three").unwrap();
+        assert_eq!(out.0, b"one\r\ntwo\r\nthree");
+    }
+}
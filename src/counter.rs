@@ -0,0 +1,51 @@
+use ffi::{UINT32, UINT64};
+use {system_table, boot_services, Result};
+
+/// Access to the platform's monotonic counter.
+///
+/// The counter is 64 bits wide. The low 32 bits are volatile and start from zero
+/// on every boot, while the high 32 bits are non-volatile and are incremented by
+/// the firmware on every system reset (or when the low 32 bits overflow).
+/// This makes the counter usable both as a source of non-repeating nonces
+/// and as a crude boot counter.
+pub struct MonotonicCounter {
+    _private: ()
+}
+
+impl MonotonicCounter {
+    pub fn new() -> Self {
+        MonotonicCounter { _private: () }
+    }
+
+    /// Returns the next value of the 64-bit monotonic counter.
+    /// No two calls ever return the same value, even across reboots.
+    /// Only available before ExitBootServices() is called.
+    pub fn next(&mut self) -> Result<u64> {
+        let bs = boot_services();
+        let mut count: UINT64 = 0;
+        unsafe {
+            ret_on_err!(((*bs).GetNextMonotonicCount)(&mut count));
+        }
+
+        Ok(count)
+    }
+
+    /// Increments the high 32 bits of the monotonic counter and returns the new value.
+    /// Unlike `next()` this goes via runtime services and hence can be used after ExitBootServices() as well.
+    pub fn next_high(&mut self) -> Result<u32> {
+        let rs = system_table().RuntimeServices;
+        let mut high_count: UINT32 = 0;
+        unsafe {
+            ret_on_err!(((*rs).GetNextHighMonotonicCount)(&mut high_count));
+        }
+
+        Ok(high_count)
+    }
+
+    /// The number of times the system has been reset, as recorded by the high 32 bits of the counter.
+    /// Note that the firmware also increments the high bits when the low 32 bits overflow,
+    /// so this is only a crude approximation of the boot count.
+    pub fn boot_count(&mut self) -> Result<u32> {
+        Ok((self.next()? >> 32) as u32)
+    }
+}
\ No newline at end of file
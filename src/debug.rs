@@ -0,0 +1,166 @@
+use ffi::{
+    EFI_HANDLE,
+    loaded_image::{EFI_LOADED_IMAGE_PROTOCOL, EFI_LOADED_IMAGE_PROTOCOL_GUID},
+};
+use core::{fmt, mem};
+use cell::OnceCell;
+use utils::open_protocol;
+
+// Backtraces for debugging on machines without a debugger. Frames are found by following the chain of saved
+// frame pointers, so the image must be built with frame pointers (`-C force-frame-pointers=yes`), otherwise
+// the backtrace stops early or is missing frames. Return addresses are printed relative to the image base
+// which is what tools like `addr2line` or `llvm-symbolizer` need to find the source line in the `.efi`'s
+// debug information (e.g. `llvm-symbolizer --obj=app.efi --adjust-vma=<preferred base> <offset>`).
+
+/// The most frames a `Backtrace` holds. Deeper frames are left out.
+pub const MAX_FRAMES: usize = 32;
+
+// The largest gap between two consecutive frames we believe. Anything larger means we're following garbage.
+const MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+// The base and size of the running image, recorded by init_env()
+static IMAGE: OnceCell<(usize, usize)> = OnceCell::new();
+
+pub(crate) fn record_image(image_handle: EFI_HANDLE) {
+    if let Ok(loaded_image) = open_protocol::<EFI_LOADED_IMAGE_PROTOCOL>(image_handle, &EFI_LOADED_IMAGE_PROTOCOL_GUID) {
+        let _ = unsafe { IMAGE.set(((*loaded_image).ImageBase as usize, (*loaded_image).ImageSize as usize)) };
+    }
+}
+
+/// The address the running image was loaded at. `None` if the firmware didn't say.
+pub fn image_base() -> Option<usize> {
+    IMAGE.get().map(|&(base, _)| base)
+}
+
+/// The return addresses of the calls that led to the point a backtrace was taken, innermost first
+#[derive(Copy, Clone)]
+pub struct Backtrace {
+    frames: [usize; MAX_FRAMES],
+    len: usize,
+    image: Option<(usize, usize)>,
+}
+
+impl Backtrace {
+    /// The absolute return addresses
+    pub fn frames(&self) -> &[usize] {
+        &self.frames[..self.len]
+    }
+
+    /// The return addresses relative to the image base. `None` for addresses outside the image, e.g. in firmware code.
+    pub fn image_offsets<'a>(&'a self) -> impl Iterator<Item=Option<usize>> + 'a {
+        self.frames().iter().map(move |&address| image_offset(self.image, address))
+    }
+}
+
+/// Prints one frame per line, e.g. `  #0 image+0x1a2b` or `  #5 0x7f8e4000` for a frame outside the image
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (&address, offset)) in self.frames().iter().zip(self.image_offsets()).enumerate() {
+            match offset {
+                Some(offset) => write!(f, "  #{} image+{:#x}\n", i, offset)?,
+                None => write!(f, "  #{} {:#x}\n", i, address)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.frames().iter().map(|a| *a as *const u8)).finish()
+    }
+}
+
+/// Captures the calls leading up to this one. Doesn't allocate so it's safe to use in a panic handler.
+#[inline(never)]
+pub fn backtrace() -> Backtrace {
+    let mut backtrace = Backtrace { frames: [0; MAX_FRAMES], len: 0, image: IMAGE.get().cloned() };
+    unsafe {
+        // Our own frame's return address is into the caller which is where the backtrace should start
+        backtrace.len = walk(frame_address(), &mut backtrace.frames);
+    }
+    backtrace
+}
+
+#[allow(improper_ctypes)]
+extern {
+    #[link_name = "llvm.frameaddress"]
+    fn llvm_frame_address(level: i32) -> *const u8;
+}
+
+#[inline(always)]
+unsafe fn frame_address() -> usize {
+    llvm_frame_address(0) as usize
+}
+
+// Follows the frame pointer chain from the given frame. Each frame starts with the caller's frame pointer
+// followed by the return address into the caller. Stops at a null or misaligned frame pointer or one that
+// doesn't move up the stack by a believable amount, since the outermost frames are often firmware code built
+// without frame pointers.
+unsafe fn walk(mut fp: usize, frames: &mut [usize]) -> usize {
+    let word = mem::size_of::<usize>();
+    let mut len = 0;
+    while len < frames.len() && fp != 0 && fp % word == 0 {
+        let return_address = *((fp + word) as *const usize);
+        if return_address == 0 {
+            break;
+        }
+
+        frames[len] = return_address;
+        len += 1;
+
+        let caller_fp = *(fp as *const usize);
+        if caller_fp <= fp || caller_fp - fp > MAX_FRAME_SIZE {
+            break;
+        }
+
+        fp = caller_fp;
+    }
+
+    len
+}
+
+fn image_offset(image: Option<(usize, usize)>, address: usize) -> Option<usize> {
+    match image {
+        Some((base, size)) if address >= base && address - base < size => Some(address - base),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::Vec;
+
+    #[test]
+    fn frame_pointer_chains_are_followed() {
+        // Three frames laid out like a real stack, the last one pointing back down the stack
+        let mut stack = [0usize; 12];
+        let base = stack.as_ptr() as usize;
+        let word = mem::size_of::<usize>();
+        stack[0] = base + 4 * word;
+        stack[1] = 0x1010;
+        stack[4] = base + 8 * word;
+        stack[5] = 0x1020;
+        stack[8] = base;
+        stack[9] = 0x1030;
+
+        let mut frames = [0; MAX_FRAMES];
+        let len = unsafe { walk(base, &mut frames) };
+        assert_eq!(&frames[..len], &[0x1010, 0x1020, 0x1030]);
+
+        let mut short = [0; 2];
+        assert_eq!(unsafe { walk(base, &mut short) }, 2);
+        assert_eq!(unsafe { walk(0, &mut frames) }, 0);
+    }
+
+    #[test]
+    fn frames_are_printed_relative_to_the_image() {
+        let mut backtrace = Backtrace { frames: [0; MAX_FRAMES], len: 2, image: Some((0x10000, 0x8000)) };
+        backtrace.frames[0] = 0x11a2b;
+        backtrace.frames[1] = 0x7f8e4000;
+        assert_eq!(format!("{}", backtrace), "  #0 image+0x1a2b\n  #1 0x7f8e4000\n");
+        assert_eq!(backtrace.image_offsets().collect::<Vec<_>>(), vec![Some(0x1a2b), None]);
+    }
+}
@@ -0,0 +1,564 @@
+use ffi::{
+    decompress::{EFI_DECOMPRESS_PROTOCOL, EFI_DECOMPRESS_PROTOCOL_GUID},
+    VOID,
+};
+use io::{self, Read};
+use byteorder::{ByteOrder, LittleEndian};
+use utils::locate_protocol;
+use alloc::{Vec, boxed::Box};
+use core::cmp;
+use {Result, EfiError, EfiErrorKind};
+
+// The compression format of the UEFI spec (EFI) and its Tiano variant, which differ only in how many
+// bits encode the size of the position table. Firmware volume sections, option ROMs and capsules carry
+// payloads in these formats. Data starts with an 8 byte header: compressed size and original size.
+
+/// Which variant of the format a payload uses
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Algorithm {
+    /// The algorithm described in the UEFI spec
+    Efi,
+    /// The variant used by Tiano (EDK) tools, with a larger dictionary
+    Tiano,
+}
+
+impl Algorithm {
+    fn pbit(self) -> u32 {
+        match self {
+            Algorithm::Efi => 4,
+            Algorithm::Tiano => 5,
+        }
+    }
+}
+
+const HEADER_SIZE: usize = 8;
+
+const BITBUFSIZ: u32 = 32;
+const MAXMATCH: usize = 256;
+const THRESHOLD: usize = 3;
+const CODE_BIT: usize = 16;
+// Char and length set
+const NC: usize = 0xFF + MAXMATCH + 2 - THRESHOLD;
+const CBIT: u32 = 9;
+// Position set
+const MAXPBIT: u32 = 5;
+const MAXNP: usize = (1 << MAXPBIT) - 1;
+// Extra set, the code lengths of the char and length set
+const TBIT: u32 = 5;
+const NT: usize = CODE_BIT + 3;
+const NPT: usize = MAXNP; // The larger of NT and MAXNP
+const TREE_SIZE: usize = 2 * NC - 1;
+
+// Largest distance a match can reach back. Tiano's dictionary is 2^19 bytes and EFI's smaller.
+const WINDOW_SIZE: usize = 1 << 19;
+
+/// The size the payload decompresses to, from its header
+pub fn decompressed_size(data: &[u8]) -> Result<usize> {
+    parse_header(data).map(|(_, size)| size)
+}
+
+/// Decompresses a whole payload, using the firmware's EFI_DECOMPRESS_PROTOCOL for the EFI algorithm
+/// when there is one and the built in decompressor otherwise
+pub fn decompress(data: &[u8], algorithm: Algorithm) -> Result<Vec<u8>> {
+    if algorithm == Algorithm::Efi {
+        if let Some(protocol) = decompress_protocol() {
+            return decompress_with_protocol(protocol, data);
+        }
+    }
+
+    let mut decompressor = Decompressor::new(data, algorithm)?;
+    let mut out = vec![0u8; decompressor.size()];
+    let mut filled = 0;
+    while filled < out.len() {
+        match decompressor.read(&mut out[filled..]) {
+            Ok(0) => return Err(EfiErrorKind::VolumeCorrupted.into()),
+            Ok(n) => filled += n,
+            Err(_) => return Err(EfiErrorKind::VolumeCorrupted.into()),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Decompresses a payload incrementally: output is produced as it's read, keeping only the
+/// dictionary window in memory rather than the whole output.
+/// Reads fail with `InvalidData` if the payload turns out to be corrupt.
+pub struct Decompressor<'a> {
+    bits: BitReader<'a>,
+    tables: Box<Tables>,
+    pbit: u32,
+    block_size: u16,
+    size: usize,
+    produced: usize,
+    window: Vec<u8>,
+    match_len: usize,
+    match_distance: usize,
+}
+
+impl<'a> Decompressor<'a> {
+    /// Fails with `InvalidParameter` if the header doesn't fit the data
+    pub fn new(data: &'a [u8], algorithm: Algorithm) -> Result<Self> {
+        let (compressed_size, size) = parse_header(data)?;
+        let mut bits = BitReader { src: &data[HEADER_SIZE..HEADER_SIZE + compressed_size], pos: 0, bit_buf: 0, sub_bit_buf: 0, bit_count: 0 };
+        bits.fill_buf(BITBUFSIZ);
+
+        Ok(Decompressor {
+            bits,
+            tables: Box::new(Tables::new()),
+            pbit: algorithm.pbit(),
+            block_size: 0,
+            size,
+            produced: 0,
+            window: vec![0u8; cmp::min(size, WINDOW_SIZE)],
+            match_len: 0,
+            match_distance: 0,
+        })
+    }
+
+    /// The size of the whole decompressed output
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn emit(&mut self, byte: u8) -> u8 {
+        let len = self.window.len();
+        self.window[self.produced % len] = byte;
+        self.produced += 1;
+        byte
+    }
+
+    fn decode_c(&mut self) -> Option<usize> {
+        if self.block_size == 0 {
+            self.block_size = self.bits.get_bits(16) as u16;
+            read_pt_len(&mut self.bits, &mut self.tables, NT, TBIT, Some(3))?;
+            read_c_len(&mut self.bits, &mut self.tables)?;
+            read_pt_len(&mut self.bits, &mut self.tables, MAXNP, self.pbit, None)?;
+        }
+
+        self.block_size = self.block_size.wrapping_sub(1);
+        let t = &self.tables;
+        let mut c = t.c_table[(self.bits.bit_buf >> (BITBUFSIZ - 12)) as usize] as usize;
+        if c >= NC {
+            let mut mask = 1u32 << (BITBUFSIZ - 1 - 12);
+            while c >= NC {
+                c = if self.bits.bit_buf & mask != 0 { t.right[c] } else { t.left[c] } as usize;
+                mask >>= 1;
+            }
+        }
+
+        self.bits.fill_buf(t.c_len[c] as u32);
+        Some(c)
+    }
+
+    fn decode_p(&mut self) -> usize {
+        let t = &self.tables;
+        let mut p = t.pt_table[(self.bits.bit_buf >> (BITBUFSIZ - 8)) as usize] as usize;
+        if p >= MAXNP {
+            let mut mask = 1u32 << (BITBUFSIZ - 1 - 8);
+            while p >= MAXNP {
+                p = if self.bits.bit_buf & mask != 0 { t.right[p] } else { t.left[p] } as usize;
+                mask >>= 1;
+            }
+        }
+
+        self.bits.fill_buf(t.pt_len[p] as u32);
+        if p > 1 {
+            (1 << (p - 1)) + self.bits.get_bits(p as u32 - 1) as usize
+        } else {
+            p
+        }
+    }
+}
+
+impl<'a> io::Read for Decompressor<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < buf.len() && self.produced < self.size {
+            if self.match_len == 0 {
+                let c = self.decode_c().ok_or_else(corrupted)?;
+                if c < 256 {
+                    buf[n] = self.emit(c as u8);
+                    n += 1;
+                    continue;
+                }
+
+                self.match_len = c - (256 - THRESHOLD);
+                self.match_distance = self.decode_p() + 1;
+                if self.match_distance > self.produced || self.match_distance > self.window.len() {
+                    return Err(corrupted());
+                }
+            }
+
+            let len = self.window.len();
+            let byte = self.window[(self.produced - self.match_distance) % len];
+            buf[n] = self.emit(byte);
+            n += 1;
+            self.match_len -= 1;
+        }
+
+        Ok(n)
+    }
+}
+
+fn corrupted() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "corrupt compressed data")
+}
+
+// Returns the compressed and original sizes
+fn parse_header(data: &[u8]) -> Result<(usize, usize)> {
+    if data.len() < HEADER_SIZE {
+        return Err(EfiErrorKind::InvalidParameter.into());
+    }
+
+    let compressed_size = LittleEndian::read_u32(&data[0..4]) as usize;
+    let size = LittleEndian::read_u32(&data[4..8]) as usize;
+    if data.len() - HEADER_SIZE < compressed_size {
+        return Err(EfiErrorKind::InvalidParameter.into());
+    }
+
+    Ok((compressed_size, size))
+}
+
+struct BitReader<'a> {
+    src: &'a [u8],
+    pos: usize,
+    bit_buf: u32,
+    sub_bit_buf: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    // Shifts n bits out of the top of bit_buf, refilling from the input. Past the end of the input zeros are read.
+    fn fill_buf(&mut self, n: u32) {
+        let mut n = n;
+        self.bit_buf = ((self.bit_buf as u64) << n) as u32;
+        while self.bit_count < n {
+            n -= self.bit_count;
+            self.bit_buf |= ((self.sub_bit_buf as u64) << n) as u32;
+            self.sub_bit_buf = match self.src.get(self.pos) {
+                Some(b) => { self.pos += 1; *b as u32 },
+                None => 0,
+            };
+            self.bit_count = 8;
+        }
+
+        self.bit_count -= n;
+        self.bit_buf |= self.sub_bit_buf >> self.bit_count;
+    }
+
+    fn get_bits(&mut self, n: u32) -> u32 {
+        let bits = self.bit_buf >> (BITBUFSIZ - n);
+        self.fill_buf(n);
+        bits
+    }
+}
+
+// Huffman tables. The trees of the three sets share the left and right node arrays since each
+// one's nodes are numbered from its own symbol count upwards.
+struct Tables {
+    left: [u16; TREE_SIZE],
+    right: [u16; TREE_SIZE],
+    c_len: [u8; NC],
+    pt_len: [u8; NPT],
+    c_table: [u16; 4096],
+    pt_table: [u16; 256],
+}
+
+impl Tables {
+    fn new() -> Self {
+        Tables { left: [0; TREE_SIZE], right: [0; TREE_SIZE], c_len: [0; NC], pt_len: [0; NPT], c_table: [0; 4096], pt_table: [0; 256] }
+    }
+}
+
+// Reads the code lengths of the extra set (special is Some(3)) or the position set (None)
+fn read_pt_len(bits: &mut BitReader, t: &mut Tables, nn: usize, nbit: u32, special: Option<usize>) -> Option<()> {
+    let number = bits.get_bits(nbit) as usize;
+    if number > nn {
+        return None;
+    }
+
+    if number == 0 {
+        let c = bits.get_bits(nbit) as u16;
+        for entry in t.pt_table.iter_mut() {
+            *entry = c;
+        }
+        for len in t.pt_len[..nn].iter_mut() {
+            *len = 0;
+        }
+        return Some(());
+    }
+
+    let mut index = 0;
+    while index < number && index < NPT {
+        let mut c = bits.bit_buf >> (BITBUFSIZ - 3);
+        if c == 7 {
+            let mut mask = 1u32 << (BITBUFSIZ - 1 - 3);
+            while mask & bits.bit_buf != 0 {
+                mask >>= 1;
+                c += 1;
+            }
+        }
+
+        bits.fill_buf(if c < 7 { 3 } else { c - 3 });
+        t.pt_len[index] = c as u8;
+        index += 1;
+
+        if Some(index) == special {
+            let zeros = bits.get_bits(2) as usize;
+            for _ in 0..zeros {
+                if index >= NPT {
+                    break;
+                }
+                t.pt_len[index] = 0;
+                index += 1;
+            }
+        }
+    }
+
+    while index < nn && index < NPT {
+        t.pt_len[index] = 0;
+        index += 1;
+    }
+
+    make_table(&mut t.left, &mut t.right, &t.pt_len[..nn], 8, &mut t.pt_table)
+}
+
+// Reads the code lengths of the char and length set, which are themselves coded with the extra set
+fn read_c_len(bits: &mut BitReader, t: &mut Tables) -> Option<()> {
+    let number = bits.get_bits(CBIT) as usize;
+    if number == 0 {
+        let c = bits.get_bits(CBIT) as u16;
+        for len in t.c_len.iter_mut() {
+            *len = 0;
+        }
+        for entry in t.c_table.iter_mut() {
+            *entry = c;
+        }
+        return Some(());
+    }
+
+    let mut index = 0;
+    while index < number && index < NC {
+        let mut c = t.pt_table[(bits.bit_buf >> (BITBUFSIZ - 8)) as usize] as usize;
+        if c >= NT {
+            let mut mask = 1u32 << (BITBUFSIZ - 1 - 8);
+            while c >= NT {
+                c = if mask & bits.bit_buf != 0 { t.right[c] } else { t.left[c] } as usize;
+                mask >>= 1;
+            }
+        }
+
+        bits.fill_buf(t.pt_len[c] as u32);
+        if c <= 2 {
+            let zeros = match c {
+                0 => 1,
+                1 => bits.get_bits(4) as usize + 3,
+                _ => bits.get_bits(CBIT) as usize + 20,
+            };
+            for _ in 0..zeros {
+                if index >= NC {
+                    break;
+                }
+                t.c_len[index] = 0;
+                index += 1;
+            }
+        } else {
+            t.c_len[index] = (c - 2) as u8;
+            index += 1;
+        }
+    }
+
+    for len in t.c_len[index..].iter_mut() {
+        *len = 0;
+    }
+
+    make_table(&mut t.left, &mut t.right, &t.c_len, 12, &mut t.c_table)
+}
+
+#[derive(Copy, Clone)]
+enum Slot {
+    Table(usize),
+    Left(usize),
+    Right(usize),
+}
+
+// Builds the lookup table for the codes of up to table_bits bits, with longer codes continuing
+// into the tree. Fails if the lengths don't describe a complete prefix code.
+fn make_table(left: &mut [u16; TREE_SIZE], right: &mut [u16; TREE_SIZE], bit_len: &[u8], table_bits: usize, table: &mut [u16]) -> Option<()> {
+    let mut count = [0u16; 17];
+    for len in bit_len {
+        if *len > 16 {
+            return None;
+        }
+        count[*len as usize] += 1;
+    }
+
+    let mut start = [0u16; 18];
+    for i in 1..17 {
+        start[i + 1] = (start[i] as u32 + ((count[i] as u32) << (16 - i))) as u16;
+    }
+
+    if start[17] != 0 {
+        return None;
+    }
+
+    let ju_bits = 16 - table_bits;
+    let mut weight = [0u16; 17];
+    for i in 1..table_bits + 1 {
+        start[i] >>= ju_bits;
+        weight[i] = 1 << (table_bits - i);
+    }
+    for i in table_bits + 1..17 {
+        weight[i] = 1 << (16 - i);
+    }
+
+    let first_unused = (start[table_bits + 1] >> ju_bits) as usize;
+    let table_len = 1 << table_bits;
+    if first_unused != 0 && first_unused < table_len {
+        for entry in table[first_unused..table_len].iter_mut() {
+            *entry = 0;
+        }
+    }
+
+    let mut avail = bit_len.len();
+    let mask = 1u16 << (15 - table_bits);
+    for (c, len) in bit_len.iter().enumerate() {
+        let len = *len as usize;
+        if len == 0 {
+            continue;
+        }
+
+        let next_code = start[len].wrapping_add(weight[len]);
+        if len <= table_bits {
+            if start[len] >= next_code || next_code as usize > table_len {
+                return None;
+            }
+            for entry in table[start[len] as usize..next_code as usize].iter_mut() {
+                *entry = c as u16;
+            }
+        } else {
+            let mut code = start[len];
+            let mut slot = Slot::Table((code >> ju_bits) as usize);
+            for _ in 0..len - table_bits {
+                if get(slot, table, left, right) == 0 && avail < TREE_SIZE {
+                    left[avail] = 0;
+                    right[avail] = 0;
+                    set(slot, avail as u16, table, left, right);
+                    avail += 1;
+                }
+
+                let node = get(slot, table, left, right) as usize;
+                if node < TREE_SIZE {
+                    slot = if code & mask != 0 { Slot::Right(node) } else { Slot::Left(node) };
+                }
+                code <<= 1;
+            }
+            set(slot, c as u16, table, left, right);
+        }
+
+        start[len] = next_code;
+    }
+
+    Some(())
+}
+
+fn get(slot: Slot, table: &[u16], left: &[u16], right: &[u16]) -> u16 {
+    match slot {
+        Slot::Table(i) => table[i],
+        Slot::Left(i) => left[i],
+        Slot::Right(i) => right[i],
+    }
+}
+
+fn set(slot: Slot, value: u16, table: &mut [u16], left: &mut [u16], right: &mut [u16]) {
+    match slot {
+        Slot::Table(i) => table[i] = value,
+        Slot::Left(i) => left[i] = value,
+        Slot::Right(i) => right[i] = value,
+    }
+}
+
+fn decompress_protocol() -> Option<*const EFI_DECOMPRESS_PROTOCOL> {
+    locate_protocol(&EFI_DECOMPRESS_PROTOCOL_GUID).ok()
+}
+
+fn decompress_with_protocol(protocol: *const EFI_DECOMPRESS_PROTOCOL, data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() > u32::max_value() as usize {
+        return Err(EfiErrorKind::InvalidParameter.into());
+    }
+
+    let (mut size, mut scratch_size) = (0u32, 0u32);
+    unsafe {
+        ret_on_err!(((*protocol).GetInfo)(protocol, data.as_ptr() as *const VOID, data.len() as u32, &mut size, &mut scratch_size));
+    }
+
+    let mut out = vec![0u8; size as usize];
+    let mut scratch = vec![0u8; scratch_size as usize];
+    let status = unsafe {
+        ((*protocol).Decompress)(protocol, data.as_ptr() as *const VOID, data.len() as u32, out.as_mut_ptr() as *mut VOID, size, scratch.as_mut_ptr() as *mut VOID, scratch_size)
+    };
+
+    if ::ffi::IsSuccess(status) { Ok(out) } else { Err(EfiError::from(status)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_header(size: u32, compressed: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_SIZE];
+        LittleEndian::write_u32(&mut data[0..4], compressed.len() as u32);
+        LittleEndian::write_u32(&mut data[4..8], size);
+        data.extend_from_slice(compressed);
+        data
+    }
+
+    fn decompress_all(data: &[u8], algorithm: Algorithm) -> io::Result<Vec<u8>> {
+        let mut decompressor = Decompressor::new(data, algorithm).unwrap();
+        let mut out = Vec::new();
+        let mut buf = [0u8; 3]; // Small reads to exercise resuming in the middle of matches
+        loop {
+            match decompressor.read(&mut buf)? {
+                0 => return Ok(out),
+                n => out.extend_from_slice(&buf[..n]),
+            }
+        }
+    }
+
+    // One block whose char/length codes are real Huffman codes ('A' = 0, 'B' = 1), themselves
+    // coded through a two symbol extra set: a zero run and length 1
+    #[test]
+    fn huffman_coded_literals_are_decoded() {
+        let efi = with_header(5, &[0x00, 0x05, 0x20, 0x04, 0x24, 0x30, 0xB7, 0x00, 0x68]);
+        assert_eq!(decompress_all(&efi, Algorithm::Efi).unwrap(), b"ABBAB");
+
+        let tiano = with_header(5, &[0x00, 0x05, 0x20, 0x04, 0x24, 0x30, 0xB7, 0x00, 0x1A]);
+        assert_eq!(decompress_all(&tiano, Algorithm::Tiano).unwrap(), b"ABBAB");
+    }
+
+    // Two blocks with single symbol tables: a literal 'A' and then a match of length 3 at distance 1
+    #[test]
+    fn matches_copy_from_earlier_output() {
+        let data = with_header(4, &[0x00, 0x01, 0x00, 0x00, 0x04, 0x10, 0x00, 0x00, 0x10, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(decompress_all(&data, Algorithm::Efi).unwrap(), b"AAAA");
+
+        // Truncating the output stops in the middle of the match
+        let data = with_header(2, &[0x00, 0x01, 0x00, 0x00, 0x04, 0x10, 0x00, 0x00, 0x10, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(decompress_all(&data, Algorithm::Efi).unwrap(), b"AA");
+    }
+
+    #[test]
+    fn corrupt_data_is_rejected() {
+        // A match before any output
+        let data = with_header(4, &[0x00, 0x01, 0x00, 0x00, 0x10, 0x00, 0x00]);
+        assert_eq!(decompress_all(&data, Algorithm::Efi).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // Compressed size beyond the end of the data
+        let mut data = with_header(4, &[0x00]);
+        data[0] = 2;
+        assert!(Decompressor::new(&data, Algorithm::Efi).is_err());
+        assert_eq!(decompressed_size(&data).unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+
+        assert_eq!(decompressed_size(&with_header(1234, &[])).unwrap(), 1234);
+    }
+}
@@ -1,23 +1,43 @@
 use ffi::{
-    IsSuccess,
     CHAR16,
     FALSE,
     device_path::{
         MEDIA_FILEPATH_DP,
         MEDIA_DEVICE_PATH,
+        END_DEVICE_PATH_TYPE,
+        END_ENTIRE_DEVICE_PATH_SUBTYPE,
+        END_INSTANCE_DEVICE_PATH_SUBTYPE,
+        HARDWARE_DEVICE_PATH,
+        HW_PCI_DP,
+        MESSAGING_DEVICE_PATH,
+        MSG_USB_DP,
+        MSG_MAC_ADDR_DP,
+        MSG_IPv4_DP as MSG_IPV4_DP,
+        MSG_URI_DP,
+        MSG_SATA_DP,
+        MSG_NVME_NAMESPACE_DP,
+        ACPI_DEVICE_PATH,
+        ACPI_DP,
+        MEDIA_HARDDRIVE_DP,
         EFI_DEVICE_PATH_PROTOCOL,
+        EFI_DEVICE_PATH_PROTOCOL_GUID,
         EFI_DEVICE_PATH_UTILITIES_PROTOCOL,
         EFI_DEVICE_PATH_UTILITIES_PROTOCOL_GUID,
         EFI_DEVICE_PATH_TO_TEXT_PROTOCOL,
         EFI_DEVICE_PATH_TO_TEXT_PROTOCOL_GUID,
+        EFI_DEVICE_PATH_FROM_TEXT_PROTOCOL,
+        EFI_DEVICE_PATH_FROM_TEXT_PROTOCOL_GUID,
     },
+    EFI_HANDLE,
     UINT16,
 };
 
-use {EfiError, EfiErrorKind, Result, utils::as_slice};
-use core::{mem, ptr, fmt, slice};
-use system_table;
+use {EfiError, EfiErrorKind, Guid, Result, utils::{as_slice, to_null_terminated_utf16, guid_from_bytes, guid_to_bytes}};
+use net::Ipv4Addr;
+use utils::{locate_protocol, open_protocol};
+use core::{cmp, ptr, fmt, slice, u16, u32, u64, u8};
 use alloc::{String, boxed::Box, Vec};
+use byteorder::{ByteOrder, LittleEndian, BigEndian};
 
 // TODO: the whole concept of wrapping device path pointers like
 // this is not safe. We need to analyze memory lifetimes etc.
@@ -75,11 +95,27 @@ impl Clone for DeviceNode {
     }
 }
 
+impl DeviceNode {
+    /// The raw bytes of this node including its header
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            let len = LittleEndian::read_u16(&(*self.inner).Length) as usize;
+            slice::from_raw_parts(self.inner as *const u8, len)
+        }
+    }
+
+    /// The node decoded into its fields
+    pub fn node(&self) -> Node {
+        Node::parse(self.as_bytes())
+    }
+}
+
 impl fmt::Display for DeviceNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let display = to_string(self.inner, false).map_err(|_| fmt::Error)?; // TODO: don't swallow lower level I/O
-        write!(f, "{}", display)?;
-        Ok(())
+        match to_string(self.inner, true) {
+            Ok(display) => write!(f, "{}", display),
+            Err(_) => write!(f, "{}", self.node()), // Not all firmware has the to text protocol
+        }
     }
 }
 
@@ -99,10 +135,60 @@ impl DevicePath {
         Ok(Self { inner: ptr, path_utils: path_utils()? })
     }
 
+    /// Creates a device path by copying the given raw bytes.
+    /// The bytes must contain a complete device path including the end node.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if !is_well_formed(bytes) {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let path_utils = path_utils()?;
+        let path = unsafe {
+            ((*path_utils).DuplicateDevicePath)(bytes.as_ptr() as *const EFI_DEVICE_PATH_PROTOCOL)
+        };
+
+        if path.is_null() {
+            return Err(EfiErrorKind::OutOfResources.into());
+        }
+
+        Ok(Self { inner: path, path_utils })
+    }
+
+    /// Parses the text representation of a device path such as `PciRoot(0x0)/Pci(0x1F,0x2)/Sata(0x0,0xFFFF,0x0)/HD(1,GPT,...)`.
+    /// Firmware without the from text protocol falls back to a parser that understands the common node types
+    /// (see `DevicePathBuilder::from_text()`).
+    pub fn from_text(text: &str) -> Result<Self> {
+        let protocol: *const EFI_DEVICE_PATH_FROM_TEXT_PROTOCOL = match locate_protocol(&EFI_DEVICE_PATH_FROM_TEXT_PROTOCOL_GUID) {
+            Ok(protocol) => protocol,
+            Err(_) => return DevicePathBuilder::from_text(text)?.build(),
+        };
+
+        let text = to_null_terminated_utf16(text);
+        let path = unsafe { ((*protocol).ConvertTextToDevicePath)(text.as_ptr()) };
+        if path.is_null() {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        Self::from_ptr(path)
+    }
+
     pub fn as_ptr(&self) -> *const EFI_DEVICE_PATH_PROTOCOL {
         self.inner
     }
 
+    /// The raw bytes of this device path including the end node
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            let size = ((*self.path_utils).GetDevicePathSize)(self.inner);
+            slice::from_raw_parts(self.inner as *const u8, size)
+        }
+    }
+
+    /// Iterates over the nodes of this device path. The last node is always `Node::End`.
+    pub fn nodes(&self) -> Nodes {
+        Nodes::new(self.as_bytes())
+    }
+
     pub fn try_clone(&self) -> Result<Self> {
         let path = unsafe {
             ((*self.path_utils).DuplicateDevicePath)(self.inner)
@@ -124,30 +210,664 @@ impl Clone for DevicePath {
 
 impl fmt::Display for DevicePath {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let display = to_string(self.inner, false).map_err(|_| fmt::Error)?; // TODO: don't swallow lower level I/O
-        write!(f, "{}", display)?;
-        Ok(())
+        match to_string(self.inner, false) {
+            Ok(display) => write!(f, "{}", display),
+            Err(_) => fmt_nodes(f, self.nodes()), // Not all firmware has the to text protocol
+        }
     }
 }
 
-fn to_string(path: *const EFI_DEVICE_PATH_PROTOCOL, is_single_node: bool) -> Result<String> {
-    let bs = (*system_table()).BootServices;
+/// The signature identifying a partition in a hard drive node
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PartitionSignature {
+    None,
+    /// The 32 bit disk signature from an MBR
+    Mbr(u32),
+    /// The unique partition GUID from a GPT
+    Guid(Guid),
+    Unknown(u8),
+}
 
-    let protocol: *mut EFI_DEVICE_PATH_TO_TEXT_PROTOCOL   = ptr::null_mut();
-    unsafe {
-        // TODO: Are we supposed to call CloseProtocol on a protocol pointer obtained via LocateProtocol?
-        // UEFI documentation seems to suggest it's not required but doesn't the firmeware need to know we're
-        // no longer using the pointer and hence if needed it can clean it up? Check this.
-        let status = ((*bs).LocateProtocol)(&EFI_DEVICE_PATH_TO_TEXT_PROTOCOL_GUID, ptr::null(), mem::transmute(&protocol));
+/// A single device path node decoded into its fields.
+/// Node types without a variant here, or nodes too short for their type, come out as `Other`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node<'a> {
+    /// An ACPI device. `hid` is usually a compressed EISA ID, see `eisa_id()`.
+    Acpi { hid: u32, uid: u32 },
+    Pci { device: u8, function: u8 },
+    Usb { parent_port: u8, interface: u8 },
+    Sata { hba_port: u16, port_multiplier_port: u16, lun: u16 },
+    Nvme { namespace_id: u32, eui: u64 },
+    MacAddr { address: &'a [u8], if_type: u8 },
+    Ipv4 {
+        local: Ipv4Addr,
+        remote: Ipv4Addr,
+        local_port: u16,
+        remote_port: u16,
+        protocol: u16,
+        static_address: bool,
+        gateway: Ipv4Addr, // Unspecified in nodes from before UEFI 2.3.1 which lack it
+        subnet_mask: Ipv4Addr,
+    },
+    HardDrive { partition_number: u32, start: u64, size: u64, signature: PartitionSignature },
+    FilePath(String),
+    Uri(String),
+    /// Separates the instances of a multi-instance path
+    EndInstance,
+    End,
+    Other { node_type: u8, sub_type: u8, data: &'a [u8] },
+}
+
+// Device path nodes start with a 4 byte header: type, sub-type and a 16 bit length that includes the header
+const NODE_HEADER_SIZE: usize = 4;
 
-        if !IsSuccess(status) {
-            return Err(EfiErrorKind::DeviceError.into());
+impl<'a> Node<'a> {
+    // Decodes a node. The bytes must be exactly one node including its header.
+    fn parse(bytes: &'a [u8]) -> Node<'a> {
+        let (node_type, sub_type, data) = (bytes[0], bytes[1], &bytes[NODE_HEADER_SIZE..]);
+        let ipv4 = |offset: usize| Ipv4Addr::new(data[offset], data[offset + 1], data[offset + 2], data[offset + 3]);
+        match (node_type, sub_type) {
+            (ACPI_DEVICE_PATH, ACPI_DP) if data.len() >= 8 => Node::Acpi { hid: LittleEndian::read_u32(&data[0..4]), uid: LittleEndian::read_u32(&data[4..8]) },
+            (HARDWARE_DEVICE_PATH, HW_PCI_DP) if data.len() >= 2 => Node::Pci { function: data[0], device: data[1] },
+            (MESSAGING_DEVICE_PATH, MSG_USB_DP) if data.len() >= 2 => Node::Usb { parent_port: data[0], interface: data[1] },
+            (MESSAGING_DEVICE_PATH, MSG_SATA_DP) if data.len() >= 6 => Node::Sata {
+                hba_port: LittleEndian::read_u16(&data[0..2]),
+                port_multiplier_port: LittleEndian::read_u16(&data[2..4]),
+                lun: LittleEndian::read_u16(&data[4..6]),
+            },
+            (MESSAGING_DEVICE_PATH, MSG_NVME_NAMESPACE_DP) if data.len() >= 12 => Node::Nvme {
+                namespace_id: LittleEndian::read_u32(&data[0..4]),
+                eui: LittleEndian::read_u64(&data[4..12]),
+            },
+            (MESSAGING_DEVICE_PATH, MSG_MAC_ADDR_DP) if data.len() >= 33 => {
+                // Only Ethernet and 802.5 addresses have a known size. Others use the whole buffer.
+                let len = if data[32] == 0 || data[32] == 1 { 6 } else { 32 };
+                Node::MacAddr { address: &data[..len], if_type: data[32] }
+            },
+            (MESSAGING_DEVICE_PATH, MSG_IPV4_DP) if data.len() >= 15 => {
+                let has_gateway = data.len() >= 23;
+                Node::Ipv4 {
+                    local: ipv4(0),
+                    remote: ipv4(4),
+                    local_port: LittleEndian::read_u16(&data[8..10]),
+                    remote_port: LittleEndian::read_u16(&data[10..12]),
+                    protocol: LittleEndian::read_u16(&data[12..14]),
+                    static_address: data[14] != 0,
+                    gateway: if has_gateway { ipv4(15) } else { Ipv4Addr::unspecified() },
+                    subnet_mask: if has_gateway { ipv4(19) } else { Ipv4Addr::unspecified() },
+                }
+            },
+            (MEDIA_DEVICE_PATH, MEDIA_HARDDRIVE_DP) if data.len() >= 38 => {
+                let signature = match data[37] {
+                    0 => PartitionSignature::None,
+                    1 => PartitionSignature::Mbr(LittleEndian::read_u32(&data[20..24])),
+                    2 => PartitionSignature::Guid(guid_from_bytes(&data[20..36])),
+                    other => PartitionSignature::Unknown(other),
+                };
+                Node::HardDrive {
+                    partition_number: LittleEndian::read_u32(&data[0..4]),
+                    start: LittleEndian::read_u64(&data[4..12]),
+                    size: LittleEndian::read_u64(&data[12..20]),
+                    signature,
+                }
+            },
+            (MEDIA_DEVICE_PATH, MEDIA_FILEPATH_DP) => {
+                let chars = data.chunks(2)
+                    .filter(|c| c.len() == 2)
+                    .map(|c| LittleEndian::read_u16(c))
+                    .take_while(|c| *c != 0)
+                    .collect::<Vec<u16>>();
+                Node::FilePath(String::from_utf16_lossy(&chars))
+            },
+            (MESSAGING_DEVICE_PATH, MSG_URI_DP) => Node::Uri(String::from_utf8_lossy(data).into_owned()),
+            (END_DEVICE_PATH_TYPE, END_INSTANCE_DEVICE_PATH_SUBTYPE) => Node::EndInstance,
+            (END_DEVICE_PATH_TYPE, _) => Node::End,
+            _ => Node::Other { node_type, sub_type, data },
         }
+    }
 
-        if protocol.is_null() { // If above call returned null protocol that means no such protocol is associated with the handle (which is odd)
-            return Err(EfiErrorKind::DeviceError.into()); // TODO: Need proper error here
+    // Encodes the node including its header. Returns None if it's too long for the 16 bit length.
+    fn encode(&self) -> Option<Vec<u8>> {
+        let ipv4 = |data: &mut Vec<u8>, addr: Ipv4Addr| data.extend_from_slice(&addr.octets());
+        let mut data = Vec::new();
+        let (node_type, sub_type) = match *self {
+            Node::Acpi { hid, uid } => {
+                data.resize(8, 0);
+                LittleEndian::write_u32(&mut data[0..4], hid);
+                LittleEndian::write_u32(&mut data[4..8], uid);
+                (ACPI_DEVICE_PATH, ACPI_DP)
+            },
+            Node::Pci { device, function } => {
+                data.extend_from_slice(&[function, device]);
+                (HARDWARE_DEVICE_PATH, HW_PCI_DP)
+            },
+            Node::Usb { parent_port, interface } => {
+                data.extend_from_slice(&[parent_port, interface]);
+                (MESSAGING_DEVICE_PATH, MSG_USB_DP)
+            },
+            Node::Sata { hba_port, port_multiplier_port, lun } => {
+                data.resize(6, 0);
+                LittleEndian::write_u16(&mut data[0..2], hba_port);
+                LittleEndian::write_u16(&mut data[2..4], port_multiplier_port);
+                LittleEndian::write_u16(&mut data[4..6], lun);
+                (MESSAGING_DEVICE_PATH, MSG_SATA_DP)
+            },
+            Node::Nvme { namespace_id, eui } => {
+                data.resize(12, 0);
+                LittleEndian::write_u32(&mut data[0..4], namespace_id);
+                LittleEndian::write_u64(&mut data[4..12], eui);
+                (MESSAGING_DEVICE_PATH, MSG_NVME_NAMESPACE_DP)
+            },
+            Node::MacAddr { address, if_type } => {
+                data.extend_from_slice(&address[..cmp::min(address.len(), 32)]);
+                data.resize(32, 0);
+                data.push(if_type);
+                (MESSAGING_DEVICE_PATH, MSG_MAC_ADDR_DP)
+            },
+            Node::Ipv4 { local, remote, local_port, remote_port, protocol, static_address, gateway, subnet_mask } => {
+                ipv4(&mut data, local);
+                ipv4(&mut data, remote);
+                let mut fields = [0_u8; 6];
+                LittleEndian::write_u16(&mut fields[0..2], local_port);
+                LittleEndian::write_u16(&mut fields[2..4], remote_port);
+                LittleEndian::write_u16(&mut fields[4..6], protocol);
+                data.extend_from_slice(&fields);
+                data.push(static_address as u8);
+                ipv4(&mut data, gateway);
+                ipv4(&mut data, subnet_mask);
+                (MESSAGING_DEVICE_PATH, MSG_IPV4_DP)
+            },
+            Node::HardDrive { partition_number, start, size, signature } => {
+                data.resize(38, 0);
+                LittleEndian::write_u32(&mut data[0..4], partition_number);
+                LittleEndian::write_u64(&mut data[4..12], start);
+                LittleEndian::write_u64(&mut data[12..20], size);
+                let (mbr_type, signature_type) = match signature {
+                    PartitionSignature::None => (MBR_TYPE_PCAT, 0),
+                    PartitionSignature::Mbr(signature) => {
+                        LittleEndian::write_u32(&mut data[20..24], signature);
+                        (MBR_TYPE_PCAT, 1)
+                    },
+                    PartitionSignature::Guid(guid) => {
+                        data[20..36].copy_from_slice(&guid_to_bytes(&guid));
+                        (MBR_TYPE_GPT, 2)
+                    },
+                    PartitionSignature::Unknown(signature_type) => (MBR_TYPE_PCAT, signature_type),
+                };
+                data[36] = mbr_type;
+                data[37] = signature_type;
+                (MEDIA_DEVICE_PATH, MEDIA_HARDDRIVE_DP)
+            },
+            Node::FilePath(ref path) => {
+                for c in to_null_terminated_utf16(path) {
+                    data.push(c as u8);
+                    data.push((c >> 8) as u8);
+                }
+                (MEDIA_DEVICE_PATH, MEDIA_FILEPATH_DP)
+            },
+            Node::Uri(ref uri) => {
+                data.extend_from_slice(uri.as_bytes());
+                (MESSAGING_DEVICE_PATH, MSG_URI_DP)
+            },
+            Node::EndInstance => (END_DEVICE_PATH_TYPE, END_INSTANCE_DEVICE_PATH_SUBTYPE),
+            Node::End => (END_DEVICE_PATH_TYPE, END_ENTIRE_DEVICE_PATH_SUBTYPE),
+            Node::Other { node_type, sub_type, data: other } => {
+                data.extend_from_slice(other);
+                (node_type, sub_type)
+            },
+        };
+
+        let len = NODE_HEADER_SIZE + data.len();
+        if len > u16::MAX as usize {
+            return None;
         }
+
+        let mut bytes = vec![node_type, sub_type, len as u8, (len >> 8) as u8];
+        bytes.extend(data);
+        Some(bytes)
     }
+}
+
+// The vendor half of compressed EISA IDs for PNP devices
+const EISA_PNP_VENDOR: u32 = 0x41D0;
+
+/// Compresses a PNP ID such as "PNP0A03" into the 32 bit form used for ACPI node HIDs.
+/// Only the "PNP" vendor prefix is supported.
+pub fn eisa_id(pnp_id: &str) -> u32 {
+    let product = if pnp_id.len() == 7 && pnp_id.starts_with("PNP") { u32::from_str_radix(&pnp_id[3..], 16).ok() } else { None };
+    product.map_or(0, |product| product << 16 | EISA_PNP_VENDOR)
+}
+
+// Values of a hard drive node's partition format field
+const MBR_TYPE_PCAT: u8 = 1;
+const MBR_TYPE_GPT: u8 = 2;
+
+// Formats nodes the way the UEFI spec's text representation does
+impl<'a> fmt::Display for Node<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Node::Acpi { hid, uid } if hid == eisa_id("PNP0A03") => write!(f, "PciRoot(0x{:X})", uid),
+            Node::Acpi { hid, uid } if hid == eisa_id("PNP0A08") => write!(f, "PcieRoot(0x{:X})", uid),
+            Node::Acpi { hid, uid } if hid & 0xFFFF == EISA_PNP_VENDOR => write!(f, "Acpi(PNP{:04X},0x{:X})", hid >> 16, uid),
+            Node::Acpi { hid, uid } => write!(f, "Acpi(0x{:08X},0x{:X})", hid, uid),
+            Node::Pci { device, function } => write!(f, "Pci(0x{:X},0x{:X})", device, function),
+            Node::Usb { parent_port, interface } => write!(f, "USB(0x{:X},0x{:X})", parent_port, interface),
+            Node::Sata { hba_port, port_multiplier_port, lun } => write!(f, "Sata(0x{:X},0x{:X},0x{:X})", hba_port, port_multiplier_port, lun),
+            Node::Nvme { namespace_id, eui } => {
+                // The EUI-64 is shown most significant byte first
+                let mut b = [0_u8; 8];
+                BigEndian::write_u64(&mut b, eui);
+                write!(f, "NVMe(0x{:X},{:02X}-{:02X}-{:02X}-{:02X}-{:02X}-{:02X}-{:02X}-{:02X})", namespace_id, b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7])
+            },
+            Node::MacAddr { address, if_type } => {
+                write!(f, "MAC(")?;
+                for byte in address {
+                    write!(f, "{:02X}", byte)?;
+                }
+                write!(f, ",0x{:X})", if_type)
+            },
+            Node::Ipv4 { local, remote, protocol, static_address, gateway, subnet_mask, .. } => {
+                write!(f, "IPv4({},", remote)?;
+                match protocol {
+                    6 => write!(f, "TCP")?,
+                    17 => write!(f, "UDP")?,
+                    other => write!(f, "0x{:X}", other)?,
+                }
+                write!(f, ",{},{},{},{})", if static_address { "Static" } else { "DHCP" }, local, gateway, subnet_mask)
+            },
+            Node::HardDrive { partition_number, start, size, signature } => {
+                match signature {
+                    PartitionSignature::None => write!(f, "HD({},0,0,", partition_number)?,
+                    PartitionSignature::Mbr(signature) => write!(f, "HD({},MBR,0x{:08X},", partition_number, signature)?,
+                    PartitionSignature::Guid(guid) => write!(f, "HD({},GPT,{},", partition_number, guid)?,
+                    PartitionSignature::Unknown(signature_type) => write!(f, "HD({},{},0,", partition_number, signature_type)?,
+                }
+                write!(f, "0x{:X},0x{:X})", start, size)
+            },
+            Node::FilePath(ref path) => write!(f, "{}", path),
+            Node::Uri(ref uri) => write!(f, "Uri({})", uri),
+            Node::EndInstance => write!(f, ","),
+            Node::End => Ok(()),
+            Node::Other { node_type, sub_type, data } => {
+                write!(f, "Path({},{},", node_type, sub_type)?;
+                for byte in data {
+                    write!(f, "{:02X}", byte)?;
+                }
+                write!(f, ")")
+            },
+        }
+    }
+}
+
+// Joins nodes with '/' except around the ',' that separates instances
+fn fmt_nodes(f: &mut fmt::Formatter, nodes: Nodes) -> fmt::Result {
+    let mut needs_separator = false;
+    for node in nodes {
+        match node {
+            Node::End => break,
+            Node::EndInstance => needs_separator = false,
+            _ => {
+                if needs_separator {
+                    write!(f, "/")?;
+                }
+                needs_separator = true;
+            },
+        }
+        write!(f, "{}", node)?;
+    }
+
+    Ok(())
+}
+
+/// Builds a device path node by node, e.g. the short-form path of a boot loader for a boot option:
+///
+/// ```ignore
+/// let path = DevicePathBuilder::new()
+///     .hard_drive(1, 0x800, 0x100000, PartitionSignature::Guid(partition_guid))
+///     .file("\\EFI\\BOOT\\BOOTX64.EFI")
+///     .build()?;
+/// ```
+///
+/// The end node is added by `build()`.
+#[derive(Debug, Clone, Default)]
+pub struct DevicePathBuilder {
+    bytes: Vec<u8>,
+    too_long: bool,
+}
+
+impl DevicePathBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts with the nodes of an existing path, e.g. the path of a volume to which a file is then appended
+    pub fn from_path(path: &DevicePath) -> Self {
+        let bytes = path.as_bytes();
+        Self { bytes: bytes[..bytes.len() - NODE_HEADER_SIZE].to_vec(), too_long: false } // Without the end node
+    }
+
+    /// Parses the text representation of a device path without relying on the firmware.
+    /// `Acpi`, `PciRoot`, `PcieRoot`, `Pci`, `USB`, `Sata`, `NVMe`, `MAC`, `IPv4`, `Uri`, `HD` and `Path` nodes are
+    /// understood and anything else not of the form `Name(...)` is taken to be a file path node.
+    pub fn from_text(text: &str) -> Result<Self> {
+        let mut builder = Self::new();
+        for (segment, separator) in split_top_level(text) {
+            if !segment.is_empty() {
+                builder = builder.text_node(segment)?;
+            }
+            if separator == Some(',') {
+                builder = builder.end_instance();
+            }
+        }
+
+        Ok(builder)
+    }
+
+    fn text_node(self, text: &str) -> Result<Self> {
+        let open = match text.find('(') {
+            Some(open) if text.ends_with(')') => open,
+            _ => return Ok(self.file(text)),
+        };
+
+        let (name, args) = (&text[..open], &text[open + 1..text.len() - 1]);
+        if !is_node_name(name) {
+            return Ok(self.file(text));
+        }
+
+        let arg_list = args.split(',').map(|a| a.trim()).collect::<Vec<_>>();
+        let arg = |index: usize| arg_list.get(index).map_or("", |a| *a); // Missing trailing arguments default to 0
+        let invalid = || EfiError::from(EfiErrorKind::InvalidParameter);
+
+        let builder = match name {
+            "PciRoot" => self.node(&Node::Acpi { hid: eisa_id("PNP0A03"), uid: parse_int_max(arg(0), u32::MAX as u64)? as u32 }),
+            "PcieRoot" => self.node(&Node::Acpi { hid: eisa_id("PNP0A08"), uid: parse_int_max(arg(0), u32::MAX as u64)? as u32 }),
+            "Acpi" => {
+                let hid = if arg(0).starts_with("PNP") { eisa_id(arg(0)) } else { parse_int_max(arg(0), u32::MAX as u64)? as u32 };
+                if hid == 0 {
+                    return Err(invalid());
+                }
+                self.node(&Node::Acpi { hid, uid: parse_int_max(arg(1), u32::MAX as u64)? as u32 })
+            },
+            "Pci" => self.pci(parse_int_max(arg(0), u8::MAX as u64)? as u8, parse_int_max(arg(1), u8::MAX as u64)? as u8),
+            "USB" => self.usb(parse_int_max(arg(0), u8::MAX as u64)? as u8, parse_int_max(arg(1), u8::MAX as u64)? as u8),
+            "Sata" => self.node(&Node::Sata {
+                hba_port: parse_int_max(arg(0), u16::MAX as u64)? as u16,
+                port_multiplier_port: parse_int_max(arg(1), u16::MAX as u64)? as u16,
+                lun: parse_int_max(arg(2), u16::MAX as u64)? as u16,
+            }),
+            "NVMe" => {
+                let eui = parse_hex_bytes(&arg(1).replace("-", ""))?;
+                if eui.len() > 8 {
+                    return Err(invalid());
+                }
+                let eui = eui.iter().fold(0_u64, |eui, b| eui << 8 | *b as u64);
+                self.node(&Node::Nvme { namespace_id: parse_int_max(arg(0), u32::MAX as u64)? as u32, eui })
+            },
+            "MAC" => self.mac(&parse_hex_bytes(arg(0))?, parse_int_max(arg(1), u8::MAX as u64)? as u8),
+            "IPv4" => {
+                let (remote, remote_port) = parse_ipv4_and_port(arg(0))?;
+                let protocol = match arg(1) {
+                    "TCP" => 6,
+                    "UDP" => 17,
+                    other => parse_int_max(other, u16::MAX as u64)? as u16,
+                };
+                let static_address = match arg(2) {
+                    "Static" => true,
+                    "DHCP" | "" => false,
+                    _ => return Err(invalid()),
+                };
+                let (local, local_port) = parse_ipv4_and_port(arg(3))?;
+                self.node(&Node::Ipv4 {
+                    local,
+                    remote,
+                    local_port,
+                    remote_port,
+                    protocol,
+                    static_address,
+                    gateway: parse_ipv4_and_port(arg(4))?.0,
+                    subnet_mask: parse_ipv4_and_port(arg(5))?.0,
+                })
+            },
+            "Uri" => self.uri(args), // URIs can contain commas
+            "HD" => {
+                let signature = match arg(1) {
+                    "MBR" | "1" => PartitionSignature::Mbr(parse_int_max(arg(2), u32::MAX as u64)? as u32),
+                    "GPT" | "2" => PartitionSignature::Guid(arg(2).parse::<Guid>().map_err(|_| invalid())?),
+                    "0" | "" => PartitionSignature::None,
+                    other => PartitionSignature::Unknown(parse_int_max(other, u8::MAX as u64)? as u8),
+                };
+                self.hard_drive(parse_int_max(arg(0), u32::MAX as u64)? as u32, parse_int(arg(3))?, parse_int(arg(4))?, signature)
+            },
+            "Path" => {
+                let data = parse_hex_bytes(arg(2))?;
+                self.node(&Node::Other { node_type: parse_int_max(arg(0), u8::MAX as u64)? as u8, sub_type: parse_int_max(arg(1), u8::MAX as u64)? as u8, data: &data })
+            },
+            _ => return Err(EfiErrorKind::Unsupported.into()),
+        };
+
+        Ok(builder)
+    }
+
+    /// Appends any node
+    pub fn node(mut self, node: &Node) -> Self {
+        match node.encode() {
+            Some(bytes) => self.bytes.extend(bytes),
+            None => self.too_long = true,
+        }
+        self
+    }
+
+    pub fn pci(self, device: u8, function: u8) -> Self {
+        self.node(&Node::Pci { device, function })
+    }
+
+    pub fn usb(self, parent_port: u8, interface: u8) -> Self {
+        self.node(&Node::Usb { parent_port, interface })
+    }
+
+    /// Appends a MAC address node. `if_type` is the interface type from RFC 3232, 1 for Ethernet.
+    pub fn mac(self, address: &[u8], if_type: u8) -> Self {
+        self.node(&Node::MacAddr { address, if_type })
+    }
+
+    /// Appends an IPv4 node without ports or gateway, as used for network boot
+    pub fn ipv4(self, local: Ipv4Addr, remote: Ipv4Addr, static_address: bool) -> Self {
+        self.node(&Node::Ipv4 {
+            local,
+            remote,
+            local_port: 0,
+            remote_port: 0,
+            protocol: 0,
+            static_address,
+            gateway: Ipv4Addr::unspecified(),
+            subnet_mask: Ipv4Addr::unspecified(),
+        })
+    }
+
+    pub fn uri(self, uri: &str) -> Self {
+        self.node(&Node::Uri(uri.into()))
+    }
+
+    pub fn hard_drive(self, partition_number: u32, start: u64, size: u64, signature: PartitionSignature) -> Self {
+        self.node(&Node::HardDrive { partition_number, start, size, signature })
+    }
+
+    /// Appends a file path node. Paths use backslashes and are relative to the root of the volume.
+    pub fn file(self, path: &str) -> Self {
+        self.node(&Node::FilePath(path.into()))
+    }
+
+    /// Ends the current instance and starts a new one in a multi-instance path
+    pub fn end_instance(self) -> Self {
+        self.node(&Node::EndInstance)
+    }
+
+    /// The raw bytes of the path including the end node
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.too_long {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let mut bytes = self.bytes.clone();
+        bytes.extend_from_slice(&[END_DEVICE_PATH_TYPE, END_ENTIRE_DEVICE_PATH_SUBTYPE, NODE_HEADER_SIZE as u8, 0]);
+        Ok(bytes)
+    }
+
+    pub fn build(&self) -> Result<DevicePath> {
+        DevicePath::from_bytes(&self.to_bytes()?)
+    }
+}
+
+// Splits device path text at the '/' and ',' separators that aren't inside a node's parentheses.
+// Each segment comes with the separator that ended it, None for the last one.
+fn split_top_level(text: &str) -> Vec<(&str, Option<char>)> {
+    let mut segments = Vec::new();
+    let mut depth = 0_usize;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            '/' | ',' if depth == 0 => {
+                segments.push((&text[start..i], Some(c)));
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+
+    segments.push((&text[start..], None));
+    segments
+}
+
+fn is_node_name(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+// Numbers are hex with a 0x prefix and decimal otherwise, like the firmware's parser. Empty means 0.
+fn parse_int(text: &str) -> Result<u64> {
+    let parsed = if text.is_empty() {
+        Ok(0)
+    } else if text.starts_with("0x") || text.starts_with("0X") {
+        u64::from_str_radix(&text[2..], 16)
+    } else {
+        u64::from_str_radix(text, 10)
+    };
+
+    parsed.map_err(|_| EfiErrorKind::InvalidParameter.into())
+}
+
+// Fails rather than truncating when the value doesn't fit in the field it's for
+fn parse_int_max(text: &str, max: u64) -> Result<u64> {
+    match parse_int(text)? {
+        value if value <= max => Ok(value),
+        _ => Err(EfiErrorKind::InvalidParameter.into()),
+    }
+}
+
+fn parse_hex_bytes(text: &str) -> Result<Vec<u8>> {
+    if text.len() % 2 != 0 || !text.is_ascii() {
+        return Err(EfiErrorKind::InvalidParameter.into());
+    }
+
+    (0..text.len() / 2)
+        .map(|i| u8::from_str_radix(&text[i * 2..i * 2 + 2], 16).map_err(|_| EfiErrorKind::InvalidParameter.into()))
+        .collect()
+}
+
+// Parses e.g. "10.0.0.1:69". Empty means the unspecified address.
+fn parse_ipv4_and_port(text: &str) -> Result<(Ipv4Addr, u16)> {
+    if text.is_empty() {
+        return Ok((Ipv4Addr::unspecified(), 0));
+    }
+
+    let (addr, port) = match text.find(':') {
+        Some(colon) => (&text[..colon], parse_int_max(&text[colon + 1..], u16::MAX as u64)? as u16),
+        None => (text, 0),
+    };
+
+    let addr = addr.parse::<Ipv4Addr>().map_err(|_| EfiError::from(EfiErrorKind::InvalidParameter))?;
+    Ok((addr, port))
+}
+
+/// An iterator over the nodes of a device path
+pub struct Nodes<'a> {
+    bytes: &'a [u8],
+    done: bool,
+}
+
+impl<'a> Nodes<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Nodes { bytes, done: false }
+    }
+}
+
+impl<'a> Iterator for Nodes<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Node<'a>> {
+        if self.done || self.bytes.len() < NODE_HEADER_SIZE {
+            return None;
+        }
+
+        let len = LittleEndian::read_u16(&self.bytes[2..4]) as usize;
+        if len < NODE_HEADER_SIZE || len > self.bytes.len() {
+            self.done = true; // Malformed; the rest can't be trusted
+            return None;
+        }
+
+        let (node, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        let node = Node::parse(node);
+        if node == Node::End {
+            self.done = true;
+        }
+        Some(node)
+    }
+}
+
+pub(crate) fn device_path_of(handle: EFI_HANDLE) -> Result<DevicePath> {
+    DevicePath::from_ptr(open_protocol(handle, &EFI_DEVICE_PATH_PROTOCOL_GUID)?)
+}
+
+// Device path nodes start with a 4 byte header: type, sub-type and a 16 bit length that includes the header
+pub(crate) const DEV_PATH_NODE_HEADER_SIZE: usize = 4;
+
+#[cfg(feature = "fs")]
+pub(crate) fn node_len(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < DEV_PATH_NODE_HEADER_SIZE {
+        return None;
+    }
+
+    let len = LittleEndian::read_u16(&bytes[2..4]) as usize;
+    if len < DEV_PATH_NODE_HEADER_SIZE || len > bytes.len() { None } else { Some(len) }
+}
+
+// Checks that the nodes in the buffer are properly sized and that the path ends with an end node
+fn is_well_formed(bytes: &[u8]) -> bool {
+    let mut offset = 0;
+    while offset + DEV_PATH_NODE_HEADER_SIZE <= bytes.len() {
+        let node_len = bytes[offset + 2] as usize | ((bytes[offset + 3] as usize) << 8);
+        if node_len < DEV_PATH_NODE_HEADER_SIZE || offset + node_len > bytes.len() {
+            return false;
+        }
+
+        if bytes[offset] == END_DEVICE_PATH_TYPE && bytes[offset + 1] == END_ENTIRE_DEVICE_PATH_SUBTYPE {
+            return true;
+        }
+
+        offset += node_len;
+    }
+
+    false
+}
+
+fn to_string(path: *const EFI_DEVICE_PATH_PROTOCOL, is_single_node: bool) -> Result<String> {
+    // TODO: Are we supposed to call CloseProtocol on a protocol pointer obtained via LocateProtocol?
+    // UEFI documentation seems to suggest it's not required but doesn't the firmeware need to know we're
+    // no longer using the pointer and hence if needed it can clean it up? Check this.
+    let protocol: *const EFI_DEVICE_PATH_TO_TEXT_PROTOCOL = locate_protocol(&EFI_DEVICE_PATH_TO_TEXT_PROTOCOL_GUID)
+        .map_err(|_| EfiError::from(EfiErrorKind::DeviceError))?; // TODO: Need proper error here
 
     let text_ptr = unsafe { if is_single_node {
         ((*protocol).ConvertDeviceNodeToText)(path, FALSE, FALSE)
@@ -168,29 +888,13 @@ fn to_string(path: *const EFI_DEVICE_PATH_PROTOCOL, is_single_node: bool) -> Res
     Ok(utf8_string)
 }
 
-// TODO: Make device paths strongly typed by introducing independent types for
-// different kinds of device paths like file, harddrive, usb, ipv4 and so on.
-
-// pub struct FileDevicePath {
-// }
-
 fn path_utils() -> Result<*mut EFI_DEVICE_PATH_UTILITIES_PROTOCOL> {
     // TODO: Don't "locate" this protocol every time. Do it once and keep a global pointer.
-    let bs = (*system_table()).BootServices;
-
-    let utils: *mut EFI_DEVICE_PATH_UTILITIES_PROTOCOL   = ptr::null_mut();
-    unsafe {
-        // TODO: Are we supposed to call CloseProtocol on a protocol pointer obtained via LocateProtocol?
-        // UEFI documentation seems to suggest it's not required but doesn't the firmeware need to know we're
-        // no longer using the pointer and hence if needed it can clean it up? Check this.
-        ret_on_err!(((*bs).LocateProtocol)(&EFI_DEVICE_PATH_UTILITIES_PROTOCOL_GUID, ptr::null(), mem::transmute(&utils)));
-
-        if utils.is_null() { // If above call returned null protocol that means no such protocol is associated with the handle (which is odd)
-            return Err(EfiErrorKind::LoadError.into()); // TODO: Need proper error here
-        }
-    }
-
-    Ok(utils)
+    // TODO: Are we supposed to call CloseProtocol on a protocol pointer obtained via LocateProtocol?
+    // UEFI documentation seems to suggest it's not required but doesn't the firmeware need to know we're
+    // no longer using the pointer and hence if needed it can clean it up? Check this.
+    let utils: *const EFI_DEVICE_PATH_UTILITIES_PROTOCOL = locate_protocol(&EFI_DEVICE_PATH_UTILITIES_PROTOCOL_GUID)?;
+    Ok(utils as *mut EFI_DEVICE_PATH_UTILITIES_PROTOCOL)
 }
 
 pub fn create_file_path_node<P: AsRef<str>>(relative_file_path: P) -> Result<DeviceNode> { // TODO: return value should be strongly typed as FileDeviceNode 
@@ -212,4 +916,170 @@ pub fn append_path(path1: &DevicePath, path2: &DevicePath) -> Result<DevicePath>
     };
 
     DevicePath::from_ptr(path)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(node_type: u8, sub_type: u8, data: &[u8]) -> Vec<u8> {
+        let len = (data.len() + NODE_HEADER_SIZE) as u16;
+        let mut bytes = vec![node_type, sub_type, len as u8, (len >> 8) as u8];
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn end() -> Vec<u8> {
+        node(END_DEVICE_PATH_TYPE, END_ENTIRE_DEVICE_PATH_SUBTYPE, &[])
+    }
+
+    fn file(path: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        for c in path.encode_utf16().chain(Some(0)) {
+            data.push(c as u8);
+            data.push((c >> 8) as u8);
+        }
+        node(MEDIA_DEVICE_PATH, MEDIA_FILEPATH_DP, &data)
+    }
+
+    struct Text<'a>(&'a [u8]);
+
+    impl<'a> fmt::Display for Text<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            fmt_nodes(f, Nodes::new(self.0))
+        }
+    }
+
+    #[test]
+    fn nodes_are_decoded() {
+        let mut hd = vec![1, 0, 0, 0];
+        hd.extend_from_slice(&[0x00, 0x08, 0, 0, 0, 0, 0, 0]);
+        hd.extend_from_slice(&[0x00, 0x00, 0x10, 0, 0, 0, 0, 0]);
+        hd.extend_from_slice(&[0x28, 0x73, 0x2A, 0xC1, 0x1F, 0xF8, 0xD2, 0x11, 0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B]);
+        hd.extend_from_slice(&[2, 2]);
+
+        let mut bytes = node(HARDWARE_DEVICE_PATH, HW_PCI_DP, &[0x2, 0x1F]);
+        bytes.extend(node(MEDIA_DEVICE_PATH, MEDIA_HARDDRIVE_DP, &hd));
+        bytes.extend(file("\\EFI\\BOOT\\BOOTX64.EFI"));
+        bytes.extend(end());
+
+        let nodes = Nodes::new(&bytes).collect::<Vec<_>>();
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(nodes[0], Node::Pci { device: 0x1F, function: 0x2 });
+        assert_eq!(nodes[1], Node::HardDrive {
+            partition_number: 1,
+            start: 0x800,
+            size: 0x100000,
+            signature: PartitionSignature::Guid(EFI_GUID_ESP),
+        });
+        assert_eq!(nodes[2], Node::FilePath("\\EFI\\BOOT\\BOOTX64.EFI".into()));
+        assert_eq!(nodes[3], Node::End);
+
+        assert_eq!(format!("{}", Text(&bytes)), "Pci(0x1F,0x2)/HD(1,GPT,C12A7328-F81F-11D2-BA4B-00A0C93EC93B,0x800,0x100000)/\\EFI\\BOOT\\BOOTX64.EFI");
+    }
+
+    const EFI_GUID_ESP: Guid = ::ffi::EFI_GUID(0xC12A7328, 0xF81F, 0x11D2, [0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B]);
+
+    #[test]
+    fn network_nodes_are_formatted() {
+        let mut mac = vec![0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+        mac.resize(32, 0);
+        mac.push(1);
+        let ipv4 = [10, 0, 0, 2, 10, 0, 0, 1, 0, 0, 69, 0, 17, 0, 0, 10, 0, 0, 1, 255, 255, 255, 0];
+
+        let mut bytes = node(MESSAGING_DEVICE_PATH, MSG_USB_DP, &[3, 0]);
+        bytes.extend(node(MESSAGING_DEVICE_PATH, MSG_MAC_ADDR_DP, &mac));
+        bytes.extend(node(MESSAGING_DEVICE_PATH, MSG_IPV4_DP, &ipv4));
+        bytes.extend(node(END_DEVICE_PATH_TYPE, END_INSTANCE_DEVICE_PATH_SUBTYPE, &[]));
+        bytes.extend(node(0x42, 0x7, &[0xAB]));
+        bytes.extend(end());
+
+        assert_eq!(format!("{}", Text(&bytes)), "USB(0x3,0x0)/MAC(525400123456,0x1)/IPv4(10.0.0.1,UDP,DHCP,10.0.0.2,10.0.0.1,255.255.255.0),Path(66,7,AB)");
+    }
+
+    #[test]
+    fn built_paths_decode_to_the_same_nodes() {
+        let mac = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+        let builder = DevicePathBuilder::new()
+            .pci(0x3, 0x0)
+            .mac(&mac, 1)
+            .ipv4(Ipv4Addr::unspecified(), Ipv4Addr::new(10, 0, 0, 1), false)
+            .uri("http://10.0.0.1/boot.efi")
+            .end_instance()
+            .hard_drive(2, 0x800, 0x1000, PartitionSignature::Mbr(0xDEADBEEF))
+            .file("\\EFI\\BOOT\\BOOTX64.EFI");
+        let bytes = builder.to_bytes().unwrap();
+        assert!(is_well_formed(&bytes));
+
+        let nodes = Nodes::new(&bytes).collect::<Vec<_>>();
+        assert_eq!(nodes, vec![
+            Node::Pci { device: 0x3, function: 0x0 },
+            Node::MacAddr { address: &mac, if_type: 1 },
+            Node::Ipv4 {
+                local: Ipv4Addr::unspecified(),
+                remote: Ipv4Addr::new(10, 0, 0, 1),
+                local_port: 0,
+                remote_port: 0,
+                protocol: 0,
+                static_address: false,
+                gateway: Ipv4Addr::unspecified(),
+                subnet_mask: Ipv4Addr::unspecified(),
+            },
+            Node::Uri("http://10.0.0.1/boot.efi".into()),
+            Node::EndInstance,
+            Node::HardDrive { partition_number: 2, start: 0x800, size: 0x1000, signature: PartitionSignature::Mbr(0xDEADBEEF) },
+            Node::FilePath("\\EFI\\BOOT\\BOOTX64.EFI".into()),
+            Node::End,
+        ]);
+
+        // MAC nodes are always padded to 32 bytes and IPv4 nodes have the full UEFI 2.3.1 layout
+        assert_eq!(&bytes[8..10], &[37, 0]);
+        assert_eq!(&bytes[45..47], &[27, 0]);
+
+        let long_name = (0..0x8000).map(|_| 'a').collect::<String>();
+        assert!(DevicePathBuilder::new().file(&long_name).to_bytes().is_err());
+    }
+
+    #[test]
+    fn text_is_parsed_without_firmware() {
+        let texts = [
+            "PciRoot(0x0)/Pci(0x1F,0x2)/Sata(0x0,0xFFFF,0x0)/HD(1,GPT,C12A7328-F81F-11D2-BA4B-00A0C93EC93B,0x800,0x100000)/\\EFI\\BOOT\\BOOTX64.EFI",
+            "PcieRoot(0x1)/Pci(0x0,0x0)/NVMe(0x1,00-25-38-5B-71-B0-12-34)/HD(2,MBR,0xDEADBEEF,0x800,0x1000)",
+            "PciRoot(0x0)/Pci(0x3,0x0)/MAC(525400123456,0x1)/IPv4(10.0.0.1,UDP,Static,10.0.0.2,10.0.0.1,255.255.255.0)/Uri(http://10.0.0.1/a,b.efi)",
+            "Acpi(PNP0501,0x0),Path(66,7,ABCD)",
+        ];
+
+        for text in texts.iter() {
+            let bytes = DevicePathBuilder::from_text(text).unwrap().to_bytes().unwrap();
+            assert_eq!(format!("{}", Text(&bytes)), *text);
+        }
+
+        // Decimal numbers, missing arguments and ports are accepted too
+        let bytes = DevicePathBuilder::from_text("Pci(31,2)/IPv4(10.0.0.1:80,TCP)/USB(1)").unwrap().to_bytes().unwrap();
+        let nodes = Nodes::new(&bytes).collect::<Vec<_>>();
+        assert_eq!(nodes[0], Node::Pci { device: 31, function: 2 });
+        match nodes[1] {
+            Node::Ipv4 { remote_port, protocol, static_address, .. } => assert_eq!((remote_port, protocol, static_address), (80, 6, false)),
+            ref other => panic!("unexpected node {:?}", other),
+        }
+        assert_eq!(nodes[2], Node::Usb { parent_port: 1, interface: 0 });
+
+        assert_eq!(DevicePathBuilder::from_text("Pci(0xZZ,0x0)").unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+        assert_eq!(DevicePathBuilder::from_text("Pci(256,0)").unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+        assert_eq!(DevicePathBuilder::from_text("Sata(0x10000,0,0)").unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+        assert_eq!(DevicePathBuilder::from_text("VenHw(...)").unwrap_err().kind(), EfiErrorKind::Unsupported);
+        assert_eq!(DevicePathBuilder::from_text("HD(1,GPT,not-a-guid,0x0,0x0)").unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+    }
+
+    #[test]
+    fn malformed_paths_stop_iteration() {
+        let mut bytes = node(HARDWARE_DEVICE_PATH, HW_PCI_DP, &[0]); // Too short for a PCI node
+        bytes.extend_from_slice(&[MEDIA_DEVICE_PATH, MEDIA_FILEPATH_DP, 0xFF, 0]);
+
+        let nodes = Nodes::new(&bytes).collect::<Vec<_>>();
+        assert_eq!(nodes, vec![Node::Other { node_type: HARDWARE_DEVICE_PATH, sub_type: HW_PCI_DP, data: &[0] }]);
+
+        let mut bytes = end();
+        bytes.extend(file("after the end"));
+        assert_eq!(Nodes::new(&bytes).count(), 1);
+    }
+}
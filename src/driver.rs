@@ -0,0 +1,356 @@
+use ffi::{
+    driver_binding::{EFI_DRIVER_BINDING_PROTOCOL, EFI_DRIVER_BINDING_PROTOCOL_GUID},
+    device_path::{EFI_DEVICE_PATH_PROTOCOL, EFI_DEVICE_PATH_PROTOCOL_GUID},
+    boot_services::{
+        EFI_INTERFACE_TYPE,
+        EFI_LOCATE_SEARCH_TYPE,
+        EFI_OPEN_PROTOCOL_BY_DRIVER,
+        EFI_OPEN_PROTOCOL_BY_CHILD_CONTROLLER,
+    },
+    EFI_GUID,
+    EFI_HANDLE,
+    EFI_STATUS,
+    EFI_SUCCESS,
+    EFI_SYSTEM_TABLE,
+    UINTN,
+    VOID,
+};
+use device_path::{DevicePath, DevicePathBuilder, Node};
+use image::LoadedImage;
+use boxed::EfiBox;
+use device_path::device_path_of;
+use alloc::{Vec, boxed::Box};
+use core::{ptr, mem, slice};
+use {Result, boot_services, image_handle, init_env};
+
+// Support for writing UEFI drivers that follow the driver model. The firmware calls a driver's
+// DriverBinding protocol to ask whether it can manage a controller and to start or stop managing it.
+//
+// A driver's entry point installs its binding and keeps it alive until the image is unloaded:
+//
+//     #[no_mangle]
+//     pub extern "win64" fn efi_main(image: EFI_HANDLE, system_table: *const EFI_SYSTEM_TABLE) -> EFI_STATUS {
+//         driver::driver_main(image, system_table, || {
+//             driver::keep_until_unload(DriverBinding::install(MyDriver::new(), 0x10)?)
+//         })
+//     }
+//
+// Build with the `boot-driver` feature so that the heap comes from boot services memory.
+
+/// The callbacks behind a `DriverBinding`. They're called by the firmware, possibly re-entrantly
+/// (e.g. starting a child may connect it, which asks every driver including this one whether it's supported),
+/// so any state has to live behind a `RefCell` or similar.
+pub trait Driver {
+    /// Returns Ok if the driver can manage the controller. This must not change anything;
+    /// protocols opened to check should be closed again before returning.
+    /// `remaining_path` is the path of the child the caller wants created, for bus drivers that support that.
+    fn supported(&self, controller: EFI_HANDLE, remaining_path: Option<&DevicePath>) -> Result<()>;
+
+    /// Starts managing the controller, usually by opening its protocols with `open_by_driver()`
+    /// and installing new protocols on it or on child handles.
+    fn start(&self, controller: EFI_HANDLE, remaining_path: Option<&DevicePath>) -> Result<()>;
+
+    /// Stops managing the controller. If `children` is empty the driver must release the controller itself,
+    /// otherwise only the given children must be destroyed.
+    fn stop(&self, controller: EFI_HANDLE, children: &[EFI_HANDLE]) -> Result<()>;
+}
+
+/// A DriverBinding protocol installed on the image handle and backed by a `Driver`.
+/// Dropping it disconnects the driver from all controllers and uninstalls the protocol.
+pub struct DriverBinding<D: Driver>(Box<Binding<D>>);
+
+#[repr(C)] // repr C needed so that we can safely cast the protocol pointer back to this struct in the callbacks below
+struct Binding<D: Driver> {
+    proto: EFI_DRIVER_BINDING_PROTOCOL,
+    driver: D,
+}
+
+impl<D: Driver> DriverBinding<D> {
+    /// Installs the binding. `version` orders drivers that support the same controller, higher ones winning.
+    /// Versions 0x0-0x0f and 0xfffffff0-0xffffffff are reserved for IHV-developed drivers.
+    pub fn install(driver: D, version: u32) -> Result<Self> {
+        let bs = boot_services();
+        let mut handle = image_handle();
+        let binding = Box::new(Binding {
+            proto: EFI_DRIVER_BINDING_PROTOCOL {
+                Supported: supported_callback::<D>,
+                Start: start_callback::<D>,
+                Stop: stop_callback::<D>,
+                Version: version,
+                ImageHandle: handle,
+                DriverBindingHandle: handle,
+            },
+            driver,
+        });
+
+        unsafe {
+            ret_on_err!(((*bs).InstallProtocolInterface)(&mut handle, &EFI_DRIVER_BINDING_PROTOCOL_GUID, EFI_INTERFACE_TYPE::EFI_NATIVE_INTERFACE, &binding.proto as *const EFI_DRIVER_BINDING_PROTOCOL as *const VOID));
+        }
+
+        Ok(DriverBinding(binding))
+    }
+
+    pub fn driver(&self) -> &D {
+        &self.0.driver
+    }
+
+    /// The handle the binding is installed on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.0.proto.DriverBindingHandle
+    }
+}
+
+impl<D: Driver> Drop for DriverBinding<D> {
+    fn drop(&mut self) {
+        let bs = boot_services();
+        let handle = self.handle();
+        unsafe {
+            // Controllers must be released before the binding's memory goes away
+            if let Ok(controllers) = all_handles() {
+                for controller in controllers {
+                    ((*bs).DisconnectController)(controller, handle, ptr::null());
+                }
+            }
+
+            ((*bs).UninstallProtocolInterface)(handle, &EFI_DRIVER_BINDING_PROTOCOL_GUID, &self.0.proto as *const EFI_DRIVER_BINDING_PROTOCOL as *const VOID); // TODO: Can't do anything if this fails. So we should log here
+        }
+    }
+}
+
+fn all_handles() -> Result<Vec<EFI_HANDLE>> {
+    let bs = boot_services();
+    let mut no_of_handles: UINTN = 0;
+    let mut handle_buf: *const EFI_HANDLE = ptr::null_mut();
+    unsafe {
+        ret_on_err!(((*bs).LocateHandleBuffer)(EFI_LOCATE_SEARCH_TYPE::AllHandles, ptr::null(), ptr::null(), &mut no_of_handles, &mut handle_buf));
+    }
+
+    if no_of_handles == 0 || handle_buf.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let handle_buf = unsafe { EfiBox::from_raw(handle_buf as *mut EFI_HANDLE) };  // Putting it in a box for proper cleanup on exit
+    let handles = unsafe { slice::from_raw_parts(handle_buf.as_raw() as *const EFI_HANDLE, no_of_handles) };
+    Ok(handles.to_vec())
+}
+
+// The remaining device path is only borrowed for the duration of the callback
+unsafe fn remaining_path(path: *const EFI_DEVICE_PATH_PROTOCOL) -> Option<DevicePath> {
+    if path.is_null() { None } else { DevicePath::from_ptr(path).ok() }
+}
+
+fn to_status(result: Result<()>) -> EFI_STATUS {
+    match result {
+        Ok(()) => EFI_SUCCESS,
+        Err(e) => e.into(),
+    }
+}
+
+extern "win64" fn supported_callback<D: Driver>(this: *const EFI_DRIVER_BINDING_PROTOCOL, controller: EFI_HANDLE, remaining: *const EFI_DEVICE_PATH_PROTOCOL) -> EFI_STATUS {
+    let binding = unsafe { &*(this as *const Binding<D>) }; // Should be safe to do this cast since Binding is marked repr C
+    let path = unsafe { remaining_path(remaining) };
+    to_status(binding.driver.supported(controller, path.as_ref()))
+}
+
+extern "win64" fn start_callback<D: Driver>(this: *const EFI_DRIVER_BINDING_PROTOCOL, controller: EFI_HANDLE, remaining: *const EFI_DEVICE_PATH_PROTOCOL) -> EFI_STATUS {
+    let binding = unsafe { &*(this as *const Binding<D>) };
+    let path = unsafe { remaining_path(remaining) };
+    to_status(binding.driver.start(controller, path.as_ref()))
+}
+
+extern "win64" fn stop_callback<D: Driver>(this: *const EFI_DRIVER_BINDING_PROTOCOL, controller: EFI_HANDLE, no_of_children: UINTN, children: *const EFI_HANDLE) -> EFI_STATUS {
+    let binding = unsafe { &*(this as *const Binding<D>) };
+    let children = if no_of_children == 0 || children.is_null() { &[] } else { unsafe { slice::from_raw_parts(children, no_of_children) } };
+    to_status(binding.driver.stop(controller, children))
+}
+
+// Anything can be kept; all that's needed of it is to be dropped on unload
+trait Kept {}
+impl<T> Kept for T {}
+
+static mut KEPT: Option<Vec<Box<Kept>>> = None;
+
+/// Keeps a value, typically a `DriverBinding`, alive after the entry point returns.
+/// Kept values are dropped in reverse order when the image is unloaded.
+pub fn keep_until_unload<T: 'static>(value: T) -> Result<()> {
+    unsafe {
+        if KEPT.is_none() {
+            LoadedImage::current()?.set_unload(unload_callback);
+            KEPT = Some(Vec::new());
+        }
+
+        KEPT.as_mut().expect("just set").push(Box::new(value));
+    }
+
+    Ok(())
+}
+
+fn drop_kept() {
+    unsafe {
+        if let Some(mut kept) = KEPT.take() {
+            while let Some(value) = kept.pop() {
+                mem::drop(value);
+            }
+        }
+    }
+}
+
+extern "win64" fn unload_callback(_image_handle: EFI_HANDLE) -> EFI_STATUS {
+    drop_kept();
+    EFI_SUCCESS
+}
+
+/// The body of a driver's entry point. Initialises the crate, runs `init` and converts its result to
+/// the status the firmware expects. Unlike an application a driver returns straight away and stays
+/// resident, so `init` should install its protocols and hand them to `keep_until_unload()`.
+/// If `init` fails anything it kept is dropped again and the firmware unloads the image.
+pub fn driver_main<F: FnOnce() -> Result<()>>(image_handle: EFI_HANDLE, system_table: *const EFI_SYSTEM_TABLE, init: F) -> EFI_STATUS {
+    init_env(image_handle, system_table);
+    let result = init();
+    if result.is_err() {
+        drop_kept();
+    }
+    to_status(result)
+}
+
+/// Opens a protocol on a controller for the driver's exclusive use as a driver, from `Driver::start()`.
+/// Fails with `AccessDenied` or `AlreadyStarted` if another driver, or this one, already manages it.
+pub fn open_by_driver<T>(controller: EFI_HANDLE, protocol: &EFI_GUID) -> Result<*const T> {
+    let bs = boot_services();
+    let interface: *const T = ptr::null();
+    unsafe {
+        ret_on_err!(((*bs).OpenProtocol)(controller, protocol, mem::transmute(&interface), image_handle(), controller, EFI_OPEN_PROTOCOL_BY_DRIVER));
+    }
+
+    Ok(interface)
+}
+
+/// Closes a protocol opened with `open_by_driver()`, from `Driver::stop()` or when `Driver::supported()` is done checking
+pub fn close_by_driver(controller: EFI_HANDLE, protocol: &EFI_GUID) -> Result<()> {
+    let bs = boot_services();
+    unsafe {
+        ret_on_err!(((*bs).CloseProtocol)(controller, protocol, image_handle(), controller));
+    }
+
+    Ok(())
+}
+
+/// The device path of a child of the controller: the controller's own path followed by the given node
+pub fn child_device_path(controller: EFI_HANDLE, node: &Node) -> Result<DevicePath> {
+    DevicePathBuilder::from_path(&device_path_of(controller)?).node(node).build()
+}
+
+/// A handle created by a bus driver for a child of a controller it manages. It carries a device path and
+/// the protocols the driver produces for the child, and is recorded as a child of the controller by opening
+/// the controller's protocol BY_CHILD_CONTROLLER, which is what lets the firmware pass it to `Driver::stop()`.
+pub struct ChildHandle {
+    handle: EFI_HANDLE,
+    controller: EFI_HANDLE,
+    parent_protocol: EFI_GUID,
+    path: DevicePath,
+    protocols: Vec<(EFI_GUID, *const VOID)>,
+}
+
+impl ChildHandle {
+    /// Creates the child. `parent_protocol` is the protocol the driver opened on the controller with `open_by_driver()`.
+    /// The protocol interfaces must stay valid until the child is uninstalled.
+    pub fn install(controller: EFI_HANDLE, parent_protocol: &EFI_GUID, path: DevicePath, protocols: &[(EFI_GUID, *const VOID)]) -> Result<Self> {
+        let bs = boot_services();
+        let mut child = ChildHandle { handle: ptr::null_mut(), controller, parent_protocol: *parent_protocol, path, protocols: Vec::new() };
+
+        unsafe {
+            ret_on_err!(((*bs).InstallProtocolInterface)(&mut child.handle, &EFI_DEVICE_PATH_PROTOCOL_GUID, EFI_INTERFACE_TYPE::EFI_NATIVE_INTERFACE, child.path.as_ptr() as *const VOID));
+        }
+
+        let result = child.install_protocols(protocols).and_then(|_| {
+            let interface: *const VOID = ptr::null();
+            unsafe {
+                ret_on_err!(((*bs).OpenProtocol)(controller, parent_protocol, mem::transmute(&interface), image_handle(), child.handle, EFI_OPEN_PROTOCOL_BY_CHILD_CONTROLLER));
+            }
+            Ok(())
+        });
+
+        if let Err(e) = result {
+            let _ = child.uninstall_protocols();
+            return Err(e);
+        }
+
+        Ok(child)
+    }
+
+    fn install_protocols(&mut self, protocols: &[(EFI_GUID, *const VOID)]) -> Result<()> {
+        let bs = boot_services();
+        for &(guid, interface) in protocols {
+            unsafe {
+                ret_on_err!(((*bs).InstallProtocolInterface)(&mut self.handle, &guid, EFI_INTERFACE_TYPE::EFI_NATIVE_INTERFACE, interface));
+            }
+            self.protocols.push((guid, interface));
+        }
+
+        Ok(())
+    }
+
+    // Uninstalls as much as it can in reverse order, stopping at the first failure so the child stays consistent
+    fn uninstall_protocols(&mut self) -> Result<()> {
+        let bs = boot_services();
+        while let Some(&(guid, interface)) = self.protocols.last() {
+            unsafe {
+                ret_on_err!(((*bs).UninstallProtocolInterface)(self.handle, &guid, interface));
+            }
+            self.protocols.pop();
+        }
+
+        unsafe {
+            ret_on_err!(((*bs).UninstallProtocolInterface)(self.handle, &EFI_DEVICE_PATH_PROTOCOL_GUID, self.path.as_ptr() as *const VOID));
+        }
+
+        Ok(())
+    }
+
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    pub fn device_path(&self) -> &DevicePath {
+        &self.path
+    }
+
+    /// Destroys the child, from `Driver::stop()`. Fails, leaving the child usable, if its protocols are still
+    /// in use by other drivers; `stop()` should then return the error so the firmware knows.
+    pub fn uninstall(&mut self) -> Result<()> {
+        let bs = boot_services();
+        unsafe {
+            ret_on_err!(((*bs).CloseProtocol)(self.controller, &self.parent_protocol, image_handle(), self.handle));
+        }
+
+        let result = self.uninstall_protocols();
+        if result.is_err() {
+            // Restore the parent link so that stop() can be retried
+            let interface: *const VOID = ptr::null();
+            unsafe { ((*bs).OpenProtocol)(self.controller, &self.parent_protocol, mem::transmute(&interface), image_handle(), self.handle, EFI_OPEN_PROTOCOL_BY_CHILD_CONTROLLER) };
+        }
+
+        result
+    }
+}
+
+/// Asks the firmware to connect drivers to the controller, e.g. to children created in `Driver::start()`
+/// when the bus driver wants them usable straight away. Fails with `NotFound` if no driver supports it.
+pub fn connect_controller(controller: EFI_HANDLE, recursive: bool) -> Result<()> {
+    let bs = boot_services();
+    unsafe {
+        ret_on_err!(((*bs).ConnectController)(controller, ptr::null(), ptr::null(), recursive as u8));
+    }
+
+    Ok(())
+}
+
+/// Asks every driver managing the controller to stop
+pub fn disconnect_controller(controller: EFI_HANDLE) -> Result<()> {
+    let bs = boot_services();
+    unsafe {
+        ret_on_err!(((*bs).DisconnectController)(controller, ptr::null(), ptr::null()));
+    }
+
+    Ok(())
+}
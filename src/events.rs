@@ -16,7 +16,7 @@ use ffi::{
 };
 
 use core::{ptr, time::Duration};
-use {system_table, Result};
+use {boot_services, Result};
 
 pub trait Signal {
     fn signal(&mut self) -> Result<()>;
@@ -74,7 +74,7 @@ pub enum EventTpl {
 
 // impl<F: FnMut()> Event<F> {
 //     fn create<N: Into<Option<F>>>(notify_flags: UINT32, tpl: EventTpl, notify_func: N) -> Result<Self> {
-//         let bs = system_table().BootServices;
+//         let bs = boot_services();
 //         let notify_func: Option<F> = notify_func.into();
 
 //         let raw_func_ptr =  if let Some(notify_func) = notify_func {
@@ -99,7 +99,7 @@ pub enum EventTpl {
 //     }
 
 //     fn wait(&self) -> Result<()> {
-//         let bs = system_table().BootServices;
+//         let bs = boot_services();
 //         unsafe {
 //             let mut signaled_index = 0;
 //             ret_on_err!(((*bs).WaitForEvent)(1, &self.inner, &mut signaled_index));
@@ -109,13 +109,13 @@ pub enum EventTpl {
 //     }
 
 //     fn signal(&mut self) -> Result<()> {
-//         let bs = system_table().BootServices;
+//         let bs = boot_services();
 //         unsafe { ret_on_err!(((*bs).SignalEvent)(self.inner)); }
 //         Ok(())
 //     }
 
 //     fn is_signaled(&self) ->  Result<bool> {
-//         let bs = system_table().BootServices;
+//         let bs = boot_services();
 //         let status = unsafe { ((*bs).CheckEvent)(self.inner) };
 //         match status {
 //             EFI_SUCCESS => Ok(true),
@@ -132,7 +132,7 @@ pub enum EventTpl {
 
 // impl<F: FnMut()> Drop for Event<F> {
 //     fn drop(&mut self) {
-//         let bs = system_table().BootServices;
+//         let bs = boot_services();
 //         unsafe {
 //             ((*bs).CloseEvent)(self.inner); // Can't do a fucking thing if it returns failure
 //         }
@@ -219,7 +219,7 @@ pub struct Timer(EFI_EVENT);
 
 impl Timer {
     pub fn create(interval: Duration, schedule: TimerSchedule, state: TimerState, tpl: EventTpl) -> Result<Self> {
-        let bs = system_table().BootServices;
+        let bs = boot_services();
         let mut event: EFI_EVENT = ptr::null();
         unsafe {
             ret_on_err!(((*bs).CreateEvent)(EVT_TIMER, tpl as EFI_TPL, None, ptr::null(), &mut event));
@@ -235,7 +235,7 @@ impl Timer {
     }
 
     pub fn set(&mut self, interval: Duration, schedule: TimerSchedule) -> Result<()> {
-        let bs = system_table().BootServices;
+        let bs = boot_services();
         unsafe {
             ret_on_err!(((*bs).SetTimer)(self.0, schedule.as_raw(), as_100ns_units(&interval)));
         }
@@ -244,7 +244,7 @@ impl Timer {
     }
 
     pub fn cancel(&mut self) -> Result<()> {
-        let bs = system_table().BootServices;
+        let bs = boot_services();
         unsafe {
             ret_on_err!(((*bs).SetTimer)(self.0, EFI_TIMER_DELAY::TimerCancel, 0));
         }
@@ -256,7 +256,7 @@ impl Timer {
 
 impl Drop for Timer {
     fn drop(&mut self) {
-        let bs = system_table().BootServices;
+        let bs = boot_services();
         unsafe {
             ((*bs).CloseEvent)(self.0); // Can't do a fucking thing if it returns failure
         }
@@ -265,7 +265,7 @@ impl Drop for Timer {
 
 impl Wait for Timer {
     fn wait(&self) -> Result<()> {
-        let bs = system_table().BootServices;
+        let bs = boot_services();
         unsafe {
             let mut signaled_index = 0;
             ret_on_err!(((*bs).WaitForEvent)(1, &self.0, &mut signaled_index));
@@ -275,7 +275,7 @@ impl Wait for Timer {
     }
 
     fn is_signaled(&self) ->  Result<bool> {
-        let bs = system_table().BootServices;
+        let bs = boot_services();
         let status = unsafe { ((*bs).CheckEvent)(self.0) };
         match status {
             EFI_SUCCESS => Ok(true),
@@ -358,7 +358,7 @@ impl AsRawEvt for Timer {
 //     }
 // }
 
-fn as_100ns_units(dur: &Duration) -> UINT64 {
+pub(crate) fn as_100ns_units(dur: &Duration) -> UINT64 {
     const T_100NS_UNITS_IN_A_SEC: UINT64 = 10_000_000;
     const T_100NS_UNITS_IN_A_MICRO: UINT64  = 10;
     (dur.as_secs()* T_100NS_UNITS_IN_A_SEC) + (dur.subsec_micros() as u64 * T_100NS_UNITS_IN_A_MICRO)
@@ -0,0 +1,514 @@
+use ffi::{
+    EFI_EVENT,
+    EFI_SUCCESS,
+    EFI_NOT_READY,
+    UINTN,
+    boot_services::{EFI_TPL, TPL_CALLBACK},
+};
+use events::{Timer, TimerSchedule, TimerState, EventTpl, AsRawEvt};
+use time::Instant;
+use alloc::{Vec, boxed::Box, rc::Rc};
+use core::{ptr, mem, time::Duration};
+use {boot_services, Result};
+
+// A single-threaded executor for futures, driven by EFI events. Every task has an event of its own which its
+// waker signals. A future that waits on the firmware (a timer, a completion token) also tells the executor
+// which event the firmware will signal through `Context::wake_on()`. When no task has been woken the executor
+// blocks in WaitForEvent() on all these events at once, so the firmware gets to run in the meantime.
+//
+// This toolchain predates `core::future`, so the `Future` trait here is the crate's own. Futures are polled
+// through `&mut self` and must not move out from under anything they've handed to the firmware (boxing them is
+// the usual way to make sure of that).
+//
+// The executor must run at TPL_APPLICATION because WaitForEvent() isn't allowed at higher TPLs. Wakers can be
+// used from event notification functions.
+
+/// The result of polling a future
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Poll<T> {
+    Ready(T),
+    Pending,
+}
+
+impl<T> Poll<T> {
+    pub fn is_ready(&self) -> bool {
+        match *self {
+            Poll::Ready(_) => true,
+            Poll::Pending => false,
+        }
+    }
+
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Poll<U> {
+        match self {
+            Poll::Ready(t) => Poll::Ready(f(t)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// A computation that completes at some point. `poll()` either returns the output or, before returning
+/// `Pending`, arranges for the task to be woken through the context when it's worth polling again.
+pub trait Future {
+    type Output;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Self::Output>;
+}
+
+impl<'a, F: Future + ?Sized> Future for &'a mut F {
+    type Output = F::Output;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Self::Output> {
+        (**self).poll(cx)
+    }
+}
+
+impl<F: Future + ?Sized> Future for Box<F> {
+    type Output = F::Output;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Self::Output> {
+        (**self).poll(cx)
+    }
+}
+
+/// Wakes up a task by signaling its event. Clones wake the same task.
+#[derive(Clone)]
+pub struct Waker(Rc<WakeEvent>);
+
+struct WakeEvent(EFI_EVENT);
+
+impl Drop for WakeEvent {
+    fn drop(&mut self) {
+        let bs = boot_services();
+        unsafe {
+            ((*bs).CloseEvent)(self.0); // Nothing to be done if it fails
+        }
+    }
+}
+
+impl Waker {
+    fn new() -> Result<Self> {
+        let bs = boot_services();
+        let mut event: EFI_EVENT = ptr::null();
+        unsafe {
+            // An event without a notification function stays signaled until it's checked or waited on
+            ret_on_err!(((*bs).CreateEvent)(0, TPL_CALLBACK as EFI_TPL, None, ptr::null(), &mut event));
+        }
+
+        Ok(Waker(Rc::new(WakeEvent(event))))
+    }
+
+    /// Has the task polled again
+    pub fn wake(&self) {
+        let bs = boot_services();
+        unsafe {
+            ((*bs).SignalEvent)((self.0).0); // Only fails for invalid events
+        }
+    }
+
+    fn event(&self) -> EFI_EVENT {
+        (self.0).0
+    }
+
+    // Whether the waker has been signaled since the last check. Clears the signal.
+    fn take_signal(&self) -> Result<bool> {
+        let bs = boot_services();
+        match unsafe { ((*bs).CheckEvent)(self.event()) } {
+            EFI_SUCCESS => Ok(true),
+            EFI_NOT_READY => Ok(false),
+            s => Err(s.into()),
+        }
+    }
+}
+
+/// What a future gets to arrange being woken with
+pub struct Context<'a> {
+    waker: &'a Waker,
+    events: &'a mut Vec<EFI_EVENT>,
+}
+
+impl<'a> Context<'a> {
+    /// The waker of the task being polled
+    pub fn waker(&self) -> &Waker {
+        self.waker
+    }
+
+    /// Has the task polled again once the firmware signals the event. The registration only lasts until
+    /// the next poll and the event must stay open until then. It can't be a notify-signal event.
+    ///
+    /// The executor resets the event when it waits on it, so when polled again the future should check
+    /// its condition directly (the deadline, the token's status) rather than the event's state.
+    pub fn wake_on<E: AsRawEvt>(&mut self, event: &E) {
+        let event = unsafe { event.as_raw() };
+        if !self.events.contains(&event) {
+            self.events.push(event);
+        }
+    }
+}
+
+struct Task {
+    future: Box<Future<Output = ()>>,
+    waker: Waker,
+    events: Vec<EFI_EVENT>,
+    woken: bool,
+}
+
+impl Task {
+    fn poll(&mut self) -> bool {
+        self.events.clear();
+        let mut cx = Context { waker: &self.waker, events: &mut self.events };
+        self.future.poll(&mut cx).is_ready()
+    }
+}
+
+/// Runs tasks concurrently on the current thread
+///
+/// ```ignore
+/// let mut executor = Executor::new();
+/// executor.spawn(async_download(&server, "kernel"))?;
+/// executor.spawn(async_spinner())?;
+/// executor.run()?;
+/// ```
+pub struct Executor {
+    tasks: Vec<Task>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Executor { tasks: Vec::new() }
+    }
+
+    /// Adds a task. It's first polled by `run()`.
+    pub fn spawn<F: Future<Output = ()> + 'static>(&mut self, future: F) -> Result<()> {
+        let waker = Waker::new()?;
+        self.tasks.push(Task { future: Box::new(future), waker, events: Vec::new(), woken: true });
+        Ok(())
+    }
+
+    /// The number of tasks that haven't completed
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Polls the tasks as they are woken until all of them have completed
+    pub fn run(&mut self) -> Result<()> {
+        while !self.tasks.is_empty() {
+            let mut polled = false;
+            let mut i = 0;
+            while i < self.tasks.len() {
+                let woken = self.tasks[i].woken || self.tasks[i].waker.take_signal()?;
+                self.tasks[i].woken = false;
+                if woken {
+                    polled = true;
+                    if self.tasks[i].poll() {
+                        self.tasks.remove(i);
+                        continue;
+                    }
+                }
+
+                i += 1;
+            }
+
+            if !polled {
+                self.wait()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Waits until any of the tasks is woken and marks it so
+    fn wait(&mut self) -> Result<()> {
+        let mut events = Vec::new();
+        let mut owners = Vec::new();
+        for (i, task) in self.tasks.iter().enumerate() {
+            events.push(task.waker.event());
+            owners.push(i);
+            for &event in &task.events {
+                events.push(event);
+                owners.push(i);
+            }
+        }
+
+        let signaled = wait_for_any(&events)?;
+        self.tasks[owners[signaled]].woken = true;
+        Ok(())
+    }
+}
+
+/// Runs a future to completion on the current thread and returns its output
+pub fn block_on<F: Future>(mut future: F) -> Result<F::Output> {
+    let waker = Waker::new()?;
+    let mut events = Vec::new();
+    loop {
+        events.clear();
+        if let Poll::Ready(output) = future.poll(&mut Context { waker: &waker, events: &mut events }) {
+            return Ok(output);
+        }
+
+        events.push(waker.event());
+        wait_for_any(&events)?;
+    }
+}
+
+// Blocks until one of the events is signaled and returns its index
+fn wait_for_any(events: &[EFI_EVENT]) -> Result<usize> {
+    let bs = boot_services();
+    let mut index: UINTN = 0;
+    unsafe {
+        ret_on_err!(((*bs).WaitForEvent)(events.len() as UINTN, events.as_ptr(), &mut index));
+    }
+
+    Ok(index)
+}
+
+/// A future made from a closure that's called on every poll
+pub struct PollFn<F>(F);
+
+/// Makes a future out of a closure, e.g. to wrap a device's non-blocking check
+pub fn poll_fn<T, F: FnMut(&mut Context) -> Poll<T>>(f: F) -> PollFn<F> {
+    PollFn(f)
+}
+
+impl<T, F: FnMut(&mut Context) -> Poll<T>> Future for PollFn<F> {
+    type Output = T;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<T> {
+        (self.0)(cx)
+    }
+}
+
+/// A future that completes after a while. See `sleep()`.
+pub struct Sleep {
+    timer: Timer,
+    deadline: Instant,
+}
+
+/// Completes once the duration has passed. Fails if the firmware has no time stamp counter or timers.
+pub fn sleep(dur: Duration) -> Result<Sleep> {
+    let deadline = Instant::now()? + dur;
+    let timer = Timer::create(dur, TimerSchedule::Relative, TimerState::Active, EventTpl::Callback)?;
+    Ok(Sleep { timer, deadline })
+}
+
+impl Future for Sleep {
+    type Output = Result<()>;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        let now = match Instant::now() {
+            Ok(now) => now,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+
+        if now >= self.deadline {
+            return Poll::Ready(Ok(()));
+        }
+
+        // The timer may have gone off a little early as per the counter, or the task was woken for
+        // something else, so the timer is rearmed for whatever is left
+        if let Err(e) = self.timer.set(self.deadline - now, TimerSchedule::Relative) {
+            return Poll::Ready(Err(e));
+        }
+
+        cx.wake_on(&self.timer);
+        Poll::Pending
+    }
+}
+
+// A future in a combinator that may have completed already
+enum MaybeDone<F: Future> {
+    Pending(F),
+    Done(F::Output),
+    Taken,
+}
+
+impl<F: Future> MaybeDone<F> {
+    // Polls the future if it hasn't completed yet. Returns whether it has now.
+    fn poll(&mut self, cx: &mut Context) -> bool {
+        let output = match *self {
+            MaybeDone::Pending(ref mut future) => match future.poll(cx) {
+                Poll::Ready(output) => output,
+                Poll::Pending => return false,
+            },
+            _ => return true,
+        };
+
+        *self = MaybeDone::Done(output);
+        true
+    }
+
+    fn take(&mut self) -> F::Output {
+        match mem::replace(self, MaybeDone::Taken) {
+            MaybeDone::Done(output) => output,
+            _ => panic!("output of a combined future taken before it was ready"),
+        }
+    }
+}
+
+/// Runs two futures concurrently. See `join()`.
+pub struct Join<A: Future, B: Future> {
+    a: MaybeDone<A>,
+    b: MaybeDone<B>,
+}
+
+/// Completes with the outputs of both futures once both have completed
+pub fn join<A: Future, B: Future>(a: A, b: B) -> Join<A, B> {
+    Join { a: MaybeDone::Pending(a), b: MaybeDone::Pending(b) }
+}
+
+impl<A: Future, B: Future> Future for Join<A, B> {
+    type Output = (A::Output, B::Output);
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Self::Output> {
+        let a_done = self.a.poll(cx);
+        let b_done = self.b.poll(cx);
+        if a_done && b_done {
+            Poll::Ready((self.a.take(), self.b.take()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Runs any number of futures of the same type concurrently. See `join_all()`.
+pub struct JoinAll<F: Future> {
+    futures: Vec<MaybeDone<F>>,
+}
+
+/// Completes with the outputs of all the futures, in the same order, once all have completed
+pub fn join_all<I: IntoIterator>(futures: I) -> JoinAll<I::Item> where I::Item: Future {
+    JoinAll { futures: futures.into_iter().map(MaybeDone::Pending).collect() }
+}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Self::Output> {
+        let mut all_done = true;
+        for future in self.futures.iter_mut() {
+            all_done &= future.poll(cx);
+        }
+
+        if all_done {
+            Poll::Ready(self.futures.iter_mut().map(MaybeDone::take).collect())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// The output of whichever future completed first in a `select()`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/// Races two futures. See `select()`.
+pub struct Select<A, B> {
+    a: A,
+    b: B,
+}
+
+/// Completes with the output of whichever future completes first. The other one is dropped with the
+/// `Select`. If both are ready at once the first one wins. Handy for timeouts:
+///
+/// ```ignore
+/// match block_on(select(stream.read_async(&mut buf), sleep(Duration::from_secs(5))?))? {
+///     Either::Left(read) => read?,
+///     Either::Right(_) => return Err(EfiErrorKind::Timeout.into()),
+/// }
+/// ```
+pub fn select<A: Future, B: Future>(a: A, b: B) -> Select<A, B> {
+    Select { a, b }
+}
+
+impl<A: Future, B: Future> Future for Select<A, B> {
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Self::Output> {
+        if let Poll::Ready(a) = self.a.poll(cx) {
+            return Poll::Ready(Either::Left(a));
+        }
+
+        self.b.poll(cx).map(Either::Right)
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use mock::install;
+    use alloc::Vec;
+    use core::cell::{Cell, RefCell};
+
+    #[test]
+    fn joined_sleeps_run_concurrently() {
+        let env = install();
+        let (a, b) = block_on(join(sleep(Duration::from_secs(1)).unwrap(), sleep(Duration::from_secs(2)).unwrap())).unwrap();
+        assert!(a.is_ok() && b.is_ok());
+        assert_eq!(env.elapsed(), Duration::from_secs(2));
+
+        let all = block_on(join_all((1..4).map(|s| sleep(Duration::from_secs(s)).unwrap()))).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(env.elapsed(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn select_takes_the_first_to_complete() {
+        let env = install();
+        let first = block_on(select(sleep(Duration::from_secs(5)).unwrap(), sleep(Duration::from_secs(1)).unwrap())).unwrap();
+        assert!(match first { Either::Right(Ok(())) => true, _ => false });
+        assert_eq!(env.elapsed(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn wakers_get_tasks_polled_again() {
+        let env = install();
+        let mut polls = 0;
+        let output = block_on(poll_fn(|cx| {
+            polls += 1;
+            if polls == 3 {
+                return Poll::Ready("done");
+            }
+            cx.waker().wake();
+            Poll::Pending
+        })).unwrap();
+        assert_eq!(output, "done");
+        assert_eq!(env.elapsed(), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn executor_interleaves_tasks() {
+        let _env = install();
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let mut executor = Executor::new();
+        for &(name, secs) in &[("slow", 3), ("fast", 1)] {
+            let order = order.clone();
+            let mut sleep = sleep(Duration::from_secs(secs)).unwrap();
+            executor.spawn(poll_fn(move |cx| sleep.poll(cx).map(|_| order.borrow_mut().push(name)))).unwrap();
+        }
+
+        // A task that waits for another to wake it
+        let waker = Rc::new(RefCell::new(None));
+        let woken = Rc::new(Cell::new(false));
+        {
+            let (waker, woken) = (waker.clone(), woken.clone());
+            executor.spawn(poll_fn(move |cx| {
+                if woken.get() {
+                    return Poll::Ready(());
+                }
+                *waker.borrow_mut() = Some(cx.waker().clone());
+                Poll::Pending
+            })).unwrap();
+        }
+        executor.spawn(poll_fn(move |_| {
+            woken.set(true);
+            waker.borrow().as_ref().map(Waker::wake);
+            Poll::Ready(())
+        })).unwrap();
+
+        executor.run().unwrap();
+        assert_eq!(executor.len(), 0);
+        assert_eq!(*order.borrow(), vec!["fast", "slow"]);
+    }
+}
@@ -0,0 +1,151 @@
+use ffi::{
+    base::{EFI_GUID, EFI_STATUS, EFI_EVENT, UINT8, UINT16, UINT32, UINT64, VOID},
+    device_path::EFI_DEVICE_PATH_PROTOCOL,
+};
+
+pub const EFI_ATA_PASS_THRU_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x1D3DE7F0, 0x0807, 0x424F, [0xAA, 0x69, 0x11, 0xA5, 0x4E, 0x19, 0xA4, 0x6F]);
+
+pub const EFI_ATA_PASS_THRU_ATTRIBUTES_PHYSICAL: UINT32 = 0x0001;
+pub const EFI_ATA_PASS_THRU_ATTRIBUTES_LOGICAL: UINT32 = 0x0002;
+pub const EFI_ATA_PASS_THRU_ATTRIBUTES_NONBLOCKIO: UINT32 = 0x0004;
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_ATA_PASS_THRU_MODE {
+    pub Attributes: UINT32,
+    pub IoAlign: UINT32,
+}
+
+#[repr(C)]
+pub struct EFI_ATA_PASS_THRU_PROTOCOL {
+    pub Mode: *const EFI_ATA_PASS_THRU_MODE,
+    pub PassThru: EFI_ATA_PASS_THRU_PASSTHRU,
+    pub GetNextPort: EFI_ATA_PASS_THRU_GET_NEXT_PORT,
+    pub GetNextDevice: EFI_ATA_PASS_THRU_GET_NEXT_DEVICE,
+    pub BuildDevicePath: EFI_ATA_PASS_THRU_BUILD_DEVICE_PATH,
+    pub GetDevice: EFI_ATA_PASS_THRU_GET_DEVICE,
+    pub ResetPort: EFI_ATA_PASS_THRU_RESET_PORT,
+    pub ResetDevice: EFI_ATA_PASS_THRU_RESET_DEVICE,
+}
+
+#[derive(Debug, Default)]
+#[repr(C)]
+pub struct EFI_ATA_COMMAND_BLOCK {
+    pub Reserved1: [UINT8; 2],
+    pub AtaCommand: UINT8,
+    pub AtaFeatures: UINT8,
+    pub AtaSectorNumber: UINT8,
+    pub AtaCylinderLow: UINT8,
+    pub AtaCylinderHigh: UINT8,
+    pub AtaDeviceHead: UINT8,
+    pub AtaSectorNumberExp: UINT8,
+    pub AtaCylinderLowExp: UINT8,
+    pub AtaCylinderHighExp: UINT8,
+    pub AtaFeaturesExp: UINT8,
+    pub AtaSectorCount: UINT8,
+    pub AtaSectorCountExp: UINT8,
+    pub Reserved2: [UINT8; 6],
+}
+
+#[derive(Debug, Default)]
+#[repr(C)]
+pub struct EFI_ATA_STATUS_BLOCK {
+    pub Reserved1: [UINT8; 2],
+    pub AtaStatus: UINT8,
+    pub AtaError: UINT8,
+    pub AtaSectorNumber: UINT8,
+    pub AtaCylinderLow: UINT8,
+    pub AtaCylinderHigh: UINT8,
+    pub AtaDeviceHead: UINT8,
+    pub AtaSectorNumberExp: UINT8,
+    pub AtaCylinderLowExp: UINT8,
+    pub AtaCylinderHighExp: UINT8,
+    pub Reserved2: UINT8,
+    pub AtaSectorCount: UINT8,
+    pub AtaSectorCountExp: UINT8,
+    pub Reserved3: [UINT8; 6],
+}
+
+pub type EFI_ATA_PASS_THRU_CMD_PROTOCOL = UINT8;
+
+pub const EFI_ATA_PASS_THRU_PROTOCOL_ATA_HARDWARE_RESET: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x00;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_ATA_SOFTWARE_RESET: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x01;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_ATA_NON_DATA: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x02;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_PIO_DATA_IN: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x04;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_PIO_DATA_OUT: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x05;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_DMA: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x06;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_DMA_QUEUED: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x07;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_DEVICE_DIAGNOSTIC: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x08;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_DEVICE_RESET: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x09;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_UDMA_DATA_IN: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x0A;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_UDMA_DATA_OUT: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x0B;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_FPDMA: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0x0C;
+pub const EFI_ATA_PASS_THRU_PROTOCOL_RETURN_RESPONSE: EFI_ATA_PASS_THRU_CMD_PROTOCOL = 0xFF;
+
+pub type EFI_ATA_PASS_THRU_LENGTH = UINT8;
+
+pub const EFI_ATA_PASS_THRU_LENGTH_BYTES: EFI_ATA_PASS_THRU_LENGTH = 0x80;
+pub const EFI_ATA_PASS_THRU_LENGTH_MASK: EFI_ATA_PASS_THRU_LENGTH = 0x70;
+pub const EFI_ATA_PASS_THRU_LENGTH_NO_DATA_TRANSFER: EFI_ATA_PASS_THRU_LENGTH = 0x00;
+pub const EFI_ATA_PASS_THRU_LENGTH_FEATURES: EFI_ATA_PASS_THRU_LENGTH = 0x10;
+pub const EFI_ATA_PASS_THRU_LENGTH_SECTOR_COUNT: EFI_ATA_PASS_THRU_LENGTH = 0x20;
+pub const EFI_ATA_PASS_THRU_LENGTH_TPSIU: EFI_ATA_PASS_THRU_LENGTH = 0x30;
+pub const EFI_ATA_PASS_THRU_LENGTH_COUNT: EFI_ATA_PASS_THRU_LENGTH = 0x0F;
+
+#[repr(C)]
+pub struct EFI_ATA_PASS_THRU_COMMAND_PACKET {
+    pub Asb: *mut EFI_ATA_STATUS_BLOCK,
+    pub Acb: *mut EFI_ATA_COMMAND_BLOCK,
+    /// In units of 100ns. 0 means wait indefinitely.
+    pub Timeout: UINT64,
+    pub InDataBuffer: *mut VOID,
+    pub OutDataBuffer: *mut VOID,
+    pub InTransferLength: UINT32,
+    pub OutTransferLength: UINT32,
+    pub Protocol: EFI_ATA_PASS_THRU_CMD_PROTOCOL,
+    pub Length: EFI_ATA_PASS_THRU_LENGTH,
+}
+
+pub type EFI_ATA_PASS_THRU_PASSTHRU = extern "win64" fn(
+    This: *const EFI_ATA_PASS_THRU_PROTOCOL,
+    Port: UINT16,
+    PortMultiplierPort: UINT16,
+    Packet: *mut EFI_ATA_PASS_THRU_COMMAND_PACKET,
+    Event: EFI_EVENT
+) -> EFI_STATUS;
+
+pub type EFI_ATA_PASS_THRU_GET_NEXT_PORT = extern "win64" fn(
+    This: *const EFI_ATA_PASS_THRU_PROTOCOL,
+    Port: *mut UINT16
+) -> EFI_STATUS;
+
+pub type EFI_ATA_PASS_THRU_GET_NEXT_DEVICE = extern "win64" fn(
+    This: *const EFI_ATA_PASS_THRU_PROTOCOL,
+    Port: UINT16,
+    PortMultiplierPort: *mut UINT16
+) -> EFI_STATUS;
+
+pub type EFI_ATA_PASS_THRU_BUILD_DEVICE_PATH = extern "win64" fn(
+    This: *const EFI_ATA_PASS_THRU_PROTOCOL,
+    Port: UINT16,
+    PortMultiplierPort: UINT16,
+    DevicePath: *mut *const EFI_DEVICE_PATH_PROTOCOL
+) -> EFI_STATUS;
+
+pub type EFI_ATA_PASS_THRU_GET_DEVICE = extern "win64" fn(
+    This: *const EFI_ATA_PASS_THRU_PROTOCOL,
+    DevicePath: *const EFI_DEVICE_PATH_PROTOCOL,
+    Port: *mut UINT16,
+    PortMultiplierPort: *mut UINT16
+) -> EFI_STATUS;
+
+pub type EFI_ATA_PASS_THRU_RESET_PORT = extern "win64" fn(
+    This: *const EFI_ATA_PASS_THRU_PROTOCOL,
+    Port: UINT16
+) -> EFI_STATUS;
+
+pub type EFI_ATA_PASS_THRU_RESET_DEVICE = extern "win64" fn(
+    This: *const EFI_ATA_PASS_THRU_PROTOCOL,
+    Port: UINT16,
+    PortMultiplierPort: UINT16
+) -> EFI_STATUS;
@@ -11,7 +11,7 @@ use core::{mem, fmt};
 ///  Nanosecond: 0 - 999,999,999
 ///  TimeZone:   -1440 to 1440 or 2047
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub struct EFI_TIME {
     pub Year:   UINT16,
     pub Month: UINT8,
@@ -135,11 +135,11 @@ pub type EFI_PHYSICAL_ADDRESS = UINT64;
 #[derive(Debug)]
 #[repr(C)]
 pub struct EFI_TABLE_HEADER {
-    Signature : UINT64,
-    Revision : UINT32,
-    HeaderSize : UINT32,
-    CRC32 : UINT32,
-    Reserved : UINT32
+    pub Signature : UINT64,
+    pub Revision : UINT32,
+    pub HeaderSize : UINT32,
+    pub CRC32 : UINT32,
+    pub Reserved : UINT32
 }
 
 macro_rules! with_high_bit_set {
@@ -179,6 +179,7 @@ pub const EFI_END_OF_FILE: UINTN = with_high_bit_set!(31); // The end of the fil
 pub const EFI_INVALID_LANGUAGE: UINTN = with_high_bit_set!(32); // The language specified was invalid.
 pub const EFI_COMPROMISED_DATA: UINTN = with_high_bit_set!(33); // The security status of the data is unknown or compromisedand the data must be updated or replaced to restore a valid security status.
 pub const EFI_IP_ADDRESS_CONFLICT: UINTN = with_high_bit_set!(34); // There is an address conflict address allocation
+pub const EFI_HTTP_ERROR: UINTN = with_high_bit_set!(35); // A HTTP error occurred during the network operation.
 
 
 pub const EFI_WARN_UNKNOWN_GLYPH: UINTN = 1; // The string contained one or more characters that the device could not render and were skipped.
@@ -186,6 +187,8 @@ pub const EFI_WARN_DELETE_FAILURE: UINTN = 2; // The handle was closed, but the
 pub const EFI_WARN_WRITE_FAILURE: UINTN = 3; // The handle was closed, but the data to the file was not flushed properly.
 pub const EFI_WARN_BUFFER_TOO_SMALL: UINTN = 4; // The resulting buffer was too small, and the data was truncated to the buffer size.
 pub const EFI_WARN_STALE_DATA: UINTN = 5; // The data has not been updated within the timeframe set by local policy for this type of data.
+pub const EFI_WARN_FILE_SYSTEM: UINTN = 6; // The resulting buffer contains UEFI-compliant file system.
+pub const EFI_WARN_RESET_REQUIRED: UINTN = 7; // The operation will be processed across a system reset.
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum EFI_STATUS_TYPE {
@@ -239,7 +242,7 @@ pub type VOID = ();
 pub type EFI_HANDLE = *const VOID;
 pub type EFI_EVENT = *const VOID;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[repr(C)]
 pub struct EFI_GUID(pub UINT32, pub UINT16, pub UINT16, pub [UINT8; 8]);
 
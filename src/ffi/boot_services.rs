@@ -71,24 +71,36 @@ pub struct EFI_BOOT_SERVICES {
 }
 
 // The below are methods currently not defined
-pub type EFI_RAISE_TPL = *const NOT_DEFINED;
-pub type EFI_RESTORE_TPL = *const NOT_DEFINED;
-pub type EFI_ALLOCATE_PAGES = *const NOT_DEFINED;
-pub type EFI_FREE_PAGES = *const NOT_DEFINED;
+pub type EFI_RAISE_TPL = extern "win64" fn(
+    NewTpl: EFI_TPL
+) -> EFI_TPL;
+
+pub type EFI_RESTORE_TPL = extern "win64" fn(
+    OldTpl: EFI_TPL
+);
+
+pub type EFI_ALLOCATE_PAGES = extern "win64" fn(
+    Type: EFI_ALLOCATE_TYPE,
+    MemoryType: EFI_MEMORY_TYPE,
+    Pages: UINTN,
+    Memory: *mut EFI_PHYSICAL_ADDRESS
+) -> EFI_STATUS;
+
+pub type EFI_FREE_PAGES = extern "win64" fn(
+    Memory: EFI_PHYSICAL_ADDRESS,
+    Pages: UINTN
+) -> EFI_STATUS;
+
+pub const EFI_PAGE_SIZE: UINTN = 4096;
 pub type EFI_GET_MEMORY_MAP = *const NOT_DEFINED;
 
 pub type EFI_REINSTALL_PROTOCOL_INTERFACE = *const NOT_DEFINED;
 pub type EFI_HANDLE_PROTOCOL = *const NOT_DEFINED;
 pub type EFI_REGISTER_PROTOCOL_NOTIFY = *const NOT_DEFINED;
 pub type EFI_LOCATE_HANDLE = *const NOT_DEFINED;
-pub type EFI_LOCATE_DEVICE_PATH = *const NOT_DEFINED;
 pub type EFI_INSTALL_CONFIGURATION_TABLE = *const NOT_DEFINED;
-pub type EFI_EXIT = *const NOT_DEFINED;
 pub type EFI_IMAGE_UNLOAD = *const NOT_DEFINED;
 pub type EFI_EXIT_BOOT_SERVICES = *const NOT_DEFINED;
-pub type EFI_SET_WATCHDOG_TIMER = *const NOT_DEFINED;
-pub type EFI_CONNECT_CONTROLLER = *const NOT_DEFINED;
-pub type EFI_DISCONNECT_CONTROLLER = *const NOT_DEFINED;
 pub type EFI_OPEN_PROTOCOL_INFORMATION = *const NOT_DEFINED;
 pub type EFI_PROTOCOLS_PER_HANDLE = *const NOT_DEFINED;
 pub type EFI_INSTALL_MULTIPLE_PROTOCOL_INTERFACES = *const NOT_DEFINED;
@@ -162,6 +174,13 @@ pub type EFI_SET_TIMER = extern "win64" fn(
     TriggerTime: UINT64
 ) -> EFI_STATUS;
 
+pub type EFI_SET_WATCHDOG_TIMER = extern "win64" fn(
+    Timeout: UINTN,
+    WatchdogCode: UINT64,
+    DataSize: UINTN,
+    WatchdogData: *const CHAR16
+) -> EFI_STATUS;
+
 #[derive(Debug)]
 #[repr(C)]
 pub enum EFI_INTERFACE_TYPE {
@@ -186,6 +205,19 @@ pub type EFI_EVENT_NOTIFY = extern "win64" fn(
     Context: *const VOID
 ) -> EFI_STATUS;
 
+pub type EFI_CONNECT_CONTROLLER = extern "win64" fn(
+    ControllerHandle: EFI_HANDLE,
+    DriverImageHandle: *const EFI_HANDLE,
+    RemainingDevicePath: *const EFI_DEVICE_PATH_PROTOCOL,
+    Recursive: BOOLEAN
+) -> EFI_STATUS;
+
+pub type EFI_DISCONNECT_CONTROLLER = extern "win64" fn(
+    ControllerHandle: EFI_HANDLE,
+    DriverImageHandle: EFI_HANDLE,
+    ChildHandle: EFI_HANDLE
+) -> EFI_STATUS;
+
 pub const EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL: UINT32 = 0x00000001;
 pub const EFI_OPEN_PROTOCOL_GET_PROTOCOL: UINT32 = 0x00000002;
 pub const EFI_OPEN_PROTOCOL_TEST_PROTOCOL: UINT32 = 0x00000004;
@@ -229,6 +261,12 @@ pub type EFI_LOCATE_HANDLE_BUFFER = extern "win64" fn(
     Buffer: *mut *const EFI_HANDLE
 ) -> EFI_STATUS;
  
+pub type EFI_LOCATE_DEVICE_PATH = extern "win64" fn(
+    Protocol: *const EFI_GUID,
+    DevicePath: *mut *const EFI_DEVICE_PATH_PROTOCOL,
+    Device: *mut EFI_HANDLE
+) -> EFI_STATUS;
+
 pub type EFI_LOCATE_PROTOCOL = extern "win64" fn(
     Protocol: *const EFI_GUID,
     Registration: *const VOID,
@@ -250,6 +288,13 @@ pub type EFI_IMAGE_START = extern "win64" fn(
     ExitData: *mut *const CHAR16
 ) -> EFI_STATUS;
 
+pub type EFI_EXIT = extern "win64" fn(
+    ImageHandle: EFI_HANDLE,
+    ExitStatus: EFI_STATUS,
+    ExitDataSize: UINTN,
+    ExitData: *const CHAR16
+) -> EFI_STATUS;
+
 pub type EFI_STALL = extern "win64" fn(
     Microseconds: UINTN
 ) -> EFI_STATUS;
@@ -288,3 +333,22 @@ pub enum EFI_MEMORY_TYPE {
 } 
 
 pub type EFI_PHYSICAL_ADDRESS = UINT64;
+pub type EFI_VIRTUAL_ADDRESS = UINT64;
+
+pub const EFI_MEMORY_DESCRIPTOR_VERSION: UINT32 = 1;
+
+// Memory protection attributes of EFI_MEMORY_DESCRIPTOR.Attribute
+pub const EFI_MEMORY_RP: UINT64 = 0x0000000000002000;
+pub const EFI_MEMORY_XP: UINT64 = 0x0000000000004000;
+pub const EFI_MEMORY_RO: UINT64 = 0x0000000000020000;
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+pub struct EFI_MEMORY_DESCRIPTOR {
+    pub Type: UINT32,
+    pub PhysicalStart: EFI_PHYSICAL_ADDRESS,
+    pub VirtualStart: EFI_VIRTUAL_ADDRESS,
+    pub NumberOfPages: UINT64,
+    pub Attribute: UINT64,
+}
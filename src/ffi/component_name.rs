@@ -0,0 +1,25 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, EFI_HANDLE, CHAR8, CHAR16};
+
+pub const EFI_COMPONENT_NAME2_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x6A7A5CFF, 0xE8D9, 0x4F70, [0xBA, 0xDA, 0x75, 0xAB, 0x30, 0x25, 0xCE, 0x14]);
+
+#[repr(C)]
+pub struct EFI_COMPONENT_NAME2_PROTOCOL {
+    pub GetDriverName: EFI_COMPONENT_NAME2_GET_DRIVER_NAME,
+    pub GetControllerName: EFI_COMPONENT_NAME2_GET_CONTROLLER_NAME,
+    /// A null terminated ASCII string of RFC 4646 language codes separated by semicolons
+    pub SupportedLanguages: *const CHAR8,
+}
+
+pub type EFI_COMPONENT_NAME2_GET_DRIVER_NAME = extern "win64" fn(
+    This: *const EFI_COMPONENT_NAME2_PROTOCOL,
+    Language: *const CHAR8,
+    DriverName: *mut *const CHAR16
+) -> EFI_STATUS;
+
+pub type EFI_COMPONENT_NAME2_GET_CONTROLLER_NAME = extern "win64" fn(
+    This: *const EFI_COMPONENT_NAME2_PROTOCOL,
+    ControllerHandle: EFI_HANDLE,
+    ChildHandle: EFI_HANDLE,
+    Language: *const CHAR8,
+    ControllerName: *mut *const CHAR16
+) -> EFI_STATUS;
@@ -116,8 +116,8 @@ pub type EFI_TEXT_TEST_STRING = extern "win64" fn(
 pub type EFI_TEXT_QUERY_MODE = extern "win64" fn(
     This: *const EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL,
     ModeNumber: UINTN,
-    Columns: *const UINTN,
-    Rows: *const UINTN
+    Columns: *mut UINTN,
+    Rows: *mut UINTN
 ) -> EFI_STATUS;
 
 pub type EFI_TEXT_SET_MODE = extern "win64" fn(
@@ -198,7 +198,7 @@ pub type EFI_INPUT_READ_KEY = extern "win64" fn(
 ) -> EFI_STATUS;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub struct EFI_INPUT_KEY {
     pub ScanCode: UINT16,
     pub UnicodeChar: CHAR16
@@ -213,6 +213,31 @@ impl Default for EFI_INPUT_KEY  {
     }
 }
 
+pub const CHAR_NULL: CHAR16 = 0x0000;
+pub const CHAR_BACKSPACE: CHAR16 = 0x0008;
+pub const CHAR_TAB: CHAR16 = 0x0009;
+pub const CHAR_LINEFEED: CHAR16 = 0x000A;
+pub const CHAR_CARRIAGE_RETURN: CHAR16 = 0x000D;
+
+pub const SCAN_NULL: UINT16 = 0x0000;
+pub const SCAN_UP: UINT16 = 0x0001;
+pub const SCAN_DOWN: UINT16 = 0x0002;
+pub const SCAN_RIGHT: UINT16 = 0x0003;
+pub const SCAN_LEFT: UINT16 = 0x0004;
+pub const SCAN_HOME: UINT16 = 0x0005;
+pub const SCAN_END: UINT16 = 0x0006;
+pub const SCAN_INSERT: UINT16 = 0x0007;
+pub const SCAN_DELETE: UINT16 = 0x0008;
+pub const SCAN_PAGE_UP: UINT16 = 0x0009;
+pub const SCAN_PAGE_DOWN: UINT16 = 0x000A;
+pub const SCAN_F1: UINT16 = 0x000B;
+pub const SCAN_F10: UINT16 = 0x0014;
+pub const SCAN_F11: UINT16 = 0x0015;
+pub const SCAN_F12: UINT16 = 0x0016;
+pub const SCAN_ESC: UINT16 = 0x0017;
+pub const SCAN_F13: UINT16 = 0x0068;
+pub const SCAN_F24: UINT16 = 0x0073;
+
 #[repr(C)]
 pub struct EFI_SIMPLE_TEXT_INPUT_PROTOCOL {
     pub Reset: EFI_INPUT_RESET,
@@ -251,7 +276,7 @@ pub type EFI_REGISTER_KEYSTROKE_NOTIFY = extern "win64" fn(
     This: *mut EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL,
     KeyData: *const EFI_KEY_DATA,
     KeyNotificationFunction: EFI_KEY_NOTIFY_FUNCTION,
-    NotifyHandle: *const *mut VOID
+    NotifyHandle: *mut *mut VOID
 ) -> EFI_STATUS;
 
 pub type EFI_KEY_NOTIFY_FUNCTION = extern "win64" fn(
@@ -260,10 +285,11 @@ pub type EFI_KEY_NOTIFY_FUNCTION = extern "win64" fn(
 
 pub type EFI_UNREGISTER_KEYSTROKE_NOTIFY = extern "win64" fn(
     This: *mut EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL,
-    NotificationHandle: *const VOID
-);
+    NotificationHandle: *mut VOID
+) -> EFI_STATUS;
 
 #[repr(C)]
+#[derive(Debug, Copy, Clone)]
 pub struct EFI_KEY_DATA {
     pub Key: EFI_INPUT_KEY,
     pub KeyState: EFI_KEY_STATE
@@ -298,6 +324,8 @@ pub const EFI_SCROLL_LOCK_ACTIVE: EFI_KEY_TOGGLE_STATE = 0x01;
 pub const EFI_NUM_LOCK_ACTIVE: EFI_KEY_TOGGLE_STATE = 0x02;
 pub const EFI_CAPS_LOCK_ACTIVE: EFI_KEY_TOGGLE_STATE = 0x04;
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
 pub struct EFI_KEY_STATE {
     pub KeyShiftState: UINT32,
     pub KeyToggleState: EFI_KEY_TOGGLE_STATE,
@@ -0,0 +1,27 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, UINT32, VOID};
+
+pub const EFI_DECOMPRESS_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xD8117CFE, 0x94A6, 0x11D4, [0x9A, 0x3A, 0x00, 0x90, 0x27, 0x3F, 0xC1, 0x4D]);
+
+#[repr(C)]
+pub struct EFI_DECOMPRESS_PROTOCOL {
+    pub GetInfo: EFI_DECOMPRESS_GET_INFO,
+    pub Decompress: EFI_DECOMPRESS_DECOMPRESS,
+}
+
+pub type EFI_DECOMPRESS_GET_INFO = extern "win64" fn(
+    This: *const EFI_DECOMPRESS_PROTOCOL,
+    Source: *const VOID,
+    SourceSize: UINT32,
+    DestinationSize: *mut UINT32,
+    ScratchSize: *mut UINT32
+) -> EFI_STATUS;
+
+pub type EFI_DECOMPRESS_DECOMPRESS = extern "win64" fn(
+    This: *const EFI_DECOMPRESS_PROTOCOL,
+    Source: *const VOID,
+    SourceSize: UINT32,
+    Destination: *mut VOID,
+    DestinationSize: UINT32,
+    Scratch: *mut VOID,
+    ScratchSize: UINT32
+) -> EFI_STATUS;
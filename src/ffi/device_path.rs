@@ -29,11 +29,14 @@ pub struct EFI_DEVICE_PATH_UTILITIES_PROTOCOL {
     pub CreateDeviceNode: EFI_DEVICE_PATH_UTILS_CREATE_NODE,
 }
 
-pub type EFI_DEVICE_PATH_UTILS_GET_DEVICE_PATH_SIZE = *const NOT_DEFINED;
 pub type EFI_DEVICE_PATH_UTILS_APPEND_INSTANCE = *const NOT_DEFINED;
 pub type EFI_DEVICE_PATH_UTILS_GET_NEXT_INSTANCE = *const NOT_DEFINED;
 pub type EFI_DEVICE_PATH_UTILS_IS_MULTI_INSTANCE = *const NOT_DEFINED;
 
+pub type EFI_DEVICE_PATH_UTILS_GET_DEVICE_PATH_SIZE = extern "win64" fn(
+    DevicePath: *const EFI_DEVICE_PATH_PROTOCOL
+) -> UINTN;
+
 pub type EFI_DEVICE_PATH_UTILS_APPEND_PATH = extern "win64" fn(
     Src1: *const EFI_DEVICE_PATH_PROTOCOL,
     Src2: *const EFI_DEVICE_PATH_PROTOCOL
@@ -718,6 +721,17 @@ pub struct VLAN_DEVICE_PATH {
   pub VlanId: UINT16,
 }
 
+///
+/// Uniform Resource Identifiers (URI) Device Path SubType
+///
+pub const MSG_URI_DP: UINT8 = 0x18;
+
+#[repr(packed)]
+pub struct URI_DEVICE_PATH {
+  pub Header: EFI_DEVICE_PATH_PROTOCOL,
+  // Variable length URI here, not null terminated.
+}
+
 //
 // Media Device Path
 //
@@ -1011,4 +1025,20 @@ pub type EFI_DEVICE_PATH_TO_TEXT_PATH = extern "win64" fn(
     DevicePath: *const EFI_DEVICE_PATH_PROTOCOL,
     DisplayOnly: BOOLEAN,
     AllowShortcuts: BOOLEAN
-) -> *const CHAR16;
\ No newline at end of file
+) -> *const CHAR16;
+
+pub const EFI_DEVICE_PATH_FROM_TEXT_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x05c99a21, 0xc70f, 0x4ad2, [0x8a, 0x5f, 0x35, 0xdf, 0x33, 0x43, 0xf5, 0x1e]);
+
+#[repr(C)]
+pub struct EFI_DEVICE_PATH_FROM_TEXT_PROTOCOL {
+    pub ConvertTextToDeviceNode: EFI_DEVICE_PATH_FROM_TEXT_NODE,
+    pub ConvertTextToDevicePath: EFI_DEVICE_PATH_FROM_TEXT_PATH,
+}
+
+pub type EFI_DEVICE_PATH_FROM_TEXT_NODE = extern "win64" fn(
+    TextDeviceNode: *const CHAR16
+) -> *mut EFI_DEVICE_PATH_PROTOCOL;
+
+pub type EFI_DEVICE_PATH_FROM_TEXT_PATH = extern "win64" fn(
+    TextDevicePath: *const CHAR16
+) -> *mut EFI_DEVICE_PATH_PROTOCOL;
@@ -0,0 +1,35 @@
+use ffi::{
+    base::{EFI_GUID, EFI_STATUS, EFI_HANDLE, UINT32, UINTN},
+    device_path::EFI_DEVICE_PATH_PROTOCOL,
+};
+
+pub const EFI_DRIVER_BINDING_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x18A031AB, 0xB443, 0x4D1A, [0xA5, 0xC0, 0x0C, 0x09, 0x26, 0x1E, 0x9F, 0x71]);
+
+#[repr(C)]
+pub struct EFI_DRIVER_BINDING_PROTOCOL {
+    pub Supported: EFI_DRIVER_BINDING_SUPPORTED,
+    pub Start: EFI_DRIVER_BINDING_START,
+    pub Stop: EFI_DRIVER_BINDING_STOP,
+    pub Version: UINT32,
+    pub ImageHandle: EFI_HANDLE,
+    pub DriverBindingHandle: EFI_HANDLE,
+}
+
+pub type EFI_DRIVER_BINDING_SUPPORTED = extern "win64" fn(
+    This: *const EFI_DRIVER_BINDING_PROTOCOL,
+    ControllerHandle: EFI_HANDLE,
+    RemainingDevicePath: *const EFI_DEVICE_PATH_PROTOCOL
+) -> EFI_STATUS;
+
+pub type EFI_DRIVER_BINDING_START = extern "win64" fn(
+    This: *const EFI_DRIVER_BINDING_PROTOCOL,
+    ControllerHandle: EFI_HANDLE,
+    RemainingDevicePath: *const EFI_DEVICE_PATH_PROTOCOL
+) -> EFI_STATUS;
+
+pub type EFI_DRIVER_BINDING_STOP = extern "win64" fn(
+    This: *const EFI_DRIVER_BINDING_PROTOCOL,
+    ControllerHandle: EFI_HANDLE,
+    NumberOfChildren: UINTN,
+    ChildHandleBuffer: *const EFI_HANDLE
+) -> EFI_STATUS;
@@ -0,0 +1,95 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, EFI_HANDLE, UINT8, UINT32, UINTN, VOID, NOT_DEFINED};
+
+pub const EFI_FIRMWARE_VOLUME2_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x220E73B6, 0x6BDB, 0x4413, [0x84, 0x05, 0xB9, 0x74, 0xB1, 0x08, 0x61, 0x9A]);
+
+pub type EFI_FV_FILETYPE = UINT8;
+pub type EFI_SECTION_TYPE = UINT8;
+pub type EFI_FV_FILE_ATTRIBUTES = UINT32;
+
+pub const EFI_FV_FILETYPE_ALL: EFI_FV_FILETYPE = 0x00;
+pub const EFI_FV_FILETYPE_RAW: EFI_FV_FILETYPE = 0x01;
+pub const EFI_FV_FILETYPE_FREEFORM: EFI_FV_FILETYPE = 0x02;
+pub const EFI_FV_FILETYPE_SECURITY_CORE: EFI_FV_FILETYPE = 0x03;
+pub const EFI_FV_FILETYPE_PEI_CORE: EFI_FV_FILETYPE = 0x04;
+pub const EFI_FV_FILETYPE_DXE_CORE: EFI_FV_FILETYPE = 0x05;
+pub const EFI_FV_FILETYPE_PEIM: EFI_FV_FILETYPE = 0x06;
+pub const EFI_FV_FILETYPE_DRIVER: EFI_FV_FILETYPE = 0x07;
+pub const EFI_FV_FILETYPE_COMBINED_PEIM_DRIVER: EFI_FV_FILETYPE = 0x08;
+pub const EFI_FV_FILETYPE_APPLICATION: EFI_FV_FILETYPE = 0x09;
+pub const EFI_FV_FILETYPE_MM: EFI_FV_FILETYPE = 0x0A;
+pub const EFI_FV_FILETYPE_FIRMWARE_VOLUME_IMAGE: EFI_FV_FILETYPE = 0x0B;
+pub const EFI_FV_FILETYPE_FFS_PAD: EFI_FV_FILETYPE = 0xF0;
+
+pub const EFI_SECTION_ALL: EFI_SECTION_TYPE = 0x00;
+pub const EFI_SECTION_COMPRESSION: EFI_SECTION_TYPE = 0x01;
+pub const EFI_SECTION_GUID_DEFINED: EFI_SECTION_TYPE = 0x02;
+pub const EFI_SECTION_DISPOSABLE: EFI_SECTION_TYPE = 0x03;
+pub const EFI_SECTION_PE32: EFI_SECTION_TYPE = 0x10;
+pub const EFI_SECTION_PIC: EFI_SECTION_TYPE = 0x11;
+pub const EFI_SECTION_TE: EFI_SECTION_TYPE = 0x12;
+pub const EFI_SECTION_DXE_DEPEX: EFI_SECTION_TYPE = 0x13;
+pub const EFI_SECTION_VERSION: EFI_SECTION_TYPE = 0x14;
+pub const EFI_SECTION_USER_INTERFACE: EFI_SECTION_TYPE = 0x15;
+pub const EFI_SECTION_COMPATIBILITY16: EFI_SECTION_TYPE = 0x16;
+pub const EFI_SECTION_FIRMWARE_VOLUME_IMAGE: EFI_SECTION_TYPE = 0x17;
+pub const EFI_SECTION_FREEFORM_SUBTYPE_GUID: EFI_SECTION_TYPE = 0x18;
+pub const EFI_SECTION_RAW: EFI_SECTION_TYPE = 0x19;
+pub const EFI_SECTION_PEI_DEPEX: EFI_SECTION_TYPE = 0x1B;
+pub const EFI_SECTION_MM_DEPEX: EFI_SECTION_TYPE = 0x1C;
+
+// Compression types of an EFI_COMPRESSION_SECTION
+pub const EFI_NOT_COMPRESSED: UINT8 = 0x00;
+pub const EFI_STANDARD_COMPRESSION: UINT8 = 0x01;
+
+// Attributes of an EFI_GUID_DEFINED_SECTION
+pub const EFI_GUIDED_SECTION_PROCESSING_REQUIRED: u16 = 0x01;
+pub const EFI_GUIDED_SECTION_AUTH_STATUS_VALID: u16 = 0x02;
+
+#[repr(C)]
+pub struct EFI_FIRMWARE_VOLUME2_PROTOCOL {
+    pub GetVolumeAttributes: EFI_FV_GET_ATTRIBUTES,
+    pub SetVolumeAttributes: EFI_FV_SET_ATTRIBUTES,
+    pub ReadFile: EFI_FV_READ_FILE,
+    pub ReadSection: EFI_FV_READ_SECTION,
+    pub WriteFile: EFI_FV_WRITE_FILE,
+    pub GetNextFile: EFI_FV_GET_NEXT_FILE,
+    pub KeySize: UINT32,
+    pub ParentHandle: EFI_HANDLE,
+    pub GetInfo: EFI_FV_GET_INFO,
+    pub SetInfo: EFI_FV_SET_INFO,
+}
+
+pub type EFI_FV_READ_FILE = extern "win64" fn(
+    This: *const EFI_FIRMWARE_VOLUME2_PROTOCOL,
+    NameGuid: *const EFI_GUID,
+    Buffer: *mut *mut VOID,
+    BufferSize: *mut UINTN,
+    FoundType: *mut EFI_FV_FILETYPE,
+    FileAttributes: *mut EFI_FV_FILE_ATTRIBUTES,
+    AuthenticationStatus: *mut UINT32
+) -> EFI_STATUS;
+
+pub type EFI_FV_READ_SECTION = extern "win64" fn(
+    This: *const EFI_FIRMWARE_VOLUME2_PROTOCOL,
+    NameGuid: *const EFI_GUID,
+    SectionType: EFI_SECTION_TYPE,
+    SectionInstance: UINTN,
+    Buffer: *mut *mut VOID,
+    BufferSize: *mut UINTN,
+    AuthenticationStatus: *mut UINT32
+) -> EFI_STATUS;
+
+pub type EFI_FV_GET_NEXT_FILE = extern "win64" fn(
+    This: *const EFI_FIRMWARE_VOLUME2_PROTOCOL,
+    Key: *mut VOID,
+    FileType: *mut EFI_FV_FILETYPE,
+    NameGuid: *mut EFI_GUID,
+    Attributes: *mut EFI_FV_FILE_ATTRIBUTES,
+    Size: *mut UINTN
+) -> EFI_STATUS;
+
+pub type EFI_FV_GET_ATTRIBUTES = *const NOT_DEFINED;
+pub type EFI_FV_SET_ATTRIBUTES = *const NOT_DEFINED;
+pub type EFI_FV_WRITE_FILE = *const NOT_DEFINED;
+pub type EFI_FV_GET_INFO = *const NOT_DEFINED;
+pub type EFI_FV_SET_INFO = *const NOT_DEFINED;
@@ -0,0 +1,110 @@
+use ffi::{
+    base::{EFI_GUID, EFI_STATUS, EFI_PHYSICAL_ADDRESS, UINT8, UINT32, UINTN},
+};
+
+pub const EFI_GRAPHICS_OUTPUT_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x9042a9de, 0x23dc, 0x4a38, [0x96, 0xfb, 0x7a, 0xde, 0xd0, 0x80, 0x51, 0x6a]);
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct EFI_PIXEL_BITMASK {
+    pub RedMask: UINT32,
+    pub GreenMask: UINT32,
+    pub BlueMask: UINT32,
+    pub ReservedMask: UINT32,
+}
+
+pub type EFI_GRAPHICS_PIXEL_FORMAT = UINT32;
+
+pub const PIXEL_RED_GREEN_BLUE_RESERVED_8_BIT_PER_COLOR: EFI_GRAPHICS_PIXEL_FORMAT = 0;
+pub const PIXEL_BLUE_GREEN_RED_RESERVED_8_BIT_PER_COLOR: EFI_GRAPHICS_PIXEL_FORMAT = 1;
+pub const PIXEL_BIT_MASK: EFI_GRAPHICS_PIXEL_FORMAT = 2;
+pub const PIXEL_BLT_ONLY: EFI_GRAPHICS_PIXEL_FORMAT = 3;
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct EFI_GRAPHICS_OUTPUT_MODE_INFORMATION {
+    pub Version: UINT32,
+    pub HorizontalResolution: UINT32,
+    pub VerticalResolution: UINT32,
+    pub PixelFormat: EFI_GRAPHICS_PIXEL_FORMAT,
+    pub PixelInformation: EFI_PIXEL_BITMASK,
+    pub PixelsPerScanLine: UINT32,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_GRAPHICS_OUTPUT_PROTOCOL_MODE {
+    pub MaxMode: UINT32,
+    pub Mode: UINT32,
+    pub Info: *const EFI_GRAPHICS_OUTPUT_MODE_INFORMATION,
+    pub SizeOfInfo: UINTN,
+    pub FrameBufferBase: EFI_PHYSICAL_ADDRESS,
+    pub FrameBufferSize: UINTN,
+}
+
+#[repr(C)]
+pub struct EFI_GRAPHICS_OUTPUT_PROTOCOL {
+    pub QueryMode: EFI_GRAPHICS_OUTPUT_PROTOCOL_QUERY_MODE,
+    pub SetMode: EFI_GRAPHICS_OUTPUT_PROTOCOL_SET_MODE,
+    pub Blt: EFI_GRAPHICS_OUTPUT_PROTOCOL_BLT,
+    pub Mode: *const EFI_GRAPHICS_OUTPUT_PROTOCOL_MODE,
+}
+
+pub type EFI_GRAPHICS_OUTPUT_PROTOCOL_QUERY_MODE = extern "win64" fn(
+    This: *mut EFI_GRAPHICS_OUTPUT_PROTOCOL,
+    ModeNumber: UINT32,
+    SizeOfInfo: *mut UINTN,
+    Info: *mut *mut EFI_GRAPHICS_OUTPUT_MODE_INFORMATION
+) -> EFI_STATUS;
+
+pub type EFI_GRAPHICS_OUTPUT_PROTOCOL_SET_MODE = extern "win64" fn(
+    This: *mut EFI_GRAPHICS_OUTPUT_PROTOCOL,
+    ModeNumber: UINT32
+) -> EFI_STATUS;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct EFI_GRAPHICS_OUTPUT_BLT_PIXEL {
+    pub Blue: UINT8,
+    pub Green: UINT8,
+    pub Red: UINT8,
+    pub Reserved: UINT8,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub enum EFI_GRAPHICS_OUTPUT_BLT_OPERATION {
+    EfiBltVideoFill,
+    EfiBltVideoToBltBuffer,
+    EfiBltBufferToVideo,
+    EfiBltVideoToVideo,
+    EfiGraphicsOutputBltOperationMax,
+}
+
+pub type EFI_GRAPHICS_OUTPUT_PROTOCOL_BLT = extern "win64" fn(
+    This: *mut EFI_GRAPHICS_OUTPUT_PROTOCOL,
+    BltBuffer: *mut EFI_GRAPHICS_OUTPUT_BLT_PIXEL,
+    BltOperation: EFI_GRAPHICS_OUTPUT_BLT_OPERATION,
+    SourceX: UINTN,
+    SourceY: UINTN,
+    DestinationX: UINTN,
+    DestinationY: UINTN,
+    Width: UINTN,
+    Height: UINTN,
+    Delta: UINTN
+) -> EFI_STATUS;
+
+pub const EFI_EDID_DISCOVERED_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x1c0c34f6, 0xd380, 0x41fa, [0xa0, 0x49, 0x8a, 0xd0, 0x6c, 0x1a, 0x66, 0xaa]);
+pub const EFI_EDID_ACTIVE_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xbd8c1056, 0x9f36, 0x44ec, [0x92, 0xa8, 0xa6, 0x33, 0x7f, 0x81, 0x79, 0x86]);
+
+#[repr(C)]
+pub struct EFI_EDID_DISCOVERED_PROTOCOL {
+    pub SizeOfEdid: UINT32,
+    pub Edid: *const UINT8,
+}
+
+#[repr(C)]
+pub struct EFI_EDID_ACTIVE_PROTOCOL {
+    pub SizeOfEdid: UINT32,
+    pub Edid: *const UINT8,
+}
@@ -0,0 +1,53 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, UINTN, UINT8};
+
+pub const EFI_HASH2_SERVICE_BINDING_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xda836f8d, 0x217f, 0x4ca0, [0x99, 0xc2, 0x1c, 0xa4, 0xe1, 0x60, 0x77, 0xea]);
+pub const EFI_HASH2_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x55b1d734, 0xc5e1, 0x49db, [0x96, 0x47, 0xb1, 0x6a, 0xfb, 0x0e, 0x30, 0x5b]);
+
+pub const EFI_HASH_ALGORITHM_SHA1_GUID: EFI_GUID = EFI_GUID(0x2ae9d80f, 0x3fb2, 0x4095, [0xb7, 0xb1, 0xe9, 0x31, 0x57, 0xb9, 0x46, 0xb6]);
+pub const EFI_HASH_ALGORITHM_SHA224_GUID: EFI_GUID = EFI_GUID(0x8df01a06, 0x9bd5, 0x4bf7, [0xb0, 0x21, 0xdb, 0x4f, 0xd9, 0xcc, 0xf4, 0x5b]);
+pub const EFI_HASH_ALGORITHM_SHA256_GUID: EFI_GUID = EFI_GUID(0x51aa59de, 0xfdf2, 0x4ea3, [0xbc, 0x63, 0x87, 0x5f, 0xb7, 0x84, 0x2e, 0xe9]);
+pub const EFI_HASH_ALGORITHM_SHA384_GUID: EFI_GUID = EFI_GUID(0xefa96432, 0xde33, 0x4dd2, [0xae, 0xe6, 0x32, 0x8c, 0x33, 0xdf, 0x77, 0x7a]);
+pub const EFI_HASH_ALGORITHM_SHA512_GUID: EFI_GUID = EFI_GUID(0xcaa4381e, 0x750c, 0x4770, [0xb8, 0x70, 0x7a, 0x23, 0xb4, 0xe4, 0x21, 0x30]);
+pub const EFI_HASH_ALGORITHM_MD5_GUID: EFI_GUID = EFI_GUID(0x0af7c79c, 0x65b5, 0x4319, [0xb0, 0xae, 0x44, 0xec, 0x48, 0x4e, 0x4a, 0xd7]);
+
+/// Big enough for the largest digest (SHA-512). The spec defines this as a union of per-algorithm arrays.
+pub type EFI_HASH2_OUTPUT = [UINT8; 64];
+
+#[repr(C)]
+pub struct EFI_HASH2_PROTOCOL {
+    pub GetHashSize: EFI_HASH2_GET_HASH_SIZE,
+    pub Hash: EFI_HASH2_HASH,
+    pub HashInit: EFI_HASH2_HASH_INIT,
+    pub HashUpdate: EFI_HASH2_HASH_UPDATE,
+    pub HashFinal: EFI_HASH2_HASH_FINAL,
+}
+
+pub type EFI_HASH2_GET_HASH_SIZE = extern "win64" fn(
+    This: *const EFI_HASH2_PROTOCOL,
+    HashAlgorithm: *const EFI_GUID,
+    HashSize: *mut UINTN
+) -> EFI_STATUS;
+
+pub type EFI_HASH2_HASH = extern "win64" fn(
+    This: *const EFI_HASH2_PROTOCOL,
+    HashAlgorithm: *const EFI_GUID,
+    Message: *const UINT8,
+    MessageSize: UINTN,
+    Hash: *mut EFI_HASH2_OUTPUT
+) -> EFI_STATUS;
+
+pub type EFI_HASH2_HASH_INIT = extern "win64" fn(
+    This: *const EFI_HASH2_PROTOCOL,
+    HashAlgorithm: *const EFI_GUID
+) -> EFI_STATUS;
+
+pub type EFI_HASH2_HASH_UPDATE = extern "win64" fn(
+    This: *const EFI_HASH2_PROTOCOL,
+    Message: *const UINT8,
+    MessageSize: UINTN
+) -> EFI_STATUS;
+
+pub type EFI_HASH2_HASH_FINAL = extern "win64" fn(
+    This: *const EFI_HASH2_PROTOCOL,
+    Hash: *mut EFI_HASH2_OUTPUT
+) -> EFI_STATUS;
@@ -0,0 +1,270 @@
+use ffi::{
+    base::{EFI_GUID, EFI_HANDLE, EFI_STATUS, UINT8, UINT16, UINT32, UINTN, CHAR8, CHAR16, VOID, NOT_DEFINED},
+    graphics::EFI_GRAPHICS_OUTPUT_BLT_PIXEL,
+};
+
+// Human Interface Infrastructure: the firmware's database of strings, fonts, forms and keyboard layouts
+
+pub type EFI_HII_HANDLE = *const VOID;
+pub type EFI_STRING_ID = UINT16;
+
+pub const EFI_HII_DATABASE_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xef9fc172, 0xa1b2, 0x4693, [0xb3, 0x27, 0x6d, 0x32, 0xfc, 0x41, 0x60, 0x42]);
+pub const EFI_HII_STRING_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x0fd96974, 0x23aa, 0x4cdc, [0xb9, 0xcb, 0x98, 0xd1, 0x77, 0x50, 0x32, 0x2a]);
+pub const EFI_HII_FONT_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xe9ca4775, 0x8657, 0x47fc, [0x97, 0xe7, 0x7e, 0xd6, 0x5a, 0x08, 0x43, 0x24]);
+
+pub const EFI_HII_PACKAGE_TYPE_ALL: UINT8 = 0x00;
+pub const EFI_HII_PACKAGE_TYPE_GUID: UINT8 = 0x01;
+pub const EFI_HII_PACKAGE_FORMS: UINT8 = 0x02;
+pub const EFI_HII_PACKAGE_STRINGS: UINT8 = 0x04;
+pub const EFI_HII_PACKAGE_FONTS: UINT8 = 0x05;
+pub const EFI_HII_PACKAGE_IMAGES: UINT8 = 0x06;
+pub const EFI_HII_PACKAGE_SIMPLE_FONTS: UINT8 = 0x07;
+pub const EFI_HII_PACKAGE_DEVICE_PATH: UINT8 = 0x08;
+pub const EFI_HII_PACKAGE_KEYBOARD_LAYOUT: UINT8 = 0x09;
+pub const EFI_HII_PACKAGE_ANIMATIONS: UINT8 = 0x0A;
+pub const EFI_HII_PACKAGE_END: UINT8 = 0xDF;
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct EFI_HII_PACKAGE_LIST_HEADER {
+    pub PackageListGuid: EFI_GUID,
+    pub PackageLength: UINT32,
+}
+
+// Each package starts with a UINT32 whose low 24 bits are the package's length (header included)
+// and whose high 8 bits are its type
+pub const EFI_HII_PACKAGE_HEADER_SIZE: usize = 4;
+
+#[repr(C)]
+pub struct EFI_HII_DATABASE_PROTOCOL {
+    pub NewPackageList: EFI_HII_DATABASE_NEW_PACK,
+    pub RemovePackageList: EFI_HII_DATABASE_REMOVE_PACK,
+    pub UpdatePackageList: EFI_HII_DATABASE_UPDATE_PACK,
+    pub ListPackageLists: EFI_HII_DATABASE_LIST_PACKS,
+    pub ExportPackageLists: EFI_HII_DATABASE_EXPORT_PACKS,
+    pub RegisterPackageNotify: EFI_HII_DATABASE_REGISTER_NOTIFY,
+    pub UnregisterPackageNotify: EFI_HII_DATABASE_UNREGISTER_NOTIFY,
+    pub FindKeyboardLayouts: EFI_HII_FIND_KEYBOARD_LAYOUTS,
+    pub GetKeyboardLayout: EFI_HII_GET_KEYBOARD_LAYOUT,
+    pub SetKeyboardLayout: EFI_HII_SET_KEYBOARD_LAYOUT,
+    pub GetPackageListHandle: EFI_HII_DATABASE_GET_PACKAGE_LIST_HANDLE,
+}
+
+pub type EFI_HII_DATABASE_NEW_PACK = *const NOT_DEFINED;
+pub type EFI_HII_DATABASE_REMOVE_PACK = *const NOT_DEFINED;
+pub type EFI_HII_DATABASE_UPDATE_PACK = *const NOT_DEFINED;
+pub type EFI_HII_DATABASE_REGISTER_NOTIFY = *const NOT_DEFINED;
+pub type EFI_HII_DATABASE_UNREGISTER_NOTIFY = *const NOT_DEFINED;
+
+pub type EFI_HII_DATABASE_LIST_PACKS = extern "win64" fn(
+    This: *const EFI_HII_DATABASE_PROTOCOL,
+    PackageType: UINT8,
+    PackageGuid: *const EFI_GUID,
+    HandleBufferLength: *mut UINTN,
+    Handle: *mut EFI_HII_HANDLE
+) -> EFI_STATUS;
+
+pub type EFI_HII_DATABASE_EXPORT_PACKS = extern "win64" fn(
+    This: *const EFI_HII_DATABASE_PROTOCOL,
+    Handle: EFI_HII_HANDLE,
+    BufferSize: *mut UINTN,
+    Buffer: *mut EFI_HII_PACKAGE_LIST_HEADER
+) -> EFI_STATUS;
+
+pub type EFI_HII_FIND_KEYBOARD_LAYOUTS = extern "win64" fn(
+    This: *const EFI_HII_DATABASE_PROTOCOL,
+    KeyGuidBufferLength: *mut UINT16,
+    KeyGuidBuffer: *mut EFI_GUID
+) -> EFI_STATUS;
+
+// The layout is an EFI_HII_KEYBOARD_LAYOUT: a UINT16 length, the layout's GUID, the UINT32 offset of its
+// description and a UINT8 count of EFI_KEY_DESCRIPTORs followed by the descriptors
+pub type EFI_HII_GET_KEYBOARD_LAYOUT = extern "win64" fn(
+    This: *const EFI_HII_DATABASE_PROTOCOL,
+    KeyGuid: *const EFI_GUID,
+    KeyboardLayoutLength: *mut UINT16,
+    KeyboardLayout: *mut UINT8
+) -> EFI_STATUS;
+
+pub type EFI_HII_SET_KEYBOARD_LAYOUT = extern "win64" fn(
+    This: *const EFI_HII_DATABASE_PROTOCOL,
+    KeyGuid: *const EFI_GUID
+) -> EFI_STATUS;
+
+pub type EFI_HII_DATABASE_GET_PACKAGE_LIST_HANDLE = extern "win64" fn(
+    This: *const EFI_HII_DATABASE_PROTOCOL,
+    PackageListHandle: EFI_HII_HANDLE,
+    DriverHandle: *mut EFI_HANDLE
+) -> EFI_STATUS;
+
+#[repr(C)]
+pub struct EFI_HII_STRING_PROTOCOL {
+    pub NewString: EFI_HII_NEW_STRING,
+    pub GetString: EFI_HII_GET_STRING,
+    pub SetString: EFI_HII_SET_STRING,
+    pub GetLanguages: EFI_HII_GET_LANGUAGES,
+    pub GetSecondaryLanguages: EFI_HII_GET_2ND_LANGUAGES,
+}
+
+pub type EFI_HII_NEW_STRING = *const NOT_DEFINED;
+pub type EFI_HII_SET_STRING = *const NOT_DEFINED;
+
+// StringSize is in bytes, including the null terminator
+pub type EFI_HII_GET_STRING = extern "win64" fn(
+    This: *const EFI_HII_STRING_PROTOCOL,
+    Language: *const CHAR8,
+    PackageList: EFI_HII_HANDLE,
+    StringId: EFI_STRING_ID,
+    String: *mut CHAR16,
+    StringSize: *mut UINTN,
+    StringFontInfo: *mut *mut EFI_FONT_INFO
+) -> EFI_STATUS;
+
+// Languages is a null terminated, semicolon separated list of RFC 4646 language codes
+pub type EFI_HII_GET_LANGUAGES = extern "win64" fn(
+    This: *const EFI_HII_STRING_PROTOCOL,
+    PackageList: EFI_HII_HANDLE,
+    Languages: *mut CHAR8,
+    LanguagesSize: *mut UINTN
+) -> EFI_STATUS;
+
+pub type EFI_HII_GET_2ND_LANGUAGES = extern "win64" fn(
+    This: *const EFI_HII_STRING_PROTOCOL,
+    PackageList: EFI_HII_HANDLE,
+    PrimaryLanguage: *const CHAR8,
+    SecondaryLanguages: *mut CHAR8,
+    SecondaryLanguagesSize: *mut UINTN
+) -> EFI_STATUS;
+
+pub type EFI_HII_FONT_STYLE = UINT32;
+pub const EFI_HII_FONT_STYLE_NORMAL: EFI_HII_FONT_STYLE = 0x00000000;
+pub const EFI_HII_FONT_STYLE_BOLD: EFI_HII_FONT_STYLE = 0x00000001;
+pub const EFI_HII_FONT_STYLE_ITALIC: EFI_HII_FONT_STYLE = 0x00000002;
+
+// FontName is really a null terminated string of any length
+#[repr(C)]
+pub struct EFI_FONT_INFO {
+    pub FontStyle: EFI_HII_FONT_STYLE,
+    pub FontSize: UINT16,
+    pub FontName: [CHAR16; 1],
+}
+
+pub type EFI_FONT_INFO_MASK = UINT32;
+pub const EFI_FONT_INFO_SYS_FONT: EFI_FONT_INFO_MASK = 0x00000001;
+pub const EFI_FONT_INFO_SYS_SIZE: EFI_FONT_INFO_MASK = 0x00000002;
+pub const EFI_FONT_INFO_SYS_STYLE: EFI_FONT_INFO_MASK = 0x00000004;
+pub const EFI_FONT_INFO_SYS_FORE_COLOR: EFI_FONT_INFO_MASK = 0x00000010;
+pub const EFI_FONT_INFO_SYS_BACK_COLOR: EFI_FONT_INFO_MASK = 0x00000020;
+
+#[repr(C)]
+pub struct EFI_FONT_DISPLAY_INFO {
+    pub ForegroundColor: EFI_GRAPHICS_OUTPUT_BLT_PIXEL,
+    pub BackgroundColor: EFI_GRAPHICS_OUTPUT_BLT_PIXEL,
+    pub FontInfoMask: EFI_FONT_INFO_MASK,
+    pub FontInfo: EFI_FONT_INFO,
+}
+
+// Image is a union of a bitmap and an EFI_GRAPHICS_OUTPUT_PROTOCOL to draw on directly. Only the bitmap is used here.
+#[repr(C)]
+pub struct EFI_IMAGE_OUTPUT {
+    pub Width: UINT16,
+    pub Height: UINT16,
+    pub Image: *mut EFI_GRAPHICS_OUTPUT_BLT_PIXEL,
+}
+
+#[repr(C)]
+pub struct EFI_HII_FONT_PROTOCOL {
+    pub StringToImage: EFI_HII_STRING_TO_IMAGE,
+    pub StringIdToImage: EFI_HII_STRING_ID_TO_IMAGE,
+    pub GetGlyph: EFI_HII_GET_GLYPH,
+    pub GetFontInfo: EFI_HII_GET_FONT_INFO,
+}
+
+pub type EFI_HII_STRING_TO_IMAGE = *const NOT_DEFINED;
+pub type EFI_HII_STRING_ID_TO_IMAGE = *const NOT_DEFINED;
+pub type EFI_HII_GET_FONT_INFO = *const NOT_DEFINED;
+
+// If *Blt is null the firmware allocates the EFI_IMAGE_OUTPUT and its bitmap, which the caller frees with FreePool()
+pub type EFI_HII_GET_GLYPH = extern "win64" fn(
+    This: *const EFI_HII_FONT_PROTOCOL,
+    Char: CHAR16,
+    StringInfo: *const EFI_FONT_DISPLAY_INFO,
+    Blt: *mut *mut EFI_IMAGE_OUTPUT,
+    Baseline: *mut UINTN
+) -> EFI_STATUS;
+
+pub const EFI_HII_CONFIG_ROUTING_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x587e72d7, 0xcc50, 0x4f79, [0x82, 0x09, 0xca, 0x29, 0x1f, 0xc1, 0xa1, 0x0f]);
+
+// Configuration strings are null terminated UTF-16. The ones returned in Results are allocated by the firmware
+// and freed by the caller with FreePool(). Progress points to where in the input processing stopped.
+#[repr(C)]
+pub struct EFI_HII_CONFIG_ROUTING_PROTOCOL {
+    pub ExtractConfig: EFI_HII_EXTRACT_CONFIG,
+    pub ExportConfig: EFI_HII_EXPORT_CONFIG,
+    pub RouteConfig: EFI_HII_ROUTE_CONFIG,
+    pub BlockToConfig: EFI_HII_BLOCK_TO_CONFIG,
+    pub ConfigToBlock: EFI_HII_CONFIG_TO_BLOCK,
+    pub GetAltConfig: EFI_HII_GET_ALT_CFG,
+}
+
+pub type EFI_HII_BLOCK_TO_CONFIG = *const NOT_DEFINED;
+pub type EFI_HII_CONFIG_TO_BLOCK = *const NOT_DEFINED;
+pub type EFI_HII_GET_ALT_CFG = *const NOT_DEFINED;
+
+pub type EFI_HII_EXTRACT_CONFIG = extern "win64" fn(
+    This: *const EFI_HII_CONFIG_ROUTING_PROTOCOL,
+    Request: *const CHAR16,
+    Progress: *mut *const CHAR16,
+    Results: *mut *mut CHAR16
+) -> EFI_STATUS;
+
+pub type EFI_HII_EXPORT_CONFIG = extern "win64" fn(
+    This: *const EFI_HII_CONFIG_ROUTING_PROTOCOL,
+    Results: *mut *mut CHAR16
+) -> EFI_STATUS;
+
+pub type EFI_HII_ROUTE_CONFIG = extern "win64" fn(
+    This: *const EFI_HII_CONFIG_ROUTING_PROTOCOL,
+    Configuration: *const CHAR16,
+    Progress: *mut *const CHAR16
+) -> EFI_STATUS;
+
+// Keyboard layouts are packed: the header below is followed by DescriptorCount EFI_KEY_DESCRIPTORs
+// and, at LayoutDescriptorStringOffset from the start of the layout, an EFI_DESCRIPTION_STRING_BUNDLE
+#[repr(C, packed)]
+pub struct EFI_HII_KEYBOARD_LAYOUT {
+    pub LayoutLength: UINT16,
+    pub Guid: EFI_GUID,
+    pub LayoutDescriptorStringOffset: UINT32,
+    pub DescriptorCount: UINT8,
+}
+
+pub const EFI_HII_KEYBOARD_LAYOUT_HEADER_SIZE: usize = 23;
+
+// Key is an EFI_KEY: the physical position of the key, as numbered in the UEFI spec's keyboard figure
+#[repr(C, packed)]
+pub struct EFI_KEY_DESCRIPTOR {
+    pub Key: UINT32,
+    pub Unicode: CHAR16,
+    pub ShiftedUnicode: CHAR16,
+    pub AltGrUnicode: CHAR16,
+    pub ShiftedAltGrUnicode: CHAR16,
+    pub Modifier: UINT16,
+    pub AffectedAttribute: UINT16,
+}
+
+pub const EFI_KEY_DESCRIPTOR_SIZE: usize = 16;
+
+pub const EFI_NULL_MODIFIER: UINT16 = 0x0000;
+pub const EFI_LEFT_CONTROL_MODIFIER: UINT16 = 0x0001;
+pub const EFI_RIGHT_CONTROL_MODIFIER: UINT16 = 0x0002;
+pub const EFI_LEFT_ALT_MODIFIER: UINT16 = 0x0003;
+pub const EFI_RIGHT_ALT_MODIFIER: UINT16 = 0x0004;
+pub const EFI_ALT_GR_MODIFIER: UINT16 = 0x0005;
+pub const EFI_CAPS_LOCK_MODIFIER: UINT16 = 0x000B;
+pub const EFI_LEFT_SHIFT_MODIFIER: UINT16 = 0x000C;
+pub const EFI_RIGHT_SHIFT_MODIFIER: UINT16 = 0x000D;
+
+pub const EFI_AFFECTED_BY_STANDARD_SHIFT: UINT16 = 0x0001;
+pub const EFI_AFFECTED_BY_CAPS_LOCK: UINT16 = 0x0002;
+pub const EFI_AFFECTED_BY_NUM_LOCK: UINT16 = 0x0004;
@@ -4,6 +4,8 @@ use ffi::base::{
     EFI_EVENT,
     EFI_TIME,
     UINTN,
+    UINT8,
+    UINT16,
     UINT32,
     UINT64,
     CHAR16,
@@ -29,6 +31,15 @@ pub type EFI_LOAD_FILE = extern "win64" fn(
     BufferPtr: *mut VOID
 ) -> EFI_STATUS;
 
+pub const EFI_LOAD_FILE2_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x4006C0C1, 0xFCB3, 0x403E, [0x99, 0x6D, 0x4A, 0x6C, 0x87, 0x24, 0xE0, 0x6D]);
+
+/// Same layout as EFI_LOAD_FILE_PROTOCOL. Only the meaning differs: LoadFile2 is never used for booting
+/// so the BootPolicy argument must be FALSE.
+pub type EFI_LOAD_FILE2_PROTOCOL = EFI_LOAD_FILE_PROTOCOL;
+
+/// Vendor media device path GUID on which the Linux EFI stub looks for a LoadFile2 instance serving the initrd
+pub const LINUX_EFI_INITRD_MEDIA_GUID: EFI_GUID = EFI_GUID(0x5568E427, 0x68FC, 0x4F3D, [0xAC, 0x74, 0xCA, 0x55, 0x52, 0x31, 0xCC, 0x68]);
+
 pub const EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID: EFI_GUID  = EFI_GUID(0x0964E5B22, 0x6459, 0x11D2, [0x8E, 0x39, 0x00, 0xA0, 0xC9, 0x69, 0x72, 0x3B]);
 
 pub const EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_REVISION: UINT64 = 0x00010000;
@@ -223,4 +234,281 @@ pub struct EFI_FILE_SYSTEM_INFO {
     pub FreeSpace: UINT64,
     pub BlockSize: UINT32,
     pub VolumeLabel: [CHAR16; 1], // Dynamically sized, null-terminated embedded string
-}
\ No newline at end of file
+}
+
+pub const EFI_FILE_SYSTEM_VOLUME_LABEL_ID: EFI_GUID = EFI_GUID(0xDB47D7D3, 0xFE81, 0x11D3, [0x9A, 0x35, 0x00, 0x90, 0x27, 0x3F, 0xC1, 0x4D]);
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_FILE_SYSTEM_VOLUME_LABEL {
+    pub VolumeLabel: [CHAR16; 1], // Dynamically sized, null-terminated embedded string
+}
+
+pub type EFI_LBA = UINT64;
+
+pub const EFI_BLOCK_IO_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x964E5B21, 0x6459, 0x11D2, [0x8E, 0x39, 0x00, 0xA0, 0xC9, 0x69, 0x72, 0x3B]);
+
+pub const EFI_BLOCK_IO_PROTOCOL_REVISION2: UINT64 = 0x00020001;
+pub const EFI_BLOCK_IO_PROTOCOL_REVISION3: UINT64 = 0x0002001F;
+
+#[repr(C)]
+pub struct EFI_BLOCK_IO_PROTOCOL {
+    pub Revision: UINT64,
+    pub Media: *const EFI_BLOCK_IO_MEDIA,
+    pub Reset: EFI_BLOCK_RESET,
+    pub ReadBlocks: EFI_BLOCK_READ,
+    pub WriteBlocks: EFI_BLOCK_WRITE,
+    pub FlushBlocks: EFI_BLOCK_FLUSH,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_BLOCK_IO_MEDIA {
+    pub MediaId: UINT32,
+    pub RemovableMedia: BOOLEAN,
+    pub MediaPresent: BOOLEAN,
+    pub LogicalPartition: BOOLEAN,
+    pub ReadOnly: BOOLEAN,
+    pub WriteCaching: BOOLEAN,
+    pub BlockSize: UINT32,
+    pub IoAlign: UINT32,
+    pub LastBlock: EFI_LBA,
+    // Present only if revision >= EFI_BLOCK_IO_PROTOCOL_REVISION2
+    pub LowestAlignedLba: EFI_LBA,
+    pub LogicalBlocksPerPhysicalBlock: UINT32,
+    // Present only if revision >= EFI_BLOCK_IO_PROTOCOL_REVISION3
+    pub OptimalTransferLengthGranularity: UINT32,
+}
+
+pub type EFI_BLOCK_RESET = extern "win64" fn(
+    This: *const EFI_BLOCK_IO_PROTOCOL,
+    ExtendedVerification: BOOLEAN
+) -> EFI_STATUS;
+
+pub type EFI_BLOCK_READ = extern "win64" fn(
+    This: *const EFI_BLOCK_IO_PROTOCOL,
+    MediaId: UINT32,
+    Lba: EFI_LBA,
+    BufferSize: UINTN,
+    Buffer: *mut VOID
+) -> EFI_STATUS;
+
+pub type EFI_BLOCK_WRITE = extern "win64" fn(
+    This: *const EFI_BLOCK_IO_PROTOCOL,
+    MediaId: UINT32,
+    Lba: EFI_LBA,
+    BufferSize: UINTN,
+    Buffer: *const VOID
+) -> EFI_STATUS;
+
+pub type EFI_BLOCK_FLUSH = extern "win64" fn(
+    This: *const EFI_BLOCK_IO_PROTOCOL
+) -> EFI_STATUS;
+
+pub const EFI_DISK_IO_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xCE345171, 0xBA0B, 0x11D2, [0x8E, 0x4F, 0x00, 0xA0, 0xC9, 0x69, 0x72, 0x3B]);
+
+pub const EFI_DISK_IO_PROTOCOL_REVISION: UINT64 = 0x00010000;
+
+#[repr(C)]
+pub struct EFI_DISK_IO_PROTOCOL {
+    pub Revision: UINT64,
+    pub ReadDisk: EFI_DISK_READ,
+    pub WriteDisk: EFI_DISK_WRITE,
+}
+
+pub type EFI_DISK_READ = extern "win64" fn(
+    This: *const EFI_DISK_IO_PROTOCOL,
+    MediaId: UINT32,
+    Offset: UINT64,
+    BufferSize: UINTN,
+    Buffer: *mut VOID
+) -> EFI_STATUS;
+
+pub type EFI_DISK_WRITE = extern "win64" fn(
+    This: *const EFI_DISK_IO_PROTOCOL,
+    MediaId: UINT32,
+    Offset: UINT64,
+    BufferSize: UINTN,
+    Buffer: *const VOID
+) -> EFI_STATUS;
+
+pub const EFI_DISK_IO2_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x151C8EAE, 0x7F2C, 0x472C, [0x9E, 0x54, 0x98, 0x28, 0x19, 0x4F, 0x6A, 0x88]);
+
+pub const EFI_DISK_IO2_PROTOCOL_REVISION: UINT64 = 0x00020000;
+
+#[repr(C)]
+pub struct EFI_DISK_IO2_PROTOCOL {
+    pub Revision: UINT64,
+    pub Cancel: EFI_DISK_CANCEL_EX,
+    pub ReadDiskEx: EFI_DISK_READ_EX,
+    pub WriteDiskEx: EFI_DISK_WRITE_EX,
+    pub FlushDiskEx: EFI_DISK_FLUSH_EX,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_DISK_IO2_TOKEN {
+    pub Event: EFI_EVENT,
+    pub TransactionStatus: EFI_STATUS,
+}
+
+pub type EFI_DISK_CANCEL_EX = extern "win64" fn(
+    This: *const EFI_DISK_IO2_PROTOCOL
+) -> EFI_STATUS;
+
+pub type EFI_DISK_READ_EX = extern "win64" fn(
+    This: *const EFI_DISK_IO2_PROTOCOL,
+    MediaId: UINT32,
+    Offset: UINT64,
+    Token: *mut EFI_DISK_IO2_TOKEN,
+    BufferSize: UINTN,
+    Buffer: *mut VOID
+) -> EFI_STATUS;
+
+pub type EFI_DISK_WRITE_EX = extern "win64" fn(
+    This: *const EFI_DISK_IO2_PROTOCOL,
+    MediaId: UINT32,
+    Offset: UINT64,
+    Token: *mut EFI_DISK_IO2_TOKEN,
+    BufferSize: UINTN,
+    Buffer: *const VOID
+) -> EFI_STATUS;
+
+pub type EFI_DISK_FLUSH_EX = extern "win64" fn(
+    This: *const EFI_DISK_IO2_PROTOCOL,
+    Token: *mut EFI_DISK_IO2_TOKEN
+) -> EFI_STATUS;
+
+pub const EFI_ERASE_BLOCK_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x95A9A93E, 0xA86E, 0x4926, [0xAA, 0xEF, 0x99, 0x18, 0xE7, 0x72, 0xD9, 0x87]);
+
+pub const EFI_ERASE_BLOCK_PROTOCOL_REVISION: UINT64 = (2 << 16) | 60;
+
+#[repr(C)]
+pub struct EFI_ERASE_BLOCK_PROTOCOL {
+    pub Revision: UINT64,
+    /// In blocks. Erases should start and end on a multiple of this for best performance.
+    pub EraseLengthGranularity: UINT32,
+    pub EraseBlocks: EFI_BLOCK_ERASE,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_ERASE_BLOCK_TOKEN {
+    pub Event: EFI_EVENT,
+    pub TransactionStatus: EFI_STATUS,
+}
+
+pub type EFI_BLOCK_ERASE = extern "win64" fn(
+    This: *const EFI_ERASE_BLOCK_PROTOCOL,
+    MediaId: UINT32,
+    LBA: EFI_LBA,
+    Token: *mut EFI_ERASE_BLOCK_TOKEN,
+    Size: UINTN
+) -> EFI_STATUS;
+
+pub const EFI_STORAGE_SECURITY_COMMAND_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xC88B0B6D, 0x0DFC, 0x49A7, [0x9C, 0xB4, 0x49, 0x07, 0x4B, 0x4C, 0x3A, 0x78]);
+
+#[repr(C)]
+pub struct EFI_STORAGE_SECURITY_COMMAND_PROTOCOL {
+    pub ReceiveData: EFI_STORAGE_SECURITY_RECEIVE_DATA,
+    pub SendData: EFI_STORAGE_SECURITY_SEND_DATA,
+}
+
+pub type EFI_STORAGE_SECURITY_RECEIVE_DATA = extern "win64" fn(
+    This: *const EFI_STORAGE_SECURITY_COMMAND_PROTOCOL,
+    MediaId: UINT32,
+    Timeout: UINT64,
+    SecurityProtocolId: UINT8,
+    SecurityProtocolSpecificData: UINT16,
+    PayloadBufferSize: UINTN,
+    PayloadBuffer: *mut VOID,
+    PayloadTransferSize: *mut UINTN
+) -> EFI_STATUS;
+
+pub type EFI_STORAGE_SECURITY_SEND_DATA = extern "win64" fn(
+    This: *const EFI_STORAGE_SECURITY_COMMAND_PROTOCOL,
+    MediaId: UINT32,
+    Timeout: UINT64,
+    SecurityProtocolId: UINT8,
+    SecurityProtocolSpecificData: UINT16,
+    PayloadBufferSize: UINTN,
+    PayloadBuffer: *const VOID
+) -> EFI_STATUS;
+
+pub const EFI_PARTITION_INFO_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x8CF2F62C, 0xBC9B, 0x4821, [0x80, 0x8D, 0xEC, 0x9E, 0xC4, 0x21, 0xA1, 0xA0]);
+
+pub const EFI_PARTITION_INFO_PROTOCOL_REVISION: UINT32 = 0x0001000;
+
+pub const PARTITION_TYPE_OTHER: UINT32 = 0x00;
+pub const PARTITION_TYPE_MBR: UINT32 = 0x01;
+pub const PARTITION_TYPE_GPT: UINT32 = 0x02;
+
+#[repr(packed)]
+pub struct EFI_PARTITION_INFO_PROTOCOL {
+    pub Revision: UINT32,
+    /// One of the PARTITION_TYPE_* values
+    pub Type: UINT32,
+    /// 1 if this is an EFI system partition
+    pub System: UINT8,
+    pub Reserved: [UINT8; 7],
+    pub Info: EFI_PARTITION_INFO,
+}
+
+/// Which member is valid depends on EFI_PARTITION_INFO_PROTOCOL.Type
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub union EFI_PARTITION_INFO {
+    pub Mbr: MBR_PARTITION_RECORD,
+    pub Gpt: EFI_PARTITION_ENTRY,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct MBR_PARTITION_RECORD {
+    pub BootIndicator: UINT8,
+    pub StartHead: UINT8,
+    pub StartSector: UINT8,
+    pub StartTrack: UINT8,
+    pub OSIndicator: UINT8,
+    pub EndHead: UINT8,
+    pub EndSector: UINT8,
+    pub EndTrack: UINT8,
+    pub StartingLBA: [UINT8; 4],
+    pub SizeInLBA: [UINT8; 4],
+}
+
+#[derive(Copy, Clone)]
+#[repr(packed)]
+pub struct EFI_PARTITION_ENTRY {
+    pub PartitionTypeGUID: EFI_GUID,
+    pub UniquePartitionGUID: EFI_GUID,
+    pub StartingLBA: EFI_LBA,
+    pub EndingLBA: EFI_LBA,
+    pub Attributes: UINT64,
+    pub PartitionName: [CHAR16; 36],
+}
+
+pub const EFI_RAM_DISK_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xAB38A0DF, 0x6873, 0x44A9, [0x87, 0xE6, 0xD4, 0xEB, 0x56, 0x14, 0x84, 0x49]);
+
+pub const EFI_VIRTUAL_DISK_GUID: EFI_GUID = EFI_GUID(0x77AB535A, 0x45FC, 0x624B, [0x55, 0x60, 0xF7, 0xB2, 0x81, 0xD1, 0xF9, 0x6E]);
+pub const EFI_VIRTUAL_CD_GUID: EFI_GUID = EFI_GUID(0x3D5ABD30, 0x4175, 0x87CE, [0x6D, 0x64, 0xD2, 0xAD, 0xE5, 0x23, 0xC4, 0xBB]);
+pub const EFI_PERSISTENT_VIRTUAL_DISK_GUID: EFI_GUID = EFI_GUID(0x5CEA02C9, 0x4D07, 0x69D3, [0x26, 0x9F, 0x44, 0x96, 0xFB, 0xE0, 0x96, 0xF9]);
+pub const EFI_PERSISTENT_VIRTUAL_CD_GUID: EFI_GUID = EFI_GUID(0x08018188, 0x42CD, 0xBB48, [0x10, 0x0F, 0x53, 0x87, 0xD5, 0x3D, 0xED, 0x3D]);
+
+#[repr(C)]
+pub struct EFI_RAM_DISK_PROTOCOL {
+    pub Register: EFI_RAM_DISK_REGISTER_RAMDISK,
+    pub Unregister: EFI_RAM_DISK_UNREGISTER_RAMDISK,
+}
+
+pub type EFI_RAM_DISK_REGISTER_RAMDISK = extern "win64" fn(
+    RamDiskBase: UINT64,
+    RamDiskSize: UINT64,
+    RamDiskType: *const EFI_GUID,
+    ParentDevicePath: *const EFI_DEVICE_PATH_PROTOCOL,
+    DevicePath: *mut *const EFI_DEVICE_PATH_PROTOCOL
+) -> EFI_STATUS;
+
+pub type EFI_RAM_DISK_UNREGISTER_RAMDISK = extern "win64" fn(
+    DevicePath: *const EFI_DEVICE_PATH_PROTOCOL
+) -> EFI_STATUS;
@@ -0,0 +1,33 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, EFI_PHYSICAL_ADDRESS, UINT64};
+
+pub const EFI_MEMORY_ATTRIBUTE_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xf4560cf6, 0x40ec, 0x4b4a, [0xa1, 0x92, 0xbf, 0x1d, 0x57, 0xd0, 0xb1, 0x89]);
+
+#[repr(C)]
+pub struct EFI_MEMORY_ATTRIBUTE_PROTOCOL {
+    pub GetMemoryAttributes: EFI_GET_MEMORY_ATTRIBUTES,
+    pub SetMemoryAttributes: EFI_SET_MEMORY_ATTRIBUTES,
+    pub ClearMemoryAttributes: EFI_CLEAR_MEMORY_ATTRIBUTES,
+}
+
+// Only EFI_MEMORY_RP, EFI_MEMORY_XP and EFI_MEMORY_RO may be passed or returned.
+// BaseAddress and Length must be multiples of EFI_PAGE_SIZE.
+pub type EFI_GET_MEMORY_ATTRIBUTES = extern "win64" fn(
+    This: *const EFI_MEMORY_ATTRIBUTE_PROTOCOL,
+    BaseAddress: EFI_PHYSICAL_ADDRESS,
+    Length: UINT64,
+    Attributes: *mut UINT64
+) -> EFI_STATUS;
+
+pub type EFI_SET_MEMORY_ATTRIBUTES = extern "win64" fn(
+    This: *const EFI_MEMORY_ATTRIBUTE_PROTOCOL,
+    BaseAddress: EFI_PHYSICAL_ADDRESS,
+    Length: UINT64,
+    Attributes: UINT64
+) -> EFI_STATUS;
+
+pub type EFI_CLEAR_MEMORY_ATTRIBUTES = extern "win64" fn(
+    This: *const EFI_MEMORY_ATTRIBUTE_PROTOCOL,
+    BaseAddress: EFI_PHYSICAL_ADDRESS,
+    Length: UINT64,
+    Attributes: UINT64
+) -> EFI_STATUS;
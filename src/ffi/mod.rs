@@ -16,6 +16,31 @@ pub mod tcp4;
 pub mod console;
 pub mod boot_services;
 pub mod runtime_services;
+pub mod security;
+pub mod shell;
+pub mod shell_parameters;
+pub mod nvme;
+pub mod scsi;
+pub mod ata;
+pub mod graphics;
+pub mod serial;
+pub mod timestamp;
+pub mod pointer;
+pub mod driver_binding;
+pub mod component_name;
+pub mod pci;
+pub mod usb_io;
+pub mod decompress;
+pub mod firmware_volume;
+pub mod tcg2;
+pub mod rng;
+pub mod hash2;
+pub mod pkcs7;
+pub mod security_arch;
+pub mod memory_attribute;
+pub mod performance;
+pub mod mp_services;
+pub mod hii;
 
 pub use self::base::*;
 use ffi::boot_services::EFI_BOOT_SERVICES;
@@ -59,6 +84,12 @@ pub struct EFI_CONFIGURATION_TABLE {
     pub VendorTable : *const ()
 }
 
+pub const EFI_ACPI_TABLE_GUID: EFI_GUID = EFI_GUID(0xeb9d2d30, 0x2d88, 0x11d3, [0x9a, 0x16, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d]);
+pub const EFI_ACPI_20_TABLE_GUID: EFI_GUID = EFI_GUID(0x8868e871, 0xe4f1, 0x11d3, [0xbc, 0x22, 0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81]);
+pub const SMBIOS_TABLE_GUID: EFI_GUID = EFI_GUID(0xeb9d2d31, 0x2d88, 0x11d3, [0x9a, 0x16, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d]);
+pub const SMBIOS3_TABLE_GUID: EFI_GUID = EFI_GUID(0xf2fd1544, 0x9794, 0x4a2c, [0x99, 0x2e, 0xe5, 0xbb, 0xcf, 0x20, 0xe3, 0x94]);
+pub const EFI_DTB_TABLE_GUID: EFI_GUID = EFI_GUID(0xb1b621d5, 0xf19c, 0x41a5, [0x83, 0x0b, 0xd9, 0x15, 0x2c, 0x69, 0xaa, 0xe0]);
+
 #[repr(C)]
 pub struct EFI_SERVICE_BINDING_PROTOCOL {
     pub CreateChild: EFI_SERVICE_BINDING_CREATE_CHILD,
@@ -0,0 +1,92 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, EFI_EVENT, BOOLEAN, UINTN, UINT32, UINT64, VOID};
+
+pub const EFI_MP_SERVICES_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x3fdda605, 0xa76e, 0x4f46, [0xad, 0x29, 0x12, 0xf4, 0x53, 0x1b, 0x3d, 0x08]);
+
+// Bits of EFI_PROCESSOR_INFORMATION.StatusFlag
+pub const PROCESSOR_AS_BSP_BIT: UINT32 = 0x00000001;
+pub const PROCESSOR_ENABLED_BIT: UINT32 = 0x00000002;
+pub const PROCESSOR_HEALTH_STATUS_BIT: UINT32 = 0x00000004;
+
+#[repr(C)]
+pub struct EFI_MP_SERVICES_PROTOCOL {
+    pub GetNumberOfProcessors: EFI_MP_SERVICES_GET_NUMBER_OF_PROCESSORS,
+    pub GetProcessorInfo: EFI_MP_SERVICES_GET_PROCESSOR_INFO,
+    pub StartupAllAPs: EFI_MP_SERVICES_STARTUP_ALL_APS,
+    pub StartupThisAP: EFI_MP_SERVICES_STARTUP_THIS_AP,
+    pub SwitchBSP: EFI_MP_SERVICES_SWITCH_BSP,
+    pub EnableDisableAP: EFI_MP_SERVICES_ENABLEDISABLEAP,
+    pub WhoAmI: EFI_MP_SERVICES_WHOAMI,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct EFI_CPU_PHYSICAL_LOCATION {
+    pub Package: UINT32,
+    pub Core: UINT32,
+    pub Thread: UINT32,
+}
+
+// The UEFI 2.7 ExtendedInformation field is left out. Firmware only fills it in when the processor
+// number passed to GetProcessorInfo() has CPU_V2_EXTENDED_TOPOLOGY set, which we never do.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct EFI_PROCESSOR_INFORMATION {
+    pub ProcessorId: UINT64,
+    pub StatusFlag: UINT32,
+    pub Location: EFI_CPU_PHYSICAL_LOCATION,
+}
+
+pub type EFI_AP_PROCEDURE = extern "win64" fn(
+    ProcedureArgument: *const VOID
+);
+
+pub type EFI_MP_SERVICES_GET_NUMBER_OF_PROCESSORS = extern "win64" fn(
+    This: *const EFI_MP_SERVICES_PROTOCOL,
+    NumberOfProcessors: *mut UINTN,
+    NumberOfEnabledProcessors: *mut UINTN
+) -> EFI_STATUS;
+
+pub type EFI_MP_SERVICES_GET_PROCESSOR_INFO = extern "win64" fn(
+    This: *const EFI_MP_SERVICES_PROTOCOL,
+    ProcessorNumber: UINTN,
+    ProcessorInfoBuffer: *mut EFI_PROCESSOR_INFORMATION
+) -> EFI_STATUS;
+
+// A null WaitEvent makes the call blocking. A TimeoutInMicroSeconds of zero means no timeout.
+pub type EFI_MP_SERVICES_STARTUP_ALL_APS = extern "win64" fn(
+    This: *const EFI_MP_SERVICES_PROTOCOL,
+    Procedure: EFI_AP_PROCEDURE,
+    SingleThread: BOOLEAN,
+    WaitEvent: EFI_EVENT,
+    TimeoutInMicroSeconds: UINTN,
+    ProcedureArgument: *const VOID,
+    FailedCpuList: *mut *mut UINTN
+) -> EFI_STATUS;
+
+pub type EFI_MP_SERVICES_STARTUP_THIS_AP = extern "win64" fn(
+    This: *const EFI_MP_SERVICES_PROTOCOL,
+    Procedure: EFI_AP_PROCEDURE,
+    ProcessorNumber: UINTN,
+    WaitEvent: EFI_EVENT,
+    TimeoutInMicroseconds: UINTN,
+    ProcedureArgument: *const VOID,
+    Finished: *mut BOOLEAN
+) -> EFI_STATUS;
+
+pub type EFI_MP_SERVICES_SWITCH_BSP = extern "win64" fn(
+    This: *const EFI_MP_SERVICES_PROTOCOL,
+    ProcessorNumber: UINTN,
+    EnableOldBSP: BOOLEAN
+) -> EFI_STATUS;
+
+pub type EFI_MP_SERVICES_ENABLEDISABLEAP = extern "win64" fn(
+    This: *const EFI_MP_SERVICES_PROTOCOL,
+    ProcessorNumber: UINTN,
+    EnableAP: BOOLEAN,
+    HealthFlag: *const UINT32
+) -> EFI_STATUS;
+
+pub type EFI_MP_SERVICES_WHOAMI = extern "win64" fn(
+    This: *const EFI_MP_SERVICES_PROTOCOL,
+    ProcessorNumber: *mut UINTN
+) -> EFI_STATUS;
@@ -0,0 +1,90 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, UINT8, UINT32, UINT64, UINTN, VOID, NOT_DEFINED};
+
+pub const EFI_PCI_IO_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x4CF5B200, 0x68B8, 0x4CA5, [0x9E, 0xEC, 0xB2, 0x3E, 0x3F, 0x50, 0x02, 0x9A]);
+
+#[repr(C)]
+pub struct EFI_PCI_IO_PROTOCOL {
+    pub PollMem: EFI_PCI_IO_PROTOCOL_POLL_IO_MEM,
+    pub PollIo: EFI_PCI_IO_PROTOCOL_POLL_IO_MEM,
+    pub Mem: EFI_PCI_IO_PROTOCOL_ACCESS,
+    pub Io: EFI_PCI_IO_PROTOCOL_ACCESS,
+    pub Pci: EFI_PCI_IO_PROTOCOL_CONFIG_ACCESS,
+    pub CopyMem: EFI_PCI_IO_PROTOCOL_COPY_MEM,
+    pub Map: EFI_PCI_IO_PROTOCOL_MAP,
+    pub Unmap: EFI_PCI_IO_PROTOCOL_UNMAP,
+    pub AllocateBuffer: EFI_PCI_IO_PROTOCOL_ALLOCATE_BUFFER,
+    pub FreeBuffer: EFI_PCI_IO_PROTOCOL_FREE_BUFFER,
+    pub Flush: EFI_PCI_IO_PROTOCOL_FLUSH,
+    pub GetLocation: EFI_PCI_IO_PROTOCOL_GET_LOCATION,
+    pub Attributes: EFI_PCI_IO_PROTOCOL_ATTRIBUTES,
+    pub GetBarAttributes: EFI_PCI_IO_PROTOCOL_GET_BAR_ATTRIBUTES,
+    pub SetBarAttributes: EFI_PCI_IO_PROTOCOL_SET_BAR_ATTRIBUTES,
+    pub RomSize: UINT64,
+    pub RomImage: *const VOID,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum EFI_PCI_IO_PROTOCOL_WIDTH {
+    EfiPciIoWidthUint8 = 0,
+    EfiPciIoWidthUint16,
+    EfiPciIoWidthUint32,
+    EfiPciIoWidthUint64,
+    EfiPciIoWidthFifoUint8,
+    EfiPciIoWidthFifoUint16,
+    EfiPciIoWidthFifoUint32,
+    EfiPciIoWidthFifoUint64,
+    EfiPciIoWidthFillUint8,
+    EfiPciIoWidthFillUint16,
+    EfiPciIoWidthFillUint32,
+    EfiPciIoWidthFillUint64,
+    EfiPciIoWidthMaximum,
+}
+
+pub type EFI_PCI_IO_PROTOCOL_IO_MEM = extern "win64" fn(
+    This: *const EFI_PCI_IO_PROTOCOL,
+    Width: EFI_PCI_IO_PROTOCOL_WIDTH,
+    BarIndex: UINT8,
+    Offset: UINT64,
+    Count: UINTN,
+    Buffer: *mut VOID
+) -> EFI_STATUS;
+
+#[repr(C)]
+pub struct EFI_PCI_IO_PROTOCOL_ACCESS {
+    pub Read: EFI_PCI_IO_PROTOCOL_IO_MEM,
+    pub Write: EFI_PCI_IO_PROTOCOL_IO_MEM,
+}
+
+pub type EFI_PCI_IO_PROTOCOL_CONFIG = extern "win64" fn(
+    This: *const EFI_PCI_IO_PROTOCOL,
+    Width: EFI_PCI_IO_PROTOCOL_WIDTH,
+    Offset: UINT32,
+    Count: UINTN,
+    Buffer: *mut VOID
+) -> EFI_STATUS;
+
+#[repr(C)]
+pub struct EFI_PCI_IO_PROTOCOL_CONFIG_ACCESS {
+    pub Read: EFI_PCI_IO_PROTOCOL_CONFIG,
+    pub Write: EFI_PCI_IO_PROTOCOL_CONFIG,
+}
+
+pub type EFI_PCI_IO_PROTOCOL_GET_LOCATION = extern "win64" fn(
+    This: *const EFI_PCI_IO_PROTOCOL,
+    SegmentNumber: *mut UINTN,
+    BusNumber: *mut UINTN,
+    DeviceNumber: *mut UINTN,
+    FunctionNumber: *mut UINTN
+) -> EFI_STATUS;
+
+pub type EFI_PCI_IO_PROTOCOL_POLL_IO_MEM = *const NOT_DEFINED;
+pub type EFI_PCI_IO_PROTOCOL_COPY_MEM = *const NOT_DEFINED;
+pub type EFI_PCI_IO_PROTOCOL_MAP = *const NOT_DEFINED;
+pub type EFI_PCI_IO_PROTOCOL_UNMAP = *const NOT_DEFINED;
+pub type EFI_PCI_IO_PROTOCOL_ALLOCATE_BUFFER = *const NOT_DEFINED;
+pub type EFI_PCI_IO_PROTOCOL_FREE_BUFFER = *const NOT_DEFINED;
+pub type EFI_PCI_IO_PROTOCOL_FLUSH = *const NOT_DEFINED;
+pub type EFI_PCI_IO_PROTOCOL_ATTRIBUTES = *const NOT_DEFINED;
+pub type EFI_PCI_IO_PROTOCOL_GET_BAR_ATTRIBUTES = *const NOT_DEFINED;
+pub type EFI_PCI_IO_PROTOCOL_SET_BAR_ATTRIBUTES = *const NOT_DEFINED;
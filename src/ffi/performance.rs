@@ -0,0 +1,78 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, CHAR8, UINT8, UINT16, UINT32, UINT64, VOID};
+
+// Firmware Performance Data Table (ACPI 6.x section 5.2.23) and the EDK II extensions to it
+
+pub const FPDT_RECORD_HEADER_SIZE: usize = 4;
+
+// Records in the FPDT itself
+pub const FPDT_FIRMWARE_BASIC_BOOT_POINTER_TYPE: UINT16 = 0x0000;
+pub const FPDT_S3_PERFORMANCE_TABLE_POINTER_TYPE: UINT16 = 0x0001;
+
+// Records in the Firmware Basic Boot Performance Table
+pub const FPDT_FIRMWARE_BASIC_BOOT_RECORD_TYPE: UINT16 = 0x0002;
+
+// EDK II extended records, also in the FBPT
+pub const FPDT_GUID_EVENT_TYPE: UINT16 = 0x1010;
+pub const FPDT_DYNAMIC_STRING_EVENT_TYPE: UINT16 = 0x1011;
+pub const FPDT_DUAL_GUID_STRING_EVENT_TYPE: UINT16 = 0x1012;
+pub const FPDT_GUID_QWORD_EVENT_TYPE: UINT16 = 0x1013;
+pub const FPDT_GUID_QWORD_STRING_EVENT_TYPE: UINT16 = 0x1014;
+
+pub const FPDT_FBPT_SIGNATURE: [UINT8; 4] = *b"FBPT";
+pub const FPDT_FBPT_HEADER_SIZE: usize = 8;
+
+// Progress identifiers for measurements made inside a module (PERF_INMODULE_START/END in EDK II)
+pub const PERF_INMODULE_START_ID: UINT32 = 0x40;
+pub const PERF_INMODULE_END_ID: UINT32 = 0x41;
+
+#[repr(C)]
+pub struct FPDT_RECORD_HEADER {
+    pub Type: UINT16,
+    pub Length: UINT8,
+    pub Revision: UINT8,
+}
+
+#[repr(C, packed)]
+pub struct FPDT_FIRMWARE_BASIC_BOOT_RECORD {
+    pub Header: FPDT_RECORD_HEADER,
+    pub Reserved: UINT32,
+    pub ResetEnd: UINT64,
+    pub OsLoaderLoadImageStart: UINT64,
+    pub OsLoaderStartImageStart: UINT64,
+    pub ExitBootServicesEntry: UINT64,
+    pub ExitBootServicesExit: UINT64,
+}
+
+#[repr(C, packed)]
+pub struct FPDT_DYNAMIC_STRING_EVENT_RECORD {
+    pub Header: FPDT_RECORD_HEADER,
+    pub ProgressID: UINT16,
+    pub ApicID: UINT32,
+    pub Timestamp: UINT64,
+    pub Guid: EFI_GUID,
+    // Followed by a null terminated ASCII string
+}
+
+pub const EDKII_PERFORMANCE_MEASUREMENT_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xc85d06be, 0x5f75, 0x48ce, [0xa8, 0x0f, 0x12, 0x36, 0xba, 0x3b, 0x87, 0xb1]);
+
+#[repr(C)]
+pub enum PERF_MEASUREMENT_ATTRIBUTE {
+    PerfStartEntry,
+    PerfEndEntry,
+    PerfEntry,
+}
+
+#[repr(C)]
+pub struct EDKII_PERFORMANCE_MEASUREMENT_PROTOCOL {
+    pub CreatePerformanceMeasurement: CREATE_PERFORMANCE_MEASUREMENT,
+}
+
+pub type CREATE_PERFORMANCE_MEASUREMENT = extern "win64" fn(
+    CallerIdentifier: *const VOID,
+    Guid: *const VOID,
+    String: *const CHAR8,
+    TimeStamp: UINT64,
+    Address: UINT64,
+    Identifier: UINT32,
+    Attribute: PERF_MEASUREMENT_ATTRIBUTE
+) -> EFI_STATUS;
@@ -0,0 +1,35 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, UINTN, VOID};
+use ffi::security::EFI_SIGNATURE_LIST;
+
+pub const EFI_PKCS7_VERIFY_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x47889fb2, 0xd671, 0x4fab, [0xa0, 0xca, 0xdf, 0x0e, 0x44, 0xdf, 0x70, 0xd6]);
+
+#[repr(C)]
+pub struct EFI_PKCS7_VERIFY_PROTOCOL {
+    pub VerifyBuffer: EFI_PKCS7_VERIFY_BUFFER,
+    pub VerifySignature: EFI_PKCS7_VERIFY_SIGNATURE,
+}
+
+// The signature databases are NULL terminated arrays of pointers to signature lists
+pub type EFI_PKCS7_VERIFY_BUFFER = extern "win64" fn(
+    This: *const EFI_PKCS7_VERIFY_PROTOCOL,
+    SignedData: *const VOID,
+    SignedDataSize: UINTN,
+    InData: *const VOID,
+    InDataSize: UINTN,
+    AllowedDb: *const *const EFI_SIGNATURE_LIST,
+    RevokedDb: *const *const EFI_SIGNATURE_LIST,
+    TimeStampDb: *const *const EFI_SIGNATURE_LIST,
+    Content: *mut VOID,
+    ContentSize: *mut UINTN
+) -> EFI_STATUS;
+
+pub type EFI_PKCS7_VERIFY_SIGNATURE = extern "win64" fn(
+    This: *const EFI_PKCS7_VERIFY_PROTOCOL,
+    Signature: *const VOID,
+    SignatureSize: UINTN,
+    InHash: *const VOID,
+    InHashSize: UINTN,
+    AllowedDb: *const *const EFI_SIGNATURE_LIST,
+    RevokedDb: *const *const EFI_SIGNATURE_LIST,
+    TimeStampDb: *const *const EFI_SIGNATURE_LIST
+) -> EFI_STATUS;
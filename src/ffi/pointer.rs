@@ -0,0 +1,92 @@
+use ffi::{
+    base::{EFI_GUID, EFI_STATUS, EFI_EVENT, BOOLEAN, INT32, UINT32, UINT64},
+};
+
+pub const EFI_SIMPLE_POINTER_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x31878C87, 0x0B75, 0x11D5, [0x9A, 0x4F, 0x00, 0x90, 0x27, 0x3F, 0xC1, 0x4D]);
+
+#[repr(C)]
+pub struct EFI_SIMPLE_POINTER_PROTOCOL {
+    pub Reset: EFI_SIMPLE_POINTER_RESET,
+    pub GetState: EFI_SIMPLE_POINTER_GET_STATE,
+    pub WaitForInput: EFI_EVENT,
+    pub Mode: *const EFI_SIMPLE_POINTER_MODE,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_SIMPLE_POINTER_MODE {
+    pub ResolutionX: UINT64,
+    pub ResolutionY: UINT64,
+    pub ResolutionZ: UINT64,
+    pub LeftButton: BOOLEAN,
+    pub RightButton: BOOLEAN,
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+pub struct EFI_SIMPLE_POINTER_STATE {
+    pub RelativeMovementX: INT32,
+    pub RelativeMovementY: INT32,
+    pub RelativeMovementZ: INT32,
+    pub LeftButton: BOOLEAN,
+    pub RightButton: BOOLEAN,
+}
+
+pub type EFI_SIMPLE_POINTER_RESET = extern "win64" fn(
+    This: *const EFI_SIMPLE_POINTER_PROTOCOL,
+    ExtendedVerification: BOOLEAN
+) -> EFI_STATUS;
+
+pub type EFI_SIMPLE_POINTER_GET_STATE = extern "win64" fn(
+    This: *const EFI_SIMPLE_POINTER_PROTOCOL,
+    State: *mut EFI_SIMPLE_POINTER_STATE
+) -> EFI_STATUS;
+
+pub const EFI_ABSOLUTE_POINTER_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x8D59D32B, 0xC655, 0x4AE9, [0x9B, 0x15, 0xF2, 0x59, 0x04, 0x99, 0x2A, 0x43]);
+
+#[repr(C)]
+pub struct EFI_ABSOLUTE_POINTER_PROTOCOL {
+    pub Reset: EFI_ABSOLUTE_POINTER_RESET,
+    pub GetState: EFI_ABSOLUTE_POINTER_GET_STATE,
+    pub WaitForInput: EFI_EVENT,
+    pub Mode: *const EFI_ABSOLUTE_POINTER_MODE,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_ABSOLUTE_POINTER_MODE {
+    pub AbsoluteMinX: UINT64,
+    pub AbsoluteMinY: UINT64,
+    pub AbsoluteMinZ: UINT64,
+    pub AbsoluteMaxX: UINT64,
+    pub AbsoluteMaxY: UINT64,
+    pub AbsoluteMaxZ: UINT64,
+    pub Attributes: UINT32,
+}
+
+// Bits of EFI_ABSOLUTE_POINTER_MODE.Attributes
+pub const EFI_ABSP_SUPPORTS_ALT_ACTIVE: UINT32 = 0x00000001;
+pub const EFI_ABSP_SUPPORTS_PRESSURE_AS_Z: UINT32 = 0x00000002;
+
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+pub struct EFI_ABSOLUTE_POINTER_STATE {
+    pub CurrentX: UINT64,
+    pub CurrentY: UINT64,
+    pub CurrentZ: UINT64,
+    pub ActiveButtons: UINT32,
+}
+
+// Bits of EFI_ABSOLUTE_POINTER_STATE.ActiveButtons
+pub const EFI_ABSP_TOUCH_ACTIVE: UINT32 = 0x00000001;
+pub const EFI_ABS_ALT_ACTIVE: UINT32 = 0x00000002;
+
+pub type EFI_ABSOLUTE_POINTER_RESET = extern "win64" fn(
+    This: *const EFI_ABSOLUTE_POINTER_PROTOCOL,
+    ExtendedVerification: BOOLEAN
+) -> EFI_STATUS;
+
+pub type EFI_ABSOLUTE_POINTER_GET_STATE = extern "win64" fn(
+    This: *const EFI_ABSOLUTE_POINTER_PROTOCOL,
+    State: *mut EFI_ABSOLUTE_POINTER_STATE
+) -> EFI_STATUS;
@@ -465,4 +465,44 @@ pub type EFI_PXE_BASE_CODE_SET_PACKETS = extern "win64" fn(
     NewPxeDiscover: *const EFI_PXE_BASE_CODE_PACKET,
     NewPxeReply: *const EFI_PXE_BASE_CODE_PACKET,
     NewPxeBisReply: *const EFI_PXE_BASE_CODE_PACKET
-) -> EFI_STATUS;
\ No newline at end of file
+) -> EFI_STATUS;
+pub const EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x245DCA21, 0xFB7B, 0x11D3, [0x8F, 0x01, 0x00, 0xA0, 0xC9, 0x69, 0x72, 0x3B]);
+
+pub const EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL_REVISION: UINT64 = 0x00010000;
+
+#[repr(C)]
+pub struct EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL {
+    pub Revision: UINT64,
+    pub Callback: EFI_PXE_CALLBACK,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub enum EFI_PXE_BASE_CODE_FUNCTION {
+    EFI_PXE_BASE_CODE_FUNCTION_FIRST,
+    EFI_PXE_BASE_CODE_FUNCTION_DHCP,
+    EFI_PXE_BASE_CODE_FUNCTION_DISCOVER,
+    EFI_PXE_BASE_CODE_FUNCTION_MTFTP,
+    EFI_PXE_BASE_CODE_FUNCTION_UDP_WRITE,
+    EFI_PXE_BASE_CODE_FUNCTION_UDP_READ,
+    EFI_PXE_BASE_CODE_FUNCTION_ARP,
+    EFI_PXE_BASE_CODE_FUNCTION_IGMP,
+    EFI_PXE_BASE_CODE_PXE_FUNCTION_LAST
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub enum EFI_PXE_BASE_CODE_CALLBACK_STATUS {
+    EFI_PXE_BASE_CODE_CALLBACK_STATUS_FIRST,
+    EFI_PXE_BASE_CODE_CALLBACK_STATUS_CONTINUE,
+    EFI_PXE_BASE_CODE_CALLBACK_STATUS_ABORT,
+    EFI_PXE_BASE_CODE_CALLBACK_STATUS_LAST
+}
+
+pub type EFI_PXE_CALLBACK = extern "win64" fn(
+    This: *const EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL,
+    Function: EFI_PXE_BASE_CODE_FUNCTION,
+    Received: BOOLEAN,
+    PacketLen: UINT32,
+    Packet: *const EFI_PXE_BASE_CODE_PACKET
+) -> EFI_PXE_BASE_CODE_CALLBACK_STATUS;
@@ -0,0 +1,31 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, UINTN, UINT8};
+
+pub const EFI_RNG_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x3152bca5, 0xeade, 0x433d, [0x86, 0x2e, 0xc0, 0x1c, 0xdc, 0x29, 0x1f, 0x44]);
+
+pub type EFI_RNG_ALGORITHM = EFI_GUID;
+
+pub const EFI_RNG_ALGORITHM_SP800_90_HASH_256_GUID: EFI_GUID = EFI_GUID(0xa7af67cb, 0x603b, 0x4d42, [0xba, 0x21, 0x70, 0xbf, 0xb6, 0x29, 0x3f, 0x96]);
+pub const EFI_RNG_ALGORITHM_SP800_90_HMAC_256_GUID: EFI_GUID = EFI_GUID(0xc5149b43, 0xae85, 0x4f53, [0x99, 0x82, 0xb9, 0x43, 0x35, 0xd3, 0xa9, 0xe7]);
+pub const EFI_RNG_ALGORITHM_SP800_90_CTR_256_GUID: EFI_GUID = EFI_GUID(0x44f0de6e, 0x4d8c, 0x4045, [0xa8, 0xc7, 0x4d, 0xd1, 0x68, 0x85, 0x6b, 0x9e]);
+pub const EFI_RNG_ALGORITHM_X9_31_3DES_GUID: EFI_GUID = EFI_GUID(0x63c4785a, 0xca34, 0x4012, [0xa3, 0xc8, 0x0b, 0x6a, 0x32, 0x4f, 0x55, 0x46]);
+pub const EFI_RNG_ALGORITHM_X9_31_AES_GUID: EFI_GUID = EFI_GUID(0xacd03321, 0x777e, 0x4d3d, [0xb1, 0xc8, 0x20, 0xcf, 0xd8, 0x88, 0x20, 0xc9]);
+pub const EFI_RNG_ALGORITHM_RAW: EFI_GUID = EFI_GUID(0xe43176d7, 0xb6e8, 0x4827, [0xb7, 0x84, 0x7f, 0xfd, 0xc4, 0xb6, 0x85, 0x61]);
+
+#[repr(C)]
+pub struct EFI_RNG_PROTOCOL {
+    pub GetInfo: EFI_RNG_GET_INFO,
+    pub GetRNG: EFI_RNG_GET_RNG,
+}
+
+pub type EFI_RNG_GET_INFO = extern "win64" fn(
+    This: *const EFI_RNG_PROTOCOL,
+    RNGAlgorithmListSize: *mut UINTN,
+    RNGAlgorithmList: *mut EFI_RNG_ALGORITHM
+) -> EFI_STATUS;
+
+pub type EFI_RNG_GET_RNG = extern "win64" fn(
+    This: *const EFI_RNG_PROTOCOL,
+    RNGAlgorithm: *const EFI_RNG_ALGORITHM,
+    RNGValueLength: UINTN,
+    RNGValue: *mut UINT8
+) -> EFI_STATUS;
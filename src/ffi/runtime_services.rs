@@ -1,5 +1,6 @@
 use ffi::{
-    base::{EFI_STATUS, EFI_TIME, EFI_TIME_CAPABILITIES, EFI_TABLE_HEADER, UINTN, NOT_DEFINED},
+    boot_services::EFI_MEMORY_DESCRIPTOR,
+    base::{EFI_STATUS, EFI_GUID, EFI_TIME, EFI_TIME_CAPABILITIES, EFI_TABLE_HEADER, BOOLEAN, CHAR16, UINT32, UINT64, UINTN, VOID, NOT_DEFINED},
     EFI_SPECIFICATION_VERSION,
 };
 
@@ -33,20 +34,103 @@ pub struct EFI_RUNTIME_SERVICES {
 
 pub type EFI_RAISE_TPL = *const NOT_DEFINED;
 pub type EFI_SET_TIME = *const NOT_DEFINED;
-pub type EFI_GET_WAKEUP_TIME = *const NOT_DEFINED;
-pub type EFI_SET_WAKEUP_TIME = *const NOT_DEFINED;
-pub type EFI_SET_VIRTUAL_ADDRESS_MAP = *const NOT_DEFINED;
-pub type EFI_CONVERT_POINTER = *const NOT_DEFINED;
-pub type EFI_GET_VARIABLE = *const NOT_DEFINED;
-pub type EFI_GET_NEXT_VARIABLE_NAME = *const NOT_DEFINED;
-pub type EFI_SET_VARIABLE = *const NOT_DEFINED;
-pub type EFI_GET_NEXT_HIGH_MONO_COUNT = *const NOT_DEFINED;
-pub type EFI_RESET_SYSTEM = *const NOT_DEFINED;
 pub type EFI_UPDATE_CAPSULE = *const NOT_DEFINED;
 pub type EFI_QUERY_CAPSULE_CAPABILITIES = *const NOT_DEFINED;
-pub type EFI_QUERY_VARIABLE_INFO = *const NOT_DEFINED;
+
+pub type EFI_GET_WAKEUP_TIME = extern "win64" fn(
+    Enabled: *mut BOOLEAN,
+    Pending: *mut BOOLEAN,
+    Time: *mut EFI_TIME
+) -> EFI_STATUS;
+
+pub type EFI_SET_WAKEUP_TIME = extern "win64" fn(
+    Enable: BOOLEAN,
+    Time: *const EFI_TIME
+) -> EFI_STATUS;
+
+pub type EFI_SET_VIRTUAL_ADDRESS_MAP = extern "win64" fn(
+    MemoryMapSize: UINTN,
+    DescriptorSize: UINTN,
+    DescriptorVersion: UINT32,
+    VirtualMap: *const EFI_MEMORY_DESCRIPTOR
+) -> EFI_STATUS;
+
+pub const EFI_OPTIONAL_PTR: UINTN = 0x00000001;
+
+pub type EFI_CONVERT_POINTER = extern "win64" fn(
+    DebugDisposition: UINTN,
+    Address: *mut *const VOID
+) -> EFI_STATUS;
 
 pub type EFI_GET_TIME = extern "win64" fn(
     Time: *mut EFI_TIME,
     Capabilities: *mut EFI_TIME_CAPABILITIES
-) -> EFI_STATUS;
\ No newline at end of file
+) -> EFI_STATUS;
+
+pub type EFI_GET_NEXT_HIGH_MONO_COUNT = extern "win64" fn(
+    HighCount: *mut UINT32
+) -> EFI_STATUS;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub enum EFI_RESET_TYPE {
+    EfiResetCold,
+    EfiResetWarm,
+    EfiResetShutdown,
+    EfiResetPlatformSpecific
+}
+
+pub type EFI_RESET_SYSTEM = extern "win64" fn(
+    ResetType: EFI_RESET_TYPE,
+    ResetStatus: EFI_STATUS,
+    DataSize: UINTN,
+    ResetData: *const VOID
+);
+
+pub const EFI_GLOBAL_VARIABLE: EFI_GUID = EFI_GUID(0x8BE4DF61, 0x93CA, 0x11d2, [0xAA, 0x0D, 0x00, 0xE0, 0x98, 0x03, 0x2B, 0x8C]);
+
+pub const EFI_VARIABLE_NON_VOLATILE: UINT32 = 0x00000001;
+pub const EFI_VARIABLE_BOOTSERVICE_ACCESS: UINT32 = 0x00000002;
+pub const EFI_VARIABLE_RUNTIME_ACCESS: UINT32 = 0x00000004;
+pub const EFI_VARIABLE_HARDWARE_ERROR_RECORD: UINT32 = 0x00000008;
+pub const EFI_VARIABLE_AUTHENTICATED_WRITE_ACCESS: UINT32 = 0x00000010;
+pub const EFI_VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS: UINT32 = 0x00000020;
+pub const EFI_VARIABLE_APPEND_WRITE: UINT32 = 0x00000040;
+
+pub type EFI_GET_VARIABLE = extern "win64" fn(
+    VariableName: *const CHAR16,
+    VendorGuid: *const EFI_GUID,
+    Attributes: *mut UINT32,
+    DataSize: *mut UINTN,
+    Data: *mut VOID
+) -> EFI_STATUS;
+
+pub type EFI_GET_NEXT_VARIABLE_NAME = extern "win64" fn(
+    VariableNameSize: *mut UINTN,
+    VariableName: *mut CHAR16,
+    VendorGuid: *mut EFI_GUID
+) -> EFI_STATUS;
+
+pub type EFI_SET_VARIABLE = extern "win64" fn(
+    VariableName: *const CHAR16,
+    VendorGuid: *const EFI_GUID,
+    Attributes: UINT32,
+    DataSize: UINTN,
+    Data: *const VOID
+) -> EFI_STATUS;
+
+pub type EFI_QUERY_VARIABLE_INFO = extern "win64" fn(
+    Attributes: UINT32,
+    MaximumVariableStorageSize: *mut UINT64,
+    RemainingVariableStorageSize: *mut UINT64,
+    MaximumVariableSize: *mut UINT64
+) -> EFI_STATUS;
+
+pub const EFI_OS_INDICATIONS_BOOT_TO_FW_UI: UINT64 = 0x0000000000000001;
+pub const EFI_OS_INDICATIONS_TIMESTAMP_REVOCATION: UINT64 = 0x0000000000000002;
+pub const EFI_OS_INDICATIONS_FILE_CAPSULE_DELIVERY_SUPPORTED: UINT64 = 0x0000000000000004;
+pub const EFI_OS_INDICATIONS_FMP_CAPSULE_SUPPORTED: UINT64 = 0x0000000000000008;
+pub const EFI_OS_INDICATIONS_CAPSULE_RESULT_VAR_SUPPORTED: UINT64 = 0x0000000000000010;
+pub const EFI_OS_INDICATIONS_START_OS_RECOVERY: UINT64 = 0x0000000000000020;
+pub const EFI_OS_INDICATIONS_START_PLATFORM_RECOVERY: UINT64 = 0x0000000000000040;
+pub const EFI_OS_INDICATIONS_JSON_CONFIG_DATA_REFRESH: UINT64 = 0x0000000000000080;
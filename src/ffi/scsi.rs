@@ -0,0 +1,100 @@
+use ffi::{
+    base::{EFI_GUID, EFI_STATUS, EFI_EVENT, UINT8, UINT32, UINT64, VOID},
+    device_path::EFI_DEVICE_PATH_PROTOCOL,
+};
+
+pub const EFI_EXT_SCSI_PASS_THRU_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x143B7632, 0xB81B, 0x4CB7, [0xAB, 0xD3, 0xB6, 0x25, 0xA5, 0xB9, 0xBF, 0xFE]);
+
+pub const TARGET_MAX_BYTES: usize = 0x10;
+
+pub const EFI_EXT_SCSI_PASS_THRU_ATTRIBUTES_PHYSICAL: UINT32 = 0x0001;
+pub const EFI_EXT_SCSI_PASS_THRU_ATTRIBUTES_LOGICAL: UINT32 = 0x0002;
+pub const EFI_EXT_SCSI_PASS_THRU_ATTRIBUTES_NONBLOCKIO: UINT32 = 0x0004;
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_EXT_SCSI_PASS_THRU_MODE {
+    pub AdapterId: UINT32,
+    pub Attributes: UINT32,
+    pub IoAlign: UINT32,
+}
+
+#[repr(C)]
+pub struct EFI_EXT_SCSI_PASS_THRU_PROTOCOL {
+    pub Mode: *const EFI_EXT_SCSI_PASS_THRU_MODE,
+    pub PassThru: EFI_EXT_SCSI_PASS_THRU_PASSTHRU,
+    pub GetNextTargetLun: EFI_EXT_SCSI_PASS_THRU_GET_NEXT_TARGET_LUN,
+    pub BuildDevicePath: EFI_EXT_SCSI_PASS_THRU_BUILD_DEVICE_PATH,
+    pub GetTargetLun: EFI_EXT_SCSI_PASS_THRU_GET_TARGET_LUN,
+    pub ResetChannel: EFI_EXT_SCSI_PASS_THRU_RESET_CHANNEL,
+    pub ResetTargetLun: EFI_EXT_SCSI_PASS_THRU_RESET_TARGET_LUN,
+    pub GetNextTarget: EFI_EXT_SCSI_PASS_THRU_GET_NEXT_TARGET,
+}
+
+pub const EFI_EXT_SCSI_DATA_DIRECTION_READ: UINT8 = 0;
+pub const EFI_EXT_SCSI_DATA_DIRECTION_WRITE: UINT8 = 1;
+pub const EFI_EXT_SCSI_DATA_DIRECTION_BIDIRECTIONAL: UINT8 = 2;
+
+pub const EFI_EXT_SCSI_STATUS_HOST_ADAPTER_OK: UINT8 = 0x00;
+pub const EFI_EXT_SCSI_STATUS_TARGET_GOOD: UINT8 = 0x00;
+pub const EFI_EXT_SCSI_STATUS_TARGET_CHECK_CONDITION: UINT8 = 0x02;
+
+#[repr(C)]
+pub struct EFI_EXT_SCSI_PASS_THRU_SCSI_REQUEST_PACKET {
+    /// In units of 100ns. 0 means wait indefinitely.
+    pub Timeout: UINT64,
+    pub InDataBuffer: *mut VOID,
+    pub OutDataBuffer: *mut VOID,
+    pub SenseData: *mut VOID,
+    pub Cdb: *mut VOID,
+    pub InTransferLength: UINT32,
+    pub OutTransferLength: UINT32,
+    pub CdbLength: UINT8,
+    pub DataDirection: UINT8,
+    pub HostAdapterStatus: UINT8,
+    pub TargetStatus: UINT8,
+    pub SenseDataLength: UINT8,
+}
+
+pub type EFI_EXT_SCSI_PASS_THRU_PASSTHRU = extern "win64" fn(
+    This: *const EFI_EXT_SCSI_PASS_THRU_PROTOCOL,
+    Target: *const UINT8,
+    Lun: UINT64,
+    Packet: *mut EFI_EXT_SCSI_PASS_THRU_SCSI_REQUEST_PACKET,
+    Event: EFI_EVENT
+) -> EFI_STATUS;
+
+pub type EFI_EXT_SCSI_PASS_THRU_GET_NEXT_TARGET_LUN = extern "win64" fn(
+    This: *const EFI_EXT_SCSI_PASS_THRU_PROTOCOL,
+    Target: *mut *mut UINT8,
+    Lun: *mut UINT64
+) -> EFI_STATUS;
+
+pub type EFI_EXT_SCSI_PASS_THRU_BUILD_DEVICE_PATH = extern "win64" fn(
+    This: *const EFI_EXT_SCSI_PASS_THRU_PROTOCOL,
+    Target: *const UINT8,
+    Lun: UINT64,
+    DevicePath: *mut *const EFI_DEVICE_PATH_PROTOCOL
+) -> EFI_STATUS;
+
+pub type EFI_EXT_SCSI_PASS_THRU_GET_TARGET_LUN = extern "win64" fn(
+    This: *const EFI_EXT_SCSI_PASS_THRU_PROTOCOL,
+    DevicePath: *const EFI_DEVICE_PATH_PROTOCOL,
+    Target: *mut *mut UINT8,
+    Lun: *mut UINT64
+) -> EFI_STATUS;
+
+pub type EFI_EXT_SCSI_PASS_THRU_RESET_CHANNEL = extern "win64" fn(
+    This: *const EFI_EXT_SCSI_PASS_THRU_PROTOCOL
+) -> EFI_STATUS;
+
+pub type EFI_EXT_SCSI_PASS_THRU_RESET_TARGET_LUN = extern "win64" fn(
+    This: *const EFI_EXT_SCSI_PASS_THRU_PROTOCOL,
+    Target: *const UINT8,
+    Lun: UINT64
+) -> EFI_STATUS;
+
+pub type EFI_EXT_SCSI_PASS_THRU_GET_NEXT_TARGET = extern "win64" fn(
+    This: *const EFI_EXT_SCSI_PASS_THRU_PROTOCOL,
+    Target: *mut *mut UINT8
+) -> EFI_STATUS;
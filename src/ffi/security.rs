@@ -0,0 +1,72 @@
+use ffi::base::{EFI_GUID, EFI_TIME, UINT8, UINT16, UINT32};
+
+pub const EFI_IMAGE_SECURITY_DATABASE_GUID: EFI_GUID = EFI_GUID(0xd719b2cb, 0x3d3a, 0x4596, [0xa3, 0xbc, 0xda, 0xd0, 0x0e, 0x67, 0x65, 0x6f]);
+
+/// Vendor GUID of shim's variables (MokList, MokNew etc.) and owner of the keys it enrolls
+pub const SHIM_LOCK_GUID: EFI_GUID = EFI_GUID(0x605dab50, 0xe046, 0x4300, [0xab, 0xb6, 0x3d, 0xd8, 0x10, 0xdd, 0x8b, 0x23]);
+
+/// Vendor GUID of the TlsCaCertificate variable HTTP boot takes its trust anchors from
+pub const EFI_TLS_CA_CERTIFICATE_GUID: EFI_GUID = EFI_GUID(0xfd2340d0, 0x3dab, 0x4349, [0xa6, 0xc7, 0x3b, 0x4f, 0x12, 0xb4, 0x8e, 0xae]);
+
+pub const EFI_CERT_SHA1_GUID: EFI_GUID = EFI_GUID(0x826ca512, 0xcf10, 0x4ac9, [0xb1, 0x87, 0xbe, 0x01, 0x49, 0x66, 0x31, 0xbd]);
+pub const EFI_CERT_SHA256_GUID: EFI_GUID = EFI_GUID(0xc1c41626, 0x504c, 0x4092, [0xac, 0xa9, 0x41, 0xf9, 0x36, 0x93, 0x43, 0x28]);
+pub const EFI_CERT_SHA384_GUID: EFI_GUID = EFI_GUID(0xff3e5307, 0x9fd0, 0x48c9, [0x85, 0xf1, 0x8a, 0xd5, 0x6c, 0x70, 0x1e, 0x01]);
+pub const EFI_CERT_SHA512_GUID: EFI_GUID = EFI_GUID(0x093e0fae, 0xa6c4, 0x4f50, [0x9f, 0x1b, 0xd4, 0x1e, 0x2b, 0x89, 0xc1, 0x9a]);
+pub const EFI_CERT_RSA2048_GUID: EFI_GUID = EFI_GUID(0x3c5766e8, 0x269c, 0x4e34, [0xaa, 0x14, 0xed, 0x77, 0x6e, 0x85, 0xb3, 0xb6]);
+pub const EFI_CERT_X509_GUID: EFI_GUID = EFI_GUID(0xa5c059a1, 0x94e4, 0x4aa7, [0x87, 0xb5, 0xab, 0x15, 0x5c, 0x2b, 0xf0, 0x72]);
+pub const EFI_CERT_X509_SHA256_GUID: EFI_GUID = EFI_GUID(0x3bd2a492, 0x96c0, 0x4079, [0xb4, 0x20, 0xfc, 0xf9, 0x8e, 0xf1, 0x03, 0xed]);
+pub const EFI_CERT_X509_SHA384_GUID: EFI_GUID = EFI_GUID(0x7076876e, 0x80c2, 0x4ee6, [0xaa, 0xd2, 0x28, 0xb3, 0x49, 0xa6, 0x86, 0x5b]);
+pub const EFI_CERT_X509_SHA512_GUID: EFI_GUID = EFI_GUID(0x446dbf63, 0x2502, 0x4cda, [0xbc, 0xfa, 0x22, 0x65, 0xd2, 0x36, 0xc8, 0x0f]);
+pub const EFI_CERT_TYPE_PKCS7_GUID: EFI_GUID = EFI_GUID(0x4aafd29d, 0x68df, 0x49ee, [0x8a, 0xa9, 0x34, 0x7d, 0x37, 0x56, 0x65, 0xa7]);
+
+/// The header of a list of signatures all of the same type.
+/// It is followed by SignatureHeaderSize bytes of header and then an array of EFI_SIGNATURE_DATA
+/// each of which is SignatureSize bytes long.
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_SIGNATURE_LIST {
+    pub SignatureType: EFI_GUID,
+    pub SignatureListSize: UINT32,
+    pub SignatureHeaderSize: UINT32,
+    pub SignatureSize: UINT32,
+    // UINT8 SignatureHeader[SignatureHeaderSize];
+    // EFI_SIGNATURE_DATA Signatures[][SignatureSize];
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_SIGNATURE_DATA {
+    pub SignatureOwner: EFI_GUID,
+    pub SignatureData: [u8; 1], // Dynamically sized
+}
+
+pub const WIN_CERT_TYPE_PKCS_SIGNED_DATA: UINT16 = 0x0002;
+pub const WIN_CERT_TYPE_EFI_PKCS115: UINT16 = 0x0EF0;
+pub const WIN_CERT_TYPE_EFI_GUID: UINT16 = 0x0EF1;
+
+pub const WIN_CERT_REVISION_2_0: UINT16 = 0x0200;
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct WIN_CERTIFICATE {
+    pub dwLength: UINT32,
+    pub wRevision: UINT16,
+    pub wCertificateType: UINT16,
+    // UINT8 bCertificate[ANYSIZE_ARRAY];
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct WIN_CERTIFICATE_UEFI_GUID {
+    pub Hdr: WIN_CERTIFICATE,
+    pub CertType: EFI_GUID,
+    pub CertData: [UINT8; 1], // Dynamically sized
+}
+
+/// Prefixed to the data of a time-based authenticated variable write
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_VARIABLE_AUTHENTICATION_2 {
+    pub TimeStamp: EFI_TIME,
+    pub AuthInfo: WIN_CERTIFICATE_UEFI_GUID,
+}
@@ -0,0 +1,19 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, UINTN, BOOLEAN, VOID};
+use ffi::device_path::EFI_DEVICE_PATH_PROTOCOL;
+
+pub const EFI_SECURITY2_ARCH_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x94ab2f58, 0x1438, 0x4ef1, [0x91, 0x52, 0x18, 0x94, 0x1a, 0x3a, 0x0e, 0x68]);
+
+/// Consulted by the DXE core for every image it loads (and for every file a boot option points at)
+#[repr(C)]
+pub struct EFI_SECURITY2_ARCH_PROTOCOL {
+    pub FileAuthentication: EFI_SECURITY2_FILE_AUTHENTICATION,
+}
+
+// Either DevicePath or FileBuffer may be NULL but not both
+pub type EFI_SECURITY2_FILE_AUTHENTICATION = extern "win64" fn(
+    This: *const EFI_SECURITY2_ARCH_PROTOCOL,
+    DevicePath: *const EFI_DEVICE_PATH_PROTOCOL,
+    FileBuffer: *const VOID,
+    FileSize: UINTN,
+    BootPolicy: BOOLEAN
+) -> EFI_STATUS;
@@ -0,0 +1,100 @@
+use ffi::{
+    base::{EFI_GUID, EFI_STATUS, UINT8, UINT32, UINT64, UINTN, VOID},
+};
+
+pub const EFI_SERIAL_IO_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xBB25CF6F, 0xF1D4, 0x11D2, [0x9A, 0x0C, 0x00, 0x90, 0x27, 0x3F, 0xC1, 0xFD]);
+
+pub const EFI_SERIAL_IO_PROTOCOL_REVISION: UINT32 = 0x00010000;
+pub const EFI_SERIAL_IO_PROTOCOL_REVISION1P1: UINT32 = 0x00010001;
+
+#[repr(C)]
+pub struct EFI_SERIAL_IO_PROTOCOL {
+    pub Revision: UINT32,
+    pub Reset: EFI_SERIAL_RESET,
+    pub SetAttributes: EFI_SERIAL_SET_ATTRIBUTES,
+    pub SetControl: EFI_SERIAL_SET_CONTROL_BITS,
+    pub GetControl: EFI_SERIAL_GET_CONTROL_BITS,
+    pub Write: EFI_SERIAL_WRITE,
+    pub Read: EFI_SERIAL_READ,
+    pub Mode: *const SERIAL_IO_MODE,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct SERIAL_IO_MODE {
+    pub ControlMask: UINT32,
+    pub Timeout: UINT32,
+    pub BaudRate: UINT64,
+    pub ReceiveFifoDepth: UINT32,
+    pub DataBits: UINT32,
+    pub Parity: UINT32,
+    pub StopBits: UINT32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum EFI_PARITY_TYPE {
+    DefaultParity,
+    NoParity,
+    EvenParity,
+    OddParity,
+    MarkParity,
+    SpaceParity,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum EFI_STOP_BITS_TYPE {
+    DefaultStopBits,
+    OneStopBit,
+    OneFiveStopBits,
+    TwoStopBits,
+}
+
+pub const EFI_SERIAL_CLEAR_TO_SEND: UINT32 = 0x0010;
+pub const EFI_SERIAL_DATA_SET_READY: UINT32 = 0x0020;
+pub const EFI_SERIAL_RING_INDICATE: UINT32 = 0x0040;
+pub const EFI_SERIAL_CARRIER_DETECT: UINT32 = 0x0080;
+pub const EFI_SERIAL_REQUEST_TO_SEND: UINT32 = 0x0002;
+pub const EFI_SERIAL_DATA_TERMINAL_READY: UINT32 = 0x0001;
+pub const EFI_SERIAL_INPUT_BUFFER_EMPTY: UINT32 = 0x0100;
+pub const EFI_SERIAL_OUTPUT_BUFFER_EMPTY: UINT32 = 0x0200;
+pub const EFI_SERIAL_HARDWARE_LOOPBACK_ENABLE: UINT32 = 0x1000;
+pub const EFI_SERIAL_SOFTWARE_LOOPBACK_ENABLE: UINT32 = 0x2000;
+pub const EFI_SERIAL_HARDWARE_FLOW_CONTROL_ENABLE: UINT32 = 0x4000;
+
+pub type EFI_SERIAL_RESET = extern "win64" fn(
+    This: *mut EFI_SERIAL_IO_PROTOCOL
+) -> EFI_STATUS;
+
+pub type EFI_SERIAL_SET_ATTRIBUTES = extern "win64" fn(
+    This: *mut EFI_SERIAL_IO_PROTOCOL,
+    BaudRate: UINT64,
+    ReceiveFifoDepth: UINT32,
+    Timeout: UINT32,
+    Parity: EFI_PARITY_TYPE,
+    DataBits: UINT8,
+    StopBits: EFI_STOP_BITS_TYPE
+) -> EFI_STATUS;
+
+pub type EFI_SERIAL_SET_CONTROL_BITS = extern "win64" fn(
+    This: *mut EFI_SERIAL_IO_PROTOCOL,
+    Control: UINT32
+) -> EFI_STATUS;
+
+pub type EFI_SERIAL_GET_CONTROL_BITS = extern "win64" fn(
+    This: *mut EFI_SERIAL_IO_PROTOCOL,
+    Control: *mut UINT32
+) -> EFI_STATUS;
+
+pub type EFI_SERIAL_WRITE = extern "win64" fn(
+    This: *mut EFI_SERIAL_IO_PROTOCOL,
+    BufferSize: *mut UINTN,
+    Buffer: *const VOID
+) -> EFI_STATUS;
+
+pub type EFI_SERIAL_READ = extern "win64" fn(
+    This: *mut EFI_SERIAL_IO_PROTOCOL,
+    BufferSize: *mut UINTN,
+    Buffer: *mut VOID
+) -> EFI_STATUS;
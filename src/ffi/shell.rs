@@ -0,0 +1,142 @@
+use ffi::{
+    base::{EFI_GUID, EFI_EVENT, EFI_HANDLE, EFI_STATUS, UINT32, CHAR16, BOOLEAN, VOID, NOT_DEFINED},
+    device_path::EFI_DEVICE_PATH_PROTOCOL,
+};
+
+pub const EFI_SHELL_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x6302d008, 0x7f9b, 0x4f30, [0x87, 0xac, 0x60, 0xc9, 0xfe, 0xf5, 0xda, 0x4e]);
+
+// An EFI_FILE_PROTOCOL in the shell's implementation, including for the console and pipes
+pub type SHELL_FILE_HANDLE = *mut VOID;
+
+#[repr(C)]
+pub struct EFI_SHELL_PROTOCOL {
+    pub Execute: EFI_SHELL_EXECUTE,
+    pub GetEnv: EFI_SHELL_GET_ENV,
+    pub SetEnv: EFI_SHELL_SET_ENV,
+    pub GetAlias: EFI_SHELL_GET_ALIAS,
+    pub SetAlias: EFI_SHELL_SET_ALIAS,
+    pub GetHelpText: EFI_SHELL_GET_HELP_TEXT,
+    pub GetDevicePathFromMap: EFI_SHELL_GET_DEVICE_PATH_FROM_MAP,
+    pub GetMapFromDevicePath: EFI_SHELL_GET_MAP_FROM_DEVICE_PATH,
+    pub GetDevicePathFromFilePath: EFI_SHELL_GET_DEVICE_PATH_FROM_FILE_PATH,
+    pub GetFilePathFromDevicePath: EFI_SHELL_GET_FILE_PATH_FROM_DEVICE_PATH,
+    pub SetMap: EFI_SHELL_SET_MAP,
+    pub GetCurDir: EFI_SHELL_GET_CUR_DIR,
+    pub SetCurDir: EFI_SHELL_SET_CUR_DIR,
+    pub OpenFileList: EFI_SHELL_OPEN_FILE_LIST,
+    pub FreeFileList: EFI_SHELL_FREE_FILE_LIST,
+    pub RemoveDupInFileList: EFI_SHELL_REMOVE_DUP_IN_FILE_LIST,
+    pub BatchIsActive: EFI_SHELL_BATCH_IS_ACTIVE,
+    pub IsRootShell: EFI_SHELL_IS_ROOT_SHELL,
+    pub EnablePageBreak: EFI_SHELL_ENABLE_PAGE_BREAK,
+    pub DisablePageBreak: EFI_SHELL_DISABLE_PAGE_BREAK,
+    pub GetPageBreak: EFI_SHELL_GET_PAGE_BREAK,
+    pub GetDeviceName: EFI_SHELL_GET_DEVICE_NAME,
+    pub GetFileInfo: EFI_SHELL_GET_FILE_INFO,
+    pub SetFileInfo: EFI_SHELL_SET_FILE_INFO,
+    pub OpenFileByName: EFI_SHELL_OPEN_FILE_BY_NAME,
+    pub CloseFile: EFI_SHELL_CLOSE_FILE,
+    pub CreateFile: EFI_SHELL_CREATE_FILE,
+    pub ReadFile: EFI_SHELL_READ_FILE,
+    pub WriteFile: EFI_SHELL_WRITE_FILE,
+    pub DeleteFile: EFI_SHELL_DELETE_FILE,
+    pub DeleteFileByName: EFI_SHELL_DELETE_FILE_BY_NAME,
+    pub GetFilePosition: EFI_SHELL_GET_FILE_POSITION,
+    pub SetFilePosition: EFI_SHELL_SET_FILE_POSITION,
+    pub FlushFile: EFI_SHELL_FLUSH_FILE,
+    pub FindFiles: EFI_SHELL_FIND_FILES,
+    pub FindFilesInDir: EFI_SHELL_FIND_FILES_IN_DIR,
+    pub GetFileSize: EFI_SHELL_GET_FILE_SIZE,
+    pub OpenRoot: EFI_SHELL_OPEN_ROOT,
+    pub OpenRootByHandle: EFI_SHELL_OPEN_ROOT_BY_HANDLE,
+    pub ExecutionBreak: EFI_EVENT,
+    pub MajorVersion: UINT32,
+    pub MinorVersion: UINT32,
+    pub RegisterGuidName: EFI_SHELL_REGISTER_GUID_NAME,
+    pub GetGuidName: EFI_SHELL_GET_GUID_NAME,
+    pub GetGuidFromName: EFI_SHELL_GET_GUID_FROM_NAME,
+    pub GetEnvEx: EFI_SHELL_GET_ENV_EX,
+}
+
+pub type EFI_SHELL_GET_ALIAS = *const NOT_DEFINED;
+pub type EFI_SHELL_SET_ALIAS = *const NOT_DEFINED;
+pub type EFI_SHELL_GET_HELP_TEXT = *const NOT_DEFINED;
+pub type EFI_SHELL_SET_MAP = *const NOT_DEFINED;
+pub type EFI_SHELL_OPEN_FILE_LIST = *const NOT_DEFINED;
+pub type EFI_SHELL_FREE_FILE_LIST = *const NOT_DEFINED;
+pub type EFI_SHELL_REMOVE_DUP_IN_FILE_LIST = *const NOT_DEFINED;
+pub type EFI_SHELL_BATCH_IS_ACTIVE = *const NOT_DEFINED;
+pub type EFI_SHELL_IS_ROOT_SHELL = *const NOT_DEFINED;
+pub type EFI_SHELL_ENABLE_PAGE_BREAK = *const NOT_DEFINED;
+pub type EFI_SHELL_DISABLE_PAGE_BREAK = *const NOT_DEFINED;
+pub type EFI_SHELL_GET_PAGE_BREAK = *const NOT_DEFINED;
+pub type EFI_SHELL_GET_DEVICE_NAME = *const NOT_DEFINED;
+pub type EFI_SHELL_GET_FILE_INFO = *const NOT_DEFINED;
+pub type EFI_SHELL_SET_FILE_INFO = *const NOT_DEFINED;
+pub type EFI_SHELL_OPEN_FILE_BY_NAME = *const NOT_DEFINED;
+pub type EFI_SHELL_CLOSE_FILE = *const NOT_DEFINED;
+pub type EFI_SHELL_CREATE_FILE = *const NOT_DEFINED;
+pub type EFI_SHELL_READ_FILE = *const NOT_DEFINED;
+pub type EFI_SHELL_WRITE_FILE = *const NOT_DEFINED;
+pub type EFI_SHELL_DELETE_FILE = *const NOT_DEFINED;
+pub type EFI_SHELL_DELETE_FILE_BY_NAME = *const NOT_DEFINED;
+pub type EFI_SHELL_GET_FILE_POSITION = *const NOT_DEFINED;
+pub type EFI_SHELL_SET_FILE_POSITION = *const NOT_DEFINED;
+pub type EFI_SHELL_FLUSH_FILE = *const NOT_DEFINED;
+pub type EFI_SHELL_FIND_FILES = *const NOT_DEFINED;
+pub type EFI_SHELL_FIND_FILES_IN_DIR = *const NOT_DEFINED;
+pub type EFI_SHELL_GET_FILE_SIZE = *const NOT_DEFINED;
+pub type EFI_SHELL_OPEN_ROOT = *const NOT_DEFINED;
+pub type EFI_SHELL_OPEN_ROOT_BY_HANDLE = *const NOT_DEFINED;
+pub type EFI_SHELL_REGISTER_GUID_NAME = *const NOT_DEFINED;
+pub type EFI_SHELL_GET_GUID_NAME = *const NOT_DEFINED;
+pub type EFI_SHELL_GET_GUID_FROM_NAME = *const NOT_DEFINED;
+
+pub type EFI_SHELL_GET_DEVICE_PATH_FROM_MAP = extern "win64" fn(
+    Mapping: *const CHAR16
+) -> *const EFI_DEVICE_PATH_PROTOCOL;
+
+pub type EFI_SHELL_EXECUTE = extern "win64" fn(
+    ParentImageHandle: *const EFI_HANDLE,
+    CommandLine: *const CHAR16,
+    Environment: *const *const CHAR16,
+    StatusCode: *mut EFI_STATUS
+) -> EFI_STATUS;
+
+// With a null Name the names of all variables are returned, each null terminated with an extra null at the end
+pub type EFI_SHELL_GET_ENV = extern "win64" fn(
+    Name: *const CHAR16
+) -> *const CHAR16;
+
+// Only in shell 2.1 and later. Attributes gets EFI_VARIABLE_NON_VOLATILE for non-volatile variables.
+pub type EFI_SHELL_GET_ENV_EX = extern "win64" fn(
+    Name: *const CHAR16,
+    Attributes: *mut UINT32
+) -> *const CHAR16;
+
+pub type EFI_SHELL_SET_ENV = extern "win64" fn(
+    Name: *const CHAR16,
+    Value: *const CHAR16,
+    Volatile: BOOLEAN
+) -> EFI_STATUS;
+
+pub type EFI_SHELL_GET_MAP_FROM_DEVICE_PATH = extern "win64" fn(
+    DevicePath: *mut *const EFI_DEVICE_PATH_PROTOCOL
+) -> *const CHAR16;
+
+pub type EFI_SHELL_GET_DEVICE_PATH_FROM_FILE_PATH = extern "win64" fn(
+    Path: *const CHAR16
+) -> *mut EFI_DEVICE_PATH_PROTOCOL;
+
+pub type EFI_SHELL_GET_FILE_PATH_FROM_DEVICE_PATH = extern "win64" fn(
+    Path: *const EFI_DEVICE_PATH_PROTOCOL
+) -> *mut CHAR16;
+
+pub type EFI_SHELL_GET_CUR_DIR = extern "win64" fn(
+    FileSystemMapping: *const CHAR16
+) -> *const CHAR16;
+
+pub type EFI_SHELL_SET_CUR_DIR = extern "win64" fn(
+    FileSystem: *const CHAR16,
+    Dir: *const CHAR16
+) -> EFI_STATUS;
@@ -0,0 +1,15 @@
+use ffi::{
+    base::{EFI_GUID, UINTN, CHAR16},
+    shell::SHELL_FILE_HANDLE,
+};
+
+pub const EFI_SHELL_PARAMETERS_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x752F3136, 0x4E16, 0x4FDC, [0xA2, 0x2A, 0xE5, 0xF4, 0x68, 0x12, 0xF4, 0xCA]);
+
+#[repr(C)]
+pub struct EFI_SHELL_PARAMETERS_PROTOCOL {
+    pub Argv: *const *const CHAR16,
+    pub Argc: UINTN,
+    pub StdIn: SHELL_FILE_HANDLE,
+    pub StdOut: SHELL_FILE_HANDLE,
+    pub StdErr: SHELL_FILE_HANDLE,
+}
@@ -0,0 +1,126 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, EFI_PHYSICAL_ADDRESS, BOOLEAN, NOT_DEFINED, UINT8, UINT16, UINT32, UINT64};
+
+pub const EFI_TCG2_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x607f766c, 0x7455, 0x42be, [0x93, 0x0b, 0xe4, 0xd7, 0x6d, 0xb2, 0x72, 0x0f]);
+pub const EFI_TCG2_FINAL_EVENTS_TABLE_GUID: EFI_GUID = EFI_GUID(0x1e2ed096, 0x30e2, 0x4254, [0xbd, 0x89, 0x86, 0x3b, 0xbe, 0xf8, 0x23, 0x25]);
+
+pub type EFI_TCG2_EVENT_ALGORITHM_BITMAP = UINT32;
+pub const EFI_TCG2_BOOT_HASH_ALG_SHA1: EFI_TCG2_EVENT_ALGORITHM_BITMAP = 0x00000001;
+pub const EFI_TCG2_BOOT_HASH_ALG_SHA256: EFI_TCG2_EVENT_ALGORITHM_BITMAP = 0x00000002;
+pub const EFI_TCG2_BOOT_HASH_ALG_SHA384: EFI_TCG2_EVENT_ALGORITHM_BITMAP = 0x00000004;
+pub const EFI_TCG2_BOOT_HASH_ALG_SHA512: EFI_TCG2_EVENT_ALGORITHM_BITMAP = 0x00000008;
+pub const EFI_TCG2_BOOT_HASH_ALG_SM3_256: EFI_TCG2_EVENT_ALGORITHM_BITMAP = 0x00000010;
+
+pub type EFI_TCG2_EVENT_LOG_BITMAP = UINT32;
+pub type EFI_TCG2_EVENT_LOG_FORMAT = UINT32;
+pub const EFI_TCG2_EVENT_LOG_FORMAT_TCG_1_2: EFI_TCG2_EVENT_LOG_FORMAT = 0x00000001;
+pub const EFI_TCG2_EVENT_LOG_FORMAT_TCG_2: EFI_TCG2_EVENT_LOG_FORMAT = 0x00000002;
+
+pub const EFI_TCG2_EXTEND_ONLY: UINT64 = 0x0000000000000001;
+pub const PE_COFF_IMAGE: UINT64 = 0x0000000000000010;
+
+pub const EFI_TCG2_EVENT_HEADER_VERSION: UINT16 = 1;
+
+pub type TCG_PCRINDEX = UINT32;
+pub type TCG_EVENTTYPE = UINT32;
+
+// TPM_ALG_ID values used in TCG_PCR_EVENT2 digests
+pub const TPM_ALG_SHA1: UINT16 = 0x0004;
+pub const TPM_ALG_SHA256: UINT16 = 0x000B;
+pub const TPM_ALG_SHA384: UINT16 = 0x000C;
+pub const TPM_ALG_SHA512: UINT16 = 0x000D;
+pub const TPM_ALG_SM3_256: UINT16 = 0x0012;
+
+#[repr(C)]
+pub struct EFI_TCG2_PROTOCOL {
+    pub GetCapability: EFI_TCG2_GET_CAPABILITY,
+    pub GetEventLog: EFI_TCG2_GET_EVENT_LOG,
+    pub HashLogExtendEvent: EFI_TCG2_HASH_LOG_EXTEND_EVENT,
+    pub SubmitCommand: EFI_TCG2_SUBMIT_COMMAND,
+    pub GetActivePcrBanks: EFI_TCG2_GET_ACTIVE_PCR_BANKS,
+    pub SetActivePcrBanks: *const NOT_DEFINED,
+    pub GetResultOfSetActivePcrBanks: *const NOT_DEFINED,
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+#[repr(C, packed)]
+pub struct EFI_TCG2_VERSION {
+    pub Major: UINT8,
+    pub Minor: UINT8,
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+#[repr(C, packed)]
+pub struct EFI_TCG2_BOOT_SERVICE_CAPABILITY {
+    pub Size: UINT8,
+    pub StructureVersion: EFI_TCG2_VERSION,
+    pub ProtocolVersion: EFI_TCG2_VERSION,
+    pub HashAlgorithmBitmap: EFI_TCG2_EVENT_ALGORITHM_BITMAP,
+    pub SupportedEventLogs: EFI_TCG2_EVENT_LOG_BITMAP,
+    pub TPMPresentFlag: BOOLEAN,
+    pub MaxCommandSize: UINT32,
+    pub MaxResponseSize: UINT32,
+    pub ManufacturerID: UINT32,
+    pub NumberOfPCRBanks: UINT32,
+    pub ActivePcrBanks: EFI_TCG2_EVENT_ALGORITHM_BITMAP,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C, packed)]
+pub struct EFI_TCG2_EVENT_HEADER {
+    pub HeaderSize: UINT32,
+    pub HeaderVersion: UINT16,
+    pub PCRIndex: TCG_PCRINDEX,
+    pub EventType: TCG_EVENTTYPE,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C, packed)]
+pub struct EFI_TCG2_EVENT {
+    pub Size: UINT32,
+    pub Header: EFI_TCG2_EVENT_HEADER,
+    // UINT8 Event[];
+}
+
+/// The final events table published in the configuration table. Holds the events logged after
+/// GetEventLog() was first called, which therefore may be missing from the log it returned.
+#[derive(Debug)]
+#[repr(C)]
+pub struct EFI_TCG2_FINAL_EVENTS_TABLE {
+    pub Version: UINT64,
+    pub NumberOfEvents: UINT64,
+    // TCG_PCR_EVENT2 Event[];
+}
+
+pub type EFI_TCG2_GET_CAPABILITY = extern "win64" fn(
+    This: *const EFI_TCG2_PROTOCOL,
+    ProtocolCapability: *mut EFI_TCG2_BOOT_SERVICE_CAPABILITY
+) -> EFI_STATUS;
+
+pub type EFI_TCG2_GET_EVENT_LOG = extern "win64" fn(
+    This: *const EFI_TCG2_PROTOCOL,
+    EventLogFormat: EFI_TCG2_EVENT_LOG_FORMAT,
+    EventLogLocation: *mut EFI_PHYSICAL_ADDRESS,
+    EventLogLastEntry: *mut EFI_PHYSICAL_ADDRESS,
+    EventLogTruncated: *mut BOOLEAN
+) -> EFI_STATUS;
+
+pub type EFI_TCG2_HASH_LOG_EXTEND_EVENT = extern "win64" fn(
+    This: *const EFI_TCG2_PROTOCOL,
+    Flags: UINT64,
+    DataToHash: EFI_PHYSICAL_ADDRESS,
+    DataToHashLen: UINT64,
+    EfiTcgEvent: *const EFI_TCG2_EVENT
+) -> EFI_STATUS;
+
+pub type EFI_TCG2_SUBMIT_COMMAND = extern "win64" fn(
+    This: *const EFI_TCG2_PROTOCOL,
+    InputParameterBlockSize: UINT32,
+    InputParameterBlock: *const UINT8,
+    OutputParameterBlockSize: UINT32,
+    OutputParameterBlock: *mut UINT8
+) -> EFI_STATUS;
+
+pub type EFI_TCG2_GET_ACTIVE_PCR_BANKS = extern "win64" fn(
+    This: *const EFI_TCG2_PROTOCOL,
+    ActivePcrBanks: *mut UINT32
+) -> EFI_STATUS;
@@ -0,0 +1,24 @@
+use ffi::{
+    base::{EFI_GUID, EFI_STATUS, UINT64},
+};
+
+pub const EFI_TIMESTAMP_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0xafbfde41, 0x2e6e, 0x4262, [0xba, 0x65, 0x62, 0xb9, 0x23, 0x6e, 0x54, 0x95]);
+
+#[repr(C)]
+pub struct EFI_TIMESTAMP_PROTOCOL {
+    pub GetTimestamp: TIMESTAMP_GET,
+    pub GetProperties: TIMESTAMP_GET_PROPERTIES,
+}
+
+#[derive(Debug, Default)]
+#[repr(C)]
+pub struct EFI_TIMESTAMP_PROPERTIES {
+    pub Frequency: UINT64,
+    pub EndValue: UINT64,
+}
+
+pub type TIMESTAMP_GET = extern "win64" fn() -> UINT64;
+
+pub type TIMESTAMP_GET_PROPERTIES = extern "win64" fn(
+    Properties: *mut EFI_TIMESTAMP_PROPERTIES
+) -> EFI_STATUS;
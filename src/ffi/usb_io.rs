@@ -0,0 +1,178 @@
+use ffi::base::{EFI_GUID, EFI_STATUS, UINT8, UINT16, UINT32, UINTN, CHAR16, VOID, NOT_DEFINED};
+
+pub const EFI_USB_IO_PROTOCOL_GUID: EFI_GUID = EFI_GUID(0x2B2F68D6, 0x0CD2, 0x44CF, [0x8E, 0x8B, 0xBB, 0xA2, 0x0B, 0x1B, 0x5B, 0x75]);
+
+#[repr(C)]
+pub struct EFI_USB_IO_PROTOCOL {
+    pub UsbControlTransfer: EFI_USB_IO_CONTROL_TRANSFER,
+    pub UsbBulkTransfer: EFI_USB_IO_BULK_TRANSFER,
+    pub UsbAsyncInterruptTransfer: EFI_USB_IO_ASYNC_INTERRUPT_TRANSFER,
+    pub UsbSyncInterruptTransfer: EFI_USB_IO_SYNC_INTERRUPT_TRANSFER,
+    pub UsbIsochronousTransfer: EFI_USB_IO_ISOCHRONOUS_TRANSFER,
+    pub UsbAsyncIsochronousTransfer: EFI_USB_IO_ASYNC_ISOCHRONOUS_TRANSFER,
+    pub UsbGetDeviceDescriptor: EFI_USB_IO_GET_DEVICE_DESCRIPTOR,
+    pub UsbGetConfigDescriptor: EFI_USB_IO_GET_CONFIG_DESCRIPTOR,
+    pub UsbGetInterfaceDescriptor: EFI_USB_IO_GET_INTERFACE_DESCRIPTOR,
+    pub UsbGetEndpointDescriptor: EFI_USB_IO_GET_ENDPOINT_DESCRIPTOR,
+    pub UsbGetStringDescriptor: EFI_USB_IO_GET_STRING_DESCRIPTOR,
+    pub UsbGetSupportedLanguages: EFI_USB_IO_GET_SUPPORTED_LANGUAGE,
+    pub UsbPortReset: EFI_USB_IO_PORT_RESET,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum EFI_USB_DATA_DIRECTION {
+    EfiUsbDataIn = 0,
+    EfiUsbDataOut,
+    EfiUsbNoData,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct EFI_USB_DEVICE_REQUEST {
+    pub RequestType: UINT8,
+    pub Request: UINT8,
+    pub Value: UINT16,
+    pub Index: UINT16,
+    pub Length: UINT16,
+}
+
+// Descriptors are laid out exactly as they come over the wire, hence packed
+#[derive(Debug, Copy, Clone)]
+#[repr(packed)]
+pub struct EFI_USB_DEVICE_DESCRIPTOR {
+    pub Length: UINT8,
+    pub DescriptorType: UINT8,
+    pub BcdUSB: UINT16,
+    pub DeviceClass: UINT8,
+    pub DeviceSubClass: UINT8,
+    pub DeviceProtocol: UINT8,
+    pub MaxPacketSize0: UINT8,
+    pub IdVendor: UINT16,
+    pub IdProduct: UINT16,
+    pub BcdDevice: UINT16,
+    pub StrManufacturer: UINT8,
+    pub StrProduct: UINT8,
+    pub StrSerialNumber: UINT8,
+    pub NumConfigurations: UINT8,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(packed)]
+pub struct EFI_USB_CONFIG_DESCRIPTOR {
+    pub Length: UINT8,
+    pub DescriptorType: UINT8,
+    pub TotalLength: UINT16,
+    pub NumInterfaces: UINT8,
+    pub ConfigurationValue: UINT8,
+    pub Configuration: UINT8,
+    pub Attributes: UINT8,
+    pub MaxPower: UINT8,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(packed)]
+pub struct EFI_USB_INTERFACE_DESCRIPTOR {
+    pub Length: UINT8,
+    pub DescriptorType: UINT8,
+    pub InterfaceNumber: UINT8,
+    pub AlternateSetting: UINT8,
+    pub NumEndpoints: UINT8,
+    pub InterfaceClass: UINT8,
+    pub InterfaceSubClass: UINT8,
+    pub InterfaceProtocol: UINT8,
+    pub Interface: UINT8,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(packed)]
+pub struct EFI_USB_ENDPOINT_DESCRIPTOR {
+    pub Length: UINT8,
+    pub DescriptorType: UINT8,
+    pub EndpointAddress: UINT8,
+    pub Attributes: UINT8,
+    pub MaxPacketSize: UINT16,
+    pub Interval: UINT8,
+}
+
+// Bits of the transfer status returned alongside EFI_DEVICE_ERROR
+pub const EFI_USB_NOERROR: UINT32 = 0x00;
+pub const EFI_USB_ERR_NOTEXECUTE: UINT32 = 0x01;
+pub const EFI_USB_ERR_STALL: UINT32 = 0x02;
+pub const EFI_USB_ERR_BUFFER: UINT32 = 0x04;
+pub const EFI_USB_ERR_BABBLE: UINT32 = 0x08;
+pub const EFI_USB_ERR_NAK: UINT32 = 0x10;
+pub const EFI_USB_ERR_CRC: UINT32 = 0x20;
+pub const EFI_USB_ERR_TIMEOUT: UINT32 = 0x40;
+pub const EFI_USB_ERR_BITSTUFF: UINT32 = 0x80;
+pub const EFI_USB_ERR_SYSTEM: UINT32 = 0x100;
+
+pub type EFI_USB_IO_CONTROL_TRANSFER = extern "win64" fn(
+    This: *const EFI_USB_IO_PROTOCOL,
+    Request: *const EFI_USB_DEVICE_REQUEST,
+    Direction: EFI_USB_DATA_DIRECTION,
+    Timeout: UINT32,
+    Data: *mut VOID,
+    DataLength: UINTN,
+    Status: *mut UINT32
+) -> EFI_STATUS;
+
+pub type EFI_USB_IO_BULK_TRANSFER = extern "win64" fn(
+    This: *const EFI_USB_IO_PROTOCOL,
+    DeviceEndpoint: UINT8,
+    Data: *mut VOID,
+    DataLength: *mut UINTN,
+    Timeout: UINTN,
+    Status: *mut UINT32
+) -> EFI_STATUS;
+
+pub type EFI_USB_IO_SYNC_INTERRUPT_TRANSFER = extern "win64" fn(
+    This: *const EFI_USB_IO_PROTOCOL,
+    DeviceEndpoint: UINT8,
+    Data: *mut VOID,
+    DataLength: *mut UINTN,
+    Timeout: UINTN,
+    Status: *mut UINT32
+) -> EFI_STATUS;
+
+pub type EFI_USB_IO_GET_DEVICE_DESCRIPTOR = extern "win64" fn(
+    This: *const EFI_USB_IO_PROTOCOL,
+    DeviceDescriptor: *mut EFI_USB_DEVICE_DESCRIPTOR
+) -> EFI_STATUS;
+
+pub type EFI_USB_IO_GET_CONFIG_DESCRIPTOR = extern "win64" fn(
+    This: *const EFI_USB_IO_PROTOCOL,
+    ConfigurationDescriptor: *mut EFI_USB_CONFIG_DESCRIPTOR
+) -> EFI_STATUS;
+
+pub type EFI_USB_IO_GET_INTERFACE_DESCRIPTOR = extern "win64" fn(
+    This: *const EFI_USB_IO_PROTOCOL,
+    InterfaceDescriptor: *mut EFI_USB_INTERFACE_DESCRIPTOR
+) -> EFI_STATUS;
+
+pub type EFI_USB_IO_GET_ENDPOINT_DESCRIPTOR = extern "win64" fn(
+    This: *const EFI_USB_IO_PROTOCOL,
+    EndpointIndex: UINT8,
+    EndpointDescriptor: *mut EFI_USB_ENDPOINT_DESCRIPTOR
+) -> EFI_STATUS;
+
+pub type EFI_USB_IO_GET_STRING_DESCRIPTOR = extern "win64" fn(
+    This: *const EFI_USB_IO_PROTOCOL,
+    LangID: UINT16,
+    StringID: UINT8,
+    String: *mut *mut CHAR16
+) -> EFI_STATUS;
+
+pub type EFI_USB_IO_GET_SUPPORTED_LANGUAGE = extern "win64" fn(
+    This: *const EFI_USB_IO_PROTOCOL,
+    LangIDTable: *mut *mut UINT16,
+    TableSize: *mut UINT16
+) -> EFI_STATUS;
+
+pub type EFI_USB_IO_PORT_RESET = extern "win64" fn(
+    This: *const EFI_USB_IO_PROTOCOL
+) -> EFI_STATUS;
+
+pub type EFI_USB_IO_ASYNC_INTERRUPT_TRANSFER = *const NOT_DEFINED;
+pub type EFI_USB_IO_ISOCHRONOUS_TRANSFER = *const NOT_DEFINED;
+pub type EFI_USB_IO_ASYNC_ISOCHRONOUS_TRANSFER = *const NOT_DEFINED;
@@ -0,0 +1,481 @@
+use ffi::{
+    firmware_volume::*,
+    EFI_GUID,
+    EFI_HANDLE,
+    EFI_NOT_FOUND,
+    IsSuccess,
+    VOID,
+};
+use decompress::{self, Algorithm};
+use utils::{guid_from_bytes, handles_by_protocol, open_protocol};
+use boxed::EfiBox;
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec};
+use core::{ptr, mem, slice};
+use {Result, EfiError, EfiErrorKind, Guid};
+
+// Firmware volumes (EFI_FIRMWARE_VOLUME2_PROTOCOL) and the Firmware File System files in them.
+// Each file is a sequence of sections: leaf sections hold things like PE images, UI names and raw
+// data, while encapsulation sections (compression and GUID-defined) hold further sections.
+
+// Real images nest encapsulation sections a few levels deep at most. Deeper nesting means a malicious or
+// corrupt file, which could otherwise exhaust the stack.
+const MAX_SECTION_DEPTH: usize = 16;
+
+/// The type of an FFS file
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FileType {
+    Raw,
+    Freeform,
+    SecurityCore,
+    PeiCore,
+    DxeCore,
+    Peim,
+    Driver,
+    CombinedPeimDriver,
+    Application,
+    Mm,
+    FirmwareVolumeImage,
+    Pad,
+    Other(u8),
+}
+
+impl From<u8> for FileType {
+    fn from(t: u8) -> Self {
+        match t {
+            EFI_FV_FILETYPE_RAW => FileType::Raw,
+            EFI_FV_FILETYPE_FREEFORM => FileType::Freeform,
+            EFI_FV_FILETYPE_SECURITY_CORE => FileType::SecurityCore,
+            EFI_FV_FILETYPE_PEI_CORE => FileType::PeiCore,
+            EFI_FV_FILETYPE_DXE_CORE => FileType::DxeCore,
+            EFI_FV_FILETYPE_PEIM => FileType::Peim,
+            EFI_FV_FILETYPE_DRIVER => FileType::Driver,
+            EFI_FV_FILETYPE_COMBINED_PEIM_DRIVER => FileType::CombinedPeimDriver,
+            EFI_FV_FILETYPE_APPLICATION => FileType::Application,
+            EFI_FV_FILETYPE_MM => FileType::Mm,
+            EFI_FV_FILETYPE_FIRMWARE_VOLUME_IMAGE => FileType::FirmwareVolumeImage,
+            EFI_FV_FILETYPE_FFS_PAD => FileType::Pad,
+            t => FileType::Other(t),
+        }
+    }
+}
+
+impl From<FileType> for u8 {
+    fn from(t: FileType) -> Self {
+        match t {
+            FileType::Raw => EFI_FV_FILETYPE_RAW,
+            FileType::Freeform => EFI_FV_FILETYPE_FREEFORM,
+            FileType::SecurityCore => EFI_FV_FILETYPE_SECURITY_CORE,
+            FileType::PeiCore => EFI_FV_FILETYPE_PEI_CORE,
+            FileType::DxeCore => EFI_FV_FILETYPE_DXE_CORE,
+            FileType::Peim => EFI_FV_FILETYPE_PEIM,
+            FileType::Driver => EFI_FV_FILETYPE_DRIVER,
+            FileType::CombinedPeimDriver => EFI_FV_FILETYPE_COMBINED_PEIM_DRIVER,
+            FileType::Application => EFI_FV_FILETYPE_APPLICATION,
+            FileType::Mm => EFI_FV_FILETYPE_MM,
+            FileType::FirmwareVolumeImage => EFI_FV_FILETYPE_FIRMWARE_VOLUME_IMAGE,
+            FileType::Pad => EFI_FV_FILETYPE_FFS_PAD,
+            FileType::Other(t) => t,
+        }
+    }
+}
+
+/// The type of a section of an FFS file
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SectionType {
+    Compression,
+    GuidDefined,
+    Disposable,
+    Pe32,
+    Pic,
+    Te,
+    DxeDepex,
+    Version,
+    UserInterface,
+    Compatibility16,
+    FirmwareVolumeImage,
+    FreeformSubtypeGuid,
+    Raw,
+    PeiDepex,
+    MmDepex,
+    Other(u8),
+}
+
+impl From<u8> for SectionType {
+    fn from(t: u8) -> Self {
+        match t {
+            EFI_SECTION_COMPRESSION => SectionType::Compression,
+            EFI_SECTION_GUID_DEFINED => SectionType::GuidDefined,
+            EFI_SECTION_DISPOSABLE => SectionType::Disposable,
+            EFI_SECTION_PE32 => SectionType::Pe32,
+            EFI_SECTION_PIC => SectionType::Pic,
+            EFI_SECTION_TE => SectionType::Te,
+            EFI_SECTION_DXE_DEPEX => SectionType::DxeDepex,
+            EFI_SECTION_VERSION => SectionType::Version,
+            EFI_SECTION_USER_INTERFACE => SectionType::UserInterface,
+            EFI_SECTION_COMPATIBILITY16 => SectionType::Compatibility16,
+            EFI_SECTION_FIRMWARE_VOLUME_IMAGE => SectionType::FirmwareVolumeImage,
+            EFI_SECTION_FREEFORM_SUBTYPE_GUID => SectionType::FreeformSubtypeGuid,
+            EFI_SECTION_RAW => SectionType::Raw,
+            EFI_SECTION_PEI_DEPEX => SectionType::PeiDepex,
+            EFI_SECTION_MM_DEPEX => SectionType::MmDepex,
+            t => SectionType::Other(t),
+        }
+    }
+}
+
+impl From<SectionType> for u8 {
+    fn from(t: SectionType) -> Self {
+        match t {
+            SectionType::Compression => EFI_SECTION_COMPRESSION,
+            SectionType::GuidDefined => EFI_SECTION_GUID_DEFINED,
+            SectionType::Disposable => EFI_SECTION_DISPOSABLE,
+            SectionType::Pe32 => EFI_SECTION_PE32,
+            SectionType::Pic => EFI_SECTION_PIC,
+            SectionType::Te => EFI_SECTION_TE,
+            SectionType::DxeDepex => EFI_SECTION_DXE_DEPEX,
+            SectionType::Version => EFI_SECTION_VERSION,
+            SectionType::UserInterface => EFI_SECTION_USER_INTERFACE,
+            SectionType::Compatibility16 => EFI_SECTION_COMPATIBILITY16,
+            SectionType::FirmwareVolumeImage => EFI_SECTION_FIRMWARE_VOLUME_IMAGE,
+            SectionType::FreeformSubtypeGuid => EFI_SECTION_FREEFORM_SUBTYPE_GUID,
+            SectionType::Raw => EFI_SECTION_RAW,
+            SectionType::PeiDepex => EFI_SECTION_PEI_DEPEX,
+            SectionType::MmDepex => EFI_SECTION_MM_DEPEX,
+            SectionType::Other(t) => t,
+        }
+    }
+}
+
+/// A file in a firmware volume as listed by `FirmwareVolume::files()`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    pub name: Guid,
+    pub file_type: FileType,
+    pub attributes: u32,
+    pub size: usize,
+}
+
+/// A firmware volume the firmware has made accessible
+pub struct FirmwareVolume {
+    handle: EFI_HANDLE,
+    protocol: *const EFI_FIRMWARE_VOLUME2_PROTOCOL,
+}
+
+impl FirmwareVolume {
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol::<EFI_FIRMWARE_VOLUME2_PROTOCOL>(handle, &EFI_FIRMWARE_VOLUME2_PROTOCOL_GUID)?;
+
+        Ok(FirmwareVolume { handle, protocol })
+    }
+
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_FIRMWARE_VOLUME2_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// Every file in the volume
+    pub fn files(&self) -> Result<Vec<FileInfo>> {
+        self.files_filtered(EFI_FV_FILETYPE_ALL)
+    }
+
+    /// The files of the given type
+    pub fn files_of_type(&self, file_type: FileType) -> Result<Vec<FileInfo>> {
+        self.files_filtered(file_type.into())
+    }
+
+    /// The whole contents of a file: its sections with their headers
+    pub fn read_file(&self, name: &Guid) -> Result<Vec<u8>> {
+        let mut buffer: *mut VOID = ptr::null_mut();
+        let mut size = 0;
+        let (mut found_type, mut attributes, mut auth_status) = (0, 0, 0);
+        unsafe {
+            ret_unless_success!(((*self.protocol).ReadFile)(self.protocol, name, &mut buffer, &mut size, &mut found_type, &mut attributes, &mut auth_status));
+        }
+
+        Ok(take_buffer(buffer, size))
+    }
+
+    /// The contents of the `instance`th section of the given type in a file, without its header.
+    /// The firmware looks through encapsulation sections, decompressing and authenticating as it goes.
+    pub fn read_section(&self, name: &Guid, section_type: SectionType, instance: usize) -> Result<Vec<u8>> {
+        let mut buffer: *mut VOID = ptr::null_mut();
+        let mut size = 0;
+        let mut auth_status = 0;
+        unsafe {
+            ret_unless_success!(((*self.protocol).ReadSection)(self.protocol, name, section_type.into(), instance, &mut buffer, &mut size, &mut auth_status));
+        }
+
+        Ok(take_buffer(buffer, size))
+    }
+
+    /// The name given to a file by its user interface section, e.g. "Shell" or "Logo"
+    pub fn ui_name(&self, name: &Guid) -> Result<String> {
+        Ok(decode_ui_name(&self.read_section(name, SectionType::UserInterface, 0)?))
+    }
+
+    fn files_filtered(&self, filter: EFI_FV_FILETYPE) -> Result<Vec<FileInfo>> {
+        let mut key = vec![0u8; unsafe { (*self.protocol).KeySize } as usize];
+        let mut files = Vec::new();
+        loop {
+            let mut file_type = filter;
+            let mut name: EFI_GUID = unsafe { mem::zeroed() };
+            let (mut attributes, mut size) = (0, 0);
+            let status = unsafe { ((*self.protocol).GetNextFile)(self.protocol, key.as_mut_ptr() as *mut VOID, &mut file_type, &mut name, &mut attributes, &mut size) };
+            if status == EFI_NOT_FOUND {
+                return Ok(files);
+            }
+            if !IsSuccess(status) {
+                return Err(EfiError::from(status));
+            }
+
+            files.push(FileInfo { name, file_type: file_type.into(), attributes, size });
+        }
+    }
+}
+
+/// Looks for a file in all firmware volumes and returns the `instance`th section of the given type in it
+pub fn find_section(name: &Guid, section_type: SectionType, instance: usize) -> Result<Vec<u8>> {
+    for volume in FirmwareVolume::all()? {
+        if let Ok(section) = volume.read_section(name, section_type, instance) {
+            return Ok(section);
+        }
+    }
+
+    Err(EfiErrorKind::NotFound.into())
+}
+
+/// A section of an FFS file as found by `parse_sections()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section<'a> {
+    pub section_type: SectionType,
+    /// Everything after the common section header, including any type specific header
+    pub data: &'a [u8],
+    header_size: usize,
+}
+
+/// Splits the contents of a file (or of an encapsulation section) into its sections
+pub fn parse_sections(data: &[u8]) -> Result<Vec<Section>> {
+    let mut sections = Vec::new();
+    let mut offset = 0;
+    while offset + 4 <= data.len() {
+        let header = &data[offset..];
+        let size = header[0] as usize | (header[1] as usize) << 8 | (header[2] as usize) << 16;
+        let (header_size, size) = if size == 0xFFFFFF {
+            if header.len() < 8 {
+                return Err(EfiErrorKind::VolumeCorrupted.into());
+            }
+            (8, LittleEndian::read_u32(&header[4..8]) as usize)
+        } else {
+            (4, size)
+        };
+
+        if size < header_size || size > header.len() {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        sections.push(Section { section_type: header[3].into(), data: &header[header_size..size], header_size });
+        offset += align4(size);
+    }
+
+    Ok(sections)
+}
+
+/// Finds the `instance`th section of the given type in the contents of a file such as returned by
+/// `FirmwareVolume::read_file()`, descending into compression sections and into GUID-defined sections
+/// that don't need processing. Returns the section's contents without its header.
+pub fn extract_section(data: &[u8], section_type: SectionType, instance: usize) -> Result<Vec<u8>> {
+    let mut found = Vec::new();
+    collect_sections(data, section_type, &mut found, instance + 1, 0)?;
+    found.into_iter().nth(instance).ok_or_else(|| EfiErrorKind::NotFound.into())
+}
+
+fn collect_sections(data: &[u8], section_type: SectionType, found: &mut Vec<Vec<u8>>, wanted: usize, depth: usize) -> Result<()> {
+    if depth > MAX_SECTION_DEPTH {
+        return Err(EfiErrorKind::VolumeCorrupted.into());
+    }
+
+    for section in parse_sections(data)? {
+        if found.len() >= wanted {
+            break;
+        }
+
+        if section.section_type == section_type {
+            found.push(section.data.to_vec());
+            continue;
+        }
+
+        match section.section_type {
+            SectionType::Compression => {
+                if section.data.len() < 5 {
+                    return Err(EfiErrorKind::VolumeCorrupted.into());
+                }
+
+                let contents = &section.data[5..]; // After UncompressedLength and CompressionType
+                match section.data[4] {
+                    EFI_NOT_COMPRESSED => collect_sections(contents, section_type, found, wanted, depth + 1)?,
+                    EFI_STANDARD_COMPRESSION => collect_sections(&decompress::decompress(contents, Algorithm::Efi)?, section_type, found, wanted, depth + 1)?,
+                    _ => return Err(EfiErrorKind::Unsupported.into()),
+                }
+            },
+            SectionType::GuidDefined => {
+                if section.data.len() < 20 {
+                    return Err(EfiErrorKind::VolumeCorrupted.into());
+                }
+
+                // The data offset counts from the start of the section including the common header
+                let data_offset = LittleEndian::read_u16(&section.data[16..18]) as usize;
+                let attributes = LittleEndian::read_u16(&section.data[18..20]);
+                if attributes & EFI_GUIDED_SECTION_PROCESSING_REQUIRED == 0 {
+                    if data_offset < section.header_size || data_offset - section.header_size > section.data.len() {
+                        return Err(EfiErrorKind::VolumeCorrupted.into());
+                    }
+                    collect_sections(&section.data[data_offset - section.header_size..], section_type, found, wanted, depth + 1)?;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    Ok(())
+}
+
+/// The GUID identifying how a GUID-defined section's contents are encoded, e.g. LZMA
+pub fn guided_section_guid(section: &Section) -> Option<Guid> {
+    if section.section_type == SectionType::GuidDefined && section.data.len() >= 16 {
+        Some(guid_from_bytes(&section.data[..16]))
+    } else {
+        None
+    }
+}
+
+fn decode_ui_name(data: &[u8]) -> String {
+    let chars = data.chunks(2)
+        .filter(|c| c.len() == 2)
+        .map(|c| LittleEndian::read_u16(c))
+        .take_while(|c| *c != 0)
+        .collect::<Vec<u16>>();
+    String::from_utf16_lossy(&chars)
+}
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+// Copies out a buffer the firmware allocated for us and frees it
+fn take_buffer(buffer: *mut VOID, size: usize) -> Vec<u8> {
+    if buffer.is_null() {
+        return Vec::new();
+    }
+
+    let buffer = unsafe { EfiBox::from_raw(buffer as *mut u8) };  // Putting it in a box for proper cleanup on exit
+    unsafe { slice::from_raw_parts(buffer.as_raw() as *const u8, size) }.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::guid_to_bytes;
+
+    fn section(section_type: u8, data: &[u8]) -> Vec<u8> {
+        let mut section = vec![0u8; 4];
+        let size = data.len() + 4;
+        section[0] = size as u8;
+        section[1] = (size >> 8) as u8;
+        section[2] = (size >> 16) as u8;
+        section[3] = section_type;
+        section.extend_from_slice(data);
+        while section.len() % 4 != 0 {
+            section.push(0);
+        }
+        section
+    }
+
+    fn ui(name: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        for c in name.encode_utf16().chain(Some(0)) {
+            data.push(c as u8);
+            data.push((c >> 8) as u8);
+        }
+        section(EFI_SECTION_USER_INTERFACE, &data)
+    }
+
+    #[test]
+    fn sections_are_split_at_aligned_boundaries() {
+        let mut file = section(EFI_SECTION_RAW, b"abcde");
+        file.extend(ui("Logo"));
+        let sections = parse_sections(&file).unwrap();
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].section_type, SectionType::Raw);
+        assert_eq!(sections[0].data, b"abcde");
+        assert_eq!(sections[1].section_type, SectionType::UserInterface);
+        assert_eq!(decode_ui_name(sections[1].data), "Logo");
+    }
+
+    #[test]
+    fn extended_size_headers_are_understood() {
+        let mut file = vec![0xFF, 0xFF, 0xFF, EFI_SECTION_RAW, 11, 0, 0, 0, 1, 2, 3];
+        file.push(0);
+        let sections = parse_sections(&file).unwrap();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].data, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn corrupt_sizes_are_rejected() {
+        assert_eq!(parse_sections(&[9, 0, 0, EFI_SECTION_RAW, 0]).unwrap_err().kind(), EfiErrorKind::VolumeCorrupted);
+        assert_eq!(parse_sections(&[2, 0, 0, EFI_SECTION_RAW]).unwrap_err().kind(), EfiErrorKind::VolumeCorrupted);
+    }
+
+    #[test]
+    fn encapsulated_sections_are_searched() {
+        // An uncompressed compression section holding a raw section
+        let inner = section(EFI_SECTION_RAW, b"inner");
+        let mut compression = vec![0u8; 5];
+        LittleEndian::write_u32(&mut compression[0..4], inner.len() as u32);
+        compression[4] = EFI_NOT_COMPRESSED;
+        compression.extend_from_slice(&inner);
+
+        // A GUID-defined section that needs no processing, holding a UI section
+        let mut guided = vec![0u8; 20];
+        guided[0] = 0xAB;
+        LittleEndian::write_u16(&mut guided[16..18], 24);
+        LittleEndian::write_u16(&mut guided[18..20], EFI_GUIDED_SECTION_AUTH_STATUS_VALID);
+        guided.extend(ui("Shell"));
+
+        let mut file = section(EFI_SECTION_RAW, b"outer");
+        file.extend(section(EFI_SECTION_COMPRESSION, &compression));
+        file.extend(section(EFI_SECTION_GUID_DEFINED, &guided));
+
+        assert_eq!(extract_section(&file, SectionType::Raw, 0).unwrap(), b"outer");
+        assert_eq!(extract_section(&file, SectionType::Raw, 1).unwrap(), b"inner");
+        assert_eq!(extract_section(&file, SectionType::Raw, 2).unwrap_err().kind(), EfiErrorKind::NotFound);
+        assert_eq!(decode_ui_name(&extract_section(&file, SectionType::UserInterface, 0).unwrap()), "Shell");
+
+        let sections = parse_sections(&file).unwrap();
+        assert_eq!(guided_section_guid(&sections[2]).map(|g| guid_to_bytes(&g)[0]), Some(0xAB));
+        assert_eq!(guided_section_guid(&sections[0]), None);
+
+        // Sections needing processing are left alone
+        LittleEndian::write_u16(&mut guided[18..20], EFI_GUIDED_SECTION_PROCESSING_REQUIRED);
+        let file = section(EFI_SECTION_GUID_DEFINED, &guided);
+        assert_eq!(extract_section(&file, SectionType::UserInterface, 0).unwrap_err().kind(), EfiErrorKind::NotFound);
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let mut file = section(EFI_SECTION_RAW, b"leaf");
+        for _ in 0..MAX_SECTION_DEPTH + 1 {
+            let mut compression = vec![0u8; 5];
+            LittleEndian::write_u32(&mut compression[0..4], file.len() as u32);
+            compression[4] = EFI_NOT_COMPRESSED;
+            compression.extend_from_slice(&file);
+            file = section(EFI_SECTION_COMPRESSION, &compression);
+        }
+
+        assert_eq!(extract_section(&file, SectionType::Raw, 0).unwrap_err().kind(), EfiErrorKind::VolumeCorrupted);
+    }
+}
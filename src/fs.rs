@@ -0,0 +1,804 @@
+use ffi::{
+    media::{
+        EFI_SIMPLE_FILE_SYSTEM_PROTOCOL,
+        EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID,
+        EFI_FILE_PROTOCOL,
+        EFI_FILE_MODE_READ,
+        EFI_FILE_MODE_WRITE,
+        EFI_FILE_MODE_CREATE,
+        EFI_FILE_READ_ONLY,
+        EFI_FILE_HIDDEN,
+        EFI_FILE_SYSTEM,
+        EFI_FILE_DIRECTORY,
+        EFI_FILE_ARCHIVE,
+        EFI_FILE_INFO_ID,
+        EFI_FILE_SYSTEM_INFO_ID,
+        EFI_FILE_SYSTEM_VOLUME_LABEL_ID,
+    },
+    device_path::{
+        EFI_DEVICE_PATH_PROTOCOL,
+        MEDIA_DEVICE_PATH,
+        MEDIA_HARDDRIVE_DP,
+        MEDIA_FILEPATH_DP,
+        END_DEVICE_PATH_TYPE,
+    },
+    IsSuccess,
+    EFI_HANDLE,
+    EFI_GUID,
+    EFI_TIME,
+    EFI_BUFFER_TOO_SMALL,
+    UINT64,
+    UINTN,
+    VOID,
+};
+use io::{self, Read, Write, Seek, SeekFrom};
+use image::{Len, LoadedImage};
+use utils::{to_null_terminated_utf16, handles_by_protocol, open_protocol};
+use device_path::{DevicePath, device_path_of, node_len, DEV_PATH_NODE_HEADER_SIZE};
+#[cfg(feature = "shell")] use shell::Shell;
+use time::DateTime;
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec};
+use core::{ptr, mem};
+use {Result, EfiError, EfiErrorKind, EfiWarning, WithWarning, to_res_with_warning, boot_services, image_handle};
+
+/// The ways in which a file can be opened. These are the only combinations the UEFI spec allows.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OpenMode {
+    Read,
+    ReadWrite,
+    /// Opens the file for reading and writing, creating it first if it does not exist
+    CreateReadWrite,
+}
+
+impl OpenMode {
+    fn bits(&self) -> UINT64 {
+        match *self {
+            OpenMode::Read => EFI_FILE_MODE_READ,
+            OpenMode::ReadWrite => EFI_FILE_MODE_READ | EFI_FILE_MODE_WRITE,
+            OpenMode::CreateReadWrite => EFI_FILE_MODE_READ | EFI_FILE_MODE_WRITE | EFI_FILE_MODE_CREATE,
+        }
+    }
+}
+
+flags! {
+    /// Attributes of a file.
+    /// Can be combined using the `|` operator e.g. `FileAttributes::HIDDEN | FileAttributes::READ_ONLY`
+    pub struct FileAttributes(UINT64) {
+        const READ_ONLY = EFI_FILE_READ_ONLY;
+        const HIDDEN = EFI_FILE_HIDDEN;
+        const SYSTEM = EFI_FILE_SYSTEM;
+        const DIRECTORY = EFI_FILE_DIRECTORY;
+        const ARCHIVE = EFI_FILE_ARCHIVE;
+    }
+}
+
+/// Information about a file or directory as kept in EFI_FILE_INFO
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub file_name: String,
+    /// Size of the file in bytes
+    pub size: u64,
+    /// Space the file takes up on the volume. Ignored by `File::set_metadata()`.
+    pub physical_size: u64,
+    pub created: DateTime,
+    pub accessed: DateTime,
+    pub modified: DateTime,
+    pub attributes: FileAttributes,
+}
+
+// Offset of FileName in EFI_FILE_INFO
+const FILE_INFO_NAME_OFFSET: usize = 80;
+
+impl Metadata {
+    pub fn is_dir(&self) -> bool {
+        self.attributes.contains(FileAttributes::DIRECTORY)
+    }
+
+    /// Parses an EFI_FILE_INFO structure
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < FILE_INFO_NAME_OFFSET {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        Ok(Metadata {
+            file_name: string_from_utf16_bytes(&bytes[FILE_INFO_NAME_OFFSET..])?,
+            size: LittleEndian::read_u64(&bytes[8..16]),
+            physical_size: LittleEndian::read_u64(&bytes[16..24]),
+            created: time_from_bytes(&bytes[24..40]),
+            accessed: time_from_bytes(&bytes[40..56]),
+            modified: time_from_bytes(&bytes[56..72]),
+            attributes: FileAttributes(LittleEndian::read_u64(&bytes[72..80])),
+        })
+    }
+
+    /// Serializes to an EFI_FILE_INFO structure
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = to_null_terminated_utf16(&self.file_name);
+        let mut bytes = vec![0_u8; FILE_INFO_NAME_OFFSET + name.len() * 2];
+        let total_size = bytes.len() as u64;
+        LittleEndian::write_u64(&mut bytes[0..8], total_size);
+        LittleEndian::write_u64(&mut bytes[8..16], self.size);
+        LittleEndian::write_u64(&mut bytes[16..24], self.physical_size);
+        time_to_bytes(&self.created, &mut bytes[24..40]);
+        time_to_bytes(&self.accessed, &mut bytes[40..56]);
+        time_to_bytes(&self.modified, &mut bytes[56..72]);
+        LittleEndian::write_u64(&mut bytes[72..80], self.attributes.bits());
+        for (i, c) in name.iter().enumerate() {
+            let offset = FILE_INFO_NAME_OFFSET + i * 2;
+            LittleEndian::write_u16(&mut bytes[offset..offset + 2], *c);
+        }
+        bytes
+    }
+}
+
+/// Information about a volume as kept in EFI_FILE_SYSTEM_INFO
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeInfo {
+    pub label: String,
+    pub read_only: bool,
+    /// Size of the volume in bytes
+    pub volume_size: u64,
+    /// Free space on the volume in bytes
+    pub free_space: u64,
+    /// Size of the volume's blocks in bytes
+    pub block_size: u32,
+}
+
+// Offset of VolumeLabel in EFI_FILE_SYSTEM_INFO
+const FILE_SYSTEM_INFO_LABEL_OFFSET: usize = 36;
+
+impl VolumeInfo {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < FILE_SYSTEM_INFO_LABEL_OFFSET {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        Ok(VolumeInfo {
+            label: string_from_utf16_bytes(&bytes[FILE_SYSTEM_INFO_LABEL_OFFSET..])?,
+            read_only: bytes[8] != 0,
+            volume_size: LittleEndian::read_u64(&bytes[16..24]),
+            free_space: LittleEndian::read_u64(&bytes[24..32]),
+            block_size: LittleEndian::read_u32(&bytes[32..36]),
+        })
+    }
+}
+
+/// A volume exposed by the firmware through EFI_SIMPLE_FILE_SYSTEM_PROTOCOL (e.g. the EFI system partition)
+pub struct Volume {
+    root: File,
+}
+
+impl Volume {
+    /// Opens the volume on the given handle. The handle must support EFI_SIMPLE_FILE_SYSTEM_PROTOCOL.
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let bs = boot_services();
+        let current_image_handle = image_handle();
+
+        let fs = open_protocol::<EFI_SIMPLE_FILE_SYSTEM_PROTOCOL>(handle, &EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID)?;
+        unsafe {
+            let mut root: *const EFI_FILE_PROTOCOL = ptr::null();
+            let status = ((*fs).OpenVolume)(fs, &mut root);
+            ret_on_err!(((*bs).CloseProtocol)(handle, &EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID, current_image_handle, ptr::null()));
+            ret_on_err!(status);
+
+            Ok(Volume { root: File(root as *mut EFI_FILE_PROTOCOL) })
+        }
+    }
+
+    /// Opens the volume from which the running image was loaded
+    pub fn of_current_image() -> Result<Self> {
+        Self::from_handle(LoadedImage::current()?.device_handle())
+    }
+
+    /// Opens all the volumes in the system
+    pub fn all() -> Result<Vec<Self>> {
+        file_system_handles()?.into_iter().map(Self::from_handle).collect()
+    }
+
+    /// Opens the volume with the given shell mapping e.g. "fs0" or "fs0:".
+    /// The shell's mappings are used if the shell protocol is present (and the `shell` feature is enabled). Otherwise only "fsN" mappings can be resolved
+    /// and they are taken to mean the Nth file system handle, which may not match the numbering the shell would have used.
+    pub fn from_mapping(mapping: &str) -> Result<Self> {
+        let mapping = mapping.trim_right_matches(':');
+        #[cfg(feature = "shell")]
+        {
+            if let Some(path) = Shell::get().ok().and_then(|shell| shell.device_path_from_map(mapping).ok()) {
+                return Self::from_device_path(&path).map(|(volume, _)| volume);
+            }
+        }
+
+        let handle = fs_mapping_index(mapping).and_then(|i| file_system_handles().ok().and_then(|h| h.get(i).cloned()));
+        match handle {
+            Some(handle) => Self::from_handle(handle),
+            None => Err(EfiErrorKind::NotFound.into()),
+        }
+    }
+
+    /// Finds the volume a device path points into, returning it along with the path of the file
+    /// within the volume made up by the path's trailing file path nodes (if any).
+    /// Short-form paths that start at a hard drive node, as found in boot options, are supported.
+    pub fn from_device_path(path: &DevicePath) -> Result<(Self, String)> {
+        let bs = boot_services();
+        let bytes = path.as_bytes();
+
+        let mut remaining: *const EFI_DEVICE_PATH_PROTOCOL = path.as_ptr();
+        let mut handle: EFI_HANDLE = ptr::null_mut();
+        let status = unsafe { ((*bs).LocateDevicePath)(&EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID, &mut remaining, &mut handle) };
+        if IsSuccess(status) {
+            let offset = remaining as usize - path.as_ptr() as usize;
+            return Ok((Self::from_handle(handle)?, file_path_from_nodes(&bytes[offset..])?));
+        }
+
+        if let Some(first_node) = first_node(bytes) {
+            if first_node[0] == MEDIA_DEVICE_PATH && first_node[1] == MEDIA_HARDDRIVE_DP {
+                for handle in file_system_handles()? {
+                    let matches = device_path_of(handle).map(|p| last_node(p.as_bytes()) == Some(first_node)).unwrap_or(false);
+                    if matches {
+                        return Ok((Self::from_handle(handle)?, file_path_from_nodes(&bytes[first_node.len()..])?));
+                    }
+                }
+            }
+        }
+
+        Err(EfiErrorKind::NotFound.into())
+    }
+
+    /// The root directory of the volume
+    pub fn root(&self) -> &File {
+        &self.root
+    }
+
+    /// Returns the label, size and free space of the volume
+    pub fn info(&self) -> Result<VolumeInfo> {
+        VolumeInfo::parse(&self.root.get_info(&EFI_FILE_SYSTEM_INFO_ID)?)
+    }
+
+    /// Changes the label of the volume
+    pub fn set_label(&mut self, label: &str) -> Result<()> {
+        let mut bytes = Vec::new();
+        for c in to_null_terminated_utf16(label) {
+            let mut buf = [0_u8; 2];
+            LittleEndian::write_u16(&mut buf, c);
+            bytes.extend_from_slice(&buf);
+        }
+
+        self.root.set_info(&EFI_FILE_SYSTEM_VOLUME_LABEL_ID, &bytes)
+    }
+
+    /// Opens a file or directory. The path is relative to the root of the volume.
+    /// Both '\' and '/' are accepted as separators.
+    pub fn open(&self, path: &str, mode: OpenMode, attributes: FileAttributes) -> Result<File> {
+        self.root.open(path, mode, attributes)
+    }
+
+    /// Reads the entire contents of a file
+    pub fn read(&self, path: &str) -> Result<Vec<u8>> {
+        let mut file = self.open(path, OpenMode::Read, FileAttributes::empty())?;
+        let size = file.metadata()?.size as usize;
+        let mut contents = vec![0_u8; size];
+        let mut filled = 0;
+        while filled < size {
+            let read = file.read_buf(&mut contents[filled..])?;
+            if read == 0 {
+                break; // The file shrank since we got its size
+            }
+            filled += read;
+        }
+
+        contents.truncate(filled);
+        Ok(contents)
+    }
+
+    /// Writes the contents to a file, creating it if it does not exist and replacing its contents if it does
+    pub fn write(&self, path: &str, contents: &[u8]) -> Result<()> {
+        let mut file = self.open(path, OpenMode::CreateReadWrite, FileAttributes::empty())?;
+        file.set_len(0)?;
+        let mut written = 0;
+        while written < contents.len() {
+            match file.write_buf(&contents[written..])? {
+                0 => return Err(EfiError::from(EfiErrorKind::VolumeFull).add_context("the file system stopped accepting data")),
+                n => written += n,
+            }
+        }
+
+        file.flush_buf()
+    }
+
+    /// Creates a directory along with any missing parent directories.
+    /// Fails with `AccessDenied` if a component of the path exists but is not a directory.
+    pub fn create_dir_all(&self, path: &str) -> Result<()> {
+        let path = to_uefi_path(path);
+        let mut dir_path = String::new();
+        for component in path.split('\\').filter(|c| !c.is_empty()) {
+            dir_path.push('\\');
+            dir_path.push_str(component);
+            let dir = self.open(&dir_path, OpenMode::CreateReadWrite, FileAttributes::DIRECTORY)?;
+            if !dir.metadata()?.is_dir() {
+                return Err(EfiErrorKind::AccessDenied.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes a file or an empty directory
+    pub fn remove_file(&self, path: &str) -> Result<()> {
+        let deleted = self.open(path, OpenMode::ReadWrite, FileAttributes::empty())?.delete()?;
+        if deleted.warning == Some(EfiWarning::DeleteFailure) {
+            return Err(EfiError::from(EfiErrorKind::AccessDenied).add_context("the file was closed but not deleted"));
+        }
+
+        Ok(())
+    }
+
+    /// Renames or moves a file within the volume. The destination's directory must already exist.
+    pub fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let mut file = self.open(from, OpenMode::ReadWrite, FileAttributes::empty())?;
+        let mut metadata = file.metadata()?;
+        let to = to_uefi_path(to);
+        // A file name starting with a '\' is taken by SetInfo() as a path from the root of the volume
+        metadata.file_name = if to.starts_with('\\') { to } else { format!("\\{}", to) };
+        file.set_metadata(&metadata)
+    }
+}
+
+/// Opens a file given a path that can be:
+/// - a mapped path such as `fs0:\EFI\BOOT\grub.cfg`. See `Volume::from_mapping()` for how mappings are resolved
+/// - the text form of a device path ending in file path nodes e.g. `HD(1,GPT,...)/\EFI\BOOT\BOOTX64.EFI`
+/// - a plain path such as `\EFI\BOOT\grub.cfg` which refers to the volume hosting the running image
+pub fn open(path: &str, mode: OpenMode, attributes: FileAttributes) -> Result<File> {
+    let (volume, path) = resolve(path)?;
+    volume.open(&path, mode, attributes)
+}
+
+/// Reads the entire contents of a file. The path can be of any form accepted by `open()`.
+pub fn read(path: &str) -> Result<Vec<u8>> {
+    let (volume, path) = resolve(path)?;
+    volume.read(&path)
+}
+
+/// Writes a file. See `Volume::write()`. The path can be of any form accepted by `open()`.
+pub fn write(path: &str, contents: &[u8]) -> Result<()> {
+    let (volume, path) = resolve(path)?;
+    volume.write(&path, contents)
+}
+
+/// Creates a directory and its parents. The path can be of any form accepted by `open()`.
+pub fn create_dir_all(path: &str) -> Result<()> {
+    let (volume, path) = resolve(path)?;
+    volume.create_dir_all(&path)
+}
+
+/// Deletes a file or empty directory. The path can be of any form accepted by `open()`.
+pub fn remove_file(path: &str) -> Result<()> {
+    let (volume, path) = resolve(path)?;
+    volume.remove_file(&path)
+}
+
+/// Renames a file. `from` can be of any form accepted by `open()` while `to` is a path on the same volume.
+pub fn rename(from: &str, to: &str) -> Result<()> {
+    let (volume, from) = resolve(from)?;
+    volume.rename(&from, to)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PathSpec<'a> {
+    Mapped(&'a str, &'a str),
+    DevicePath(&'a str),
+    Plain(&'a str),
+}
+
+// Tells apart the different kinds of paths accepted by open().
+// The first component of a device path in text form always has parentheses in it e.g. "PciRoot(0x0)".
+fn parse_path_spec(path: &str) -> PathSpec {
+    let first_separator = path.find(|c| c == '\\' || c == '/').unwrap_or(path.len());
+    let head = &path[..first_separator];
+    if head.contains('(') && head.ends_with(')') {
+        return PathSpec::DevicePath(path);
+    }
+
+    if let Some(colon) = head.find(':') {
+        let mapping = &head[..colon];
+        if !mapping.is_empty() && mapping.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return PathSpec::Mapped(mapping, &path[colon + 1..]);
+        }
+    }
+
+    PathSpec::Plain(path)
+}
+
+fn resolve(path: &str) -> Result<(Volume, String)> {
+    match parse_path_spec(path) {
+        PathSpec::Mapped(mapping, path) => Ok((Volume::from_mapping(mapping)?, path.into())),
+        PathSpec::DevicePath(text) => Volume::from_device_path(&DevicePath::from_text(text)?),
+        PathSpec::Plain(path) => Ok((Volume::of_current_image()?, path.into())),
+    }
+}
+
+// The N in "fsN"
+fn fs_mapping_index(mapping: &str) -> Option<usize> {
+    if mapping.len() > 2 && mapping[..2].eq_ignore_ascii_case("fs") {
+        mapping[2..].parse().ok()
+    } else {
+        None
+    }
+}
+
+pub(crate) fn file_system_handles() -> Result<Vec<EFI_HANDLE>> {
+    handles_by_protocol(&EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID)
+}
+
+fn first_node(bytes: &[u8]) -> Option<&[u8]> {
+    node_len(bytes).map(|len| &bytes[..len])
+}
+
+// The last node before the end node
+fn last_node(bytes: &[u8]) -> Option<&[u8]> {
+    let mut offset = 0;
+    let mut last = None;
+    while let Some(len) = node_len(&bytes[offset..]) {
+        if bytes[offset] == END_DEVICE_PATH_TYPE {
+            break;
+        }
+
+        last = Some(&bytes[offset..offset + len]);
+        offset += len;
+    }
+
+    last
+}
+
+// Joins the file path nodes at the start of the bytes into a single path
+fn file_path_from_nodes(bytes: &[u8]) -> Result<String> {
+    let mut path = String::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let len = node_len(&bytes[offset..]).ok_or_else(|| EfiError::from(EfiErrorKind::InvalidParameter))?;
+        let node = &bytes[offset..offset + len];
+        if node[0] == END_DEVICE_PATH_TYPE {
+            break;
+        }
+
+        if node[0] != MEDIA_DEVICE_PATH || node[1] != MEDIA_FILEPATH_DP {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let segment = string_from_utf16_bytes(&node[DEV_PATH_NODE_HEADER_SIZE..])?;
+        let segment = segment.trim_matches('\\');
+        if !segment.is_empty() {
+            path.push('\\');
+            path.push_str(segment);
+        }
+
+        offset += len;
+    }
+
+    Ok(path)
+}
+
+/// An open file or directory on a `Volume`. Closed when dropped.
+pub struct File(*mut EFI_FILE_PROTOCOL);
+
+impl File {
+    /// Opens a file or directory relative to this directory.
+    /// `attributes` are only used when a new file is created. To create a directory pass `FileAttributes::DIRECTORY`.
+    pub fn open(&self, path: &str, mode: OpenMode, attributes: FileAttributes) -> Result<File> {
+        let path = to_null_terminated_utf16(&to_uefi_path(path));
+        let mut new_handle: *const EFI_FILE_PROTOCOL = ptr::null();
+        unsafe {
+            ret_on_err!(((*self.0).Open)(self.0, &mut new_handle, path.as_ptr(), mode.bits(), attributes.bits()));
+        }
+
+        Ok(File(new_handle as *mut EFI_FILE_PROTOCOL))
+    }
+
+    /// Deletes the file. The file must have been opened for writing.
+    /// If the handle was closed but the file could not be deleted the warning is `EfiWarning::DeleteFailure`.
+    pub fn delete(self) -> Result<WithWarning<()>> {
+        let status = unsafe { ((*self.0).Delete)(self.0) };
+        mem::forget(self); // Delete() closes the handle even if it fails
+        to_res_with_warning((), status)
+    }
+
+    /// Closes the file. Unlike dropping it this reports `EfiWarning::WriteFailure`
+    /// if the handle was closed but the data could not be flushed.
+    pub fn close(self) -> Result<WithWarning<()>> {
+        let status = unsafe { ((*self.0).Close)(self.0) };
+        mem::forget(self);
+        to_res_with_warning((), status)
+    }
+
+    /// The current position in the file
+    pub fn position(&self) -> Result<u64> {
+        let mut position: UINT64 = 0;
+        unsafe {
+            ret_on_err!(((*self.0).GetPosition)(self.0, &mut position));
+        }
+
+        Ok(position)
+    }
+
+    /// Sets the position in the file. Setting it beyond the end of the file is allowed and
+    /// causes the file to grow on the next write. Not supported on directories except for rewinding to 0.
+    pub fn set_position(&mut self, position: u64) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.0).SetPosition)(self.0, position));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the metadata of the file
+    pub fn metadata(&self) -> Result<Metadata> {
+        Metadata::parse(&self.get_info(&EFI_FILE_INFO_ID)?)
+    }
+
+    /// Updates the metadata of the file. This can be used to:
+    /// - change the size of the file, truncating it or extending it with zeros
+    /// - change its attributes except for `DIRECTORY` which cannot be changed
+    /// - change its timestamps. All three are written, so read the metadata first to keep the ones not being changed
+    /// - rename it within the same directory by changing `file_name`
+    ///
+    /// The file must have been opened for writing.
+    pub fn set_metadata(&mut self, metadata: &Metadata) -> Result<()> {
+        self.set_info(&EFI_FILE_INFO_ID, &metadata.to_bytes())
+    }
+
+    /// Truncates or extends the file to the given size
+    pub fn set_len(&mut self, size: u64) -> Result<()> {
+        let mut metadata = self.metadata()?;
+        metadata.size = size;
+        self.set_metadata(&metadata)
+    }
+
+    fn get_info(&self, info_type: &EFI_GUID) -> Result<Vec<u8>> {
+        let mut size: UINTN = 0;
+        let status = unsafe { ((*self.0).GetInfo)(self.0, info_type, &mut size, ptr::null_mut()) };
+        if status != EFI_BUFFER_TOO_SMALL {
+            ret_on_err!(status);
+        }
+
+        let mut info = vec![0_u8; size];
+        unsafe {
+            ret_on_err!(((*self.0).GetInfo)(self.0, info_type, &mut size, info.as_mut_ptr() as *mut VOID));
+        }
+
+        info.truncate(size);
+        Ok(info)
+    }
+
+    fn set_info(&mut self, info_type: &EFI_GUID, info: &[u8]) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.0).SetInfo)(self.0, info_type, info.len(), info.as_ptr() as *const VOID));
+        }
+
+        Ok(())
+    }
+
+    fn read_buf(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut size: UINTN = buf.len();
+        unsafe {
+            ret_on_err!(((*self.0).Read)(self.0, &mut size, buf.as_mut_ptr() as *mut VOID));
+        }
+
+        Ok(size)
+    }
+
+    fn write_buf(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut size: UINTN = buf.len();
+        unsafe {
+            ret_on_err!(((*self.0).Write)(self.0, &mut size, buf.as_ptr() as *const VOID));
+        }
+
+        Ok(size)
+    }
+
+    fn flush_buf(&mut self) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.0).Flush)(self.0));
+        }
+
+        Ok(())
+    }
+
+    // Returns the size of the file leaving the position unchanged
+    fn size(&mut self) -> Result<u64> {
+        let position = self.position()?;
+        self.set_position(END_OF_FILE)?;
+        let size = self.position()?;
+        self.set_position(position)?;
+        Ok(size)
+    }
+}
+
+// Passing this to SetPosition() moves to the end of the file
+const END_OF_FILE: u64 = 0xFFFFFFFFFFFFFFFF;
+
+impl Drop for File {
+    fn drop(&mut self) {
+        unsafe {
+            ((*self.0).Close)(self.0);
+        }
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_buf(buf).map_err(io::Error::from)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_buf(buf).map_err(io::Error::from)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf().map_err(io::Error::from)
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.set_position(position)?;
+                return Ok(position);
+            },
+            SeekFrom::End(offset) => (self.size()?, offset),
+            SeekFrom::Current(offset) => (self.position()?, offset),
+        };
+
+        let position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+
+        match position {
+            Some(position) => {
+                self.set_position(position)?;
+                Ok(position)
+            },
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+        }
+    }
+}
+
+impl Len for File {
+    fn len(&mut self) -> Result<Option<u64>> {
+        self.size().map(Some)
+    }
+}
+
+// EFI_TIME is 16 bytes long
+fn time_from_bytes(bytes: &[u8]) -> DateTime {
+    let mut time = EFI_TIME::zero();
+    time.Year = LittleEndian::read_u16(&bytes[0..2]);
+    time.Month = bytes[2];
+    time.Day = bytes[3];
+    time.Hour = bytes[4];
+    time.Minute = bytes[5];
+    time.Second = bytes[6];
+    time.Nanosecond = LittleEndian::read_u32(&bytes[8..12]);
+    time.TimeZone = LittleEndian::read_i16(&bytes[12..14]);
+    time.Daylight = bytes[14];
+    time.into()
+}
+
+fn time_to_bytes(date_time: &DateTime, bytes: &mut [u8]) {
+    let time: EFI_TIME = (*date_time).into();
+    LittleEndian::write_u16(&mut bytes[0..2], time.Year);
+    bytes[2] = time.Month;
+    bytes[3] = time.Day;
+    bytes[4] = time.Hour;
+    bytes[5] = time.Minute;
+    bytes[6] = time.Second;
+    LittleEndian::write_u32(&mut bytes[8..12], time.Nanosecond);
+    LittleEndian::write_i16(&mut bytes[12..14], time.TimeZone);
+    bytes[14] = time.Daylight;
+}
+
+// Reads a null-terminated UTF-16 string stored in little endian bytes.
+// The string runs to the end of the buffer if there is no terminator.
+fn string_from_utf16_bytes(bytes: &[u8]) -> Result<String> {
+    let chars = bytes.chunks(2)
+        .filter(|c| c.len() == 2)
+        .map(|c| LittleEndian::read_u16(c))
+        .take_while(|c| *c != 0)
+        .collect::<Vec<u16>>();
+    String::from_utf16(&chars).map_err(|_| EfiErrorKind::VolumeCorrupted.into())
+}
+
+fn to_uefi_path(path: &str) -> String {
+    path.replace('/', "\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_roundtrip() {
+        let mut modified = DateTime::new(2018, 4, 1, 13, 5, 59);
+        modified.time_zone = Some(-300);
+        let metadata = Metadata {
+            file_name: "grub.cfg".into(),
+            size: 4242,
+            physical_size: 8192,
+            created: DateTime::new(2017, 12, 31, 23, 59, 0),
+            accessed: DateTime::new(2018, 4, 2, 0, 0, 0),
+            modified,
+            attributes: FileAttributes::ARCHIVE | FileAttributes::HIDDEN,
+        };
+
+        let bytes = metadata.to_bytes();
+        assert_eq!(bytes.len(), FILE_INFO_NAME_OFFSET + 9 * 2);
+        assert_eq!(LittleEndian::read_u64(&bytes[0..8]), bytes.len() as u64);
+        assert_eq!(Metadata::parse(&bytes).unwrap(), metadata);
+    }
+
+    #[test]
+    fn parse_directory_entry() {
+        let mut bytes = vec![0_u8; FILE_INFO_NAME_OFFSET];
+        LittleEndian::write_u64(&mut bytes[72..80], EFI_FILE_DIRECTORY);
+        bytes.extend_from_slice(&[b'E', 0, b'F', 0, b'I', 0, 0, 0]);
+
+        let metadata = Metadata::parse(&bytes).unwrap();
+        assert!(metadata.is_dir());
+        assert_eq!(metadata.file_name, "EFI");
+    }
+
+    #[test]
+    fn parse_volume_info() {
+        let mut bytes = vec![0_u8; FILE_SYSTEM_INFO_LABEL_OFFSET];
+        bytes[8] = 1;
+        LittleEndian::write_u64(&mut bytes[16..24], 512 * 1024 * 1024);
+        LittleEndian::write_u64(&mut bytes[24..32], 100 * 1024 * 1024);
+        LittleEndian::write_u32(&mut bytes[32..36], 4096);
+        bytes.extend_from_slice(&[b'E', 0, b'S', 0, b'P', 0, 0, 0]);
+
+        let info = VolumeInfo::parse(&bytes).unwrap();
+        assert_eq!(info, VolumeInfo { label: "ESP".into(), read_only: true, volume_size: 512 * 1024 * 1024, free_space: 100 * 1024 * 1024, block_size: 4096 });
+    }
+
+    #[test]
+    fn path_specs() {
+        assert_eq!(parse_path_spec("fs0:\\EFI\\BOOT\\grub.cfg"), PathSpec::Mapped("fs0", "\\EFI\\BOOT\\grub.cfg"));
+        assert_eq!(parse_path_spec("FS12:/boot.cfg"), PathSpec::Mapped("FS12", "/boot.cfg"));
+        assert_eq!(parse_path_spec("HD(1,GPT,0x800)/\\EFI\\x.efi"), PathSpec::DevicePath("HD(1,GPT,0x800)/\\EFI\\x.efi"));
+        assert_eq!(parse_path_spec("\\EFI\\BOOT\\grub.cfg"), PathSpec::Plain("\\EFI\\BOOT\\grub.cfg"));
+        assert_eq!(parse_path_spec("EFI/a:b"), PathSpec::Plain("EFI/a:b"));
+        assert_eq!(fs_mapping_index("fs3"), Some(3));
+        assert_eq!(fs_mapping_index("blk0"), None);
+    }
+
+    #[test]
+    fn file_path_nodes() {
+        fn file_node(path: &str) -> Vec<u8> {
+            let name = to_null_terminated_utf16(path);
+            let mut node = vec![MEDIA_DEVICE_PATH, MEDIA_FILEPATH_DP, 0, 0];
+            LittleEndian::write_u16(&mut node[2..4], (DEV_PATH_NODE_HEADER_SIZE + name.len() * 2) as u16);
+            for c in name {
+                node.push(c as u8);
+                node.push((c >> 8) as u8);
+            }
+            node
+        }
+
+        let mut bytes = file_node("\\EFI\\");
+        bytes.extend(file_node("BOOT"));
+        bytes.extend(file_node("\\BOOTX64.EFI"));
+        bytes.extend_from_slice(&[END_DEVICE_PATH_TYPE, 0xFF, 4, 0]);
+
+        assert_eq!(file_path_from_nodes(&bytes).unwrap(), "\\EFI\\BOOT\\BOOTX64.EFI");
+        assert_eq!(last_node(&bytes), Some(&file_node("\\BOOTX64.EFI")[..]));
+        assert_eq!(file_path_from_nodes(&[1, 1, 6, 0, 0, 0]).unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+    }
+
+    #[test]
+    fn parse_truncated_info() {
+        assert_eq!(Metadata::parse(&[0_u8; 40]).unwrap_err().kind(), EfiErrorKind::BadBufferSize);
+    }
+}
@@ -0,0 +1,571 @@
+//! Reading and editing GUID Partition Tables (GPT) as laid out in chapter 5 of the UEFI spec
+
+use storage::BlockDevice;
+use ffi::EFI_GUID;
+use utils::{guid_to_bytes, guid_from_bytes};
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{Vec, String};
+use core::cmp;
+use {Result, EfiErrorKind, Guid};
+
+pub const PARTITION_TYPE_UNUSED: Guid = EFI_GUID(0x00000000, 0x0000, 0x0000, [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+pub const PARTITION_TYPE_EFI_SYSTEM: Guid = EFI_GUID(0xC12A7328, 0xF81F, 0x11D2, [0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B]);
+pub const PARTITION_TYPE_LEGACY_MBR: Guid = EFI_GUID(0x024DEE41, 0x33E7, 0x11D3, [0x9D, 0x69, 0x00, 0x08, 0xC7, 0x81, 0xF3, 0x9F]);
+pub const PARTITION_TYPE_BASIC_DATA: Guid = EFI_GUID(0xEBD0A0A2, 0xB9E5, 0x4433, [0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26, 0x99, 0xC7]);
+pub const PARTITION_TYPE_LINUX_FILESYSTEM: Guid = EFI_GUID(0x0FC63DAF, 0x8483, 0x4772, [0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D, 0xE4]);
+
+const SIGNATURE: &[u8; 8] = b"EFI PART";
+const REVISION: u32 = 0x00010000;
+const HEADER_SIZE: usize = 92;
+const ENTRY_SIZE: usize = 128;
+const DEFAULT_NO_OF_ENTRIES: u32 = 128;
+// Keeps a corrupt header from making us allocate an absurd amount of memory
+const MAX_ENTRY_ARRAY_SIZE: usize = 1024 * 1024;
+const NAME_LEN: usize = 36; // In UTF-16 code units
+const PROTECTIVE_MBR_OS_TYPE: u8 = 0xEE;
+const MBR_SIZE: usize = 512;
+const MBR_PARTITION_RECORD_OFFSET: usize = 446;
+
+flags! {
+    /// Attribute bits of a partition entry. Bits 48-63 are reserved for the partition type's own use.
+    pub struct PartitionAttributes(u64) {
+        /// The partition is required for the platform to function and must not be deleted or modified
+        const REQUIRED = 1 << 0;
+        /// Firmware must not produce EFI_BLOCK_IO_PROTOCOL for the partition
+        const NO_BLOCK_IO_PROTOCOL = 1 << 1;
+        const LEGACY_BIOS_BOOTABLE = 1 << 2;
+    }
+}
+
+/// A partition entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partition {
+    pub type_guid: Guid,
+    pub unique_guid: Guid,
+    pub first_lba: u64,
+    /// Inclusive
+    pub last_lba: u64,
+    pub attributes: PartitionAttributes,
+    /// At most 36 UTF-16 code units
+    pub name: String,
+}
+
+impl Partition {
+    /// Size of the partition in blocks
+    pub fn block_count(&self) -> u64 {
+        self.last_lba - self.first_lba + 1
+    }
+
+    // None for an unused entry
+    fn parse(bytes: &[u8]) -> Result<Option<Self>> {
+        let type_guid = guid_from_bytes(&bytes[0..16]);
+        if type_guid == PARTITION_TYPE_UNUSED {
+            return Ok(None);
+        }
+
+        let first_lba = LittleEndian::read_u64(&bytes[32..40]);
+        let last_lba = LittleEndian::read_u64(&bytes[40..48]);
+        if first_lba > last_lba {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        let name = bytes[56..56 + NAME_LEN * 2].chunks(2)
+            .map(|c| LittleEndian::read_u16(c))
+            .take_while(|c| *c != 0)
+            .collect::<Vec<_>>();
+
+        Ok(Some(Partition {
+            type_guid,
+            unique_guid: guid_from_bytes(&bytes[16..32]),
+            first_lba,
+            last_lba,
+            attributes: PartitionAttributes(LittleEndian::read_u64(&bytes[48..56])),
+            name: String::from_utf16_lossy(&name),
+        }))
+    }
+
+    fn write_to(&self, bytes: &mut [u8]) {
+        bytes[0..16].copy_from_slice(&guid_to_bytes(&self.type_guid));
+        bytes[16..32].copy_from_slice(&guid_to_bytes(&self.unique_guid));
+        LittleEndian::write_u64(&mut bytes[32..40], self.first_lba);
+        LittleEndian::write_u64(&mut bytes[40..48], self.last_lba);
+        LittleEndian::write_u64(&mut bytes[48..56], self.attributes.0);
+        for (i, c) in self.name.encode_utf16().take(NAME_LEN).enumerate() {
+            LittleEndian::write_u16(&mut bytes[56 + i * 2..58 + i * 2], c);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Header {
+    my_lba: u64,
+    alternate_lba: u64,
+    first_usable_lba: u64,
+    last_usable_lba: u64,
+    disk_guid: Guid,
+    entries_lba: u64,
+    no_of_entries: u32,
+    entry_size: u32,
+    entries_crc: u32,
+}
+
+impl Header {
+    fn parse(block: &[u8], lba: u64) -> Result<Self> {
+        if block.len() < HEADER_SIZE || &block[0..8] != SIGNATURE {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        let header_size = LittleEndian::read_u32(&block[12..16]) as usize;
+        if header_size < HEADER_SIZE || header_size > block.len() {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        let mut header_bytes = block[..header_size].to_vec();
+        let crc = LittleEndian::read_u32(&header_bytes[16..20]);
+        LittleEndian::write_u32(&mut header_bytes[16..20], 0);
+        if crc32(&header_bytes) != crc {
+            return Err(EfiErrorKind::CrcError.into());
+        }
+
+        let header = Header {
+            my_lba: LittleEndian::read_u64(&block[24..32]),
+            alternate_lba: LittleEndian::read_u64(&block[32..40]),
+            first_usable_lba: LittleEndian::read_u64(&block[40..48]),
+            last_usable_lba: LittleEndian::read_u64(&block[48..56]),
+            disk_guid: guid_from_bytes(&block[56..72]),
+            entries_lba: LittleEndian::read_u64(&block[72..80]),
+            no_of_entries: LittleEndian::read_u32(&block[80..84]),
+            entry_size: LittleEndian::read_u32(&block[84..88]),
+            entries_crc: LittleEndian::read_u32(&block[88..92]),
+        };
+
+        if header.my_lba != lba
+            || header.entry_size < ENTRY_SIZE as u32
+            || header.entry_size % 8 != 0
+            || header.entries_size() > MAX_ENTRY_ARRAY_SIZE
+            || header.first_usable_lba > header.last_usable_lba {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        Ok(header)
+    }
+
+    fn to_bytes(&self, block_size: usize) -> Vec<u8> {
+        let mut block = vec![0_u8; block_size];
+        block[0..8].copy_from_slice(SIGNATURE);
+        LittleEndian::write_u32(&mut block[8..12], REVISION);
+        LittleEndian::write_u32(&mut block[12..16], HEADER_SIZE as u32);
+        LittleEndian::write_u64(&mut block[24..32], self.my_lba);
+        LittleEndian::write_u64(&mut block[32..40], self.alternate_lba);
+        LittleEndian::write_u64(&mut block[40..48], self.first_usable_lba);
+        LittleEndian::write_u64(&mut block[48..56], self.last_usable_lba);
+        block[56..72].copy_from_slice(&guid_to_bytes(&self.disk_guid));
+        LittleEndian::write_u64(&mut block[72..80], self.entries_lba);
+        LittleEndian::write_u32(&mut block[80..84], self.no_of_entries);
+        LittleEndian::write_u32(&mut block[84..88], self.entry_size);
+        LittleEndian::write_u32(&mut block[88..92], self.entries_crc);
+        let crc = crc32(&block[..HEADER_SIZE]);
+        LittleEndian::write_u32(&mut block[16..20], crc);
+        block
+    }
+
+    fn entries_size(&self) -> usize {
+        self.no_of_entries as usize * self.entry_size as usize
+    }
+}
+
+/// The partition table of a GPT formatted disk.
+/// Changes are made in memory and only reach the disk on `write()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionTable {
+    // Boot code and disk signature in front of the protective MBR's partition records, kept as found on disk
+    boot_code: Vec<u8>,
+    block_size: usize,
+    last_block: u64,
+    disk_guid: Guid,
+    first_usable_lba: u64,
+    last_usable_lba: u64,
+    entry_size: u32,
+    entries: Vec<Option<Partition>>,
+}
+
+impl PartitionTable {
+    /// Creates an empty partition table for a disk with the given geometry, leaving room for 128 entries
+    pub fn new(disk_guid: Guid, block_size: u32, last_block: u64) -> Result<Self> {
+        let block_size = block_size as usize;
+        if block_size < MBR_SIZE {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let entry_blocks = blocks_for(DEFAULT_NO_OF_ENTRIES as usize * ENTRY_SIZE, block_size);
+        let first_usable_lba = 2 + entry_blocks;
+        if last_block < 2 * first_usable_lba {
+            return Err(EfiErrorKind::VolumeFull.into());
+        }
+
+        Ok(PartitionTable {
+            boot_code: vec![0; MBR_PARTITION_RECORD_OFFSET],
+            block_size,
+            last_block,
+            disk_guid,
+            first_usable_lba,
+            last_usable_lba: last_block - entry_blocks - 1,
+            entry_size: ENTRY_SIZE as u32,
+            entries: vec![None; DEFAULT_NO_OF_ENTRIES as usize],
+        })
+    }
+
+    /// Reads the partition table from the given device. Falls back to the backup table if the primary one is damaged.
+    /// Fails with `NotFound` if the disk doesn't have a protective MBR or a GPT signature.
+    pub fn read(device: &mut BlockDevice) -> Result<Self> {
+        let media = device.media();
+        Self::read_with(media.block_size, media.last_block, |lba, buf| device.read_blocks(lba, buf))
+    }
+
+    fn read_with<F: FnMut(u64, &mut [u8]) -> Result<()>>(block_size: u32, last_block: u64, mut read_blocks: F) -> Result<Self> {
+        let block_size = block_size as usize;
+        if block_size < MBR_SIZE {
+            return Err(EfiErrorKind::Unsupported.into());
+        }
+
+        let mut block = vec![0_u8; block_size];
+        read_blocks(0, &mut block)?;
+        if !is_protective_mbr(&block) {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        let primary = Self::read_table(1, block_size, &mut read_blocks);
+        let (header, entries) = match primary {
+            Ok(table) => table,
+            Err(primary_err) => {
+                // The backup header is always in the last block
+                Self::read_table(last_block, block_size, &mut read_blocks).map_err(|_| primary_err)?
+            }
+        };
+
+        Ok(PartitionTable {
+            boot_code: block[..MBR_PARTITION_RECORD_OFFSET].to_vec(),
+            block_size,
+            last_block,
+            disk_guid: header.disk_guid,
+            first_usable_lba: header.first_usable_lba,
+            last_usable_lba: header.last_usable_lba,
+            entry_size: header.entry_size,
+            entries,
+        })
+    }
+
+    fn read_table<F: FnMut(u64, &mut [u8]) -> Result<()>>(lba: u64, block_size: usize, read_blocks: &mut F) -> Result<(Header, Vec<Option<Partition>>)> {
+        let mut block = vec![0_u8; block_size];
+        read_blocks(lba, &mut block)?;
+        let header = Header::parse(&block, lba)?;
+
+        let entries_size = header.entries_size();
+        let mut entry_bytes = vec![0_u8; blocks_for(entries_size, block_size) as usize * block_size];
+        read_blocks(header.entries_lba, &mut entry_bytes)?;
+        entry_bytes.truncate(entries_size);
+        if crc32(&entry_bytes) != header.entries_crc {
+            return Err(EfiErrorKind::CrcError.into());
+        }
+
+        let entries = entry_bytes.chunks(header.entry_size as usize).map(Partition::parse).collect::<Result<_>>()?;
+        Ok((header, entries))
+    }
+
+    /// Writes the protective MBR and both the primary and backup tables to the given device.
+    /// The boot code and disk signature of a table that was read from disk are written back unchanged.
+    /// The backup is written first so that a failure midway leaves at least one valid table on disk.
+    pub fn write(&self, device: &mut BlockDevice) -> Result<()> {
+        let media = device.media();
+        if media.block_size as usize != self.block_size || media.last_block != self.last_block {
+            return Err(EfiErrorKind::MediaChanged.into());
+        }
+
+        for (lba, blocks) in self.to_blocks() {
+            device.write_blocks(lba, &blocks)?;
+        }
+
+        device.flush()
+    }
+
+    // The blocks making up the table in the order in which they should be written
+    fn to_blocks(&self) -> Vec<(u64, Vec<u8>)> {
+        let entry_size = self.entry_size as usize;
+        let entries_size = self.entries.len() * entry_size;
+        let entry_blocks = blocks_for(entries_size, self.block_size);
+
+        let mut entry_bytes = vec![0_u8; entry_blocks as usize * self.block_size];
+        for (partition, bytes) in self.entries.iter().zip(entry_bytes.chunks_mut(entry_size)) {
+            if let Some(ref partition) = *partition {
+                partition.write_to(bytes);
+            }
+        }
+
+        let backup_entries_lba = self.last_block - entry_blocks;
+        let primary = Header {
+            my_lba: 1,
+            alternate_lba: self.last_block,
+            first_usable_lba: self.first_usable_lba,
+            last_usable_lba: cmp::min(self.last_usable_lba, backup_entries_lba - 1),
+            disk_guid: self.disk_guid,
+            entries_lba: 2,
+            no_of_entries: self.entries.len() as u32,
+            entry_size: self.entry_size,
+            entries_crc: crc32(&entry_bytes[..entries_size]),
+        };
+        let backup = Header { my_lba: self.last_block, alternate_lba: 1, entries_lba: backup_entries_lba, ..primary.clone() };
+
+        vec![
+            (backup.entries_lba, entry_bytes.clone()),
+            (backup.my_lba, backup.to_bytes(self.block_size)),
+            (primary.entries_lba, entry_bytes),
+            (primary.my_lba, primary.to_bytes(self.block_size)),
+            (0, protective_mbr(&self.boot_code, self.block_size, self.last_block)),
+        ]
+    }
+
+    pub fn disk_guid(&self) -> Guid {
+        self.disk_guid
+    }
+
+    pub fn set_disk_guid(&mut self, disk_guid: Guid) {
+        self.disk_guid = disk_guid;
+    }
+
+    /// First block that can be used by a partition
+    pub fn first_usable_lba(&self) -> u64 {
+        self.first_usable_lba
+    }
+
+    /// Last block that can be used by a partition (inclusive)
+    pub fn last_usable_lba(&self) -> u64 {
+        self.last_usable_lba
+    }
+
+    /// Maximum number of partitions the table can hold
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The partition in the given entry if the entry is in use
+    pub fn get(&self, index: usize) -> Option<&Partition> {
+        self.entries.get(index).and_then(|e| e.as_ref())
+    }
+
+    /// All partitions in the table along with the index of their entries
+    pub fn partitions(&self) -> Vec<(usize, &Partition)> {
+        self.entries.iter()
+            .enumerate()
+            .filter_map(|(i, e)| e.as_ref().map(|p| (i, p)))
+            .collect()
+    }
+
+    /// Adds the partition to the first free entry and returns the index of that entry
+    pub fn add(&mut self, partition: Partition) -> Result<usize> {
+        let index = self.entries.iter().position(|e| e.is_none()).ok_or(EfiErrorKind::VolumeFull)?;
+        self.set(index, partition)?;
+        Ok(index)
+    }
+
+    /// Puts the partition in the given entry replacing whatever was there.
+    /// Fails with `InvalidParameter` if the partition lies outside the usable area, overlaps another partition
+    /// or has a name longer than 36 UTF-16 code units.
+    pub fn set(&mut self, index: usize, partition: Partition) -> Result<()> {
+        if index >= self.entries.len() {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        self.validate(index, &partition)?;
+        self.entries[index] = Some(partition);
+        Ok(())
+    }
+
+    /// Clears the given entry and returns the partition that was in it
+    pub fn remove(&mut self, index: usize) -> Result<Partition> {
+        self.entries.get_mut(index)
+            .and_then(|e| e.take())
+            .ok_or(EfiErrorKind::NotFound.into())
+    }
+
+    fn validate(&self, index: usize, partition: &Partition) -> Result<()> {
+        if partition.type_guid == PARTITION_TYPE_UNUSED
+            || partition.first_lba > partition.last_lba
+            || partition.first_lba < self.first_usable_lba
+            || partition.last_lba > self.last_usable_lba
+            || partition.name.encode_utf16().count() > NAME_LEN {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let overlaps = self.partitions().iter()
+            .any(|&(i, p)| i != index && p.first_lba <= partition.last_lba && partition.first_lba <= p.last_lba);
+        if overlaps {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        Ok(())
+    }
+}
+
+fn blocks_for(size: usize, block_size: usize) -> u64 {
+    ((size + block_size - 1) / block_size) as u64
+}
+
+fn is_protective_mbr(block: &[u8]) -> bool {
+    block[510] == 0x55 && block[511] == 0xAA
+        && (0..4).any(|i| block[MBR_PARTITION_RECORD_OFFSET + i * 16 + 4] == PROTECTIVE_MBR_OS_TYPE)
+}
+
+fn protective_mbr(boot_code: &[u8], block_size: usize, last_block: u64) -> Vec<u8> {
+    let mut block = vec![0_u8; block_size];
+    block[..MBR_PARTITION_RECORD_OFFSET].copy_from_slice(boot_code);
+    {
+        let record = &mut block[MBR_PARTITION_RECORD_OFFSET..MBR_PARTITION_RECORD_OFFSET + 16];
+        record[1..4].copy_from_slice(&[0x00, 0x02, 0x00]); // Starting CHS
+        record[4] = PROTECTIVE_MBR_OS_TYPE;
+        record[5..8].copy_from_slice(&[0xFF, 0xFF, 0xFF]); // Ending CHS
+        LittleEndian::write_u32(&mut record[8..12], 1);
+        LittleEndian::write_u32(&mut record[12..16], cmp::min(last_block, 0xFFFFFFFF) as u32);
+    }
+    block[510] = 0x55;
+    block[511] = 0xAA;
+    block
+}
+
+/// CRC32 as used by GPT (the same one used by zip, Ethernet etc.)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_SIZE: usize = 512;
+    const LAST_BLOCK: u64 = 1023;
+
+    fn disk_guid() -> Guid {
+        EFI_GUID(0x12345678, 0x9ABC, 0xDEF0, [1, 2, 3, 4, 5, 6, 7, 8])
+    }
+
+    fn esp(table: &PartitionTable) -> Partition {
+        Partition {
+            type_guid: PARTITION_TYPE_EFI_SYSTEM,
+            unique_guid: EFI_GUID(1, 2, 3, [4; 8]),
+            first_lba: table.first_usable_lba(),
+            last_lba: table.first_usable_lba() + 99,
+            attributes: PartitionAttributes::REQUIRED,
+            name: "EFI system partition".into(),
+        }
+    }
+
+    fn write_to_disk(table: &PartitionTable) -> Vec<u8> {
+        let mut disk = vec![0_u8; (LAST_BLOCK as usize + 1) * BLOCK_SIZE];
+        for (lba, blocks) in table.to_blocks() {
+            let start = lba as usize * BLOCK_SIZE;
+            disk[start..start + blocks.len()].copy_from_slice(&blocks);
+        }
+        disk
+    }
+
+    fn read_from_disk(disk: &[u8]) -> Result<PartitionTable> {
+        PartitionTable::read_with(BLOCK_SIZE as u32, LAST_BLOCK, |lba, buf| {
+            let start = lba as usize * BLOCK_SIZE;
+            let end = start + buf.len();
+            buf.copy_from_slice(&disk[start..end]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn crc32_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn table_round_trips_through_disk() {
+        let mut table = PartitionTable::new(disk_guid(), BLOCK_SIZE as u32, LAST_BLOCK).unwrap();
+        assert_eq!(table.first_usable_lba(), 34);
+        assert_eq!(table.last_usable_lba(), LAST_BLOCK - 33);
+
+        let esp = esp(&table);
+        assert_eq!(table.add(esp.clone()).unwrap(), 0);
+        let disk = write_to_disk(&table);
+
+        let read = read_from_disk(&disk).unwrap();
+        assert_eq!(read, table);
+        assert_eq!(read.partitions(), vec![(0, &esp)]);
+    }
+
+    #[test]
+    fn falls_back_to_backup_table() {
+        let mut table = PartitionTable::new(disk_guid(), BLOCK_SIZE as u32, LAST_BLOCK).unwrap();
+        let esp = esp(&table);
+        table.add(esp).unwrap();
+        let mut disk = write_to_disk(&table);
+
+        disk[2 * BLOCK_SIZE] ^= 0xFF; // Corrupting the primary entry array
+        assert_eq!(read_from_disk(&disk).unwrap(), table);
+
+        disk[LAST_BLOCK as usize * BLOCK_SIZE + 20] ^= 0xFF; // And now the backup header
+        assert_eq!(read_from_disk(&disk).unwrap_err().kind(), EfiErrorKind::CrcError);
+    }
+
+    #[test]
+    fn keeps_boot_code_and_disk_signature() {
+        let table = PartitionTable::new(disk_guid(), BLOCK_SIZE as u32, LAST_BLOCK).unwrap();
+        let mut disk = write_to_disk(&table);
+        for (i, byte) in disk[..MBR_PARTITION_RECORD_OFFSET].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let rewritten = write_to_disk(&read_from_disk(&disk).unwrap());
+        assert_eq!(&rewritten[..BLOCK_SIZE], &disk[..BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn rejects_entries_ending_before_they_start() {
+        let mut table = PartitionTable::new(disk_guid(), BLOCK_SIZE as u32, LAST_BLOCK).unwrap();
+        let esp = esp(&table);
+        table.entries[0] = Some(Partition { first_lba: esp.last_lba, last_lba: esp.first_lba, ..esp });
+        let disk = write_to_disk(&table);
+        assert_eq!(read_from_disk(&disk).unwrap_err().kind(), EfiErrorKind::VolumeCorrupted);
+    }
+
+    #[test]
+    fn rejects_disk_without_protective_mbr() {
+        let table = PartitionTable::new(disk_guid(), BLOCK_SIZE as u32, LAST_BLOCK).unwrap();
+        let mut disk = write_to_disk(&table);
+        disk[MBR_PARTITION_RECORD_OFFSET + 4] = 0x07;
+        assert_eq!(read_from_disk(&disk).unwrap_err().kind(), EfiErrorKind::NotFound);
+    }
+
+    #[test]
+    fn rejects_invalid_partitions() {
+        let mut table = PartitionTable::new(disk_guid(), BLOCK_SIZE as u32, LAST_BLOCK).unwrap();
+        let esp = esp(&table);
+        table.add(esp.clone()).unwrap();
+
+        let overlapping = Partition { first_lba: esp.last_lba, last_lba: esp.last_lba + 10, ..esp.clone() };
+        assert_eq!(table.add(overlapping).unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+
+        let past_end = Partition { first_lba: esp.last_lba + 1, last_lba: LAST_BLOCK, ..esp.clone() };
+        assert_eq!(table.add(past_end).unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+
+        let long_name = Partition { first_lba: esp.last_lba + 1, last_lba: esp.last_lba + 10, name: "x".repeat(37), ..esp.clone() };
+        assert_eq!(table.add(long_name).unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+
+        // Resizing an existing partition must not count as overlapping itself
+        let grown = Partition { last_lba: esp.last_lba + 10, ..esp.clone() };
+        table.set(0, grown).unwrap();
+        assert_eq!(table.remove(0).unwrap().last_lba, esp.last_lba + 10);
+        assert!(table.partitions().is_empty());
+    }
+}
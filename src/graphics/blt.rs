@@ -0,0 +1,267 @@
+use ffi::graphics::{
+    EFI_GRAPHICS_OUTPUT_BLT_PIXEL,
+    EFI_GRAPHICS_OUTPUT_BLT_OPERATION,
+};
+use super::{Gop, Canvas, Color, framebuffer::check_rect};
+use alloc::Vec;
+use core::{cmp, mem};
+use {Result, EfiErrorKind};
+
+/// A pixel in the layout Blt expects
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct BltPixel {
+    pub blue: u8,
+    pub green: u8,
+    pub red: u8,
+    reserved: u8,
+}
+
+impl From<Color> for BltPixel {
+    fn from(color: Color) -> Self {
+        BltPixel { blue: color.blue, green: color.green, red: color.red, reserved: 0 }
+    }
+}
+
+impl From<BltPixel> for Color {
+    fn from(pixel: BltPixel) -> Self {
+        Color::new(pixel.red, pixel.green, pixel.blue)
+    }
+}
+
+/// An image in memory that can be drawn to and read from the screen with Blt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BltImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<BltPixel>,
+}
+
+impl BltImage {
+    /// A black image of the given size
+    pub fn new(width: u32, height: u32) -> Self {
+        BltImage { width, height, pixels: vec![BltPixel::default(); width as usize * height as usize] }
+    }
+
+    /// An image made of the given pixels, row by row from the top.
+    /// Fails with `BadBufferSize` if there aren't exactly `width * height` of them.
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<BltPixel>) -> Result<Self> {
+        if pixels.len() as u64 != width as u64 * height as u64 {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        Ok(BltImage { width, height, pixels })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[BltPixel] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [BltPixel] {
+        &mut self.pixels
+    }
+
+    /// None if the pixel is outside the image
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Color> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        Some(self.pixels[self.index(x, y)].into())
+    }
+
+    /// Fails with `InvalidParameter` if the pixel is outside the image
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color) -> Result<()> {
+        check_rect(self.width, self.height, x, y, 1, 1)?;
+        let index = self.index(x, y);
+        self.pixels[index] = color.into();
+        Ok(())
+    }
+
+    /// Fills the given rectangle. Fails with `InvalidParameter` if any part of it is outside the image.
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) -> Result<()> {
+        check_rect(self.width, self.height, x, y, width, height)?;
+        let pixel = BltPixel::from(color);
+        for row in y..y + height {
+            let start = self.index(x, row);
+            for p in &mut self.pixels[start..start + width as usize] {
+                *p = pixel;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+}
+
+impl Canvas for BltImage {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) -> Result<()> {
+        BltImage::fill_rect(self, x, y, width, height, color)
+    }
+}
+
+// Drawing with Blt works even when the framebuffer can't be accessed directly e.g. with PixelFormat::BltOnly.
+// All coordinates are checked against the current mode (and the image where there is one) and
+// anything that doesn't fit fails with `InvalidParameter` rather than being clipped.
+impl Gop {
+    /// Fills a rectangle of the screen with the given color
+    pub fn blt_fill(&mut self, color: Color, x: u32, y: u32, width: u32, height: u32) -> Result<()> {
+        self.check_screen_rect(x, y, width, height)?;
+        let mut pixel = BltPixel::from(color);
+        self.blt(&mut pixel, EFI_GRAPHICS_OUTPUT_BLT_OPERATION::EfiBltVideoFill, 0, 0, x, y, width, height, 0)
+    }
+
+    /// Copies the `width` x `height` rectangle at (`src_x`, `src_y`) in the image to (`dest_x`, `dest_y`) on the screen
+    pub fn blt_buffer_to_video(&mut self, image: &BltImage, src_x: u32, src_y: u32, dest_x: u32, dest_y: u32, width: u32, height: u32) -> Result<()> {
+        check_rect(image.width, image.height, src_x, src_y, width, height)?;
+        self.check_screen_rect(dest_x, dest_y, width, height)?;
+        let pixels = image.pixels.as_ptr() as *mut BltPixel; // Blt only reads from the buffer for this operation
+        let delta = image.width as usize * mem::size_of::<BltPixel>();
+        self.blt(pixels, EFI_GRAPHICS_OUTPUT_BLT_OPERATION::EfiBltBufferToVideo, src_x, src_y, dest_x, dest_y, width, height, delta)
+    }
+
+    /// Copies the `width` x `height` rectangle at (`src_x`, `src_y`) on the screen to (`dest_x`, `dest_y`) in the image
+    pub fn blt_video_to_buffer(&self, image: &mut BltImage, src_x: u32, src_y: u32, dest_x: u32, dest_y: u32, width: u32, height: u32) -> Result<()> {
+        self.check_screen_rect(src_x, src_y, width, height)?;
+        check_rect(image.width, image.height, dest_x, dest_y, width, height)?;
+        let delta = image.width as usize * mem::size_of::<BltPixel>();
+        self.blt(image.pixels.as_mut_ptr(), EFI_GRAPHICS_OUTPUT_BLT_OPERATION::EfiBltVideoToBltBuffer, src_x, src_y, dest_x, dest_y, width, height, delta)
+    }
+
+    /// Captures the given rectangle of the screen into a new image
+    pub fn capture(&self, x: u32, y: u32, width: u32, height: u32) -> Result<BltImage> {
+        let mut image = BltImage::new(width, height);
+        self.blt_video_to_buffer(&mut image, x, y, 0, 0, width, height)?;
+        Ok(image)
+    }
+
+    /// Draws the whole image with its top left corner at (`x`, `y`)
+    pub fn blit_image(&mut self, image: &BltImage, x: u32, y: u32) -> Result<()> {
+        let (width, height) = (image.width, image.height);
+        self.blt_buffer_to_video(image, 0, 0, x, y, width, height)
+    }
+
+    /// Draws the outline of a rectangle one pixel thick
+    pub fn draw_rect(&mut self, color: Color, x: u32, y: u32, width: u32, height: u32) -> Result<()> {
+        self.check_screen_rect(x, y, width, height)?;
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        self.blt_fill(color, x, y, width, 1)?;
+        self.blt_fill(color, x, y + height - 1, width, 1)?;
+        self.blt_fill(color, x, y, 1, height)?;
+        self.blt_fill(color, x + width - 1, y, 1, height)
+    }
+
+    /// Draws a one pixel thick line between the two points, both of which are included
+    pub fn draw_line(&mut self, color: Color, x0: u32, y0: u32, x1: u32, y1: u32) -> Result<()> {
+        self.check_screen_rect(x0, y0, 1, 1)?;
+        self.check_screen_rect(x1, y1, 1, 1)?;
+        for (x, y, len) in line_runs(x0, y0, x1, y1) {
+            self.blt_fill(color, x, y, len, 1)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_screen_rect(&self, x: u32, y: u32, width: u32, height: u32) -> Result<()> {
+        let mode = self.current_mode()?;
+        check_rect(mode.width, mode.height, x, y, width, height)
+    }
+
+    fn blt(&self, buffer: *mut BltPixel, operation: EFI_GRAPHICS_OUTPUT_BLT_OPERATION, src_x: u32, src_y: u32, dest_x: u32, dest_y: u32, width: u32, height: u32, delta: usize) -> Result<()> {
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        unsafe {
+            ret_on_err!(((*self.protocol).Blt)(self.protocol, buffer as *mut EFI_GRAPHICS_OUTPUT_BLT_PIXEL, operation, src_x as usize, src_y as usize, dest_x as usize, dest_y as usize, width as usize, height as usize, delta));
+        }
+
+        Ok(())
+    }
+}
+
+// Bresenham's line split into horizontal runs of (x, y, length) so that each run is a single Blt
+fn line_runs(x0: u32, y0: u32, x1: u32, y1: u32) -> Vec<(u32, u32, u32)> {
+    let (dx, dy) = ((x1 as i64 - x0 as i64).abs(), -(y1 as i64 - y0 as i64).abs());
+    let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+    let (mut x, mut y) = (x0 as i64, y0 as i64);
+    let mut err = dx + dy;
+
+    let mut runs: Vec<(u32, u32, u32)> = Vec::new();
+    loop {
+        // Extend the current run if this pixel is next to it on the same row
+        let extended = match runs.last_mut() {
+            Some(&mut (ref mut start, row, ref mut len)) if row as i64 == y && (x == *start as i64 - 1 || x == *start as i64 + *len as i64) => {
+                *start = cmp::min(*start, x as u32);
+                *len += 1;
+                true
+            },
+            _ => false
+        };
+        if !extended {
+            runs.push((x as u32, y as u32, 1));
+        }
+
+        if x == x1 as i64 && y == y1 as i64 {
+            return runs;
+        }
+
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_split_into_runs() {
+        assert_eq!(line_runs(2, 3, 2, 3), vec![(2, 3, 1)]);
+        assert_eq!(line_runs(0, 0, 4, 0), vec![(0, 0, 5)]);
+        assert_eq!(line_runs(4, 0, 0, 0), vec![(0, 0, 5)]);
+        assert_eq!(line_runs(0, 0, 0, 2), vec![(0, 0, 1), (0, 1, 1), (0, 2, 1)]);
+        assert_eq!(line_runs(0, 0, 5, 1), vec![(0, 0, 3), (3, 1, 3)]);
+        assert_eq!(line_runs(3, 3, 0, 0), vec![(3, 3, 1), (2, 2, 1), (1, 1, 1), (0, 0, 1)]);
+    }
+
+    #[test]
+    fn images_are_sized_and_bounds_checked() {
+        assert!(BltImage::from_pixels(2, 2, vec![BltPixel::default(); 3]).is_err());
+        let mut image = BltImage::from_pixels(2, 2, vec![BltPixel::default(); 4]).unwrap();
+        image.set_pixel(1, 1, Color::WHITE).unwrap();
+        assert_eq!(image.pixels()[3], BltPixel::from(Color::WHITE));
+        assert_eq!(image.pixel(1, 1), Some(Color::WHITE));
+        assert_eq!(image.pixel(2, 0), None);
+        assert!(image.set_pixel(0, 2, Color::WHITE).is_err());
+    }
+}
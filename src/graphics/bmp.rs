@@ -0,0 +1,259 @@
+use super::{Gop, Color, BltImage, BltPixel};
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::Vec;
+use core::cmp;
+#[cfg(feature = "fs")] use fs;
+use {Result, EfiErrorKind};
+
+// BMP is the format UEFI itself uses for logos. Only uncompressed 24 and 32 bit images are supported.
+
+const FILE_HEADER_SIZE: usize = 14;
+const INFO_HEADER_SIZE: usize = 40; // BITMAPINFOHEADER. The later versions only add fields after it.
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BGRA_MASKS: [u32; 3] = [0x00FF0000, 0x0000FF00, 0x000000FF];
+
+/// How `Gop::show_splash()` sizes the image
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SplashScaling {
+    /// Keep the image's own size, shrinking it only if it's larger than the screen
+    Original,
+    /// Scale the image up or down to the largest size that fits the screen
+    Fit,
+}
+
+impl BltImage {
+    /// Decodes an uncompressed 24 or 32 bit BMP file.
+    /// Fails with `Unsupported` for other bit depths or compressed images
+    /// and with `InvalidParameter` if the data isn't a well formed BMP.
+    pub fn from_bmp(data: &[u8]) -> Result<Self> {
+        if data.len() < FILE_HEADER_SIZE + INFO_HEADER_SIZE || &data[..2] != b"BM" {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let pixel_offset = LittleEndian::read_u32(&data[10..14]) as usize;
+        let info = &data[FILE_HEADER_SIZE..];
+        let header_size = LittleEndian::read_u32(&info[0..4]) as usize;
+        let width = LittleEndian::read_i32(&info[4..8]);
+        let height = LittleEndian::read_i32(&info[8..12]);
+        let bits_per_pixel = LittleEndian::read_u16(&info[14..16]);
+        let compression = LittleEndian::read_u32(&info[16..20]);
+
+        if header_size < INFO_HEADER_SIZE || width <= 0 || height == 0 || height == i32::min_value() {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        match (bits_per_pixel, compression) {
+            (24, BI_RGB) | (32, BI_RGB) => (),
+            (32, BI_BITFIELDS) => {
+                // The masks follow a BITMAPINFOHEADER and are part of the later headers, at the same offset either way
+                let masks = info.get(INFO_HEADER_SIZE..INFO_HEADER_SIZE + 12).ok_or(EfiErrorKind::InvalidParameter)?;
+                let masks = [LittleEndian::read_u32(&masks[0..4]), LittleEndian::read_u32(&masks[4..8]), LittleEndian::read_u32(&masks[8..12])];
+                if masks != BGRA_MASKS {
+                    return Err(EfiErrorKind::Unsupported.into());
+                }
+            },
+            _ => return Err(EfiErrorKind::Unsupported.into())
+        }
+
+        // Rows are stored bottom up unless the height is negative and each is padded to 4 bytes
+        let (width, top_down) = (width as u32, height < 0);
+        let height = height.abs() as u32;
+        let bytes_per_pixel = bits_per_pixel as usize / 8;
+        let row_size = (width as usize * bytes_per_pixel + 3) & !3;
+        let pixel_data_size = row_size.checked_mul(height as usize).ok_or(EfiErrorKind::InvalidParameter)?;
+        let pixel_data = pixel_offset.checked_add(pixel_data_size)
+            .and_then(|end| data.get(pixel_offset..end))
+            .ok_or(EfiErrorKind::InvalidParameter)?;
+
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height as usize {
+            let row = if top_down { y } else { height as usize - 1 - y };
+            let row = &pixel_data[row * row_size..row * row_size + width as usize * bytes_per_pixel];
+            pixels.extend(row.chunks(bytes_per_pixel).map(|p| BltPixel::from(Color::new(p[2], p[1], p[0]))));
+        }
+
+        BltImage::from_pixels(width, height, pixels)
+    }
+
+    /// A copy of the image resized to the given size using nearest neighbour sampling
+    pub fn scaled(&self, width: u32, height: u32) -> BltImage {
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height as u64 {
+            let src_y = y * self.height() as u64 / height as u64;
+            for x in 0..width as u64 {
+                let src_x = x * self.width() as u64 / width as u64;
+                pixels.push(self.pixels()[(src_y * self.width() as u64 + src_x) as usize]);
+            }
+        }
+
+        BltImage::from_pixels(width, height, pixels).expect("pixel count matches the size")
+    }
+
+    /// Encodes the image as an uncompressed 24 bit BMP file
+    pub fn to_bmp(&self) -> Vec<u8> {
+        let row_size = (self.width() as usize * 3 + 3) & !3;
+        let pixel_offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE;
+        let file_size = pixel_offset + row_size * self.height() as usize;
+
+        let mut data = vec![0_u8; pixel_offset];
+        data[..2].copy_from_slice(b"BM");
+        LittleEndian::write_u32(&mut data[2..6], file_size as u32);
+        LittleEndian::write_u32(&mut data[10..14], pixel_offset as u32);
+        LittleEndian::write_u32(&mut data[14..18], INFO_HEADER_SIZE as u32);
+        LittleEndian::write_i32(&mut data[18..22], self.width() as i32);
+        LittleEndian::write_i32(&mut data[22..26], self.height() as i32);
+        LittleEndian::write_u16(&mut data[26..28], 1); // Planes
+        LittleEndian::write_u16(&mut data[28..30], 24);
+        LittleEndian::write_u32(&mut data[30..34], BI_RGB);
+        LittleEndian::write_u32(&mut data[34..38], (file_size - pixel_offset) as u32);
+
+        // Bottom up as most readers expect
+        data.reserve(file_size - pixel_offset);
+        for row in self.pixels().chunks(self.width() as usize).rev() {
+            for pixel in row {
+                data.extend_from_slice(&[pixel.blue, pixel.green, pixel.red]);
+            }
+            let padding = row_size - row.len() * 3;
+            data.extend_from_slice(&[0, 0, 0][..padding]);
+        }
+
+        data
+    }
+}
+
+impl Gop {
+    /// Clears the screen to `background` and draws the image centered on it, e.g. a boot splash or logo
+    pub fn show_splash(&mut self, image: &BltImage, scaling: SplashScaling, background: Color) -> Result<()> {
+        let mode = self.current_mode()?;
+        let (width, height) = splash_size(image.width(), image.height(), mode.width, mode.height, scaling);
+
+        self.blt_fill(background, 0, 0, mode.width, mode.height)?;
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        let (x, y) = ((mode.width - width) / 2, (mode.height - height) / 2);
+        if (width, height) == (image.width(), image.height()) {
+            self.blit_image(image, x, y)
+        } else {
+            self.blit_image(&image.scaled(width, height), x, y)
+        }
+    }
+
+    /// Captures the whole screen as a BMP file
+    pub fn screenshot(&self) -> Result<Vec<u8>> {
+        let mode = self.current_mode()?;
+        Ok(self.capture(0, 0, mode.width, mode.height)?.to_bmp())
+    }
+}
+
+/// Captures the screen of the primary graphics output as a BMP file
+pub fn screenshot() -> Result<Vec<u8>> {
+    Gop::primary()?.screenshot()
+}
+
+/// Captures the screen of the primary graphics output and saves it as a BMP file.
+/// The path can be of any form accepted by `fs::open()`.
+#[cfg(feature = "fs")]
+pub fn save_screenshot(path: &str) -> Result<()> {
+    fs::write(path, &screenshot()?)
+}
+
+// The size to draw an image at on a screen, keeping its aspect ratio
+fn splash_size(width: u32, height: u32, screen_width: u32, screen_height: u32, scaling: SplashScaling) -> (u32, u32) {
+    if width == 0 || height == 0 {
+        return (0, 0);
+    }
+
+    let fits = width <= screen_width && height <= screen_height;
+    if fits && scaling == SplashScaling::Original {
+        return (width, height);
+    }
+
+    // Scale by the smaller of the two ratios, compared without division as w1/h1 < w2/h2 <=> w1*h2 < w2*h1
+    let (width, height, screen_width, screen_height) = (width as u64, height as u64, screen_width as u64, screen_height as u64);
+    if screen_width * height <= screen_height * width {
+        (screen_width as u32, cmp::min(height * screen_width / width, screen_height) as u32)
+    } else {
+        (cmp::min(width * screen_height / height, screen_width) as u32, screen_height as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A BMP of the given pixels (bottom up, BGR(A) bytes with rows already padded)
+    fn bmp(width: i32, height: i32, bits_per_pixel: u16, pixel_data: &[u8]) -> Vec<u8> {
+        let mut data = vec![0_u8; FILE_HEADER_SIZE + INFO_HEADER_SIZE];
+        data[..2].copy_from_slice(b"BM");
+        LittleEndian::write_u32(&mut data[2..6], (FILE_HEADER_SIZE + INFO_HEADER_SIZE + pixel_data.len()) as u32);
+        LittleEndian::write_u32(&mut data[10..14], (FILE_HEADER_SIZE + INFO_HEADER_SIZE) as u32);
+        LittleEndian::write_u32(&mut data[14..18], INFO_HEADER_SIZE as u32);
+        LittleEndian::write_i32(&mut data[18..22], width);
+        LittleEndian::write_i32(&mut data[22..26], height);
+        LittleEndian::write_u16(&mut data[26..28], 1);
+        LittleEndian::write_u16(&mut data[28..30], bits_per_pixel);
+        data.extend_from_slice(pixel_data);
+        data
+    }
+
+    #[test]
+    fn bottom_up_24_bit_bmp_is_decoded() {
+        // Bottom row: red, green. Top row: blue, white. Each row padded from 6 to 8 bytes.
+        let data = bmp(2, 2, 24, &[0, 0, 0xFF, 0, 0xFF, 0, 0, 0, 0xFF, 0, 0, 0xFF, 0xFF, 0xFF, 0, 0]);
+        let image = BltImage::from_bmp(&data).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 2));
+        assert_eq!(image.pixel(0, 0), Some(Color::new(0, 0, 0xFF)));
+        assert_eq!(image.pixel(1, 0), Some(Color::WHITE));
+        assert_eq!(image.pixel(0, 1), Some(Color::new(0xFF, 0, 0)));
+        assert_eq!(image.pixel(1, 1), Some(Color::new(0, 0xFF, 0)));
+    }
+
+    #[test]
+    fn top_down_32_bit_bmp_is_decoded() {
+        let data = bmp(1, -2, 32, &[0x10, 0x20, 0x30, 0xFF, 0x40, 0x50, 0x60, 0xFF]);
+        let image = BltImage::from_bmp(&data).unwrap();
+        assert_eq!(image.pixel(0, 0), Some(Color::new(0x30, 0x20, 0x10)));
+        assert_eq!(image.pixel(0, 1), Some(Color::new(0x60, 0x50, 0x40)));
+    }
+
+    #[test]
+    fn bad_bmps_are_rejected() {
+        let data = bmp(2, 2, 24, &[0; 15]); // One byte short
+        assert_eq!(BltImage::from_bmp(&data).unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+        let data = bmp(2, 2, 8, &[0; 16]);
+        assert_eq!(BltImage::from_bmp(&data).unwrap_err().kind(), EfiErrorKind::Unsupported);
+        let mut data = bmp(2, 2, 24, &[0; 16]);
+        LittleEndian::write_u32(&mut data[30..34], 1); // RLE8
+        assert_eq!(BltImage::from_bmp(&data).unwrap_err().kind(), EfiErrorKind::Unsupported);
+        assert!(BltImage::from_bmp(b"BM").is_err());
+    }
+
+    #[test]
+    fn encoded_bmp_decodes_to_the_same_image() {
+        let pixels = [Color::new(1, 2, 3), Color::WHITE, Color::BLACK, Color::new(0xFF, 0, 0), Color::new(0, 0xFF, 0), Color::new(0, 0, 0xFF)];
+        let image = BltImage::from_pixels(3, 2, pixels.iter().map(|c| BltPixel::from(*c)).collect()).unwrap();
+        let data = image.to_bmp();
+        assert_eq!(data.len(), FILE_HEADER_SIZE + INFO_HEADER_SIZE + 2 * 12); // Rows padded from 9 to 12 bytes
+        assert_eq!(&data[FILE_HEADER_SIZE + INFO_HEADER_SIZE..][..3], &[0, 0, 0xFF]); // Bottom left pixel first, as BGR
+        assert_eq!(BltImage::from_bmp(&data).unwrap(), image);
+    }
+
+    #[test]
+    fn images_are_scaled() {
+        let image = BltImage::from_pixels(2, 1, vec![BltPixel::from(Color::BLACK), BltPixel::from(Color::WHITE)]).unwrap();
+        let scaled = image.scaled(4, 2);
+        assert_eq!(scaled.pixel(1, 1), Some(Color::BLACK));
+        assert_eq!(scaled.pixel(2, 0), Some(Color::WHITE));
+    }
+
+    #[test]
+    fn splash_keeps_aspect_ratio() {
+        assert_eq!(splash_size(400, 300, 1920, 1080, SplashScaling::Original), (400, 300));
+        assert_eq!(splash_size(400, 300, 1920, 1080, SplashScaling::Fit), (1440, 1080));
+        assert_eq!(splash_size(4000, 1000, 1920, 1080, SplashScaling::Original), (1920, 480));
+        assert_eq!(splash_size(0, 10, 1920, 1080, SplashScaling::Fit), (0, 0));
+    }
+}
@@ -0,0 +1,232 @@
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::Vec;
+use core::str;
+use {Result, EfiErrorKind};
+
+// An 8x16 PC screen font covering printable ASCII in the style of the VGA BIOS font
+static DEFAULT_FONT: &[u8] = include_bytes!("font8x16.psf");
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_MODE_HAS_TABLE: u8 = 0x02 | 0x04;
+const PSF1_SEPARATOR: u16 = 0xFFFF;
+const PSF1_START_SEQUENCE: u16 = 0xFFFE;
+const PSF1_HEADER_SIZE: usize = 4;
+
+const PSF2_MAGIC: u32 = 0x864ab572;
+const PSF2_FLAG_HAS_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xFF;
+const PSF2_START_SEQUENCE: u8 = 0xFE;
+const PSF2_HEADER_SIZE: usize = 32;
+
+/// A bitmap font in the PC Screen Font format (PSF1 or PSF2), the format of the Linux console fonts
+pub struct Font<'a> {
+    glyphs: &'a [u8],
+    count: usize,
+    width: u32,
+    height: u32,
+    bytes_per_glyph: usize,
+    // (code point, glyph index) sorted by code point. Empty if the font has no unicode table in which case
+    // code points map directly to glyph indices.
+    unicode: Vec<(u32, usize)>,
+}
+
+impl<'a> Font<'a> {
+    /// The font built into the crate, 8x16 pixels per character
+    pub fn default_8x16() -> Font<'static> {
+        Font::from_psf(DEFAULT_FONT).expect("built in font is valid")
+    }
+
+    /// Parses a PSF1 or PSF2 font. Fails with `InvalidParameter` if the data isn't a well formed PSF font.
+    pub fn from_psf(data: &'a [u8]) -> Result<Self> {
+        if data.len() >= PSF1_HEADER_SIZE && data[..2] == PSF1_MAGIC {
+            Self::from_psf1(data)
+        } else if data.len() >= PSF2_HEADER_SIZE && LittleEndian::read_u32(&data[0..4]) == PSF2_MAGIC {
+            Self::from_psf2(data)
+        } else {
+            Err(EfiErrorKind::InvalidParameter.into())
+        }
+    }
+
+    fn from_psf1(data: &'a [u8]) -> Result<Self> {
+        let mode = data[2];
+        let height = data[3] as usize;
+        let count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+        let glyphs_end = PSF1_HEADER_SIZE + count * height;
+        if height == 0 || data.len() < glyphs_end {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let mut unicode = Vec::new();
+        if mode & PSF1_MODE_HAS_TABLE != 0 {
+            let mut table = data[glyphs_end..].chunks(2).filter(|c| c.len() == 2).map(LittleEndian::read_u16);
+            for index in 0..count {
+                let mut in_sequence = false;
+                loop {
+                    match table.next() {
+                        Some(PSF1_SEPARATOR) | None => break,
+                        Some(PSF1_START_SEQUENCE) => in_sequence = true,
+                        Some(c) if !in_sequence => unicode.push((c as u32, index)),
+                        Some(_) => ()
+                    }
+                }
+            }
+        }
+
+        Ok(Self::new(&data[PSF1_HEADER_SIZE..glyphs_end], count, 8, height as u32, height, unicode))
+    }
+
+    fn from_psf2(data: &'a [u8]) -> Result<Self> {
+        let header_size = LittleEndian::read_u32(&data[8..12]) as usize;
+        let flags = LittleEndian::read_u32(&data[12..16]);
+        let count = LittleEndian::read_u32(&data[16..20]) as usize;
+        let bytes_per_glyph = LittleEndian::read_u32(&data[20..24]) as usize;
+        let height = LittleEndian::read_u32(&data[24..28]);
+        let width = LittleEndian::read_u32(&data[28..32]);
+
+        let glyphs_end = count.checked_mul(bytes_per_glyph).and_then(|s| s.checked_add(header_size));
+        let glyphs_end = match glyphs_end {
+            Some(end) if end <= data.len() && header_size >= PSF2_HEADER_SIZE => end,
+            _ => return Err(EfiErrorKind::InvalidParameter.into())
+        };
+
+        if width == 0 || height == 0 || bytes_per_glyph < ((width as usize + 7) / 8) * height as usize {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let mut unicode = Vec::new();
+        if flags & PSF2_FLAG_HAS_TABLE != 0 {
+            let mut entries = data[glyphs_end..].split(|b| *b == PSF2_SEPARATOR);
+            for index in 0..count {
+                let entry = match entries.next() {
+                    Some(entry) => entry,
+                    None => break
+                };
+
+                // Only single code points are used. Sequences (after the first 0xFE) are for combining characters.
+                let singles = entry.split(|b| *b == PSF2_START_SEQUENCE).next().unwrap_or(&[]);
+                let singles = str::from_utf8(singles).map_err(|_| EfiErrorKind::InvalidParameter)?;
+                unicode.extend(singles.chars().map(|c| (c as u32, index)));
+            }
+        }
+
+        Ok(Self::new(&data[header_size..glyphs_end], count, width, height, bytes_per_glyph, unicode))
+    }
+
+    fn new(glyphs: &'a [u8], count: usize, width: u32, height: u32, bytes_per_glyph: usize, mut unicode: Vec<(u32, usize)>) -> Self {
+        unicode.sort();
+        Font { glyphs, count, width, height, bytes_per_glyph, unicode }
+    }
+
+    /// Width of a character in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of a character in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The glyph for the given character or None if the font doesn't have one
+    pub fn glyph(&self, c: char) -> Option<Glyph<'a>> {
+        let index = if self.unicode.is_empty() {
+            c as usize
+        } else {
+            let i = self.unicode.binary_search_by_key(&(c as u32), |&(c, _)| c).ok()?;
+            self.unicode[i].1
+        };
+
+        if index >= self.count {
+            return None;
+        }
+
+        let start = index * self.bytes_per_glyph;
+        Some(Glyph { bits: &self.glyphs[start..start + self.bytes_per_glyph], width: self.width, height: self.height })
+    }
+
+    /// The glyph for the given character. If there isn't one the glyph for U+FFFD or else '?'. None if there isn't even that.
+    pub fn glyph_or_replacement(&self, c: char) -> Option<Glyph<'a>> {
+        self.glyph(c).or_else(|| self.glyph('\u{FFFD}')).or_else(|| self.glyph('?'))
+    }
+}
+
+/// The bitmap of a character
+#[derive(Debug, Copy, Clone)]
+pub struct Glyph<'a> {
+    bits: &'a [u8],
+    width: u32,
+    height: u32,
+}
+
+impl<'a> Glyph<'a> {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Whether the pixel is part of the character. False for pixels outside the glyph.
+    pub fn is_set(&self, x: u32, y: u32) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+
+        let bytes_per_row = (self.width as usize + 7) / 8;
+        let byte = self.bits[y as usize * bytes_per_row + x as usize / 8];
+        byte & (0x80 >> (x % 8)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(glyph: &Glyph) -> Vec<u8> {
+        (0..glyph.height()).map(|y| (0..glyph.width()).fold(0, |row, x| row << 1 | glyph.is_set(x, y) as u8)).collect()
+    }
+
+    #[test]
+    fn default_font_has_ascii() {
+        let font = Font::default_8x16();
+        assert_eq!((font.width(), font.height()), (8, 16));
+        let a = font.glyph('A').unwrap();
+        assert_eq!(render(&a), vec![0x00, 0x00, 0x10, 0x38, 0x6C, 0xC6, 0xC6, 0xFE, 0xC6, 0xC6, 0xC6, 0xC6, 0x00, 0x00, 0x00, 0x00]);
+        assert!(font.glyph('é').is_none());
+        assert_eq!(render(&font.glyph_or_replacement('é').unwrap()), render(&font.glyph('?').unwrap()));
+    }
+
+    #[test]
+    fn psf1_unicode_table_is_used() {
+        let mut data = vec![0x36, 0x04, PSF1_MODE_HAS_TABLE, 1];
+        data.extend((0..256).map(|i| i as u8)); // Each glyph's only row is its index
+        let mut table = Vec::new();
+        for i in 0..256 {
+            match i {
+                0 => table.extend_from_slice(&[0x00E9, PSF1_START_SEQUENCE, 0x0065, 0x0301, PSF1_SEPARATOR]), // é and e + combining acute
+                1 => table.extend_from_slice(&[0x0041, 0x0391, PSF1_SEPARATOR]), // A and Greek Alpha
+                _ => table.push(PSF1_SEPARATOR)
+            }
+        }
+        for c in table {
+            let mut bytes = [0; 2];
+            LittleEndian::write_u16(&mut bytes, c);
+            data.extend_from_slice(&bytes);
+        }
+
+        let font = Font::from_psf(&data).unwrap();
+        assert_eq!(font.glyph('é').map(|g| render(&g)), Some(vec![0]));
+        assert_eq!(font.glyph('Α').map(|g| render(&g)), Some(vec![1]));
+        assert_eq!(font.glyph('A').map(|g| render(&g)), Some(vec![1]));
+        assert!(font.glyph('e').is_none());
+    }
+
+    #[test]
+    fn truncated_fonts_are_rejected() {
+        assert!(Font::from_psf(&DEFAULT_FONT[..DEFAULT_FONT.len() - 1]).is_err());
+        assert!(Font::from_psf(&[0x36, 0x04, 0, 16]).is_err());
+        assert!(Font::from_psf(b"not a font").is_err());
+    }
+}
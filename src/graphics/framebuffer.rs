@@ -0,0 +1,255 @@
+use super::{Gop, Canvas, Color, PixelFormat, PixelBitmask};
+use core::{slice, marker::PhantomData};
+use {Result, EfiErrorKind};
+
+const BYTES_PER_PIXEL: usize = 4;
+
+/// Direct access to the pixels of a graphics output device.
+/// Colors are converted to the device's pixel format so drawing code doesn't need to care about it.
+/// Every access is bounds checked so nothing outside the framebuffer is ever written.
+pub struct Framebuffer<'a> {
+    base: *mut u32,
+    width: u32,
+    height: u32,
+    stride: u32,
+    pixel_format: PixelFormat,
+    _gop: PhantomData<&'a mut Gop>,
+}
+
+impl<'a> Framebuffer<'a> {
+    /// The framebuffer of the device's current mode.
+    /// Fails with `Unsupported` if the mode has no framebuffer (`PixelFormat::BltOnly`)
+    /// or if the firmware reports a framebuffer too small for the mode.
+    pub fn new(gop: &'a mut Gop) -> Result<Self> {
+        let mode = gop.current_mode()?;
+        unsafe { Self::from_raw_parts(gop.framebuffer_base() as usize as *mut u8, gop.framebuffer_size(), mode.width, mode.height, mode.stride, mode.pixel_format) }
+    }
+
+    /// A framebuffer over the given memory.
+    /// Unsafe because the memory must be valid and not otherwise accessed for as long as the framebuffer lives.
+    pub unsafe fn from_raw_parts(base: *mut u8, size: usize, width: u32, height: u32, stride: u32, pixel_format: PixelFormat) -> Result<Self> {
+        if let PixelFormat::BltOnly = pixel_format {
+            return Err(EfiErrorKind::Unsupported.into());
+        }
+
+        if base.is_null() || base as usize % BYTES_PER_PIXEL != 0 || stride < width || required_size(width, height, stride).map_or(true, |s| s > size) {
+            return Err(EfiErrorKind::Unsupported.into());
+        }
+
+        Ok(Framebuffer { base: base as *mut u32, width, height, stride, pixel_format, _gop: PhantomData })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Pixels per row in memory. Can be greater than `width()`.
+    pub fn stride(&self) -> u32 {
+        self.stride
+    }
+
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    /// The raw pixel value for the given color in this framebuffer's format, as stored in the row slices
+    pub fn encode(&self, color: Color) -> u32 {
+        encode(self.pixel_format, color)
+    }
+
+    /// The color of a raw pixel value from the row slices
+    pub fn decode(&self, pixel: u32) -> Color {
+        decode(self.pixel_format, pixel)
+    }
+
+    /// Fails with `InvalidParameter` if the pixel is outside the framebuffer
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color) -> Result<()> {
+        check_rect(self.width, self.height, x, y, 1, 1)?;
+        let pixel = self.encode(color);
+        self.row_mut(y)?[x as usize] = pixel;
+        Ok(())
+    }
+
+    /// Fails with `InvalidParameter` if the pixel is outside the framebuffer
+    pub fn pixel(&self, x: u32, y: u32) -> Result<Color> {
+        check_rect(self.width, self.height, x, y, 1, 1)?;
+        Ok(self.decode(self.row(y)?[x as usize]))
+    }
+
+    /// Fills the given rectangle. Fails with `InvalidParameter` if any part of it is outside the framebuffer.
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) -> Result<()> {
+        check_rect(self.width, self.height, x, y, width, height)?;
+        let pixel = self.encode(color);
+        let (start, end) = (x as usize, (x + width) as usize);
+        for row in y..y + height {
+            for p in &mut self.row_mut(row)?[start..end] {
+                *p = pixel;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fills the whole framebuffer
+    pub fn clear(&mut self, color: Color) -> Result<()> {
+        let (width, height) = (self.width, self.height);
+        self.fill_rect(0, 0, width, height, color)
+    }
+
+    /// The raw pixel values of the given row, `width()` of them.
+    /// Fails with `InvalidParameter` if the row is outside the framebuffer.
+    pub fn row(&self, y: u32) -> Result<&[u32]> {
+        check_rect(self.width, self.height, 0, y, self.width, 1)?;
+        Ok(unsafe { slice::from_raw_parts(self.base.add(y as usize * self.stride as usize), self.width as usize) })
+    }
+
+    /// The raw pixel values of the given row, `width()` of them.
+    /// Fails with `InvalidParameter` if the row is outside the framebuffer.
+    pub fn row_mut(&mut self, y: u32) -> Result<&mut [u32]> {
+        check_rect(self.width, self.height, 0, y, self.width, 1)?;
+        Ok(unsafe { slice::from_raw_parts_mut(self.base.add(y as usize * self.stride as usize), self.width as usize) })
+    }
+}
+
+impl<'a> Canvas for Framebuffer<'a> {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) -> Result<()> {
+        Framebuffer::fill_rect(self, x, y, width, height, color)
+    }
+}
+
+// Bytes needed to hold `height` rows of `stride` pixels, the last of which only needs `width`
+fn required_size(width: u32, height: u32, stride: u32) -> Option<usize> {
+    if height == 0 {
+        return Some(0);
+    }
+
+    (stride as usize).checked_mul(height as usize - 1)
+        .and_then(|p| p.checked_add(width as usize))
+        .and_then(|p| p.checked_mul(BYTES_PER_PIXEL))
+}
+
+// Checks that the rectangle lies within a surface of the given size
+pub(crate) fn check_rect(surface_width: u32, surface_height: u32, x: u32, y: u32, width: u32, height: u32) -> Result<()> {
+    let fits = |start: u32, len: u32, limit: u32| start.checked_add(len).map_or(false, |end| end <= limit);
+    if !fits(x, width, surface_width) || !fits(y, height, surface_height) {
+        return Err(EfiErrorKind::InvalidParameter.into());
+    }
+
+    Ok(())
+}
+
+fn encode(format: PixelFormat, color: Color) -> u32 {
+    let (r, g, b) = (color.red as u32, color.green as u32, color.blue as u32);
+    match format {
+        PixelFormat::Rgb => r | g << 8 | b << 16,
+        PixelFormat::Bgr | PixelFormat::BltOnly => b | g << 8 | r << 16,
+        PixelFormat::BitMask(PixelBitmask { red, green, blue, .. }) => {
+            to_mask(color.red, red) | to_mask(color.green, green) | to_mask(color.blue, blue)
+        }
+    }
+}
+
+fn decode(format: PixelFormat, pixel: u32) -> Color {
+    match format {
+        PixelFormat::Rgb => Color::new(pixel as u8, (pixel >> 8) as u8, (pixel >> 16) as u8),
+        PixelFormat::Bgr | PixelFormat::BltOnly => Color::new((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8),
+        PixelFormat::BitMask(PixelBitmask { red, green, blue, .. }) => {
+            Color::new(from_mask(pixel, red), from_mask(pixel, green), from_mask(pixel, blue))
+        }
+    }
+}
+
+// Scales an 8 bit component to the width of the mask and moves it into place
+fn to_mask(value: u8, mask: u32) -> u32 {
+    if mask == 0 {
+        return 0;
+    }
+
+    let shift = mask.trailing_zeros();
+    let bits = (mask >> shift).count_ones();
+    let scaled = if bits >= 8 { (value as u32) << (bits - 8) } else { value as u32 >> (8 - bits) };
+    (scaled << shift) & mask
+}
+
+fn from_mask(pixel: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+
+    let shift = mask.trailing_zeros();
+    let bits = (mask >> shift).count_ones();
+    let value = (pixel & mask) >> shift;
+    if bits >= 8 {
+        (value >> (bits - 8)) as u8
+    } else {
+        // Replicate the high bits into the low ones so that full intensity stays full e.g. 5 bit 0x1F becomes 0xFF
+        let value = value << (8 - bits);
+        (value | value >> bits) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::Vec;
+
+    const RGB565: PixelFormat = PixelFormat::BitMask(PixelBitmask { red: 0xF800, green: 0x07E0, blue: 0x001F, reserved: 0 });
+
+    #[test]
+    fn colors_are_encoded_per_format() {
+        let color = Color::new(0x12, 0x34, 0x56);
+        assert_eq!(encode(PixelFormat::Rgb, color), 0x563412);
+        assert_eq!(encode(PixelFormat::Bgr, color), 0x123456);
+        let xrgb = PixelFormat::BitMask(PixelBitmask { red: 0xFF0000, green: 0xFF00, blue: 0xFF, reserved: 0xFF000000 });
+        assert_eq!(encode(xrgb, color), 0x123456);
+        assert_eq!(encode(RGB565, Color::new(0xFF, 0xFF, 0xFF)), 0xFFFF);
+        assert_eq!(encode(RGB565, Color::new(0xFF, 0, 0)), 0xF800);
+
+        for format in [PixelFormat::Rgb, PixelFormat::Bgr, xrgb].iter() {
+            assert_eq!(decode(*format, encode(*format, color)), color);
+        }
+        assert_eq!(decode(RGB565, 0xFFFF), Color::new(0xFF, 0xFF, 0xFF));
+        assert_eq!(decode(RGB565, 0x07E0), Color::new(0, 0xFF, 0));
+    }
+
+    #[test]
+    fn rects_are_bounds_checked() {
+        assert!(check_rect(800, 600, 0, 0, 800, 600).is_ok());
+        assert!(check_rect(800, 600, 799, 599, 1, 1).is_ok());
+        assert!(check_rect(800, 600, 800, 0, 1, 1).is_err());
+        assert!(check_rect(800, 600, 0, 1, 800, 600).is_err());
+        assert!(check_rect(800, 600, u32::max_value(), 0, 2, 1).is_err());
+    }
+
+    #[test]
+    fn framebuffer_draws_within_bounds() {
+        let mut mem: Vec<u32> = vec![0; 8 * 3]; // 6x3 pixels with a stride of 8
+        let mut fb = unsafe { Framebuffer::from_raw_parts(mem.as_mut_ptr() as *mut u8, mem.len() * 4, 6, 3, 8, PixelFormat::Bgr) }.unwrap();
+        fb.fill_rect(1, 1, 5, 2, Color::new(0xFF, 0, 0)).unwrap();
+        fb.set_pixel(0, 0, Color::new(0, 0, 0xFF)).unwrap();
+        assert_eq!(fb.pixel(5, 2).unwrap(), Color::new(0xFF, 0, 0));
+        assert!(fb.set_pixel(6, 0, Color::default()).is_err());
+        assert!(fb.fill_rect(2, 2, 5, 1, Color::default()).is_err());
+        assert!(fb.row(3).is_err());
+        assert_eq!(fb.row(1).unwrap().len(), 6);
+        drop(fb);
+
+        assert_eq!(mem[0], 0xFF);
+        assert_eq!(&mem[8..16], &[0, 0xFF0000, 0xFF0000, 0xFF0000, 0xFF0000, 0xFF0000, 0, 0]);
+
+        let too_small = unsafe { Framebuffer::from_raw_parts(mem.as_mut_ptr() as *mut u8, mem.len() * 4, 8, 4, 8, PixelFormat::Bgr) };
+        assert!(too_small.is_err());
+    }
+}
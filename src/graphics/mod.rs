@@ -0,0 +1,338 @@
+use ffi::{
+    graphics::{
+        EFI_GRAPHICS_OUTPUT_PROTOCOL,
+        EFI_GRAPHICS_OUTPUT_PROTOCOL_GUID,
+        EFI_GRAPHICS_OUTPUT_MODE_INFORMATION,
+        EFI_EDID_ACTIVE_PROTOCOL,
+        EFI_EDID_ACTIVE_PROTOCOL_GUID,
+        EFI_EDID_DISCOVERED_PROTOCOL,
+        EFI_EDID_DISCOVERED_PROTOCOL_GUID,
+        PIXEL_RED_GREEN_BLUE_RESERVED_8_BIT_PER_COLOR,
+        PIXEL_BLUE_GREEN_RED_RESERVED_8_BIT_PER_COLOR,
+        PIXEL_BIT_MASK,
+    },
+    EFI_HANDLE,
+    UINTN,
+};
+use boxed::EfiBox;
+use utils::{handles_by_protocol, open_protocol};
+use alloc::Vec;
+use core::{ptr, slice};
+use {Result, EfiErrorKind, system_table};
+
+mod framebuffer;
+mod blt;
+mod bmp;
+mod font;
+mod text;
+mod surface;
+
+pub use self::framebuffer::Framebuffer;
+pub use self::blt::{BltPixel, BltImage};
+pub use self::bmp::{SplashScaling, screenshot};
+#[cfg(feature = "fs")] pub use self::bmp::save_screenshot;
+pub use self::font::{Font, Glyph};
+pub use self::text::TextRenderer;
+pub use self::surface::{Surface, Rect};
+
+/// A 24 bit color
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color { red: 0, green: 0, blue: 0 };
+    pub const WHITE: Color = Color { red: 0xFF, green: 0xFF, blue: 0xFF };
+
+    pub fn new(red: u8, green: u8, blue: u8) -> Self {
+        Color { red, green, blue }
+    }
+}
+
+/// Something that can be drawn on
+pub trait Canvas {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    /// Fills the given rectangle. Fails with `InvalidParameter` if any part of it is outside the canvas.
+    fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) -> Result<()>;
+}
+
+/// Bit masks describing where each color lives in a pixel of a `PixelFormat::BitMask` framebuffer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PixelBitmask {
+    pub red: u32,
+    pub green: u32,
+    pub blue: u32,
+    pub reserved: u32,
+}
+
+/// The layout of a pixel in the framebuffer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Byte 0 is red, byte 1 green and byte 2 blue
+    Rgb,
+    /// Byte 0 is blue, byte 1 green and byte 2 red
+    Bgr,
+    BitMask(PixelBitmask),
+    /// There's no framebuffer. Drawing is only possible with Blt.
+    BltOnly,
+}
+
+impl PixelFormat {
+    fn from_efi(info: &EFI_GRAPHICS_OUTPUT_MODE_INFORMATION) -> Self {
+        match info.PixelFormat {
+            PIXEL_RED_GREEN_BLUE_RESERVED_8_BIT_PER_COLOR => PixelFormat::Rgb,
+            PIXEL_BLUE_GREEN_RED_RESERVED_8_BIT_PER_COLOR => PixelFormat::Bgr,
+            PIXEL_BIT_MASK => {
+                let masks = &info.PixelInformation;
+                PixelFormat::BitMask(PixelBitmask { red: masks.RedMask, green: masks.GreenMask, blue: masks.BlueMask, reserved: masks.ReservedMask })
+            },
+            _ => PixelFormat::BltOnly // The framebuffer of a format we don't know about can't be used safely
+        }
+    }
+}
+
+/// A video mode supported by a graphics output device
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ModeInfo {
+    pub number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: PixelFormat,
+    /// Pixels per scan line. Can be greater than `width`.
+    pub stride: u32,
+}
+
+impl ModeInfo {
+    fn from_efi(number: u32, info: &EFI_GRAPHICS_OUTPUT_MODE_INFORMATION) -> Self {
+        ModeInfo {
+            number,
+            width: info.HorizontalResolution,
+            height: info.VerticalResolution,
+            pixel_format: PixelFormat::from_efi(info),
+            stride: info.PixelsPerScanLine,
+        }
+    }
+}
+
+/// A graphics output device (EFI_GRAPHICS_OUTPUT_PROTOCOL)
+pub struct Gop {
+    handle: EFI_HANDLE,
+    protocol: *mut EFI_GRAPHICS_OUTPUT_PROTOCOL,
+}
+
+impl Gop {
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol::<EFI_GRAPHICS_OUTPUT_PROTOCOL>(handle, &EFI_GRAPHICS_OUTPUT_PROTOCOL_GUID)?;
+        Ok(Gop { handle, protocol: protocol as *mut _ })
+    }
+
+    /// Opens all graphics output devices in the system
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_GRAPHICS_OUTPUT_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    /// The device behind the console if it has one, otherwise the first one found.
+    /// Fails with `NotFound` if there's no graphics output device at all.
+    pub fn primary() -> Result<Self> {
+        if let Ok(gop) = Self::from_handle(system_table().ConsoleOutHandle) {
+            return Ok(gop);
+        }
+
+        match Self::all()?.into_iter().next() {
+            Some(gop) => Ok(gop),
+            None => Err(EfiErrorKind::NotFound.into())
+        }
+    }
+
+    /// The handle the device is on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// The number of modes the device supports. Valid mode numbers are 0 to this minus 1.
+    pub fn mode_count(&self) -> u32 {
+        unsafe { (*(*self.protocol).Mode).MaxMode }
+    }
+
+    /// Describes the given mode
+    pub fn query_mode(&self, number: u32) -> Result<ModeInfo> {
+        let mut size: UINTN = 0;
+        let mut info: *mut EFI_GRAPHICS_OUTPUT_MODE_INFORMATION = ptr::null_mut();
+        unsafe {
+            ret_on_err!(((*self.protocol).QueryMode)(self.protocol, number, &mut size, &mut info));
+        }
+
+        let info = unsafe { EfiBox::from_raw(info) }; // The firmware allocated it and we must free it
+        Ok(ModeInfo::from_efi(number, unsafe { &*info.as_raw() }))
+    }
+
+    /// All modes the device supports
+    pub fn modes(&self) -> Result<Vec<ModeInfo>> {
+        (0..self.mode_count()).map(|n| self.query_mode(n)).collect()
+    }
+
+    /// The mode the device is currently in
+    pub fn current_mode(&self) -> Result<ModeInfo> {
+        let mode = unsafe { &*(*self.protocol).Mode };
+        if mode.Info.is_null() {
+            return Err(EfiErrorKind::NotReady.into());
+        }
+
+        Ok(ModeInfo::from_efi(mode.Mode, unsafe { &*mode.Info }))
+    }
+
+    /// Switches to the given mode. This clears the screen to black.
+    pub fn set_mode(&mut self, number: u32) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).SetMode)(self.protocol, number));
+        }
+
+        Ok(())
+    }
+
+    /// Switches to a mode with the given resolution. Fails with `NotFound` if there isn't one.
+    pub fn set_resolution(&mut self, width: u32, height: u32) -> Result<ModeInfo> {
+        let mode = match self.modes()?.into_iter().find(|m| m.width == width && m.height == height) {
+            Some(mode) => mode,
+            None => return Err(EfiErrorKind::NotFound.into())
+        };
+
+        self.set_mode(mode.number)?;
+        Ok(mode)
+    }
+
+    /// Switches to the mode with the most pixels
+    pub fn set_highest_mode(&mut self) -> Result<ModeInfo> {
+        let mode = match highest_mode(&self.modes()?) {
+            Some(mode) => mode,
+            None => return Err(EfiErrorKind::NotFound.into())
+        };
+
+        self.set_mode(mode.number)?;
+        Ok(mode)
+    }
+
+    /// The display's preferred resolution as reported by its EDID, if known
+    pub fn native_resolution(&self) -> Option<(u32, u32)> {
+        self.edid().and_then(edid_preferred_resolution)
+    }
+
+    /// Switches to the display's native resolution. Falls back to the highest mode if the native
+    /// resolution isn't known or there's no mode for it.
+    pub fn set_native_mode(&mut self) -> Result<ModeInfo> {
+        if let Some((width, height)) = self.native_resolution() {
+            match self.set_resolution(width, height) {
+                Err(ref e) if e.kind() == EfiErrorKind::NotFound => (),
+                result => return result
+            }
+        }
+
+        self.set_highest_mode()
+    }
+
+    /// The physical address of the framebuffer in the current mode.
+    /// Meaningless if the current mode's pixel format is `PixelFormat::BltOnly`.
+    pub fn framebuffer_base(&self) -> u64 {
+        unsafe { (*(*self.protocol).Mode).FrameBufferBase }
+    }
+
+    /// The size in bytes of the framebuffer in the current mode
+    pub fn framebuffer_size(&self) -> usize {
+        unsafe { (*(*self.protocol).Mode).FrameBufferSize }
+    }
+
+    /// Direct access to the framebuffer of the current mode. See `Framebuffer::new()`.
+    pub fn framebuffer(&mut self) -> Result<Framebuffer> {
+        Framebuffer::new(self)
+    }
+
+    // The EDID of the connected display. The active EDID (as overridden by the platform) is preferred over the discovered one.
+    fn edid(&self) -> Option<&[u8]> {
+        if let Ok(active) = open_protocol::<EFI_EDID_ACTIVE_PROTOCOL>(self.handle, &EFI_EDID_ACTIVE_PROTOCOL_GUID) {
+            if unsafe { !(*active).Edid.is_null() } {
+                return Some(unsafe { slice::from_raw_parts((*active).Edid, (*active).SizeOfEdid as usize) });
+            }
+        }
+
+        if let Ok(discovered) = open_protocol::<EFI_EDID_DISCOVERED_PROTOCOL>(self.handle, &EFI_EDID_DISCOVERED_PROTOCOL_GUID) {
+            if unsafe { !(*discovered).Edid.is_null() } {
+                return Some(unsafe { slice::from_raw_parts((*discovered).Edid, (*discovered).SizeOfEdid as usize) });
+            }
+        }
+
+        None
+    }
+}
+
+// The mode with the most pixels. The lowest numbered one wins a tie.
+fn highest_mode(modes: &[ModeInfo]) -> Option<ModeInfo> {
+    let mut highest: Option<ModeInfo> = None;
+    for mode in modes {
+        let pixels = mode.width as u64 * mode.height as u64;
+        match highest {
+            Some(h) if h.width as u64 * h.height as u64 >= pixels => (),
+            _ => highest = Some(*mode)
+        }
+    }
+
+    highest
+}
+
+const EDID_HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+const EDID_BASE_BLOCK_SIZE: usize = 128;
+const EDID_FIRST_DETAILED_TIMING: usize = 54;
+
+// The first detailed timing descriptor of an EDID is the display's preferred mode
+fn edid_preferred_resolution(edid: &[u8]) -> Option<(u32, u32)> {
+    if edid.len() < EDID_BASE_BLOCK_SIZE || edid[..8] != EDID_HEADER {
+        return None;
+    }
+
+    let dtd = &edid[EDID_FIRST_DETAILED_TIMING..EDID_FIRST_DETAILED_TIMING + 18];
+    if dtd[0] == 0 && dtd[1] == 0 { // A zero pixel clock means it's a display descriptor rather than a timing
+        return None;
+    }
+
+    let width = dtd[2] as u32 | ((dtd[4] as u32 & 0xF0) << 4);
+    let height = dtd[5] as u32 | ((dtd[7] as u32 & 0xF0) << 4);
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    Some((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(number: u32, width: u32, height: u32) -> ModeInfo {
+        ModeInfo { number, width, height, pixel_format: PixelFormat::Bgr, stride: width }
+    }
+
+    #[test]
+    fn highest_mode_has_most_pixels() {
+        let modes = [mode(0, 800, 600), mode(1, 1920, 1080), mode(2, 1024, 768), mode(3, 1080, 1920)];
+        assert_eq!(highest_mode(&modes).map(|m| m.number), Some(1));
+        assert_eq!(highest_mode(&[]), None);
+    }
+
+    #[test]
+    fn edid_preferred_resolution_is_parsed() {
+        let mut edid = [0_u8; 128];
+        edid[..8].copy_from_slice(&EDID_HEADER);
+        assert_eq!(edid_preferred_resolution(&edid), None); // No detailed timing
+
+        // 1920x1080 at 148.5 MHz
+        edid[54..62].copy_from_slice(&[0x02, 0x3A, 0x80, 0x18, 0x71, 0x38, 0x2D, 0x40]);
+        assert_eq!(edid_preferred_resolution(&edid), Some((1920, 1080)));
+        assert_eq!(edid_preferred_resolution(&edid[..100]), None);
+
+        edid[0] = 0xFF;
+        assert_eq!(edid_preferred_resolution(&edid), None);
+    }
+}
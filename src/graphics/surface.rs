@@ -0,0 +1,227 @@
+use super::{Gop, Canvas, Color, BltImage, framebuffer::check_rect};
+use alloc::Vec;
+use core::cmp;
+use Result;
+
+// Beyond this many separate dirty regions they're merged into one to keep the number of Blts down
+const MAX_DIRTY_RECTS: usize = 16;
+
+/// A rectangle of pixels
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    fn right(&self) -> u32 {
+        self.x.saturating_add(self.width)
+    }
+
+    fn bottom(&self) -> u32 {
+        self.y.saturating_add(self.height)
+    }
+
+    // Whether the two overlap or share an edge, i.e. their union covers no more than the two do
+    fn touches(&self, other: &Rect) -> bool {
+        self.x <= other.right() && other.x <= self.right() && self.y <= other.bottom() && other.y <= self.bottom()
+    }
+
+    fn union(&self, other: &Rect) -> Rect {
+        let (x, y) = (cmp::min(self.x, other.x), cmp::min(self.y, other.y));
+        Rect::new(x, y, cmp::max(self.right(), other.right()) - x, cmp::max(self.bottom(), other.bottom()) - y)
+    }
+}
+
+/// An off screen drawing surface. Drawing happens in memory and `present()` copies only the regions
+/// that changed to the screen in one go, so redrawing e.g. a progress bar every frame doesn't flicker.
+pub struct Surface {
+    image: BltImage,
+    dirty: Vec<Rect>,
+}
+
+impl Surface {
+    /// A black surface of the given size. All of it is dirty so the first `present()` draws everything.
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut surface = Surface { image: BltImage::new(width, height), dirty: Vec::new() };
+        surface.mark_all_dirty();
+        surface
+    }
+
+    /// A surface the size of the screen in the device's current mode
+    pub fn for_screen(gop: &Gop) -> Result<Self> {
+        let mode = gop.current_mode()?;
+        Ok(Self::new(mode.width, mode.height))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    /// The surface's contents
+    pub fn image(&self) -> &BltImage {
+        &self.image
+    }
+
+    /// Regions changed since the last `present()`
+    pub fn dirty_rects(&self) -> &[Rect] {
+        &self.dirty
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Color> {
+        self.image.pixel(x, y)
+    }
+
+    /// Fails with `InvalidParameter` if the pixel is outside the surface
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color) -> Result<()> {
+        self.image.set_pixel(x, y, color)?;
+        self.mark_dirty(Rect::new(x, y, 1, 1));
+        Ok(())
+    }
+
+    /// Fails with `InvalidParameter` if any part of the rectangle is outside the surface
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) -> Result<()> {
+        self.image.fill_rect(x, y, width, height, color)?;
+        self.mark_dirty(Rect::new(x, y, width, height));
+        Ok(())
+    }
+
+    pub fn clear(&mut self, color: Color) -> Result<()> {
+        let (width, height) = (self.width(), self.height());
+        self.fill_rect(0, 0, width, height, color)
+    }
+
+    /// Copies the whole image onto the surface with its top left corner at (`x`, `y`).
+    /// Fails with `InvalidParameter` if it doesn't fit.
+    pub fn draw_image(&mut self, image: &BltImage, x: u32, y: u32) -> Result<()> {
+        check_rect(self.width(), self.height(), x, y, image.width(), image.height())?;
+        let (surface_width, image_width) = (self.width() as usize, image.width() as usize);
+        for (row, src) in image.pixels().chunks(cmp::max(image_width, 1)).enumerate() {
+            let start = (y as usize + row) * surface_width + x as usize;
+            self.image.pixels_mut()[start..start + image_width].copy_from_slice(src);
+        }
+
+        self.mark_dirty(Rect::new(x, y, image.width(), image.height()));
+        Ok(())
+    }
+
+    /// Records that the region has to be copied to the screen on the next `present()`.
+    /// Only needed after changing the surface through something other than its own methods.
+    /// Any part of the rect outside the surface is ignored.
+    pub fn mark_dirty(&mut self, rect: Rect) {
+        let (width, height) = (self.width(), self.height());
+        if rect.x >= width || rect.y >= height {
+            return;
+        }
+
+        let clipped = Rect::new(rect.x, rect.y, cmp::min(rect.right(), width) - rect.x, cmp::min(rect.bottom(), height) - rect.y);
+        add_dirty_rect(&mut self.dirty, clipped);
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.clear();
+        let (width, height) = (self.width(), self.height());
+        self.mark_dirty(Rect::new(0, 0, width, height));
+    }
+
+    /// Copies the regions changed since the last call to the screen, with the surface's top left corner at the screen's
+    pub fn present(&mut self, gop: &mut Gop) -> Result<()> {
+        self.present_at(gop, 0, 0)
+    }
+
+    /// Copies the regions changed since the last call to the screen, with the surface's top left corner at (`x`, `y`)
+    pub fn present_at(&mut self, gop: &mut Gop, x: u32, y: u32) -> Result<()> {
+        for rect in &self.dirty {
+            let (dest_x, dest_y) = (x.saturating_add(rect.x), y.saturating_add(rect.y));
+            gop.blt_buffer_to_video(&self.image, rect.x, rect.y, dest_x, dest_y, rect.width, rect.height)?;
+        }
+
+        self.dirty.clear();
+        Ok(())
+    }
+}
+
+impl Canvas for Surface {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) -> Result<()> {
+        Surface::fill_rect(self, x, y, width, height, color)
+    }
+}
+
+// Adds the rect to the list merging it with any it touches
+fn add_dirty_rect(dirty: &mut Vec<Rect>, rect: Rect) {
+    if rect.width == 0 || rect.height == 0 {
+        return;
+    }
+
+    // A merge can make the rect touch ones it didn't before so keep going until nothing changes
+    let mut rect = rect;
+    while let Some(i) = dirty.iter().position(|d| d.touches(&rect)) {
+        rect = rect.union(&dirty.swap_remove(i));
+    }
+    dirty.push(rect);
+
+    if dirty.len() > MAX_DIRTY_RECTS {
+        let all = dirty.iter().fold(dirty[0], |acc, r| acc.union(r));
+        dirty.clear();
+        dirty.push(all);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dirty_rects_are_merged() {
+        let mut dirty = Vec::new();
+        add_dirty_rect(&mut dirty, Rect::new(0, 0, 10, 10));
+        add_dirty_rect(&mut dirty, Rect::new(100, 100, 10, 10));
+        assert_eq!(dirty.len(), 2);
+        add_dirty_rect(&mut dirty, Rect::new(5, 5, 10, 10));
+        assert!(dirty.contains(&Rect::new(0, 0, 15, 15)));
+        add_dirty_rect(&mut dirty, Rect::new(15, 0, 90, 100)); // Touches both
+        assert_eq!(dirty, vec![Rect::new(0, 0, 110, 110)]);
+        add_dirty_rect(&mut dirty, Rect::new(200, 200, 0, 5));
+        assert_eq!(dirty.len(), 1);
+
+        let mut dirty = Vec::new();
+        for i in 0..MAX_DIRTY_RECTS as u32 + 1 {
+            add_dirty_rect(&mut dirty, Rect::new(i * 10, 0, 5, 5));
+        }
+        assert_eq!(dirty, vec![Rect::new(0, 0, MAX_DIRTY_RECTS as u32 * 10 + 5, 5)]);
+    }
+
+    #[test]
+    fn drawing_marks_regions_dirty() {
+        let mut surface = Surface::new(20, 10);
+        assert_eq!(surface.dirty_rects(), &[Rect::new(0, 0, 20, 10)]);
+        surface.dirty.clear();
+
+        let mut image = BltImage::new(2, 2);
+        image.fill_rect(0, 0, 2, 2, Color::WHITE).unwrap();
+        surface.draw_image(&image, 18, 8).unwrap();
+        assert_eq!(surface.pixel(19, 9), Some(Color::WHITE));
+        assert_eq!(surface.pixel(17, 9), Some(Color::BLACK));
+        assert!(surface.draw_image(&image, 19, 8).is_err());
+        surface.set_pixel(0, 0, Color::WHITE).unwrap();
+        assert_eq!(surface.dirty_rects(), &[Rect::new(18, 8, 2, 2), Rect::new(0, 0, 1, 1)]);
+    }
+}
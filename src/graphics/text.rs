@@ -0,0 +1,166 @@
+use super::{Canvas, Color, Font};
+use core::cmp;
+use Result;
+
+/// Draws text onto anything that implements `Canvas` using a bitmap font.
+/// Useful when the text console is no longer shown e.g. after switching graphics modes.
+pub struct TextRenderer<'f> {
+    font: Font<'f>,
+    color: Color,
+    background: Option<Color>,
+    scale: u32,
+}
+
+impl TextRenderer<'static> {
+    /// A renderer using the built in 8x16 font
+    pub fn with_default_font() -> Self {
+        TextRenderer::new(Font::default_8x16())
+    }
+}
+
+impl<'f> TextRenderer<'f> {
+    /// Draws white text without a background at scale 1
+    pub fn new(font: Font<'f>) -> Self {
+        TextRenderer { font, color: Color::WHITE, background: None, scale: 1 }
+    }
+
+    pub fn font(&self) -> &Font<'f> {
+        &self.font
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    /// None if the text is drawn over whatever is already on the canvas
+    pub fn background(&self) -> Option<Color> {
+        self.background
+    }
+
+    pub fn set_background(&mut self, background: Option<Color>) {
+        self.background = background;
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Every pixel of the font is drawn as a `scale` x `scale` square. A scale of 0 is treated as 1.
+    pub fn set_scale(&mut self, scale: u32) {
+        self.scale = cmp::max(scale, 1);
+    }
+
+    /// The size of a character cell in pixels at the current scale
+    pub fn char_size(&self) -> (u32, u32) {
+        (self.font.width() * self.scale, self.font.height() * self.scale)
+    }
+
+    /// The size of the box the text would take up when drawn
+    pub fn text_size(&self, text: &str) -> (u32, u32) {
+        let (char_width, char_height) = self.char_size();
+        let (mut widest, mut lines) = (0, 0);
+        for line in text.split('\n') {
+            widest = cmp::max(widest, line.chars().filter(|c| !c.is_control()).count() as u32);
+            lines += 1;
+        }
+
+        (widest * char_width, lines * char_height)
+    }
+
+    /// Draws the text with its top left corner at (`x`, `y`). A '\n' starts a new line back at `x`.
+    /// Whatever falls outside the canvas is clipped. Returns the position just after the last character.
+    pub fn draw_str<C: Canvas>(&self, canvas: &mut C, x: u32, y: u32, text: &str) -> Result<(u32, u32)> {
+        let (char_width, char_height) = self.char_size();
+        let (mut cx, mut cy) = (x, y);
+        for c in text.chars() {
+            if c == '\n' {
+                cx = x;
+                cy = cy.saturating_add(char_height);
+                continue;
+            }
+
+            if c.is_control() {
+                continue;
+            }
+
+            self.draw_char(canvas, cx, cy, c)?;
+            cx = cx.saturating_add(char_width);
+        }
+
+        Ok((cx, cy))
+    }
+
+    fn draw_char<C: Canvas>(&self, canvas: &mut C, x: u32, y: u32, c: char) -> Result<()> {
+        let glyph = self.font.glyph_or_replacement(c);
+        let (canvas_width, canvas_height) = (canvas.width(), canvas.height());
+        for gy in 0..self.font.height() {
+            let py = y.saturating_add(gy * self.scale);
+            if py >= canvas_height {
+                break;
+            }
+
+            for gx in 0..self.font.width() {
+                let px = x.saturating_add(gx * self.scale);
+                if px >= canvas_width {
+                    break;
+                }
+
+                let color = match glyph {
+                    Some(ref g) if g.is_set(gx, gy) => self.color,
+                    _ => match self.background {
+                        Some(background) => background,
+                        None => continue
+                    }
+                };
+
+                let width = cmp::min(self.scale, canvas_width - px);
+                let height = cmp::min(self.scale, canvas_height - py);
+                canvas.fill_rect(px, py, width, height, color)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graphics::BltImage;
+
+    #[test]
+    fn text_is_measured() {
+        let mut renderer = TextRenderer::with_default_font();
+        assert_eq!(renderer.text_size("abc"), (24, 16));
+        assert_eq!(renderer.text_size("abc\nhello"), (40, 32));
+        renderer.set_scale(2);
+        assert_eq!(renderer.text_size("ab"), (32, 32));
+    }
+
+    #[test]
+    fn text_is_drawn_and_clipped() {
+        let mut image = BltImage::new(12, 20);
+        let mut renderer = TextRenderer::with_default_font();
+        renderer.set_background(Some(Color::new(0, 0, 0x80)));
+        assert_eq!(renderer.draw_str(&mut image, 0, 0, "AB").unwrap(), (16, 0));
+
+        // The apex of the 'A' is the single pixel at (3, 2)
+        assert_eq!(image.pixel(3, 2), Some(Color::WHITE));
+        assert_eq!(image.pixel(2, 2), Some(Color::new(0, 0, 0x80)));
+        // The 'B' is clipped to 4 columns and the canvas below the text untouched
+        assert_eq!(image.pixel(8, 2), Some(Color::WHITE));
+        assert_eq!(image.pixel(0, 16), Some(Color::BLACK));
+
+        renderer.set_scale(2);
+        renderer.set_background(None);
+        let mut image = BltImage::new(16, 32);
+        renderer.draw_str(&mut image, 0, 0, "A").unwrap();
+        assert_eq!(image.pixel(6, 4), Some(Color::WHITE));
+        assert_eq!(image.pixel(7, 5), Some(Color::WHITE));
+        assert_eq!(image.pixel(5, 4), Some(Color::BLACK));
+    }
+}
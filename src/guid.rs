@@ -0,0 +1,128 @@
+//! Formatting and parsing of GUIDs in the registry format, e.g. "C12A7328-F81F-11D2-BA4B-00A0C93EC93B"
+
+use core::{fmt, str::FromStr};
+use {EfiError, EfiErrorKind, Guid};
+
+/// Creates a `Guid` from its registry format string. Can be used to define constants:
+///
+/// ```ignore
+/// const ESP: Guid = guid!("C12A7328-F81F-11D2-BA4B-00A0C93EC93B");
+/// ```
+///
+/// A malformed string fails const evaluation (or panics when not used in a constant).
+#[macro_export]
+macro_rules! guid {
+    ($s:expr) => {
+        $crate::guid::parse_const(unsafe { $crate::guid::StrBytes { s: $s }.bytes })
+    }
+}
+
+// Lets the guid! macro get at the bytes of a str in a const context where as_bytes() is not available
+#[doc(hidden)]
+#[allow(unions_with_drop_fields)]
+pub union StrBytes {
+    pub s: &'static str,
+    pub bytes: &'static [u8],
+}
+
+// Value of the hex digits from '0' to 'f'. Anything in between that is not a hex digit is 0xFF.
+const HEX_VALUES: [u8; 55] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, // '0'..'9'
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // ':'..'@'
+    10, 11, 12, 13, 14, 15, // 'A'..'F'
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // 'G'..'`'
+    10, 11, 12, 13, 14, 15, // 'a'..'f'
+];
+
+// Indexing this with anything but zero fails. It's how the const fns below reject bad input
+// since they can't branch or panic.
+const VALID: [u8; 1] = [0];
+
+const fn hex_digit(c: u8) -> u8 {
+    VALID[(HEX_VALUES[(c - b'0') as usize] >> 4) as usize] + HEX_VALUES[(c - b'0') as usize]
+}
+
+const fn hex_byte(s: &[u8], i: usize) -> u8 {
+    hex_digit(s[i]) << 4 | hex_digit(s[i + 1])
+}
+
+const fn dashes(s: &[u8]) -> usize {
+    ((s[8] ^ b'-') | (s[13] ^ b'-') | (s[18] ^ b'-') | (s[23] ^ b'-')) as usize
+}
+
+#[doc(hidden)]
+pub const fn parse_const(s: &[u8]) -> Guid {
+    ::ffi::EFI_GUID(
+        (VALID[dashes(s)] as u32) << 24 // Only checks the dashes, contributes nothing to the value
+            | (hex_byte(s, 0) as u32) << 24 | (hex_byte(s, 2) as u32) << 16 | (hex_byte(s, 4) as u32) << 8 | hex_byte(s, 6) as u32,
+        (hex_byte(s, 9) as u16) << 8 | hex_byte(s, 11) as u16,
+        (hex_byte(s, 14) as u16) << 8 | hex_byte(s, 16) as u16,
+        [hex_byte(s, 19), hex_byte(s, 21), hex_byte(s, 24), hex_byte(s, 26), hex_byte(s, 28), hex_byte(s, 30), hex_byte(s, 32), hex_byte(s, 34)],
+    )
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let d = self.3;
+        write!(f, "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}", self.0, self.1, self.2, d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7])
+    }
+}
+
+impl FromStr for Guid {
+    type Err = EfiError;
+
+    /// Parses the registry format. Hex digits may be in either case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s.as_bytes();
+        let is_valid = bytes.len() == 36 && bytes.iter().enumerate().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => *c == b'-',
+            _ => c.is_ascii_hexdigit(),
+        });
+
+        if !is_valid {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        Ok(parse_const(bytes))
+    }
+}
+
+/// GUIDs are serialized in the registry format
+#[cfg(feature = "with-serde")]
+impl ::serde::Serialize for Guid {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "with-serde")]
+impl<'de> ::serde::Deserialize<'de> for Guid {
+    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ::utils::deserialize_from_str(deserializer, "a GUID in registry format")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Guid;
+    use ffi::EFI_GUID;
+
+    const ESP: Guid = guid!("C12A7328-F81F-11D2-BA4B-00A0C93EC93B");
+
+    #[test]
+    fn guid_macro() {
+        assert_eq!(ESP, EFI_GUID(0xC12A7328, 0xF81F, 0x11D2, [0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B]));
+        assert_eq!(guid!("c12a7328-f81f-11d2-ba4b-00a0c93ec93b"), ESP);
+    }
+
+    #[test]
+    fn display_and_parse() {
+        assert_eq!(format!("{}", ESP), "C12A7328-F81F-11D2-BA4B-00A0C93EC93B");
+        assert_eq!("c12a7328-f81f-11d2-ba4b-00a0c93ec93b".parse::<Guid>().unwrap(), ESP);
+        assert!("C12A7328-F81F-11D2-BA4B-00A0C93EC93".parse::<Guid>().is_err());
+        assert!("C12A7328-F81F-11D2-BA4B-00A0C93EC93B0".parse::<Guid>().is_err());
+        assert!("C12A7328+F81F-11D2-BA4B-00A0C93EC93B".parse::<Guid>().is_err());
+        assert!("G12A7328-F81F-11D2-BA4B-00A0C93EC93B".parse::<Guid>().is_err());
+    }
+}
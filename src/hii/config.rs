@@ -0,0 +1,406 @@
+use ffi::{
+    hii::*,
+    CHAR16,
+};
+use utils::{as_slice, guid_from_bytes, guid_to_bytes, locate_protocol, to_null_terminated_utf16};
+use boxed::EfiBox;
+use alloc::{String, Vec};
+use core::{fmt, ptr};
+use {Result, EfiError, EfiErrorKind, Guid};
+
+// Reading and changing the settings drivers keep in their storage (e.g. the options in the firmware's setup
+// screens) through EFI_HII_CONFIG_ROUTING_PROTOCOL. Settings are exchanged as configuration strings like
+// `GUID=...&NAME=...&PATH=...&OFFSET=0010&WIDTH=0001&VALUE=01`, one header per storage followed by its settings.
+// The header fields are hex encoded: the GUID's bytes, the name's UTF-16 code units and the device path's bytes.
+// Which offset holds which setting is described by the driver's forms, it isn't in the strings themselves.
+
+// Widths are written as four hex digits. Anything wider is garbage and not worth allocating for.
+const MAX_WIDTH: usize = 0xFFFF;
+
+/// The settings of one storage, as found in a configuration string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub guid: Guid,
+    /// The name of the storage, often the name of the variable backing it
+    pub name: String,
+    /// The device path of the driver that owns the storage
+    pub path: Vec<u8>,
+    /// The ID of an alternate configuration (e.g. 0 for the defaults) or None for the current one
+    pub alt_config: Option<u16>,
+    pub elements: Vec<Element>,
+}
+
+/// A setting in a storage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Element {
+    /// Bytes of a buffer storage. The value is None in requests.
+    Block { offset: usize, width: usize, value: Option<Vec<u8>> },
+    /// A setting of a name/value storage. The value is kept as the hex digits the driver gave it in.
+    NameValue { name: String, value: Option<String> },
+}
+
+impl Config {
+    /// A configuration for the storage with no settings in it. As a request it asks for the whole storage.
+    pub fn new(guid: Guid, name: &str, path: &[u8]) -> Self {
+        Config { guid, name: String::from(name), path: path.to_vec(), alt_config: None, elements: Vec::new() }
+    }
+
+    /// The bytes at the offset if a block with a value covers them
+    pub fn value(&self, offset: usize, width: usize) -> Option<&[u8]> {
+        self.elements.iter().rev().filter_map(|element| match *element {
+            Element::Block { offset: start, value: Some(ref value), .. } if covers(start, value.len(), offset, width) => {
+                Some(&value[offset - start..offset - start + width])
+            },
+            _ => None,
+        }).next()
+    }
+
+    /// Changes the bytes at the offset. A block already covering them is updated, otherwise a new block is added.
+    pub fn set_value(&mut self, offset: usize, bytes: &[u8]) {
+        for element in self.elements.iter_mut().rev() {
+            if let Element::Block { offset: start, value: Some(ref mut value), .. } = *element {
+                if covers(start, value.len(), offset, bytes.len()) {
+                    value[offset - start..offset - start + bytes.len()].copy_from_slice(bytes);
+                    return;
+                }
+            }
+        }
+
+        self.elements.push(Element::Block { offset, width: bytes.len(), value: Some(bytes.to_vec()) });
+    }
+
+    /// The value of a setting in a name/value storage
+    pub fn named_value(&self, name: &str) -> Option<&str> {
+        self.elements.iter().rev().filter_map(|element| match *element {
+            Element::NameValue { name: ref n, value: Some(ref value) } if n == name => Some(&value[..]),
+            _ => None,
+        }).next()
+    }
+
+    // The same configuration with the values left out, as ExtractConfig() expects it
+    fn to_request(&self) -> Config {
+        let elements = self.elements.iter().map(|element| match *element {
+            Element::Block { offset, width, .. } => Element::Block { offset, width, value: None },
+            Element::NameValue { ref name, .. } => Element::NameValue { name: name.clone(), value: None },
+        }).collect();
+        Config { elements, ..self.clone() }
+    }
+}
+
+// Whether a block of the given length at start holds all the bytes from offset to offset + width
+fn covers(start: usize, len: usize, offset: usize, width: usize) -> bool {
+    offset >= start && (offset - start).checked_add(width).map_or(false, |end| end <= len)
+}
+
+/// Prints the configuration in the configuration string format
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GUID=")?;
+        write_hex(f, &guid_to_bytes(&self.guid))?;
+        write!(f, "&NAME=")?;
+        for c in self.name.encode_utf16() {
+            write!(f, "{:04x}", c)?;
+        }
+        write!(f, "&PATH=")?;
+        write_hex(f, &self.path)?;
+        if let Some(id) = self.alt_config {
+            write!(f, "&ALTCFG={:04x}", id)?;
+        }
+
+        for element in &self.elements {
+            match *element {
+                Element::Block { offset, width, ref value } => {
+                    write!(f, "&OFFSET={:04x}&WIDTH={:04x}", offset, width)?;
+                    if let Some(ref value) = *value {
+                        // Values are written as numbers, i.e. starting with the last byte
+                        write!(f, "&VALUE=")?;
+                        for b in value.iter().rev() {
+                            write!(f, "{:02x}", b)?;
+                        }
+                    }
+                },
+                Element::NameValue { ref name, ref value } => {
+                    write!(f, "&{}", name)?;
+                    if let Some(ref value) = *value {
+                        write!(f, "={}", value)?;
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter, bytes: &[u8]) -> fmt::Result {
+    for b in bytes {
+        write!(f, "{:02x}", b)?;
+    }
+
+    Ok(())
+}
+
+/// Joins configurations into one configuration string
+pub fn format(configs: &[Config]) -> String {
+    configs.iter().map(|config| format!("{}", config)).collect::<Vec<_>>().join("&")
+}
+
+/// Splits a configuration string into the configurations of each storage
+pub fn parse(s: &str) -> Result<Vec<Config>> {
+    let mut configs: Vec<Config> = Vec::new();
+    if s.is_empty() {
+        return Ok(configs);
+    }
+
+    let mut parts = s.split('&').peekable();
+    while let Some(part) = parts.next() {
+        let (key, value) = split_pair(part);
+        if key == "GUID" {
+            let guid = decode_bytes(value.ok_or_else(malformed)?)?;
+            if guid.len() != 16 {
+                return Err(malformed());
+            }
+
+            let name = match parts.next().map(split_pair) {
+                Some(("NAME", Some(name))) => decode_name(name)?,
+                _ => return Err(malformed()),
+            };
+            let path = match parts.next().map(split_pair) {
+                Some(("PATH", Some(path))) => decode_bytes(path)?,
+                _ => return Err(malformed()),
+            };
+
+            configs.push(Config { guid: guid_from_bytes(&guid), name, path, alt_config: None, elements: Vec::new() });
+            continue;
+        }
+
+        let config = configs.last_mut().ok_or_else(malformed)?; // Settings must come after a header
+        match key {
+            "ALTCFG" => config.alt_config = Some(parse_number(value.ok_or_else(malformed)?)? as u16),
+            "OFFSET" => {
+                let offset = parse_number(value.ok_or_else(malformed)?)?;
+                let width = match parts.next().map(split_pair) {
+                    Some(("WIDTH", Some(width))) => parse_number(width)?,
+                    _ => return Err(malformed()),
+                };
+                if width > MAX_WIDTH {
+                    return Err(malformed());
+                }
+
+                let value = match parts.peek().map(|&part| split_pair(part)) {
+                    Some(("VALUE", Some(value))) => Some(decode_value(value, width)?),
+                    _ => None,
+                };
+                if value.is_some() {
+                    parts.next();
+                }
+
+                config.elements.push(Element::Block { offset, width, value });
+            },
+            "" => return Err(malformed()),
+            name => config.elements.push(Element::NameValue { name: String::from(name), value: value.map(String::from) }),
+        }
+    }
+
+    Ok(configs)
+}
+
+fn malformed() -> EfiError {
+    EfiError::from(EfiErrorKind::InvalidParameter).add_context("malformed configuration string")
+}
+
+fn split_pair(part: &str) -> (&str, Option<&str>) {
+    match part.find('=') {
+        Some(i) => (&part[..i], Some(&part[i + 1..])),
+        None => (part, None),
+    }
+}
+
+fn hex_digits(s: &str) -> Result<Vec<u8>> {
+    s.chars().map(|c| c.to_digit(16).map(|d| d as u8).ok_or_else(malformed)).collect()
+}
+
+fn decode_bytes(s: &str) -> Result<Vec<u8>> {
+    let digits = hex_digits(s)?;
+    if digits.len() % 2 != 0 {
+        return Err(malformed());
+    }
+
+    Ok(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+}
+
+fn decode_name(s: &str) -> Result<String> {
+    let digits = hex_digits(s)?;
+    if digits.len() % 4 != 0 {
+        return Err(malformed());
+    }
+
+    let units = digits.chunks(4).map(|d| d.iter().fold(0u16, |n, &d| n << 4 | d as u16)).collect::<Vec<_>>();
+    String::from_utf16(&units).map_err(|_| malformed())
+}
+
+fn parse_number(s: &str) -> Result<usize> {
+    let digits = hex_digits(s)?;
+    if digits.is_empty() {
+        return Err(malformed());
+    }
+
+    digits.iter().fold(Some(0usize), |n, &d| n.and_then(|n| n.checked_mul(16)).map(|n| n + d as usize))
+        .ok_or_else(malformed)
+}
+
+// Values are numbers, so the first digits are the last byte. Drivers sometimes leave out leading zeros.
+fn decode_value(s: &str, width: usize) -> Result<Vec<u8>> {
+    let mut digits = hex_digits(s)?;
+    if digits.len() % 2 != 0 {
+        digits.insert(0, 0);
+    }
+
+    let mut bytes = digits.chunks(2).rev().map(|pair| pair[0] << 4 | pair[1]).collect::<Vec<_>>();
+    if bytes[width.min(bytes.len())..].iter().any(|&b| b != 0) {
+        return Err(malformed());
+    }
+
+    bytes.resize(width, 0);
+    Ok(bytes)
+}
+
+/// The firmware's router of configuration strings to the drivers owning the storage
+pub struct HiiConfigRouting {
+    protocol: *const EFI_HII_CONFIG_ROUTING_PROTOCOL,
+}
+
+impl HiiConfigRouting {
+    pub fn get() -> Result<Self> {
+        Ok(HiiConfigRouting { protocol: locate_protocol(&EFI_HII_CONFIG_ROUTING_PROTOCOL_GUID)? })
+    }
+
+    /// The current settings of every storage, along with their alternate configurations such as the defaults
+    pub fn export(&self) -> Result<Vec<Config>> {
+        parse(&self.export_string()?)
+    }
+
+    /// The current settings the request asks for. A request without elements asks for the whole storage.
+    /// Values in the request are ignored.
+    pub fn extract(&self, request: &Config) -> Result<Vec<Config>> {
+        parse(&self.extract_string(&format!("{}", request.to_request()))?)
+    }
+
+    /// Changes the settings to the values in the configuration. The driver owning the storage checks them and
+    /// fails with `InvalidParameter` if any setting isn't allowed.
+    pub fn route(&self, config: &Config) -> Result<()> {
+        self.route_string(&format!("{}", config))
+    }
+
+    /// Like `export()` but returns the configuration string as is
+    pub fn export_string(&self) -> Result<String> {
+        let mut results = ptr::null_mut();
+        unsafe {
+            ret_on_err!(((*self.protocol).ExportConfig)(self.protocol, &mut results));
+        }
+
+        Ok(take_string(results))
+    }
+
+    /// Like `extract()` but takes and returns configuration strings as is
+    pub fn extract_string(&self, request: &str) -> Result<String> {
+        let request = to_null_terminated_utf16(request);
+        let mut progress = ptr::null();
+        let mut results = ptr::null_mut();
+        let status = unsafe { ((*self.protocol).ExtractConfig)(self.protocol, request.as_ptr(), &mut progress, &mut results) };
+        let results = take_string(results);
+        if !::ffi::IsSuccess(status) {
+            return Err(EfiError::from(status).add_context("the firmware could not extract the configuration"));
+        }
+
+        Ok(results)
+    }
+
+    /// Like `route()` but takes the configuration string as is
+    pub fn route_string(&self, config: &str) -> Result<()> {
+        let config = to_null_terminated_utf16(config);
+        let mut progress = ptr::null();
+        unsafe {
+            let status = ((*self.protocol).RouteConfig)(self.protocol, config.as_ptr(), &mut progress);
+            if !::ffi::IsSuccess(status) {
+                return Err(EfiError::from(status).add_context("the firmware rejected the configuration"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Takes ownership of a string the firmware allocated
+fn take_string(raw: *mut CHAR16) -> String {
+    if raw.is_null() {
+        return String::new();
+    }
+
+    let raw = unsafe { EfiBox::from_raw(raw) };
+    String::from_utf16_lossy(unsafe { as_slice(raw.as_raw()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETUP: &str = "GUID=78563412bc9af0de0102030405060708&NAME=00530065007400750070&PATH=7f01040001020304\
+                         &OFFSET=0010&WIDTH=0002&VALUE=0102&OFFSET=0020&WIDTH=0001&VALUE=1";
+
+    #[test]
+    fn config_strings_are_parsed() {
+        let configs = parse(SETUP).unwrap();
+        assert_eq!(configs.len(), 1);
+
+        let setup = &configs[0];
+        assert_eq!(setup.guid, Guid(0x12345678, 0x9abc, 0xdef0, [1, 2, 3, 4, 5, 6, 7, 8]));
+        assert_eq!(setup.name, "Setup");
+        assert_eq!(setup.path, vec![0x7f, 0x01, 0x04, 0x00, 0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(setup.alt_config, None);
+        assert_eq!(setup.elements, vec![
+            Element::Block { offset: 0x10, width: 2, value: Some(vec![0x02, 0x01]) },
+            Element::Block { offset: 0x20, width: 1, value: Some(vec![0x01]) },
+        ]);
+        assert_eq!(setup.value(0x11, 1), Some(&[0x01][..]));
+        assert_eq!(setup.value(0x20, 2), None);
+        assert_eq!(setup.value(0x11, usize::max_value()), None);
+    }
+
+    #[test]
+    fn config_strings_round_trip() {
+        let mut configs = parse(SETUP).unwrap();
+        let mut defaults = configs[0].clone();
+        defaults.alt_config = Some(0);
+        defaults.elements = vec![Element::NameValue { name: String::from("Mode"), value: Some(String::from("0003")) }];
+        configs.push(defaults);
+
+        let s = format(&configs);
+        assert!(s.contains("&VALUE=01&GUID="));
+        assert!(s.contains("&ALTCFG=0000&Mode=0003"));
+        assert_eq!(parse(&s).unwrap(), configs);
+        assert_eq!(configs[1].named_value("Mode"), Some("0003"));
+    }
+
+    #[test]
+    fn values_are_changed_in_place() {
+        let mut setup = parse(SETUP).unwrap().remove(0);
+        setup.set_value(0x11, &[0xff]);
+        setup.set_value(0x30, &[0xaa, 0xbb]);
+        assert_eq!(setup.elements[0], Element::Block { offset: 0x10, width: 2, value: Some(vec![0x02, 0xff]) });
+        assert_eq!(setup.elements[2], Element::Block { offset: 0x30, width: 2, value: Some(vec![0xaa, 0xbb]) });
+        assert!(format!("{}", setup.to_request()).ends_with("&OFFSET=0030&WIDTH=0002"));
+    }
+
+    #[test]
+    fn malformed_config_strings_are_rejected() {
+        assert_eq!(parse("").unwrap(), vec![]);
+        assert!(parse("OFFSET=0010&WIDTH=0001").is_err()); // No header
+        assert!(parse("GUID=7856&NAME=&PATH=").is_err()); // Short GUID
+        assert!(parse("GUID=78563412bc9af0de0102030405060708&PATH=00").is_err());
+        assert!(parse("GUID=78563412bc9af0de0102030405060708&NAME=&PATH=&OFFSET=0010&VALUE=01").is_err());
+        assert!(parse("GUID=78563412bc9af0de0102030405060708&NAME=&PATH=&OFFSET=0010&WIDTH=0001&VALUE=0102").is_err());
+        assert!(parse("GUID=78563412bc9af0de0102030405060708&NAME=&PATH=&OFFSET=xyz&WIDTH=0001").is_err());
+        assert!(parse("GUID=78563412bc9af0de0102030405060708&NAME=&PATH=&OFFSET=0010&WIDTH=ffffffff&VALUE=01").is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use ffi::{
+    hii::*,
+    graphics::EFI_GRAPHICS_OUTPUT_BLT_PIXEL,
+    EFI_WARN_UNKNOWN_GLYPH,
+    CHAR16,
+    UINTN,
+    VOID,
+};
+use alloc::Vec;
+use core::{ptr, slice};
+use utils::locate_protocol;
+use {Result, EfiErrorKind, boot_services};
+
+/// A character rendered with the firmware's system font
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glyph {
+    pub width: usize,
+    pub height: usize,
+    /// Rows from the top of the glyph to the baseline the text sits on
+    pub baseline: usize,
+    // One entry per pixel, row by row
+    lit: Vec<bool>,
+}
+
+impl Glyph {
+    /// Whether the pixel is part of the character (foreground) rather than the background
+    pub fn is_lit(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.lit[y * self.width + x]
+    }
+
+    fn from_bitmap(width: usize, height: usize, baseline: usize, bitmap: &[EFI_GRAPHICS_OUTPUT_BLT_PIXEL]) -> Self {
+        // Glyphs are rendered white on black so any brightness at all is foreground (some fonts are anti-aliased)
+        let lit = bitmap.iter().map(|p| p.Red as u16 + p.Green as u16 + p.Blue as u16 >= 3 * 0x80).collect();
+        Glyph { width, height, baseline, lit }
+    }
+}
+
+/// The fonts in the HII database
+pub struct HiiFont {
+    protocol: *const EFI_HII_FONT_PROTOCOL,
+}
+
+impl HiiFont {
+    pub fn get() -> Result<Self> {
+        Ok(HiiFont { protocol: locate_protocol(&EFI_HII_FONT_PROTOCOL_GUID)? })
+    }
+
+    /// The character as drawn by the system font. Fails with `NotFound` if the font has no glyph for it.
+    pub fn glyph(&self, c: char) -> Result<Glyph> {
+        if c as u32 > 0xFFFF {
+            return Err(EfiErrorKind::NotFound.into()); // Fonts only cover the basic multilingual plane
+        }
+
+        let white = EFI_GRAPHICS_OUTPUT_BLT_PIXEL { Blue: 0xFF, Green: 0xFF, Red: 0xFF, Reserved: 0 };
+        let black = EFI_GRAPHICS_OUTPUT_BLT_PIXEL::default();
+        let info = EFI_FONT_DISPLAY_INFO {
+            ForegroundColor: white,
+            BackgroundColor: black,
+            FontInfoMask: EFI_FONT_INFO_SYS_FONT | EFI_FONT_INFO_SYS_SIZE | EFI_FONT_INFO_SYS_STYLE,
+            FontInfo: EFI_FONT_INFO { FontStyle: EFI_HII_FONT_STYLE_NORMAL, FontSize: 0, FontName: [0] },
+        };
+
+        let mut image: *mut EFI_IMAGE_OUTPUT = ptr::null_mut();
+        let mut baseline: UINTN = 0;
+        let status = unsafe { ((*self.protocol).GetGlyph)(self.protocol, c as u32 as CHAR16, &info, &mut image, &mut baseline) };
+        if image.is_null() {
+            ret_on_err!(status);
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        let glyph = unsafe {
+            let (width, height) = ((*image).Width as usize, (*image).Height as usize);
+            let bitmap = (*image).Image;
+            let glyph = if bitmap.is_null() { None } else { Some(Glyph::from_bitmap(width, height, baseline, slice::from_raw_parts(bitmap, width * height))) };
+            free_image(image);
+            glyph
+        };
+
+        match (status, glyph) {
+            (EFI_WARN_UNKNOWN_GLYPH, _) | (_, None) => Err(EfiErrorKind::NotFound.into()), // We got the replacement character
+            (status, Some(glyph)) => {
+                ret_on_err!(status);
+                Ok(glyph)
+            }
+        }
+    }
+}
+
+// GetGlyph() allocates both the image and its bitmap
+unsafe fn free_image(image: *mut EFI_IMAGE_OUTPUT) {
+    let bs = boot_services();
+    if !(*image).Image.is_null() {
+        ((*bs).FreePool)((*image).Image as *const VOID);
+    }
+    ((*bs).FreePool)(image as *const VOID);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bright_pixels_are_lit() {
+        let on = EFI_GRAPHICS_OUTPUT_BLT_PIXEL { Blue: 0xFF, Green: 0xFF, Red: 0xFF, Reserved: 0 };
+        let dim = EFI_GRAPHICS_OUTPUT_BLT_PIXEL { Blue: 0x20, Green: 0x20, Red: 0x20, Reserved: 0 };
+        let glyph = Glyph::from_bitmap(2, 2, 1, &[on, dim, dim, on]);
+        assert!(glyph.is_lit(0, 0) && glyph.is_lit(1, 1));
+        assert!(!glyph.is_lit(1, 0) && !glyph.is_lit(0, 1));
+        assert!(!glyph.is_lit(2, 0) && !glyph.is_lit(0, 2));
+    }
+}
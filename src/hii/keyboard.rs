@@ -0,0 +1,298 @@
+use ffi::{
+    hii::*,
+    EFI_BUFFER_TOO_SMALL,
+    EFI_NOT_FOUND,
+    UINT8,
+};
+use utils::guid_from_bytes;
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec};
+use core::{ptr, mem, char};
+use super::{HiiDatabase, pick_language};
+use {Result, EfiErrorKind, Guid};
+
+// Keyboard layouts in the HII database. Keyboard drivers use the current layout to turn the keys pressed into
+// characters, so with the wrong one a password typed on e.g. a French keyboard doesn't come out as the user
+// meant it. Keys are identified by their physical position (an EFI_KEY), which is the same for all layouts.
+
+/// A key of a keyboard layout
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyDescriptor {
+    /// The physical position of the key (an EFI_KEY)
+    pub key: u32,
+    pub unicode: Option<char>,
+    pub shifted: Option<char>,
+    pub alt_gr: Option<char>,
+    pub shifted_alt_gr: Option<char>,
+    /// What the key does if it's a modifier rather than a character key, e.g. `EFI_LEFT_SHIFT_MODIFIER`
+    pub modifier: u16,
+    /// Which lock and shift keys change the character, e.g. `EFI_AFFECTED_BY_CAPS_LOCK`
+    pub affected_by: u16,
+}
+
+impl KeyDescriptor {
+    fn parse(bytes: &[u8]) -> Self {
+        let to_char = |offset: usize| match LittleEndian::read_u16(&bytes[offset..]) {
+            0 => None,
+            c => char::from_u32(c as u32),
+        };
+
+        KeyDescriptor {
+            key: LittleEndian::read_u32(&bytes[0..4]),
+            unicode: to_char(4),
+            shifted: to_char(6),
+            alt_gr: to_char(8),
+            shifted_alt_gr: to_char(10),
+            modifier: LittleEndian::read_u16(&bytes[12..14]),
+            affected_by: LittleEndian::read_u16(&bytes[14..16]),
+        }
+    }
+
+    /// The character the key types in the given state
+    pub fn char(&self, state: KeyState) -> Option<char> {
+        let caps = state.caps_lock && self.affected_by & EFI_AFFECTED_BY_CAPS_LOCK != 0;
+        let shift = (state.shift && self.affected_by & EFI_AFFECTED_BY_STANDARD_SHIFT != 0) != caps;
+        match (shift, state.alt_gr) {
+            (false, false) => self.unicode,
+            (true, false) => self.shifted,
+            (false, true) => self.alt_gr,
+            (true, true) => self.shifted_alt_gr,
+        }
+    }
+}
+
+/// The modifiers held down (or locked) while a key is pressed
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct KeyState {
+    pub shift: bool,
+    pub alt_gr: bool,
+    pub caps_lock: bool,
+}
+
+impl KeyState {
+    // Every state that types a distinct character, the plain one first
+    fn all() -> [KeyState; 4] {
+        [
+            KeyState { shift: false, alt_gr: false, caps_lock: false },
+            KeyState { shift: true, alt_gr: false, caps_lock: false },
+            KeyState { shift: false, alt_gr: true, caps_lock: false },
+            KeyState { shift: true, alt_gr: true, caps_lock: false },
+        ]
+    }
+}
+
+/// A keyboard layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboardLayout {
+    pub guid: Guid,
+    /// The names of the layout as (RFC 4646 language, name) pairs, e.g. ("en-US", "English Keyboard")
+    pub descriptions: Vec<(String, String)>,
+    pub keys: Vec<KeyDescriptor>,
+}
+
+impl KeyboardLayout {
+    /// Parses an EFI_HII_KEYBOARD_LAYOUT
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < EFI_HII_KEYBOARD_LAYOUT_HEADER_SIZE {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        let length = LittleEndian::read_u16(&bytes[0..2]) as usize;
+        let guid = guid_from_bytes(&bytes[2..18]);
+        let descriptions_offset = LittleEndian::read_u32(&bytes[18..22]) as usize;
+        let count = bytes[22] as usize;
+        let keys_end = EFI_HII_KEYBOARD_LAYOUT_HEADER_SIZE + count * EFI_KEY_DESCRIPTOR_SIZE;
+        if length > bytes.len() || keys_end > length || descriptions_offset > length {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        let keys = bytes[EFI_HII_KEYBOARD_LAYOUT_HEADER_SIZE..keys_end]
+            .chunks(EFI_KEY_DESCRIPTOR_SIZE)
+            .map(KeyDescriptor::parse)
+            .collect();
+        let descriptions = parse_descriptions(&bytes[descriptions_offset..length])?;
+
+        Ok(KeyboardLayout { guid, descriptions, keys })
+    }
+
+    /// The layout's name in the given language, or in English or any other language it has if None
+    pub fn description(&self, language: Option<&str>) -> Option<&str> {
+        let languages = self.descriptions.iter().map(|&(ref l, _)| l.clone()).collect::<Vec<_>>();
+        let language = pick_language(&languages, language)?;
+        self.descriptions.iter().find(|&&(ref l, _)| *l == language).map(|&(_, ref d)| &d[..])
+    }
+
+    /// The character the key at the given position types
+    pub fn char(&self, key: u32, state: KeyState) -> Option<char> {
+        self.keys.iter().find(|k| k.key == key).and_then(|k| k.char(state))
+    }
+
+    /// The key and the state that type the character, preferring unshifted keys
+    pub fn key_for(&self, c: char) -> Option<(u32, KeyState)> {
+        KeyState::all().iter()
+            .filter_map(|&state| self.keys.iter().find(|k| k.char(state) == Some(c)).map(|k| (k.key, state)))
+            .next()
+    }
+
+    /// The character that the keys typing `c` in this layout type in the other layout. Use it to type what a
+    /// user sees on their keyboard when the firmware assumes a different layout, e.g. with this layout being
+    /// the one the firmware uses and the other one the keyboard's real layout.
+    pub fn translate(&self, c: char, to: &KeyboardLayout) -> Option<char> {
+        let (key, state) = self.key_for(c)?;
+        to.char(key, state)
+    }
+
+    /// `translate()` for every character of the string. None if any of them can't be translated.
+    pub fn translate_str(&self, s: &str, to: &KeyboardLayout) -> Option<String> {
+        s.chars().map(|c| self.translate(c, to)).collect()
+    }
+}
+
+// An EFI_DESCRIPTION_STRING_BUNDLE: a UINT16 count followed by that many strings. Each is a language,
+// a space and the description, null terminated.
+fn parse_descriptions(bytes: &[u8]) -> Result<Vec<(String, String)>> {
+    if bytes.len() < 2 {
+        return Err(EfiErrorKind::VolumeCorrupted.into());
+    }
+
+    let count = LittleEndian::read_u16(bytes) as usize;
+    let mut units = bytes[2..].chunks(2).filter(|c| c.len() == 2).map(LittleEndian::read_u16);
+    let mut descriptions = Vec::new();
+    for _ in 0..count {
+        let language = units.by_ref().take_while(|&c| c != ' ' as u16).collect::<Vec<_>>();
+        let description = units.by_ref().take_while(|&c| c != 0).collect::<Vec<_>>();
+        if description.is_empty() {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        descriptions.push((String::from_utf16_lossy(&language), String::from_utf16_lossy(&description)));
+    }
+
+    Ok(descriptions)
+}
+
+impl HiiDatabase {
+    /// The GUIDs of the keyboard layouts in the database
+    pub fn keyboard_layouts(&self) -> Result<Vec<Guid>> {
+        let protocol = self.as_raw();
+        let mut size: u16 = 0;
+        let status = unsafe { ((*protocol).FindKeyboardLayouts)(protocol, &mut size, ptr::null_mut()) };
+        match status {
+            EFI_NOT_FOUND => return Ok(Vec::new()),
+            EFI_BUFFER_TOO_SMALL => {},
+            status => {
+                ret_on_err!(status);
+                return Ok(Vec::new());
+            }
+        }
+
+        let mut guids = vec![Guid(0, 0, 0, [0; 8]); size as usize / mem::size_of::<Guid>()];
+        unsafe {
+            ret_on_err!(((*protocol).FindKeyboardLayouts)(protocol, &mut size, guids.as_mut_ptr()));
+        }
+
+        guids.truncate(size as usize / mem::size_of::<Guid>());
+        Ok(guids)
+    }
+
+    /// The keyboard layout with the given GUID
+    pub fn keyboard_layout(&self, guid: &Guid) -> Result<KeyboardLayout> {
+        self.get_keyboard_layout(guid)
+    }
+
+    /// The keyboard layout keyboard drivers currently use. Fails with `NotFound` if none was set.
+    pub fn current_keyboard_layout(&self) -> Result<KeyboardLayout> {
+        self.get_keyboard_layout(ptr::null())
+    }
+
+    /// Makes keyboard drivers use the layout with the given GUID
+    pub fn set_keyboard_layout(&self, guid: &Guid) -> Result<()> {
+        let protocol = self.as_raw();
+        unsafe {
+            ret_on_err!(((*protocol).SetKeyboardLayout)(protocol, guid));
+        }
+
+        Ok(())
+    }
+
+    fn get_keyboard_layout(&self, guid: *const Guid) -> Result<KeyboardLayout> {
+        let protocol = self.as_raw();
+        let mut size: u16 = 0;
+        let status = unsafe { ((*protocol).GetKeyboardLayout)(protocol, guid, &mut size, ptr::null_mut()) };
+        if status != EFI_BUFFER_TOO_SMALL {
+            ret_on_err!(status);
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        let mut buf = vec![0u8; size as usize];
+        unsafe {
+            ret_on_err!(((*protocol).GetKeyboardLayout)(protocol, guid, &mut size, buf.as_mut_ptr() as *mut UINT8));
+        }
+
+        KeyboardLayout::parse(&buf[..size as usize])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // EFI_KEYs of the first two letter keys of the top row (EfiKeyD1 and EfiKeyD2)
+    const KEY_D1: u32 = 0x31;
+    const KEY_D2: u32 = 0x32;
+
+    fn layout(guid: Guid, description: &str, keys: &[(u32, char, char)]) -> Vec<u8> {
+        let mut bytes = vec![0u8; EFI_HII_KEYBOARD_LAYOUT_HEADER_SIZE];
+        bytes[2..18].copy_from_slice(&::utils::guid_to_bytes(&guid));
+        bytes[22] = keys.len() as u8;
+        for &(key, unicode, shifted) in keys {
+            let mut descriptor = [0u8; EFI_KEY_DESCRIPTOR_SIZE];
+            LittleEndian::write_u32(&mut descriptor[0..4], key);
+            LittleEndian::write_u16(&mut descriptor[4..6], unicode as u16);
+            LittleEndian::write_u16(&mut descriptor[6..8], shifted as u16);
+            LittleEndian::write_u16(&mut descriptor[14..16], EFI_AFFECTED_BY_STANDARD_SHIFT | EFI_AFFECTED_BY_CAPS_LOCK);
+            bytes.extend_from_slice(&descriptor);
+        }
+
+        let offset = bytes.len() as u32;
+        LittleEndian::write_u32(&mut bytes[18..22], offset);
+        bytes.extend_from_slice(&[1, 0]);
+        for c in "en-US ".encode_utf16().chain(description.encode_utf16()).chain(Some(0)) {
+            let mut unit = [0u8; 2];
+            LittleEndian::write_u16(&mut unit, c);
+            bytes.extend_from_slice(&unit);
+        }
+
+        let len = bytes.len() as u16;
+        LittleEndian::write_u16(&mut bytes[0..2], len);
+        bytes
+    }
+
+    #[test]
+    fn keyboard_layouts_are_parsed() {
+        let guid = Guid(0x12345678, 0x9abc, 0xdef0, [1, 2, 3, 4, 5, 6, 7, 8]);
+        let bytes = layout(guid, "English Keyboard", &[(KEY_D1, 'q', 'Q'), (KEY_D2, 'w', 'W')]);
+        let us = KeyboardLayout::parse(&bytes).unwrap();
+        assert_eq!(us.guid, guid);
+        assert_eq!(us.descriptions, vec![(String::from("en-US"), String::from("English Keyboard"))]);
+        assert_eq!(us.description(None), Some("English Keyboard"));
+        assert_eq!(us.keys.len(), 2);
+        assert_eq!(us.char(KEY_D2, KeyState::default()), Some('w'));
+        assert_eq!(us.char(KEY_D2, KeyState { shift: true, ..KeyState::default() }), Some('W'));
+        assert_eq!(us.char(KEY_D2, KeyState { shift: true, caps_lock: true, ..KeyState::default() }), Some('w'));
+        assert_eq!(us.char(KEY_D2, KeyState { alt_gr: true, ..KeyState::default() }), None);
+
+        assert!(KeyboardLayout::parse(&bytes[..30]).is_err());
+    }
+
+    #[test]
+    fn characters_are_translated_between_layouts() {
+        let guid = Guid(0, 0, 0, [0; 8]);
+        let us = KeyboardLayout::parse(&layout(guid, "US", &[(KEY_D1, 'q', 'Q'), (KEY_D2, 'w', 'W')])).unwrap();
+        let fr = KeyboardLayout::parse(&layout(guid, "French", &[(KEY_D1, 'a', 'A'), (KEY_D2, 'z', 'Z')])).unwrap();
+        assert_eq!(us.key_for('W'), Some((KEY_D2, KeyState { shift: true, ..KeyState::default() })));
+        assert_eq!(us.translate('Q', &fr), Some('A'));
+        assert_eq!(fr.translate_str("zAz", &us), Some(String::from("wQw")));
+        assert_eq!(fr.translate_str("zx", &us), None);
+    }
+}
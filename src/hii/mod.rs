@@ -0,0 +1,354 @@
+use ffi::{
+    hii::*,
+    runtime_services::EFI_GLOBAL_VARIABLE,
+    EFI_HANDLE,
+    EFI_BUFFER_TOO_SMALL,
+    EFI_NOT_FOUND,
+    CHAR8,
+    CHAR16,
+};
+use utils::{guid_from_bytes, locate_protocol, split_languages, to_null_terminated_ascii};
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec};
+use core::{ptr, mem};
+use {Result, EfiError, EfiErrorKind, Guid, variables};
+
+pub mod font;
+pub mod config;
+pub mod keyboard;
+
+pub use self::font::{HiiFont, Glyph};
+pub use self::config::HiiConfigRouting;
+pub use self::keyboard::{KeyboardLayout, KeyState};
+
+// The firmware's Human Interface Infrastructure (HII) database. Drivers publish package lists in it holding the
+// strings, fonts, forms and keyboard layouts they present to the user, which lets tools show the firmware's own
+// (localized) names for devices and settings.
+
+/// The kind of a package in a package list
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PackageType {
+    Guid,
+    Forms,
+    Strings,
+    Fonts,
+    Images,
+    SimpleFonts,
+    DevicePath,
+    KeyboardLayout,
+    Animations,
+    End,
+    /// A system or vendor specific type (0xE0 and above)
+    Other(u8),
+}
+
+impl From<u8> for PackageType {
+    fn from(value: u8) -> Self {
+        match value {
+            EFI_HII_PACKAGE_TYPE_GUID => PackageType::Guid,
+            EFI_HII_PACKAGE_FORMS => PackageType::Forms,
+            EFI_HII_PACKAGE_STRINGS => PackageType::Strings,
+            EFI_HII_PACKAGE_FONTS => PackageType::Fonts,
+            EFI_HII_PACKAGE_IMAGES => PackageType::Images,
+            EFI_HII_PACKAGE_SIMPLE_FONTS => PackageType::SimpleFonts,
+            EFI_HII_PACKAGE_DEVICE_PATH => PackageType::DevicePath,
+            EFI_HII_PACKAGE_KEYBOARD_LAYOUT => PackageType::KeyboardLayout,
+            EFI_HII_PACKAGE_ANIMATIONS => PackageType::Animations,
+            EFI_HII_PACKAGE_END => PackageType::End,
+            value => PackageType::Other(value),
+        }
+    }
+}
+
+impl From<PackageType> for u8 {
+    fn from(kind: PackageType) -> Self {
+        match kind {
+            PackageType::Guid => EFI_HII_PACKAGE_TYPE_GUID,
+            PackageType::Forms => EFI_HII_PACKAGE_FORMS,
+            PackageType::Strings => EFI_HII_PACKAGE_STRINGS,
+            PackageType::Fonts => EFI_HII_PACKAGE_FONTS,
+            PackageType::Images => EFI_HII_PACKAGE_IMAGES,
+            PackageType::SimpleFonts => EFI_HII_PACKAGE_SIMPLE_FONTS,
+            PackageType::DevicePath => EFI_HII_PACKAGE_DEVICE_PATH,
+            PackageType::KeyboardLayout => EFI_HII_PACKAGE_KEYBOARD_LAYOUT,
+            PackageType::Animations => EFI_HII_PACKAGE_ANIMATIONS,
+            PackageType::End => EFI_HII_PACKAGE_END,
+            PackageType::Other(value) => value,
+        }
+    }
+}
+
+/// A package list in the HII database, as published by a driver
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PackageList {
+    handle: EFI_HII_HANDLE,
+}
+
+impl PackageList {
+    pub fn handle(&self) -> EFI_HII_HANDLE {
+        self.handle
+    }
+}
+
+/// A package as found in an exported package list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    pub kind: PackageType,
+    /// The package's bytes after its header
+    pub data: Vec<u8>,
+}
+
+/// The contents of a package list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageListContents {
+    pub guid: Guid,
+    /// The packages in the list, without the terminating end package
+    pub packages: Vec<Package>,
+}
+
+impl PackageListContents {
+    /// Parses an exported package list
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let header_size = mem::size_of::<EFI_HII_PACKAGE_LIST_HEADER>();
+        if bytes.len() < header_size {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        let guid = guid_from_bytes(&bytes[..16]);
+        let length = LittleEndian::read_u32(&bytes[16..20]) as usize;
+        if length < header_size || length > bytes.len() {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        let mut packages = Vec::new();
+        let mut rest = &bytes[header_size..length];
+        while rest.len() >= EFI_HII_PACKAGE_HEADER_SIZE {
+            let header = LittleEndian::read_u32(rest);
+            let (len, kind) = ((header & 0x00FF_FFFF) as usize, PackageType::from((header >> 24) as u8));
+            if len < EFI_HII_PACKAGE_HEADER_SIZE || len > rest.len() {
+                return Err(EfiErrorKind::VolumeCorrupted.into());
+            }
+
+            if kind == PackageType::End {
+                break;
+            }
+
+            packages.push(Package { kind, data: rest[EFI_HII_PACKAGE_HEADER_SIZE..len].to_vec() });
+            rest = &rest[len..];
+        }
+
+        Ok(PackageListContents { guid, packages })
+    }
+}
+
+/// The HII database
+pub struct HiiDatabase {
+    protocol: *const EFI_HII_DATABASE_PROTOCOL,
+}
+
+impl HiiDatabase {
+    pub fn get() -> Result<Self> {
+        Ok(HiiDatabase { protocol: locate_protocol(&EFI_HII_DATABASE_PROTOCOL_GUID)? })
+    }
+
+    pub(crate) fn as_raw(&self) -> *const EFI_HII_DATABASE_PROTOCOL {
+        self.protocol
+    }
+
+    /// Every package list in the database
+    pub fn package_lists(&self) -> Result<Vec<PackageList>> {
+        self.list(EFI_HII_PACKAGE_TYPE_ALL, ptr::null())
+    }
+
+    /// The package lists that have at least one package of the given type.
+    /// Use `package_lists_with_guid()` for `PackageType::Guid`.
+    pub fn package_lists_with(&self, kind: PackageType) -> Result<Vec<PackageList>> {
+        self.list(kind.into(), ptr::null())
+    }
+
+    /// The package lists that have a GUID package with the given GUID
+    pub fn package_lists_with_guid(&self, guid: &Guid) -> Result<Vec<PackageList>> {
+        self.list(EFI_HII_PACKAGE_TYPE_GUID, guid)
+    }
+
+    fn list(&self, kind: u8, guid: *const Guid) -> Result<Vec<PackageList>> {
+        let mut size = 0;
+        let status = unsafe { ((*self.protocol).ListPackageLists)(self.protocol, kind, guid, &mut size, ptr::null_mut()) };
+        match status {
+            EFI_NOT_FOUND => return Ok(Vec::new()),
+            EFI_BUFFER_TOO_SMALL => {},
+            status => {
+                ret_on_err!(status);
+                return Ok(Vec::new());
+            }
+        }
+
+        let mut handles: Vec<EFI_HII_HANDLE> = vec![ptr::null(); size / mem::size_of::<EFI_HII_HANDLE>()];
+        unsafe {
+            ret_on_err!(((*self.protocol).ListPackageLists)(self.protocol, kind, guid, &mut size, handles.as_mut_ptr()));
+        }
+
+        handles.truncate(size / mem::size_of::<EFI_HII_HANDLE>());
+        Ok(handles.into_iter().map(|handle| PackageList { handle }).collect())
+    }
+
+    /// The GUID and packages of a package list
+    pub fn contents(&self, list: &PackageList) -> Result<PackageListContents> {
+        let mut size = 0;
+        let status = unsafe { ((*self.protocol).ExportPackageLists)(self.protocol, list.handle, &mut size, ptr::null_mut()) };
+        if status != EFI_BUFFER_TOO_SMALL {
+            ret_on_err!(status);
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        let mut buf = vec![0u8; size];
+        unsafe {
+            ret_on_err!(((*self.protocol).ExportPackageLists)(self.protocol, list.handle, &mut size, buf.as_mut_ptr() as *mut EFI_HII_PACKAGE_LIST_HEADER));
+        }
+
+        PackageListContents::parse(&buf[..size])
+    }
+
+    /// The handle of the driver that published the package list
+    pub fn driver_handle(&self, list: &PackageList) -> Result<EFI_HANDLE> {
+        let mut handle: EFI_HANDLE = ptr::null();
+        unsafe {
+            ret_on_err!(((*self.protocol).GetPackageListHandle)(self.protocol, list.handle, &mut handle));
+        }
+
+        Ok(handle)
+    }
+}
+
+/// The strings in the HII database
+pub struct HiiStrings {
+    protocol: *const EFI_HII_STRING_PROTOCOL,
+}
+
+impl HiiStrings {
+    pub fn get() -> Result<Self> {
+        Ok(HiiStrings { protocol: locate_protocol(&EFI_HII_STRING_PROTOCOL_GUID)? })
+    }
+
+    /// The RFC 4646 languages the package list has strings in, e.g. "en-US"
+    pub fn languages(&self, list: &PackageList) -> Result<Vec<String>> {
+        let languages = get_ascii(|buf, size| unsafe { ((*self.protocol).GetLanguages)(self.protocol, list.handle, buf, size) })?;
+        Ok(split_languages(&languages))
+    }
+
+    /// The languages that strings missing from `primary` fall back to
+    pub fn secondary_languages(&self, list: &PackageList, primary: &str) -> Result<Vec<String>> {
+        let primary = to_null_terminated_ascii(primary);
+        let languages = get_ascii(|buf, size| unsafe {
+            ((*self.protocol).GetSecondaryLanguages)(self.protocol, list.handle, primary.as_ptr() as *const CHAR8, buf, size)
+        })?;
+        Ok(split_languages(&languages))
+    }
+
+    /// The string with the given ID. If `language` is None the platform's language (the PlatformLang variable)
+    /// is preferred, then English, then whatever the package list has.
+    pub fn string(&self, list: &PackageList, id: u16, language: Option<&str>) -> Result<String> {
+        let language = match language {
+            Some(language) => String::from(language),
+            None => pick_language(&self.languages(list)?, platform_language().as_ref().map(|l| &l[..]))
+                .ok_or_else(|| EfiError::from(EfiErrorKind::NotFound))?,
+        };
+        let language = to_null_terminated_ascii(&language);
+
+        let mut size = 0;
+        let status = unsafe {
+            ((*self.protocol).GetString)(self.protocol, language.as_ptr() as *const CHAR8, list.handle, id, ptr::null_mut(), &mut size, ptr::null_mut())
+        };
+        if status != EFI_BUFFER_TOO_SMALL {
+            ret_on_err!(status);
+            return Ok(String::new());
+        }
+
+        let mut buf: Vec<CHAR16> = vec![0; (size + 1) / 2];
+        unsafe {
+            ret_on_err!(((*self.protocol).GetString)(self.protocol, language.as_ptr() as *const CHAR8, list.handle, id, buf.as_mut_ptr(), &mut size, ptr::null_mut()));
+        }
+
+        let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        Ok(String::from_utf16_lossy(&buf[..len]))
+    }
+}
+
+// Calls a function that fills a buffer with a null terminated ASCII string, first to learn the size
+fn get_ascii<F: Fn(*mut CHAR8, *mut usize) -> ::ffi::EFI_STATUS>(f: F) -> Result<String> {
+    let mut size = 0;
+    let status = f(ptr::null_mut(), &mut size);
+    if status != EFI_BUFFER_TOO_SMALL {
+        ret_on_err!(status);
+        return Ok(String::new());
+    }
+
+    let mut buf = vec![0u8; size];
+    ret_on_err!(f(buf.as_mut_ptr() as *mut CHAR8, &mut size));
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Ok(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+// The language the user picked in setup, if the firmware keeps it
+fn platform_language() -> Option<String> {
+    let bytes = variables::try_get("PlatformLang", &EFI_GLOBAL_VARIABLE).ok()??;
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8(bytes[..len].to_vec()).ok()
+}
+
+// Prefers the preferred language, then a language with the same primary subtag (e.g. "en" for "en-US"),
+// then English, then the first language
+fn pick_language(supported: &[String], preferred: Option<&str>) -> Option<String> {
+    fn primary(language: &str) -> &str {
+        language.split('-').next().unwrap_or(language)
+    }
+
+    let matching = |wanted: &str| {
+        supported.iter().find(|l| l.eq_ignore_ascii_case(wanted))
+            .or_else(|| supported.iter().find(|l| primary(l).eq_ignore_ascii_case(primary(wanted))))
+    };
+
+    preferred.and_then(&matching)
+        .or_else(|| matching("en"))
+        .or_else(|| supported.first())
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn languages(languages: &[&str]) -> Vec<String> {
+        languages.iter().map(|&l| String::from(l)).collect()
+    }
+
+    #[test]
+    fn languages_are_picked_by_preference() {
+        let supported = languages(&["fr-FR", "en-US", "de-DE"]);
+        assert_eq!(pick_language(&supported, Some("de-DE")), Some(String::from("de-DE")));
+        assert_eq!(pick_language(&supported, Some("de-AT")), Some(String::from("de-DE")));
+        assert_eq!(pick_language(&supported, Some("ja-JP")), Some(String::from("en-US")));
+        assert_eq!(pick_language(&languages(&["ja-JP", "zh-CN"]), None), Some(String::from("ja-JP")));
+        assert_eq!(pick_language(&[], None), None);
+        assert_eq!(split_languages("en-US;fr-FR;"), languages(&["en-US", "fr-FR"]));
+    }
+
+    #[test]
+    fn package_lists_are_parsed() {
+        let guid = Guid(0x12345678, 0x9abc, 0xdef0, [1, 2, 3, 4, 5, 6, 7, 8]);
+        let mut bytes = ::utils::guid_to_bytes(&guid).to_vec();
+        bytes.extend_from_slice(&[0; 4]); // Length, patched below
+        bytes.extend_from_slice(&[0x06, 0x00, 0x00, EFI_HII_PACKAGE_STRINGS, 0xAA, 0xBB]);
+        bytes.extend_from_slice(&[0x04, 0x00, 0x00, EFI_HII_PACKAGE_END]);
+        let len = bytes.len() as u32;
+        LittleEndian::write_u32(&mut bytes[16..20], len);
+
+        let contents = PackageListContents::parse(&bytes).unwrap();
+        assert_eq!(contents.guid, guid);
+        assert_eq!(contents.packages, vec![Package { kind: PackageType::Strings, data: vec![0xAA, 0xBB] }]);
+
+        bytes[20] = 0x40; // Longer than the list
+        assert!(PackageListContents::parse(&bytes).is_err());
+        assert!(PackageListContents::parse(&bytes[..10]).is_err());
+    }
+}
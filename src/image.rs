@@ -1,7 +1,7 @@
-use {Result, io::{self, Read}, system_table, image_handle, EfiErrorKind};
+use {Result, io::{self, Read}, boot_services, image_handle, EfiErrorKind};
 use ffi::{
     media::{EFI_LOAD_FILE_PROTOCOL, EFI_LOAD_FILE_PROTOCOL_GUID}, 
-    loaded_image::{EFI_LOADED_IMAGE_PROTOCOL, EFI_LOADED_IMAGE_PROTOCOL_GUID},
+    loaded_image::{EFI_LOADED_IMAGE_PROTOCOL, EFI_LOADED_IMAGE_PROTOCOL_GUID, EFI_IMAGE_UNLOAD},
     device_path::{EFI_DEVICE_PATH_PROTOCOL, EFI_DEVICE_PATH_PROTOCOL_GUID},
     EFI_HANDLE,
     EFI_STATUS,
@@ -9,7 +9,7 @@ use ffi::{
     EFI_BUFFER_TOO_SMALL,
     EFI_INVALID_PARAMETER,
     EFI_DEVICE_ERROR,
-    boot_services::{EFI_INTERFACE_TYPE, EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL},
+    boot_services::{EFI_INTERFACE_TYPE, EFI_MEMORY_TYPE},
     UINTN,
     CHAR16,
     BOOLEAN,
@@ -17,6 +17,9 @@ use ffi::{
     FALSE,
 };
 use device_path::{DevicePath, create_file_path_node, append_path};
+use device_path::device_path_of;
+use utils::open_protocol;
+#[cfg(feature = "tpm")] use security::measure::auto_measure;
 use core::{self, ptr, mem, slice, cmp};
 use alloc::Vec;
 
@@ -33,7 +36,7 @@ pub trait Len { // TODO: Move this a more general module like 'io' or something.
 
 // TODO: this whole shit about wrapping raw paths into DevicePath type is unsafe. Address this unsafety
 pub fn load_image_from_path(path: &mut DevicePath) -> Result<LoadedImage> {
-    let bs = (*system_table()).BootServices;
+    let bs = boot_services();
     let current_image_handle = image_handle();
     let path = path.as_ptr();
 
@@ -43,14 +46,15 @@ pub fn load_image_from_path(path: &mut DevicePath) -> Result<LoadedImage> {
         loaded_img_handle
     };
 
-    Ok(LoadedImage(loaded_img_handle))
+    LoadedImage::from_handle(loaded_img_handle)
 }
 
 //TODO: Provide a way for the user to specify load options as well
-/// Loads image read from the given reader
+/// Loads image read from the given reader.
+/// The image is measured into the TPM first if `security::measure::set_auto_measure()` is on (needs the `tpm` feature).
 pub fn load_image<R: Read + Len>(reader: &mut R) -> Result<LoadedImage> {
     let loader = Loader::new(reader);
-    let bs = (*system_table()).BootServices;
+    let bs = boot_services();
 
     let (mut image_path, device_handle) = unsafe {
         // Install our load file protocol and get a newly generated handle to it
@@ -59,24 +63,11 @@ pub fn load_image<R: Read + Len>(reader: &mut R) -> Result<LoadedImage> {
 
         // Open loaded image protocol on the currently running image in order to obtain its device handle
         let current_image_handle = image_handle();
-        let loaded_image: *mut EFI_LOADED_IMAGE_PROTOCOL = ptr::null_mut();
-        ret_on_err!(((*bs).OpenProtocol)(current_image_handle, &EFI_LOADED_IMAGE_PROTOCOL_GUID, mem::transmute(&loaded_image), current_image_handle, ptr::null(), EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL)); // TODO: should we use GET_PROTOCOL instead of BY_HANDLE_PROTOCOL? Not clear from UEFI documentation.
-
-
-        if loaded_image.is_null() { // If above call returned null protocol that means no such protocol is associated with the handle (which is odd)
-            return Err(EfiErrorKind::LoadError.into()); // TODO: Need proper error here
-        }
-
+        let loaded_image = open_protocol::<EFI_LOADED_IMAGE_PROTOCOL>(current_image_handle, &EFI_LOADED_IMAGE_PROTOCOL_GUID)?; // TODO: should we use GET_PROTOCOL instead of BY_HANDLE_PROTOCOL? Not clear from UEFI documentation.
         ret_on_err!(((*bs).CloseProtocol)(current_image_handle, &EFI_LOADED_IMAGE_PROTOCOL_GUID, current_image_handle, ptr::null()));
 
         // Open device path protocol on the device handle of the currently running image
-        let current_image_device_path: *mut EFI_DEVICE_PATH_PROTOCOL  = ptr::null_mut();
-        ret_on_err!(((*bs).OpenProtocol)((*loaded_image).DeviceHandle, &EFI_DEVICE_PATH_PROTOCOL_GUID, mem::transmute(&current_image_device_path), current_image_handle, ptr::null(), EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL)); // TODO: should we use GET_PROTOCOL instead of BY_HANDLE_PROTOCOL? Not clear from UEFI documentation.
-
-        if current_image_device_path.is_null() { // If above call returned null protocol that means no such protocol is associated with the handle (which is odd)
-            return Err(EfiErrorKind::LoadError.into()); // TODO: Need proper error here
-        }
-
+        let current_image_device_path = open_protocol::<EFI_DEVICE_PATH_PROTOCOL>((*loaded_image).DeviceHandle, &EFI_DEVICE_PATH_PROTOCOL_GUID)?;
         ret_on_err!(((*bs).CloseProtocol)((*loaded_image).DeviceHandle, &EFI_DEVICE_PATH_PROTOCOL_GUID, current_image_handle, ptr::null()));
 
         // Create a new device path and associate it with the our load file protocol. This path will be used for loading the image in LoadImage EFI call later
@@ -102,12 +93,12 @@ pub fn load_image<R: Read + Len>(reader: &mut R) -> Result<LoadedImage> {
 
 /// Starts an image previously loaded using load_image
 pub fn start_image(image: &LoadedImage ) -> Result<ExitData> {
-    let bs = (*system_table()).BootServices;
+    let bs = boot_services();
 
     unsafe {
         let mut exit_data_size: UINTN = 0;
         let mut exit_data_ptr = ptr::null_mut() as *const CHAR16;
-        ret_on_err!(((*bs).StartImage)(image.0, &mut exit_data_size, &mut exit_data_ptr));
+        ret_on_err!(((*bs).StartImage)(image.handle, &mut exit_data_size, &mut exit_data_ptr));
         Ok(ExitData::from_raw_parts(exit_data_ptr, exit_data_size)) // TODO: Will exit_data_ptr ever be null? Test this by starting an image that doesn't call Exit()
     }
 }
@@ -184,6 +175,13 @@ extern "win64" fn load_file_callback<'a, R: 'a + Read + Len>(
     let mut buf = unsafe { slice::from_raw_parts_mut(buffer_ptr as *mut u8, *buffer_size) };
     match io::fill_buf(&mut loader.reader, &mut buf) {
         Ok(bytes_read) => {
+            #[cfg(feature = "tpm")]
+            {
+                if let Err(e) = auto_measure("load_image", &buf[..bytes_read]) {
+                    return e.into();
+                }
+            }
+
             unsafe { *buffer_size = bytes_read };
             EFI_SUCCESS
         },
@@ -192,8 +190,98 @@ extern "win64" fn load_file_callback<'a, R: 'a + Read + Len>(
 }
 
 
+/// An image in memory, either the running one or one loaded with `load_image()` but not yet started
+pub struct LoadedImage {
+    handle: EFI_HANDLE,
+    protocol: *const EFI_LOADED_IMAGE_PROTOCOL,
+}
+
+impl LoadedImage {
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol::<EFI_LOADED_IMAGE_PROTOCOL>(handle, &EFI_LOADED_IMAGE_PROTOCOL_GUID)?;
+
+        Ok(Self { handle, protocol })
+    }
+
+    /// The running image
+    pub fn current() -> Result<Self> {
+        Self::from_handle(image_handle())
+    }
+
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// The handle of the image that loaded this one. Null for images loaded by the firmware itself.
+    pub fn parent_handle(&self) -> EFI_HANDLE {
+        unsafe { (*self.protocol).ParentHandle }
+    }
+
+    /// Where the image was loaded into memory
+    pub fn base(&self) -> *const u8 {
+        unsafe { (*self.protocol).ImageBase as *const u8 }
+    }
+
+    /// The size of the image in memory in bytes
+    pub fn size(&self) -> u64 {
+        unsafe { (*self.protocol).ImageSize }
+    }
+
+    /// The memory type of the image's code sections
+    pub fn code_type(&self) -> EFI_MEMORY_TYPE {
+        unsafe { (*self.protocol).ImageCodeType }
+    }
+
+    /// The memory type of the image's data sections
+    pub fn data_type(&self) -> EFI_MEMORY_TYPE {
+        unsafe { (*self.protocol).ImageDataType }
+    }
+
+    /// The handle of the device the image was loaded from, e.g. a file system or a network card.
+    /// Null if the image was loaded from memory.
+    pub fn device_handle(&self) -> EFI_HANDLE {
+        unsafe { (*self.protocol).DeviceHandle }
+    }
+
+    /// The device path of the device the image was loaded from
+    pub fn device_path(&self) -> Result<DevicePath> {
+        let device_handle = self.device_handle();
+        if device_handle.is_null() {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        device_path_of(device_handle)
+    }
 
-pub struct LoadedImage(EFI_HANDLE);
+    /// The path of the image file relative to `device_path()`. For images loaded from a file system
+    /// this is normally a file path node such as `\EFI\BOOT\BOOTX64.EFI`.
+    pub fn file_path(&self) -> Result<DevicePath> {
+        let path = unsafe { (*self.protocol).FilePath };
+        if path.is_null() {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        DevicePath::from_ptr(path)
+    }
+
+    // Sets the function the firmware calls when the image is unloaded, e.g. with the shell's unload command
+    pub(crate) fn set_unload(&self, unload: EFI_IMAGE_UNLOAD) {
+        unsafe { (*(self.protocol as *mut EFI_LOADED_IMAGE_PROTOCOL)).Unload = unload };
+    }
+
+    /// The raw load options the image was started with. For boot options these are the optional data of the
+    /// Boot#### variable and from the shell they are the command line as a UCS-2 string.
+    pub fn load_options(&self) -> &[u8] {
+        unsafe {
+            let options = (*self.protocol).LoadOptions as *const u8;
+            if options.is_null() {
+                return &[];
+            }
+
+            slice::from_raw_parts(options, (*self.protocol).LoadOptionsSize as usize)
+        }
+    }
+}
 
 /// The data returned by a running image when it exits.
 /// Contains a UCS-2 string part followed by an optional binary data.
@@ -236,7 +324,7 @@ impl ExitData {
 
 impl Drop for ExitData {
     fn drop(&mut self) { // The exit data ptr is allocated by the image we loaded but must be deallocated by us as per UEFI spec
-        let bs = (*system_table()).BootServices;
+        let bs = boot_services();
         unsafe { ((*bs).FreePool)(self.ptr as *const VOID) }; // TODO: Can't do anything if this fails except. So we should log here
     }
 }
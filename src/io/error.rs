@@ -16,6 +16,9 @@ use core::option::Option::{self, Some, None};
 use core::result;
 use alloc::string::String;
 use core::convert::From;
+use ffi::EFI_STATUS;
+use {EfiError, EfiErrorKind};
+use failure::Fail;
 
 /// A specialized [`Result`](../result/enum.Result.html) type for I/O
 /// operations.
@@ -56,14 +59,16 @@ pub type Result<T> = result::Result<T, Error>;
 /// The error type for I/O operations of the [`Read`], [`Write`], [`Seek`], and
 /// associated traits.
 ///
-/// Errors mostly originate from the underlying OS, but custom instances of
-/// `Error` can be created with crafted error messages and a particular value of
-/// [`ErrorKind`].
+/// Errors mostly originate from the firmware, in which case they carry the
+/// EFI status they were mapped from (see [`efi_status`]), but custom instances
+/// of `Error` can be created with crafted error messages and a particular value
+/// of [`ErrorKind`].
 ///
 /// [`Read`]: ../io/trait.Read.html
 /// [`Write`]: ../io/trait.Write.html
 /// [`Seek`]: ../io/trait.Seek.html
 /// [`ErrorKind`]: enum.ErrorKind.html
+/// [`efi_status`]: #method.efi_status
 pub struct Error {
     repr: Repr,
 }
@@ -78,6 +83,7 @@ enum Repr {
     Os(i32),
     Simple(ErrorKind),
     Custom(Box<Custom>),
+    Efi(ErrorKind, EfiErrorKind),
 }
 
 #[derive(Debug)]
@@ -194,6 +200,37 @@ impl ErrorKind {
     }
 }
 
+impl ErrorKind {
+    /// The kind an EFI error is reported as by default. Callers that know better, e.g. that an
+    /// `AccessDenied` from a TCP connection means it has been closed, use `Error::from_efi_error()`.
+    pub fn from_efi_error_kind(kind: EfiErrorKind) -> ErrorKind {
+        match kind {
+            EfiErrorKind::NotFound => ErrorKind::NotFound,
+            EfiErrorKind::AccessDenied | EfiErrorKind::WriteProtected | EfiErrorKind::SecurityViolation => ErrorKind::PermissionDenied,
+            EfiErrorKind::InvalidParameter | EfiErrorKind::BadBufferSize => ErrorKind::InvalidInput,
+            EfiErrorKind::VolumeCorrupted | EfiErrorKind::CrcError | EfiErrorKind::CompromisedData => ErrorKind::InvalidData,
+            EfiErrorKind::Timeout => ErrorKind::TimedOut,
+            EfiErrorKind::NotReady => ErrorKind::WouldBlock,
+            EfiErrorKind::ConnectionReset => ErrorKind::ConnectionReset,
+            EfiErrorKind::ConnectionFin => ErrorKind::ConnectionAborted,
+            EfiErrorKind::ConnectionRefused => ErrorKind::ConnectionRefused,
+            EfiErrorKind::NoMapping => ErrorKind::AddrNotAvailable,
+            EfiErrorKind::IpAddressConflict => ErrorKind::AddrInUse,
+            EfiErrorKind::EndOfFile | EfiErrorKind::EndOfMedia => ErrorKind::UnexpectedEof,
+            EfiErrorKind::Aborted => ErrorKind::Interrupted,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// Maps the EFI error to an `ErrorKind` with `ErrorKind::from_efi_error_kind()` and keeps
+/// its status so that it can be recovered with `Error::efi_status()`
+impl From<EfiError> for Error {
+    fn from(error: EfiError) -> Error {
+        Error::from_efi_error(ErrorKind::from_efi_error_kind(error.kind()), error)
+    }
+}
+
 /// Intended for use for errors not exposed to the user, where allocating onto
 /// the heap (for normal construction via Error::new) is too costly.
 impl From<ErrorKind> for Error {
@@ -239,6 +276,24 @@ impl Error {
         }
     }
 
+    /// Creates an error of the given kind that originated from an EFI error
+    pub fn from_efi_error(kind: ErrorKind, error: EfiError) -> Error {
+        Error { repr: Repr::Efi(kind, error.kind()) }
+    }
+
+    /// The EFI status this error originated from, if it came from the firmware
+    pub fn efi_status(&self) -> Option<EFI_STATUS> {
+        self.efi_error_kind().map(|kind| kind.into())
+    }
+
+    /// Same as `efi_status()` but as an `EfiErrorKind`, which is easier to match on
+    pub fn efi_error_kind(&self) -> Option<EfiErrorKind> {
+        match self.repr {
+            Repr::Efi(_, efi_kind) => Some(efi_kind),
+            _ => None,
+        }
+    }
+
     /// Creates a new instance of an `Error` from a particular OS error code.
     ///
     /// # Examples
@@ -299,6 +354,7 @@ impl Error {
             Repr::Os(i) => Some(i),
             Repr::Custom(..) => None,
             Repr::Simple(..) => None,
+            Repr::Efi(..) => None,
         }
     }
 
@@ -331,6 +387,7 @@ impl Error {
         match self.repr {
             Repr::Os(..) => None,
             Repr::Simple(..) => None,
+            Repr::Efi(..) => None,
             Repr::Custom(ref c) => Some(&c.error),
         }
     }
@@ -401,6 +458,7 @@ impl Error {
         match self.repr {
             Repr::Os(..) => None,
             Repr::Simple(..) => None,
+            Repr::Efi(..) => None,
             Repr::Custom(ref mut c) => Some(&mut c.error),
         }
     }
@@ -434,6 +492,7 @@ impl Error {
         match self.repr {
             Repr::Os(..) => None,
             Repr::Simple(..) => None,
+            Repr::Efi(..) => None,
             Repr::Custom(c) => Some(c.error)
         }
     }
@@ -461,6 +520,7 @@ impl Error {
             Repr::Os(_code) => ErrorKind::Other,
             Repr::Custom(ref c) => c.kind,
             Repr::Simple(kind) => kind,
+            Repr::Efi(kind, _) => kind,
         }
     }
 }
@@ -473,6 +533,7 @@ impl fmt::Debug for Repr {
                     .field("code", &code).finish(),
             Repr::Custom(ref c) => fmt::Debug::fmt(&c, fmt),
             Repr::Simple(kind) => fmt.debug_tuple("Kind").field(&kind).finish(),
+            Repr::Efi(kind, efi_kind) => fmt.debug_struct("Efi").field("kind", &kind).field("status", &efi_kind).finish(),
         }
     }
 }
@@ -485,10 +546,13 @@ impl fmt::Display for Error {
             }
             Repr::Custom(ref c) => c.error.fmt(fmt),
             Repr::Simple(kind) => write!(fmt, "{}", kind.as_str()),
+            Repr::Efi(kind, efi_kind) => write!(fmt, "{} ({:?})", kind.as_str(), efi_kind),
         }
     }
 }
 
+impl Fail for Error {}
+
 fn _assert_error_is_sync_send() {
     fn _is_sync_send<T: Sync+Send>() {}
     _is_sync_send::<Error>();
@@ -14,24 +14,76 @@
 #![feature(ptr_internals)]
 #![feature(duration_extras)]
 #![feature(duration_from_micros)]
+#![feature(const_fn)]
+#![feature(stdsimd)]
+#![feature(link_llvm_intrinsics)]
+#![cfg_attr(feature = "panic-handler", feature(lang_items))]
 
 // #![warn(missing_debug_implementations)]
 
 #[macro_use] extern crate failure;
 #[macro_use] extern crate alloc;
 extern crate byteorder;
+#[cfg(feature = "with-serde")] #[macro_use] extern crate serde;
+#[cfg(feature = "with-serde")] #[macro_use] extern crate serde_derive;
+#[cfg(feature = "log")] extern crate log;
+#[cfg(feature = "getrandom")] #[macro_use] extern crate getrandom;
+#[cfg(feature = "mock")] extern crate std as host_std;
 
 #[macro_use] mod utils;
 #[macro_use] pub mod console;
+#[macro_use] pub mod guid;
+#[macro_use] pub mod ucs2;
+pub(crate) mod cell;
+pub mod debug;
 pub mod ffi;
 pub mod io;
-pub mod net;
-pub mod image;
-pub mod device_path;
+pub mod ansi;
+#[cfg(not(feature = "runtime-driver"))] pub mod net;
+#[cfg(not(feature = "runtime-driver"))] pub mod image;
+#[cfg(not(feature = "runtime-driver"))] pub mod args;
+#[cfg(not(feature = "runtime-driver"))] pub mod driver;
+#[cfg(not(feature = "runtime-driver"))] pub mod component_name;
+#[cfg(not(feature = "runtime-driver"))] pub mod device_path;
+#[cfg(not(feature = "runtime-driver"))] pub mod pci;
+#[cfg(not(feature = "runtime-driver"))] pub mod usb;
+#[cfg(not(feature = "runtime-driver"))] pub mod decompress;
+#[cfg(not(feature = "runtime-driver"))] pub mod firmware_volume;
+#[cfg(not(feature = "runtime-driver"))] pub mod rng;
+#[cfg(not(feature = "runtime-driver"))] pub mod memory_attribute;
+#[cfg(not(feature = "runtime-driver"))] pub mod performance;
 pub mod boxed;
-pub mod events;
+#[cfg(not(feature = "runtime-driver"))] pub mod events;
+#[cfg(not(feature = "runtime-driver"))] pub mod executor;
+#[cfg(not(feature = "runtime-driver"))] pub mod watchdog;
+#[cfg(not(feature = "runtime-driver"))] pub mod mp;
+#[cfg(not(feature = "runtime-driver"))] pub mod hii;
 pub mod time;
-mod allocator;
+pub mod counter;
+pub mod variables;
+#[cfg(not(feature = "runtime-driver"))] pub mod boot_manager;
+pub mod runtime;
+pub mod security;
+pub mod os_indications;
+pub mod config_store;
+pub mod config_table;
+pub mod acpi;
+pub mod smbios;
+#[cfg(all(feature = "fs", not(feature = "runtime-driver")))] pub mod fs;
+#[cfg(all(feature = "shell", not(feature = "runtime-driver")))] pub mod shell;
+#[cfg(all(feature = "storage", not(feature = "runtime-driver")))] pub mod storage;
+#[cfg(all(feature = "storage", not(feature = "runtime-driver")))] pub mod gpt;
+#[cfg(all(feature = "storage", not(feature = "runtime-driver")))] pub mod mbr;
+#[cfg(not(feature = "runtime-driver"))] pub mod load_file;
+#[cfg(all(feature = "graphics", not(feature = "runtime-driver")))] pub mod graphics;
+#[cfg(not(feature = "runtime-driver"))] pub mod serial;
+#[cfg(not(feature = "runtime-driver"))] pub mod pointer;
+#[cfg(not(feature = "runtime-driver"))] pub mod progress;
+#[cfg(not(feature = "runtime-driver"))] pub mod tui;
+#[cfg(all(feature = "log", not(feature = "runtime-driver")))] pub mod logger;
+#[cfg(all(feature = "panic-handler", not(feature = "runtime-driver")))] pub mod panic_handler;
+#[cfg(all(feature = "mock", not(feature = "runtime-driver")))] pub mod mock;
+#[cfg(not(feature = "mock"))] mod allocator;
 
 // Hack: this std declartion is to work around a bug in failure crate
 // wherein it looks for std even in no_std crates. Will remove it when
@@ -41,106 +93,272 @@ mod std {
     pub use core::fmt;
 }
 
-use core::{fmt::{Debug, Display, Formatter}, ptr, mem::transmute};
+use core::{fmt::{Debug, Display, Formatter}, ptr, mem::transmute, sync::atomic::{AtomicBool, Ordering}};
 use ffi::{
     tcp4,
     EFI_STATUS,
+    EFI_SUCCESS,
     EFI_SYSTEM_TABLE,
     EFI_HANDLE, 
+    EFI_EVENT,
+    VOID,
+    boot_services::{EFI_BOOT_SERVICES, EVT_SIGNAL_EXIT_BOOT_SERVICES, TPL_NOTIFY},
     console::{EFI_SIMPLE_TEXT_INPUT_PROTOCOL, EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL, EFI_SIMPLE_TEXT_INPUT_PROTOCOL_GUID, EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL_GUID},
-    boot_services::EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL,
 };
 
-use failure::{Context, Fail, Backtrace};
-use allocator::EfiAllocator;
+use failure::{Fail, Backtrace};
+use alloc::{boxed::Box, String};
+#[cfg(not(feature = "mock"))] use allocator::EfiAllocator;
 pub use console::{Console, stdin, stdout};
 pub use utils::NullTerminatedAsciiStr;
+use cell::OnceCell;
+
+// Set by init_env(). The system table pointer is changed once more if the OS switches the firmware to
+// virtual addressing (see the runtime module).
+static SYSTEM_TABLE: OnceCell<*const EFI_SYSTEM_TABLE> = OnceCell::new();
+static IMAGE_HANDLE: OnceCell<EFI_HANDLE> = OnceCell::new();
+static BOOT_SERVICES_EXITED: AtomicBool = AtomicBool::new(false);
+
+/// Initializes the crate with what the firmware passed to the image's entry point. Must be called before
+/// anything else in the crate is used. Calling it again with the same arguments does nothing.
+///
+/// Panics if called again with a different system table or image handle.
+pub fn init_env(image_handle: EFI_HANDLE, system_table: *const EFI_SYSTEM_TABLE) {
+    if let Some(&existing) = SYSTEM_TABLE.get() {
+        assert!(existing == system_table && IMAGE_HANDLE.get() == Some(&image_handle),
+            "init_env() was called again with a different system table or image handle");
+        return;
+    }
 
-static mut SYSTEM_TABLE: Option<*const EFI_SYSTEM_TABLE> = None;
-static mut IMAGE_HANDLE: Option<EFI_HANDLE> = None;
+    let _ = SYSTEM_TABLE.set(system_table);
+    let _ = IMAGE_HANDLE.set(image_handle);
 
-pub fn init_env(image_handle: EFI_HANDLE, system_table: *const EFI_SYSTEM_TABLE) {
+    // Nothing can be done if either of these fails other than forgo image-relative backtraces or
+    // the check in boot_services()
+    debug::record_image(image_handle);
     unsafe {
-        SYSTEM_TABLE = Some(system_table);
-        IMAGE_HANDLE = Some(image_handle);
+        let mut event: EFI_EVENT = ptr::null();
+        ((*(*system_table).BootServices).CreateEvent)(EVT_SIGNAL_EXIT_BOOT_SERVICES, TPL_NOTIFY, Some(exit_boot_services_cb), ptr::null(), &mut event);
     }
 }
 
-#[inline]
-pub fn system_table() -> &'static EFI_SYSTEM_TABLE {
+// The mock firmware installs a fresh system table for every test, which init_env() would refuse
+#[cfg(all(feature = "mock", not(feature = "runtime-driver")))]
+pub(crate) fn reset_env(image_handle: EFI_HANDLE, system_table: *const EFI_SYSTEM_TABLE) {
     unsafe {
-        transmute(SYSTEM_TABLE.expect("lib uninitalized"))
+        SYSTEM_TABLE.replace(system_table);
+        IMAGE_HANDLE.replace(image_handle);
+    }
+    BOOT_SERVICES_EXITED.store(false, Ordering::SeqCst);
+}
+
+extern "win64" fn exit_boot_services_cb(_event: EFI_EVENT, _context: *const VOID) -> EFI_STATUS {
+    BOOT_SERVICES_EXITED.store(true, Ordering::SeqCst);
+    EFI_SUCCESS
+}
+
+// The system table the image was started with. After the OS has switched the firmware to virtual addressing
+// it's only valid if runtime::enable_virtual_address_change_fixups() was called. Crate internal since the
+// function pointers in it can be called from safe code with any arguments.
+//
+// Panics if init_env() hasn't been called.
+#[inline]
+pub(crate) fn system_table() -> &'static EFI_SYSTEM_TABLE {
+    match SYSTEM_TABLE.get() {
+        Some(&system_table) => unsafe { &*system_table },
+        None => panic!("the efi crate was used before init_env() was called"),
+    }
+}
+
+// The boot services table.
+//
+// Panics if init_env() hasn't been called or if ExitBootServices() has been, since boot services are
+// gone by then and calling them would crash (or worse) rather than fail.
+#[inline]
+pub(crate) fn boot_services() -> &'static EFI_BOOT_SERVICES {
+    if boot_services_exited() {
+        panic!("boot services were used after ExitBootServices() was called");
+    }
+
+    unsafe { &*system_table().BootServices }
+}
+
+/// Whether ExitBootServices() has been called
+pub fn boot_services_exited() -> bool {
+    BOOT_SERVICES_EXITED.load(Ordering::SeqCst)
+}
+
+/// The firmware that started the image, as described by its system table
+#[derive(Copy, Clone)]
+pub struct Firmware {
+    table: &'static EFI_SYSTEM_TABLE,
+}
+
+impl Firmware {
+    /// The firmware vendor, e.g. "EDK II"
+    pub fn vendor(&self) -> String {
+        if self.table.FirmwareVendor.is_null() {
+            return String::new();
+        }
+
+        String::from_utf16_lossy(unsafe { utils::as_slice(self.table.FirmwareVendor) })
+    }
+
+    /// The vendor specific revision of the firmware
+    pub fn revision(&self) -> u32 {
+        self.table.FirmwareRevision
+    }
+
+    /// The version of the UEFI specification the firmware implements as (major, minor), e.g. (2, 70) for 2.7
+    pub fn uefi_version(&self) -> (u16, u16) {
+        let revision = self.table.Hdr.Revision;
+        ((revision >> 16) as u16, revision as u16)
     }
+
+    /// The (GUID, pointer) entries of the configuration table
+    pub fn config_table(&self) -> config_table::ConfigTableIter<'static> {
+        config_table::entries(self.table)
+    }
+}
+
+/// The firmware the image was started by.
+///
+/// Panics if `init_env()` hasn't been called.
+pub fn firmware() -> Firmware {
+    Firmware { table: system_table() }
 }
 
-// TODO: if a function (like this) returns an raw pointer, shouldn't it be marked unsafe?
+/// The handle of the running image.
+///
+/// Panics if `init_env()` hasn't been called.
 #[inline]
 pub fn image_handle() -> EFI_HANDLE {
-    unsafe {
-        IMAGE_HANDLE.expect("lib uninitalized")
+    match IMAGE_HANDLE.get() {
+        Some(&image_handle) => image_handle,
+        None => panic!("the efi crate was used before init_env() was called"),
     }
 }
 
 
+ #[cfg(not(feature = "mock"))]
  #[global_allocator]
  static ALLOCATOR: EfiAllocator = EfiAllocator;
 
 
-// TODO: instead of calling them errors we should change the name to status and remove Fail etc. from them.
-// They'll then only be used in as the "causes" of actual errors which we will introduce
+/// The error type used throughout the crate.
+///
+/// Besides the kind it remembers the raw `EFI_STATUS` it was created from (which
+/// matters for statuses `EfiErrorKind` does not recognize), an optional static string
+/// describing what was being attempted and an optional underlying cause.
 pub struct EfiError {
-    inner: Context<EfiErrorKind>
+    kind: EfiErrorKind,
+    status: EFI_STATUS,
+    context: Option<&'static str>,
+    source: Option<Box<Fail>>,
 }
 
 impl EfiError {
+    /// Creates an error of the given kind caused by `source`
+    pub fn with_source<F: Fail>(kind: EfiErrorKind, source: F) -> Self {
+        let mut error = EfiError::from(kind);
+        error.source = Some(Box::new(source));
+        error
+    }
+
     pub fn kind(&self) -> EfiErrorKind {
-        *self.inner.get_context()
+        self.kind
     }
-}
 
-impl From<EfiErrorKind> for EfiError {
-    fn from(kind: EfiErrorKind) -> EfiError {
-        EfiError { inner: Context::new(kind) }
+    /// The raw status this error was created from
+    pub fn status(&self) -> EFI_STATUS {
+        self.status
+    }
+
+    /// What was being attempted when the error occurred, if known
+    pub fn context_str(&self) -> Option<&'static str> {
+        self.context
+    }
+
+    /// The underlying error that caused this one, if any
+    pub fn source(&self) -> Option<&Fail> {
+        self.source.as_ref().map(|s| &**s)
+    }
+
+    /// Attaches a description of what was being attempted. An existing context
+    /// is kept as the cause so that no information is lost.
+    pub fn add_context(self, context: &'static str) -> Self {
+        if self.context.is_none() {
+            return EfiError { context: Some(context), ..self };
+        }
+
+        let (kind, status) = (self.kind, self.status);
+        EfiError { kind, status, context: Some(context), source: Some(Box::new(self)) }
     }
 }
 
-impl From<Context<EfiErrorKind>> for EfiError {
-    fn from(inner: Context<EfiErrorKind>) -> EfiError {
-        EfiError { inner: inner }
+impl From<EfiErrorKind> for EfiError {
+    fn from(kind: EfiErrorKind) -> EfiError {
+        EfiError { kind, status: kind.into(), context: None, source: None }
     }
 }
 
 impl From<EFI_STATUS> for EfiError {
     fn from(status: ffi::EFI_STATUS) -> Self {
-        EfiError::from(EfiErrorKind::from(status))
+        EfiError { kind: EfiErrorKind::from(status), status, context: None, source: None }
     }
 }
 
 impl From<EfiError> for EFI_STATUS {
     fn from(error: EfiError) -> Self {
-        error.kind().into()
+        error.status
     }
 }
 
 impl Fail for EfiError {
     fn cause(&self) -> Option<&Fail> {
-        self.inner.cause()
+        self.source()
     }
 
     fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
+        None
     }
 }
 
 impl Debug for EfiError {
     fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
-        write!(f, "{:?} (0x{:X})", self.kind() , self.kind() as usize)
+        write!(f, "{:?} (0x{:X})", self.kind, self.status)?;
+        if let Some(context) = self.context {
+            write!(f, " while {}", context)?;
+        }
+        if let Some(ref source) = self.source {
+            write!(f, ", caused by {:?}", source)?;
+        }
+        Ok(())
     }
 }
 
 impl Display for EfiError {
     fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
-        write!(f, "{:?} (0x{:X}) - {}", self.kind() , self.kind() as usize, self.kind())
+        if let Some(context) = self.context {
+            write!(f, "{}: ", context)?;
+        }
+        write!(f, "{:?} (0x{:X}) - {}", self.kind, self.status, self.kind)?;
+        if let Some(ref source) = self.source {
+            write!(f, " (caused by: {})", source)?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds context to the error of a `Result`
+pub trait ResultExt<T> {
+    /// Attaches a description of what was being attempted to the error, if any
+    fn context(self, context: &'static str) -> Result<T>;
+}
+
+impl<T, E: Into<EfiError>> ResultExt<T> for core::result::Result<T, E> {
+    fn context(self, context: &'static str) -> Result<T> {
+        self.map_err(|e| e.into().add_context(context))
     }
 }
 
@@ -211,6 +429,8 @@ pub enum EfiErrorKind {
     CompromisedData = ffi::EFI_COMPROMISED_DATA,
     #[fail(display = "There is an address conflict during address allocation")]
     IpAddressConflict = ffi::EFI_IP_ADDRESS_CONFLICT,
+    #[fail(display = "An HTTP error occurred during the network operation")]
+    HttpError = ffi::EFI_HTTP_ERROR,
 
     // TODO: The below are not standard, common EFI_STATUSes, but only specific to TCP
     // So is it good to include them in this enum?
@@ -230,8 +450,42 @@ pub enum EfiErrorKind {
 impl From<EFI_STATUS> for EfiErrorKind {
     fn from(status: ffi::EFI_STATUS) -> Self {
         match status {
-            | ffi::EFI_LOAD_ERROR..=ffi::EFI_IP_ADDRESS_CONFLICT 
-            | tcp4::EFI_CONNECTION_FIN..=tcp4::EFI_CONNECTION_REFUSED =>  unsafe { transmute(status) },
+            ffi::EFI_LOAD_ERROR => EfiErrorKind::LoadError,
+            ffi::EFI_INVALID_PARAMETER => EfiErrorKind::InvalidParameter,
+            ffi::EFI_UNSUPPORTED => EfiErrorKind::Unsupported,
+            ffi::EFI_BAD_BUFFER_SIZE => EfiErrorKind::BadBufferSize,
+            ffi::EFI_BUFFER_TOO_SMALL => EfiErrorKind::BufferTooSmall,
+            ffi::EFI_NOT_READY => EfiErrorKind::NotReady,
+            ffi::EFI_DEVICE_ERROR => EfiErrorKind::DeviceError,
+            ffi::EFI_WRITE_PROTECTED => EfiErrorKind::WriteProtected,
+            ffi::EFI_OUT_OF_RESOURCES => EfiErrorKind::OutOfResources,
+            ffi::EFI_VOLUME_CORRUPTED => EfiErrorKind::VolumeCorrupted,
+            ffi::EFI_VOLUME_FULL => EfiErrorKind::VolumeFull,
+            ffi::EFI_NO_MEDIA => EfiErrorKind::NoMedia,
+            ffi::EFI_MEDIA_CHANGED => EfiErrorKind::MediaChanged,
+            ffi::EFI_NOT_FOUND => EfiErrorKind::NotFound,
+            ffi::EFI_ACCESS_DENIED => EfiErrorKind::AccessDenied,
+            ffi::EFI_NO_RESPONSE => EfiErrorKind::NoResponse,
+            ffi::EFI_NO_MAPPING => EfiErrorKind::NoMapping,
+            ffi::EFI_TIMEOUT => EfiErrorKind::Timeout,
+            ffi::EFI_NOT_STARTED => EfiErrorKind::NotStarted,
+            ffi::EFI_ALREADY_STARTED => EfiErrorKind::AlreadyStarted,
+            ffi::EFI_ABORTED => EfiErrorKind::Aborted,
+            ffi::EFI_ICMP_ERROR => EfiErrorKind::IcmpError,
+            ffi::EFI_TFTP_ERROR => EfiErrorKind::TftpError,
+            ffi::EFI_PROTOCOL_ERROR => EfiErrorKind::ProtocolError,
+            ffi::EFI_INCOMPATIBLE_VERSION => EfiErrorKind::IncompatibleVersion,
+            ffi::EFI_SECURITY_VIOLATION => EfiErrorKind::SecurityViolation,
+            ffi::EFI_CRC_ERROR => EfiErrorKind::CrcError,
+            ffi::EFI_END_OF_MEDIA => EfiErrorKind::EndOfMedia,
+            ffi::EFI_END_OF_FILE => EfiErrorKind::EndOfFile,
+            ffi::EFI_INVALID_LANGUAGE => EfiErrorKind::InvalidLanguage,
+            ffi::EFI_COMPROMISED_DATA => EfiErrorKind::CompromisedData,
+            ffi::EFI_IP_ADDRESS_CONFLICT => EfiErrorKind::IpAddressConflict,
+            ffi::EFI_HTTP_ERROR => EfiErrorKind::HttpError,
+            tcp4::EFI_CONNECTION_FIN => EfiErrorKind::ConnectionFin,
+            tcp4::EFI_CONNECTION_RESET => EfiErrorKind::ConnectionReset,
+            tcp4::EFI_CONNECTION_REFUSED => EfiErrorKind::ConnectionRefused,
             _ => EfiErrorKind::UnrecognizedError
         }
     }
@@ -243,20 +497,46 @@ impl Into<usize> for EfiErrorKind {
     }
 }
 
-#[derive(Debug)]
+/// A warning status. The operation was carried out but something noteworthy happened.
+#[derive(Debug, Fail, Copy, Clone, PartialEq)]
 #[repr(usize)]
 pub enum EfiWarning {
-    UnknownGlyph = ffi::EFI_WARN_UNKNOWN_GLYPH, // The string contained one or more characters that the device could not render and were skipped.
-    DeleteFailure = ffi::EFI_WARN_DELETE_FAILURE, // The handle was closed, but the file was not deleted.
-    WriteFailure = ffi::EFI_WARN_WRITE_FAILURE, // The handle was closed, but the data to the file was not flushed properly.
-    BufferTooSmall = ffi::EFI_WARN_BUFFER_TOO_SMALL, // The resulting buffer was too small, and the data was truncated to the buffer size.
-    StaleData = ffi::EFI_WARN_STALE_DATA, // The data has not been updated within the timeframe set by local policy for this type of data.
+    #[fail(display = "The string contained one or more characters that the device could not render and were skipped")]
+    UnknownGlyph = ffi::EFI_WARN_UNKNOWN_GLYPH,
+    #[fail(display = "The handle was closed, but the file was not deleted")]
+    DeleteFailure = ffi::EFI_WARN_DELETE_FAILURE,
+    #[fail(display = "The handle was closed, but the data to the file was not flushed properly")]
+    WriteFailure = ffi::EFI_WARN_WRITE_FAILURE,
+    #[fail(display = "The resulting buffer was too small, and the data was truncated to the buffer size")]
+    BufferTooSmall = ffi::EFI_WARN_BUFFER_TOO_SMALL,
+    #[fail(display = "The data has not been updated within the timeframe set by local policy for this type of data")]
+    StaleData = ffi::EFI_WARN_STALE_DATA,
+    #[fail(display = "The resulting buffer contains a UEFI-compliant file system")]
+    FileSystem = ffi::EFI_WARN_FILE_SYSTEM,
+    #[fail(display = "The operation will be processed across a system reset")]
+    ResetRequired = ffi::EFI_WARN_RESET_REQUIRED,
+    #[fail(display = "Unrecognized EFI warning")]
     UnrecognizedWarning = <EFI_STATUS>::max_value()
 }
 
 impl From<EFI_STATUS> for EfiWarning {
     fn from(status: ffi::EFI_STATUS) -> Self {
-        if ffi::IsWarning(status) { unsafe { transmute(status) } } else { EfiWarning::UnrecognizedWarning }
+        match status {
+            ffi::EFI_WARN_UNKNOWN_GLYPH => EfiWarning::UnknownGlyph,
+            ffi::EFI_WARN_DELETE_FAILURE => EfiWarning::DeleteFailure,
+            ffi::EFI_WARN_WRITE_FAILURE => EfiWarning::WriteFailure,
+            ffi::EFI_WARN_BUFFER_TOO_SMALL => EfiWarning::BufferTooSmall,
+            ffi::EFI_WARN_STALE_DATA => EfiWarning::StaleData,
+            ffi::EFI_WARN_FILE_SYSTEM => EfiWarning::FileSystem,
+            ffi::EFI_WARN_RESET_REQUIRED => EfiWarning::ResetRequired,
+            _ => EfiWarning::UnrecognizedWarning
+        }
+    }
+}
+
+impl Into<usize> for EfiWarning {
+    fn into(self) -> usize {
+        self as usize
     }
 }
 
@@ -265,11 +545,32 @@ pub enum GeneralError {
     #[fail(display = "Failed to convert from one value to another")]
     ConversionFailed,
 }
+/// The value of an operation that succeeded, along with the warning it returned if any
+#[derive(Debug)]
 pub struct WithWarning<T> {
     pub value: T,
     pub warning: Option<EfiWarning>
 }
 
+impl<T> WithWarning<T> {
+    pub fn new(value: T, warning: Option<EfiWarning>) -> Self {
+        WithWarning { value, warning }
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.warning.is_some()
+    }
+
+    /// Discards the warning, if any
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> WithWarning<U> {
+        WithWarning { value: f(self.value), warning: self.warning }
+    }
+}
+
 pub type Result<T> = core::result::Result<T, EfiError>;
 
 pub type Guid = ffi::EFI_GUID;
@@ -285,21 +586,20 @@ fn from_boolean(val: ffi::BOOLEAN) -> bool {
     val != 0
 }
 
+// Warnings mean the operation was carried out, so they come back as Ok. Use `to_res_with_warning()`
+// where the warning is of interest to the caller.
 fn to_res<T>(value: T, status: ffi::EFI_STATUS) -> Result<T> {
+    to_res_with_warning(value, status).map(WithWarning::into_value)
+}
+
+fn to_res_with_warning<T>(value: T, status: ffi::EFI_STATUS) -> Result<WithWarning<T>> {
     match ffi::StatusType(status) {
-        ffi::EFI_STATUS_TYPE::SUCCESS => Ok(value),
-        _ => Err(EfiError::from(status))
+        ffi::EFI_STATUS_TYPE::SUCCESS => Ok(WithWarning::new(value, None)),
+        ffi::EFI_STATUS_TYPE::WARNING => Ok(WithWarning::new(value, Some(EfiWarning::from(status)))),
+        ffi::EFI_STATUS_TYPE::ERROR => Err(EfiError::from(status))
     }
 }
 
-// fn to_res_with_warning<T>(value: T, status: ffi::EFI_STATUS) -> Result<WithWarning<T>> {
-//     match ffi::StatusType(status) {
-//         ffi::EFI_STATUS_TYPE::SUCCESS => Ok(WithWarning { value, warning: None }),
-//         ffi::EFI_STATUS_TYPE::WARNING => Ok(WithWarning { value, warning: Some(EfiWarning::from(status))}),
-//         ffi::EFI_STATUS_TYPE::ERROR => Err(EfiError::from(status))
-//     }
-// }
-
 pub enum TextInputProcolPtr {
     Input(*mut EFI_SIMPLE_TEXT_INPUT_PROTOCOL),
     InputEx(*mut EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL),
@@ -333,38 +633,21 @@ impl SystemTable {
             Console::new(self.con_in, (*self.table_ptr).ConOut)
         }
     }
+
+    /// The (GUID, pointer) entries of the configuration table
+    pub fn config_table(&self) -> config_table::ConfigTableIter<'static> {
+        unsafe { config_table::entries(&*self.table_ptr) }
+    }
 }
 
 fn get_simple_text_input_ex(table_ptr: *const EFI_SYSTEM_TABLE) -> Result<*mut EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL> {
-    let mut protocol: *mut EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL = ptr::null_mut();
-
-    let status = unsafe {
-        ((*(*table_ptr).BootServices).OpenProtocol)(
-            (*table_ptr).ConsoleInHandle, 
-            &EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL_GUID, 
-            transmute(&mut protocol), 
-            image_handle(), 
-            ptr::null(), 
-            EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL)
-    };
-
-    to_res(protocol, status)
+    let protocol = utils::open_protocol::<EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL>(unsafe { (*table_ptr).ConsoleInHandle }, &EFI_SIMPLE_TEXT_INPUT_EX_PROTOCOL_GUID)?;
+    Ok(protocol as *mut _)
 }
 
 fn get_simple_text_input(table_ptr: *const EFI_SYSTEM_TABLE) -> Result<*mut EFI_SIMPLE_TEXT_INPUT_PROTOCOL> {
-    let mut protocol: *mut EFI_SIMPLE_TEXT_INPUT_PROTOCOL = ptr::null_mut();
-
-    let status = unsafe {
-        ((*(*table_ptr).BootServices).OpenProtocol)(
-            (*table_ptr).ConsoleInHandle, 
-            &EFI_SIMPLE_TEXT_INPUT_PROTOCOL_GUID, 
-            transmute(&mut protocol), 
-            image_handle(), 
-            ptr::null(), 
-            EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL)
-    };
-
-    to_res(protocol, status)
+    let protocol = utils::open_protocol::<EFI_SIMPLE_TEXT_INPUT_PROTOCOL>(unsafe { (*table_ptr).ConsoleInHandle }, &EFI_SIMPLE_TEXT_INPUT_PROTOCOL_GUID)?;
+    Ok(protocol as *mut _)
 }
 
 // Used for opaque pointers such as efi handles
@@ -402,4 +685,63 @@ impl<'a> Into<&'a Opaque> for &'a OpaqueController {
     }
 }
 
-pub struct OpaqueEvent { _private: [u8; 0] }
\ No newline at end of file
+pub struct OpaqueEvent { _private: [u8; 0] }
+#[cfg(test)]
+mod tests {
+    use super::{ffi, EfiError, EfiErrorKind, EfiWarning, ResultExt, Result, to_res, to_res_with_warning};
+    use failure::Fail;
+
+    #[test]
+    fn unrecognized_status_is_preserved() {
+        let status = 0x8000_0000_0000_0100usize;
+        let error = EfiError::from(status);
+        assert_eq!(error.kind(), EfiErrorKind::UnrecognizedError);
+        assert_eq!(error.status(), status);
+        assert_eq!(usize::from(error), status);
+    }
+
+    #[test]
+    fn context_is_attached_and_chained() {
+        let result: Result<()> = Err(EfiErrorKind::Timeout.into());
+        let error = result.context("receiving").context("querying").unwrap_err();
+        assert_eq!(error.kind(), EfiErrorKind::Timeout);
+        assert_eq!(error.context_str(), Some("querying"));
+        assert!(error.cause().is_some());
+        assert_eq!(format!("{:?}", error), "Timeout (0x8000000000000012) while querying, caused by Timeout (0x8000000000000012) while receiving");
+    }
+
+    #[test]
+    fn source_is_exposed_as_cause() {
+        let error = EfiError::with_source(EfiErrorKind::ProtocolError, EfiErrorKind::BadBufferSize);
+        assert_eq!(error.status(), EfiErrorKind::ProtocolError as usize);
+        assert_eq!(format!("{:?}", error.cause().unwrap()), "BadBufferSize");
+    }
+
+    #[test]
+    fn warnings_are_ok_with_warning() {
+        let res = to_res_with_warning(5, ffi::EFI_WARN_STALE_DATA).unwrap();
+        assert_eq!(res.value, 5);
+        assert_eq!(res.warning, Some(EfiWarning::StaleData));
+        assert_eq!(to_res_with_warning((), ffi::EFI_SUCCESS).unwrap().warning, None);
+        assert_eq!(to_res_with_warning((), 0x42).unwrap().warning, Some(EfiWarning::UnrecognizedWarning));
+        assert_eq!(to_res(1, ffi::EFI_WARN_RESET_REQUIRED).unwrap(), 1);
+        assert_eq!(to_res(1, ffi::EFI_SUCCESS).unwrap(), 1);
+        assert_eq!(to_res((), ffi::EFI_HTTP_ERROR).unwrap_err().kind(), EfiErrorKind::HttpError);
+    }
+
+    #[test]
+    fn status_round_trips_through_kinds() {
+        let high_bit = ffi::EFI_LOAD_ERROR & !1;
+        for code in 1..36 {
+            let kind = EfiErrorKind::from(high_bit | code);
+            match code {
+                29 | 30 => assert_eq!(kind, EfiErrorKind::UnrecognizedError), // Not defined by the spec
+                _ => assert_eq!(kind as usize, high_bit | code),
+            }
+        }
+
+        for status in 1..8 {
+            assert_eq!(EfiWarning::from(status) as usize, status);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Loading files from devices through EFI_LOAD_FILE_PROTOCOL and EFI_LOAD_FILE2_PROTOCOL,
+//! and serving an initrd to the Linux EFI stub through the latter
+
+use ffi::{
+    media::{
+        EFI_LOAD_FILE_PROTOCOL,
+        EFI_LOAD_FILE_PROTOCOL_GUID,
+        EFI_LOAD_FILE2_PROTOCOL,
+        EFI_LOAD_FILE2_PROTOCOL_GUID,
+        LINUX_EFI_INITRD_MEDIA_GUID,
+    },
+    device_path::{
+        EFI_DEVICE_PATH_PROTOCOL,
+        EFI_DEVICE_PATH_PROTOCOL_GUID,
+        MEDIA_DEVICE_PATH,
+        MEDIA_VENDOR_DP,
+        END_DEVICE_PATH_TYPE,
+        END_ENTIRE_DEVICE_PATH_SUBTYPE,
+    },
+    boot_services::EFI_INTERFACE_TYPE,
+    EFI_HANDLE,
+    EFI_STATUS,
+    EFI_SUCCESS,
+    EFI_BUFFER_TOO_SMALL,
+    EFI_INVALID_PARAMETER,
+    EFI_UNSUPPORTED,
+    UINTN,
+    BOOLEAN,
+    VOID,
+    IsSuccess,
+};
+use device_path::DevicePath;
+use utils::{guid_to_bytes, open_protocol};
+use alloc::{Vec, boxed::Box};
+use core::ptr;
+use {Result, EfiErrorKind, boot_services, to_boolean, from_boolean};
+
+/// A device that can produce files on request through EFI_LOAD_FILE_PROTOCOL e.g. a PXE or HTTP boot device
+pub struct LoadFile(*const EFI_LOAD_FILE_PROTOCOL);
+
+impl LoadFile {
+    /// Opens EFI_LOAD_FILE_PROTOCOL on the given handle
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        open_protocol(handle, &EFI_LOAD_FILE_PROTOCOL_GUID).map(LoadFile)
+    }
+
+    /// Loads the file with the given path. The path is relative to the device i.e. it excludes the device's own path.
+    /// If `boot_policy` is true the request is for a boot option and the device may pick the file itself.
+    pub fn load(&self, file_path: &DevicePath, boot_policy: bool) -> Result<Vec<u8>> {
+        load(self.0, file_path.as_ptr(), boot_policy)
+    }
+}
+
+/// A device that can produce files on request through EFI_LOAD_FILE2_PROTOCOL.
+/// Unlike `LoadFile` it's never used for booting.
+pub struct LoadFile2(*const EFI_LOAD_FILE2_PROTOCOL);
+
+impl LoadFile2 {
+    /// Opens EFI_LOAD_FILE2_PROTOCOL on the given handle
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        open_protocol(handle, &EFI_LOAD_FILE2_PROTOCOL_GUID).map(LoadFile2)
+    }
+
+    /// Loads the file with the given path. The path is relative to the device i.e. it excludes the device's own path.
+    pub fn load(&self, file_path: &DevicePath) -> Result<Vec<u8>> {
+        load(self.0, file_path.as_ptr(), false)
+    }
+}
+
+fn load(protocol: *const EFI_LOAD_FILE_PROTOCOL, file_path: *const EFI_DEVICE_PATH_PROTOCOL, boot_policy: bool) -> Result<Vec<u8>> {
+    // First call with no buffer to find out the size of the file
+    let mut size: UINTN = 0;
+    let status = unsafe { ((*protocol).LoadFile)(protocol, file_path, to_boolean(boot_policy), &mut size, ptr::null_mut()) };
+    match status {
+        EFI_SUCCESS => return Ok(Vec::new()),
+        EFI_BUFFER_TOO_SMALL => (),
+        s => return Err(s.into())
+    }
+
+    let mut buf = vec![0_u8; size];
+    unsafe {
+        ret_on_err!(((*protocol).LoadFile)(protocol, file_path, to_boolean(boot_policy), &mut size, buf.as_mut_ptr() as *mut VOID));
+    }
+
+    buf.truncate(size);
+    Ok(buf)
+}
+
+/// An initrd served to the Linux EFI stub. Linux (5.8 onwards) looks for a LoadFile2 instance on a vendor media
+/// device path with LINUX_EFI_INITRD_MEDIA_GUID and loads its initrd from there instead of taking an initrd= argument.
+/// The initrd stays available until this is dropped so make sure to keep it alive until the kernel's been started.
+pub struct LinuxInitrd(Box<InitrdServer>);
+
+#[repr(C)] // repr C needed so that we can safely transmute back to this struct in initrd_load_file_callback below
+struct InitrdServer {
+    proto: EFI_LOAD_FILE2_PROTOCOL,
+    data: Vec<u8>,
+    path: DevicePath,
+    handle: EFI_HANDLE,
+}
+
+impl LinuxInitrd {
+    /// Installs a LoadFile2 instance serving the given data on the Linux initrd media device path.
+    /// Fails with `AlreadyStarted` if some other initrd is already installed.
+    pub fn install(data: Vec<u8>) -> Result<Self> {
+        let bs = boot_services();
+        let path = DevicePath::from_bytes(&initrd_device_path())?;
+
+        unsafe {
+            let mut remaining = path.as_ptr();
+            let mut existing: EFI_HANDLE = ptr::null_mut();
+            let status = ((*bs).LocateDevicePath)(&EFI_LOAD_FILE2_PROTOCOL_GUID, &mut remaining, &mut existing);
+            if IsSuccess(status) && (*remaining).Type == END_DEVICE_PATH_TYPE {
+                return Err(EfiErrorKind::AlreadyStarted.into());
+            }
+        }
+
+        let mut server = Box::new(InitrdServer {
+            proto: EFI_LOAD_FILE2_PROTOCOL { LoadFile: initrd_load_file_callback },
+            data,
+            path,
+            handle: ptr::null_mut(),
+        });
+
+        unsafe {
+            ret_on_err!(((*bs).InstallProtocolInterface)(&mut server.handle, &EFI_DEVICE_PATH_PROTOCOL_GUID, EFI_INTERFACE_TYPE::EFI_NATIVE_INTERFACE, server.path.as_ptr() as *const VOID));
+            let status = ((*bs).InstallProtocolInterface)(&mut server.handle, &EFI_LOAD_FILE2_PROTOCOL_GUID, EFI_INTERFACE_TYPE::EFI_NATIVE_INTERFACE, &server.proto as *const EFI_LOAD_FILE2_PROTOCOL as *const VOID);
+            if !IsSuccess(status) {
+                ((*bs).UninstallProtocolInterface)(server.handle, &EFI_DEVICE_PATH_PROTOCOL_GUID, server.path.as_ptr() as *const VOID);
+                return Err(status.into());
+            }
+        }
+
+        Ok(LinuxInitrd(server))
+    }
+
+    /// The handle the device path and LoadFile2 protocols are installed on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.0.handle
+    }
+}
+
+impl Drop for LinuxInitrd {
+    fn drop(&mut self) {
+        let bs = boot_services();
+        unsafe {
+            ((*bs).UninstallProtocolInterface)(self.0.handle, &EFI_LOAD_FILE2_PROTOCOL_GUID, &self.0.proto as *const EFI_LOAD_FILE2_PROTOCOL as *const VOID); // TODO: Can't do anything if this fails. So we should log here
+            ((*bs).UninstallProtocolInterface)(self.0.handle, &EFI_DEVICE_PATH_PROTOCOL_GUID, self.0.path.as_ptr() as *const VOID);
+        }
+    }
+}
+
+extern "win64" fn initrd_load_file_callback(
+    this: *const EFI_LOAD_FILE2_PROTOCOL,
+    file_path: *const EFI_DEVICE_PATH_PROTOCOL,
+    boot_policy: BOOLEAN,
+    buffer_size: *mut UINTN,
+    buffer_ptr: *mut VOID
+) -> EFI_STATUS {
+    if this.is_null() || file_path.is_null() || buffer_size.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    // LoadFile2 must never be used for booting
+    if from_boolean(boot_policy) {
+        return EFI_UNSUPPORTED;
+    }
+
+    let server: &InitrdServer = unsafe { &*(this as *const InitrdServer) }; // Should be safe to do this cast since InitrdServer is marked repr C
+    let len = server.data.len();
+
+    if buffer_ptr.is_null() || unsafe { *buffer_size } < len {
+        unsafe { *buffer_size = len };
+        return EFI_BUFFER_TOO_SMALL;
+    }
+
+    unsafe {
+        ptr::copy_nonoverlapping(server.data.as_ptr(), buffer_ptr as *mut u8, len);
+        *buffer_size = len;
+    }
+
+    EFI_SUCCESS
+}
+
+// VenMedia(LINUX_EFI_INITRD_MEDIA_GUID) followed by the end node
+fn initrd_device_path() -> Vec<u8> {
+    let mut bytes = vec![MEDIA_DEVICE_PATH, MEDIA_VENDOR_DP, 20, 0];
+    bytes.extend_from_slice(&guid_to_bytes(&LINUX_EFI_INITRD_MEDIA_GUID));
+    bytes.extend_from_slice(&[END_DEVICE_PATH_TYPE, END_ENTIRE_DEVICE_PATH_SUBTYPE, 4, 0]);
+    bytes
+}
@@ -0,0 +1,203 @@
+use log::{self, Log, Metadata, Record, LevelFilter};
+use console::{self, Console};
+use serial::SerialPort;
+use time;
+use io::Write;
+use alloc::{Vec, String, boxed::Box};
+use alloc::fmt::Write as FmtWrite;
+use core::{cmp, mem, cell::RefCell};
+use {Result, EfiErrorKind};
+
+// A backend for the `log` crate. Messages go to any combination of the console, a serial port
+// and an in-memory buffer, each with its own level filter.
+
+/// Where the timestamp at the start of each message comes from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Timestamps {
+    None,
+    /// Date and time from the real time clock (GetTime())
+    WallClock,
+    /// Seconds since the platform was reset as per the timestamp counter. Omitted if there isn't one.
+    SinceReset,
+}
+
+enum Sink {
+    Console(Console),
+    Stdout,
+    Serial(SerialPort),
+    Memory { buf: Vec<u8>, capacity: usize },
+}
+
+struct Target {
+    level: LevelFilter,
+    sink: RefCell<Sink>,
+}
+
+/// Builds and installs the logger. Nothing is logged anywhere until at least one target is added.
+pub struct Logger {
+    targets: Vec<Target>,
+    timestamps: Timestamps,
+}
+
+// UEFI boot services run on a single processor. Reentrancy (e.g. logging from an event
+// notification that interrupted logging) is caught by the RefCells and such messages are dropped.
+unsafe impl Sync for Logger {}
+unsafe impl Send for Logger {}
+
+static mut LOGGER: Option<&'static Logger> = None;
+
+impl Logger {
+    pub fn new() -> Self {
+        Logger { targets: Vec::new(), timestamps: Timestamps::None }
+    }
+
+    /// Logs messages of the given level and above to ConOut
+    pub fn console(self, level: LevelFilter) -> Self {
+        self.target(level, Sink::Console(console::console()))
+    }
+
+    /// Logs messages of the given level and above to standard output i.e. the installed `console::Tee`
+    /// if there is one, otherwise ConOut. Use this instead of `console()` and `serial()` when logging through a tee.
+    pub fn stdout(self, level: LevelFilter) -> Self {
+        self.target(level, Sink::Stdout)
+    }
+
+    /// Logs messages of the given level and above to the serial port
+    pub fn serial(self, port: SerialPort, level: LevelFilter) -> Self {
+        self.target(level, Sink::Serial(port))
+    }
+
+    /// Keeps the most recent messages of the given level and above in memory, up to `capacity` bytes.
+    /// Read them back with `memory_contents()` e.g. to save them to a file or show them after an error.
+    pub fn memory(self, capacity: usize, level: LevelFilter) -> Self {
+        self.target(level, Sink::Memory { buf: Vec::new(), capacity })
+    }
+
+    pub fn timestamps(mut self, timestamps: Timestamps) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Makes this the logger behind the `log` crate's macros.
+    /// Fails with `AlreadyStarted` if a logger has already been installed.
+    pub fn install(self) -> Result<()> {
+        let max_level = self.targets.iter().map(|t| t.level).max().unwrap_or(LevelFilter::Off);
+        let logger: &'static Logger = unsafe { mem::transmute(Box::into_raw(Box::new(self))) }; // Leaked since it has to live forever
+        log::set_logger(logger).map_err(|_| EfiErrorKind::AlreadyStarted)?;
+        log::set_max_level(max_level);
+        unsafe { LOGGER = Some(logger); }
+        Ok(())
+    }
+
+    fn target(mut self, level: LevelFilter, sink: Sink) -> Self {
+        self.targets.push(Target { level, sink: RefCell::new(sink) });
+        self
+    }
+
+    fn format(&self, record: &Record) -> String {
+        let mut line = String::new();
+        match self.timestamps {
+            Timestamps::None => (),
+            Timestamps::WallClock => if let Ok(t) = time::now() {
+                let _ = write!(line, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03} ", t.year, t.month, t.day, t.hour, t.minute, t.second, t.nanosecond / 1_000_000);
+            },
+            Timestamps::SinceReset => if let Ok(t) = time::timestamp() {
+                let _ = write!(line, "[{:5}.{:06}] ", t.as_secs(), t.subsec_micros());
+            },
+        }
+
+        let _ = write!(line, "{:<5} {}: {}\n", record.level(), record.target(), record.args());
+        line
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.targets.iter().any(|t| metadata.level() <= t.level)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = self.format(record);
+        for target in self.targets.iter().filter(|t| record.level() <= t.level) {
+            let mut sink = match target.sink.try_borrow_mut() {
+                Ok(sink) => sink,
+                Err(_) => continue
+            };
+
+            // Nothing sensible can be done if logging itself fails
+            let _ = match *sink {
+                Sink::Console(ref mut console) => console.write_all(line.as_bytes()),
+                Sink::Stdout => console::write_stdout(line.as_bytes()),
+                Sink::Serial(ref mut port) => port.write_all(line.replace("\n", "\r\n").as_bytes()),
+                Sink::Memory { ref mut buf, capacity } => Ok(push_bounded(buf, line.as_bytes(), capacity)),
+            };
+        }
+    }
+
+    fn flush(&self) {
+    }
+}
+
+/// The messages kept in memory by the installed logger, oldest first.
+/// None if no logger with a memory target has been installed.
+pub fn memory_contents() -> Option<String> {
+    let logger = unsafe { LOGGER? };
+    for target in &logger.targets {
+        if let Ok(sink) = target.sink.try_borrow() {
+            if let Sink::Memory { ref buf, .. } = *sink {
+                return Some(String::from_utf8_lossy(buf).into_owned());
+            }
+        }
+    }
+
+    None
+}
+
+// Appends the line dropping whole lines from the front to stay within capacity
+fn push_bounded(buf: &mut Vec<u8>, line: &[u8], capacity: usize) {
+    if line.len() > capacity {
+        buf.clear();
+        return;
+    }
+
+    buf.extend_from_slice(line);
+    if buf.len() > capacity {
+        let excess = buf.len() - capacity;
+        // A newline right before the excess already ends a line, so that's where the search starts
+        let cut = buf[excess - 1..].iter().position(|b| *b == b'\n').map_or(buf.len(), |i| excess + i);
+        let cut = cmp::min(cut, buf.len());
+        buf.drain(..cut);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_buffer_drops_oldest_lines() {
+        let mut buf = Vec::new();
+        push_bounded(&mut buf, b"one\n", 10);
+        push_bounded(&mut buf, b"two\n", 10);
+        assert_eq!(buf, b"one\ntwo\n");
+        push_bounded(&mut buf, b"three\n", 10);
+        assert_eq!(buf, b"two\nthree\n");
+        push_bounded(&mut buf, b"4\n", 10);
+        assert_eq!(buf, b"three\n4\n");
+        push_bounded(&mut buf, b"much too long\n", 10);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn memory_buffer_keeps_lines_that_fit_exactly() {
+        let mut buf = Vec::new();
+        push_bounded(&mut buf, b"ab\n", 6);
+        push_bounded(&mut buf, b"cd\n", 6);
+        push_bounded(&mut buf, b"ef\n", 6);
+        assert_eq!(buf, b"cd\nef\n");
+    }
+}
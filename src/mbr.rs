@@ -0,0 +1,487 @@
+//! Reading and editing legacy Master Boot Record (MBR) partition tables including logical partitions in EBR chains
+
+use storage::BlockDevice;
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::Vec;
+use {Result, EfiErrorKind};
+
+pub const OS_TYPE_EMPTY: u8 = 0x00;
+pub const OS_TYPE_FAT12: u8 = 0x01;
+pub const OS_TYPE_FAT16: u8 = 0x06;
+pub const OS_TYPE_NTFS: u8 = 0x07;
+pub const OS_TYPE_FAT32_LBA: u8 = 0x0C;
+pub const OS_TYPE_FAT16_LBA: u8 = 0x0E;
+pub const OS_TYPE_EXTENDED: u8 = 0x05;
+pub const OS_TYPE_EXTENDED_LBA: u8 = 0x0F;
+pub const OS_TYPE_LINUX: u8 = 0x83;
+pub const OS_TYPE_LINUX_EXTENDED: u8 = 0x85;
+pub const OS_TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+pub const OS_TYPE_EFI_SYSTEM: u8 = 0xEF;
+
+const MBR_SIZE: usize = 512;
+const BOOT_CODE_SIZE: usize = 440;
+const DISK_SIGNATURE_OFFSET: usize = 440;
+const PARTITION_RECORD_OFFSET: usize = 446;
+const PARTITION_RECORD_SIZE: usize = 16;
+const BOOT_INDICATOR_ACTIVE: u8 = 0x80;
+// Guards against EBR chains that loop back on themselves
+const MAX_LOGICAL_PARTITIONS: usize = 128;
+// CHS address used for partitions beyond what CHS can express. We always use it and rely on LBA addressing.
+const CHS_UNUSED: [u8; 3] = [0xFE, 0xFF, 0xFF];
+
+/// A partition record. Addresses are absolute LBAs even for logical partitions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Partition {
+    /// Whether this is the active partition legacy BIOS boots from
+    pub bootable: bool,
+    /// One of the `OS_TYPE_*` constants or any other partition type byte
+    pub os_type: u8,
+    pub first_lba: u64,
+    pub block_count: u64,
+}
+
+impl Partition {
+    /// Last block of the partition (inclusive)
+    pub fn last_lba(&self) -> u64 {
+        self.first_lba + self.block_count - 1
+    }
+
+    /// Whether this is an extended partition containing logical partitions
+    pub fn is_extended(&self) -> bool {
+        is_extended_type(self.os_type)
+    }
+
+    fn contains(&self, other: &Partition) -> bool {
+        other.first_lba >= self.first_lba && other.last_lba() <= self.last_lba()
+    }
+
+    fn overlaps(&self, other: &Partition) -> bool {
+        self.first_lba <= other.last_lba() && other.first_lba <= self.last_lba()
+    }
+}
+
+/// A partition inside the extended partition along with the Extended Boot Record describing it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LogicalPartition {
+    /// Block holding the EBR. Must lie inside the extended partition and before `partition.first_lba`.
+    pub ebr_lba: u64,
+    pub partition: Partition,
+}
+
+impl LogicalPartition {
+    // The record pointing to this partition's EBR from the previous EBR in the chain
+    fn link(&self) -> Partition {
+        Partition {
+            bootable: false,
+            os_type: OS_TYPE_EXTENDED,
+            first_lba: self.ebr_lba,
+            block_count: self.partition.last_lba() - self.ebr_lba + 1,
+        }
+    }
+}
+
+/// The MBR partition table of a disk.
+/// Changes are made in memory and only reach the disk on `write()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionTable {
+    boot_code: Vec<u8>,
+    disk_signature: u32,
+    primary: [Option<Partition>; 4],
+    logical: Vec<LogicalPartition>,
+}
+
+impl PartitionTable {
+    /// Creates an empty partition table with no boot code
+    pub fn new(disk_signature: u32) -> Self {
+        PartitionTable { boot_code: vec![0; BOOT_CODE_SIZE], disk_signature, primary: [None; 4], logical: Vec::new() }
+    }
+
+    /// Reads the MBR and follows the EBR chain of the extended partition if there is one.
+    /// Fails with `NotFound` if the first block doesn't look like an MBR.
+    pub fn read(device: &mut BlockDevice) -> Result<Self> {
+        let block_size = device.media().block_size;
+        Self::read_with(block_size, |lba, buf| device.read_blocks(lba, buf))
+    }
+
+    fn read_with<F: FnMut(u64, &mut [u8]) -> Result<()>>(block_size: u32, mut read_blocks: F) -> Result<Self> {
+        let block_size = block_size as usize;
+        if block_size < MBR_SIZE {
+            return Err(EfiErrorKind::Unsupported.into());
+        }
+
+        let mut block = vec![0_u8; block_size];
+        read_blocks(0, &mut block)?;
+        let records = parse_records(&block).ok_or(EfiErrorKind::NotFound)?;
+        let boot_code = block[..BOOT_CODE_SIZE].to_vec();
+        let disk_signature = LittleEndian::read_u32(&block[DISK_SIGNATURE_OFFSET..DISK_SIGNATURE_OFFSET + 4]);
+
+        let mut primary = [None; 4];
+        for (i, record) in records.iter().enumerate() {
+            primary[i] = record.map(|r| r.to_partition(0));
+        }
+
+        let mut logical = Vec::new();
+        if let Some(extended) = primary.iter().filter_map(|p| *p).find(|p| p.is_extended()) {
+            let mut ebr_lba = extended.first_lba;
+            loop {
+                if logical.len() == MAX_LOGICAL_PARTITIONS {
+                    return Err(EfiErrorKind::VolumeCorrupted.into());
+                }
+
+                read_blocks(ebr_lba, &mut block)?;
+                let records = parse_records(&block).ok_or(EfiErrorKind::VolumeCorrupted)?;
+
+                // In an EBR the first record is relative to the EBR and the second relative to the extended partition
+                if let Some(record) = records[0] {
+                    let partition = record.to_partition(ebr_lba);
+                    if !extended.contains(&partition) {
+                        return Err(EfiErrorKind::VolumeCorrupted.into());
+                    }
+                    logical.push(LogicalPartition { ebr_lba, partition });
+                }
+
+                match records[1] {
+                    Some(next) if is_extended_type(next.os_type) => {
+                        let next_lba = extended.first_lba + next.relative_lba as u64;
+                        if next_lba <= ebr_lba || next_lba > extended.last_lba() {
+                            return Err(EfiErrorKind::VolumeCorrupted.into());
+                        }
+                        ebr_lba = next_lba;
+                    },
+                    _ => break
+                }
+            }
+        }
+
+        Ok(PartitionTable {
+            boot_code,
+            disk_signature,
+            primary,
+            logical,
+        })
+    }
+
+    /// Writes the MBR and the EBR chain to the given device keeping the existing boot code
+    pub fn write(&self, device: &mut BlockDevice) -> Result<()> {
+        let block_size = device.media().block_size as usize;
+        for (lba, block) in self.to_blocks(block_size)? {
+            device.write_blocks(lba, &block)?;
+        }
+
+        device.flush()
+    }
+
+    // The blocks making up the table. EBRs come before the MBR so that the MBR is the last thing to change.
+    fn to_blocks(&self, block_size: usize) -> Result<Vec<(u64, Vec<u8>)>> {
+        if block_size < MBR_SIZE {
+            return Err(EfiErrorKind::Unsupported.into());
+        }
+
+        self.validate()?;
+
+        let mut blocks = Vec::new();
+        if let Some((_, extended)) = self.extended() {
+            if self.logical.is_empty() {
+                // An EBR with no records so the firmware and OSes see an empty extended partition
+                blocks.push((extended.first_lba, empty_block(block_size)));
+            }
+
+            for (i, logical) in self.logical.iter().enumerate() {
+                let mut block = empty_block(block_size);
+                write_record(&mut block, 0, &logical.partition, logical.ebr_lba)?;
+                if let Some(next) = self.logical.get(i + 1) {
+                    write_record(&mut block, 1, &next.link(), extended.first_lba)?;
+                }
+                blocks.push((logical.ebr_lba, block));
+            }
+
+            // The chain has to start at the beginning of the extended partition. If the first logical partition's EBR
+            // is further in, we put an EBR without a partition of its own at the start to link to it.
+            if let Some(first) = self.logical.first() {
+                if first.ebr_lba != extended.first_lba {
+                    let mut block = empty_block(block_size);
+                    write_record(&mut block, 1, &first.link(), extended.first_lba)?;
+                    blocks.push((extended.first_lba, block));
+                }
+            }
+        }
+
+        let mut block = empty_block(block_size);
+        block[..BOOT_CODE_SIZE].copy_from_slice(&self.boot_code);
+        LittleEndian::write_u32(&mut block[DISK_SIGNATURE_OFFSET..DISK_SIGNATURE_OFFSET + 4], self.disk_signature);
+        for (i, partition) in self.primary.iter().enumerate() {
+            if let Some(ref partition) = *partition {
+                write_record(&mut block, i, partition, 0)?;
+            }
+        }
+        blocks.push((0, block));
+
+        Ok(blocks)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let primaries = self.primary.iter().filter_map(|p| *p).collect::<Vec<_>>();
+        for (i, p) in primaries.iter().enumerate() {
+            if p.os_type == OS_TYPE_EMPTY || p.block_count == 0 || primaries[i + 1..].iter().any(|q| p.overlaps(q)) {
+                return Err(EfiErrorKind::InvalidParameter.into());
+            }
+        }
+
+        if primaries.iter().filter(|p| p.is_extended()).count() > 1 {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let extended = match self.extended() {
+            Some((_, extended)) => extended,
+            None if self.logical.is_empty() => return Ok(()),
+            None => return Err(EfiErrorKind::InvalidParameter.into()),
+        };
+
+        let mut next_free_lba = extended.first_lba;
+        for logical in &self.logical {
+            let partition = &logical.partition;
+            if partition.os_type == OS_TYPE_EMPTY
+                || partition.is_extended()
+                || partition.block_count == 0
+                || logical.ebr_lba < next_free_lba
+                || logical.ebr_lba >= partition.first_lba
+                || !extended.contains(partition) {
+                return Err(EfiErrorKind::InvalidParameter.into());
+            }
+            next_free_lba = partition.last_lba() + 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn disk_signature(&self) -> u32 {
+        self.disk_signature
+    }
+
+    pub fn set_disk_signature(&mut self, disk_signature: u32) {
+        self.disk_signature = disk_signature;
+    }
+
+    /// The four primary partition records. Unused records are `None`.
+    pub fn primary(&self) -> &[Option<Partition>; 4] {
+        &self.primary
+    }
+
+    /// Replaces the given primary record. Consistency is checked on `write()`.
+    pub fn set_primary(&mut self, index: usize, partition: Option<Partition>) -> Result<()> {
+        if index >= self.primary.len() {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        self.primary[index] = partition;
+        Ok(())
+    }
+
+    /// Logical partitions in the order of the EBR chain
+    pub fn logical(&self) -> &[LogicalPartition] {
+        &self.logical
+    }
+
+    /// Logical partitions for editing. They must stay in ascending order of position on the disk.
+    /// Consistency is checked on `write()`.
+    pub fn logical_mut(&mut self) -> &mut Vec<LogicalPartition> {
+        &mut self.logical
+    }
+
+    /// The extended partition and the index of its record if there is one
+    pub fn extended(&self) -> Option<(usize, Partition)> {
+        self.primary.iter()
+            .enumerate()
+            .filter_map(|(i, p)| p.map(|p| (i, p)))
+            .find(|&(_, p)| p.is_extended())
+    }
+
+    /// The partition marked active for legacy BIOS boot
+    pub fn active(&self) -> Option<Partition> {
+        self.partitions().into_iter().find(|p| p.bootable)
+    }
+
+    /// The EFI system partition i.e. the first partition of type 0xEF
+    pub fn esp(&self) -> Option<Partition> {
+        self.partitions().into_iter().find(|p| p.os_type == OS_TYPE_EFI_SYSTEM)
+    }
+
+    /// Whether this is the protective MBR of a GPT disk in which case the partitions are in the GPT instead
+    pub fn is_protective(&self) -> bool {
+        self.primary.iter().any(|p| p.map_or(false, |p| p.os_type == OS_TYPE_GPT_PROTECTIVE))
+    }
+
+    /// All primary and logical partitions except the extended partition itself
+    pub fn partitions(&self) -> Vec<Partition> {
+        self.primary.iter()
+            .filter_map(|p| *p)
+            .filter(|p| !p.is_extended())
+            .chain(self.logical.iter().map(|l| l.partition))
+            .collect()
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Record {
+    boot_indicator: u8,
+    os_type: u8,
+    relative_lba: u32,
+    block_count: u32,
+}
+
+impl Record {
+    fn to_partition(&self, base_lba: u64) -> Partition {
+        Partition {
+            bootable: self.boot_indicator == BOOT_INDICATOR_ACTIVE,
+            os_type: self.os_type,
+            first_lba: base_lba + self.relative_lba as u64,
+            block_count: self.block_count as u64,
+        }
+    }
+}
+
+fn is_extended_type(os_type: u8) -> bool {
+    os_type == OS_TYPE_EXTENDED || os_type == OS_TYPE_EXTENDED_LBA || os_type == OS_TYPE_LINUX_EXTENDED
+}
+
+// Returns None if the block doesn't carry a valid boot record signature or has malformed records
+fn parse_records(block: &[u8]) -> Option<[Option<Record>; 4]> {
+    if block[510] != 0x55 || block[511] != 0xAA {
+        return None;
+    }
+
+    let mut records = [None; 4];
+    for (i, bytes) in block[PARTITION_RECORD_OFFSET..PARTITION_RECORD_OFFSET + 4 * PARTITION_RECORD_SIZE].chunks(PARTITION_RECORD_SIZE).enumerate() {
+        let boot_indicator = bytes[0];
+        if boot_indicator != 0 && boot_indicator != BOOT_INDICATOR_ACTIVE {
+            return None;
+        }
+
+        let record = Record {
+            boot_indicator,
+            os_type: bytes[4],
+            relative_lba: LittleEndian::read_u32(&bytes[8..12]),
+            block_count: LittleEndian::read_u32(&bytes[12..16]),
+        };
+        if record.os_type != OS_TYPE_EMPTY && record.block_count != 0 {
+            records[i] = Some(record);
+        }
+    }
+
+    Some(records)
+}
+
+fn write_record(block: &mut [u8], index: usize, partition: &Partition, base_lba: u64) -> Result<()> {
+    let relative_lba = partition.first_lba - base_lba;
+    if relative_lba > u32::max_value() as u64 || partition.block_count > u32::max_value() as u64 {
+        return Err(EfiErrorKind::InvalidParameter.into());
+    }
+
+    let offset = PARTITION_RECORD_OFFSET + index * PARTITION_RECORD_SIZE;
+    let record = &mut block[offset..offset + PARTITION_RECORD_SIZE];
+    record[0] = if partition.bootable { BOOT_INDICATOR_ACTIVE } else { 0 };
+    record[1..4].copy_from_slice(&CHS_UNUSED);
+    record[4] = partition.os_type;
+    record[5..8].copy_from_slice(&CHS_UNUSED);
+    LittleEndian::write_u32(&mut record[8..12], relative_lba as u32);
+    LittleEndian::write_u32(&mut record[12..16], partition.block_count as u32);
+    Ok(())
+}
+
+fn empty_block(block_size: usize) -> Vec<u8> {
+    let mut block = vec![0_u8; block_size];
+    block[510] = 0x55;
+    block[511] = 0xAA;
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_SIZE: usize = 512;
+    const NO_OF_BLOCKS: usize = 4096;
+
+    fn partition(os_type: u8, first_lba: u64, block_count: u64) -> Partition {
+        Partition { bootable: false, os_type, first_lba, block_count }
+    }
+
+    fn sample_table() -> PartitionTable {
+        let mut table = PartitionTable::new(0xDEADBEEF);
+        table.set_primary(0, Some(Partition { bootable: true, ..partition(OS_TYPE_EFI_SYSTEM, 2048, 1024) })).unwrap();
+        table.set_primary(1, Some(partition(OS_TYPE_EXTENDED_LBA, 3072, 1024))).unwrap();
+        table.logical_mut().push(LogicalPartition { ebr_lba: 3072, partition: partition(OS_TYPE_LINUX, 3080, 500) });
+        table.logical_mut().push(LogicalPartition { ebr_lba: 3600, partition: partition(OS_TYPE_NTFS, 3608, 488) });
+        table
+    }
+
+    fn write_to_disk(table: &PartitionTable) -> Vec<u8> {
+        let mut disk = vec![0_u8; NO_OF_BLOCKS * BLOCK_SIZE];
+        for (lba, block) in table.to_blocks(BLOCK_SIZE).unwrap() {
+            let start = lba as usize * BLOCK_SIZE;
+            disk[start..start + block.len()].copy_from_slice(&block);
+        }
+        disk
+    }
+
+    fn read_from_disk(disk: &[u8]) -> Result<PartitionTable> {
+        PartitionTable::read_with(BLOCK_SIZE as u32, |lba, buf| {
+            let start = lba as usize * BLOCK_SIZE;
+            let end = start + buf.len();
+            buf.copy_from_slice(&disk[start..end]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn table_round_trips_through_disk() {
+        let table = sample_table();
+        let read = read_from_disk(&write_to_disk(&table)).unwrap();
+        assert_eq!(read, table);
+        assert_eq!(read.partitions().len(), 3);
+        assert_eq!(read.esp().unwrap().first_lba, 2048);
+        assert_eq!(read.active().unwrap().first_lba, 2048);
+        assert_eq!(read.extended().unwrap().0, 1);
+        assert!(!read.is_protective());
+    }
+
+    #[test]
+    fn chain_can_start_past_first_ebr() {
+        let mut table = sample_table();
+        table.logical_mut().remove(0);
+        let read = read_from_disk(&write_to_disk(&table)).unwrap();
+        assert_eq!(read.logical(), table.logical());
+    }
+
+    #[test]
+    fn rejects_looping_ebr_chain() {
+        let mut disk = write_to_disk(&sample_table());
+        // Pointing the second EBR's link back at the first one
+        let link = 3600 * BLOCK_SIZE + PARTITION_RECORD_OFFSET + PARTITION_RECORD_SIZE;
+        disk[link + 4] = OS_TYPE_EXTENDED;
+        LittleEndian::write_u32(&mut disk[link + 8..link + 12], 0);
+        LittleEndian::write_u32(&mut disk[link + 12..link + 16], 1);
+        assert_eq!(read_from_disk(&disk).unwrap_err().kind(), EfiErrorKind::VolumeCorrupted);
+    }
+
+    #[test]
+    fn rejects_blank_disk() {
+        let disk = vec![0_u8; NO_OF_BLOCKS * BLOCK_SIZE];
+        assert_eq!(read_from_disk(&disk).unwrap_err().kind(), EfiErrorKind::NotFound);
+    }
+
+    #[test]
+    fn rejects_inconsistent_tables() {
+        let mut table = sample_table();
+        table.set_primary(2, Some(partition(OS_TYPE_LINUX, 2500, 10))).unwrap();
+        assert_eq!(table.to_blocks(BLOCK_SIZE).unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+
+        let mut table = sample_table();
+        table.logical_mut()[1].partition.block_count = 1000;
+        assert_eq!(table.to_blocks(BLOCK_SIZE).unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+
+        let mut table = sample_table();
+        table.set_primary(1, None).unwrap();
+        assert_eq!(table.to_blocks(BLOCK_SIZE).unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+    }
+}
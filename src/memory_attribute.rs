@@ -0,0 +1,107 @@
+use ffi::{
+    memory_attribute::*,
+    boot_services::{EFI_MEMORY_RP, EFI_MEMORY_XP, EFI_MEMORY_RO, EFI_PAGE_SIZE},
+};
+use utils::locate_protocol;
+use {Result, EfiErrorKind};
+
+// Page protections through EFI_MEMORY_ATTRIBUTE_PROTOCOL. Firmware that enforces memory protections
+// (NX for data, no writable code) hands out loader data that can't be executed, so a loader placing a
+// kernel or trampoline in memory it allocated has to make those pages executable itself.
+
+flags! {
+    /// A set of memory protection attributes
+    pub struct MemoryAttributes(u64) {
+        /// Reads are not allowed
+        const READ_PROTECT = EFI_MEMORY_RP;
+        /// Execution is not allowed
+        const EXECUTE_PROTECT = EFI_MEMORY_XP;
+        /// Writes are not allowed
+        const READ_ONLY = EFI_MEMORY_RO;
+    }
+}
+
+/// The firmware's memory attribute service.
+/// All ranges passed to it must start on a page boundary and be a non-zero multiple of the page size
+/// long, otherwise the calls fail with `InvalidParameter`.
+pub struct MemoryAttribute {
+    protocol: *const EFI_MEMORY_ATTRIBUTE_PROTOCOL,
+}
+
+impl MemoryAttribute {
+    /// Fails with `NotFound` if the firmware doesn't have the protocol, which usually means it
+    /// doesn't enforce memory protections either
+    pub fn get() -> Result<Self> {
+        Ok(MemoryAttribute { protocol: locate_protocol(&EFI_MEMORY_ATTRIBUTE_PROTOCOL_GUID)? })
+    }
+
+    /// The attributes of the range. Fails with `NoMapping` if the pages don't all have the same attributes.
+    pub fn attributes(&self, base: u64, length: u64) -> Result<MemoryAttributes> {
+        check_range(base, length)?;
+        let mut attributes = 0;
+        unsafe {
+            ret_on_err!(((*self.protocol).GetMemoryAttributes)(self.protocol, base, length, &mut attributes));
+        }
+
+        Ok(MemoryAttributes(attributes))
+    }
+
+    /// Adds the given attributes to the range, leaving the others as they are
+    pub fn set(&self, base: u64, length: u64, attributes: MemoryAttributes) -> Result<()> {
+        check_range(base, length)?;
+        unsafe {
+            ret_on_err!(((*self.protocol).SetMemoryAttributes)(self.protocol, base, length, attributes.bits()));
+        }
+
+        Ok(())
+    }
+
+    /// Removes the given attributes from the range, leaving the others as they are
+    pub fn clear(&self, base: u64, length: u64, attributes: MemoryAttributes) -> Result<()> {
+        check_range(base, length)?;
+        unsafe {
+            ret_on_err!(((*self.protocol).ClearMemoryAttributes)(self.protocol, base, length, attributes.bits()));
+        }
+
+        Ok(())
+    }
+
+    /// Makes the range executable and read-only, e.g. for a loaded kernel's code
+    pub fn make_executable(&self, base: u64, length: u64) -> Result<()> {
+        self.set(base, length, MemoryAttributes::READ_ONLY)?;
+        self.clear(base, length, MemoryAttributes::EXECUTE_PROTECT)
+    }
+
+    /// Makes the range writable and not executable, e.g. for data buffers
+    pub fn make_data(&self, base: u64, length: u64) -> Result<()> {
+        self.set(base, length, MemoryAttributes::EXECUTE_PROTECT)?;
+        self.clear(base, length, MemoryAttributes::READ_ONLY)
+    }
+}
+
+fn check_range(base: u64, length: u64) -> Result<()> {
+    let page_size = EFI_PAGE_SIZE as u64;
+    if length == 0 || base % page_size != 0 || length % page_size != 0 || base.checked_add(length).is_none() {
+        return Err(EfiErrorKind::InvalidParameter.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_must_be_whole_pages() {
+        assert!(check_range(0x10000, 0x2000).is_ok());
+        assert!(check_range(0x10000, 0).is_err());
+        assert!(check_range(0x10800, 0x1000).is_err());
+        assert!(check_range(0x10000, 0x800).is_err());
+        assert!(check_range(0xFFFF_FFFF_FFFF_F000, 0x2000).is_err());
+
+        let attributes = MemoryAttributes::READ_ONLY | MemoryAttributes::EXECUTE_PROTECT;
+        assert!(attributes.contains(MemoryAttributes::READ_ONLY));
+        assert!(!attributes.contains(MemoryAttributes::READ_PROTECT));
+    }
+}
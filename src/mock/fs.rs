@@ -0,0 +1,665 @@
+//! In-memory volumes served through EFI_SIMPLE_FILE_SYSTEM_PROTOCOL.
+//!
+//! Paths are matched case-insensitively like on FAT. Files created by the code under test can be inspected with
+//! `MockVolume::contents()` and files it expects to find can be put in place beforehand with `MockVolume::add_file()`.
+
+use ffi::{
+    EFI_STATUS,
+    EFI_SUCCESS,
+    EFI_UNSUPPORTED,
+    EFI_INVALID_PARAMETER,
+    EFI_NOT_FOUND,
+    EFI_BUFFER_TOO_SMALL,
+    EFI_WRITE_PROTECTED,
+    EFI_ACCESS_DENIED,
+    EFI_DEVICE_ERROR,
+    EFI_WARN_DELETE_FAILURE,
+    EFI_HANDLE,
+    EFI_GUID,
+    CHAR16,
+    UINT64,
+    UINTN,
+    VOID,
+    media::{
+        EFI_SIMPLE_FILE_SYSTEM_PROTOCOL,
+        EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID,
+        EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_REVISION,
+        EFI_FILE_PROTOCOL,
+        EFI_FILE_PROTOCOL_REVISION,
+        EFI_FILE_MODE_WRITE,
+        EFI_FILE_MODE_CREATE,
+        EFI_FILE_READ_ONLY,
+        EFI_FILE_DIRECTORY,
+        EFI_FILE_INFO_ID,
+        EFI_FILE_SYSTEM_INFO_ID,
+        EFI_FILE_SYSTEM_VOLUME_LABEL_ID,
+    },
+};
+use fs::{Metadata, FileAttributes};
+use ucs2::CStr16;
+use time::DateTime;
+use utils::to_null_terminated_utf16;
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec, boxed::Box};
+use core::{ptr, slice, cmp};
+use super::{MockEnv, with_state, unsupported};
+
+const BLOCK_SIZE: u64 = 512;
+const VOLUME_SIZE: u64 = 64 * 1024 * 1024;
+const END_OF_FILE: u64 = 0xFFFFFFFFFFFFFFFF;
+
+// Offsets in EFI_FILE_SYSTEM_INFO
+const FILE_SYSTEM_INFO_READ_ONLY_OFFSET: usize = 8;
+const FILE_SYSTEM_INFO_LABEL_OFFSET: usize = 36;
+
+const ROOT: usize = 0;
+
+struct Node {
+    name: String,
+    parent: Option<usize>, // None for the root and for deleted nodes
+    data: Vec<u8>,
+    attributes: UINT64,
+    created: DateTime,
+    modified: DateTime,
+}
+
+impl Node {
+    fn is_dir(&self) -> bool {
+        self.attributes & EFI_FILE_DIRECTORY != 0
+    }
+}
+
+// The protocol is the first field so that the pointer the firmware hands out can be cast back to the whole struct
+#[repr(C)]
+struct MockFileSystem {
+    protocol: EFI_SIMPLE_FILE_SYSTEM_PROTOCOL,
+    volume: usize,
+}
+
+#[repr(C)]
+struct MockFile {
+    protocol: EFI_FILE_PROTOCOL,
+    volume: usize,
+    node: usize,
+    position: u64,
+    writable: bool,
+}
+
+struct Volume {
+    label: String,
+    read_only: bool,
+    nodes: Vec<Node>,
+    protocol: Box<MockFileSystem>,
+}
+
+impl Volume {
+    fn children<'a>(&'a self, dir: usize) -> impl Iterator<Item = usize> + 'a {
+        (0..self.nodes.len()).filter(move |&i| self.nodes[i].parent == Some(dir))
+    }
+
+    fn child(&self, dir: usize, name: &str) -> Option<usize> {
+        self.children(dir).find(|&i| self.nodes[i].name.eq_ignore_ascii_case(name))
+    }
+
+    // Resolves a path relative to `dir` (or to the root if it starts with '\') to the parent directory and
+    // the last component. A path that ends in a separator or names the root has an empty last component.
+    fn split<'p>(&self, dir: usize, path: &'p str) -> Option<(usize, &'p str)> {
+        let (mut current, path) = if path.starts_with('\\') { (ROOT, &path[1..]) } else { (dir, path) };
+        let mut components = path.split('\\').filter(|c| !c.is_empty()).peekable();
+        while let Some(component) = components.next() {
+            if components.peek().is_none() {
+                return match component {
+                    "." => Some((current, "")),
+                    ".." => self.nodes[current].parent.map(|p| (p, "")),
+                    _ => Some((current, component)),
+                };
+            }
+
+            current = match component {
+                "." => current,
+                ".." => self.nodes[current].parent?,
+                _ => match self.child(current, component) {
+                    Some(c) if self.nodes[c].is_dir() => c,
+                    _ => return None,
+                },
+            };
+        }
+
+        Some((current, ""))
+    }
+
+    fn find(&self, dir: usize, path: &str) -> Option<usize> {
+        let (parent, name) = self.split(dir, path)?;
+        if name.is_empty() { Some(parent) } else { self.child(parent, name) }
+    }
+
+    fn create(&mut self, parent: usize, name: &str, attributes: UINT64, now: DateTime) -> usize {
+        self.nodes.push(Node { name: name.into(), parent: Some(parent), data: Vec::new(), attributes, created: now, modified: now });
+        self.nodes.len() - 1
+    }
+
+    fn used_space(&self) -> u64 {
+        self.nodes.iter().filter(|n| n.parent.is_some()).map(|n| physical_size(n.data.len() as u64)).sum()
+    }
+
+    fn metadata(&self, node: usize) -> Metadata {
+        let node = &self.nodes[node];
+        Metadata {
+            file_name: node.name.clone(),
+            size: node.data.len() as u64,
+            physical_size: physical_size(node.data.len() as u64),
+            created: node.created,
+            accessed: node.modified,
+            modified: node.modified,
+            attributes: FileAttributes::from_bits(node.attributes),
+        }
+    }
+
+    // EFI_FILE_SYSTEM_INFO
+    fn info(&self) -> Vec<u8> {
+        let label = utf16_bytes(&self.label);
+        let mut bytes = vec![0_u8; FILE_SYSTEM_INFO_LABEL_OFFSET + label.len()];
+        let total_size = bytes.len() as u64;
+        LittleEndian::write_u64(&mut bytes[0..8], total_size);
+        bytes[FILE_SYSTEM_INFO_READ_ONLY_OFFSET] = self.read_only as u8;
+        LittleEndian::write_u64(&mut bytes[16..24], VOLUME_SIZE);
+        LittleEndian::write_u64(&mut bytes[24..32], VOLUME_SIZE.saturating_sub(self.used_space()));
+        LittleEndian::write_u32(&mut bytes[32..36], BLOCK_SIZE as u32);
+        bytes[FILE_SYSTEM_INFO_LABEL_OFFSET..].copy_from_slice(&label);
+        bytes
+    }
+}
+
+fn physical_size(size: u64) -> u64 {
+    (size + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE
+}
+
+fn utf16_bytes(s: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for c in to_null_terminated_utf16(s) {
+        let mut buf = [0_u8; 2];
+        LittleEndian::write_u16(&mut buf, c);
+        bytes.extend_from_slice(&buf);
+    }
+    bytes
+}
+
+fn string_from_utf16_bytes(bytes: &[u8]) -> String {
+    let chars = bytes.chunks(2)
+        .filter(|c| c.len() == 2)
+        .map(|c| LittleEndian::read_u16(c))
+        .take_while(|c| *c != 0)
+        .collect::<Vec<u16>>();
+    String::from_utf16_lossy(&chars)
+}
+
+pub(super) struct State {
+    volumes: Vec<Volume>,
+}
+
+impl State {
+    pub(super) fn new() -> Self {
+        State { volumes: Vec::new() }
+    }
+}
+
+/// A volume added with `MockEnv::add_volume()`
+pub struct MockVolume {
+    index: usize,
+    handle: EFI_HANDLE,
+}
+
+impl MockEnv {
+    /// Adds an empty volume. The first volume added is also the one the current image was loaded from
+    /// so plain paths such as `\EFI\app.cfg` passed to `fs::read()` refer to it.
+    pub fn add_volume(&self, label: &str) -> MockVolume {
+        with_state(|s| {
+            let index = s.fs.volumes.len();
+            let protocol = Box::new(MockFileSystem {
+                protocol: EFI_SIMPLE_FILE_SYSTEM_PROTOCOL { Revision: EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_REVISION, OpenVolume: open_volume },
+                volume: index,
+            });
+
+            let handle = s.install(None, EFI_SIMPLE_FILE_SYSTEM_PROTOCOL_GUID, &*protocol as *const MockFileSystem as *const VOID);
+            let epoch = DateTime::new(2000, 1, 1, 0, 0, 0);
+            let root = Node { name: String::new(), parent: None, data: Vec::new(), attributes: EFI_FILE_DIRECTORY, created: epoch, modified: epoch };
+            s.fs.volumes.push(Volume { label: label.into(), read_only: false, nodes: vec![root], protocol });
+
+            if s.loaded_image.DeviceHandle.is_null() {
+                s.loaded_image.DeviceHandle = handle;
+            }
+
+            MockVolume { index, handle }
+        })
+    }
+}
+
+impl MockVolume {
+    /// The handle that EFI_SIMPLE_FILE_SYSTEM_PROTOCOL is installed on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// Creates or replaces a file along with any missing parent directories
+    pub fn add_file(&self, path: &str, contents: &[u8]) {
+        self.with_volume(|v, now| {
+            let path = path.replace('/', "\\");
+            let (dir, name) = match path.rfind('\\') {
+                Some(i) => (make_dirs(v, &path[..i], now), &path[i + 1..]),
+                None => (ROOT, &path[..]),
+            };
+
+            let node = match v.child(dir, name) {
+                Some(node) => node,
+                None => v.create(dir, name, 0, now),
+            };
+            v.nodes[node].data = contents.to_vec();
+        })
+    }
+
+    /// Creates a directory along with any missing parents
+    pub fn add_dir(&self, path: &str) {
+        self.with_volume(|v, now| { make_dirs(v, &path.replace('/', "\\"), now); })
+    }
+
+    /// The contents of a file or `None` if there is no such file
+    pub fn contents(&self, path: &str) -> Option<Vec<u8>> {
+        self.with_volume(|v, _| {
+            match v.find(ROOT, &path.replace('/', "\\")) {
+                Some(n) if !v.nodes[n].is_dir() => Some(v.nodes[n].data.clone()),
+                _ => None,
+            }
+        })
+    }
+
+    /// Whether a file or directory exists at the path
+    pub fn exists(&self, path: &str) -> bool {
+        self.with_volume(|v, _| v.find(ROOT, &path.replace('/', "\\")).is_some())
+    }
+
+    /// Makes the volume write protected as if the media were
+    pub fn set_read_only(&self, read_only: bool) {
+        self.with_volume(|v, _| v.read_only = read_only)
+    }
+
+    pub fn label(&self) -> String {
+        self.with_volume(|v, _| v.label.clone())
+    }
+
+    fn with_volume<R, F: FnOnce(&mut Volume, DateTime) -> R>(&self, f: F) -> R {
+        with_state(|s| {
+            let now = s.runtime.time();
+            f(&mut s.fs.volumes[self.index], now)
+        })
+    }
+}
+
+fn make_dirs(v: &mut Volume, path: &str, now: DateTime) -> usize {
+    let mut dir = ROOT;
+    for component in path.split('\\').filter(|c| !c.is_empty()) {
+        dir = match v.child(dir, component) {
+            Some(c) => c,
+            None => v.create(dir, component, EFI_FILE_DIRECTORY, now),
+        };
+    }
+    dir
+}
+
+fn new_file(volume: usize, node: usize, writable: bool) -> *const EFI_FILE_PROTOCOL {
+    let file = Box::new(MockFile {
+        protocol: EFI_FILE_PROTOCOL {
+            Revision: EFI_FILE_PROTOCOL_REVISION,
+            Open: open,
+            Close: close,
+            Delete: delete,
+            Read: read,
+            Write: write,
+            GetPosition: get_position,
+            SetPosition: set_position,
+            GetInfo: get_info,
+            SetInfo: set_info,
+            Flush: flush,
+            OpenEx: stub!(unsupported),
+            ReadEx: stub!(unsupported),
+            WriteEx: stub!(unsupported),
+            FlushEx: stub!(unsupported),
+        },
+        volume,
+        node,
+        position: 0,
+        writable,
+    });
+
+    Box::into_raw(file) as *const EFI_FILE_PROTOCOL
+}
+
+fn file<'a>(this: *const EFI_FILE_PROTOCOL) -> &'a mut MockFile {
+    unsafe { &mut *(this as *mut MockFile) }
+}
+
+// Runs the closure with the volume and the node of the file unless the file has been deleted under it
+fn with_file<F: FnOnce(&mut MockFile, &mut Volume, DateTime) -> EFI_STATUS>(this: *const EFI_FILE_PROTOCOL, f: F) -> EFI_STATUS {
+    let file = file(this);
+    with_state(|s| {
+        let now = s.runtime.time();
+        let volume = &mut s.fs.volumes[file.volume];
+        if file.node != ROOT && volume.nodes[file.node].parent.is_none() {
+            return EFI_DEVICE_ERROR;
+        }
+        f(file, volume, now)
+    })
+}
+
+extern "win64" fn open_volume(this: *const EFI_SIMPLE_FILE_SYSTEM_PROTOCOL, root: *mut *const EFI_FILE_PROTOCOL) -> EFI_STATUS {
+    let volume = unsafe { (*(this as *const MockFileSystem)).volume };
+    unsafe { *root = new_file(volume, ROOT, true); }
+    EFI_SUCCESS
+}
+
+extern "win64" fn open(this: *mut EFI_FILE_PROTOCOL, new_handle: *mut *const EFI_FILE_PROTOCOL, file_name: *const CHAR16, open_mode: UINT64, attributes: UINT64) -> EFI_STATUS {
+    if new_handle.is_null() || file_name.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let path = unsafe { CStr16::from_ptr(file_name) }.to_string_lossy();
+    let writable = open_mode & EFI_FILE_MODE_WRITE != 0;
+    let create = open_mode & EFI_FILE_MODE_CREATE != 0;
+    with_file(this, |file, volume, now| {
+        if writable && volume.read_only {
+            return EFI_WRITE_PROTECTED;
+        }
+
+        let (parent, name) = match volume.split(file.node, &path) {
+            Some(split) => split,
+            None => return EFI_NOT_FOUND,
+        };
+
+        let node = match if name.is_empty() { Some(parent) } else { volume.child(parent, name) } {
+            Some(node) => {
+                if writable && volume.nodes[node].attributes & EFI_FILE_READ_ONLY != 0 {
+                    return EFI_ACCESS_DENIED;
+                }
+                node
+            },
+            None if create => volume.create(parent, name, attributes, now),
+            None => return EFI_NOT_FOUND,
+        };
+
+        unsafe { *new_handle = new_file(file.volume, node, writable); }
+        EFI_SUCCESS
+    })
+}
+
+extern "win64" fn close(this: *mut EFI_FILE_PROTOCOL) -> EFI_STATUS {
+    unsafe { drop(Box::from_raw(this as *mut MockFile)); }
+    EFI_SUCCESS
+}
+
+extern "win64" fn delete(this: *mut EFI_FILE_PROTOCOL) -> EFI_STATUS {
+    let status = with_file(this, |file, volume, _| {
+        let node = file.node;
+        if !file.writable || node == ROOT || volume.children(node).next().is_some() {
+            return EFI_WARN_DELETE_FAILURE;
+        }
+
+        volume.nodes[node].parent = None;
+        EFI_SUCCESS
+    });
+
+    close(this);
+    status
+}
+
+extern "win64" fn read(this: *mut EFI_FILE_PROTOCOL, buffer_size: *mut UINTN, buffer: *mut VOID) -> EFI_STATUS {
+    if buffer_size.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    with_file(this, |file, volume, _| {
+        let available = unsafe { *buffer_size };
+        let out = |bytes: &[u8]| unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), buffer as *mut u8, bytes.len());
+            *buffer_size = bytes.len();
+        };
+
+        if !volume.nodes[file.node].is_dir() {
+            let data = &volume.nodes[file.node].data;
+            let start = cmp::min(file.position, data.len() as u64) as usize;
+            let end = cmp::min(start + available, data.len());
+            out(&data[start..end]);
+            file.position += (end - start) as u64;
+            return EFI_SUCCESS;
+        }
+
+        // Reading a directory returns the EFI_FILE_INFO of its next entry. The position is the index of that entry.
+        let entry = volume.children(file.node).nth(file.position as usize);
+        match entry {
+            Some(entry) => {
+                let info = volume.metadata(entry).to_bytes();
+                if available < info.len() {
+                    unsafe { *buffer_size = info.len(); }
+                    return EFI_BUFFER_TOO_SMALL;
+                }
+                out(&info);
+                file.position += 1;
+            },
+            None => out(&[]),
+        }
+
+        EFI_SUCCESS
+    })
+}
+
+extern "win64" fn write(this: *mut EFI_FILE_PROTOCOL, buffer_size: *mut UINTN, buffer: *const VOID) -> EFI_STATUS {
+    if buffer_size.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let bytes = unsafe { slice::from_raw_parts(buffer as *const u8, *buffer_size) };
+    with_file(this, |file, volume, now| {
+        if volume.nodes[file.node].is_dir() {
+            return EFI_UNSUPPORTED;
+        }
+
+        if volume.read_only {
+            return EFI_WRITE_PROTECTED;
+        }
+
+        if !file.writable {
+            return EFI_ACCESS_DENIED;
+        }
+
+        let node = &mut volume.nodes[file.node];
+        let start = file.position as usize;
+        let end = start + bytes.len();
+        if node.data.len() < end {
+            node.data.resize(end, 0);
+        }
+
+        node.data[start..end].copy_from_slice(bytes);
+        node.modified = now;
+        file.position = end as u64;
+        EFI_SUCCESS
+    })
+}
+
+extern "win64" fn get_position(this: *const EFI_FILE_PROTOCOL, position: *mut UINT64) -> EFI_STATUS {
+    with_file(this, |file, volume, _| {
+        if volume.nodes[file.node].is_dir() {
+            return EFI_UNSUPPORTED;
+        }
+
+        unsafe { *position = file.position; }
+        EFI_SUCCESS
+    })
+}
+
+extern "win64" fn set_position(this: *mut EFI_FILE_PROTOCOL, position: UINT64) -> EFI_STATUS {
+    with_file(this, |file, volume, _| {
+        let node = &volume.nodes[file.node];
+        file.position = match position {
+            0 => 0,
+            _ if node.is_dir() => return EFI_UNSUPPORTED, // Directories can only be rewound
+            END_OF_FILE => node.data.len() as u64,
+            p => p,
+        };
+
+        EFI_SUCCESS
+    })
+}
+
+extern "win64" fn get_info(this: *const EFI_FILE_PROTOCOL, information_type: *const EFI_GUID, buffer_size: *mut UINTN, buffer: *mut VOID) -> EFI_STATUS {
+    if information_type.is_null() || buffer_size.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    with_file(this, |file, volume, _| {
+        let info = match unsafe { *information_type } {
+            EFI_FILE_INFO_ID => volume.metadata(file.node).to_bytes(),
+            EFI_FILE_SYSTEM_INFO_ID => volume.info(),
+            EFI_FILE_SYSTEM_VOLUME_LABEL_ID => utf16_bytes(&volume.label),
+            _ => return EFI_UNSUPPORTED,
+        };
+
+        unsafe {
+            let available = *buffer_size;
+            *buffer_size = info.len();
+            if available < info.len() {
+                return EFI_BUFFER_TOO_SMALL;
+            }
+            ptr::copy_nonoverlapping(info.as_ptr(), buffer as *mut u8, info.len());
+        }
+
+        EFI_SUCCESS
+    })
+}
+
+extern "win64" fn set_info(this: *mut EFI_FILE_PROTOCOL, information_type: *const EFI_GUID, buffer_size: UINTN, buffer: *const VOID) -> EFI_STATUS {
+    if information_type.is_null() || buffer.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let info = unsafe { slice::from_raw_parts(buffer as *const u8, buffer_size) };
+    with_file(this, |file, volume, now| {
+        if volume.read_only {
+            return EFI_WRITE_PROTECTED;
+        }
+
+        match unsafe { *information_type } {
+            EFI_FILE_INFO_ID => {},
+            EFI_FILE_SYSTEM_INFO_ID if info.len() >= FILE_SYSTEM_INFO_LABEL_OFFSET => {
+                volume.label = string_from_utf16_bytes(&info[FILE_SYSTEM_INFO_LABEL_OFFSET..]);
+                return EFI_SUCCESS;
+            },
+            EFI_FILE_SYSTEM_VOLUME_LABEL_ID => {
+                volume.label = string_from_utf16_bytes(info);
+                return EFI_SUCCESS;
+            },
+            _ => return EFI_UNSUPPORTED,
+        }
+
+        let metadata = match Metadata::parse(info) {
+            Ok(metadata) => metadata,
+            Err(_) => return EFI_INVALID_PARAMETER,
+        };
+
+        if !file.writable || file.node == ROOT {
+            return EFI_ACCESS_DENIED;
+        }
+
+        let node = file.node;
+        let is_dir = volume.nodes[node].is_dir();
+        if metadata.is_dir() != is_dir || (is_dir && metadata.size != 0 && metadata.size != volume.nodes[node].data.len() as u64) {
+            return EFI_ACCESS_DENIED;
+        }
+
+        // A name starting with '\' moves the file to that path from the root. Otherwise it is renamed in place.
+        if !metadata.file_name.eq_ignore_ascii_case(&volume.nodes[node].name) {
+            let parent = volume.nodes[node].parent.unwrap_or(ROOT);
+            let (new_parent, new_name) = match volume.split(parent, &metadata.file_name) {
+                Some((p, n)) if !n.is_empty() => (p, String::from(n)),
+                _ => return EFI_ACCESS_DENIED,
+            };
+
+            match volume.child(new_parent, &new_name) {
+                Some(existing) if existing != node => return EFI_ACCESS_DENIED,
+                _ => {},
+            }
+
+            volume.nodes[node].parent = Some(new_parent);
+            volume.nodes[node].name = new_name;
+        }
+
+        let n = &mut volume.nodes[node];
+        if !is_dir {
+            n.data.resize(metadata.size as usize, 0);
+        }
+        n.attributes = metadata.attributes.bits() & !EFI_FILE_DIRECTORY | n.attributes & EFI_FILE_DIRECTORY;
+        n.modified = if metadata.modified.year == 0 { now } else { metadata.modified };
+        if metadata.created.year != 0 {
+            n.created = metadata.created;
+        }
+
+        EFI_SUCCESS
+    })
+}
+
+extern "win64" fn flush(this: *mut EFI_FILE_PROTOCOL) -> EFI_STATUS {
+    with_file(this, |_, volume, _| if volume.read_only { EFI_WRITE_PROTECTED } else { EFI_SUCCESS })
+}
+
+#[cfg(test)]
+mod tests {
+    use mock::install;
+    use fs::{self, Volume, OpenMode, FileAttributes};
+    use io::Read;
+    use alloc::Vec;
+
+    #[test]
+    fn files_written_through_fs_are_visible_on_the_volume() {
+        let env = install();
+        let volume = env.add_volume("ESP");
+        volume.add_file("\\EFI\\app\\settings.ini", b"verbose=0");
+
+        assert_eq!(fs::read("\\efi\\APP\\settings.ini").unwrap(), b"verbose=0");
+        fs::write("\\EFI\\app\\settings.ini", b"verbose=1").unwrap();
+        assert_eq!(volume.contents("\\EFI\\app\\settings.ini").unwrap(), b"verbose=1");
+
+        fs::create_dir_all("\\EFI\\app\\logs").unwrap();
+        fs::write("\\EFI\\app\\logs\\boot.log", b"booted").unwrap();
+        fs::rename("\\EFI\\app\\logs\\boot.log", "\\EFI\\app\\boot.log").unwrap();
+        assert!(!volume.exists("\\EFI\\app\\logs\\boot.log"));
+        assert_eq!(volume.contents("\\EFI\\app\\boot.log").unwrap(), b"booted");
+
+        fs::remove_file("\\EFI\\app\\boot.log").unwrap();
+        assert!(!volume.exists("\\EFI\\app\\boot.log"));
+        assert_eq!(env.outstanding_allocations(), 0);
+    }
+
+    #[test]
+    fn volumes_are_found_by_handle_and_mapping() {
+        let env = install();
+        env.add_volume("ESP");
+        let data = env.add_volume("DATA");
+        data.add_file("readme.txt", b"hi");
+
+        let volume = Volume::from_handle(data.handle()).unwrap();
+        assert_eq!(volume.info().unwrap().label, "DATA");
+        let mut contents = Vec::new();
+        volume.open("README.TXT", OpenMode::Read, FileAttributes::empty()).unwrap().read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, b"hi");
+
+        assert_eq!(fs::read("fs1:\\readme.txt").unwrap(), b"hi");
+        assert!(fs::read("fs0:\\readme.txt").is_err());
+    }
+
+    #[test]
+    fn read_only_volumes_reject_writes() {
+        let env = install();
+        let volume = env.add_volume("RO");
+        volume.add_file("a.txt", b"a");
+        volume.set_read_only(true);
+        assert!(fs::write("\\a.txt", b"b").is_err());
+        assert_eq!(volume.contents("a.txt").unwrap(), b"a");
+    }
+}
@@ -0,0 +1,911 @@
+//! Fake firmware for unit testing code that uses this crate on the host with `cargo test` instead of only inside a VM.
+//!
+//! `install()` points the crate at an in-memory system table. Its boot services keep a protocol database,
+//! allocate from the host heap and run timers off a simulated clock which only moves when the code under test
+//! stalls, waits or polls, so tests never actually sleep. Its runtime services keep variables in memory and
+//! ConOut collects everything printed. The `fs` and `net` submodules add fake volumes and a fake network whose
+//! peers are closures.
+//!
+//! ```ignore
+//! #[test]
+//! fn saves_settings() {
+//!     let env = efi::mock::install();
+//!     let volume = env.add_volume("ESP");
+//!     app::save_settings().unwrap();
+//!     assert_eq!(volume.contents("\\settings.ini").unwrap(), b"verbose=1");
+//! }
+//! ```
+//!
+//! Only one environment exists at a time. `install()` blocks until the previous `MockEnv` is dropped so tests
+//! running on parallel threads take turns. Anything obtained from the firmware (files, sockets, events) must be
+//! dropped before the `MockEnv` is.
+
+use ffi::{
+    EFI_SYSTEM_TABLE,
+    EFI_STATUS,
+    EFI_SUCCESS,
+    EFI_UNSUPPORTED,
+    EFI_INVALID_PARAMETER,
+    EFI_NOT_FOUND,
+    EFI_NOT_READY,
+    EFI_DEVICE_ERROR,
+    EFI_OUT_OF_RESOURCES,
+    EFI_HANDLE,
+    EFI_EVENT,
+    EFI_GUID,
+    BOOLEAN,
+    CHAR16,
+    UINT32,
+    UINT64,
+    UINTN,
+    VOID,
+    boot_services::{
+        EFI_BOOT_SERVICES,
+        EFI_TPL,
+        TPL_APPLICATION,
+        EVT_TIMER,
+        EVT_NOTIFY_WAIT,
+        EVT_NOTIFY_SIGNAL,
+        EFI_EVENT_NOTIFY,
+        EFI_TIMER_DELAY,
+        EFI_INTERFACE_TYPE,
+        EFI_LOCATE_SEARCH_TYPE,
+        EFI_ALLOCATE_TYPE,
+        EFI_MEMORY_TYPE,
+        EFI_PHYSICAL_ADDRESS,
+        EFI_PAGE_SIZE,
+        EFI_OPEN_PROTOCOL_TEST_PROTOCOL,
+    },
+    console::{
+        EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL,
+        EFI_SIMPLE_TEXT_OUTPUT_MODE,
+        EFI_SIMPLE_TEXT_INPUT_PROTOCOL,
+        EFI_SIMPLE_TEXT_INPUT_PROTOCOL_GUID,
+        EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL_GUID,
+    },
+    device_path::EFI_DEVICE_PATH_PROTOCOL,
+    loaded_image::{EFI_LOADED_IMAGE_PROTOCOL, EFI_LOADED_IMAGE_PROTOCOL_GUID},
+    timestamp::{EFI_TIMESTAMP_PROTOCOL, EFI_TIMESTAMP_PROPERTIES, EFI_TIMESTAMP_PROTOCOL_GUID},
+};
+use ucs2::CStr16;
+use alloc::{String, Vec, boxed::Box};
+use core::{ptr, mem, slice, time::Duration, sync::atomic::{AtomicBool, Ordering}};
+use host_std::thread;
+use reset_env;
+
+// Functions of the tables that the mock doesn't implement. The win64 calling convention leaves it to the caller
+// to clean up the arguments so a function that takes none can stand in for any of them.
+extern "win64" fn unsupported() -> EFI_STATUS {
+    EFI_UNSUPPORTED
+}
+
+extern "win64" fn succeed() -> EFI_STATUS {
+    EFI_SUCCESS
+}
+
+macro_rules! stub {
+    ($f:ident) => {
+        unsafe { ::core::mem::transmute($f as extern "win64" fn() -> ::ffi::EFI_STATUS) }
+    }
+}
+
+pub mod fs;
+pub mod net;
+mod runtime;
+
+/// How long each `CheckEvent()` is taken to last so that polling loops make progress on the simulated clock
+const POLL_STEP: u64 = 1_000_000;
+
+const HANDLE_BASE: usize = 0xA000_0000;
+const EVENT_BASE: usize = 0xE000_0000;
+const ID_STRIDE: usize = 8;
+
+// Handles created by State::new() in this order. The system table refers to them so they must not change.
+const IMAGE_HANDLE_INDEX: usize = 0;
+const CONSOLE_HANDLE_INDEX: usize = 1;
+// The event created by State::new() for ConIn's WaitForKey
+const WAIT_FOR_KEY_INDEX: usize = 0;
+
+fn handle_id(index: usize) -> EFI_HANDLE {
+    (HANDLE_BASE + index * ID_STRIDE) as EFI_HANDLE
+}
+
+fn event_id(index: usize) -> EFI_EVENT {
+    (EVENT_BASE + index * ID_STRIDE) as EFI_EVENT
+}
+
+fn event_index(event: EFI_EVENT) -> Option<usize> {
+    let offset = (event as usize).wrapping_sub(EVENT_BASE);
+    if offset % ID_STRIDE == 0 { Some(offset / ID_STRIDE) } else { None }
+}
+
+struct HandleEntry {
+    handle: EFI_HANDLE,
+    protocols: Vec<(EFI_GUID, *const VOID)>,
+}
+
+struct Event {
+    event_type: UINT32,
+    notify: Option<(EFI_EVENT_NOTIFY, *const VOID)>,
+    signaled: bool,
+    deadline: Option<u64>,
+    period: Option<u64>,
+    closed: bool,
+}
+
+// A notification function that is due to run once the state is no longer borrowed
+type Notify = (EFI_EVENT_NOTIFY, EFI_EVENT, *const VOID);
+
+struct State {
+    clock: u64, // Simulated nanoseconds since install()
+    tpl: EFI_TPL,
+    monotonic_count: u64,
+    watchdog: Option<u64>, // When the watchdog resets the platform
+    handles: Vec<HandleEntry>,
+    handle_count: usize,
+    events: Vec<Event>,
+    pages: Vec<(EFI_PHYSICAL_ADDRESS, Vec<u8>)>,
+    pool_allocations: usize,
+    console: String,
+    loaded_image: Box<EFI_LOADED_IMAGE_PROTOCOL>,
+    runtime: runtime::State,
+    fs: fs::State,
+    net: net::State,
+}
+
+impl State {
+    fn new(table: &Table) -> Self {
+        let loaded_image = Box::new(EFI_LOADED_IMAGE_PROTOCOL {
+            Revision: 0x1000,
+            ParentHandle: ptr::null(),
+            SystemTable: table.system_table,
+            DeviceHandle: ptr::null(),
+            FilePath: ptr::null(),
+            Reserved: ptr::null(),
+            LoadOptionsSize: 0,
+            LoadOptions: ptr::null(),
+            ImageBase: ptr::null(),
+            ImageSize: 0,
+            ImageCodeType: EFI_MEMORY_TYPE::EfiLoaderCode,
+            ImageDataType: EFI_MEMORY_TYPE::EfiLoaderData,
+            Unload: stub!(unsupported),
+        });
+
+        let mut state = State {
+            clock: 0,
+            tpl: TPL_APPLICATION,
+            monotonic_count: 0,
+            watchdog: None,
+            handles: Vec::new(),
+            handle_count: 0,
+            events: Vec::new(),
+            pages: Vec::new(),
+            pool_allocations: 0,
+            console: String::new(),
+            loaded_image,
+            runtime: runtime::State::new(),
+            fs: fs::State::new(),
+            net: net::State::new(),
+        };
+
+        let loaded_image = &*state.loaded_image as *const _ as *const VOID;
+        let image_handle = state.install(None, EFI_LOADED_IMAGE_PROTOCOL_GUID, loaded_image);
+        let console_handle = state.install(None, EFI_SIMPLE_TEXT_INPUT_PROTOCOL_GUID, table.con_in as *const VOID);
+        state.install(Some(console_handle), EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL_GUID, table.con_out as *const VOID);
+        debug_assert!(image_handle == handle_id(IMAGE_HANDLE_INDEX) && console_handle == handle_id(CONSOLE_HANDLE_INDEX));
+
+        state.create_event(0, None);
+        state
+    }
+
+    /// Installs a protocol on the given handle or on a new one. The handle must not already have the protocol.
+    fn install(&mut self, handle: Option<EFI_HANDLE>, guid: EFI_GUID, interface: *const VOID) -> EFI_HANDLE {
+        if let Some(handle) = handle {
+            if let Some(entry) = self.handles.iter_mut().find(|e| e.handle == handle) {
+                entry.protocols.push((guid, interface));
+                return handle;
+            }
+        }
+
+        let handle = handle_id(self.handle_count);
+        self.handle_count += 1;
+        self.handles.push(HandleEntry { handle, protocols: vec![(guid, interface)] });
+        handle
+    }
+
+    fn uninstall(&mut self, handle: EFI_HANDLE, guid: &EFI_GUID) -> EFI_STATUS {
+        let index = match self.handles.iter().position(|e| e.handle == handle) {
+            Some(index) => index,
+            None => return EFI_NOT_FOUND,
+        };
+
+        let protocol_count = {
+            let protocols = &mut self.handles[index].protocols;
+            match protocols.iter().position(|&(g, _)| g == *guid) {
+                Some(i) => { protocols.remove(i); },
+                None => return EFI_NOT_FOUND,
+            }
+            protocols.len()
+        };
+
+        if protocol_count == 0 {
+            self.handles.remove(index); // A handle goes away along with its last protocol
+        }
+
+        EFI_SUCCESS
+    }
+
+    fn protocol(&self, handle: EFI_HANDLE, guid: &EFI_GUID) -> ::core::result::Result<*const VOID, EFI_STATUS> {
+        let entry = self.handles.iter().find(|e| e.handle == handle).ok_or(EFI_INVALID_PARAMETER)?;
+        entry.protocols.iter().find(|&&(g, _)| g == *guid).map(|&(_, i)| i).ok_or(EFI_UNSUPPORTED)
+    }
+
+    fn handles_with(&self, guid: Option<&EFI_GUID>) -> Vec<EFI_HANDLE> {
+        self.handles.iter()
+            .filter(|e| guid.map(|guid| e.protocols.iter().any(|&(g, _)| g == *guid)).unwrap_or(true))
+            .map(|e| e.handle)
+            .collect()
+    }
+
+    fn create_event(&mut self, event_type: UINT32, notify: Option<(EFI_EVENT_NOTIFY, *const VOID)>) -> EFI_EVENT {
+        self.events.push(Event { event_type, notify, signaled: false, deadline: None, period: None, closed: false });
+        event_id(self.events.len() - 1)
+    }
+
+    fn event(&mut self, event: EFI_EVENT) -> Option<&mut Event> {
+        match event_index(event).and_then(move |i| self.events.get_mut(i)) {
+            Some(e) => if e.closed { None } else { Some(e) },
+            None => None,
+        }
+    }
+
+    // Signal events with a notification function have it run instead of staying signaled
+    fn signal(&mut self, event: EFI_EVENT) -> Option<Notify> {
+        let e = self.event(event)?;
+        match e.notify {
+            Some((notify, context)) if e.event_type & EVT_NOTIFY_SIGNAL == EVT_NOTIFY_SIGNAL => Some((notify, event, context)),
+            _ => {
+                e.signaled = true;
+                None
+            }
+        }
+    }
+
+    fn advance(&mut self, nanos: u64) -> Vec<Notify> {
+        self.clock = self.clock.saturating_add(nanos);
+        let clock = self.clock;
+
+        let mut expired = Vec::new();
+        for (i, e) in self.events.iter_mut().enumerate() {
+            match e.deadline {
+                Some(deadline) if !e.closed && deadline <= clock => {
+                    e.deadline = e.period.map(|p| clock + p);
+                    expired.push(event_id(i));
+                },
+                _ => {},
+            }
+        }
+
+        expired.into_iter().filter_map(|e| self.signal(e)).collect()
+    }
+}
+
+static mut STATE: Option<State> = None;
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+fn with_state<R, F: FnOnce(&mut State) -> R>(f: F) -> R {
+    unsafe { f(STATE.as_mut().expect("the mock firmware was used without a MockEnv")) }
+}
+
+fn run_notifies(notifies: Vec<Notify>) {
+    for (notify, event, context) in notifies {
+        notify(event, context);
+    }
+}
+
+/// Signals an event running its notification function if it has one
+fn signal_event(event: EFI_EVENT) {
+    if let Some(notify) = with_state(|s| s.signal(event)) {
+        run_notifies(vec![notify]);
+    }
+}
+
+// The parts of the system table that must keep their addresses for the life of the process
+// because the crate caches pointers into them (e.g. the timestamp protocol in time::Instant).
+struct Table {
+    system_table: *const EFI_SYSTEM_TABLE,
+    con_in: *const EFI_SIMPLE_TEXT_INPUT_PROTOCOL,
+    con_out: *const EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL,
+    timestamp: *const EFI_TIMESTAMP_PROTOCOL,
+}
+
+static mut TABLE: Option<Table> = None;
+
+// "Mock"
+static FIRMWARE_VENDOR: [CHAR16; 5] = [0x4D, 0x6F, 0x63, 0x6B, 0];
+
+fn table() -> &'static Table {
+    unsafe {
+        if TABLE.is_none() {
+            TABLE = Some(build_table());
+        }
+        TABLE.as_ref().unwrap()
+    }
+}
+
+fn build_table() -> Table {
+    let boot_services = Box::new(EFI_BOOT_SERVICES {
+        Hdr: unsafe { mem::zeroed() },
+        RaiseTPL: raise_tpl,
+        RestoreTPL: restore_tpl,
+        AllocatePages: allocate_pages,
+        FreePages: free_pages,
+        GetMemoryMap: ptr::null(),
+        AllocatePool: allocate_pool,
+        FreePool: free_pool,
+        CreateEvent: create_event,
+        SetTimer: set_timer,
+        WaitForEvent: wait_for_event,
+        SignalEvent: signal_event_fn,
+        CloseEvent: close_event,
+        CheckEvent: check_event,
+        InstallProtocolInterface: install_protocol_interface,
+        ReinstallProtocolInterface: ptr::null(),
+        UninstallProtocolInterface: uninstall_protocol_interface,
+        HandleProtocol: ptr::null(),
+        Reserve: ptr::null(),
+        RegisterProtocolNotify: ptr::null(),
+        LocateHandle: ptr::null(),
+        LocateDevicePath: locate_device_path,
+        InstallConfigurationTable: ptr::null(),
+        LoadImage: stub!(unsupported),
+        StartImage: stub!(unsupported),
+        Exit: stub!(unsupported),
+        UnloadImage: ptr::null(),
+        ExitBootServices: ptr::null(),
+        GetNextMonotonicCount: get_next_monotonic_count,
+        Stall: stall,
+        SetWatchdogTimer: set_watchdog_timer,
+        ConnectController: connect_controller,
+        DisconnectController: stub!(succeed),
+        OpenProtocol: open_protocol,
+        CloseProtocol: close_protocol,
+        OpenProtocolInformation: ptr::null(),
+        ProtocolsPerHandle: ptr::null(),
+        LocateHandleBuffer: locate_handle_buffer,
+        LocateProtocol: locate_protocol,
+        InstallMultipleProtocolInterfaces: ptr::null(),
+        UninstallMultipleProtocolInterfaces: ptr::null(),
+        CalculateCrc32: ptr::null(),
+        CopyMem: ptr::null(),
+        SetMem: ptr::null(),
+        CreateEventEx: ptr::null(),
+    });
+
+    let mut mode = EFI_SIMPLE_TEXT_OUTPUT_MODE::default();
+    mode.MaxMode = 1;
+    let con_out = Box::new(EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL {
+        Reset: stub!(succeed),
+        OutputString: output_string,
+        TestString: stub!(succeed),
+        QueryMode: query_mode,
+        SetMode: stub!(succeed),
+        SetAttribute: stub!(succeed),
+        ClearScreen: stub!(succeed),
+        SetCursorPosition: stub!(succeed),
+        EnableCursor: stub!(succeed),
+        Mode: Box::into_raw(Box::new(mode)),
+    });
+
+    let con_in = Box::new(EFI_SIMPLE_TEXT_INPUT_PROTOCOL {
+        Reset: stub!(succeed),
+        ReadKeyStroke: read_key_stroke,
+        WaitForKey: event_id(WAIT_FOR_KEY_INDEX),
+    });
+
+    let timestamp = Box::new(EFI_TIMESTAMP_PROTOCOL {
+        GetTimestamp: get_timestamp,
+        GetProperties: get_timestamp_properties,
+    });
+
+    let con_out = Box::into_raw(con_out) as *const EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL;
+    let system_table = Box::new(EFI_SYSTEM_TABLE {
+        Hdr: unsafe { mem::zeroed() },
+        FirmwareVendor: FIRMWARE_VENDOR.as_ptr(),
+        FirmwareRevision: 0,
+        ConsoleInHandle: handle_id(CONSOLE_HANDLE_INDEX),
+        ConIn: Box::into_raw(con_in),
+        ConsoleOutHandle: handle_id(CONSOLE_HANDLE_INDEX),
+        ConOut: con_out,
+        ConsoleErrorHandle: handle_id(CONSOLE_HANDLE_INDEX),
+        StdErr: con_out,
+        RuntimeServices: Box::into_raw(Box::new(runtime::services())),
+        BootServices: Box::into_raw(boot_services),
+        NumberOfTableEntries: 0,
+        ConfigurationTable: ptr::null(),
+    });
+
+    Table {
+        con_in: system_table.ConIn,
+        con_out,
+        system_table: Box::into_raw(system_table),
+        timestamp: Box::into_raw(timestamp),
+    }
+}
+
+/// The installed mock firmware. Dropping it discards everything the firmware holds and lets the next `install()` proceed.
+pub struct MockEnv {
+    _private: (),
+}
+
+/// Installs a fresh mock firmware and initializes the crate to use it, waiting for any other `MockEnv` to be dropped first
+pub fn install() -> MockEnv {
+    while LOCKED.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+        thread::yield_now();
+    }
+
+    let table = table();
+    let state = State::new(table);
+    unsafe { STATE = Some(state); }
+
+    // The timestamp protocol isn't on a handle of its own in real firmware either; it is found with LocateProtocol()
+    with_state(|s| s.install(None, EFI_TIMESTAMP_PROTOCOL_GUID, table.timestamp as *const VOID));
+    reset_env(handle_id(IMAGE_HANDLE_INDEX), table.system_table);
+    MockEnv { _private: () }
+}
+
+impl MockEnv {
+    /// Simulated time since `install()`
+    pub fn elapsed(&self) -> Duration {
+        let nanos = with_state(|s| s.clock);
+        Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+    }
+
+    /// Moves the simulated clock forward firing any timers that come due
+    pub fn advance(&self, dur: Duration) {
+        let nanos = dur.as_secs().saturating_mul(1_000_000_000).saturating_add(dur.subsec_nanos() as u64);
+        run_notifies(with_state(|s| s.advance(nanos)));
+    }
+
+    /// Everything written to ConOut (and StdErr, which is the same device) so far
+    pub fn console_output(&self) -> String {
+        with_state(|s| s.console.clone())
+    }
+
+    /// The number of pool allocations that have not been freed. Handy for catching leaks.
+    pub fn outstanding_allocations(&self) -> usize {
+        with_state(|s| s.pool_allocations)
+    }
+
+    /// The time left before the watchdog resets the platform, zero if it would have, or `None` if it's disabled
+    pub fn watchdog(&self) -> Option<Duration> {
+        let (clock, watchdog) = with_state(|s| (s.clock, s.watchdog));
+        watchdog.map(|deadline| {
+            let nanos = deadline.saturating_sub(clock);
+            Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+        })
+    }
+
+    /// Installs a protocol implemented by the test on a new handle. The interface must outlive the `MockEnv`.
+    pub fn install_protocol(&self, guid: &EFI_GUID, interface: *const VOID) -> EFI_HANDLE {
+        with_state(|s| s.install(None, *guid, interface))
+    }
+}
+
+impl Drop for MockEnv {
+    fn drop(&mut self) {
+        unsafe { STATE = None; }
+        LOCKED.store(false, Ordering::Release);
+    }
+}
+
+extern "win64" fn raise_tpl(new_tpl: EFI_TPL) -> EFI_TPL {
+    with_state(|s| mem::replace(&mut s.tpl, new_tpl))
+}
+
+extern "win64" fn restore_tpl(old_tpl: EFI_TPL) {
+    with_state(|s| s.tpl = old_tpl)
+}
+
+extern "win64" fn allocate_pages(allocation_type: EFI_ALLOCATE_TYPE, _memory_type: EFI_MEMORY_TYPE, pages: UINTN, memory: *mut EFI_PHYSICAL_ADDRESS) -> EFI_STATUS {
+    match allocation_type {
+        EFI_ALLOCATE_TYPE::AllocateAnyPages => {},
+        _ => return EFI_UNSUPPORTED, // Host memory can't be had at a particular address
+    }
+
+    // One page extra so that a page aligned start can always be found
+    let buf = vec![0_u8; (pages + 1) * EFI_PAGE_SIZE];
+    let address = (buf.as_ptr() as usize + EFI_PAGE_SIZE - 1) & !(EFI_PAGE_SIZE - 1);
+    with_state(|s| s.pages.push((address as EFI_PHYSICAL_ADDRESS, buf)));
+    unsafe { *memory = address as EFI_PHYSICAL_ADDRESS; }
+    EFI_SUCCESS
+}
+
+extern "win64" fn free_pages(memory: EFI_PHYSICAL_ADDRESS, _pages: UINTN) -> EFI_STATUS {
+    with_state(|s| {
+        match s.pages.iter().position(|&(address, _)| address == memory) {
+            Some(i) => {
+                s.pages.remove(i);
+                EFI_SUCCESS
+            },
+            None => EFI_NOT_FOUND,
+        }
+    })
+}
+
+// Pool allocations are prefixed with a word holding their length in words so that free_pool() can give them back
+extern "win64" fn allocate_pool(_pool_type: EFI_MEMORY_TYPE, size: UINTN, buffer: *mut *const VOID) -> EFI_STATUS {
+    let words = size / 8 + 2;
+    let mut block = vec![0_u64; words].into_boxed_slice();
+    block[0] = words as u64;
+    let start = Box::into_raw(block) as *mut u64;
+    with_state(|s| s.pool_allocations += 1);
+    unsafe { *buffer = start.offset(1) as *const VOID; }
+    EFI_SUCCESS
+}
+
+extern "win64" fn free_pool(buffer: *const VOID) -> EFI_STATUS {
+    if buffer.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    unsafe {
+        let start = (buffer as *mut u64).offset(-1);
+        let words = *start as usize;
+        drop(Box::from_raw(slice::from_raw_parts_mut(start, words) as *mut [u64]));
+    }
+
+    with_state(|s| s.pool_allocations -= 1);
+    EFI_SUCCESS
+}
+
+extern "win64" fn create_event(event_type: UINT32, _notify_tpl: EFI_TPL, notify_function: Option<EFI_EVENT_NOTIFY>, notify_context: *const VOID, event: *mut EFI_EVENT) -> EFI_STATUS {
+    let needs_notify = event_type & (EVT_NOTIFY_WAIT | EVT_NOTIFY_SIGNAL) != 0;
+    if event.is_null() || needs_notify != notify_function.is_some() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let created = with_state(|s| s.create_event(event_type, notify_function.map(|f| (f, notify_context))));
+    unsafe { *event = created; }
+    EFI_SUCCESS
+}
+
+extern "win64" fn set_timer(event: EFI_EVENT, timer_type: EFI_TIMER_DELAY, trigger_time: UINT64) -> EFI_STATUS {
+    with_state(|s| {
+        let clock = s.clock;
+        let e = match s.event(event) {
+            Some(e) => e,
+            None => return EFI_INVALID_PARAMETER,
+        };
+
+        if e.event_type & EVT_TIMER != EVT_TIMER {
+            return EFI_INVALID_PARAMETER;
+        }
+
+        let nanos = trigger_time.saturating_mul(100);
+        match timer_type {
+            EFI_TIMER_DELAY::TimerCancel => {
+                e.deadline = None;
+                e.period = None;
+            },
+            EFI_TIMER_DELAY::TimerRelative => {
+                e.deadline = Some(clock + nanos);
+                e.period = None;
+            },
+            EFI_TIMER_DELAY::TimerPeriodic => {
+                let period = if nanos == 0 { POLL_STEP } else { nanos }; // Zero means every timer tick
+                e.deadline = Some(clock + period);
+                e.period = Some(period);
+            },
+        }
+
+        EFI_SUCCESS
+    })
+}
+
+enum WaitStep {
+    Done(EFI_STATUS),
+    Notify(Vec<Notify>),
+}
+
+// Consumes the signal of the event if it has one. Otherwise queues the notification function of a wait event
+// if asked to so that the caller can run it and check again.
+fn check(s: &mut State, event: EFI_EVENT, notify: bool) -> WaitStep {
+    let e = match s.event(event) {
+        Some(e) => e,
+        None => return WaitStep::Done(EFI_INVALID_PARAMETER),
+    };
+
+    if e.event_type & EVT_NOTIFY_SIGNAL == EVT_NOTIFY_SIGNAL {
+        return WaitStep::Done(EFI_INVALID_PARAMETER);
+    }
+
+    if e.signaled {
+        e.signaled = false;
+        return WaitStep::Done(EFI_SUCCESS);
+    }
+
+    match e.notify {
+        Some((f, context)) if notify => WaitStep::Notify(vec![(f, event, context)]),
+        _ => WaitStep::Done(EFI_NOT_READY),
+    }
+}
+
+// Checks the events in order giving their notification functions one chance to signal them
+fn check_all(events: &[EFI_EVENT], index: *mut UINTN) -> EFI_STATUS {
+    for &notify in &[true, false] {
+        let mut notifies = Vec::new();
+        for (i, &event) in events.iter().enumerate() {
+            match with_state(|s| check(s, event, notify)) {
+                WaitStep::Done(EFI_NOT_READY) => {},
+                WaitStep::Done(status) => {
+                    unsafe { *index = i; }
+                    return status;
+                },
+                WaitStep::Notify(n) => notifies.extend(n),
+            }
+        }
+
+        run_notifies(notifies);
+    }
+
+    EFI_NOT_READY
+}
+
+extern "win64" fn wait_for_event(number_of_events: UINTN, event: *const EFI_EVENT, index: *mut UINTN) -> EFI_STATUS {
+    if number_of_events == 0 || event.is_null() || index.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let events = unsafe { slice::from_raw_parts(event, number_of_events) };
+    loop {
+        match check_all(events, index) {
+            EFI_NOT_READY => {},
+            status => return status,
+        }
+
+        // Nothing to do but wait for the first of the timers to go off
+        let notifies = with_state(|s| {
+            let clock = s.clock;
+            let next_deadline = events.iter().filter_map(|&e| s.event(e).and_then(|e| e.deadline)).min();
+            next_deadline.map(|deadline| s.advance(deadline.saturating_sub(clock)))
+        });
+
+        match notifies {
+            Some(notifies) => run_notifies(notifies),
+            None => return EFI_DEVICE_ERROR, // Nothing will ever signal the events
+        }
+    }
+}
+
+extern "win64" fn signal_event_fn(event: EFI_EVENT) -> EFI_STATUS {
+    if with_state(|s| s.event(event).is_none()) {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    signal_event(event);
+    EFI_SUCCESS
+}
+
+extern "win64" fn set_watchdog_timer(timeout: UINTN, _watchdog_code: UINT64, _data_size: UINTN, _watchdog_data: *const CHAR16) -> EFI_STATUS {
+    with_state(|s| {
+        s.watchdog = if timeout == 0 { None } else { Some(s.clock.saturating_add((timeout as u64).saturating_mul(1_000_000_000))) };
+    });
+    EFI_SUCCESS
+}
+
+extern "win64" fn close_event(event: EFI_EVENT) -> EFI_STATUS {
+    with_state(|s| {
+        match s.event(event) {
+            Some(e) => {
+                e.closed = true;
+                EFI_SUCCESS
+            },
+            None => EFI_INVALID_PARAMETER,
+        }
+    })
+}
+
+extern "win64" fn check_event(event: EFI_EVENT) -> EFI_STATUS {
+    run_notifies(with_state(|s| s.advance(POLL_STEP)));
+    let mut index = 0;
+    check_all(&[event], &mut index)
+}
+
+extern "win64" fn install_protocol_interface(handle: *mut EFI_HANDLE, protocol: *const EFI_GUID, interface_type: EFI_INTERFACE_TYPE, interface: *const VOID) -> EFI_STATUS {
+    let EFI_INTERFACE_TYPE::EFI_NATIVE_INTERFACE = interface_type;
+    if handle.is_null() || protocol.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    unsafe {
+        let existing = if (*handle).is_null() { None } else { Some(*handle) };
+        let installed = with_state(|s| {
+            if let Some(h) = existing {
+                if s.protocol(h, &*protocol).is_ok() || s.handles.iter().all(|e| e.handle != h) {
+                    return None;
+                }
+            }
+            Some(s.install(existing, *protocol, interface))
+        });
+
+        match installed {
+            Some(h) => {
+                *handle = h;
+                EFI_SUCCESS
+            },
+            None => EFI_INVALID_PARAMETER,
+        }
+    }
+}
+
+extern "win64" fn uninstall_protocol_interface(handle: EFI_HANDLE, protocol: *const EFI_GUID, interface: *const VOID) -> EFI_STATUS {
+    with_state(|s| {
+        match s.protocol(handle, unsafe { &*protocol }) {
+            Ok(i) if i == interface => s.uninstall(handle, unsafe { &*protocol }),
+            _ => EFI_NOT_FOUND,
+        }
+    })
+}
+
+extern "win64" fn locate_device_path(_protocol: *const EFI_GUID, _device_path: *mut *const EFI_DEVICE_PATH_PROTOCOL, _device: *mut EFI_HANDLE) -> EFI_STATUS {
+    EFI_NOT_FOUND // None of the mock's handles have device paths
+}
+
+extern "win64" fn get_next_monotonic_count(count: *mut UINT64) -> EFI_STATUS {
+    let next = with_state(|s| {
+        s.monotonic_count += 1;
+        s.monotonic_count
+    });
+    unsafe { *count = next; }
+    EFI_SUCCESS
+}
+
+extern "win64" fn stall(microseconds: UINTN) -> EFI_STATUS {
+    run_notifies(with_state(|s| s.advance((microseconds as u64).saturating_mul(1000))));
+    EFI_SUCCESS
+}
+
+extern "win64" fn connect_controller(_controller_handle: EFI_HANDLE, _driver_image_handle: *const EFI_HANDLE, _remaining_device_path: *const EFI_DEVICE_PATH_PROTOCOL, _recursive: BOOLEAN) -> EFI_STATUS {
+    EFI_NOT_FOUND // There are no drivers to connect
+}
+
+extern "win64" fn open_protocol(handle: EFI_HANDLE, protocol: *const EFI_GUID, interface: *mut *const VOID, _agent_handle: EFI_HANDLE, _controller_handle: EFI_HANDLE, attributes: UINT32) -> EFI_STATUS {
+    if protocol.is_null() || (interface.is_null() && attributes != EFI_OPEN_PROTOCOL_TEST_PROTOCOL) {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    match with_state(|s| s.protocol(handle, unsafe { &*protocol })) {
+        Ok(i) => {
+            if !interface.is_null() {
+                unsafe { *interface = i; }
+            }
+            EFI_SUCCESS
+        },
+        Err(status) => status,
+    }
+}
+
+extern "win64" fn close_protocol(handle: EFI_HANDLE, protocol: *const EFI_GUID, _agent_handle: EFI_HANDLE, _controller_handle: EFI_HANDLE) -> EFI_STATUS {
+    match with_state(|s| s.protocol(handle, unsafe { &*protocol })) {
+        Ok(_) => EFI_SUCCESS,
+        Err(_) => EFI_NOT_FOUND,
+    }
+}
+
+extern "win64" fn locate_handle_buffer(search_type: EFI_LOCATE_SEARCH_TYPE, protocol: *const EFI_GUID, _search_key: *const VOID, no_handles: *mut UINTN, buffer: *mut *const EFI_HANDLE) -> EFI_STATUS {
+    let handles = match search_type {
+        EFI_LOCATE_SEARCH_TYPE::AllHandles => with_state(|s| s.handles_with(None)),
+        EFI_LOCATE_SEARCH_TYPE::ByProtocol if !protocol.is_null() => with_state(|s| s.handles_with(Some(unsafe { &*protocol }))),
+        _ => return EFI_INVALID_PARAMETER,
+    };
+
+    if handles.is_empty() {
+        return EFI_NOT_FOUND;
+    }
+
+    let mut pool: *const VOID = ptr::null();
+    if allocate_pool(EFI_MEMORY_TYPE::EfiBootServicesData, handles.len() * mem::size_of::<EFI_HANDLE>(), &mut pool) != EFI_SUCCESS {
+        return EFI_OUT_OF_RESOURCES;
+    }
+
+    unsafe {
+        ptr::copy_nonoverlapping(handles.as_ptr(), pool as *mut EFI_HANDLE, handles.len());
+        *no_handles = handles.len();
+        *buffer = pool as *const EFI_HANDLE;
+    }
+
+    EFI_SUCCESS
+}
+
+extern "win64" fn locate_protocol(protocol: *const EFI_GUID, _registration: *const VOID, interface: *mut *const VOID) -> EFI_STATUS {
+    let found = with_state(|s| {
+        let guid = unsafe { &*protocol };
+        s.handles_with(Some(guid)).first().and_then(|&h| s.protocol(h, guid).ok())
+    });
+
+    unsafe { *interface = found.unwrap_or(ptr::null()); }
+    if found.is_some() { EFI_SUCCESS } else { EFI_NOT_FOUND }
+}
+
+extern "win64" fn output_string(_this: *const EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL, string: *const CHAR16) -> EFI_STATUS {
+    let text = unsafe { CStr16::from_ptr(string) }.to_string_lossy();
+    with_state(|s| s.console.push_str(&text));
+    EFI_SUCCESS
+}
+
+extern "win64" fn query_mode(_this: *const EFI_SIMPLE_TEXT_OUTPUT_PROTOCOL, mode_number: UINTN, columns: *mut UINTN, rows: *mut UINTN) -> EFI_STATUS {
+    if mode_number != 0 {
+        return EFI_UNSUPPORTED;
+    }
+
+    unsafe {
+        *columns = 80;
+        *rows = 25;
+    }
+    EFI_SUCCESS
+}
+
+extern "win64" fn read_key_stroke(_this: *mut EFI_SIMPLE_TEXT_INPUT_PROTOCOL, _key: *mut ::ffi::console::EFI_INPUT_KEY) -> EFI_STATUS {
+    EFI_NOT_READY // Nobody is typing
+}
+
+// The simulated clock in nanoseconds
+extern "win64" fn get_timestamp() -> UINT64 {
+    with_state(|s| s.clock)
+}
+
+extern "win64" fn get_timestamp_properties(properties: *mut EFI_TIMESTAMP_PROPERTIES) -> EFI_STATUS {
+    unsafe {
+        (*properties).Frequency = 1_000_000_000;
+        (*properties).EndValue = UINT64::max_value();
+    }
+    EFI_SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::install;
+    use time::{self, Instant};
+    use events::{Timer, TimerSchedule, TimerState, EventTpl, Wait};
+    use fs;
+    use io::Write;
+    use core::time::Duration;
+
+    #[test]
+    fn sleeping_moves_the_simulated_clock() {
+        let env = install();
+        let start = Instant::now().unwrap();
+        time::sleep(Duration::from_secs(5)).unwrap();
+        assert_eq!(env.elapsed(), Duration::from_secs(5));
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn timers_fire_when_their_time_comes() {
+        let env = install();
+        let mut timer = Timer::create(Duration::from_secs(1), TimerSchedule::Relative, TimerState::Inactive, EventTpl::Callback).unwrap();
+        timer.set(Duration::from_secs(1), TimerSchedule::Relative).unwrap();
+        assert!(!timer.is_signaled().unwrap());
+        env.advance(Duration::from_secs(1));
+        assert!(timer.is_signaled().unwrap());
+        assert!(!timer.is_signaled().unwrap());
+    }
+
+    #[test]
+    fn pool_allocations_are_tracked() {
+        let env = install();
+        env.add_volume("ESP");
+        assert_eq!(fs::Volume::all().unwrap().len(), 1); // Gets its handles from a pool buffer
+        assert_eq!(env.outstanding_allocations(), 0);
+    }
+
+    #[test]
+    fn console_output_is_captured() {
+        let env = install();
+        let mut stdout = ::stdout();
+        stdout.write_all(b"hello").unwrap();
+        stdout.flush().unwrap();
+        assert_eq!(env.console_output(), "hello");
+    }
+}
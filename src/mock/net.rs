@@ -0,0 +1,785 @@
+//! A fake network interface whose peers are closures.
+//!
+//! `MockEnv::add_network()` makes it look as if DHCP has already completed (which is what `net` expects) and
+//! installs the UDP4 and TCP4 service bindings. Datagrams and stream writes are handed to the closures given to
+//! `MockEnv::on_udp()` and `MockEnv::on_tcp()` and whatever they return is what the code under test receives.
+//!
+//! ```ignore
+//! let env = efi::mock::install();
+//! env.add_network(NetworkConfig::default());
+//! env.on_tcp(|_peer, request| if request.ends_with(b"\r\n\r\n") { b"HTTP/1.1 204 No Content\r\n\r\n".to_vec() } else { Vec::new() });
+//! ```
+
+use ffi::{
+    EFI_STATUS,
+    EFI_SUCCESS,
+    EFI_INVALID_PARAMETER,
+    EFI_NOT_READY,
+    EFI_NOT_STARTED,
+    EFI_ACCESS_DENIED,
+    EFI_ABORTED,
+    EFI_NOT_FOUND,
+    EFI_HANDLE,
+    EFI_IPv4_ADDRESS,
+    EFI_IP_ADDRESS,
+    EFI_SERVICE_BINDING_PROTOCOL,
+    TRUE,
+    UINT32,
+    VOID,
+    ip4::EFI_IP4_MODE_DATA,
+    managed_network::EFI_MANAGED_NETWORK_CONFIG_DATA,
+    simple_network::EFI_SIMPLE_NETWORK_MODE,
+    pxe::{EFI_PXE_BASE_CODE_PROTOCOL, EFI_PXE_BASE_CODE_PROTOCOL_GUID, EFI_PXE_BASE_CODE_MODE},
+    udp4::{
+        EFI_UDP4_SERVICE_BINDING_PROTOCOL_GUID,
+        EFI_UDP4_PROTOCOL_GUID,
+        EFI_UDP4_PROTOCOL,
+        EFI_UDP4_CONFIG_DATA,
+        EFI_UDP4_COMPLETION_TOKEN,
+        EFI_UDP4_RECEIVE_DATA,
+        EFI_UDP4_SESSION_DATA,
+        EFI_UDP4_FRAGMENT_DATA,
+    },
+    tcp4::{
+        EFI_TCP4_SERVICE_BINDING_PROTOCOL_GUID,
+        EFI_TCP4_PROTOCOL_GUID,
+        EFI_TCP4_PROTOCOL,
+        EFI_TCP4_CONFIG_DATA,
+        EFI_TCP4_ACCESS_POINT,
+        EFI_TCP4_CONNECTION_STATE,
+        EFI_TCP4_CONNECTION_TOKEN,
+        EFI_TCP4_IO_TOKEN,
+        EFI_TCP4_CLOSE_TOKEN,
+        EFI_TCP4_COMPLETION_TOKEN,
+        EFI_CONNECTION_FIN,
+        EFI_CONNECTION_REFUSED,
+    },
+};
+use net::{Ipv4Addr, SocketAddrV4};
+use alloc::{Vec, boxed::Box};
+use core::{ptr, mem, slice, cmp};
+use super::{MockEnv, State as EnvState, with_state, signal_event, unsupported, succeed};
+
+// Where ports are handed out from when a socket doesn't ask for a particular one
+const EPHEMERAL_PORT_START: u16 = 49152;
+
+// DHCP message layout
+const DHCP_MAGIC_OFFSET: usize = 236;
+const DHCP_OPTIONS_OFFSET: usize = 240;
+const DHCP_MAGIC: [u8; 4] = [99, 130, 83, 99];
+const DHCP_ACK: u8 = 5;
+
+/// The IP configuration the fake DHCP server hands out
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+    pub ip: Ipv4Addr,
+    pub subnet_mask: Ipv4Addr,
+    pub router: Ipv4Addr,
+    pub dns_servers: Vec<Ipv4Addr>,
+}
+
+/// The addresses QEMU's user mode networking hands out
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            ip: Ipv4Addr::new(10, 0, 2, 15),
+            subnet_mask: Ipv4Addr::new(255, 255, 255, 0),
+            router: Ipv4Addr::new(10, 0, 2, 2),
+            dns_servers: vec![Ipv4Addr::new(10, 0, 2, 3)],
+        }
+    }
+}
+
+type UdpHandler = Box<FnMut(SocketAddrV4, &[u8]) -> Option<Vec<u8>>>;
+type TcpHandler = Box<FnMut(SocketAddrV4, &[u8]) -> Vec<u8>>;
+
+pub(super) struct State {
+    config: Option<NetworkConfig>,
+    next_port: u16,
+    udp_handler: Option<UdpHandler>,
+    tcp_handler: Option<TcpHandler>,
+    pxe: Option<(Box<EFI_PXE_BASE_CODE_PROTOCOL>, Box<EFI_PXE_BASE_CODE_MODE>)>,
+    udp_binding: Box<EFI_SERVICE_BINDING_PROTOCOL>,
+    tcp_binding: Box<EFI_SERVICE_BINDING_PROTOCOL>,
+}
+
+impl State {
+    pub(super) fn new() -> Self {
+        State {
+            config: None,
+            next_port: EPHEMERAL_PORT_START,
+            udp_handler: None,
+            tcp_handler: None,
+            pxe: None,
+            udp_binding: Box::new(EFI_SERVICE_BINDING_PROTOCOL { CreateChild: create_udp_child, DestroyChild: destroy_udp_child }),
+            tcp_binding: Box::new(EFI_SERVICE_BINDING_PROTOCOL { CreateChild: create_tcp_child, DestroyChild: destroy_tcp_child }),
+        }
+    }
+
+    fn station(&self) -> Ipv4Addr {
+        self.config.as_ref().map(|c| c.ip).unwrap_or(Ipv4Addr::new(0, 0, 0, 0))
+    }
+
+    fn port(&mut self, requested: u16) -> u16 {
+        if requested != 0 {
+            return requested;
+        }
+
+        let port = self.next_port;
+        self.next_port = if port == u16::max_value() { EPHEMERAL_PORT_START } else { port + 1 };
+        port
+    }
+}
+
+impl MockEnv {
+    /// Adds a network interface that has already got its IP configuration from DHCP
+    pub fn add_network(&self, config: NetworkConfig) {
+        with_state(|s| {
+            let mut mode: Box<EFI_PXE_BASE_CODE_MODE> = Box::new(unsafe { mem::zeroed() });
+            mode.Started = TRUE;
+            mode.DhcpAckReceived = TRUE;
+            mode.TTL = 255;
+            mode.StationIp = EFI_IP_ADDRESS { v4: config.ip.into() };
+            mode.SubnetMask = EFI_IP_ADDRESS { v4: config.subnet_mask.into() };
+            unsafe { mode.DhcpAck.Raw[..].copy_from_slice(&dhcp_ack(&config)); }
+
+            let pxe = Box::new(EFI_PXE_BASE_CODE_PROTOCOL {
+                Revision: 0x00010000,
+                Start: stub!(succeed),
+                Stop: stub!(succeed),
+                Dhcp: stub!(succeed), // The configuration is in place already
+                Discover: stub!(unsupported),
+                Mtftp: stub!(unsupported),
+                UdpWrite: stub!(unsupported),
+                UdpRead: stub!(unsupported),
+                SetIpFilter: stub!(unsupported),
+                Arp: stub!(unsupported),
+                SetParameters: stub!(unsupported),
+                SetStationIp: stub!(unsupported),
+                SetPackets: stub!(unsupported),
+                Mode: &*mode,
+            });
+
+            let handle = s.install(None, EFI_PXE_BASE_CODE_PROTOCOL_GUID, &*pxe as *const _ as *const VOID);
+            let (udp_binding, tcp_binding) = (&*s.net.udp_binding as *const _ as *const VOID, &*s.net.tcp_binding as *const _ as *const VOID);
+            s.install(Some(handle), EFI_UDP4_SERVICE_BINDING_PROTOCOL_GUID, udp_binding);
+            s.install(Some(handle), EFI_TCP4_SERVICE_BINDING_PROTOCOL_GUID, tcp_binding);
+            s.net.pxe = Some((pxe, mode));
+            s.net.config = Some(config);
+        })
+    }
+
+    /// Sets the peer that answers UDP datagrams. It is given the destination and payload of each datagram
+    /// sent and returns the payload of the reply, if any, which appears to come from that destination.
+    pub fn on_udp<F: FnMut(SocketAddrV4, &[u8]) -> Option<Vec<u8>> + 'static>(&self, handler: F) {
+        with_state(|s| s.net.udp_handler = Some(Box::new(handler)));
+    }
+
+    /// Sets the peer that accepts TCP connections. It is given the remote address and the data of each write
+    /// and returns the data to send back, which can be empty. Connections are refused until a handler is set.
+    /// Reads past the end of everything the handler has returned see the connection closed by the peer.
+    pub fn on_tcp<F: FnMut(SocketAddrV4, &[u8]) -> Vec<u8> + 'static>(&self, handler: F) {
+        with_state(|s| s.net.tcp_handler = Some(Box::new(handler)));
+    }
+}
+
+fn dhcp_ack(config: &NetworkConfig) -> Vec<u8> {
+    let mut packet = vec![0_u8; 1472];
+    packet[0] = 2; // BOOTREPLY
+    packet[1] = 1; // Ethernet
+    packet[2] = 6;
+    packet[16..20].copy_from_slice(&config.ip.octets());
+    packet[20..24].copy_from_slice(&config.router.octets());
+    packet[DHCP_MAGIC_OFFSET..DHCP_OPTIONS_OFFSET].copy_from_slice(&DHCP_MAGIC);
+
+    fn add_option(options: &mut Vec<u8>, code: u8, addrs: &[Ipv4Addr]) {
+        options.push(code);
+        options.push((addrs.len() * 4) as u8);
+        for addr in addrs {
+            options.extend_from_slice(&addr.octets());
+        }
+    }
+
+    let mut options = vec![53, 1, DHCP_ACK];
+    add_option(&mut options, 54, &[config.router]); // The router doubles as the DHCP server like in QEMU
+    add_option(&mut options, 1, &[config.subnet_mask]);
+    add_option(&mut options, 3, &[config.router]);
+    if !config.dns_servers.is_empty() {
+        add_option(&mut options, 6, &config.dns_servers);
+    }
+    options.push(255);
+
+    packet[DHCP_OPTIONS_OFFSET..DHCP_OPTIONS_OFFSET + options.len()].copy_from_slice(&options);
+    packet
+}
+
+// Calls a handler taken out of the state so that it can itself use the firmware
+fn with_udp_handler(to: SocketAddrV4, data: &[u8]) -> Option<Vec<u8>> {
+    let mut handler = with_state(|s| s.net.udp_handler.take())?;
+    let reply = handler(to, data);
+    with_state(|s| if s.net.udp_handler.is_none() { s.net.udp_handler = Some(handler) });
+    reply
+}
+
+fn with_tcp_handler(to: SocketAddrV4, data: &[u8]) -> Option<Vec<u8>> {
+    let mut handler = with_state(|s| s.net.tcp_handler.take())?;
+    let reply = handler(to, data);
+    with_state(|s| if s.net.tcp_handler.is_none() { s.net.tcp_handler = Some(handler) });
+    Some(reply)
+}
+
+// Gathers the data of a transmit request from its fragment table
+unsafe fn gather(fragments: *const EFI_UDP4_FRAGMENT_DATA, count: UINT32) -> Vec<u8> {
+    let mut data = Vec::new();
+    for fragment in slice::from_raw_parts(fragments, count as usize) {
+        data.extend_from_slice(slice::from_raw_parts(fragment.FragmentBuffer as *const u8, fragment.FragmentLength as usize));
+    }
+    data
+}
+
+fn to_socket_addr(ip: EFI_IPv4_ADDRESS, port: u16) -> SocketAddrV4 {
+    SocketAddrV4::new(ip.into(), port)
+}
+
+fn install_child<P>(handle: *mut EFI_HANDLE, guid: &::ffi::EFI_GUID, child: Box<P>, set_handle: fn(&mut P, EFI_HANDLE)) -> EFI_STATUS {
+    if handle.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let child = Box::into_raw(child);
+    let installed = with_state(|s: &mut EnvState| s.install(None, *guid, child as *const VOID));
+    unsafe {
+        set_handle(&mut *child, installed);
+        *handle = installed;
+    }
+    EFI_SUCCESS
+}
+
+fn uninstall_child<P>(handle: *mut EFI_HANDLE, guid: &::ffi::EFI_GUID) -> Option<Box<P>> {
+    if handle.is_null() {
+        return None;
+    }
+
+    with_state(|s| {
+        let handle = unsafe { *handle };
+        let child = s.protocol(handle, guid).ok()?;
+        s.uninstall(handle, guid);
+        Some(unsafe { Box::from_raw(child as *mut P) })
+    })
+}
+
+// The protocol is the first field so that the pointer handed out can be cast back to the whole struct
+#[repr(C)]
+struct MockUdp {
+    protocol: EFI_UDP4_PROTOCOL,
+    handle: EFI_HANDLE,
+    config: Option<EFI_UDP4_CONFIG_DATA>,
+    pending: *const EFI_UDP4_COMPLETION_TOKEN,
+    incoming: Vec<(SocketAddrV4, Vec<u8>)>,
+    // The datagram last handed to the code under test. It stays valid until the next one is received.
+    received: Option<(Box<EFI_UDP4_RECEIVE_DATA>, Vec<u8>)>,
+}
+
+fn udp<'a>(this: *const EFI_UDP4_PROTOCOL) -> &'a mut MockUdp {
+    unsafe { &mut *(this as *mut MockUdp) }
+}
+
+extern "win64" fn create_udp_child(_this: *const EFI_SERVICE_BINDING_PROTOCOL, child_handle: *mut EFI_HANDLE) -> EFI_STATUS {
+    let child = Box::new(MockUdp {
+        protocol: EFI_UDP4_PROTOCOL {
+            GetModeData: udp_get_mode_data,
+            Configure: udp_configure,
+            Groups: stub!(unsupported),
+            Routes: stub!(succeed),
+            Transmit: udp_transmit,
+            Receive: udp_receive,
+            Cancel: udp_cancel,
+            Poll: udp_poll,
+        },
+        handle: ptr::null(),
+        config: None,
+        pending: ptr::null(),
+        incoming: Vec::new(),
+        received: None,
+    });
+
+    install_child(child_handle, &EFI_UDP4_PROTOCOL_GUID, child, |c, h| c.handle = h)
+}
+
+extern "win64" fn destroy_udp_child(_this: *const EFI_SERVICE_BINDING_PROTOCOL, child_handle: *mut EFI_HANDLE) -> EFI_STATUS {
+    match uninstall_child::<MockUdp>(child_handle, &EFI_UDP4_PROTOCOL_GUID) {
+        Some(_) => EFI_SUCCESS,
+        None => EFI_INVALID_PARAMETER,
+    }
+}
+
+extern "win64" fn udp_get_mode_data(this: *const EFI_UDP4_PROTOCOL, udp4_config_data: *mut EFI_UDP4_CONFIG_DATA, ip4_mode_data: *mut EFI_IP4_MODE_DATA, _mnp_config_data: *mut EFI_MANAGED_NETWORK_CONFIG_DATA, _snp_mode_data: *mut EFI_SIMPLE_NETWORK_MODE) -> EFI_STATUS {
+    let udp = udp(this);
+    let config = match udp.config {
+        Some(ref config) => config,
+        None => return EFI_NOT_STARTED,
+    };
+
+    unsafe {
+        if !udp4_config_data.is_null() {
+            *udp4_config_data = config.clone();
+        }
+        if !ip4_mode_data.is_null() {
+            (*ip4_mode_data).IsStarted = TRUE;
+            (*ip4_mode_data).IsConfigured = TRUE;
+        }
+    }
+
+    EFI_SUCCESS
+}
+
+extern "win64" fn udp_configure(this: *const EFI_UDP4_PROTOCOL, udp_config_data: *const EFI_UDP4_CONFIG_DATA) -> EFI_STATUS {
+    let udp = udp(this);
+    if udp_config_data.is_null() {
+        udp.config = None; // Resets the instance
+        cancel_udp_receive(udp);
+        return EFI_SUCCESS;
+    }
+
+    if udp.config.is_some() {
+        return EFI_ACCESS_DENIED; // Must be reset before being configured again
+    }
+
+    let mut config = unsafe { (*udp_config_data).clone() };
+    with_state(|s| {
+        if config.UseDefaultAddress == TRUE {
+            config.StationAddress = s.net.station().into();
+        }
+        config.StationPort = s.net.port(config.StationPort);
+    });
+
+    udp.config = Some(config);
+    EFI_SUCCESS
+}
+
+extern "win64" fn udp_transmit(this: *const EFI_UDP4_PROTOCOL, token: *const EFI_UDP4_COMPLETION_TOKEN) -> EFI_STATUS {
+    let udp = udp(this);
+    if token.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let (to, data) = unsafe {
+        let tx = (*token).Packet.TxData;
+        if tx.is_null() {
+            return EFI_INVALID_PARAMETER;
+        }
+
+        let to = match (udp.config.as_ref(), (*tx).UdpSessionData.as_ref()) {
+            (None, _) => return EFI_NOT_STARTED,
+            (_, Some(session)) => to_socket_addr(session.DestinationAddress, session.DestinationPort),
+            (Some(config), None) => to_socket_addr(config.RemoteAddress, config.RemotePort),
+        };
+
+        (to, gather((*tx).FragmentTable.as_ptr(), (*tx).FragmentCount))
+    };
+
+    if to.port() == 0 {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    if let Some(reply) = with_udp_handler(to, &data) {
+        udp.incoming.push((to, reply));
+    }
+
+    complete_udp(token, EFI_SUCCESS);
+    EFI_SUCCESS
+}
+
+extern "win64" fn udp_receive(this: *const EFI_UDP4_PROTOCOL, token: *const EFI_UDP4_COMPLETION_TOKEN) -> EFI_STATUS {
+    let udp = udp(this);
+    if token.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    if udp.config.is_none() {
+        return EFI_NOT_STARTED;
+    }
+
+    if !udp.pending.is_null() {
+        return EFI_ACCESS_DENIED; // Only one receive at a time
+    }
+
+    udp.pending = token;
+    deliver_udp(udp);
+    EFI_SUCCESS
+}
+
+extern "win64" fn udp_cancel(this: *const EFI_UDP4_PROTOCOL, token: *const EFI_UDP4_COMPLETION_TOKEN) -> EFI_STATUS {
+    let udp = udp(this);
+    if udp.pending.is_null() || !(token.is_null() || token == udp.pending) {
+        return EFI_NOT_FOUND;
+    }
+
+    cancel_udp_receive(udp);
+    EFI_SUCCESS
+}
+
+extern "win64" fn udp_poll(this: *const EFI_UDP4_PROTOCOL) -> EFI_STATUS {
+    if deliver_udp(udp(this)) { EFI_SUCCESS } else { EFI_NOT_READY }
+}
+
+fn cancel_udp_receive(udp: &mut MockUdp) {
+    let token = mem::replace(&mut udp.pending, ptr::null());
+    if !token.is_null() {
+        complete_udp(token, EFI_ABORTED);
+    }
+}
+
+// Hands the next incoming datagram to the pending receive if there are both
+fn deliver_udp(udp: &mut MockUdp) -> bool {
+    if udp.pending.is_null() || udp.incoming.is_empty() {
+        return false;
+    }
+
+    let (from, data) = udp.incoming.remove(0);
+    let local = match udp.config {
+        Some(ref c) => to_socket_addr(c.StationAddress, c.StationPort),
+        None => return false,
+    };
+
+    let rx = Box::new(EFI_UDP4_RECEIVE_DATA {
+        TimeStamp: with_state(|s| s.runtime.time()).into(),
+        RecycleSignal: ptr::null(),
+        UdpSession: EFI_UDP4_SESSION_DATA {
+            SourceAddress: (*from.ip()).into(),
+            SourcePort: from.port(),
+            DestinationAddress: (*local.ip()).into(),
+            DestinationPort: local.port(),
+        },
+        DataLength: data.len() as UINT32,
+        FragmentCount: 1,
+        FragmentTable: [EFI_UDP4_FRAGMENT_DATA { FragmentLength: data.len() as UINT32, FragmentBuffer: data.as_ptr() as *const VOID }],
+    });
+
+    let token = mem::replace(&mut udp.pending, ptr::null()) as *mut EFI_UDP4_COMPLETION_TOKEN;
+    unsafe { (*token).Packet.RxData = &*rx; }
+    udp.received = Some((rx, data));
+    complete_udp(token, EFI_SUCCESS);
+    true
+}
+
+fn complete_udp(token: *const EFI_UDP4_COMPLETION_TOKEN, status: EFI_STATUS) {
+    let token = token as *mut EFI_UDP4_COMPLETION_TOKEN;
+    unsafe {
+        (*token).Status = status;
+        signal_event((*token).Event);
+    }
+}
+
+#[repr(C)]
+struct MockTcp {
+    protocol: EFI_TCP4_PROTOCOL,
+    handle: EFI_HANDLE,
+    access_point: Option<EFI_TCP4_ACCESS_POINT>,
+    connected: bool,
+    pending: *const EFI_TCP4_IO_TOKEN,
+    incoming: Vec<u8>,
+}
+
+fn tcp<'a>(this: *const EFI_TCP4_PROTOCOL) -> &'a mut MockTcp {
+    unsafe { &mut *(this as *mut MockTcp) }
+}
+
+extern "win64" fn create_tcp_child(_this: *const EFI_SERVICE_BINDING_PROTOCOL, child_handle: *mut EFI_HANDLE) -> EFI_STATUS {
+    let child = Box::new(MockTcp {
+        protocol: EFI_TCP4_PROTOCOL {
+            GetModeData: tcp_get_mode_data,
+            Configure: tcp_configure,
+            Routes: stub!(succeed),
+            Connect: tcp_connect,
+            Accept: stub!(unsupported),
+            Transmit: tcp_transmit,
+            Receive: tcp_receive,
+            Close: tcp_close,
+            Cancel: tcp_cancel,
+            Poll: tcp_poll,
+        },
+        handle: ptr::null(),
+        access_point: None,
+        connected: false,
+        pending: ptr::null(),
+        incoming: Vec::new(),
+    });
+
+    install_child(child_handle, &EFI_TCP4_PROTOCOL_GUID, child, |c, h| c.handle = h)
+}
+
+extern "win64" fn destroy_tcp_child(_this: *const EFI_SERVICE_BINDING_PROTOCOL, child_handle: *mut EFI_HANDLE) -> EFI_STATUS {
+    match uninstall_child::<MockTcp>(child_handle, &EFI_TCP4_PROTOCOL_GUID) {
+        Some(_) => EFI_SUCCESS,
+        None => EFI_INVALID_PARAMETER,
+    }
+}
+
+fn copy_access_point(ap: &EFI_TCP4_ACCESS_POINT) -> EFI_TCP4_ACCESS_POINT {
+    EFI_TCP4_ACCESS_POINT {
+        UseDefaultAddress: ap.UseDefaultAddress,
+        StationAddress: ap.StationAddress,
+        SubnetMask: ap.SubnetMask,
+        StationPort: ap.StationPort,
+        RemoteAddress: ap.RemoteAddress,
+        RemotePort: ap.RemotePort,
+        ActiveFlag: ap.ActiveFlag,
+    }
+}
+
+extern "win64" fn tcp_get_mode_data(this: *const EFI_TCP4_PROTOCOL, tcp4_state: *mut EFI_TCP4_CONNECTION_STATE, tcp4_config_data: *mut EFI_TCP4_CONFIG_DATA, ip4_mode_data: *mut EFI_IP4_MODE_DATA, _mnp_config_data: *mut EFI_MANAGED_NETWORK_CONFIG_DATA, _snp_mode_data: *mut EFI_SIMPLE_NETWORK_MODE) -> EFI_STATUS {
+    let tcp = tcp(this);
+    let access_point = match tcp.access_point {
+        Some(ref ap) => ap,
+        None => return EFI_NOT_STARTED,
+    };
+
+    unsafe {
+        if !tcp4_state.is_null() {
+            *tcp4_state = if tcp.connected { EFI_TCP4_CONNECTION_STATE::Tcp4StateEstablished } else { EFI_TCP4_CONNECTION_STATE::Tcp4StateClosed };
+        }
+        if !tcp4_config_data.is_null() {
+            (*tcp4_config_data).TimeToLive = 255;
+            (*tcp4_config_data).AccessPoint = copy_access_point(access_point);
+        }
+        if !ip4_mode_data.is_null() {
+            (*ip4_mode_data).IsStarted = TRUE;
+            (*ip4_mode_data).IsConfigured = TRUE;
+        }
+    }
+
+    EFI_SUCCESS
+}
+
+extern "win64" fn tcp_configure(this: *const EFI_TCP4_PROTOCOL, tcp_config_data: *const EFI_TCP4_CONFIG_DATA) -> EFI_STATUS {
+    let tcp = tcp(this);
+    if tcp_config_data.is_null() {
+        tcp.access_point = None; // Resets the instance
+        tcp.connected = false;
+        return EFI_SUCCESS;
+    }
+
+    if tcp.access_point.is_some() {
+        return EFI_ACCESS_DENIED;
+    }
+
+    let mut access_point = copy_access_point(unsafe { &(*tcp_config_data).AccessPoint });
+    with_state(|s| {
+        if access_point.UseDefaultAddress == TRUE {
+            access_point.StationAddress = s.net.station().into();
+        }
+        access_point.StationPort = s.net.port(access_point.StationPort);
+    });
+
+    tcp.access_point = Some(access_point);
+    EFI_SUCCESS
+}
+
+extern "win64" fn tcp_connect(this: *const EFI_TCP4_PROTOCOL, connection_token: *mut EFI_TCP4_CONNECTION_TOKEN) -> EFI_STATUS {
+    let tcp = tcp(this);
+    if connection_token.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    if tcp.access_point.is_none() {
+        return EFI_NOT_STARTED;
+    }
+
+    tcp.connected = with_state(|s| s.net.tcp_handler.is_some());
+    let status = if tcp.connected { EFI_SUCCESS } else { EFI_CONNECTION_REFUSED };
+    complete_tcp(unsafe { &(*connection_token).CompletionToken }, status);
+    EFI_SUCCESS
+}
+
+extern "win64" fn tcp_transmit(this: *const EFI_TCP4_PROTOCOL, token: *const EFI_TCP4_IO_TOKEN) -> EFI_STATUS {
+    let tcp = tcp(this);
+    if token.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let remote = match tcp.access_point {
+        Some(ref ap) if tcp.connected => to_socket_addr(ap.RemoteAddress, ap.RemotePort),
+        _ => return EFI_NOT_STARTED,
+    };
+
+    let data = unsafe {
+        let tx = (*token).Packet.TxData;
+        if tx.is_null() {
+            return EFI_INVALID_PARAMETER;
+        }
+
+        // The fragment layout is the same as UDP's
+        gather((*tx).FragmentTable.as_ptr() as *const EFI_UDP4_FRAGMENT_DATA, (*tx).FragmentCount)
+    };
+
+    if let Some(reply) = with_tcp_handler(remote, &data) {
+        tcp.incoming.extend_from_slice(&reply);
+    }
+
+    complete_tcp(unsafe { &(*token).CompletionToken }, EFI_SUCCESS);
+    EFI_SUCCESS
+}
+
+extern "win64" fn tcp_receive(this: *const EFI_TCP4_PROTOCOL, token: *const EFI_TCP4_IO_TOKEN) -> EFI_STATUS {
+    let tcp = tcp(this);
+    if token.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    if !tcp.connected {
+        return EFI_NOT_STARTED;
+    }
+
+    if !tcp.pending.is_null() {
+        return EFI_ACCESS_DENIED;
+    }
+
+    tcp.pending = token;
+    EFI_SUCCESS
+}
+
+extern "win64" fn tcp_close(this: *const EFI_TCP4_PROTOCOL, close_token: *const EFI_TCP4_CLOSE_TOKEN) -> EFI_STATUS {
+    let tcp = tcp(this);
+    if close_token.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    if !tcp.connected {
+        return EFI_NOT_STARTED;
+    }
+
+    tcp.connected = false;
+    tcp.incoming.clear();
+    let pending = mem::replace(&mut tcp.pending, ptr::null());
+    if !pending.is_null() {
+        complete_tcp(unsafe { &(*pending).CompletionToken }, EFI_ABORTED);
+    }
+
+    complete_tcp(unsafe { &(*close_token).CompletionToken }, EFI_SUCCESS);
+    EFI_SUCCESS
+}
+
+extern "win64" fn tcp_cancel(this: *const EFI_TCP4_PROTOCOL, token: *const EFI_TCP4_COMPLETION_TOKEN) -> EFI_STATUS {
+    let tcp = tcp(this);
+    let pending = tcp.pending;
+    if pending.is_null() || !(token.is_null() || token == unsafe { &(*pending).CompletionToken } as *const _) {
+        return EFI_NOT_FOUND;
+    }
+
+    tcp.pending = ptr::null();
+    complete_tcp(unsafe { &(*pending).CompletionToken }, EFI_ABORTED);
+    EFI_SUCCESS
+}
+
+// Completes the pending receive with whatever the peer has sent or, once that has all been read,
+// with the peer having closed the connection
+extern "win64" fn tcp_poll(this: *const EFI_TCP4_PROTOCOL) -> EFI_STATUS {
+    let tcp = tcp(this);
+    let token = mem::replace(&mut tcp.pending, ptr::null()) as *mut EFI_TCP4_IO_TOKEN;
+    if token.is_null() {
+        return EFI_SUCCESS;
+    }
+
+    if tcp.incoming.is_empty() {
+        complete_tcp(unsafe { &(*token).CompletionToken }, EFI_CONNECTION_FIN);
+        return EFI_SUCCESS;
+    }
+
+    unsafe {
+        let rx = (*token).Packet.RxData as *mut ::ffi::tcp4::EFI_TCP4_RECEIVE_DATA;
+        let fragment = &mut (*rx).FragmentTable[0];
+        let len = cmp::min(cmp::min((*rx).DataLength, fragment.FragmentLength) as usize, tcp.incoming.len());
+        ptr::copy_nonoverlapping(tcp.incoming.as_ptr(), fragment.FragmentBuffer as *mut u8, len);
+        tcp.incoming.drain(..len);
+        fragment.FragmentLength = len as UINT32;
+        (*rx).DataLength = len as UINT32;
+        complete_tcp(&(*token).CompletionToken, EFI_SUCCESS);
+    }
+
+    EFI_SUCCESS
+}
+
+fn complete_tcp(token: *const EFI_TCP4_COMPLETION_TOKEN, status: EFI_STATUS) {
+    let token = token as *mut EFI_TCP4_COMPLETION_TOKEN;
+    unsafe {
+        (*token).Status = status;
+        signal_event((*token).Event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NetworkConfig;
+    use mock::install;
+    use net::{TcpStream, UdpSocket, IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs, dhcp};
+    use io::{Read, Write};
+    use alloc::Vec;
+    use core::time::Duration;
+
+    // A DNS response with one A record answering a query for `name`
+    fn dns_response(query: &[u8], addr: Ipv4Addr) -> Vec<u8> {
+        let mut response = query.to_vec();
+        response[2] |= 0x80; // QR: this is a response
+        response[7] = 1; // ANCOUNT
+        response.extend_from_slice(&[0xC0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4]); // Name pointer, A, IN, TTL, length
+        response.extend_from_slice(&addr.octets());
+        response
+    }
+
+    #[test]
+    fn dhcp_config_comes_from_the_network_config() {
+        let env = install();
+        env.add_network(NetworkConfig::default());
+        let config = dhcp::cached_dhcp_config().unwrap().unwrap();
+        assert_eq!(config.ip(), IpAddr::V4(Ipv4Addr::new(10, 0, 2, 15)));
+        assert_eq!(config.dns_server_addrs(), &[IpAddr::V4(Ipv4Addr::new(10, 0, 2, 3))]);
+    }
+
+    #[test]
+    fn host_names_are_resolved_through_the_udp_handler() {
+        let env = install();
+        env.add_network(NetworkConfig::default());
+        env.on_udp(|to, query| {
+            assert_eq!(to, SocketAddrV4::new(Ipv4Addr::new(10, 0, 2, 3), 53));
+            Some(dns_response(query, Ipv4Addr::new(93, 184, 216, 34)))
+        });
+
+        let addrs = ("example.com", 80).to_socket_addrs().unwrap().collect::<Vec<_>>();
+        assert_eq!(addrs, vec![SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(93, 184, 216, 34), 80))]);
+    }
+
+    #[test]
+    fn udp_receive_times_out_on_the_simulated_clock() {
+        let env = install();
+        env.add_network(NetworkConfig::default());
+        env.on_udp(|_, _| None);
+
+        let mut socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        socket.send_to(b"ping", "10.0.2.2:7").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let mut buf = [0_u8; 16];
+        assert!(socket.recv(&mut buf).is_err());
+        assert!(env.elapsed() >= Duration::from_secs(2));
+    }
+
+    #[test]
+    fn tcp_streams_talk_to_the_tcp_handler() {
+        let env = install();
+        env.add_network(NetworkConfig::default());
+        assert!(TcpStream::connect("10.0.2.2:80").is_err());
+
+        env.on_tcp(|to, request| {
+            assert_eq!(to.port(), 80);
+            request.iter().rev().cloned().collect()
+        });
+
+        let mut stream = TcpStream::connect("10.0.2.2:80").unwrap();
+        stream.write_all(b"hello").unwrap();
+        let mut reply = [0_u8; 5];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply, b"olleh");
+        // The refused attempt took the first ephemeral port
+        assert_eq!(stream.local_addr().unwrap(), SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(10, 0, 2, 15), 49153)));
+    }
+}
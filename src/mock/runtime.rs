@@ -0,0 +1,310 @@
+// Runtime services of the mock firmware: variables kept in memory and a real time clock that the test sets
+
+use ffi::{
+    EFI_STATUS,
+    EFI_SUCCESS,
+    EFI_UNSUPPORTED,
+    EFI_INVALID_PARAMETER,
+    EFI_NOT_FOUND,
+    EFI_BUFFER_TOO_SMALL,
+    EFI_OUT_OF_RESOURCES,
+    EFI_GUID,
+    EFI_TIME,
+    EFI_TIME_CAPABILITIES,
+    BOOLEAN,
+    FALSE,
+    CHAR16,
+    UINT32,
+    UINT64,
+    UINTN,
+    VOID,
+    runtime_services::{
+        EFI_RUNTIME_SERVICES,
+        EFI_RESET_TYPE,
+        EFI_VARIABLE_APPEND_WRITE,
+    },
+};
+use ucs2::CStr16;
+use time::DateTime;
+use alloc::Vec;
+use core::{ptr, mem, slice};
+use super::{MockEnv, with_state, unsupported};
+use utils::to_null_terminated_utf16;
+use Guid;
+
+// Sizes reported by QueryVariableInfo(). Variables are kept in memory so these are only enforced on SetVariable().
+const MAX_STORAGE: u64 = 64 * 1024;
+const MAX_VARIABLE_SIZE: u64 = 32 * 1024;
+
+struct Variable {
+    name: Vec<CHAR16>, // Without the null
+    guid: EFI_GUID,
+    attributes: UINT32,
+    data: Vec<u8>,
+}
+
+impl Variable {
+    fn size(&self) -> u64 {
+        (self.name.len() * 2 + self.data.len()) as u64
+    }
+}
+
+pub(super) struct State {
+    variables: Vec<Variable>,
+    time: EFI_TIME,
+}
+
+impl State {
+    pub(super) fn new() -> Self {
+        State { variables: Vec::new(), time: DateTime::new(2000, 1, 1, 0, 0, 0).into() }
+    }
+
+    pub(super) fn time(&self) -> DateTime {
+        self.time.into()
+    }
+
+    fn position(&self, name: &[CHAR16], guid: &EFI_GUID) -> Option<usize> {
+        self.variables.iter().position(|v| v.name == name && v.guid == *guid)
+    }
+
+    fn used_storage(&self) -> u64 {
+        self.variables.iter().map(|v| v.size()).sum()
+    }
+}
+
+pub(super) fn services() -> EFI_RUNTIME_SERVICES {
+    EFI_RUNTIME_SERVICES {
+        Hdr: unsafe { mem::zeroed() },
+        GetTime: get_time,
+        SetTime: ptr::null(),
+        GetWakeupTime: get_wakeup_time,
+        SetWakeupTime: stub!(unsupported),
+        SetVirtualAddressMap: stub!(unsupported),
+        ConvertPointer: stub!(unsupported),
+        GetVariable: get_variable,
+        GetNextVariableName: get_next_variable_name,
+        SetVariable: set_variable,
+        GetNextHighMonotonicCount: stub!(unsupported),
+        ResetSystem: reset_system,
+        UpdateCapsule: ptr::null(),
+        QueryCapsuleCapabilities: ptr::null(),
+        QueryVariableInfo: query_variable_info,
+    }
+}
+
+impl MockEnv {
+    /// Sets the time that the real time clock reports. It starts at midnight on 1 January 2000 and doesn't move on its own.
+    pub fn set_time(&self, time: DateTime) {
+        with_state(|s| s.runtime.time = time.into());
+    }
+
+    /// The data of a variable as it currently is in the mock's variable store
+    pub fn variable(&self, name: &str, vendor_guid: &Guid) -> Option<Vec<u8>> {
+        let mut name = to_null_terminated_utf16(name);
+        name.pop();
+        with_state(|s| s.runtime.position(&name, vendor_guid).map(|i| s.runtime.variables[i].data.clone()))
+    }
+}
+
+extern "win64" fn get_time(time: *mut EFI_TIME, capabilities: *mut EFI_TIME_CAPABILITIES) -> EFI_STATUS {
+    if time.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    unsafe {
+        *time = with_state(|s| s.runtime.time);
+        if !capabilities.is_null() {
+            (*capabilities).Resolution = 1;
+            (*capabilities).Accuracy = 50_000_000; // 50 ppm like a typical PC RTC
+            (*capabilities).SetsToZero = FALSE;
+        }
+    }
+
+    EFI_SUCCESS
+}
+
+extern "win64" fn get_wakeup_time(_enabled: *mut BOOLEAN, _pending: *mut BOOLEAN, _time: *mut EFI_TIME) -> EFI_STATUS {
+    EFI_UNSUPPORTED // The mock has no wakeup alarm
+}
+
+fn name_of<'a>(name: *const CHAR16) -> &'a [CHAR16] {
+    unsafe { CStr16::from_ptr(name) }.as_slice()
+}
+
+extern "win64" fn get_variable(variable_name: *const CHAR16, vendor_guid: *const EFI_GUID, attributes: *mut UINT32, data_size: *mut UINTN, data: *mut VOID) -> EFI_STATUS {
+    if variable_name.is_null() || vendor_guid.is_null() || data_size.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    with_state(|s| {
+        let variable = match s.runtime.position(name_of(variable_name), unsafe { &*vendor_guid }) {
+            Some(i) => &s.runtime.variables[i],
+            None => return EFI_NOT_FOUND,
+        };
+
+        unsafe {
+            if !attributes.is_null() {
+                *attributes = variable.attributes;
+            }
+
+            let available = *data_size;
+            *data_size = variable.data.len();
+            if available < variable.data.len() {
+                return EFI_BUFFER_TOO_SMALL;
+            }
+
+            if !variable.data.is_empty() {
+                ptr::copy_nonoverlapping(variable.data.as_ptr(), data as *mut u8, variable.data.len());
+            }
+        }
+
+        EFI_SUCCESS
+    })
+}
+
+// Variables are enumerated in the order they were created. An empty name starts the enumeration.
+extern "win64" fn get_next_variable_name(variable_name_size: *mut UINTN, variable_name: *mut CHAR16, vendor_guid: *mut EFI_GUID) -> EFI_STATUS {
+    if variable_name_size.is_null() || variable_name.is_null() || vendor_guid.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    with_state(|s| {
+        let current = name_of(variable_name);
+        let next = if current.is_empty() {
+            0
+        } else {
+            match s.runtime.position(current, unsafe { &*vendor_guid }) {
+                Some(i) => i + 1,
+                None => return EFI_INVALID_PARAMETER,
+            }
+        };
+
+        let variable = match s.runtime.variables.get(next) {
+            Some(v) => v,
+            None => return EFI_NOT_FOUND,
+        };
+
+        unsafe {
+            let needed = (variable.name.len() + 1) * 2;
+            let available = *variable_name_size;
+            *variable_name_size = needed;
+            if available < needed {
+                return EFI_BUFFER_TOO_SMALL;
+            }
+
+            let buf = slice::from_raw_parts_mut(variable_name, variable.name.len() + 1);
+            buf[..variable.name.len()].copy_from_slice(&variable.name);
+            buf[variable.name.len()] = 0;
+            *vendor_guid = variable.guid;
+        }
+
+        EFI_SUCCESS
+    })
+}
+
+extern "win64" fn set_variable(variable_name: *const CHAR16, vendor_guid: *const EFI_GUID, attributes: UINT32, data_size: UINTN, data: *const VOID) -> EFI_STATUS {
+    if variable_name.is_null() || vendor_guid.is_null() || (data_size > 0 && data.is_null()) {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let name = name_of(variable_name);
+    if name.is_empty() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    let guid = unsafe { *vendor_guid };
+    let data = if data_size == 0 { &[][..] } else { unsafe { slice::from_raw_parts(data as *const u8, data_size) } };
+    let append = attributes & EFI_VARIABLE_APPEND_WRITE != 0;
+    let attributes = attributes & !EFI_VARIABLE_APPEND_WRITE;
+
+    with_state(|s| {
+        let state = &mut s.runtime;
+        let existing = state.position(name, &guid);
+
+        // Zero attributes or no data (unless appending) deletes the variable
+        if attributes == 0 || (data.is_empty() && !append) {
+            return match existing {
+                Some(i) => {
+                    state.variables.remove(i);
+                    EFI_SUCCESS
+                },
+                None => EFI_NOT_FOUND,
+            };
+        }
+
+        let new_data = match existing {
+            Some(i) if append => {
+                let mut d = state.variables[i].data.clone();
+                d.extend_from_slice(data);
+                d
+            },
+            _ => data.to_vec(),
+        };
+
+        let old_size = existing.map(|i| state.variables[i].size()).unwrap_or(0);
+        let new_size = (name.len() * 2 + new_data.len()) as u64;
+        if new_size > MAX_VARIABLE_SIZE || state.used_storage() - old_size + new_size > MAX_STORAGE {
+            return EFI_OUT_OF_RESOURCES;
+        }
+
+        match existing {
+            Some(i) => {
+                if state.variables[i].attributes != attributes {
+                    return EFI_INVALID_PARAMETER; // The spec doesn't allow attributes to change without deleting first
+                }
+                state.variables[i].data = new_data;
+            },
+            None => state.variables.push(Variable { name: name.to_vec(), guid, attributes, data: new_data }),
+        }
+
+        EFI_SUCCESS
+    })
+}
+
+extern "win64" fn query_variable_info(_attributes: UINT32, maximum_variable_storage_size: *mut UINT64, remaining_variable_storage_size: *mut UINT64, maximum_variable_size: *mut UINT64) -> EFI_STATUS {
+    let used = with_state(|s| s.runtime.used_storage());
+    unsafe {
+        *maximum_variable_storage_size = MAX_STORAGE;
+        *remaining_variable_storage_size = MAX_STORAGE - used;
+        *maximum_variable_size = MAX_VARIABLE_SIZE;
+    }
+
+    EFI_SUCCESS
+}
+
+extern "win64" fn reset_system(reset_type: EFI_RESET_TYPE, reset_status: EFI_STATUS, _data_size: UINTN, _reset_data: *const VOID) {
+    panic!("the system was reset ({:?}, status {:#x})", reset_type, reset_status);
+}
+
+#[cfg(test)]
+mod tests {
+    use mock::install;
+    use variables::{self, Attributes};
+    use time::{self, DateTime};
+    use ffi::runtime_services::EFI_GLOBAL_VARIABLE;
+
+    #[test]
+    fn variables_round_trip() {
+        let env = install();
+        let attributes = Attributes::NON_VOLATILE | Attributes::BOOTSERVICE_ACCESS;
+        variables::set("Greeting", &EFI_GLOBAL_VARIABLE, attributes, b"hello").unwrap();
+        variables::set("Greeting", &EFI_GLOBAL_VARIABLE, attributes | Attributes::APPEND_WRITE, b" world").unwrap();
+
+        let (data, read_attributes) = variables::get_with_attributes("Greeting", &EFI_GLOBAL_VARIABLE).unwrap();
+        assert_eq!(data, b"hello world");
+        assert_eq!(read_attributes, attributes);
+        assert_eq!(env.variable("Greeting", &EFI_GLOBAL_VARIABLE).unwrap(), b"hello world");
+
+        variables::delete("Greeting", &EFI_GLOBAL_VARIABLE).unwrap();
+        assert_eq!(variables::try_get("Greeting", &EFI_GLOBAL_VARIABLE).unwrap(), None);
+        assert_eq!(variables::storage_info(attributes).unwrap().remaining_storage, super::MAX_STORAGE);
+    }
+
+    #[test]
+    fn clock_reports_the_set_time() {
+        let env = install();
+        let time = DateTime::new(2024, 2, 29, 23, 59, 58);
+        env.set_time(time);
+        assert_eq!(time::now().unwrap(), time);
+    }
+}
@@ -0,0 +1,223 @@
+use ffi::{
+    mp_services::*,
+    EFI_SUCCESS,
+    EFI_NOT_STARTED,
+    UINTN,
+    UINT32,
+    VOID,
+};
+use utils::{locate_protocol, as_micros_saturating};
+use alloc::Vec;
+use core::{ptr, mem, time::Duration};
+use {Result, to_boolean};
+
+// Running code on the application processors (APs) through EFI_MP_SERVICES_PROTOCOL.
+//
+// Code running on an AP must not call boot services. That rules out most of this crate, including heap
+// allocation, the console and events, and leaves little more than plain computation on memory the
+// bootstrap processor (BSP) has already set up. All the calls here except `current_processor()` must
+// be made on the BSP.
+
+/// Where a processor sits in the physical topology
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ProcessorLocation {
+    pub package: u32,
+    pub core: u32,
+    pub thread: u32,
+}
+
+/// Information about a processor
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ProcessorInfo {
+    /// The number other calls identify the processor with
+    pub number: usize,
+    /// The processor's APIC ID on x86
+    pub id: u64,
+    pub is_bsp: bool,
+    pub is_enabled: bool,
+    pub is_healthy: bool,
+    pub location: ProcessorLocation,
+}
+
+impl ProcessorInfo {
+    fn from_raw(number: usize, info: &EFI_PROCESSOR_INFORMATION) -> Self {
+        ProcessorInfo {
+            number,
+            id: info.ProcessorId,
+            is_bsp: info.StatusFlag & PROCESSOR_AS_BSP_BIT != 0,
+            is_enabled: info.StatusFlag & PROCESSOR_ENABLED_BIT != 0,
+            is_healthy: info.StatusFlag & PROCESSOR_HEALTH_STATUS_BIT != 0,
+            location: ProcessorLocation {
+                package: info.Location.Package,
+                core: info.Location.Core,
+                thread: info.Location.Thread,
+            },
+        }
+    }
+}
+
+/// How `MpServices::startup_all_aps()` runs the closure on the APs
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Execution {
+    /// All APs at the same time
+    Parallel,
+    /// One AP after another in processor number order
+    Serial,
+}
+
+/// The firmware's multiprocessor services
+pub struct MpServices {
+    protocol: *const EFI_MP_SERVICES_PROTOCOL,
+}
+
+impl MpServices {
+    pub fn get() -> Result<Self> {
+        Ok(MpServices { protocol: locate_protocol(&EFI_MP_SERVICES_PROTOCOL_GUID)? })
+    }
+
+    /// The number of processors, including the BSP and disabled processors
+    pub fn processor_count(&self) -> Result<usize> {
+        Ok(self.counts()?.0)
+    }
+
+    /// The number of enabled processors, including the BSP
+    pub fn enabled_processor_count(&self) -> Result<usize> {
+        Ok(self.counts()?.1)
+    }
+
+    fn counts(&self) -> Result<(usize, usize)> {
+        let (mut total, mut enabled) = (0, 0);
+        unsafe {
+            ret_on_err!(((*self.protocol).GetNumberOfProcessors)(self.protocol, &mut total, &mut enabled));
+        }
+
+        Ok((total, enabled))
+    }
+
+    pub fn processor_info(&self, number: usize) -> Result<ProcessorInfo> {
+        let mut info: EFI_PROCESSOR_INFORMATION = unsafe { mem::zeroed() };
+        unsafe {
+            ret_on_err!(((*self.protocol).GetProcessorInfo)(self.protocol, number, &mut info));
+        }
+
+        Ok(ProcessorInfo::from_raw(number, &info))
+    }
+
+    /// All processors in processor number order
+    pub fn processors(&self) -> Result<Vec<ProcessorInfo>> {
+        (0..self.processor_count()?).map(|n| self.processor_info(n)).collect()
+    }
+
+    /// The number of the processor the caller runs on. Unlike the other calls this one can be made from an AP.
+    pub fn current_processor(&self) -> Result<usize> {
+        let mut number = 0;
+        unsafe {
+            ret_on_err!(((*self.protocol).WhoAmI)(self.protocol, &mut number));
+        }
+
+        Ok(number)
+    }
+
+    /// Runs the closure on every enabled AP and waits for all of them to finish. The closure gets the
+    /// number of the processor it runs on. Succeeds without running anything if there are no enabled APs.
+    ///
+    /// If the timeout expires first the firmware stops the APs still running and the call fails with `Timeout`.
+    ///
+    /// # Safety
+    ///
+    /// The closure must not call boot services, directly or indirectly. That includes allocating or freeing
+    /// memory, printing, creating or signaling events and panicking. It must not touch anything that isn't
+    /// safe to use from several processors at once either, which `Sync` doesn't fully cover since the crate's
+    /// globals assume a single processor.
+    pub unsafe fn startup_all_aps<F: Fn(usize) + Sync>(&self, execution: Execution, timeout: Option<Duration>, f: &F) -> Result<()> {
+        let context = ApContext { protocol: self.protocol, f };
+        let single_thread = to_boolean(execution == Execution::Serial);
+        let status = ((*self.protocol).StartupAllAPs)(self.protocol, run_on_ap::<F>, single_thread, ptr::null(), as_timeout(timeout), &context as *const _ as *const VOID, ptr::null_mut());
+
+        match status {
+            EFI_SUCCESS | EFI_NOT_STARTED => Ok(()),
+            status => Err(status.into()),
+        }
+    }
+
+    /// Runs the closure on the given AP and waits for it to finish. The closure gets the number of the processor.
+    ///
+    /// If the timeout expires first the firmware stops the AP and the call fails with `Timeout`.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `startup_all_aps()`: the closure must not call boot services, directly or indirectly.
+    pub unsafe fn startup_this_ap<F: Fn(usize) + Sync>(&self, number: usize, timeout: Option<Duration>, f: &F) -> Result<()> {
+        let context = ApContext { protocol: self.protocol, f };
+        ret_on_err!(((*self.protocol).StartupThisAP)(self.protocol, run_on_ap::<F>, number, ptr::null(), as_timeout(timeout), &context as *const _ as *const VOID, ptr::null_mut()));
+        Ok(())
+    }
+
+    /// Makes the AP available to `startup_all_aps()` again and marks it healthy
+    pub fn enable_ap(&self, number: usize) -> Result<()> {
+        let health = PROCESSOR_HEALTH_STATUS_BIT;
+        self.enable_disable_ap(number, true, &health)
+    }
+
+    /// Takes the AP out of `startup_all_aps()`. It can still be started with `startup_this_ap()`.
+    pub fn disable_ap(&self, number: usize) -> Result<()> {
+        self.enable_disable_ap(number, false, ptr::null())
+    }
+
+    fn enable_disable_ap(&self, number: usize, enable: bool, health: *const UINT32) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).EnableDisableAP)(self.protocol, number, to_boolean(enable), health));
+        }
+
+        Ok(())
+    }
+}
+
+struct ApContext<'a, F: 'a> {
+    protocol: *const EFI_MP_SERVICES_PROTOCOL,
+    f: &'a F,
+}
+
+extern "win64" fn run_on_ap<F: Fn(usize) + Sync>(argument: *const VOID) {
+    unsafe {
+        let context = &*(argument as *const ApContext<F>);
+        let mut number = 0;
+        ((*context.protocol).WhoAmI)(context.protocol, &mut number); // Can't fail on a processor the firmware started
+        (context.f)(number);
+    }
+}
+
+fn as_timeout(timeout: Option<Duration>) -> UINTN {
+    match timeout {
+        // Zero means no timeout to the firmware, so a zero duration is rounded up
+        Some(timeout) => as_micros_saturating(timeout).max(1) as UINTN,
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn processor_info_is_decoded() {
+        let raw = EFI_PROCESSOR_INFORMATION {
+            ProcessorId: 6,
+            StatusFlag: PROCESSOR_ENABLED_BIT | PROCESSOR_HEALTH_STATUS_BIT,
+            Location: EFI_CPU_PHYSICAL_LOCATION { Package: 1, Core: 2, Thread: 1 },
+        };
+
+        let info = ProcessorInfo::from_raw(3, &raw);
+        assert_eq!(info.number, 3);
+        assert_eq!(info.id, 6);
+        assert!(!info.is_bsp && info.is_enabled && info.is_healthy);
+        assert_eq!(info.location, ProcessorLocation { package: 1, core: 2, thread: 1 });
+    }
+
+    #[test]
+    fn timeouts_are_in_microseconds() {
+        assert_eq!(as_timeout(None), 0);
+        assert_eq!(as_timeout(Some(Duration::from_millis(1500))), 1_500_000);
+        assert_eq!(as_timeout(Some(Duration::from_secs(0))), 1);
+    }
+}
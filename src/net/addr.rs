@@ -1,8 +1,9 @@
 use ffi::{EFI_IPv4_ADDRESS, EFI_IPv6_ADDRESS, EFI_IP_ADDRESS};
 use core::{mem, fmt, iter, slice, option, cmp::Ordering};
 use io;
-use alloc::{String, vec, Vec};
-use super::dns::lookup_host;
+use alloc::{String, vec};
+#[cfg(feature = "dns")] use alloc::Vec;
+#[cfg(feature = "dns")] use super::resolver::lookup_host;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Ipv4Addr(EFI_IPv4_ADDRESS);
@@ -658,6 +659,32 @@ impl fmt::Display for IpAddr {
     }
 }
 
+// Addresses are serialized in their textual form, e.g. "10.0.2.15"
+macro_rules! impl_serde_as_str {
+    ($t:ty, $expecting:expr) => {
+        #[cfg(feature = "with-serde")]
+        impl ::serde::Serialize for $t {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        #[cfg(feature = "with-serde")]
+        impl<'de> ::serde::Deserialize<'de> for $t {
+            fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                ::utils::deserialize_from_str(deserializer, $expecting)
+            }
+        }
+    }
+}
+
+impl_serde_as_str!(Ipv4Addr, "an IPv4 address");
+impl_serde_as_str!(Ipv6Addr, "an IPv6 address");
+impl_serde_as_str!(IpAddr, "an IP address");
+impl_serde_as_str!(SocketAddrV4, "an IPv4 socket address");
+impl_serde_as_str!(SocketAddrV6, "an IPv6 socket address");
+impl_serde_as_str!(SocketAddr, "a socket address");
+
 impl From<IpAddr> for EFI_IP_ADDRESS {
     fn from(ip: IpAddr) -> EFI_IP_ADDRESS {
         match ip {
@@ -855,12 +882,19 @@ impl ToSocketAddrs for (Ipv6Addr, u16) {
 }
 
 #[allow(deprecated)]
+#[cfg(feature = "dns")]
 fn resolve_socket_addr(hostname: &str, port: u16) -> io::Result<vec::IntoIter<SocketAddr>> {
-    let ip_addrs = lookup_host(hostname).map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to resolve host name"))?;
+    let ip_addrs = lookup_host(hostname).map_err(io::Error::from)?;
     let sock_addrs: Vec<_> = ip_addrs.into_iter().map(|ip| SocketAddr::new(ip, port)).collect();
     Ok(sock_addrs.into_iter())
 }
 
+// Without the DNS resolver only literal addresses can be used
+#[cfg(not(feature = "dns"))]
+fn resolve_socket_addr(_hostname: &str, _port: u16) -> io::Result<vec::IntoIter<SocketAddr>> {
+    Err(io::Error::new(io::ErrorKind::InvalidInput, "host names can't be resolved without the dns feature"))
+}
+
 impl<'a> ToSocketAddrs for (&'a str, u16) {
     type Iter = vec::IntoIter<SocketAddr>;
     fn to_socket_addrs(&self) -> io::Result<vec::IntoIter<SocketAddr>> {
@@ -15,8 +15,16 @@ use ffi::{
         EFI_PXE_BASE_CODE_TFTP_ERROR,
         EFI_PXE_BASE_CODE_TFTP_OPCODE,
         EFI_PXE_BASE_CODE_MTFTP_INFO,
+        EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL,
+        EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL_GUID,
+        EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL_REVISION,
+        EFI_PXE_BASE_CODE_FUNCTION,
+        EFI_PXE_BASE_CODE_CALLBACK_STATUS,
     },
+    boot_services::EFI_INTERFACE_TYPE,
     EFI_IP_ADDRESS,
+    EFI_HANDLE,
+    UINT32,
     UINT16,
     BOOLEAN,
     VOID,
@@ -25,18 +33,20 @@ use ffi::{
 use {
     EfiError,
     EfiErrorKind,
+    ResultExt,
     Result,
     to_boolean,
     from_boolean,
     to_res,
-    system_table,
+    boot_services,
     net::{IpAddr, Ipv4Addr},
     NullTerminatedAsciiStr,
 };
 
 use core::{self, slice, mem, ptr, default::Default};
-use utils::{to_ptr, Wrapper, to_opt};
-use alloc::{String, Vec};
+use utils::{to_ptr, Wrapper, to_opt, handles_by_protocol, locate_protocol, open_protocol};
+use alloc::{String, Vec, boxed::Box};
+#[cfg(feature = "tpm")] use security::measure::auto_measure;
 
 // TODO: THIS WHOLE MODULE NEEDS A COMPLETE OVERHAUL. 
 // The API surface area needs to be complete redesigned including things like:
@@ -57,6 +67,7 @@ use alloc::{String, Vec};
 
 // TODO: should we expose other packets like DhcpDiscover as well?
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
 pub struct DhcpConfig {
     ip: IpAddr,
     subnet_mask: IpAddr,
@@ -152,6 +163,7 @@ impl DhcpConfig {
     }
 }
 
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
 pub struct BootServerConfig {
     boot_server_ip: IpAddr,
     boot_file: String,
@@ -188,7 +200,7 @@ impl BootServerConfig {
 // and getting config from each.
 pub fn cached_dhcp_config() -> Result<Option<DhcpConfig>> {
     let pxe = locate_pxe_protocol()?;
-    let mode = pxe.mode().ok_or_else::<EfiError, _>(|| EfiErrorKind::ProtocolError.into())?;
+    let mode = pxe_mode(pxe)?;
 
     if !mode.dhcp_ack_received() {
         return Ok(None)
@@ -197,6 +209,12 @@ pub fn cached_dhcp_config() -> Result<Option<DhcpConfig>> {
     Ok(Some(DhcpConfig::new(&mode)))
 }
 
+/// Same as `cached_dhcp_config()` but fails with `NotStarted` if DHCP has not happened yet
+pub(crate) fn require_cached_dhcp_config() -> Result<DhcpConfig> {
+    cached_dhcp_config()?
+        .ok_or_else(|| EfiError::from(EfiErrorKind::NotStarted).add_context("DHCP has not been run"))
+}
+
 pub fn run_dhcp() -> Result<DhcpConfig> {
     // TODO: see tianocore-edk2\NetworkPkg\UefiPxeBcDxe\PxeBcBoot.c file to know to implement PXE sequence especially the method PxeBcDiscoverBootFile
 
@@ -206,20 +224,20 @@ pub fn run_dhcp() -> Result<DhcpConfig> {
     // It's too much work to maintain a the set of thin wrappers like this.
     let pxe = locate_pxe_protocol()?;
 
-    let mode = pxe.mode().ok_or_else::<EfiError, _>(|| EfiErrorKind::ProtocolError.into())?;
+    let mode = pxe_mode(pxe)?;
 
     if !mode.started(){
         let use_ipv6 = false;
-        pxe.start(use_ipv6)?;
+        pxe.start(use_ipv6).context("starting PXE base code")?;
     }
 
     let sort_offers = false; // TODO: may want to expose this out to the caller
-    pxe.dhcp(sort_offers)?;
+    pxe.dhcp(sort_offers).context("running DHCP")?;
 
     // The above code will result in the config being cached.
     // So return that
     let config = cached_dhcp_config()?
-        .ok_or_else::<EfiError, _>(|| EfiErrorKind::ProtocolError.into())?;
+        .ok_or_else(|| EfiError::from(EfiErrorKind::ProtocolError).add_context("DHCP completed without an ACK"))?;
 
     Ok(config)
 }
@@ -227,10 +245,10 @@ pub fn run_dhcp() -> Result<DhcpConfig> {
 pub fn set_proxy_offer(pxe_reply_packet: &Dhcpv4Packet) -> Result<()> {
     let pxe = locate_pxe_protocol()?;
 
-    let mode = pxe.mode().ok_or_else::<EfiError, _>(|| EfiErrorKind::ProtocolError.into())?;
+    let mode = pxe_mode(pxe)?;
 
     if !mode.started(){
-        return Err(EfiErrorKind::NotReady.into());
+        return Err(not_started());
     }
 
     let inner = unsafe { EFI_PXE_BASE_CODE_PACKET { Dhcpv4: *pxe_reply_packet.inner_ptr() }};
@@ -246,7 +264,7 @@ pub fn set_proxy_offer(pxe_reply_packet: &Dhcpv4Packet) -> Result<()> {
         Some(packet),
         None,
         None,
-        None)?;
+        None).context("setting PXE proxy offer")?;
 
     Ok(())
 }
@@ -254,10 +272,10 @@ pub fn set_proxy_offer(pxe_reply_packet: &Dhcpv4Packet) -> Result<()> {
 pub fn mtftp_get_file_size(server_ip: &IpAddr, filename: &NullTerminatedAsciiStr) -> Result<u64> {
     let pxe = locate_pxe_protocol()?;
 
-    let mode = pxe.mode().ok_or_else::<EfiError, _>(|| EfiErrorKind::ProtocolError.into())?;
+    let mode = pxe_mode(pxe)?;
 
     if !mode.started() {
-        return Err(EfiErrorKind::NotReady.into());
+        return Err(not_started());
     }
 
     let filename_ptr: *const u8 = filename.as_ptr();
@@ -265,7 +283,7 @@ pub fn mtftp_get_file_size(server_ip: &IpAddr, filename: &NullTerminatedAsciiStr
     let server_ip_ptr: *const EFI_IP_ADDRESS = &server_ip_efi as *const EFI_IP_ADDRESS;
     let file_size: u64 = 0;
     pxe.mtftp(EFI_PXE_BASE_CODE_TFTP_OPCODE::EFI_PXE_BASE_CODE_TFTP_GET_FILE_SIZE, ptr::null(), false, &file_size as *const u64, ptr::null(),
-        server_ip_ptr, filename_ptr, ptr::null(), false)?;
+        server_ip_ptr, filename_ptr, ptr::null(), false).context("getting TFTP file size")?;
 
     Ok(file_size)
 }
@@ -273,15 +291,15 @@ pub fn mtftp_get_file_size(server_ip: &IpAddr, filename: &NullTerminatedAsciiStr
 pub fn mtftp_get_file(server_ip: &IpAddr, filename: &NullTerminatedAsciiStr) -> Result<Vec<u8>> {
     let pxe = locate_pxe_protocol()?;
 
-    let mode = pxe.mode().ok_or_else::<EfiError, _>(|| EfiErrorKind::ProtocolError.into())?;
+    let mode = pxe_mode(pxe)?;
 
     if !mode.started() {
-        return Err(EfiErrorKind::NotReady.into());
+        return Err(not_started());
     }
 
     let file_size = mtftp_get_file_size(server_ip, filename)?;
     if file_size > core::usize::MAX as u64 {
-        return Err(EfiErrorKind::BadBufferSize.into());
+        return Err(EfiError::from(EfiErrorKind::BadBufferSize).add_context("TFTP file does not fit in memory"));
     }
 
     let filename_ptr: *const u8 = filename.as_ptr();
@@ -292,11 +310,109 @@ pub fn mtftp_get_file(server_ip: &IpAddr, filename: &NullTerminatedAsciiStr) ->
     let buffer_ptr = file.as_ptr() as *const VOID;
 
     pxe.mtftp(EFI_PXE_BASE_CODE_TFTP_OPCODE::EFI_PXE_BASE_CODE_TFTP_READ_FILE, buffer_ptr, false, &file_size as *const u64, ptr::null(),
-        server_ip_ptr, filename_ptr, ptr::null(), false)?;
+        server_ip_ptr, filename_ptr, ptr::null(), false).context("reading TFTP file")?;
+
+    #[cfg(feature = "tpm")]
+    auto_measure(&format!("tftp://{}/{}", server_ip, filename), &file)?;
+    Ok(file)
+}
+
+/// Same as `mtftp_get_file()` but calls `progress` with the number of bytes received so far and the size of the file
+/// as the download goes on. If the firmware can't report progress it's only called at the start and the end.
+pub fn mtftp_get_file_with_progress(server_ip: &IpAddr, filename: &NullTerminatedAsciiStr, progress: &mut FnMut(u64, u64)) -> Result<Vec<u8>> {
+    let file_size = mtftp_get_file_size(server_ip, filename)?;
+    progress(0, file_size);
+
+    let pxe = locate_pxe_protocol()?;
+    let callback = Box::new(MtftpProgress {
+        proto: EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL { Revision: EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL_REVISION, Callback: mtftp_progress_callback },
+        received: 0,
+        total: file_size,
+        progress: progress,
+    });
+
+    // The base code only calls back through a callback protocol installed on its own handle
+    let installed_on = match pxe_handle(pxe) {
+        Ok(handle) => install_pxe_callback(handle, pxe, &callback.proto).ok(),
+        Err(_) => None
+    };
+
+    let result = mtftp_get_file(server_ip, filename);
+
+    if let Some(handle) = installed_on {
+        uninstall_pxe_callback(handle, pxe, &callback.proto);
+    }
 
+    let file = result?;
+    (callback.progress)(file.len() as u64, file_size);
     Ok(file)
 }
 
+#[repr(C)] // So that we can get back to this struct from the protocol pointer passed to mtftp_progress_callback
+struct MtftpProgress<'a> {
+    proto: EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL,
+    received: u64,
+    total: u64,
+    progress: &'a mut FnMut(u64, u64),
+}
+
+const TFTP_OPCODE_DATA: u16 = 3;
+const TFTP_DATA_HEADER_LEN: u32 = 4;
+
+extern "win64" fn mtftp_progress_callback(
+    this: *const EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL,
+    function: EFI_PXE_BASE_CODE_FUNCTION,
+    received: BOOLEAN,
+    packet_len: UINT32,
+    packet: *const EFI_PXE_BASE_CODE_PACKET) -> EFI_PXE_BASE_CODE_CALLBACK_STATUS {
+    // For MTFTP the packet is the TFTP packet i.e. a big endian opcode, the block number and then the data
+    if function == EFI_PXE_BASE_CODE_FUNCTION::EFI_PXE_BASE_CODE_FUNCTION_MTFTP && from_boolean(received) && packet_len > TFTP_DATA_HEADER_LEN && !packet.is_null() {
+        let opcode = unsafe { u16::from_be(*(packet as *const u16)) };
+        if opcode == TFTP_OPCODE_DATA {
+            let state: &mut MtftpProgress = unsafe { mem::transmute(this) };
+            state.received = core::cmp::min(state.received + (packet_len - TFTP_DATA_HEADER_LEN) as u64, state.total); // Retransmissions can take us past the total
+            (state.progress)(state.received, state.total);
+        }
+    }
+
+    EFI_PXE_BASE_CODE_CALLBACK_STATUS::EFI_PXE_BASE_CODE_CALLBACK_STATUS_CONTINUE
+}
+
+// Finds the handle the given PXE base code instance is installed on
+fn pxe_handle(pxe: &PxeBaseCodeProtocol) -> Result<EFI_HANDLE> {
+    for handle in handles_by_protocol(&EFI_PXE_BASE_CODE_PROTOCOL_GUID)? {
+        if open_protocol::<EFI_PXE_BASE_CODE_PROTOCOL>(handle, &EFI_PXE_BASE_CODE_PROTOCOL_GUID).ok() == Some(pxe.inner_ptr()) {
+            return Ok(handle);
+        }
+    }
+
+    Err(EfiErrorKind::NotFound.into())
+}
+
+fn install_pxe_callback(handle: EFI_HANDLE, pxe: &PxeBaseCodeProtocol, callback: &EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL) -> Result<EFI_HANDLE> {
+    let bs = boot_services();
+    let mut handle = handle;
+    unsafe {
+        ret_on_err!(((*bs).InstallProtocolInterface)(&mut handle, &EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL_GUID, EFI_INTERFACE_TYPE::EFI_NATIVE_INTERFACE, callback as *const EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL as *const VOID));
+    }
+
+    // The base code looks the callback protocol up when callbacks get turned on
+    if let Err(e) = pxe.set_parameters(None, None, None, None, Some(true)) {
+        uninstall_pxe_callback(handle, pxe, callback);
+        return Err(e);
+    }
+
+    Ok(handle)
+}
+
+fn uninstall_pxe_callback(handle: EFI_HANDLE, pxe: &PxeBaseCodeProtocol, callback: &EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL) {
+    let bs = boot_services();
+    let _ = pxe.set_parameters(None, None, None, None, Some(false));
+    unsafe {
+        ((*bs).UninstallProtocolInterface)(handle, &EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL_GUID, callback as *const EFI_PXE_BASE_CODE_CALLBACK_PROTOCOL as *const VOID); // Can't do anything if this fails
+    }
+}
+
 // TODO: allow user to specify discovery options such as whether to do unicast, broadcast or multicast 
 // and list of boot servers to use for unicast etc.
 pub fn run_boot_server_discovery(_dhcp_config: &DhcpConfig) -> Result<BootServerConfig> {
@@ -308,14 +424,14 @@ pub fn run_boot_server_discovery(_dhcp_config: &DhcpConfig) -> Result<BootServer
     // and use raw ffi types here (except for packet wrapper types etc. we can keep thos)
     // It's too much work to maintain a the set of thin wrappers like this.
     let pxe = locate_pxe_protocol()?;
-    pxe.discover(BootType::Bootstrap, BOOT_LAYER_INITIAL, false, Some(&info))?; 
+    pxe.discover(BootType::Bootstrap, BOOT_LAYER_INITIAL, false, Some(&info)).context("discovering boot servers")?;
 
-    let mode = pxe.mode().ok_or_else::<EfiError, _>(|| EfiErrorKind::ProtocolError.into())?;
+    let mode = pxe_mode(pxe)?;
     // TODO: Is it safe to rely on proxy_offer() for getting boot file? Some question:
     // 1. If there are multiple proxy offers received, which one is recorded by UEFI in this field?
     // 2. What if there are zero proxy offers received and the bootfile was sent in the DHCP offer?
     if !mode.proxy_offer_received() {
-        return Err(EfiErrorKind::ProtocolError.into());
+        return Err(EfiError::from(EfiErrorKind::ProtocolError).add_context("no proxy offer received during discovery"));
     }
 
     Ok(BootServerConfig::new(mode))
@@ -323,12 +439,16 @@ pub fn run_boot_server_discovery(_dhcp_config: &DhcpConfig) -> Result<BootServer
 
 
 fn locate_pxe_protocol<'a>() -> Result<&'a PxeBaseCodeProtocol> {
-    let bs = (*system_table()).BootServices;
-    let mut pxe_protocol: *const EFI_PXE_BASE_CODE_PROTOCOL = ptr::null_mut();
-    unsafe {
-        ret_on_err!(((*bs).LocateProtocol)(&EFI_PXE_BASE_CODE_PROTOCOL_GUID, ptr::null_mut() as *mut VOID, mem::transmute(&mut pxe_protocol)));
-        Ok(mem::transmute(pxe_protocol))
-    }
+    let pxe_protocol: *const EFI_PXE_BASE_CODE_PROTOCOL = locate_protocol(&EFI_PXE_BASE_CODE_PROTOCOL_GUID).context("locating PXE base code protocol")?;
+    Ok(unsafe { mem::transmute(pxe_protocol) })
+}
+
+fn pxe_mode(pxe: &PxeBaseCodeProtocol) -> Result<&Mode> {
+    pxe.mode().ok_or_else(|| EfiError::from(EfiErrorKind::ProtocolError).add_context("PXE base code has no mode data"))
+}
+
+fn not_started() -> EfiError {
+    EfiError::from(EfiErrorKind::NotReady).add_context("PXE base code has not been started")
 }
 
 // TODO: This is a lot of boilerplate. Can we find a way to generate this code?
@@ -373,6 +493,25 @@ impl PxeBaseCodeProtocol {
         to_res((), status)
     }
 
+    pub fn set_parameters(&self,
+        new_auto_arp: Option<bool>,
+        new_send_guid: Option<bool>,
+        new_ttl: Option<u8>,
+        new_tos: Option<u8>,
+        new_make_callback: Option<bool>) -> Result<()> {
+            let true_ptr: *const BOOLEAN = &1;
+            let false_ptr: *const BOOLEAN = &0;
+            let map_bool_opt = |b: Option<bool>| b.map_or(ptr::null(), |v| if v { true_ptr } else { false_ptr });
+
+            let status = (self.0.SetParameters)(&self.0,
+                                map_bool_opt(new_auto_arp),
+                                map_bool_opt(new_send_guid),
+                                new_ttl.as_ref().map_or(ptr::null(), |v| v as *const u8),
+                                new_tos.as_ref().map_or(ptr::null(), |v| v as *const u8),
+                                map_bool_opt(new_make_callback));
+            to_res((), status)
+        }
+
     pub fn set_packets(&self, 
         new_dhcp_discover_valid: Option<bool>, 
         new_dhcp_ack_received: Option<bool>, 
@@ -825,6 +964,23 @@ impl Dhcpv4Packet {
     }
 }
 
+/// Serialized as the BOOTP addresses and the list of DHCP options
+#[cfg(feature = "with-serde")]
+impl ::serde::Serialize for Dhcpv4Packet {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Dhcpv4Packet", 6)?;
+        state.serialize_field("opcode", &self.bootp_opcode())?;
+        state.serialize_field("client_ip", &Ipv4Addr::from(*self.bootp_ci_addr()))?;
+        state.serialize_field("your_ip", &Ipv4Addr::from(*self.bootp_yi_addr()))?;
+        state.serialize_field("server_ip", &Ipv4Addr::from(*self.bootp_si_addr()))?;
+        state.serialize_field("relay_agent_ip", &Ipv4Addr::from(*self.bootp_gi_addr()))?;
+        state.serialize_field("options", &self.dhcp_options().collect::<Vec<_>>())?;
+        state.end()
+    }
+}
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct Dhcpv6Packet(EFI_PXE_BASE_CODE_DHCPV6_PACKET);
@@ -915,6 +1071,7 @@ impl TftpError {
  
 // TODO: Move all of this DHCP parsing code into a separate crate (called dhcparse) 
 // so other applications, such as those for testing, can use it as well.
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
 pub struct DhcpOption<'a> {
     code: u8,
     val: Option<&'a[u8]>,
@@ -980,6 +1137,7 @@ impl<'a> Iterator for DhcpOptionIter<'a> {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub enum DhcpMessageType {
     Discover = 1,
     Offer = 2,
@@ -992,6 +1150,7 @@ pub enum DhcpMessageType {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub enum DhcpOpCode {
     Request = 1,
     Reply = 2,
@@ -114,8 +114,8 @@ impl Builder {
 
 #[cfg(test)]
 mod test {
-    use QueryType as QT;
-    use QueryClass as QC;
+    use net::dns::QueryType as QT;
+    use net::dns::QueryClass as QC;
     use super::Builder;
 
     #[test]
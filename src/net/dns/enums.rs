@@ -6,6 +6,7 @@ use super::rdata::*;
 ///
 /// All "EXPERIMENTAL" markers here are from the RFC
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub enum Type {
     /// a host addresss
     A = a::Record::TYPE,
@@ -51,7 +52,7 @@ pub enum Type {
 ///
 /// All "EXPERIMENTAL" markers here are from the RFC
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-//#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub enum QueryType {
     /// a host addresss
     A = a::Record::TYPE,
@@ -100,6 +101,7 @@ pub enum QueryType {
 
 /// The CLASS value according to RFC 1035
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub enum Class {
     /// the Internet
     IN = 1,
@@ -114,6 +116,7 @@ pub enum Class {
 
 /// The QCLASS value according to RFC 1035
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub enum QueryClass {
     /// the Internet
     IN = 1,
@@ -130,6 +133,7 @@ pub enum QueryClass {
 
 /// The OPCODE value according to RFC 1035
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub enum Opcode {
     /// Normal query
     StandardQuery,
@@ -144,6 +148,7 @@ pub enum Opcode {
 //quick_error! {
     /// The RCODE value according to RFC 1035
     #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    #[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
     #[allow(missing_docs)] // names are from spec
     pub enum ResponseCode {
         NoError,
@@ -17,6 +17,7 @@ mod flag {
 
 /// Represents parsed header of the packet
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 #[allow(missing_docs)] // fields are from the spec I think
 pub struct Header {
     pub id: u16,
@@ -103,9 +104,9 @@ impl Header {
 #[cfg(test)]
 mod test {
 
-    use {Header};
-    use Opcode::*;
-    use ResponseCode::NoError;
+    use net::dns::{Header};
+    use net::dns::Opcode::*;
+    use net::dns::ResponseCode::NoError;
 
     #[test]
     fn parse_example_query() {
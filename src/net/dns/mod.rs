@@ -13,10 +13,6 @@
 //!
 #![warn(missing_docs)]
 
-#[cfg(test)] #[macro_use] extern crate matches;
-//#[macro_use(quick_error)] extern crate quick_error;
-//#[cfg(feature = "with-serde")] #[macro_use] extern crate serde_derive;
-
 mod enums;
 mod structs;
 mod name;
@@ -35,76 +31,143 @@ pub use self::header::{Header};
 pub use self::rdata::{RData};
 pub use self::builder::{Builder};
 
-use core::{time::Duration};
-use super::{UdpSocket, SocketAddr, IpAddr};
 use alloc::Vec;
-use net::dhcp;
+use net::{IpAddr, Ipv4Addr};
+
+// This module only builds and parses packets. It doesn't know about sockets, PXE or anything else EFI so that
+// it can be tested and fuzzed on the host and used over any transport that can carry a DNS message (see
+// `Transport`). The resolver the rest of the crate uses is in net::resolver.
 
-struct DnsServer {
-    addr: SocketAddr
+/// Carries a DNS query to a server and the response back, e.g. over a UDP socket or PXE's UdpWrite/UdpRead
+pub trait Transport {
+    /// The error the transport fails with
+    type Error;
+
+    /// Sends the query and receives the response into `response`, returning its length
+    fn exchange(&mut self, query: &[u8], response: &mut [u8]) -> Result<usize, Self::Error>;
 }
 
-const DNS_TIMEOUT: Duration = Duration::from_secs(30);
-// TODO: Swallowing/transmorgifying all errors. Fix this large scale shit wherever present
-impl DnsServer {
-    fn query(&self, hostname: &str) -> ::Result<Vec<IpAddr>> {
-        use net::dns::rdata::a::Record;
-        let mut builder = Builder::new_query(1, true);
-        builder.add_question(hostname, false, QueryType::A, QueryClass::IN);
-        let packet = builder.build().map_err(|_| ::EfiErrorKind::DeviceError)?; 
-        let mut socket = UdpSocket::bind("0.0.0.0:0")?;
-        socket.send_to(&packet, self.addr)?;
-        let mut buf = [0u8; 4096];
-        socket.set_read_timeout(Some(DNS_TIMEOUT))?;
-        socket.recv(&mut buf)?;
-        let pkt = Packet::parse(&buf).unwrap();
-        if pkt.header.response_code != ResponseCode::NoError {
-            // return Err(pkt.header.response_code.into());
-            return Err(::EfiErrorKind::DeviceError.into());
-        }
+/// Why `lookup_ipv4()` failed
+#[derive(Debug)]
+pub enum LookupError<E> {
+    /// The query couldn't be built, e.g. because the name is too long
+    InvalidName,
+    /// The transport failed
+    Transport(E),
+    /// The response is malformed
+    Parse(Error),
+    /// The response is for some other query
+    UnexpectedResponse,
+    /// The name doesn't exist
+    NameNotFound,
+    /// The server failed the query with the given code
+    ServerFailed(ResponseCode),
+    /// The name exists but has no IPv4 addresses
+    NoAddresses,
+}
 
-        if pkt.answers.len() == 0 {
-            return Err(::EfiErrorKind::DeviceError.into());
-        }
+/// The largest DNS response over UDP we accept (the EDNS0 limit commonly used in practice)
+pub const MAX_RESPONSE_LEN: usize = 4096;
+
+/// Resolves `hostname` to its IPv4 addresses by asking the server at the other end of the transport
+pub fn lookup_ipv4<T: Transport>(transport: &mut T, id: u16, hostname: &str) -> Result<Vec<Ipv4Addr>, LookupError<T::Error>> {
+    let query = build_ipv4_query(id, hostname)?;
+    let mut buf = [0u8; MAX_RESPONSE_LEN];
+    let len = transport.exchange(&query, &mut buf).map_err(LookupError::Transport)?;
+    parse_ipv4_response(id, &buf[..len])
+}
 
-        let addrs = pkt.answers.iter()
-                            .filter_map(|a| { 
-                                match a.data {
-                                    RData::A(Record(addr)) => Some(IpAddr::V4(addr)),
-                                    _ => None
-                                }
-                            }).collect::<Vec<_>>();
-        Ok(addrs)
+/// Builds a recursive query for the A records of `hostname`
+pub fn build_ipv4_query<E>(id: u16, hostname: &str) -> Result<Vec<u8>, LookupError<E>> {
+    let hostname = if hostname.ends_with('.') { &hostname[..hostname.len() - 1] } else { hostname };
+    if hostname.is_empty() || hostname.len() > 253 || hostname.split('.').any(|label| label.is_empty() || label.len() > 63) {
+        return Err(LookupError::InvalidName);
     }
+
+    let mut builder = Builder::new_query(id, true);
+    builder.add_question(hostname, false, QueryType::A, QueryClass::IN);
+    builder.build().map_err(|_| LookupError::InvalidName)
 }
 
-pub (crate) fn lookup_host(hostname: &str) -> ::Result<Vec<IpAddr>> {
-    let dns_servers = get_dns_servers()?;
-    if dns_servers.is_empty() {
-        return Err(::EfiErrorKind::DeviceError.into());
+/// The IPv4 addresses in the response to the query with the given ID
+pub fn parse_ipv4_response<E>(id: u16, response: &[u8]) -> Result<Vec<Ipv4Addr>, LookupError<E>> {
+    let packet = Packet::parse(response).map_err(LookupError::Parse)?;
+    if packet.header.query || packet.header.id != id {
+        return Err(LookupError::UnexpectedResponse);
     }
 
-    for dns_server in dns_servers {
-        let addrs = match dns_server.query(hostname) {
-            Ok(addrs) => addrs,
-            Err(_) => continue
-        };
-        if !addrs.is_empty() {
-            return Ok(addrs);
-        }
+    match packet.header.response_code {
+        ResponseCode::NoError => {},
+        ResponseCode::NameError => return Err(LookupError::NameNotFound),
+        code => return Err(LookupError::ServerFailed(code)),
+    }
+
+    let addrs = packet.answers.iter()
+        .filter_map(|a| match a.data {
+            RData::A(rdata::a::Record(addr)) => Some(addr),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if addrs.is_empty() {
+        return Err(LookupError::NoAddresses);
     }
 
-    Ok(Vec::new())
+    Ok(addrs)
 }
 
-fn get_dns_servers() -> ::Result<Vec<DnsServer>> {
-    // TODO: Assuming here that PXE has already happened. Should we kick it off here if it hasn't?
-    const DNS_PORT: u16 = 53;
-    let dns_servers = dhcp::cached_dhcp_config()?
-        .ok_or_else(|| ::EfiError::from(::EfiErrorKind::DeviceError))?
-        .dns_server_addrs().iter()
-        .map(|ip| DnsServer { addr: (*ip, DNS_PORT).into() })
-        .collect::<Vec<_>>();
+/// `lookup_ipv4()` with the addresses as `IpAddr`s
+pub fn lookup<T: Transport>(transport: &mut T, id: u16, hostname: &str) -> Result<Vec<IpAddr>, LookupError<T::Error>> {
+    Ok(lookup_ipv4(transport, id, hostname)?.into_iter().map(IpAddr::V4).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(dns_servers)
+    // Answers every query with a canned response whose ID is patched to match
+    struct Canned(&'static [u8]);
+
+    impl Transport for Canned {
+        type Error = ();
+
+        fn exchange(&mut self, query: &[u8], response: &mut [u8]) -> Result<usize, ()> {
+            response[..self.0.len()].copy_from_slice(self.0);
+            response[..2].copy_from_slice(&query[..2]);
+            Ok(self.0.len())
+        }
+    }
+
+    const EXAMPLE_COM: &[u8] = b"\x00\x00\x81\x80\x00\x01\x00\x01\x00\x00\x00\x00\
+                                 \x07example\x03com\x00\x00\x01\x00\x01\
+                                 \xc0\x0c\x00\x01\x00\x01\x00\x00\x04\xf8\x00\x04\x5d\xb8\xd8\x22";
+
+    #[test]
+    fn names_are_resolved_over_any_transport() {
+        let addrs = lookup_ipv4(&mut Canned(EXAMPLE_COM), 7, "example.com").unwrap();
+        assert_eq!(addrs, vec![Ipv4Addr::new(93, 184, 216, 34)]);
+    }
+
+    #[test]
+    fn failed_lookups_are_reported() {
+        let not_found = b"\x00\x07\x81\x83\x00\x00\x00\x00\x00\x00\x00\x00";
+        match parse_ipv4_response::<()>(7, not_found) {
+            Err(LookupError::NameNotFound) => {},
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        match parse_ipv4_response::<()>(8, not_found) {
+            Err(LookupError::UnexpectedResponse) => {},
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        match parse_ipv4_response::<()>(7, &not_found[..6]) {
+            Err(LookupError::Parse(Error::HeaderTooShort)) => {},
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        match build_ipv4_query::<()>(1, "bad..name") {
+            Err(LookupError::InvalidName) => {},
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
 }
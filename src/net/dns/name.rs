@@ -127,6 +127,14 @@ impl<'a> fmt::Display for Name<'a> {
         }
     }
 }
+/// Names are serialized in their dotted form with any compression pointers followed
+#[cfg(feature = "with-serde")]
+impl<'a> ::serde::Serialize for Name<'a> {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl<'a> fmt::Debug for Name<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt.debug_tuple("Name")
@@ -137,16 +145,19 @@ impl<'a> fmt::Debug for Name<'a> {
 
 #[cfg(test)]
 mod test {
-    use Error;
-    use Name;
+    use alloc::string::ToString;
+    use net::dns::Error;
+    use net::dns::Name;
 
     #[test]
     fn parse_badpointer_same_offset() {
         // A buffer where an offset points to itself,
         // which is a bad compression pointer.
         let same_offset = vec![192, 2, 192, 2];
-        let is_match = matches!(Name::scan(&same_offset, &same_offset),
-                                Err(Error::BadPointer));
+        let is_match = match Name::scan(&same_offset, &same_offset) {
+            Err(Error::BadPointer) => true,
+            _ => false,
+        };
 
         assert!(is_match);
     }
@@ -156,8 +167,10 @@ mod test {
         // A buffer where the offsets points back to each other which causes
         // infinite recursion if never checked, a bad compression pointer.
         let forwards_offset = vec![192, 2, 192, 4, 192, 2];
-        let is_match = matches!(Name::scan(&forwards_offset, &forwards_offset),
-                                Err(Error::BadPointer));
+        let is_match = match Name::scan(&forwards_offset, &forwards_offset) {
+            Err(Error::BadPointer) => true,
+            _ => false,
+        };
 
         assert!(is_match);
     }
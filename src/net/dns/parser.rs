@@ -162,15 +162,16 @@ fn parse_opt_record<'a>(data: &'a [u8], offset: &mut usize) -> Result<Opt<'a>, E
 
 #[cfg(test)]
 mod test {
+    use alloc::string::ToString;
 
-    //use std::net::Ipv4Addr;
-    use {Packet, Header};
-    use Opcode::*;
-    use ResponseCode::NoError;
-    use QueryType as QT;
-    use QueryClass as QC;
-    use Class as C;
-    use RData;
+    use net::Ipv4Addr;
+    use net::dns::{Packet, Header};
+    use net::dns::Opcode::*;
+    use net::dns::ResponseCode::NoError;
+    use net::dns::QueryType as QT;
+    use net::dns::QueryClass as QC;
+    use net::dns::Class as C;
+    use net::dns::RData;
 
     #[test]
     fn parse_example_query() {
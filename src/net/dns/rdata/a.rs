@@ -6,6 +6,7 @@ use byteorder::{BigEndian, ByteOrder};
 
 //#[derive(PartialEq, Eq)]
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub struct Record(pub Ipv4Addr);
 
 impl<'a> super::Record<'a> for Record {
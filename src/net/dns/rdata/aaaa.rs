@@ -6,6 +6,7 @@ use byteorder::{BigEndian, ByteOrder};
 
 //#[derive(PartialEq, Eq)]
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub struct Record(pub Ipv6Addr);
 
 impl<'a> super::Record<'a> for Record {
@@ -33,14 +34,15 @@ impl<'a> super::Record<'a> for Record {
 
 #[cfg(test)]
 mod test {
+    use alloc::string::ToString;
 
-    use {Packet, Header};
-    use Opcode::*;
-    use ResponseCode::NoError;
-    use QueryType as QT;
-    use QueryClass as QC;
-    use Class as C;
-    use RData;
+    use net::dns::{Packet, Header};
+    use net::dns::Opcode::*;
+    use net::dns::ResponseCode::NoError;
+    use net::dns::QueryType as QT;
+    use net::dns::QueryClass as QC;
+    use net::dns::Class as C;
+    use net::dns::RData;
     use super::*;
 
     #[test]
@@ -44,6 +44,7 @@ pub type RDataResult<'a> = Result<RData<'a>, Error>;
 
 /// The enumeration that represents known types of DNS resource records data
 //#[derive(Debug)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
 pub enum RData<'a> {
     A(A),
     AAAA(Aaaa),
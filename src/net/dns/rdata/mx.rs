@@ -3,6 +3,7 @@ use byteorder::{BigEndian, ByteOrder};
 
 //#[derive(Debug)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
 pub struct Record<'a> {
     pub preference: u16,
     pub exchange: Name<'a>,
@@ -26,14 +27,15 @@ impl<'a> super::Record<'a> for Record<'a> {
 
 #[cfg(test)]
 mod test {
+    use alloc::string::ToString;
 
-    use {Packet, Header};
-    use Opcode::*;
-    use ResponseCode::NoError;
-    use QueryType as QT;
-    use QueryClass as QC;
-    use Class as C;
-    use RData;
+    use net::dns::{Packet, Header};
+    use net::dns::Opcode::*;
+    use net::dns::ResponseCode::NoError;
+    use net::dns::QueryType as QT;
+    use net::dns::QueryClass as QC;
+    use net::dns::Class as C;
+    use net::dns::RData;
     use super::*;
 
     #[test]
@@ -1,5 +1,6 @@
 /// RFC 6891 OPT RR
 //#[derive(Debug)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
 pub struct Record<'a> {
     pub udp: u16,
     pub extrcode: u8,
@@ -4,6 +4,7 @@ use byteorder::{BigEndian, ByteOrder};
 /// The SOA (Start of Authority) record
 //#[derive(Debug)]
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
 pub struct Record<'a> {
     pub primary_ns: Name<'a>,
     pub mailbox: Name<'a>,
@@ -42,14 +43,15 @@ impl<'a> super::Record<'a> for Record<'a> {
 
 #[cfg(test)]
 mod test {
+    use alloc::string::ToString;
 
-    use {Packet, Header};
-    use Opcode::*;
-    use ResponseCode::NameError;
-    use QueryType as QT;
-    use QueryClass as QC;
-    use Class as C;
-    use RData;
+    use net::dns::{Packet, Header};
+    use net::dns::Opcode::*;
+    use net::dns::ResponseCode::NameError;
+    use net::dns::QueryType as QT;
+    use net::dns::QueryClass as QC;
+    use net::dns::Class as C;
+    use net::dns::RData;
 
      #[test]
      fn parse_response() {
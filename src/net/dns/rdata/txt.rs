@@ -34,6 +34,21 @@ impl<'a> Record<'a> {
     }
 }
 
+/// Serialized as the list of its text chunks. Chunks that aren't UTF-8 have the offending bytes replaced.
+#[cfg(feature = "with-serde")]
+impl<'a> ::serde::Serialize for Record<'a> {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+        use alloc::String;
+
+        let mut seq = serializer.serialize_seq(None)?;
+        for chunk in self.iter() {
+            seq.serialize_element(&String::from_utf8_lossy(chunk))?;
+        }
+        seq.end()
+    }
+}
+
 impl<'a> super::Record<'a> for Record<'a> {
 
     const TYPE: isize = 16;
@@ -61,16 +76,18 @@ impl<'a> super::Record<'a> for Record<'a> {
 
 #[cfg(test)]
 mod test {
+    use alloc::string::ToString;
+    use alloc::Vec;
 
     //use std::str::from_utf8;
 
-    use {Packet, Header};
-    use Opcode::*;
-    use ResponseCode::NoError;
-    use QueryType as QT;
-    use QueryClass as QC;
-    use Class as C;
-    use RData;
+    use net::dns::{Packet, Header};
+    use net::dns::Opcode::*;
+    use net::dns::ResponseCode::NoError;
+    use net::dns::QueryType as QT;
+    use net::dns::QueryClass as QC;
+    use net::dns::Class as C;
+    use net::dns::RData;
 
     #[test]
     fn parse_response_multiple_strings() {
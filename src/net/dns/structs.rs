@@ -4,6 +4,7 @@ use alloc::Vec;
 
 /// Parsed DNS packet
 //#[derive(Debug)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
 #[allow(missing_docs)]  // should be covered by spec
 pub struct Packet<'a> {
     pub header: Header,
@@ -21,6 +22,7 @@ pub struct Packet<'a> {
 
 /// A parsed chunk of data in the Query section of the packet
 //#[derive(Debug)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
 #[allow(missing_docs)]  // should be covered by spec
 pub struct Question<'a> {
     pub qname: Name<'a>,
@@ -37,6 +39,7 @@ pub struct Question<'a> {
 /// limited we have some types of packets which are parsed and other provided
 /// as unparsed slice of bytes.
 //#[derive(Debug)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
 #[allow(missing_docs)]  // should be covered by spec
 pub struct ResourceRecord<'a> {
     pub name: Name<'a>,
@@ -1,9 +1,8 @@
-use {Result, boxed::EfiBox, system_table, image_handle};
+use {Result, boxed::EfiBox};
+use utils::{handles_by_protocol, open_protocol};
 use alloc::Vec;
-use core::{ptr, mem, slice};
+use core::{ptr, slice};
 use ffi::{
-    EFI_HANDLE,
-    VOID,
     EFI_BUFFER_TOO_SMALL,
     ip4::{
         EFI_IP4_SERVICE_BINDING_PROTOCOL_GUID,
@@ -12,7 +11,6 @@ use ffi::{
         EFI_IP4_IPCONFIG_DATA,
         EFI_IP4_ROUTE_TABLE,
     },
-    boot_services::{EFI_LOCATE_SEARCH_TYPE, EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL},
 };
 use net::addr::Ipv4Addr;
 
@@ -84,35 +82,13 @@ impl Ipv4Route {
 
 pub fn interfaces() -> Result<Vec<Interface>> {
     // TODO: should we not return an iterator instead of a vec here?
-    let bs = system_table().BootServices;
+    let handles = handles_by_protocol(&EFI_IP4_SERVICE_BINDING_PROTOCOL_GUID)?;
 
-    let mut no_of_handles = 0;
-    let mut handle_buf: *const EFI_HANDLE = ptr::null_mut();
-    unsafe {
-      ret_on_err!(((*bs).LocateHandleBuffer)(EFI_LOCATE_SEARCH_TYPE::ByProtocol, &EFI_IP4_SERVICE_BINDING_PROTOCOL_GUID, ptr::null() as *const VOID, &mut no_of_handles, &mut handle_buf));
-    }
-
-    if no_of_handles == 0 || handle_buf.is_null() {
-        return Ok(Vec::new());
-    }
-
-    let handle_buf = unsafe { EfiBox::from_raw(handle_buf as *mut EFI_HANDLE) };  // Putting it in a box for proper cleanup on exit
-
-    let handles = unsafe { slice::from_raw_parts_mut(handle_buf.as_raw() as *mut EFI_HANDLE, no_of_handles) };
-    
     // Enumerate all handles that installed with ip service binding protocol.
     let mut interfaces = Vec::new();
     for handle in handles.iter() {
         // config protocol and service binding protocol are installed on the same handle.
-        let config_proto = ptr::null::<EFI_IP4_CONFIG_PROTOCOL>() as *const EFI_IP4_CONFIG_PROTOCOL;
-        unsafe {
-        ret_on_err!(((*bs).OpenProtocol)(*handle,
-                    &EFI_IP4_CONFIG_PROTOCOL_GUID,
-                    mem::transmute(&config_proto),
-                    image_handle(),
-                    ptr::null(),
-                    EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL));
-        }
+        let config_proto = open_protocol::<EFI_IP4_CONFIG_PROTOCOL>(*handle, &EFI_IP4_CONFIG_PROTOCOL_GUID)?;
 
         // TODO: add code to wait for IP protocol to initialize here.
         // Otherwise we get a no mapping error
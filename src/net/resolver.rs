@@ -0,0 +1,58 @@
+use core::time::Duration;
+use alloc::Vec;
+use super::{UdpSocket, SocketAddr, IpAddr, dhcp};
+use super::dns::{self, Transport, LookupError};
+use {Result, EfiError, EfiErrorKind, ResultExt};
+
+// Name resolution for the crate's own use (e.g. when connecting sockets to host names) with the DNS
+// servers from DHCP, over the UDP4 protocol.
+
+const DNS_PORT: u16 = 53;
+const DNS_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Queries go over a new socket each time so that a late response to an earlier query can't be mistaken for this one's
+struct UdpTransport {
+    server: SocketAddr,
+}
+
+impl Transport for UdpTransport {
+    type Error = EfiError;
+
+    fn exchange(&mut self, query: &[u8], response: &mut [u8]) -> Result<usize> {
+        let mut socket = UdpSocket::bind("0.0.0.0:0").context("binding DNS socket")?;
+        socket.send_to(query, self.server).context("sending DNS query")?;
+        socket.set_read_timeout(Some(DNS_TIMEOUT)).context("setting DNS timeout")?;
+        Ok(socket.recv(response).context("receiving DNS response")?)
+    }
+}
+
+fn query(server: SocketAddr, hostname: &str) -> Result<Vec<IpAddr>> {
+    dns::lookup(&mut UdpTransport { server }, 1, hostname).map_err(|e| match e {
+        LookupError::Transport(e) => e,
+        LookupError::InvalidName => EfiError::from(EfiErrorKind::InvalidParameter).add_context("building DNS query"),
+        LookupError::Parse(e) => EfiError::with_source(EfiErrorKind::ProtocolError, e).add_context("parsing DNS response"),
+        LookupError::UnexpectedResponse => EfiError::from(EfiErrorKind::ProtocolError).add_context("DNS response is for another query"),
+        LookupError::NameNotFound => EfiError::from(EfiErrorKind::NotFound).add_context("DNS name does not exist"),
+        LookupError::ServerFailed(_) => EfiError::from(EfiErrorKind::ProtocolError).add_context("DNS server failed the query"),
+        LookupError::NoAddresses => EfiError::from(EfiErrorKind::NotFound).add_context("DNS response has no A records"),
+    })
+}
+
+/// Resolves `hostname` using the DNS servers from the cached DHCP configuration.
+/// If every server fails the error from the last one is returned.
+pub(crate) fn lookup_host(hostname: &str) -> Result<Vec<IpAddr>> {
+    // TODO: Assuming here that PXE has already happened. Should we kick it off here if it hasn't?
+    let servers = dhcp::require_cached_dhcp_config()?.dns_server_addrs().iter()
+        .map(|ip| SocketAddr::from((*ip, DNS_PORT)))
+        .collect::<Vec<_>>();
+    let mut last_error = EfiError::from(EfiErrorKind::NotFound).add_context("no DNS servers configured");
+
+    for server in servers {
+        match query(server, hostname) {
+            Ok(addrs) => return Ok(addrs),
+            Err(e) => last_error = e,
+        }
+    }
+
+    Err(last_error)
+}
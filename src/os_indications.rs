@@ -0,0 +1,96 @@
+use ffi::{
+    runtime_services::{
+        EFI_GLOBAL_VARIABLE,
+        EFI_OS_INDICATIONS_BOOT_TO_FW_UI,
+        EFI_OS_INDICATIONS_TIMESTAMP_REVOCATION,
+        EFI_OS_INDICATIONS_FILE_CAPSULE_DELIVERY_SUPPORTED,
+        EFI_OS_INDICATIONS_FMP_CAPSULE_SUPPORTED,
+        EFI_OS_INDICATIONS_CAPSULE_RESULT_VAR_SUPPORTED,
+        EFI_OS_INDICATIONS_START_OS_RECOVERY,
+        EFI_OS_INDICATIONS_START_PLATFORM_RECOVERY,
+        EFI_OS_INDICATIONS_JSON_CONFIG_DATA_REFRESH,
+    },
+    UINT64,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use variables::{self, Attributes};
+use {Result, EfiErrorKind};
+
+flags! {
+    /// Bits of the OsIndications and OsIndicationsSupported variables.
+    /// Can be combined using the `|` operator.
+    pub struct OsIndications(UINT64) {
+        const BOOT_TO_FW_UI = EFI_OS_INDICATIONS_BOOT_TO_FW_UI;
+        const TIMESTAMP_REVOCATION = EFI_OS_INDICATIONS_TIMESTAMP_REVOCATION;
+        const FILE_CAPSULE_DELIVERY_SUPPORTED = EFI_OS_INDICATIONS_FILE_CAPSULE_DELIVERY_SUPPORTED;
+        const FMP_CAPSULE_SUPPORTED = EFI_OS_INDICATIONS_FMP_CAPSULE_SUPPORTED;
+        const CAPSULE_RESULT_VAR_SUPPORTED = EFI_OS_INDICATIONS_CAPSULE_RESULT_VAR_SUPPORTED;
+        const START_OS_RECOVERY = EFI_OS_INDICATIONS_START_OS_RECOVERY;
+        const START_PLATFORM_RECOVERY = EFI_OS_INDICATIONS_START_PLATFORM_RECOVERY;
+        const JSON_CONFIG_DATA_REFRESH = EFI_OS_INDICATIONS_JSON_CONFIG_DATA_REFRESH;
+    }
+}
+
+impl OsIndications {
+    pub fn remove(&mut self, other: OsIndications) {
+        self.0 &= !other.0;
+    }
+}
+
+/// The indications supported by the firmware (the OsIndicationsSupported variable)
+pub fn supported() -> Result<OsIndications> {
+    read("OsIndicationsSupported")
+}
+
+/// The indications currently requested of the firmware (the OsIndications variable)
+pub fn current() -> Result<OsIndications> {
+    read("OsIndications")
+}
+
+/// Overwrites the OsIndications variable with the given value
+pub fn set(indications: OsIndications) -> Result<()> {
+    let mut bytes = [0_u8; 8];
+    LittleEndian::write_u64(&mut bytes, indications.bits());
+    let attributes = Attributes::NON_VOLATILE | Attributes::BOOTSERVICE_ACCESS | Attributes::RUNTIME_ACCESS;
+    variables::set("OsIndications", &EFI_GLOBAL_VARIABLE, attributes, &bytes)
+}
+
+/// Sets the given bits in OsIndications leaving the other bits untouched.
+/// Fails with `Unsupported` if the firmware doesn't advertise support for any of them in OsIndicationsSupported.
+/// The request takes effect on the next reset.
+pub fn request(indications: OsIndications) -> Result<()> {
+    if !supported()?.contains(indications) {
+        return Err(EfiErrorKind::Unsupported.into());
+    }
+
+    let mut current = current()?;
+    current |= indications;
+    set(current)
+}
+
+/// Clears the given bits in OsIndications leaving the other bits untouched
+pub fn clear(indications: OsIndications) -> Result<()> {
+    let mut current = current()?;
+    current.remove(indications);
+    set(current)
+}
+
+/// Asks the firmware to stop at its setup UI on the next boot
+pub fn request_boot_to_firmware_ui() -> Result<()> {
+    request(OsIndications::BOOT_TO_FW_UI)
+}
+
+/// Asks the firmware to process capsules placed on the ESP (capsule-on-disk) on the next boot
+pub fn request_file_capsule_delivery() -> Result<()> {
+    request(OsIndications::FILE_CAPSULE_DELIVERY_SUPPORTED)
+}
+
+// A missing variable is treated as no bits being set
+fn read(name: &str) -> Result<OsIndications> {
+    let bits = match variables::try_get(name, &EFI_GLOBAL_VARIABLE)? {
+        Some(ref bytes) if bytes.len() >= 8 => LittleEndian::read_u64(bytes),
+        _ => 0,
+    };
+
+    Ok(OsIndications(bits))
+}
@@ -0,0 +1,142 @@
+use ffi::{
+    EFI_SYSTEM_TABLE,
+    EFI_ABORTED,
+    CHAR16,
+    UINTN,
+    VOID,
+    runtime_services::EFI_RESET_TYPE,
+    serial::{EFI_SERIAL_IO_PROTOCOL, EFI_SERIAL_IO_PROTOCOL_GUID},
+};
+use core::{fmt::{self, Write}, ptr, mem, time::Duration};
+use {SYSTEM_TABLE, IMAGE_HANDLE, boot_services_exited, debug};
+
+// The panic handler installed by the `panic-handler` feature. It reports the panic and a backtrace
+// (see the debug module) on ConOut and the first serial port and then does whatever was chosen with set_panic_action().
+// It doesn't allocate since the allocator itself may be what panicked.
+
+/// What to do after a panic has been reported
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PanicAction {
+    /// Hang so that the message stays on screen. The default.
+    Stall,
+    /// Cold reset the platform after the given delay
+    Reboot(Duration),
+    /// Exit the image with EFI_ABORTED, returning control to whoever started it (e.g. the boot manager or shell)
+    Exit,
+}
+
+static mut PANIC_ACTION: PanicAction = PanicAction::Stall;
+static mut PANICKING: bool = false;
+
+/// Sets what the panic handler does after printing the panic. Call it early during setup.
+pub fn set_panic_action(action: PanicAction) {
+    unsafe { PANIC_ACTION = action; }
+}
+
+#[cfg(not(test))]
+#[lang = "panic_fmt"]
+#[no_mangle]
+pub extern fn rust_begin_panic(msg: fmt::Arguments, file: &'static str, line: u32, column: u32) -> ! {
+    unsafe {
+        // A panic while reporting a panic. Trying again would most likely just recurse.
+        if PANICKING {
+            loop {}
+        }
+        PANICKING = true;
+
+        let st = match SYSTEM_TABLE.get() {
+            Some(&st) => &*st,
+            None => loop {} // init_env() hasn't been called so there's nowhere to report to
+        };
+
+        // The console and Stall() are gone along with boot services. Only the reset is still possible.
+        if boot_services_exited() {
+            if let PanicAction::Reboot(_) = PANIC_ACTION {
+                ((*st.RuntimeServices).ResetSystem)(EFI_RESET_TYPE::EfiResetCold, EFI_ABORTED, 0, ptr::null());
+            }
+            loop {}
+        }
+
+        let mut out = PanicWriter::new(st);
+        let _ = write!(out, "\npanicked at '{}', {}:{}:{}\n", msg, file, line, column);
+        if let Some(base) = debug::image_base() {
+            let _ = write!(out, "image base: {:#x}\n", base);
+        }
+        let _ = write!(out, "backtrace:\n{}", debug::backtrace());
+
+        match PANIC_ACTION {
+            PanicAction::Stall => (),
+            PanicAction::Reboot(delay) => {
+                let _ = write!(out, "rebooting in {} seconds\n", delay.as_secs());
+                ((*st.BootServices).Stall)((delay.as_secs() * 1000_000 + delay.subsec_micros() as u64) as UINTN);
+                ((*st.RuntimeServices).ResetSystem)(EFI_RESET_TYPE::EfiResetCold, EFI_ABORTED, 0, ptr::null());
+            },
+            PanicAction::Exit => if let Some(&image_handle) = IMAGE_HANDLE.get() {
+                ((*st.BootServices).Exit)(image_handle, EFI_ABORTED, 0, ptr::null());
+            },
+        }
+
+        // Stalling, or the reset or exit didn't happen
+        loop {}
+    }
+}
+
+// Writes to ConOut and the first serial port (if any) through fixed size stack buffers
+struct PanicWriter<'a> {
+    st: &'a EFI_SYSTEM_TABLE,
+    serial: *mut EFI_SERIAL_IO_PROTOCOL,
+}
+
+const CHUNK_LEN: usize = 64;
+
+impl<'a> PanicWriter<'a> {
+    unsafe fn new(st: &'a EFI_SYSTEM_TABLE) -> Self {
+        let serial: *mut EFI_SERIAL_IO_PROTOCOL = ptr::null_mut();
+        ((*st.BootServices).LocateProtocol)(&EFI_SERIAL_IO_PROTOCOL_GUID, ptr::null(), mem::transmute(&serial));
+        PanicWriter { st, serial }
+    }
+
+    fn write_con_out(&self, chunk: &mut [CHAR16; CHUNK_LEN + 1], len: usize) {
+        chunk[len] = 0;
+        unsafe { ((*self.st.ConOut).OutputString)(self.st.ConOut, chunk.as_ptr()); }
+    }
+
+    fn write_serial(&self, buf: &[u8]) {
+        if self.serial.is_null() {
+            return;
+        }
+
+        let mut size: UINTN = buf.len();
+        unsafe { ((*self.serial).Write)(self.serial, &mut size, buf.as_ptr() as *const VOID); }
+    }
+}
+
+impl<'a> Write for PanicWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut chunk = [0 as CHAR16; CHUNK_LEN + 1];
+        let mut len = 0;
+        for c in s.chars() {
+            if len + 2 > CHUNK_LEN {
+                self.write_con_out(&mut chunk, len);
+                len = 0;
+            }
+
+            if c == '\n' {
+                chunk[len] = '\r' as CHAR16;
+                len += 1;
+            }
+            chunk[len] = if (c as u32) < 0x10000 { c as u32 as CHAR16 } else { 0xFFFD };
+            len += 1;
+        }
+        self.write_con_out(&mut chunk, len);
+
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                self.write_serial(b"\r\n");
+            }
+            self.write_serial(line.as_bytes());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,329 @@
+use ffi::{
+    pci::{EFI_PCI_IO_PROTOCOL, EFI_PCI_IO_PROTOCOL_GUID, EFI_PCI_IO_PROTOCOL_WIDTH},
+    EFI_HANDLE,
+    VOID,
+};
+use device_path::DevicePath;
+use device_path::device_path_of;
+use byteorder::{ByteOrder, LittleEndian};
+use utils::{handles_by_protocol, open_protocol};
+use alloc::Vec;
+use core::fmt;
+use {Result, EfiErrorKind};
+
+// PCI devices as seen through EFI_PCI_IO_PROTOCOL, which the PCI bus driver installs on a handle for
+// every function it finds. Enough to produce an lspci style inventory before the OS is up.
+
+/// Where a PCI function sits: segment (domain), bus, device and function numbers
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PciLocation {
+    pub segment: u32,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl fmt::Display for PciLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04x}:{:02x}:{:02x}.{:x}", self.segment, self.bus, self.device, self.function)
+    }
+}
+
+/// The class code register: what kind of device a function is
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ClassCode {
+    pub base: u8,
+    pub sub: u8,
+    pub prog_if: u8,
+}
+
+impl ClassCode {
+    /// A human readable name such as "SATA controller", falling back to the base class' name
+    /// for subclasses that aren't known
+    pub fn name(&self) -> &'static str {
+        class_name(self.base, self.sub, self.prog_if)
+    }
+}
+
+impl fmt::Display for ClassCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} [{:02x}{:02x}]", self.name(), self.base, self.sub)
+    }
+}
+
+/// The identifying fields of a function's configuration space header
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PciHeader {
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub revision: u8,
+    pub class: ClassCode,
+    pub header_type: u8,
+    /// Subsystem vendor and device IDs. Only type 0 (non-bridge) headers have these.
+    pub subsystem: Option<(u16, u16)>,
+}
+
+impl PciHeader {
+    /// Size of the part of configuration space `parse` needs
+    pub const SIZE: usize = 0x30;
+
+    /// Decodes the first `SIZE` bytes of configuration space
+    pub fn parse(config: &[u8]) -> Result<Self> {
+        if config.len() < Self::SIZE {
+            return Err(EfiErrorKind::BufferTooSmall.into());
+        }
+
+        let header_type = config[0x0E];
+        let subsystem = if header_type & 0x7F == 0 {
+            Some((LittleEndian::read_u16(&config[0x2C..]), LittleEndian::read_u16(&config[0x2E..])))
+        } else {
+            None
+        };
+
+        Ok(PciHeader {
+            vendor_id: LittleEndian::read_u16(&config[0x00..]),
+            device_id: LittleEndian::read_u16(&config[0x02..]),
+            revision: config[0x08],
+            class: ClassCode { base: config[0x0B], sub: config[0x0A], prog_if: config[0x09] },
+            header_type,
+            subsystem,
+        })
+    }
+
+    /// Whether the function is a PCI-to-PCI or CardBus bridge
+    pub fn is_bridge(&self) -> bool {
+        self.header_type & 0x7F != 0
+    }
+
+    /// Whether the device has more than one function
+    pub fn is_multi_function(&self) -> bool {
+        self.header_type & 0x80 != 0
+    }
+}
+
+/// A PCI function with its location and identity already read
+pub struct PciDevice {
+    handle: EFI_HANDLE,
+    protocol: *const EFI_PCI_IO_PROTOCOL,
+    location: PciLocation,
+    header: PciHeader,
+}
+
+impl PciDevice {
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol::<EFI_PCI_IO_PROTOCOL>(handle, &EFI_PCI_IO_PROTOCOL_GUID)?;
+
+        let (mut segment, mut bus, mut device, mut function) = (0, 0, 0, 0);
+        unsafe {
+            ret_on_err!(((*protocol).GetLocation)(protocol, &mut segment, &mut bus, &mut device, &mut function));
+        }
+
+        let location = PciLocation { segment: segment as u32, bus: bus as u8, device: device as u8, function: function as u8 };
+        let mut config = [0u8; PciHeader::SIZE];
+        read_config(protocol, 0, &mut config)?;
+        let header = PciHeader::parse(&config)?;
+
+        Ok(PciDevice { handle, protocol, location, header })
+    }
+
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    pub fn location(&self) -> PciLocation {
+        self.location
+    }
+
+    pub fn header(&self) -> &PciHeader {
+        &self.header
+    }
+
+    pub fn vendor_id(&self) -> u16 {
+        self.header.vendor_id
+    }
+
+    pub fn device_id(&self) -> u16 {
+        self.header.device_id
+    }
+
+    pub fn class(&self) -> ClassCode {
+        self.header.class
+    }
+
+    pub fn revision(&self) -> u8 {
+        self.header.revision
+    }
+
+    /// The device path of the function, e.g. PciRoot(0x0)/Pci(0x1F,0x2)
+    pub fn device_path(&self) -> Result<DevicePath> {
+        device_path_of(self.handle)
+    }
+
+    /// Reads configuration space starting at `offset`
+    pub fn read_config(&self, offset: u32, buf: &mut [u8]) -> Result<()> {
+        read_config(self.protocol, offset, buf)
+    }
+
+    pub fn read_config_u8(&self, offset: u32) -> Result<u8> {
+        let mut buf = [0u8; 1];
+        self.read_config(offset, &mut buf)?;
+        Ok(buf[0])
+    }
+
+    pub fn read_config_u16(&self, offset: u32) -> Result<u16> {
+        let mut buf = [0u8; 2];
+        self.read_config(offset, &mut buf)?;
+        Ok(LittleEndian::read_u16(&buf))
+    }
+
+    pub fn read_config_u32(&self, offset: u32) -> Result<u32> {
+        let mut buf = [0u8; 4];
+        self.read_config(offset, &mut buf)?;
+        Ok(LittleEndian::read_u32(&buf))
+    }
+}
+
+/// One line in the style of lspci, e.g. "0000:00:1f.2 SATA controller [0106]: 8086:2922 (rev 02)"
+impl fmt::Display for PciDevice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}: {:04x}:{:04x}", self.location, self.header.class, self.header.vendor_id, self.header.device_id)?;
+        if self.header.revision != 0 {
+            write!(f, " (rev {:02x})", self.header.revision)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every PCI function in the system, ordered by location
+pub fn devices() -> Result<Vec<PciDevice>> {
+    let mut devices = handles_by_protocol(&EFI_PCI_IO_PROTOCOL_GUID)?.into_iter()
+        .map(PciDevice::from_handle)
+        .collect::<Result<Vec<_>>>()?;
+    devices.sort_by_key(|d| d.location);
+    Ok(devices)
+}
+
+fn read_config(protocol: *const EFI_PCI_IO_PROTOCOL, offset: u32, buf: &mut [u8]) -> Result<()> {
+    if buf.is_empty() {
+        return Ok(());
+    }
+
+    unsafe {
+        ret_on_err!(((*protocol).Pci.Read)(protocol, EFI_PCI_IO_PROTOCOL_WIDTH::EfiPciIoWidthUint8, offset, buf.len(), buf.as_mut_ptr() as *mut VOID));
+    }
+
+    Ok(())
+}
+
+/// The name of a class code as given in the PCI Code and ID Assignment specification
+pub fn class_name(base: u8, sub: u8, prog_if: u8) -> &'static str {
+    match (base, sub, prog_if) {
+        (0x00, 0x01, _) => "VGA compatible unclassified device",
+        (0x00, _, _) => "Unclassified device",
+
+        (0x01, 0x00, _) => "SCSI storage controller",
+        (0x01, 0x01, _) => "IDE interface",
+        (0x01, 0x02, _) => "Floppy disk controller",
+        (0x01, 0x04, _) => "RAID bus controller",
+        (0x01, 0x05, _) => "ATA controller",
+        (0x01, 0x06, _) => "SATA controller",
+        (0x01, 0x07, _) => "Serial Attached SCSI controller",
+        (0x01, 0x08, _) => "Non-Volatile memory controller",
+        (0x01, _, _) => "Mass storage controller",
+
+        (0x02, 0x00, _) => "Ethernet controller",
+        (0x02, 0x07, _) => "Infiniband controller",
+        (0x02, _, _) => "Network controller",
+
+        (0x03, 0x00, _) => "VGA compatible controller",
+        (0x03, 0x01, _) => "XGA compatible controller",
+        (0x03, 0x02, _) => "3D controller",
+        (0x03, _, _) => "Display controller",
+
+        (0x04, 0x00, _) => "Multimedia video controller",
+        (0x04, 0x01, _) => "Multimedia audio controller",
+        (0x04, 0x03, _) => "Audio device",
+        (0x04, _, _) => "Multimedia controller",
+
+        (0x05, 0x00, _) => "RAM memory",
+        (0x05, 0x01, _) => "FLASH memory",
+        (0x05, _, _) => "Memory controller",
+
+        (0x06, 0x00, _) => "Host bridge",
+        (0x06, 0x01, _) => "ISA bridge",
+        (0x06, 0x04, _) => "PCI bridge",
+        (0x06, 0x07, _) => "CardBus bridge",
+        (0x06, _, _) => "Bridge",
+
+        (0x07, 0x00, _) => "Serial controller",
+        (0x07, 0x01, _) => "Parallel controller",
+        (0x07, _, _) => "Communication controller",
+
+        (0x08, 0x00, _) => "PIC",
+        (0x08, 0x01, _) => "DMA controller",
+        (0x08, 0x02, _) => "Timer",
+        (0x08, 0x03, _) => "RTC",
+        (0x08, 0x05, _) => "SD Host controller",
+        (0x08, 0x06, _) => "IOMMU",
+        (0x08, _, _) => "System peripheral",
+
+        (0x09, _, _) => "Input device controller",
+        (0x0A, _, _) => "Docking station",
+        (0x0B, _, _) => "Processor",
+
+        (0x0C, 0x03, 0x00) => "USB controller (UHCI)",
+        (0x0C, 0x03, 0x10) => "USB controller (OHCI)",
+        (0x0C, 0x03, 0x20) => "USB controller (EHCI)",
+        (0x0C, 0x03, 0x30) => "USB controller (xHCI)",
+        (0x0C, 0x03, _) => "USB controller",
+        (0x0C, 0x00, _) => "FireWire (IEEE 1394)",
+        (0x0C, 0x05, _) => "SMBus",
+        (0x0C, _, _) => "Serial bus controller",
+
+        (0x0D, _, _) => "Wireless controller",
+        (0x0E, _, _) => "Intelligent controller",
+        (0x0F, _, _) => "Satellite communications controller",
+        (0x10, _, _) => "Encryption controller",
+        (0x11, _, _) => "Signal processing controller",
+        (0x12, _, _) => "Processing accelerators",
+        (0x13, _, _) => "Non-Essential Instrumentation",
+        (0x40, _, _) => "Coprocessor",
+        _ => "Unassigned class",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_header_is_decoded() {
+        let mut config = [0u8; PciHeader::SIZE];
+        config[0..4].copy_from_slice(&[0x86, 0x80, 0x22, 0x29]);
+        config[8..12].copy_from_slice(&[0x02, 0x01, 0x06, 0x01]);
+        config[0x0E] = 0x80;
+        config[0x2C..0x30].copy_from_slice(&[0xF4, 0x1A, 0x00, 0x11]);
+
+        let header = PciHeader::parse(&config).unwrap();
+        assert_eq!((header.vendor_id, header.device_id, header.revision), (0x8086, 0x2922, 2));
+        assert_eq!(header.class, ClassCode { base: 0x01, sub: 0x06, prog_if: 0x01 });
+        assert_eq!(header.subsystem, Some((0x1AF4, 0x1100)));
+        assert!(header.is_multi_function() && !header.is_bridge());
+
+        config[0x0E] = 0x01;
+        let header = PciHeader::parse(&config).unwrap();
+        assert!(header.is_bridge());
+        assert_eq!(header.subsystem, None);
+
+        assert!(PciHeader::parse(&config[..0x10]).is_err());
+    }
+
+    #[test]
+    fn classes_and_locations_are_formatted() {
+        assert_eq!(format!("{}", ClassCode { base: 0x01, sub: 0x06, prog_if: 0x01 }), "SATA controller [0106]");
+        assert_eq!(class_name(0x0C, 0x03, 0x30), "USB controller (xHCI)");
+        assert_eq!(class_name(0x02, 0x80, 0x00), "Network controller");
+        assert_eq!(class_name(0xFE, 0x00, 0x00), "Unassigned class");
+        assert_eq!(format!("{}", PciLocation { segment: 0, bus: 0, device: 0x1F, function: 2 }), "0000:00:1f.2");
+    }
+}
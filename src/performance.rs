@@ -0,0 +1,379 @@
+use ffi::{
+    performance::*,
+    VOID,
+};
+use acpi::{Acpi, FPDT};
+use utils::{guid_from_bytes, locate_protocol, to_null_terminated_ascii};
+use time::Instant;
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec};
+use core::{ptr, slice, time::Duration};
+use {Result, EfiErrorKind, Guid, image_handle};
+
+// Firmware boot performance. The ACPI FPDT points to the Firmware Basic Boot Performance Table (FBPT) in which
+// the firmware records when it started running, when it loaded and started the OS loader and when
+// ExitBootServices() was called. EDK II based firmware appends its own measurements to the FBPT as extended
+// records and, through EDKII_PERFORMANCE_MEASUREMENT_PROTOCOL, lets applications add theirs, so that time spent
+// in e.g. DHCP, TFTP or disk writes shows up next to the firmware's own in tools that read the FPDT.
+// All timestamps are nanoseconds of the firmware's performance counter, which starts around reset.
+
+/// The Firmware Basic Boot Performance Record. Timestamps the firmware hasn't got to yet are zero,
+/// e.g. both ExitBootServices() ones while boot services are still running.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BasicBootRecord {
+    /// When the firmware image started executing
+    pub reset_end: Duration,
+    /// When the OS loader was loaded, i.e. when LoadImage() was called for it
+    pub os_loader_load_image_start: Duration,
+    /// When the OS loader was started, i.e. when StartImage() was called for it
+    pub os_loader_start_image_start: Duration,
+    pub exit_boot_services_entry: Duration,
+    pub exit_boot_services_exit: Duration,
+}
+
+/// An EDK II extended performance record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PerformanceEvent {
+    /// The FPDT record type, e.g. `FPDT_DYNAMIC_STRING_EVENT_TYPE`
+    pub record_type: u16,
+    /// What happened, e.g. `PERF_INMODULE_START_ID`
+    pub progress_id: u16,
+    pub apic_id: u32,
+    pub timestamp: Duration,
+    /// The module that made the measurement
+    pub guid: Guid,
+    /// The name of the measurement, for record types that have one
+    pub name: Option<String>,
+}
+
+/// A measurement made up of matching start and end records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Measurement {
+    pub name: String,
+    pub guid: Guid,
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// The contents of the Firmware Basic Boot Performance Table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootPerformance {
+    pub basic: Option<BasicBootRecord>,
+    /// The extended records in the order the firmware added them
+    pub events: Vec<PerformanceEvent>,
+}
+
+impl BootPerformance {
+    /// Reads the FBPT the ACPI FPDT points to. Fails with `NotFound` if the firmware doesn't publish an FPDT.
+    pub fn get() -> Result<Self> {
+        let fpdt = Acpi::new()?.find(FPDT).ok_or(EfiErrorKind::NotFound)?;
+        let address = fbpt_address(fpdt.body()).ok_or(EfiErrorKind::NotFound)?;
+        unsafe {
+            let header = slice::from_raw_parts(address as *const u8, FPDT_FBPT_HEADER_SIZE);
+            let length = LittleEndian::read_u32(&header[4..8]) as usize;
+            Self::parse(slice::from_raw_parts(address as *const u8, length))
+        }
+    }
+
+    /// Parses an FBPT including its header. Records of unknown types are skipped.
+    pub fn parse(fbpt: &[u8]) -> Result<Self> {
+        if fbpt.len() < FPDT_FBPT_HEADER_SIZE || fbpt[..4] != FPDT_FBPT_SIGNATURE[..] {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        let length = LittleEndian::read_u32(&fbpt[4..8]) as usize;
+        if length < FPDT_FBPT_HEADER_SIZE || length > fbpt.len() {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        let mut performance = BootPerformance { basic: None, events: Vec::new() };
+        for (record_type, record) in records(&fbpt[FPDT_FBPT_HEADER_SIZE..length]) {
+            match record_type {
+                FPDT_FIRMWARE_BASIC_BOOT_RECORD_TYPE if record.len() >= 48 => {
+                    performance.basic = Some(BasicBootRecord {
+                        reset_end: nanos(LittleEndian::read_u64(&record[8..16])),
+                        os_loader_load_image_start: nanos(LittleEndian::read_u64(&record[16..24])),
+                        os_loader_start_image_start: nanos(LittleEndian::read_u64(&record[24..32])),
+                        exit_boot_services_entry: nanos(LittleEndian::read_u64(&record[32..40])),
+                        exit_boot_services_exit: nanos(LittleEndian::read_u64(&record[40..48])),
+                    });
+                },
+                FPDT_GUID_EVENT_TYPE...FPDT_GUID_QWORD_STRING_EVENT_TYPE if record.len() >= 34 => {
+                    let name_offset = match record_type {
+                        FPDT_DYNAMIC_STRING_EVENT_TYPE => Some(34),
+                        FPDT_DUAL_GUID_STRING_EVENT_TYPE => Some(50),
+                        FPDT_GUID_QWORD_STRING_EVENT_TYPE => Some(42),
+                        _ => None,
+                    };
+
+                    performance.events.push(PerformanceEvent {
+                        record_type,
+                        progress_id: LittleEndian::read_u16(&record[4..6]),
+                        apic_id: LittleEndian::read_u32(&record[6..10]),
+                        timestamp: nanos(LittleEndian::read_u64(&record[10..18])),
+                        guid: guid_from_bytes(&record[18..34]),
+                        name: name_offset.and_then(|offset| record.get(offset..)).map(ascii_string),
+                    });
+                },
+                _ => (),
+            }
+        }
+
+        Ok(performance)
+    }
+
+    /// Pairs up the start and end records of measurements made with `begin()` and `end()` (EDK II's
+    /// PERF_INMODULE_START/END), in the order they started. Measurements that haven't ended are left out.
+    pub fn measurements(&self) -> Vec<Measurement> {
+        let mut measurements = Vec::new();
+        for (i, start) in self.events.iter().enumerate() {
+            if start.progress_id as u32 != PERF_INMODULE_START_ID || start.name.is_none() {
+                continue;
+            }
+
+            let end = self.events[i + 1..].iter()
+                .find(|e| e.progress_id as u32 == PERF_INMODULE_END_ID && e.guid == start.guid && e.name == start.name);
+            if let Some(end) = end {
+                measurements.push(Measurement {
+                    name: start.name.clone().unwrap_or_default(),
+                    guid: start.guid,
+                    start: start.timestamp,
+                    duration: end.timestamp.checked_sub(start.timestamp).unwrap_or_default(),
+                });
+            }
+        }
+
+        measurements
+    }
+}
+
+/// EDK II's performance measurement service, which turns measurements into FBPT records
+pub struct PerformanceMeasurement {
+    protocol: *const EDKII_PERFORMANCE_MEASUREMENT_PROTOCOL,
+}
+
+impl PerformanceMeasurement {
+    /// Fails with `NotFound` if the firmware wasn't built with performance measurement enabled
+    pub fn get() -> Result<Self> {
+        Ok(PerformanceMeasurement { protocol: locate_protocol(&EDKII_PERFORMANCE_MEASUREMENT_PROTOCOL_GUID)? })
+    }
+
+    /// Records the start of a measurement. The firmware keeps only the first few characters of the name
+    /// (23 with EDK II) and non-ASCII characters are replaced with '?'.
+    pub fn begin(&self, name: &str) -> Result<()> {
+        self.create(name, PERF_INMODULE_START_ID)
+    }
+
+    /// Records the end of the measurement with the given name
+    pub fn end(&self, name: &str) -> Result<()> {
+        self.create(name, PERF_INMODULE_END_ID)
+    }
+
+    fn create(&self, name: &str, identifier: u32) -> Result<()> {
+        let name = to_null_terminated_ascii(name);
+        unsafe {
+            // A zero timestamp means now
+            ret_on_err!(((*self.protocol).CreatePerformanceMeasurement)(image_handle() as *const VOID, ptr::null(), name.as_ptr() as *const i8,
+                0, 0, identifier, PERF_MEASUREMENT_ATTRIBUTE::PerfEntry));
+        }
+
+        Ok(())
+    }
+}
+
+/// A measurement in progress that ends when dropped
+pub struct Measuring {
+    service: Option<PerformanceMeasurement>,
+    name: String,
+    start: Option<Instant>,
+}
+
+impl Measuring {
+    /// The time since the measurement started as per `time::Instant`, whether or not the firmware records it.
+    /// `None` only if there's no counter to measure with.
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.start.map(|start| start.elapsed())
+    }
+
+    /// Ends the measurement now and returns how long it took
+    pub fn end(self) -> Option<Duration> {
+        self.elapsed()
+    }
+}
+
+impl Drop for Measuring {
+    fn drop(&mut self) {
+        if let Some(ref service) = self.service {
+            let _ = service.end(&self.name); // Measurements are best effort
+        }
+    }
+}
+
+/// Starts a measurement that ends when the returned value is dropped. The firmware isn't told about it if it
+/// can't record measurements, so instrumentation can be left in place on any firmware.
+///
+/// ```ignore
+/// {
+///     let _m = performance::measure("DHCP");
+///     net::dhcp::run_dhcp()?;
+/// }
+/// let image = {
+///     let _m = performance::measure("TFTP");
+///     net::dhcp::mtftp_get_file(&server, &kernel_path)?
+/// };
+/// ```
+pub fn measure(name: &str) -> Measuring {
+    let start = Instant::now().ok();
+    let service = PerformanceMeasurement::get().ok().and_then(|s| s.begin(name).ok().map(|_| s));
+    Measuring { service, name: name.into(), start }
+}
+
+// The FBPT address in the body of an FPDT
+fn fbpt_address(fpdt_body: &[u8]) -> Option<u64> {
+    records(fpdt_body)
+        .find(|&(record_type, record)| record_type == FPDT_FIRMWARE_BASIC_BOOT_POINTER_TYPE && record.len() >= 16)
+        .map(|(_, record)| LittleEndian::read_u64(&record[8..16]))
+        .and_then(|address| if address == 0 { None } else { Some(address) })
+}
+
+// The type and bytes (header included) of each record, up to the first malformed one
+fn records<'a>(bytes: &'a [u8]) -> Records<'a> {
+    Records { bytes }
+}
+
+struct Records<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.len() < FPDT_RECORD_HEADER_SIZE {
+            return None;
+        }
+
+        let length = self.bytes[2] as usize;
+        if length < FPDT_RECORD_HEADER_SIZE || length > self.bytes.len() {
+            return None;
+        }
+
+        let (record, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Some((LittleEndian::read_u16(&record[0..2]), record))
+    }
+}
+
+fn nanos(nanos: u64) -> Duration {
+    Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+}
+
+fn ascii_string(bytes: &[u8]) -> String {
+    bytes.iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| if b.is_ascii() { b as char } else { '?' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: Guid = guid!("2C34A5E1-7C88-4A7B-9C57-3B6E0D1A4F20");
+
+    fn record(record_type: u16, revision: u8, body: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0u8; FPDT_RECORD_HEADER_SIZE];
+        LittleEndian::write_u16(&mut bytes[0..2], record_type);
+        bytes[2] = (FPDT_RECORD_HEADER_SIZE + body.len()) as u8;
+        bytes[3] = revision;
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    fn string_event(progress_id: u32, timestamp_ns: u64, name: &str) -> Vec<u8> {
+        let mut body = vec![0u8; 30];
+        LittleEndian::write_u16(&mut body[0..2], progress_id as u16);
+        LittleEndian::write_u64(&mut body[6..14], timestamp_ns);
+        body[14..30].copy_from_slice(&::utils::guid_to_bytes(&MODULE));
+        body.extend_from_slice(name.as_bytes());
+        body.push(0);
+        record(FPDT_DYNAMIC_STRING_EVENT_TYPE, 1, &body)
+    }
+
+    fn fbpt(records: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = FPDT_FBPT_SIGNATURE.to_vec();
+        bytes.extend_from_slice(&[0; 4]);
+        for r in records {
+            bytes.extend_from_slice(r);
+        }
+        let length = bytes.len() as u32;
+        LittleEndian::write_u32(&mut bytes[4..8], length);
+        bytes
+    }
+
+    #[test]
+    fn basic_boot_record_and_events_are_parsed() {
+        let mut basic = vec![0u8; 44];
+        for (i, ms) in [120u64, 2_500, 2_510, 0, 0].iter().enumerate() {
+            LittleEndian::write_u64(&mut basic[4 + i * 8..12 + i * 8], ms * 1_000_000);
+        }
+
+        let table = fbpt(&[
+            record(FPDT_FIRMWARE_BASIC_BOOT_RECORD_TYPE, 2, &basic),
+            record(0x7777, 1, &[1, 2, 3, 4]),
+            string_event(PERF_INMODULE_START_ID, 2_600_000_000, "DHCP"),
+        ]);
+        let performance = BootPerformance::parse(&table).unwrap();
+
+        let basic = performance.basic.unwrap();
+        assert_eq!(basic.reset_end, Duration::from_millis(120));
+        assert_eq!(basic.os_loader_start_image_start, Duration::from_millis(2_510));
+        assert_eq!(basic.exit_boot_services_entry, Duration::from_secs(0));
+
+        assert_eq!(performance.events, vec![PerformanceEvent {
+            record_type: FPDT_DYNAMIC_STRING_EVENT_TYPE,
+            progress_id: PERF_INMODULE_START_ID as u16,
+            apic_id: 0,
+            timestamp: Duration::from_millis(2_600),
+            guid: MODULE,
+            name: Some("DHCP".into()),
+        }]);
+    }
+
+    #[test]
+    fn measurements_pair_starts_with_ends() {
+        let table = fbpt(&[
+            string_event(PERF_INMODULE_START_ID, 1_000, "DHCP"),
+            string_event(PERF_INMODULE_START_ID, 1_500, "TFTP"),
+            string_event(PERF_INMODULE_END_ID, 3_000, "DHCP"),
+            string_event(PERF_INMODULE_END_ID, 9_500, "TFTP"),
+            string_event(PERF_INMODULE_START_ID, 10_000, "Disk"),
+        ]);
+        let measurements = BootPerformance::parse(&table).unwrap().measurements();
+        let spans = measurements.iter().map(|m| (&m.name[..], m.duration.subsec_nanos())).collect::<Vec<_>>();
+        assert_eq!(spans, vec![("DHCP", 2_000), ("TFTP", 8_000)]);
+    }
+
+    #[test]
+    fn malformed_tables_are_rejected() {
+        let mut table = fbpt(&[]);
+        assert!(BootPerformance::parse(&table).unwrap().events.is_empty());
+        table[0] = b'X';
+        assert_eq!(BootPerformance::parse(&table).unwrap_err().kind(), EfiErrorKind::VolumeCorrupted);
+
+        let mut table = fbpt(&[string_event(PERF_INMODULE_START_ID, 0, "DHCP")]);
+        LittleEndian::write_u32(&mut table[4..8], 1000);
+        assert_eq!(BootPerformance::parse(&table).unwrap_err().kind(), EfiErrorKind::BadBufferSize);
+    }
+
+    #[test]
+    fn fbpt_pointer_is_found() {
+        let mut pointer = vec![0u8; 12];
+        LittleEndian::write_u64(&mut pointer[4..12], 0x7fe4_1000);
+        let mut body = record(FPDT_S3_PERFORMANCE_TABLE_POINTER_TYPE, 1, &[0; 12]);
+        body.extend_from_slice(&record(FPDT_FIRMWARE_BASIC_BOOT_POINTER_TYPE, 1, &pointer));
+        assert_eq!(fbpt_address(&body), Some(0x7fe4_1000));
+        assert_eq!(fbpt_address(&body[..16]), None);
+        assert_eq!(to_null_terminated_ascii("Ünï"), b"?n?\0".to_vec());
+    }
+}
@@ -0,0 +1,399 @@
+use ffi::{
+    pointer::{
+        EFI_SIMPLE_POINTER_PROTOCOL,
+        EFI_SIMPLE_POINTER_PROTOCOL_GUID,
+        EFI_SIMPLE_POINTER_STATE,
+        EFI_ABSOLUTE_POINTER_PROTOCOL,
+        EFI_ABSOLUTE_POINTER_PROTOCOL_GUID,
+        EFI_ABSOLUTE_POINTER_STATE,
+        EFI_ABSP_SUPPORTS_ALT_ACTIVE,
+        EFI_ABSP_SUPPORTS_PRESSURE_AS_Z,
+        EFI_ABSP_TOUCH_ACTIVE,
+        EFI_ABS_ALT_ACTIVE,
+    },
+    EFI_HANDLE,
+    EFI_EVENT,
+    EFI_SUCCESS,
+    EFI_NOT_READY,
+    IsSuccess,
+};
+use events::{Wait, AsRawEvt};
+use utils::{handles_by_protocol, open_protocol};
+use alloc::Vec;
+use core::cmp;
+use {Result, EfiErrorKind, system_table, boot_services, to_boolean, from_boolean};
+
+// Mouse (EFI_SIMPLE_POINTER_PROTOCOL) and touch screen/tablet (EFI_ABSOLUTE_POINTER_PROTOCOL) input.
+// Both can be polled with try_state() or waited on through the `Wait` trait.
+
+/// Movement since the last read and the current button state of a mouse
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct PointerState {
+    /// Movement in counts. Divide by the resolution to get millimeters.
+    pub dx: i32,
+    pub dy: i32,
+    /// Scroll wheel movement, if there is one
+    pub dz: i32,
+    pub left_button: bool,
+    pub right_button: bool,
+}
+
+/// Counts per millimeter along each axis. Zero means the device doesn't have that axis.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PointerResolution {
+    pub x: u64,
+    pub y: u64,
+    pub z: u64,
+}
+
+/// A mouse, trackpad or similar (EFI_SIMPLE_POINTER_PROTOCOL)
+pub struct SimplePointer {
+    handle: EFI_HANDLE,
+    protocol: *const EFI_SIMPLE_POINTER_PROTOCOL,
+}
+
+impl SimplePointer {
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol(handle, &EFI_SIMPLE_POINTER_PROTOCOL_GUID)?;
+        Ok(SimplePointer { handle, protocol })
+    }
+
+    /// Opens all pointing devices in the system.
+    /// Note that firmware usually also installs one on the console in handle which merges all the others.
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_SIMPLE_POINTER_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    /// The pointer on the console in handle if there is one, otherwise the first one found
+    pub fn primary() -> Result<Self> {
+        match Self::from_handle(system_table().ConsoleInHandle) {
+            Ok(pointer) => Ok(pointer),
+            Err(_) => match Self::all()?.into_iter().next() {
+                Some(pointer) => Ok(pointer),
+                None => Err(EfiErrorKind::NotFound.into())
+            }
+        }
+    }
+
+    /// The handle the device is on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// Resets the device
+    pub fn reset(&mut self, extended_verification: bool) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).Reset)(self.protocol, to_boolean(extended_verification)));
+        }
+
+        Ok(())
+    }
+
+    pub fn resolution(&self) -> PointerResolution {
+        let mode = unsafe { &*(*self.protocol).Mode };
+        PointerResolution { x: mode.ResolutionX, y: mode.ResolutionY, z: mode.ResolutionZ }
+    }
+
+    pub fn has_left_button(&self) -> bool {
+        unsafe { from_boolean((*(*self.protocol).Mode).LeftButton) }
+    }
+
+    pub fn has_right_button(&self) -> bool {
+        unsafe { from_boolean((*(*self.protocol).Mode).RightButton) }
+    }
+
+    /// The movement since the last call and the button state. None if nothing has changed.
+    pub fn try_state(&mut self) -> Result<Option<PointerState>> {
+        let mut state = EFI_SIMPLE_POINTER_STATE::default();
+        let status = unsafe { ((*self.protocol).GetState)(self.protocol, &mut state) };
+        match status {
+            EFI_NOT_READY => Ok(None),
+            s if IsSuccess(s) => Ok(Some(PointerState {
+                dx: state.RelativeMovementX,
+                dy: state.RelativeMovementY,
+                dz: state.RelativeMovementZ,
+                left_button: from_boolean(state.LeftButton),
+                right_button: from_boolean(state.RightButton),
+            })),
+            s => Err(s.into())
+        }
+    }
+
+    /// Waits for the pointer to move or a button to change and returns the new state
+    pub fn read_state(&mut self) -> Result<PointerState> {
+        loop {
+            self.wait()?;
+            if let Some(state) = self.try_state()? {
+                return Ok(state);
+            }
+        }
+    }
+}
+
+impl Wait for SimplePointer {
+    fn wait(&self) -> Result<()> {
+        wait_for_event(unsafe { self.as_raw() })
+    }
+
+    fn is_signaled(&self) -> Result<bool> {
+        check_event(unsafe { self.as_raw() })
+    }
+}
+
+impl AsRawEvt for SimplePointer {
+    #[inline]
+    unsafe fn as_raw(&self) -> EFI_EVENT {
+        (*self.protocol).WaitForInput
+    }
+}
+
+/// The position of a touch or stylus and whether it is touching
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct AbsolutePointerState {
+    /// Position within the device's range (see `AbsolutePointer::range()`)
+    pub x: u64,
+    pub y: u64,
+    /// Height above the surface, or pressure if `supports_pressure()`. Zero if the device doesn't have a z axis.
+    pub z: u64,
+    pub touch_active: bool,
+    /// A second button such as a pen side button. Only reported if `supports_alt_active()`.
+    pub alt_active: bool,
+}
+
+/// The smallest and largest value the device reports along each axis
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AbsoluteRange {
+    pub min_x: u64,
+    pub min_y: u64,
+    pub min_z: u64,
+    pub max_x: u64,
+    pub max_y: u64,
+    pub max_z: u64,
+}
+
+impl AbsoluteRange {
+    /// Maps a position reported by the device to a pixel on a screen of the given size
+    pub fn to_screen(&self, state: &AbsolutePointerState, width: u32, height: u32) -> (u32, u32) {
+        (scale(state.x, self.min_x, self.max_x, width), scale(state.y, self.min_y, self.max_y, height))
+    }
+}
+
+/// A touch screen, tablet or other device reporting absolute positions (EFI_ABSOLUTE_POINTER_PROTOCOL)
+pub struct AbsolutePointer {
+    handle: EFI_HANDLE,
+    protocol: *const EFI_ABSOLUTE_POINTER_PROTOCOL,
+}
+
+impl AbsolutePointer {
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol(handle, &EFI_ABSOLUTE_POINTER_PROTOCOL_GUID)?;
+        Ok(AbsolutePointer { handle, protocol })
+    }
+
+    /// Opens all absolute pointing devices in the system
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_ABSOLUTE_POINTER_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    /// The device on the console in handle if there is one, otherwise the first one found
+    pub fn primary() -> Result<Self> {
+        match Self::from_handle(system_table().ConsoleInHandle) {
+            Ok(pointer) => Ok(pointer),
+            Err(_) => match Self::all()?.into_iter().next() {
+                Some(pointer) => Ok(pointer),
+                None => Err(EfiErrorKind::NotFound.into())
+            }
+        }
+    }
+
+    /// The handle the device is on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// Resets the device
+    pub fn reset(&mut self, extended_verification: bool) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).Reset)(self.protocol, to_boolean(extended_verification)));
+        }
+
+        Ok(())
+    }
+
+    pub fn range(&self) -> AbsoluteRange {
+        let mode = unsafe { &*(*self.protocol).Mode };
+        AbsoluteRange {
+            min_x: mode.AbsoluteMinX,
+            min_y: mode.AbsoluteMinY,
+            min_z: mode.AbsoluteMinZ,
+            max_x: mode.AbsoluteMaxX,
+            max_y: mode.AbsoluteMaxY,
+            max_z: mode.AbsoluteMaxZ,
+        }
+    }
+
+    pub fn supports_alt_active(&self) -> bool {
+        unsafe { (*(*self.protocol).Mode).Attributes & EFI_ABSP_SUPPORTS_ALT_ACTIVE != 0 }
+    }
+
+    /// Whether z is the touch pressure rather than the height above the surface
+    pub fn supports_pressure(&self) -> bool {
+        unsafe { (*(*self.protocol).Mode).Attributes & EFI_ABSP_SUPPORTS_PRESSURE_AS_Z != 0 }
+    }
+
+    /// The current position and touch state. None if nothing has changed since the last call.
+    pub fn try_state(&mut self) -> Result<Option<AbsolutePointerState>> {
+        let mut state = EFI_ABSOLUTE_POINTER_STATE::default();
+        let status = unsafe { ((*self.protocol).GetState)(self.protocol, &mut state) };
+        match status {
+            EFI_NOT_READY => Ok(None),
+            s if IsSuccess(s) => Ok(Some(AbsolutePointerState {
+                x: state.CurrentX,
+                y: state.CurrentY,
+                z: state.CurrentZ,
+                touch_active: state.ActiveButtons & EFI_ABSP_TOUCH_ACTIVE != 0,
+                alt_active: state.ActiveButtons & EFI_ABS_ALT_ACTIVE != 0,
+            })),
+            s => Err(s.into())
+        }
+    }
+
+    /// Waits for the state to change and returns the new state
+    pub fn read_state(&mut self) -> Result<AbsolutePointerState> {
+        loop {
+            self.wait()?;
+            if let Some(state) = self.try_state()? {
+                return Ok(state);
+            }
+        }
+    }
+}
+
+impl Wait for AbsolutePointer {
+    fn wait(&self) -> Result<()> {
+        wait_for_event(unsafe { self.as_raw() })
+    }
+
+    fn is_signaled(&self) -> Result<bool> {
+        check_event(unsafe { self.as_raw() })
+    }
+}
+
+impl AsRawEvt for AbsolutePointer {
+    #[inline]
+    unsafe fn as_raw(&self) -> EFI_EVENT {
+        (*self.protocol).WaitForInput
+    }
+}
+
+/// An on-screen cursor position driven by relative pointer movement
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Cursor {
+    /// A cursor in the middle of a screen of the given size
+    pub fn new(width: u32, height: u32) -> Self {
+        Cursor { x: width / 2, y: height / 2, width, height }
+    }
+
+    pub fn position(&self) -> (u32, u32) {
+        (self.x, self.y)
+    }
+
+    /// Moves the cursor to the given position, clamped to the screen
+    pub fn set_position(&mut self, x: u32, y: u32) {
+        self.x = cmp::min(x, self.width.saturating_sub(1));
+        self.y = cmp::min(y, self.height.saturating_sub(1));
+    }
+
+    /// Moves the cursor by the given number of pixels, stopping at the edges of the screen
+    pub fn move_by(&mut self, dx: i32, dy: i32) {
+        let x = cmp::max(self.x as i64 + dx as i64, 0) as u32;
+        let y = cmp::max(self.y as i64 + dy as i64, 0) as u32;
+        self.set_position(x, y);
+    }
+
+    /// Moves the cursor as per the movement in `state`, at `pixels_per_mm` pixels for every millimeter the device moved
+    pub fn apply(&mut self, state: &PointerState, resolution: &PointerResolution, pixels_per_mm: u32) {
+        let dx = counts_to_pixels(state.dx, resolution.x, pixels_per_mm);
+        let dy = counts_to_pixels(state.dy, resolution.y, pixels_per_mm);
+        self.move_by(dx, dy);
+    }
+}
+
+fn counts_to_pixels(counts: i32, counts_per_mm: u64, pixels_per_mm: u32) -> i32 {
+    if counts_per_mm == 0 {
+        return 0;
+    }
+
+    let pixels = counts as i64 * pixels_per_mm as i64 / cmp::min(counts_per_mm, i64::max_value() as u64) as i64;
+    cmp::max(cmp::min(pixels, i32::max_value() as i64), i32::min_value() as i64) as i32
+}
+
+// Maps value in min..=max to 0..size
+fn scale(value: u64, min: u64, max: u64, size: u32) -> u32 {
+    if max <= min || size == 0 {
+        return 0;
+    }
+
+    let value = cmp::min(cmp::max(value, min), max) - min;
+    let scaled = value as u128 * (size - 1) as u128 / (max - min) as u128;
+    scaled as u32
+}
+
+fn wait_for_event(event: EFI_EVENT) -> Result<()> {
+    let bs = boot_services();
+    unsafe {
+        let mut signaled_index = 0;
+        ret_on_err!(((*bs).WaitForEvent)(1, &event, &mut signaled_index));
+    }
+
+    Ok(())
+}
+
+fn check_event(event: EFI_EVENT) -> Result<bool> {
+    let bs = boot_services();
+    let status = unsafe { ((*bs).CheckEvent)(event) };
+    match status {
+        EFI_SUCCESS => Ok(true),
+        EFI_NOT_READY => Ok(false),
+        s => Err(s.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absolute_positions_are_scaled_to_the_screen() {
+        let range = AbsoluteRange { min_x: 0, min_y: 100, min_z: 0, max_x: 1000, max_y: 300, max_z: 0 };
+        let state = |x, y| AbsolutePointerState { x, y, ..Default::default() };
+        assert_eq!(range.to_screen(&state(0, 100), 800, 600), (0, 0));
+        assert_eq!(range.to_screen(&state(1000, 300), 800, 600), (799, 599));
+        assert_eq!(range.to_screen(&state(500, 200), 800, 600), (399, 299));
+        assert_eq!(range.to_screen(&state(2000, 0), 800, 600), (799, 0));
+        assert_eq!(scale(5, 10, 10, 800), 0);
+    }
+
+    #[test]
+    fn cursor_moves_within_the_screen() {
+        let mut cursor = Cursor::new(640, 480);
+        assert_eq!(cursor.position(), (320, 240));
+        cursor.move_by(-1000, 10);
+        assert_eq!(cursor.position(), (0, 250));
+        cursor.move_by(1000, 1000);
+        assert_eq!(cursor.position(), (639, 479));
+
+        let resolution = PointerResolution { x: 8, y: 8, z: 0 };
+        cursor.set_position(100, 100);
+        cursor.apply(&PointerState { dx: 16, dy: -8, ..Default::default() }, &resolution, 4);
+        assert_eq!(cursor.position(), (108, 96));
+        assert_eq!(counts_to_pixels(10, 0, 4), 0);
+    }
+}
@@ -0,0 +1,214 @@
+use console::{self, Console, Position};
+use io::Write;
+use time::Instant;
+use alloc::String;
+use alloc::fmt::Write as FmtWrite;
+use core::{cmp, time::Duration};
+use Result;
+
+// Console widgets for showing the progress of long operations. They redraw themselves in place
+// so should be the only thing writing to the console while active.
+//
+// Operations that can report progress take an optional `&mut FnMut(u64, u64)` which gets called
+// with the amount done so far and the total, e.g.
+//
+//     let mut bar = ProgressBar::new("Downloading")?;
+//     let file = mtftp_get_file_with_progress(&server, &name, &mut |done, total| bar.update(done, total))?;
+//     bar.finish();
+
+// How often throughput and ETA are recalculated. Recalculating on every update makes them flicker.
+const RATE_INTERVAL_MS: u64 = 500;
+
+/// A single line progress bar showing the percentage done, throughput and estimated time left
+pub struct ProgressBar {
+    console: Console,
+    pos: Position,
+    label: String,
+    columns: u32,
+    start: Option<Instant>,
+    last_rate_at: Duration,
+    rate: Option<u64>,
+    last_line: String,
+}
+
+impl ProgressBar {
+    /// Creates a bar on the line the cursor is on
+    pub fn new(label: &str) -> Result<Self> {
+        let console = console::console();
+        let (columns, _) = console.size()?;
+        let pos = console.cursor_pos();
+        let start = Instant::now().ok(); // Without a counter there's no throughput or ETA
+        Ok(ProgressBar {
+            console,
+            pos,
+            label: label.into(),
+            columns,
+            start,
+            last_rate_at: Duration::from_secs(0),
+            rate: None,
+            last_line: String::new(),
+        })
+    }
+
+    /// Redraws the bar. Matches the progress callbacks' signature so it can be called straight from one.
+    pub fn update(&mut self, done: u64, total: u64) {
+        if let Some(start) = self.start {
+            let elapsed = start.elapsed();
+            if as_millis(elapsed) >= as_millis(self.last_rate_at) + RATE_INTERVAL_MS {
+                self.last_rate_at = elapsed;
+                self.rate = Some(done * 1000 / cmp::max(as_millis(elapsed), 1));
+            }
+        }
+
+        let line = format_bar_line(&self.label, done, total, self.rate, self.columns);
+        if line != self.last_line {
+            self.draw(&line);
+            self.last_line = line;
+        }
+    }
+
+    /// Leaves the bar as it is and moves the cursor to the next line
+    pub fn finish(mut self) {
+        let _ = self.console.write_all(b"\n");
+    }
+
+    fn draw(&mut self, line: &str) {
+        // Nothing sensible can be done if the console fails
+        let _ = self.console.set_cursor_pos(self.pos);
+        let _ = self.console.write_all(line.as_bytes());
+    }
+}
+
+const SPINNER_FRAMES: &[u8] = b"|/-\\";
+
+/// A spinning indicator for operations whose length isn't known
+pub struct Spinner {
+    console: Console,
+    pos: Position,
+    label: String,
+    frame: usize,
+}
+
+impl Spinner {
+    /// Creates a spinner on the line the cursor is on
+    pub fn new(label: &str) -> Self {
+        let console = console::console();
+        let pos = console.cursor_pos();
+        let mut spinner = Spinner { console, pos, label: label.into(), frame: 0 };
+        spinner.draw();
+        spinner
+    }
+
+    /// Advances the spinner by a frame
+    pub fn tick(&mut self) {
+        self.frame = (self.frame + 1) % SPINNER_FRAMES.len();
+        self.draw();
+    }
+
+    /// Replaces the spinner with the given message and moves the cursor to the next line
+    pub fn finish(mut self, message: &str) {
+        let _ = self.console.set_cursor_pos(self.pos);
+        let _ = self.console.write_all(format!("{} {}\n", self.label, message).as_bytes());
+    }
+
+    fn draw(&mut self) {
+        let _ = self.console.set_cursor_pos(self.pos);
+        let _ = self.console.write_all(format!("{} {}", self.label, SPINNER_FRAMES[self.frame] as char).as_bytes());
+    }
+}
+
+fn as_millis(d: Duration) -> u64 {
+    d.as_secs() * 1000 + d.subsec_millis() as u64
+}
+
+// Renders e.g. "Label [#######.......]  50% 1.2 MiB/s ETA 0:05" so that it fits in `columns` - 1
+// (writing to the last column makes some consoles scroll)
+fn format_bar_line(label: &str, done: u64, total: u64, rate: Option<u64>, columns: u32) -> String {
+    let percent = if total == 0 { 100 } else { cmp::min(done.saturating_mul(100) / total, 100) };
+
+    let mut stats = format!(" {:3}%", percent);
+    if let Some(rate) = rate {
+        let _ = write!(stats, " {}/s", format_size(rate));
+        if rate > 0 && done < total {
+            let _ = write!(stats, " ETA {}", format_duration((total - done) / rate));
+        }
+    }
+
+    let available = (columns as usize).saturating_sub(1);
+    let bar_width = available.saturating_sub(label.chars().count() + 3 + stats.len());
+    let filled = if total == 0 { bar_width } else { cmp::min((bar_width as u64).saturating_mul(done) / total, bar_width as u64) as usize };
+
+    let mut line = format!("{} [", label);
+    line.extend((0..bar_width).map(|i| if i < filled { '#' } else { '.' }));
+    line.push(']');
+    line.push_str(&stats);
+
+    // Pad so that a shorter line overwrites all of the previous one
+    let len = line.chars().count();
+    line.extend((len..available).map(|_| ' '));
+    line.chars().take(available).collect()
+}
+
+/// Formats a byte count using binary units e.g. "1.5 MiB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut unit = 0;
+    let mut scaled = bytes;
+    while scaled >= 1024 * 1024 && unit < UNITS.len() - 2 {
+        scaled /= 1024;
+        unit += 1;
+    }
+
+    if scaled < 1024 {
+        format!("{} {}", scaled, UNITS[unit])
+    } else {
+        format!("{}.{} {}", scaled / 1024, scaled % 1024 * 10 / 1024, UNITS[unit + 1])
+    }
+}
+
+// Formats seconds as m:ss or h:mm:ss
+fn format_duration(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_formatted_in_binary_units() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024 + 512 * 1024 * 1024), "3.5 GiB");
+    }
+
+    #[test]
+    fn durations_are_formatted_as_clock_time() {
+        assert_eq!(format_duration(5), "0:05");
+        assert_eq!(format_duration(125), "2:05");
+        assert_eq!(format_duration(3725), "1:02:05");
+    }
+
+    #[test]
+    fn bar_line_fits_the_console() {
+        let line = format_bar_line("Copy", 50, 100, None, 31);
+        assert_eq!(line, "Copy [#########.........]  50%");
+        assert_eq!(line.len(), 30);
+
+        let line = format_bar_line("Copy", 0, 0, None, 21);
+        assert_eq!(line, "Copy [########] 100%");
+
+        let line = format_bar_line("Copy", 1024, 4096, Some(1024), 80);
+        assert!(line.starts_with("Copy [###"));
+        assert!(line.trim_right().ends_with("25% 1.0 KiB/s ETA 0:03"));
+        assert_eq!(line.chars().count(), 79);
+
+        assert_eq!(format_bar_line("A long label", 1, 2, None, 10), "A long la");
+    }
+}
@@ -0,0 +1,180 @@
+use ffi::rng::*;
+use time::{timestamp_ticks, sleep};
+use utils::locate_protocol;
+use alloc::Vec;
+use core::{ptr, mem, time::Duration};
+use {Result, Guid};
+
+// Random numbers from EFI_RNG_PROTOCOL. For firmware that doesn't provide the protocol there is a much
+// weaker source that harvests timing jitter from the timestamp counter, which callers have to opt into.
+// The `getrandom` feature makes the protocol the backend of the getrandom crate. That crate needs a newer
+// compiler than the nightly this crate is pinned to, so the feature only builds on a newer toolchain.
+
+/// An algorithm EFI_RNG_PROTOCOL can generate values with
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Algorithm {
+    Sp800_90Hash256,
+    Sp800_90Hmac256,
+    Sp800_90Ctr256,
+    X9_31TripleDes,
+    X9_31Aes,
+    /// Unconditioned output straight from the entropy source
+    Raw,
+    Other(Guid),
+}
+
+impl From<Guid> for Algorithm {
+    fn from(guid: Guid) -> Self {
+        match guid {
+            EFI_RNG_ALGORITHM_SP800_90_HASH_256_GUID => Algorithm::Sp800_90Hash256,
+            EFI_RNG_ALGORITHM_SP800_90_HMAC_256_GUID => Algorithm::Sp800_90Hmac256,
+            EFI_RNG_ALGORITHM_SP800_90_CTR_256_GUID => Algorithm::Sp800_90Ctr256,
+            EFI_RNG_ALGORITHM_X9_31_3DES_GUID => Algorithm::X9_31TripleDes,
+            EFI_RNG_ALGORITHM_X9_31_AES_GUID => Algorithm::X9_31Aes,
+            EFI_RNG_ALGORITHM_RAW => Algorithm::Raw,
+            guid => Algorithm::Other(guid),
+        }
+    }
+}
+
+impl From<Algorithm> for Guid {
+    fn from(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Sp800_90Hash256 => EFI_RNG_ALGORITHM_SP800_90_HASH_256_GUID,
+            Algorithm::Sp800_90Hmac256 => EFI_RNG_ALGORITHM_SP800_90_HMAC_256_GUID,
+            Algorithm::Sp800_90Ctr256 => EFI_RNG_ALGORITHM_SP800_90_CTR_256_GUID,
+            Algorithm::X9_31TripleDes => EFI_RNG_ALGORITHM_X9_31_3DES_GUID,
+            Algorithm::X9_31Aes => EFI_RNG_ALGORITHM_X9_31_AES_GUID,
+            Algorithm::Raw => EFI_RNG_ALGORITHM_RAW,
+            Algorithm::Other(guid) => guid,
+        }
+    }
+}
+
+/// The firmware's random number generator
+pub struct Rng {
+    protocol: *const EFI_RNG_PROTOCOL,
+}
+
+impl Rng {
+    pub fn get() -> Result<Self> {
+        Ok(Rng { protocol: locate_protocol(&EFI_RNG_PROTOCOL_GUID)? })
+    }
+
+    /// The algorithms the generator supports
+    pub fn algorithms(&self) -> Result<Vec<Algorithm>> {
+        let mut size = 0;
+        let status = unsafe { ((*self.protocol).GetInfo)(self.protocol, &mut size, ptr::null_mut()) };
+        if status != ::ffi::EFI_BUFFER_TOO_SMALL {
+            ret_on_err!(status);
+            return Ok(Vec::new());
+        }
+
+        let mut algorithms: Vec<Guid> = vec![unsafe { mem::zeroed() }; size / mem::size_of::<Guid>()];
+        unsafe {
+            ret_on_err!(((*self.protocol).GetInfo)(self.protocol, &mut size, algorithms.as_mut_ptr()));
+        }
+
+        algorithms.truncate(size / mem::size_of::<Guid>());
+        Ok(algorithms.into_iter().map(Algorithm::from).collect())
+    }
+
+    /// Fills the buffer using the generator's default algorithm
+    pub fn fill(&self, buf: &mut [u8]) -> Result<()> {
+        self.get_rng(ptr::null(), buf)
+    }
+
+    /// Fills the buffer using the given algorithm
+    pub fn fill_with(&self, algorithm: Algorithm, buf: &mut [u8]) -> Result<()> {
+        self.get_rng(&algorithm.into(), buf)
+    }
+
+    fn get_rng(&self, algorithm: *const Guid, buf: &mut [u8]) -> Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        unsafe {
+            ret_on_err!(((*self.protocol).GetRNG)(self.protocol, algorithm, buf.len(), buf.as_mut_ptr()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Fills the buffer with random bytes from EFI_RNG_PROTOCOL. Fails with `NotFound` if the firmware doesn't
+/// have it rather than handing out weaker bytes; see `get_jitter_bytes()` for a fallback.
+pub fn get_random_bytes(buf: &mut [u8]) -> Result<()> {
+    Rng::get()?.fill(buf)
+}
+
+// Number of timing samples folded into every 8 bytes of output
+const JITTER_SAMPLES: usize = 64;
+
+static mut JITTER_STATE: u64 = 0;
+
+/// Fills the buffer with bytes harvested from jitter in the timestamp counter. Meant for firmware without
+/// EFI_RNG_PROTOCOL, where it's better than nothing for e.g. seeding a hash table or picking a port number,
+/// but it's no match for a hardware source and must not be used for keys or anything else secret.
+pub fn get_jitter_bytes(buf: &mut [u8]) -> Result<()> {
+    // Carry the state over between calls so that back to back calls don't start from the same place
+    let mut state = unsafe { JITTER_STATE } ^ timestamp_ticks()? ^ (buf.as_ptr() as u64);
+    for chunk in buf.chunks_mut(8) {
+        for _ in 0..JITTER_SAMPLES {
+            let before = timestamp_ticks()?;
+            sleep(Duration::from_micros(1))?;
+            let after = timestamp_ticks()?;
+            state = mix(state ^ (after.wrapping_sub(before)).rotate_left(32) ^ after);
+        }
+
+        for (i, byte) in chunk.iter_mut().enumerate() {
+            *byte = (state >> (i * 8)) as u8;
+        }
+    }
+
+    unsafe { JITTER_STATE = mix(state) };
+    Ok(())
+}
+
+// The SplitMix64 finalizer: spreads every input bit over the whole output
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+// Makes get_random_bytes() the backend for the getrandom crate so that crates depending on it
+// (uuid, rand, TLS stacks) work without further setup. There is no jitter fallback here: without
+// EFI_RNG_PROTOCOL getrandom fails rather than handing out weak bytes.
+#[cfg(feature = "getrandom")]
+fn getrandom_backend(buf: &mut [u8]) -> ::core::result::Result<(), ::getrandom::Error> {
+    get_random_bytes(buf).map_err(|_| ::getrandom::Error::UNSUPPORTED)
+}
+
+#[cfg(feature = "getrandom")]
+register_custom_getrandom!(getrandom_backend);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn algorithms_convert_to_and_from_guids() {
+        let algorithm: Algorithm = EFI_RNG_ALGORITHM_SP800_90_CTR_256_GUID.into();
+        assert_eq!(algorithm, Algorithm::Sp800_90Ctr256);
+        assert_eq!(Guid::from(Algorithm::Raw), EFI_RNG_ALGORITHM_RAW);
+
+        let other = ::ffi::EFI_GUID(1, 2, 3, [4; 8]);
+        assert_eq!(Algorithm::from(other), Algorithm::Other(other));
+        assert_eq!(Guid::from(Algorithm::Other(other)), other);
+    }
+
+    #[test]
+    fn mixing_changes_every_byte() {
+        // A single bit of difference in the input flips about half the output bits
+        let (a, b) = (mix(0), mix(1));
+        assert!((a ^ b).count_ones() > 16);
+        assert_ne!(mix(a), a);
+    }
+}
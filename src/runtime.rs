@@ -0,0 +1,120 @@
+use ffi::{
+    EFI_EVENT,
+    EFI_STATUS,
+    EFI_SUCCESS,
+    EFI_SYSTEM_TABLE,
+    VOID,
+    boot_services::{EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE, TPL_NOTIFY},
+    runtime_services::{EFI_RUNTIME_SERVICES, EFI_OPTIONAL_PTR},
+};
+use core::{ptr, mem};
+use {system_table, boot_services, Result, EfiErrorKind, SYSTEM_TABLE};
+
+// Support for code that keeps running after the OS has called ExitBootServices() and SetVirtualAddressMap(),
+// i.e. runtime drivers. Once the OS switches to virtual addressing every pointer such code holds
+// must be converted to its virtual equivalent with ConvertPointer() from within the virtual address change event.
+// ConvertPointer() is only callable from that event.
+
+const MAX_CALLBACKS: usize = 16;
+
+// Plain fn pointers in a fixed array so that no allocation is needed when the event fires
+// (boot services, and hence the heap, are gone by then)
+static mut CALLBACKS: [Option<fn()>; MAX_CALLBACKS] = [None; MAX_CALLBACKS];
+static mut RUNTIME_SERVICES: Option<*const EFI_RUNTIME_SERVICES> = None;
+static mut VIRTUAL_ADDRESS_CHANGE_EVENT: Option<EFI_EVENT> = None;
+static mut IS_VIRTUAL: bool = false;
+
+/// Converts the given physical pointer to its virtual address in place.
+/// Must only be called from within a virtual address change callback.
+pub unsafe fn convert_pointer<T>(pointer: &mut *const T) -> Result<()> {
+    convert(pointer, 0)
+}
+
+/// Same as `convert_pointer()` except that a null pointer is allowed and is left as null.
+pub unsafe fn convert_optional_pointer<T>(pointer: &mut *const T) -> Result<()> {
+    convert(pointer, EFI_OPTIONAL_PTR)
+}
+
+unsafe fn convert<T>(pointer: &mut *const T, disposition: usize) -> Result<()> {
+    let rs = runtime_services();
+    let address: *mut *const VOID = mem::transmute(pointer);
+    ret_on_err!(((*rs).ConvertPointer)(disposition, address));
+    Ok(())
+}
+
+/// The runtime services table. Unlike `system_table().RuntimeServices` this stays valid
+/// after the switch to virtual addressing if `enable_virtual_address_change_fixups()` was called.
+pub fn runtime_services() -> *const EFI_RUNTIME_SERVICES {
+    unsafe {
+        match RUNTIME_SERVICES {
+            Some(rs) => rs,
+            None => system_table().RuntimeServices
+        }
+    }
+}
+
+/// Whether the OS has switched the firmware to virtual addressing
+pub fn is_virtual() -> bool {
+    unsafe { IS_VIRTUAL }
+}
+
+/// Registers for the virtual address change event so that the pointers cached
+/// by this crate (the system table and runtime services table) are fixed up when it fires.
+/// Must be called by runtime drivers before ExitBootServices(). Calling it more than once is harmless.
+pub fn enable_virtual_address_change_fixups() -> Result<()> {
+    unsafe {
+        if VIRTUAL_ADDRESS_CHANGE_EVENT.is_some() {
+            return Ok(());
+        }
+
+        RUNTIME_SERVICES = Some(system_table().RuntimeServices);
+
+        let bs = boot_services();
+        let mut event: EFI_EVENT = ptr::null();
+        ret_on_err!(((*bs).CreateEvent)(EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE, TPL_NOTIFY, Some(virtual_address_change_cb), ptr::null(), &mut event));
+        VIRTUAL_ADDRESS_CHANGE_EVENT = Some(event);
+    }
+
+    Ok(())
+}
+
+/// Registers a callback to be invoked when the OS switches to virtual addressing.
+/// Use it to convert any pointers the driver holds with `convert_pointer()`.
+/// Callbacks are invoked after the crate's own pointers have been converted.
+/// Implicitly calls `enable_virtual_address_change_fixups()`.
+pub fn on_virtual_address_change(callback: fn()) -> Result<()> {
+    enable_virtual_address_change_fixups()?;
+
+    unsafe {
+        for slot in CALLBACKS.iter_mut() {
+            if slot.is_none() {
+                *slot = Some(callback);
+                return Ok(());
+            }
+        }
+    }
+
+    Err(EfiErrorKind::OutOfResources.into())
+}
+
+extern "win64" fn virtual_address_change_cb(_event: EFI_EVENT, _context: *const VOID) -> EFI_STATUS {
+    unsafe {
+        // The runtime services pointer is converted last because ConvertPointer() itself is reached through it
+        if let Some(ref mut rs) = RUNTIME_SERVICES {
+            let mut system_table_ptr = SYSTEM_TABLE.get().cloned().unwrap_or(ptr::null());
+            let _ = convert_optional_pointer(&mut system_table_ptr); // Can't do a thing if this fails
+            let _ = convert_pointer(rs);
+            SYSTEM_TABLE.replace(system_table_ptr as *const EFI_SYSTEM_TABLE);
+        }
+
+        IS_VIRTUAL = true;
+
+        for callback in CALLBACKS.iter() {
+            if let Some(callback) = *callback {
+                callback();
+            }
+        }
+    }
+
+    EFI_SUCCESS
+}
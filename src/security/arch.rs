@@ -0,0 +1,196 @@
+use ffi::{
+    security_arch::*,
+    device_path::EFI_DEVICE_PATH_PROTOCOL,
+    boot_services::EFI_INTERFACE_TYPE,
+    EFI_HANDLE,
+    EFI_STATUS,
+    EFI_SUCCESS,
+    EFI_ACCESS_DENIED,
+    EFI_INVALID_PARAMETER,
+    UINTN,
+    BOOLEAN,
+    VOID,
+};
+use device_path::DevicePath;
+use utils::locate_protocol;
+use core::{ptr, slice};
+use {Result, EfiErrorKind, boot_services, to_boolean, from_boolean};
+
+// Image authentication through EFI_SECURITY2_ARCH_PROTOCOL. The DXE core asks it about every image
+// it loads, so querying it tells whether LoadImage() would accept an image and hooking it lets a
+// platform component decide that itself.
+
+/// The platform's image authentication service
+pub struct Security2 {
+    protocol: *const EFI_SECURITY2_ARCH_PROTOCOL,
+}
+
+impl Security2 {
+    pub fn get() -> Result<Self> {
+        let protocol = locate()?;
+        Ok(Security2 { protocol })
+    }
+
+    /// Asks whether the image at `device_path`, whose contents are `file`, may be loaded. At least
+    /// one of the two must be given. Fails with `SecurityViolation` if the image failed authentication
+    /// but may still be loaded on the user's say so and `AccessDenied` if it must not be loaded at all.
+    pub fn authenticate(&self, device_path: Option<&DevicePath>, file: Option<&[u8]>, boot_policy: bool) -> Result<()> {
+        if device_path.is_none() && file.is_none() {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        unsafe {
+            ret_on_err!(((*self.protocol).FileAuthentication)(
+                self.protocol,
+                device_path.map_or(ptr::null(), |p| p.as_ptr()),
+                file.map_or(ptr::null(), |f| f.as_ptr() as *const VOID),
+                file.map_or(0, |f| f.len()),
+                to_boolean(boot_policy)));
+        }
+
+        Ok(())
+    }
+}
+
+fn locate() -> Result<*mut EFI_SECURITY2_ARCH_PROTOCOL> {
+    let protocol: *const EFI_SECURITY2_ARCH_PROTOCOL = locate_protocol(&EFI_SECURITY2_ARCH_PROTOCOL_GUID)?;
+    Ok(protocol as *mut EFI_SECURITY2_ARCH_PROTOCOL)
+}
+
+/// An image the DXE core wants authenticated
+pub struct AuthenticationRequest<'a> {
+    /// Where the image was loaded from. None for images loaded from a memory buffer.
+    pub device_path: Option<&'a DevicePath>,
+    /// The contents of the image. None if the core only wants to know whether the device path may be used.
+    pub file: Option<&'a [u8]>,
+    /// True if the image is being loaded for a boot option
+    pub boot_policy: bool,
+}
+
+/// What an `AuthenticationPolicy` decides about an image
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Load the image without consulting the platform's own checks
+    Allow,
+    /// Refuse to load the image
+    Deny,
+    /// Leave the decision to the platform's own checks
+    Defer,
+}
+
+pub type AuthenticationPolicy = fn(&AuthenticationRequest) -> Verdict;
+
+/// An authentication policy hooked into EFI_SECURITY2_ARCH_PROTOCOL. Unhooked when dropped.
+pub struct PolicyHook {
+    _private: (),
+}
+
+struct Hook {
+    policy: AuthenticationPolicy,
+    protocol: *mut EFI_SECURITY2_ARCH_PROTOCOL,
+    original: Option<EFI_SECURITY2_FILE_AUTHENTICATION>,
+    handle: EFI_HANDLE, // Set if we installed the protocol ourselves
+}
+
+static mut HOOK: Option<Hook> = None;
+
+static PROTOCOL: EFI_SECURITY2_ARCH_PROTOCOL = EFI_SECURITY2_ARCH_PROTOCOL { FileAuthentication: file_authentication_callback };
+
+impl PolicyHook {
+    /// Puts `policy` in front of the platform's image authentication. The DXE core holds on to the
+    /// EFI_SECURITY2_ARCH_PROTOCOL it found first, so if there already is one its FileAuthentication
+    /// is replaced and `Verdict::Defer` hands the image over to the original. Otherwise a new instance
+    /// is installed and `Verdict::Defer` allows the image.
+    /// Fails with `AlreadyStarted` if a policy is already hooked.
+    pub fn install(policy: AuthenticationPolicy) -> Result<Self> {
+        if unsafe { HOOK.is_some() } {
+            return Err(EfiErrorKind::AlreadyStarted.into());
+        }
+
+        let hook = match locate() {
+            Ok(protocol) => unsafe {
+                let original = (*protocol).FileAuthentication;
+                (*protocol).FileAuthentication = file_authentication_callback;
+                Hook { policy, protocol, original: Some(original), handle: ptr::null_mut() }
+            },
+            Err(ref e) if e.kind() == EfiErrorKind::NotFound => {
+                let bs = boot_services();
+                let mut handle: EFI_HANDLE = ptr::null_mut();
+                unsafe {
+                    ret_on_err!(((*bs).InstallProtocolInterface)(&mut handle, &EFI_SECURITY2_ARCH_PROTOCOL_GUID, EFI_INTERFACE_TYPE::EFI_NATIVE_INTERFACE, &PROTOCOL as *const EFI_SECURITY2_ARCH_PROTOCOL as *const VOID));
+                }
+                Hook { policy, protocol: ptr::null_mut(), original: None, handle }
+            },
+            Err(e) => return Err(e),
+        };
+
+        unsafe { HOOK = Some(hook) };
+        Ok(PolicyHook { _private: () })
+    }
+}
+
+impl Drop for PolicyHook {
+    fn drop(&mut self) {
+        let hook = match unsafe { HOOK.take() } {
+            Some(hook) => hook,
+            None => return,
+        };
+
+        unsafe {
+            if let Some(original) = hook.original {
+                (*hook.protocol).FileAuthentication = original;
+            } else {
+                let bs = boot_services();
+                ((*bs).UninstallProtocolInterface)(hook.handle, &EFI_SECURITY2_ARCH_PROTOCOL_GUID, &PROTOCOL as *const EFI_SECURITY2_ARCH_PROTOCOL as *const VOID); // TODO: Can't do anything if this fails. So we should log here
+            }
+        }
+    }
+}
+
+extern "win64" fn file_authentication_callback(
+    this: *const EFI_SECURITY2_ARCH_PROTOCOL,
+    device_path: *const EFI_DEVICE_PATH_PROTOCOL,
+    file_buffer: *const VOID,
+    file_size: UINTN,
+    boot_policy: BOOLEAN
+) -> EFI_STATUS {
+    let hook = match unsafe { HOOK.as_ref() } {
+        Some(hook) => hook,
+        None => return EFI_SUCCESS,
+    };
+
+    if device_path.is_null() && file_buffer.is_null() {
+        return EFI_INVALID_PARAMETER;
+    }
+
+    // DevicePath doesn't free its pointer so wrapping the caller's is fine
+    let path = if device_path.is_null() { None } else { DevicePath::from_ptr(device_path).ok() };
+    let file = if file_buffer.is_null() { None } else { Some(unsafe { slice::from_raw_parts(file_buffer as *const u8, file_size) }) };
+    let request = AuthenticationRequest { device_path: path.as_ref(), file, boot_policy: from_boolean(boot_policy) };
+
+    verdict_status((hook.policy)(&request), || match hook.original {
+        Some(original) => original(this, device_path, file_buffer, file_size, boot_policy),
+        None => EFI_SUCCESS,
+    })
+}
+
+fn verdict_status<F: FnOnce() -> EFI_STATUS>(verdict: Verdict, defer: F) -> EFI_STATUS {
+    match verdict {
+        Verdict::Allow => EFI_SUCCESS,
+        Verdict::Deny => EFI_ACCESS_DENIED,
+        Verdict::Defer => defer(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi::EFI_SECURITY_VIOLATION;
+
+    #[test]
+    fn verdicts_map_to_statuses() {
+        assert_eq!(verdict_status(Verdict::Allow, || EFI_SECURITY_VIOLATION), EFI_SUCCESS);
+        assert_eq!(verdict_status(Verdict::Deny, || EFI_SUCCESS), EFI_ACCESS_DENIED);
+        assert_eq!(verdict_status(Verdict::Defer, || EFI_SECURITY_VIOLATION), EFI_SECURITY_VIOLATION);
+    }
+}
@@ -0,0 +1,507 @@
+use ffi::security::WIN_CERT_TYPE_PKCS_SIGNED_DATA;
+use super::hash::{Hasher, HashAlgorithm};
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::Vec;
+use {Result, EfiError, EfiErrorKind};
+
+// Authenticode signatures of PE images. The signatures sit in the certificate table the security
+// data directory points to, as WIN_CERTIFICATE entries holding PKCS#7 SignedData whose content is
+// the image's Authenticode digest: the hash of the image minus its checksum, the security directory
+// entry and the certificate table itself. That digest is also what db and dbx hold for unsigned
+// images, so `PeImage::authenticode_digest()` can be looked up with `SignatureDatabase::contains_hash()`.
+
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+const SECURITY_DIRECTORY: usize = 4;
+const SECTION_HEADER_SIZE: usize = 40;
+const WIN_CERTIFICATE_HEADER_SIZE: usize = 8;
+
+/// A PE/COFF image in memory, e.g. a downloaded boot loader
+pub struct PeImage<'a> {
+    bytes: &'a [u8],
+    checksum_offset: usize,
+    security_entry_offset: Option<usize>, // None if the image has too few data directories to have one
+    headers_size: usize,
+    sections: Vec<(usize, usize)>, // File offset and size of each section's raw data, in file order
+    cert_table: Option<(usize, usize)>,
+}
+
+impl<'a> PeImage<'a> {
+    /// Parses the headers of the image. Fails with `InvalidParameter` if it isn't a well formed PE32 or PE32+ image.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        if bytes.len() < 0x40 || &bytes[..2] != b"MZ" {
+            return Err(malformed());
+        }
+
+        let pe_offset = LittleEndian::read_u32(&bytes[0x3C..]) as usize;
+        let coff_offset = pe_offset.checked_add(4).ok_or_else(malformed)?;
+        let optional_offset = coff_offset.checked_add(20).ok_or_else(malformed)?;
+        if bytes.len() < optional_offset + 2 || &bytes[pe_offset..coff_offset] != b"PE\0\0" {
+            return Err(malformed());
+        }
+
+        let section_count = LittleEndian::read_u16(&bytes[coff_offset + 2..]) as usize;
+        let optional_size = LittleEndian::read_u16(&bytes[coff_offset + 16..]) as usize;
+        let section_table_offset = optional_offset + optional_size;
+        let section_table_end = section_table_offset + section_count * SECTION_HEADER_SIZE;
+        if bytes.len() < section_table_end {
+            return Err(malformed());
+        }
+
+        let (rva_count_offset, directories_offset) = match LittleEndian::read_u16(&bytes[optional_offset..]) {
+            PE32_MAGIC => (optional_offset + 92, optional_offset + 96),
+            PE32_PLUS_MAGIC => (optional_offset + 108, optional_offset + 112),
+            _ => return Err(malformed()),
+        };
+
+        if directories_offset > section_table_offset {
+            return Err(malformed());
+        }
+
+        let rva_count = LittleEndian::read_u32(&bytes[rva_count_offset..]) as usize;
+        let security_entry_offset = directories_offset + SECURITY_DIRECTORY * 8;
+        let security_entry_offset = if rva_count > SECURITY_DIRECTORY && security_entry_offset + 8 <= section_table_offset {
+            Some(security_entry_offset)
+        } else {
+            None
+        };
+
+        let cert_table = match security_entry_offset {
+            Some(entry) => {
+                let offset = LittleEndian::read_u32(&bytes[entry..]) as usize;
+                let size = LittleEndian::read_u32(&bytes[entry + 4..]) as usize;
+                match offset.checked_add(size) {
+                    _ if size == 0 => None,
+                    Some(end) if end <= bytes.len() => Some((offset, size)),
+                    _ => return Err(malformed()),
+                }
+            },
+            None => None,
+        };
+
+        let headers_size = LittleEndian::read_u32(&bytes[optional_offset + 60..]) as usize;
+        if headers_size < section_table_end || headers_size > bytes.len() {
+            return Err(malformed());
+        }
+
+        let mut sections = Vec::with_capacity(section_count);
+        for i in 0..section_count {
+            let header = &bytes[section_table_offset + i * SECTION_HEADER_SIZE..];
+            let size = LittleEndian::read_u32(&header[16..]) as usize;
+            let offset = LittleEndian::read_u32(&header[20..]) as usize;
+            if size == 0 {
+                continue;
+            }
+
+            if offset.checked_add(size).map_or(true, |end| end > bytes.len()) {
+                return Err(malformed());
+            }
+
+            sections.push((offset, size));
+        }
+
+        sections.sort_by_key(|&(offset, _)| offset);
+
+        Ok(PeImage { bytes, checksum_offset: optional_offset + 64, security_entry_offset, headers_size, sections, cert_table })
+    }
+
+    /// Whether the image carries a certificate table. It says nothing about the signatures being valid.
+    pub fn is_signed(&self) -> bool {
+        self.cert_table.is_some()
+    }
+
+    /// The entries of the certificate table
+    pub fn certificates(&self) -> Result<Vec<WinCertificate<'a>>> {
+        match self.cert_table {
+            Some((offset, size)) => parse_certificates(&self.bytes[offset..offset + size]),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Computes the Authenticode digest of the image with the given hasher
+    pub fn authenticode_digest<H: Hasher + ?Sized>(&self, hasher: &mut H) -> Result<Vec<u8>> {
+        let bytes = self.bytes;
+        hasher.update(&bytes[..self.checksum_offset])?;
+        match self.security_entry_offset {
+            Some(entry) => {
+                hasher.update(&bytes[self.checksum_offset + 4..entry])?;
+                hasher.update(&bytes[entry + 8..self.headers_size])?;
+            },
+            None => hasher.update(&bytes[self.checksum_offset + 4..self.headers_size])?,
+        }
+
+        let mut hashed = self.headers_size;
+        for &(offset, size) in self.sections.iter() {
+            hasher.update(&bytes[offset..offset + size])?;
+            hashed += size;
+        }
+
+        // Whatever follows the sections, e.g. debug data, except the certificate table
+        let end = bytes.len() - self.cert_table.map_or(0, |(_, size)| size);
+        if end > hashed {
+            hasher.update(&bytes[hashed..end])?;
+        }
+
+        hasher.finalize()
+    }
+}
+
+/// An entry of a PE image's certificate table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WinCertificate<'a> {
+    pub revision: u16,
+    /// One of the WIN_CERT_TYPE_* values. Authenticode signatures are WIN_CERT_TYPE_PKCS_SIGNED_DATA.
+    pub certificate_type: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> WinCertificate<'a> {
+    /// Parses the entry as an Authenticode signature. Fails with `Unsupported` if it's of some other type.
+    pub fn signed_data(&self) -> Result<SignedData<'a>> {
+        if self.certificate_type != WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+            return Err(EfiErrorKind::Unsupported.into());
+        }
+
+        SignedData::parse(self.data)
+    }
+}
+
+// Entries are 8 byte aligned
+fn parse_certificates(table: &[u8]) -> Result<Vec<WinCertificate>> {
+    let mut certificates = Vec::new();
+    let mut offset = 0;
+    while offset + WIN_CERTIFICATE_HEADER_SIZE <= table.len() {
+        let length = LittleEndian::read_u32(&table[offset..]) as usize;
+        if length < WIN_CERTIFICATE_HEADER_SIZE || length > table.len() - offset {
+            return Err(malformed());
+        }
+
+        certificates.push(WinCertificate {
+            revision: LittleEndian::read_u16(&table[offset + 4..]),
+            certificate_type: LittleEndian::read_u16(&table[offset + 6..]),
+            data: &table[offset + WIN_CERTIFICATE_HEADER_SIZE..offset + length],
+        });
+        offset += (length + 7) & !7;
+    }
+
+    Ok(certificates)
+}
+
+const SIGNED_DATA_OID: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02]; // 1.2.840.113549.1.7.2
+const SPC_INDIRECT_DATA_OID: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x01, 0x04]; // 1.3.6.1.4.1.311.2.1.4
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_CONTEXT_0: u8 = 0xA0;
+const TAG_CONTEXT_1: u8 = 0xA1;
+
+/// An Authenticode signature: PKCS#7 SignedData over the image's Authenticode digest.
+/// Only the structure is parsed; checking the signature is up to `Pkcs7Verifier`.
+#[derive(Debug, Clone)]
+pub struct SignedData<'a> {
+    digest_algorithm: Option<HashAlgorithm>,
+    digest: &'a [u8],
+    certificates: Vec<&'a [u8]>,
+    signer: Option<(&'a [u8], &'a [u8])>, // Issuer name and serial number identifying the signer's certificate
+}
+
+impl<'a> SignedData<'a> {
+    /// Parses DER encoded SignedData with Authenticode (SpcIndirectDataContent) content.
+    /// Fails with `InvalidParameter` if it's anything else.
+    pub fn parse(der: &'a [u8]) -> Result<Self> {
+        let (content_info, _) = expect(der, TAG_SEQUENCE)?;
+        let (oid, rest) = expect(content_info, TAG_OID)?;
+        if oid != SIGNED_DATA_OID {
+            return Err(malformed());
+        }
+
+        let (explicit, _) = expect(rest, TAG_CONTEXT_0)?;
+        let (signed_data, _) = expect(explicit, TAG_SEQUENCE)?;
+        let (_version, rest) = expect(signed_data, TAG_INTEGER)?;
+        let (_digest_algorithms, rest) = expect(rest, TAG_SET)?;
+        let (encapsulated, mut rest) = expect(rest, TAG_SEQUENCE)?;
+
+        let mut certificates = Vec::new();
+        if rest.first() == Some(&TAG_CONTEXT_0) {
+            let (mut set, after) = expect(rest, TAG_CONTEXT_0)?;
+            while !set.is_empty() {
+                let element = der_element(set).ok_or_else(malformed)?;
+                certificates.push(element.whole);
+                set = element.rest;
+            }
+            rest = after;
+        }
+
+        if rest.first() == Some(&TAG_CONTEXT_1) {
+            rest = expect(rest, TAG_CONTEXT_1)?.1;
+        }
+
+        let (signer_infos, _) = expect(rest, TAG_SET)?;
+        let signer = match expect(signer_infos, TAG_SEQUENCE) {
+            Ok((signer_info, _)) => Some(signer_identifier(signer_info)?),
+            Err(_) => None,
+        };
+
+        let (oid, rest) = expect(encapsulated, TAG_OID)?;
+        if oid != SPC_INDIRECT_DATA_OID {
+            return Err(malformed());
+        }
+
+        let (explicit, _) = expect(rest, TAG_CONTEXT_0)?;
+        let (indirect_data, _) = expect(explicit, TAG_SEQUENCE)?;
+        let (_data, rest) = expect(indirect_data, TAG_SEQUENCE)?;
+        let (digest_info, _) = expect(rest, TAG_SEQUENCE)?;
+        let (algorithm, rest) = expect(digest_info, TAG_SEQUENCE)?;
+        let (algorithm_oid, _) = expect(algorithm, TAG_OID)?;
+        let (digest, _) = expect(rest, TAG_OCTET_STRING)?;
+
+        Ok(SignedData { digest_algorithm: algorithm_from_oid(algorithm_oid), digest, certificates, signer })
+    }
+
+    /// The algorithm of the signed digest. None if it's one `HashAlgorithm` doesn't know.
+    pub fn digest_algorithm(&self) -> Option<HashAlgorithm> {
+        self.digest_algorithm
+    }
+
+    /// The Authenticode digest that was signed. An image is what was signed only if this matches
+    /// its `PeImage::authenticode_digest()`.
+    pub fn digest(&self) -> &'a [u8] {
+        self.digest
+    }
+
+    /// The DER encoded certificates embedded in the signature, the signer's and usually its issuers'
+    pub fn certificates(&self) -> &[&'a [u8]] {
+        &self.certificates
+    }
+
+    /// The DER encoded certificate of the signer if it's embedded
+    pub fn signer_certificate(&self) -> Option<&'a [u8]> {
+        let (issuer, serial) = self.signer?;
+        self.certificates.iter()
+            .cloned()
+            .find(|c| certificate_identifier(c).ok() == Some((issuer, serial)))
+    }
+}
+
+// SignerInfo starts with its version and an IssuerAndSerialNumber
+fn signer_identifier(signer_info: &[u8]) -> Result<(&[u8], &[u8])> {
+    let (_version, rest) = expect(signer_info, TAG_INTEGER)?;
+    let (sid, _) = expect(rest, TAG_SEQUENCE)?;
+    let issuer = der_element(sid).ok_or_else(malformed)?;
+    let (serial, _) = expect(issuer.rest, TAG_INTEGER)?;
+    Ok((issuer.whole, serial))
+}
+
+// The issuer name and serial number from a certificate's TBSCertificate
+fn certificate_identifier(certificate: &[u8]) -> Result<(&[u8], &[u8])> {
+    let (certificate, _) = expect(certificate, TAG_SEQUENCE)?;
+    let (mut tbs, _) = expect(certificate, TAG_SEQUENCE)?;
+    if tbs.first() == Some(&TAG_CONTEXT_0) {
+        tbs = expect(tbs, TAG_CONTEXT_0)?.1; // Version
+    }
+
+    let (serial, rest) = expect(tbs, TAG_INTEGER)?;
+    let (_signature_algorithm, rest) = expect(rest, TAG_SEQUENCE)?;
+    let issuer = der_element(rest).ok_or_else(malformed)?;
+    Ok((issuer.whole, serial))
+}
+
+fn algorithm_from_oid(oid: &[u8]) -> Option<HashAlgorithm> {
+    const NIST_HASH_PREFIX: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02]; // 2.16.840.1.101.3.4.2
+    match oid {
+        [0x2B, 0x0E, 0x03, 0x02, 0x1A] => Some(HashAlgorithm::Sha1),
+        [0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x02, 0x05] => Some(HashAlgorithm::Md5),
+        _ if oid.len() == NIST_HASH_PREFIX.len() + 1 && oid.starts_with(NIST_HASH_PREFIX) => match oid[oid.len() - 1] {
+            1 => Some(HashAlgorithm::Sha256),
+            2 => Some(HashAlgorithm::Sha384),
+            3 => Some(HashAlgorithm::Sha512),
+            4 => Some(HashAlgorithm::Sha224),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+struct DerElement<'a> {
+    tag: u8,
+    contents: &'a [u8],
+    whole: &'a [u8],
+    rest: &'a [u8],
+}
+
+// Splits off the element at the start of `bytes`. Only definite lengths of up to 4 bytes are accepted.
+fn der_element(bytes: &[u8]) -> Option<DerElement> {
+    if bytes.len() < 2 {
+        return None;
+    }
+
+    let (header_size, length) = match bytes[1] {
+        len @ 0...0x7F => (2, len as usize),
+        count @ 0x81...0x84 => {
+            let count = (count & 0x7F) as usize;
+            if bytes.len() < 2 + count {
+                return None;
+            }
+
+            (2 + count, bytes[2..2 + count].iter().fold(0, |len, b| len << 8 | *b as usize))
+        },
+        _ => return None,
+    };
+
+    let end = header_size.checked_add(length)?;
+    if end > bytes.len() {
+        return None;
+    }
+
+    Some(DerElement { tag: bytes[0], contents: &bytes[header_size..end], whole: &bytes[..end], rest: &bytes[end..] })
+}
+
+// Returns the contents of the element at the start of `bytes`, which must have the given tag, and what follows it
+fn expect(bytes: &[u8], tag: u8) -> Result<(&[u8], &[u8])> {
+    match der_element(bytes) {
+        Some(ref element) if element.tag == tag => Ok((element.contents, element.rest)),
+        _ => Err(malformed()),
+    }
+}
+
+fn malformed() -> EfiError {
+    EfiErrorKind::InvalidParameter.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Recorder(Vec<u8>);
+
+    impl Hasher for Recorder {
+        fn algorithm(&self) -> HashAlgorithm {
+            HashAlgorithm::Sha256
+        }
+
+        fn update(&mut self, data: &[u8]) -> Result<()> {
+            self.0.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn finalize(&mut self) -> Result<Vec<u8>> {
+            Ok(::core::mem::replace(&mut self.0, Vec::new()))
+        }
+    }
+
+    // A PE32+ image with 16 data directories, two sections (listed out of file order), trailing
+    // data and a certificate table at the end
+    fn image() -> Vec<u8> {
+        let mut bytes = (0..0x400u32).map(|i| i as u8).collect::<Vec<_>>();
+        bytes[..2].copy_from_slice(b"MZ");
+        LittleEndian::write_u32(&mut bytes[0x3C..], 0x80);
+        bytes[0x80..0x84].copy_from_slice(b"PE\0\0");
+        LittleEndian::write_u16(&mut bytes[0x86..], 2); // NumberOfSections
+        LittleEndian::write_u16(&mut bytes[0x94..], 240); // SizeOfOptionalHeader
+        LittleEndian::write_u16(&mut bytes[0x98..], PE32_PLUS_MAGIC);
+        LittleEndian::write_u32(&mut bytes[0x98 + 60..], 0x200); // SizeOfHeaders
+        LittleEndian::write_u32(&mut bytes[0x98 + 108..], 16); // NumberOfRvaAndSizes
+        LittleEndian::write_u32(&mut bytes[0x98 + 112 + 32..], 0x3E0); // Security directory
+        LittleEndian::write_u32(&mut bytes[0x98 + 112 + 36..], 0x20);
+        let sections = 0x98 + 240;
+        LittleEndian::write_u32(&mut bytes[sections + 16..], 0x80);
+        LittleEndian::write_u32(&mut bytes[sections + 20..], 0x300);
+        LittleEndian::write_u32(&mut bytes[sections + 40 + 16..], 0x100);
+        LittleEndian::write_u32(&mut bytes[sections + 40 + 20..], 0x200);
+
+        // Two WIN_CERTIFICATEs, the first padded to 8 bytes
+        bytes[0x3E0..].copy_from_slice(&[
+            0x0B, 0, 0, 0, 0x00, 0x02, 0x02, 0x00, 1, 2, 3, 0, 0, 0, 0, 0,
+            0x0A, 0, 0, 0, 0x00, 0x02, 0xF1, 0x0E, 4, 5, 0, 0, 0, 0, 0, 0,
+        ]);
+        bytes
+    }
+
+    #[test]
+    fn digest_skips_checksum_security_entry_and_certificates() {
+        let bytes = image();
+        let pe = PeImage::parse(&bytes).unwrap();
+        assert!(pe.is_signed());
+
+        let checksum = 0x98 + 64;
+        let entry = 0x98 + 112 + 32;
+        let mut expected = bytes[..checksum].to_vec();
+        expected.extend_from_slice(&bytes[checksum + 4..entry]);
+        expected.extend_from_slice(&bytes[entry + 8..0x200]);
+        expected.extend_from_slice(&bytes[0x200..0x300]);
+        expected.extend_from_slice(&bytes[0x300..0x380]);
+        expected.extend_from_slice(&bytes[0x380..0x3E0]);
+        assert_eq!(pe.authenticode_digest(&mut Recorder(Vec::new())).unwrap(), expected);
+
+        let certificates = pe.certificates().unwrap();
+        assert_eq!(certificates.len(), 2);
+        assert_eq!(certificates[0], WinCertificate { revision: 0x0200, certificate_type: WIN_CERT_TYPE_PKCS_SIGNED_DATA, data: &[1, 2, 3] });
+        assert_eq!(certificates[1].data, &[4, 5]);
+        assert_eq!(certificates[1].signed_data().unwrap_err().kind(), EfiErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn malformed_images_are_rejected() {
+        let mut bytes = image();
+        assert!(PeImage::parse(&bytes[..0x100]).is_err());
+        bytes[0x80] = b'X';
+        assert!(PeImage::parse(&bytes).is_err());
+        assert!(PeImage::parse(b"MZ").is_err());
+    }
+
+    fn tlv(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+        let contents = parts.iter().flat_map(|p| p.iter().cloned()).collect::<Vec<u8>>();
+        let mut bytes = vec![tag];
+        if contents.len() < 0x80 {
+            bytes.push(contents.len() as u8);
+        } else {
+            bytes.extend_from_slice(&[0x82, (contents.len() >> 8) as u8, contents.len() as u8]);
+        }
+
+        bytes.extend_from_slice(&contents);
+        bytes
+    }
+
+    fn certificate(serial: u8, issuer: &str) -> Vec<u8> {
+        let tbs = tlv(TAG_SEQUENCE, &[
+            &tlv(TAG_CONTEXT_0, &[&tlv(TAG_INTEGER, &[&[2]])]),
+            &tlv(TAG_INTEGER, &[&[serial]]),
+            &tlv(TAG_SEQUENCE, &[]),
+            &tlv(TAG_SEQUENCE, &[issuer.as_bytes()]),
+        ]);
+        tlv(TAG_SEQUENCE, &[&tbs, &tlv(TAG_SEQUENCE, &[]), &[0x03, 0x01, 0x00]])
+    }
+
+    #[test]
+    fn signed_data_yields_digest_and_signer() {
+        let sha256 = [0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+        let digest_info = tlv(TAG_SEQUENCE, &[&tlv(TAG_SEQUENCE, &[&tlv(TAG_OID, &[&sha256]), &[0x05, 0x00]]), &tlv(TAG_OCTET_STRING, &[&[0xAB; 32]])]);
+        let indirect_data = tlv(TAG_SEQUENCE, &[&tlv(TAG_SEQUENCE, &[]), &digest_info]);
+        let encapsulated = tlv(TAG_SEQUENCE, &[&tlv(TAG_OID, &[SPC_INDIRECT_DATA_OID]), &tlv(TAG_CONTEXT_0, &[&indirect_data])]);
+        let (ca, leaf) = (certificate(1, "root"), certificate(7, "root"));
+        let signer_info = tlv(TAG_SEQUENCE, &[
+            &tlv(TAG_INTEGER, &[&[1]]),
+            &tlv(TAG_SEQUENCE, &[&tlv(TAG_SEQUENCE, &[b"root"]), &tlv(TAG_INTEGER, &[&[7]])]),
+        ]);
+        let signed_data = tlv(TAG_SEQUENCE, &[
+            &tlv(TAG_INTEGER, &[&[1]]),
+            &tlv(TAG_SET, &[]),
+            &encapsulated,
+            &tlv(TAG_CONTEXT_0, &[&ca, &leaf]),
+            &tlv(TAG_SET, &[&signer_info]),
+        ]);
+        let der = tlv(TAG_SEQUENCE, &[&tlv(TAG_OID, &[SIGNED_DATA_OID]), &tlv(TAG_CONTEXT_0, &[&signed_data])]);
+
+        let parsed = SignedData::parse(&der).unwrap();
+        assert_eq!(parsed.digest_algorithm(), Some(HashAlgorithm::Sha256));
+        assert_eq!(parsed.digest(), &[0xAB; 32][..]);
+        assert_eq!(parsed.certificates().len(), 2);
+        assert_eq!(parsed.signer_certificate(), Some(&leaf[..]));
+
+        assert!(SignedData::parse(&der[..der.len() - 1]).is_err());
+        assert!(SignedData::parse(&signed_data).is_err());
+    }
+}
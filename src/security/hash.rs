@@ -0,0 +1,116 @@
+use alloc::{Vec, boxed::Box};
+use {Result, EfiErrorKind};
+
+// Message digests. `Hasher` is the streaming interface every implementation provides; the
+// firmware's EFI_HASH2_PROTOCOL is one of them (see `Hash2Hasher`), the software SHA-1 and
+// SHA-256 of the `soft-hash` feature the others.
+
+/// A hash algorithm
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// The size of the algorithm's digests in bytes
+    pub fn digest_size(&self) -> usize {
+        match *self {
+            HashAlgorithm::Md5 => 16,
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha224 => 28,
+            HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha384 => 48,
+            HashAlgorithm::Sha512 => 64,
+        }
+    }
+}
+
+/// A streaming hash computation: feed the data in with `update()` and get the digest with `finalize()`
+pub trait Hasher {
+    fn algorithm(&self) -> HashAlgorithm;
+
+    fn update(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Returns the digest of everything passed to `update()` so far and starts a new computation
+    fn finalize(&mut self) -> Result<Vec<u8>>;
+
+    /// Finishes the computation and checks the digest against an expected one,
+    /// failing with `SecurityViolation` if they differ
+    fn verify(&mut self, expected: &[u8]) -> Result<()> {
+        if digests_equal(&self.finalize()?, expected) {
+            Ok(())
+        } else {
+            Err(EfiErrorKind::SecurityViolation.into())
+        }
+    }
+}
+
+/// A hasher for the algorithm: the firmware's EFI_HASH2_PROTOCOL if it can do the algorithm, otherwise
+/// the software implementation when the `soft-hash` feature is on. Fails with `Unsupported` if neither can.
+pub fn new_hasher(algorithm: HashAlgorithm) -> Result<Box<Hasher>> {
+    #[cfg(not(feature = "runtime-driver"))]
+    {
+        if let Ok(hasher) = super::hash2::Hash2Hasher::new(algorithm) {
+            return Ok(Box::new(hasher));
+        }
+    }
+
+    #[cfg(feature = "soft-hash")]
+    {
+        match algorithm {
+            HashAlgorithm::Sha256 => return Ok(Box::new(super::soft_hash::Sha256Hasher::new())),
+            HashAlgorithm::Sha1 => return Ok(Box::new(super::soft_hash::Sha1Hasher::new())),
+            _ => (),
+        }
+    }
+
+    let _ = algorithm; // Unused when the crate is built with neither implementation
+    Err(EfiErrorKind::Unsupported.into())
+}
+
+/// Compares two digests in time that depends only on their length
+pub fn digests_equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Sum(u8);
+
+    impl Hasher for Sum {
+        fn algorithm(&self) -> HashAlgorithm {
+            HashAlgorithm::Md5
+        }
+
+        fn update(&mut self, data: &[u8]) -> Result<()> {
+            self.0 = data.iter().fold(self.0, |sum, b| sum.wrapping_add(*b));
+            Ok(())
+        }
+
+        fn finalize(&mut self) -> Result<Vec<u8>> {
+            let digest = vec![self.0];
+            self.0 = 0;
+            Ok(digest)
+        }
+    }
+
+    #[test]
+    fn verification_compares_whole_digests() {
+        let mut hasher = Sum(0);
+        hasher.update(&[1, 2]).unwrap();
+        hasher.update(&[3]).unwrap();
+        assert!(hasher.verify(&[6]).is_ok());
+        assert_eq!(hasher.verify(&[1]).unwrap_err().kind(), EfiErrorKind::SecurityViolation);
+
+        assert!(digests_equal(&[1, 2], &[1, 2]));
+        assert!(!digests_equal(&[1, 2], &[1, 2, 3]));
+        assert!(!digests_equal(&[1, 2], &[1, 3]));
+    }
+}
@@ -0,0 +1,111 @@
+use ffi::{
+    hash2::*,
+    EFI_SERVICE_BINDING_PROTOCOL,
+    EFI_HANDLE,
+};
+use super::hash::{Hasher, HashAlgorithm};
+use io;
+use utils::{locate_protocol, open_protocol};
+use alloc::Vec;
+use core::ptr;
+use {Result, Guid};
+
+fn algorithm_guid(algorithm: HashAlgorithm) -> Guid {
+    match algorithm {
+        HashAlgorithm::Md5 => EFI_HASH_ALGORITHM_MD5_GUID,
+        HashAlgorithm::Sha1 => EFI_HASH_ALGORITHM_SHA1_GUID,
+        HashAlgorithm::Sha224 => EFI_HASH_ALGORITHM_SHA224_GUID,
+        HashAlgorithm::Sha256 => EFI_HASH_ALGORITHM_SHA256_GUID,
+        HashAlgorithm::Sha384 => EFI_HASH_ALGORITHM_SHA384_GUID,
+        HashAlgorithm::Sha512 => EFI_HASH_ALGORITHM_SHA512_GUID,
+    }
+}
+
+/// A hash computation done by the firmware's EFI_HASH2_PROTOCOL
+pub struct Hash2Hasher {
+    binding_protocol: *const EFI_SERVICE_BINDING_PROTOCOL,
+    handle: EFI_HANDLE,
+    protocol: *const EFI_HASH2_PROTOCOL,
+    algorithm: HashAlgorithm,
+    guid: Guid,
+}
+
+impl Hash2Hasher {
+    /// Starts a computation with the given algorithm. Fails with `Unsupported` if the firmware
+    /// can't do the algorithm and `NotFound` if it has no EFI_HASH2_PROTOCOL at all.
+    pub fn new(algorithm: HashAlgorithm) -> Result<Self> {
+        let mut hasher = Hash2Hasher {
+            binding_protocol: ptr::null(),
+            handle: ptr::null_mut(),
+            protocol: ptr::null(),
+            algorithm,
+            guid: algorithm_guid(algorithm),
+        };
+
+        unsafe {
+            hasher.binding_protocol = locate_protocol(&EFI_HASH2_SERVICE_BINDING_PROTOCOL_GUID)?;
+            ret_on_err!(((*hasher.binding_protocol).CreateChild)(hasher.binding_protocol, &mut hasher.handle));
+            hasher.protocol = open_protocol(hasher.handle, &EFI_HASH2_PROTOCOL_GUID)?;
+
+            ret_on_err!(((*hasher.protocol).HashInit)(hasher.protocol, &hasher.guid));
+        }
+
+        Ok(hasher)
+    }
+
+    /// Hashes a message in one go
+    pub fn digest(algorithm: HashAlgorithm, data: &[u8]) -> Result<Vec<u8>> {
+        let hasher = Self::new(algorithm)?;
+        let mut output: EFI_HASH2_OUTPUT = [0; 64];
+        unsafe {
+            ret_on_err!(((*hasher.protocol).Hash)(hasher.protocol, &hasher.guid, data.as_ptr(), data.len(), &mut output));
+        }
+
+        Ok(output[..algorithm.digest_size()].to_vec())
+    }
+}
+
+impl Hasher for Hash2Hasher {
+    fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn update(&mut self, data: &[u8]) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).HashUpdate)(self.protocol, data.as_ptr(), data.len()));
+        }
+
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<Vec<u8>> {
+        let mut output: EFI_HASH2_OUTPUT = [0; 64];
+        unsafe {
+            ret_on_err!(((*self.protocol).HashFinal)(self.protocol, &mut output));
+            // A finalized computation can't be updated any more so start a new one right away
+            ret_on_err!(((*self.protocol).HashInit)(self.protocol, &self.guid));
+        }
+
+        Ok(output[..self.algorithm.digest_size()].to_vec())
+    }
+}
+
+/// Lets a hasher be the destination of `io::copy()`, e.g. to hash a file as it's read
+impl io::Write for Hash2Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Hash2Hasher {
+    fn drop(&mut self) {
+        if !self.binding_protocol.is_null() && !self.handle.is_null() {
+            unsafe { ((*self.binding_protocol).DestroyChild)(self.binding_protocol, &mut self.handle); }
+        }
+    }
+}
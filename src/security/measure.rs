@@ -0,0 +1,55 @@
+use super::tcg2::{Tcg2, ExtendFlags};
+use alloc::Vec;
+use Result;
+
+// Measured boot for whatever the application fetches or loads itself. The firmware measures what it
+// loads on its own, but data downloaded or images handed to LoadImage() from memory by an application
+// would otherwise leave no trace in the TPM.
+
+/// Hashes `data` into the given PCR and logs an event of `event_type` (e.g. `EV_IPL`) whose data is
+/// `description` as a NUL terminated ASCII string. Fails with `NotFound` if there's no TPM 2.0.
+pub fn measure(pcr_index: u32, event_type: u32, description: &str, data: &[u8]) -> Result<()> {
+    Tcg2::get()?.hash_log_extend_event(ExtendFlags::empty(), pcr_index, event_type, &event_data(description), data)
+}
+
+fn event_data(description: &str) -> Vec<u8> {
+    let mut data = description.as_bytes().to_vec();
+    data.push(0);
+    data
+}
+
+static mut AUTO_MEASURE_PCR: Option<u32> = None;
+
+/// Makes the crate measure files downloaded with `net::dhcp::mtftp_get_file()` and images loaded
+/// with `image::load_image()` into `pcr_index` as `EV_IPL` events. PCR 8 or 9 are the customary
+/// choices for a boot loader. Pass `None` to turn it off again, which is the default.
+/// While it's on, a download or load fails if its measurement does.
+pub fn set_auto_measure(pcr_index: Option<u32>) {
+    unsafe { AUTO_MEASURE_PCR = pcr_index };
+}
+
+/// The PCR set with `set_auto_measure()`, if any
+pub fn auto_measure_pcr() -> Option<u32> {
+    unsafe { AUTO_MEASURE_PCR }
+}
+
+// Called by the download and load helpers. Does nothing unless auto measurement is on.
+pub(crate) fn auto_measure(description: &str, data: &[u8]) -> Result<()> {
+    match auto_measure_pcr() {
+        Some(pcr_index) => measure(pcr_index, super::tcg2::EV_IPL, description, data),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptions_are_nul_terminated() {
+        assert_eq!(event_data("tftp://10.0.0.1/grubx64.efi").last(), Some(&0));
+        assert_eq!(event_data(""), vec![0]);
+        assert_eq!(auto_measure_pcr(), None);
+        assert!(auto_measure("image", &[1, 2, 3]).is_ok());
+    }
+}
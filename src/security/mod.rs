@@ -0,0 +1,101 @@
+pub mod signature;
+pub mod hash;
+#[cfg(feature = "soft-hash")] pub mod soft_hash;
+pub mod tls;
+pub mod mok;
+pub mod authenticode;
+#[cfg(not(feature = "runtime-driver"))] pub mod hash2;
+#[cfg(not(feature = "runtime-driver"))] pub mod pkcs7;
+#[cfg(all(feature = "tpm", not(feature = "runtime-driver")))] pub mod tcg2;
+#[cfg(all(feature = "tpm", not(feature = "runtime-driver")))] pub mod measure;
+#[cfg(not(feature = "runtime-driver"))] pub mod arch;
+
+use ffi::{
+    runtime_services::EFI_GLOBAL_VARIABLE,
+    security::EFI_IMAGE_SECURITY_DATABASE_GUID,
+};
+use variables;
+use self::signature::SignatureDatabase;
+#[cfg(all(feature = "tpm", not(feature = "runtime-driver")))] pub use self::tcg2::Tcg2;
+#[cfg(all(feature = "tpm", not(feature = "runtime-driver")))] pub use self::measure::measure;
+pub use self::hash::{Hasher, HashAlgorithm, new_hasher};
+#[cfg(not(feature = "runtime-driver"))] pub use self::hash2::Hash2Hasher;
+#[cfg(feature = "soft-hash")] pub use self::soft_hash::{Sha256Hasher, Sha1Hasher};
+#[cfg(not(feature = "runtime-driver"))] pub use self::pkcs7::Pkcs7Verifier;
+#[cfg(not(feature = "runtime-driver"))] pub use self::arch::{Security2, PolicyHook, Verdict};
+use {Result, Guid};
+
+/// The Secure Boot mode the platform is currently in
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SecureBootState {
+    /// The firmware does not support Secure Boot (the SecureBoot variable doesn't exist)
+    Unsupported,
+    /// No Platform Key (PK) is enrolled. Keys can be enrolled without authentication.
+    Setup,
+    /// A PK is enrolled and image verification is done as per the signature databases.
+    User,
+    /// Like Setup mode, but image verification is performed and the results logged without enforcing them.
+    Audit,
+    /// Like User mode, but the platform can't be transitioned back to Setup or Audit mode without a firmware specific mechanism.
+    Deployed,
+}
+
+/// Reads the SecureBoot, SetupMode, AuditMode and DeployedMode variables to determine the Secure Boot state
+pub fn secure_boot_state() -> Result<SecureBootState> {
+    let secure_boot = read_flag("SecureBoot")?;
+    if secure_boot.is_none() {
+        return Ok(SecureBootState::Unsupported);
+    }
+
+    // AuditMode and DeployedMode were only introduced in UEFI 2.5. So it's okay if they don't exist
+    let state = if read_flag("DeployedMode")?.unwrap_or(false) {
+        SecureBootState::Deployed
+    } else if read_flag("AuditMode")?.unwrap_or(false) {
+        SecureBootState::Audit
+    } else if read_flag("SetupMode")?.unwrap_or(false) {
+        SecureBootState::Setup
+    } else {
+        SecureBootState::User
+    };
+
+    Ok(state)
+}
+
+/// Whether Secure Boot is actually being enforced right now.
+/// This can be false even in User mode because some firmwares allow the user to turn off enforcement.
+pub fn is_secure_boot_enabled() -> Result<bool> {
+    Ok(read_flag("SecureBoot")?.unwrap_or(false))
+}
+
+/// The Platform Key database
+pub fn pk() -> Result<SignatureDatabase> {
+    read_database("PK", &EFI_GLOBAL_VARIABLE)
+}
+
+/// The Key Exchange Key database
+pub fn kek() -> Result<SignatureDatabase> {
+    read_database("KEK", &EFI_GLOBAL_VARIABLE)
+}
+
+/// The authorized signature database
+pub fn db() -> Result<SignatureDatabase> {
+    read_database("db", &EFI_IMAGE_SECURITY_DATABASE_GUID)
+}
+
+/// The forbidden signature database
+pub fn dbx() -> Result<SignatureDatabase> {
+    read_database("dbx", &EFI_IMAGE_SECURITY_DATABASE_GUID)
+}
+
+fn read_flag(name: &str) -> Result<Option<bool>> {
+    let value = variables::try_get(name, &EFI_GLOBAL_VARIABLE)?;
+    Ok(value.map(|v| v.first() == Some(&1)))
+}
+
+// A non-existent database is treated as an empty one
+fn read_database(name: &str, vendor_guid: &Guid) -> Result<SignatureDatabase> {
+    match variables::try_get(name, vendor_guid)? {
+        Some(bytes) => SignatureDatabase::parse(&bytes),
+        None => Ok(SignatureDatabase::new()),
+    }
+}
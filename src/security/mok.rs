@@ -0,0 +1,156 @@
+use ffi::security::SHIM_LOCK_GUID;
+use super::signature::SignatureDatabase;
+use super::hash::{Hasher, HashAlgorithm};
+use variables::{self, Attributes};
+use alloc::{String, Vec};
+use {Result, EfiErrorKind};
+
+// Machine Owner Keys as managed by shim. Shim mirrors its boot services only MokList and MokListX
+// into the runtime readable MokListRT and MokListXRT, splitting them over MokListRT1, MokListRT2
+// etc. when they're too big for a single variable. Changes are requested by writing the keys to
+// MokNew (or MokDel) along with a password hash in MokAuth (or MokDelAuth). On the next boot shim
+// starts MokManager which asks for the password and performs the request, just as if mokutil had
+// staged it.
+
+// The longest password MokManager's prompt accepts, in characters
+const PASSWORD_MAX: usize = 256;
+
+/// The keys shim trusts in addition to db
+pub fn mok_list() -> Result<SignatureDatabase> {
+    read_chunked("MokListRT")
+}
+
+/// The keys and hashes shim refuses in addition to dbx
+pub fn mok_list_x() -> Result<SignatureDatabase> {
+    read_chunked("MokListXRT")
+}
+
+/// Asks MokManager to enroll `keys` on the next boot after the user confirms with `password`.
+/// Keys already staged by an earlier request are kept. `hasher` must do SHA-256 (see `Hash2Hasher`).
+pub fn request_enrollment<H: Hasher>(keys: &SignatureDatabase, password: &str, hasher: &mut H) -> Result<()> {
+    stage("MokNew", "MokAuth", keys, password, hasher)
+}
+
+/// Asks MokManager to delete `keys` from MokList on the next boot after the user confirms with `password`
+pub fn request_deletion<H: Hasher>(keys: &SignatureDatabase, password: &str, hasher: &mut H) -> Result<()> {
+    stage("MokDel", "MokDelAuth", keys, password, hasher)
+}
+
+/// The keys staged for enrollment, if any
+pub fn pending_enrollment() -> Result<Option<SignatureDatabase>> {
+    read_optional("MokNew")
+}
+
+/// The keys staged for deletion, if any
+pub fn pending_deletion() -> Result<Option<SignatureDatabase>> {
+    read_optional("MokDel")
+}
+
+/// Withdraws any staged enrollment and deletion requests
+pub fn cancel_requests() -> Result<()> {
+    for name in ["MokNew", "MokAuth", "MokDel", "MokDelAuth"].iter() {
+        variables::delete(name, &SHIM_LOCK_GUID)?;
+    }
+
+    Ok(())
+}
+
+fn stage<H: Hasher>(request_name: &str, auth_name: &str, keys: &SignatureDatabase, password: &str, hasher: &mut H) -> Result<()> {
+    let mut request = read_optional(request_name)?.unwrap_or_else(SignatureDatabase::new);
+    for list in keys.lists.iter() {
+        if !request.lists.contains(list) {
+            request.lists.push(list.clone());
+        }
+    }
+
+    let data = request.to_bytes();
+    let auth = mok_auth(&data, password, hasher)?;
+
+    // Runtime access like mokutil does so that the OS can inspect or withdraw the request too
+    let attributes = Attributes::NON_VOLATILE | Attributes::BOOTSERVICE_ACCESS | Attributes::RUNTIME_ACCESS;
+    variables::set(request_name, &SHIM_LOCK_GUID, attributes, &data)?;
+    if let Err(e) = variables::set(auth_name, &SHIM_LOCK_GUID, attributes, &auth) {
+        let _ = variables::delete(request_name, &SHIM_LOCK_GUID);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+// The password hash MokManager checks: SHA-256 over the request followed by the password in UCS-2
+fn mok_auth<H: Hasher>(request: &[u8], password: &str, hasher: &mut H) -> Result<Vec<u8>> {
+    let password = password.encode_utf16().collect::<Vec<_>>();
+    if hasher.algorithm() != HashAlgorithm::Sha256 || password.is_empty() || password.len() > PASSWORD_MAX {
+        return Err(EfiErrorKind::InvalidParameter.into());
+    }
+
+    let password_bytes = password.iter().flat_map(|c| vec![*c as u8, (*c >> 8) as u8]).collect::<Vec<_>>();
+    hasher.update(request)?;
+    hasher.update(&password_bytes)?;
+    hasher.finalize()
+}
+
+fn read_optional(name: &str) -> Result<Option<SignatureDatabase>> {
+    match variables::try_get(name, &SHIM_LOCK_GUID)? {
+        Some(bytes) => Ok(Some(SignatureDatabase::parse(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+// Reads the base variable followed by the numbered ones until one is missing
+fn read_chunked(base_name: &str) -> Result<SignatureDatabase> {
+    let mut bytes = Vec::new();
+    let mut index = 0;
+    while let Some(chunk) = variables::try_get(&chunk_name(base_name, index), &SHIM_LOCK_GUID)? {
+        bytes.extend_from_slice(&chunk);
+        index += 1;
+    }
+
+    SignatureDatabase::parse(&bytes)
+}
+
+fn chunk_name(base_name: &str, index: usize) -> String {
+    if index == 0 {
+        base_name.into()
+    } else {
+        format!("{}{}", base_name, index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Recorder(HashAlgorithm, Vec<u8>);
+
+    impl Hasher for Recorder {
+        fn algorithm(&self) -> HashAlgorithm {
+            self.0
+        }
+
+        fn update(&mut self, data: &[u8]) -> Result<()> {
+            self.1.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn finalize(&mut self) -> Result<Vec<u8>> {
+            Ok(::core::mem::replace(&mut self.1, Vec::new()))
+        }
+    }
+
+    #[test]
+    fn auth_covers_request_and_ucs2_password() {
+        let mut hasher = Recorder(HashAlgorithm::Sha256, Vec::new());
+        assert_eq!(mok_auth(&[0xAA, 0xBB], "pw", &mut hasher).unwrap(), vec![0xAA, 0xBB, b'p', 0, b'w', 0]);
+
+        assert!(mok_auth(&[0xAA], "", &mut hasher).is_err());
+        assert!(mok_auth(&[0xAA], &"x".repeat(PASSWORD_MAX + 1), &mut hasher).is_err());
+        assert!(mok_auth(&[0xAA], "pw", &mut Recorder(HashAlgorithm::Sha1, Vec::new())).is_err());
+    }
+
+    #[test]
+    fn chunks_are_numbered_from_one() {
+        assert_eq!(chunk_name("MokListRT", 0), "MokListRT");
+        assert_eq!(chunk_name("MokListRT", 2), "MokListRT2");
+    }
+}
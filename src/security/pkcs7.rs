@@ -0,0 +1,154 @@
+use ffi::{
+    pkcs7::{EFI_PKCS7_VERIFY_PROTOCOL, EFI_PKCS7_VERIFY_PROTOCOL_GUID},
+    security::EFI_SIGNATURE_LIST,
+    VOID,
+};
+use super::signature::SignatureDatabase;
+#[cfg(feature = "fs")] use super::signature::{SignatureList, is_der_sequence};
+use variables;
+#[cfg(feature = "fs")] use fs;
+use utils::locate_protocol;
+use alloc::Vec;
+use core::{ptr, mem};
+use {Result, EfiErrorKind, Guid};
+
+// PKCS#7 (CMS SignedData) verification through EFI_PKCS7_VERIFY_PROTOCOL. The signer's chain
+// must lead to a certificate in the trusted database and no certificate or hash in the chain may
+// be in the revoked one.
+
+/// The firmware's PKCS#7 verifier
+pub struct Pkcs7Verifier {
+    protocol: *const EFI_PKCS7_VERIFY_PROTOCOL,
+}
+
+impl Pkcs7Verifier {
+    pub fn get() -> Result<Self> {
+        Ok(Pkcs7Verifier { protocol: locate_protocol(&EFI_PKCS7_VERIFY_PROTOCOL_GUID)? })
+    }
+
+    /// Verifies a detached signature over `data`. Fails with `SecurityViolation` if the signature
+    /// doesn't check out.
+    pub fn verify_detached(&self, signature: &[u8], data: &[u8], trusted: &SignatureDatabase, revoked: Option<&SignatureDatabase>) -> Result<()> {
+        if data.is_empty() {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let mut content_size = 0;
+        self.verify_buffer(signature, data, trusted, revoked, ptr::null_mut(), &mut content_size)
+    }
+
+    /// Verifies signed data with the content embedded in it and returns the content
+    pub fn verify_attached(&self, signed_data: &[u8], trusted: &SignatureDatabase, revoked: Option<&SignatureDatabase>) -> Result<Vec<u8>> {
+        // The content is part of the signed data so it can't be any bigger
+        let mut content = vec![0u8; signed_data.len()];
+        let mut content_size = content.len();
+        self.verify_buffer(signed_data, &[], trusted, revoked, content.as_mut_ptr() as *mut VOID, &mut content_size)?;
+        content.truncate(content_size);
+        Ok(content)
+    }
+
+    /// Verifies a detached signature given just the hash of the signed data
+    pub fn verify_hash(&self, signature: &[u8], hash: &[u8], trusted: &SignatureDatabase, revoked: Option<&SignatureDatabase>) -> Result<()> {
+        let trusted = RawDatabase::new(trusted);
+        let revoked = revoked.map(RawDatabase::new);
+        unsafe {
+            ret_on_err!(((*self.protocol).VerifySignature)(
+                self.protocol,
+                signature.as_ptr() as *const VOID,
+                signature.len(),
+                hash.as_ptr() as *const VOID,
+                hash.len(),
+                trusted.as_ptr(),
+                revoked.as_ref().map_or(ptr::null(), |r| r.as_ptr()),
+                ptr::null()));
+        }
+
+        Ok(())
+    }
+
+    fn verify_buffer(&self, signed_data: &[u8], data: &[u8], trusted: &SignatureDatabase, revoked: Option<&SignatureDatabase>, content: *mut VOID, content_size: &mut usize) -> Result<()> {
+        let trusted = RawDatabase::new(trusted);
+        let revoked = revoked.map(RawDatabase::new);
+        unsafe {
+            ret_on_err!(((*self.protocol).VerifyBuffer)(
+                self.protocol,
+                signed_data.as_ptr() as *const VOID,
+                signed_data.len(),
+                if data.is_empty() { ptr::null() } else { data.as_ptr() as *const VOID },
+                data.len(),
+                trusted.as_ptr(),
+                revoked.as_ref().map_or(ptr::null(), |r| r.as_ptr()),
+                ptr::null(),
+                content,
+                content_size));
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads trust anchors from a signature database variable, e.g. "db" or an application's own
+pub fn trust_anchors_from_variable(name: &str, vendor_guid: &Guid) -> Result<SignatureDatabase> {
+    SignatureDatabase::parse(&variables::get(name, vendor_guid)?)
+}
+
+/// Reads trust anchors from a file holding either signature lists (an .esl file) or a single
+/// DER encoded X.509 certificate
+#[cfg(feature = "fs")]
+pub fn trust_anchors_from_file(path: &str) -> Result<SignatureDatabase> {
+    let bytes = fs::read(path)?;
+    if is_der_sequence(&bytes) {
+        return Ok(certificate_database(bytes));
+    }
+
+    SignatureDatabase::parse(&bytes)
+}
+
+#[cfg(feature = "fs")]
+fn certificate_database(der: Vec<u8>) -> SignatureDatabase {
+    SignatureDatabase { lists: vec![SignatureList::x509(unsafe { mem::zeroed() }, &der)] }
+}
+
+// A database laid out the way the protocol wants it: a NULL terminated array of pointers
+// to serialized signature lists
+struct RawDatabase {
+    lists: Vec<Vec<u8>>,
+    pointers: Vec<*const EFI_SIGNATURE_LIST>,
+}
+
+impl RawDatabase {
+    fn new(db: &SignatureDatabase) -> Self {
+        let lists = db.lists.iter().map(|l| l.to_bytes()).collect::<Vec<_>>();
+        let mut pointers = lists.iter().map(|l| l.as_ptr() as *const EFI_SIGNATURE_LIST).collect::<Vec<_>>();
+        pointers.push(ptr::null());
+        RawDatabase { lists, pointers }
+    }
+
+    fn as_ptr(&self) -> *const *const EFI_SIGNATURE_LIST {
+        debug_assert_eq!(self.lists.len() + 1, self.pointers.len());
+        self.pointers.as_ptr()
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn certificates_become_single_entry_databases() {
+        let mut cert = vec![0x30, 0x82, 0x01, 0x00];
+        cert.extend_from_slice(&[0; 0x100]);
+        let db = certificate_database(cert.clone());
+        assert_eq!(db.certificates().collect::<Vec<_>>(), vec![&cert[..]]);
+    }
+
+    #[test]
+    fn raw_databases_are_null_terminated() {
+        let db = certificate_database(vec![0x30, 0x00]);
+        let raw = RawDatabase::new(&db);
+        unsafe {
+            assert!(!(*raw.as_ptr()).is_null());
+            assert!((*raw.as_ptr().offset(1)).is_null());
+        }
+    }
+}
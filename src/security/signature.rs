@@ -0,0 +1,401 @@
+use ffi::security::{
+    EFI_CERT_SHA1_GUID,
+    EFI_CERT_SHA256_GUID,
+    EFI_CERT_SHA384_GUID,
+    EFI_CERT_SHA512_GUID,
+    EFI_CERT_RSA2048_GUID,
+    EFI_CERT_X509_GUID,
+    EFI_CERT_X509_SHA256_GUID,
+    EFI_CERT_X509_SHA384_GUID,
+    EFI_CERT_X509_SHA512_GUID,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::Vec;
+use utils::{guid_from_bytes, guid_to_bytes};
+use {Result, Guid, EfiErrorKind};
+
+// Size of the fixed part of EFI_SIGNATURE_LIST
+const SIGNATURE_LIST_HEADER_SIZE: usize = 28;
+const GUID_SIZE: usize = 16;
+const EFI_TIME_SIZE: usize = 16;
+
+/// The type of the signatures in a signature list
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SignatureType {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+    Rsa2048,
+    X509,
+    X509Sha256,
+    X509Sha384,
+    X509Sha512,
+    Other(Guid),
+}
+
+impl SignatureType {
+    pub fn from_guid(guid: &Guid) -> Self {
+        match *guid {
+            EFI_CERT_SHA1_GUID => SignatureType::Sha1,
+            EFI_CERT_SHA256_GUID => SignatureType::Sha256,
+            EFI_CERT_SHA384_GUID => SignatureType::Sha384,
+            EFI_CERT_SHA512_GUID => SignatureType::Sha512,
+            EFI_CERT_RSA2048_GUID => SignatureType::Rsa2048,
+            EFI_CERT_X509_GUID => SignatureType::X509,
+            EFI_CERT_X509_SHA256_GUID => SignatureType::X509Sha256,
+            EFI_CERT_X509_SHA384_GUID => SignatureType::X509Sha384,
+            EFI_CERT_X509_SHA512_GUID => SignatureType::X509Sha512,
+            g => SignatureType::Other(g),
+        }
+    }
+
+    pub fn guid(&self) -> Guid {
+        match *self {
+            SignatureType::Sha1 => EFI_CERT_SHA1_GUID,
+            SignatureType::Sha256 => EFI_CERT_SHA256_GUID,
+            SignatureType::Sha384 => EFI_CERT_SHA384_GUID,
+            SignatureType::Sha512 => EFI_CERT_SHA512_GUID,
+            SignatureType::Rsa2048 => EFI_CERT_RSA2048_GUID,
+            SignatureType::X509 => EFI_CERT_X509_GUID,
+            SignatureType::X509Sha256 => EFI_CERT_X509_SHA256_GUID,
+            SignatureType::X509Sha384 => EFI_CERT_X509_SHA384_GUID,
+            SignatureType::X509Sha512 => EFI_CERT_X509_SHA512_GUID,
+            SignatureType::Other(g) => g,
+        }
+    }
+
+    /// The size of each signature's data for types where it is fixed, e.g. 32 for SHA-256.
+    /// The X.509 hash types carry the time of revocation (an EFI_TIME) after the hash.
+    pub fn data_size(&self) -> Option<usize> {
+        match *self {
+            SignatureType::Sha1 => Some(20),
+            SignatureType::Sha256 => Some(32),
+            SignatureType::Sha384 => Some(48),
+            SignatureType::Sha512 => Some(64),
+            SignatureType::Rsa2048 => Some(256),
+            SignatureType::X509Sha256 => Some(32 + EFI_TIME_SIZE),
+            SignatureType::X509Sha384 => Some(48 + EFI_TIME_SIZE),
+            SignatureType::X509Sha512 => Some(64 + EFI_TIME_SIZE),
+            SignatureType::X509 | SignatureType::Other(_) => None,
+        }
+    }
+
+    /// Whether signatures of this type are plain hashes (of images or of certificates' TBS data)
+    pub fn is_hash(&self) -> bool {
+        match *self {
+            SignatureType::X509 | SignatureType::Rsa2048 | SignatureType::Other(_) => false,
+            _ => true,
+        }
+    }
+}
+
+/// A single entry (EFI_SIGNATURE_DATA) in a signature list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// Identifies the agent which added the signature
+    pub owner: Guid,
+    /// A DER encoded certificate, a hash, a public key etc. depending on the type of the list
+    pub data: Vec<u8>,
+}
+
+/// A list of signatures all of the same type (EFI_SIGNATURE_LIST)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureList {
+    pub signature_type: SignatureType,
+    pub header: Vec<u8>,
+    pub signatures: Vec<Signature>,
+}
+
+impl SignatureList {
+    /// An empty list of the given type
+    pub fn new(signature_type: SignatureType) -> Self {
+        SignatureList { signature_type, header: Vec::new(), signatures: Vec::new() }
+    }
+
+    /// A list holding a single DER encoded X.509 certificate.
+    /// Certificates rarely have the same size so each one usually needs a list of its own.
+    pub fn x509(owner: Guid, certificate: &[u8]) -> Self {
+        SignatureList {
+            signature_type: SignatureType::X509,
+            header: Vec::new(),
+            signatures: vec![Signature { owner, data: certificate.to_vec() }],
+        }
+    }
+
+    /// A list of hashes of the given type, e.g. SHA-256 hashes of images for db or dbx
+    pub fn hashes(signature_type: SignatureType, owner: Guid, hashes: &[&[u8]]) -> Result<Self> {
+        let mut list = Self::new(signature_type);
+        for hash in hashes {
+            list.push(Signature { owner, data: hash.to_vec() })?;
+        }
+
+        Ok(list)
+    }
+
+    /// Adds a signature. Fails with `InvalidParameter` if its size doesn't fit the list, as all
+    /// signatures in a list must be of the same size.
+    pub fn push(&mut self, signature: Signature) -> Result<()> {
+        let expected_size = self.signature_type.data_size()
+            .or_else(|| self.signatures.first().map(|s| s.data.len()));
+        if expected_size.map_or(false, |size| size != signature.data.len()) {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        self.signatures.push(signature);
+        Ok(())
+    }
+
+    /// Whether a signature with this data is in the list
+    pub fn contains(&self, data: &[u8]) -> bool {
+        self.signatures.iter().any(|s| s.data == data)
+    }
+
+    /// Parses a single signature list from the start of the buffer.
+    /// Returns the list along with the number of bytes it occupied.
+    pub fn parse(bytes: &[u8]) -> Result<(Self, usize)> {
+        if bytes.len() < SIGNATURE_LIST_HEADER_SIZE {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let signature_type = SignatureType::from_guid(&guid_from_bytes(&bytes[0..GUID_SIZE]));
+        let list_size = LittleEndian::read_u32(&bytes[16..20]) as usize;
+        let header_size = LittleEndian::read_u32(&bytes[20..24]) as usize;
+        let signature_size = LittleEndian::read_u32(&bytes[24..28]) as usize;
+
+        let signatures_start = SIGNATURE_LIST_HEADER_SIZE + header_size;
+        if list_size > bytes.len() || signatures_start > list_size || signature_size < GUID_SIZE {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let signatures_bytes = &bytes[signatures_start..list_size];
+        if signatures_bytes.len() % signature_size != 0 {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let signatures = signatures_bytes.chunks(signature_size)
+            .map(|s| Signature { owner: guid_from_bytes(&s[..GUID_SIZE]), data: s[GUID_SIZE..].to_vec() })
+            .collect();
+
+        let list = SignatureList { 
+            signature_type,
+            header: bytes[SIGNATURE_LIST_HEADER_SIZE..signatures_start].to_vec(),
+            signatures,
+        };
+
+        Ok((list, list_size))
+    }
+
+    /// Serializes the list into an EFI_SIGNATURE_LIST.
+    /// All signatures are expected to be of the same size, as the format requires.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let signature_size = GUID_SIZE + self.signatures.first().map_or(0, |s| s.data.len());
+        let list_size = SIGNATURE_LIST_HEADER_SIZE + self.header.len() + signature_size * self.signatures.len();
+
+        let mut bytes = vec![0u8; SIGNATURE_LIST_HEADER_SIZE];
+        bytes[0..GUID_SIZE].copy_from_slice(&guid_to_bytes(&self.signature_type.guid()));
+        LittleEndian::write_u32(&mut bytes[16..20], list_size as u32);
+        LittleEndian::write_u32(&mut bytes[20..24], self.header.len() as u32);
+        LittleEndian::write_u32(&mut bytes[24..28], signature_size as u32);
+        bytes.extend_from_slice(&self.header);
+        for signature in &self.signatures {
+            bytes.extend_from_slice(&guid_to_bytes(&signature.owner));
+            bytes.extend_from_slice(&signature.data);
+        }
+
+        bytes
+    }
+}
+
+/// The contents of a signature database variable such as PK, KEK, db or dbx.
+/// It is simply a sequence of signature lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureDatabase {
+    pub lists: Vec<SignatureList>,
+}
+
+impl SignatureDatabase {
+    pub fn new() -> Self {
+        SignatureDatabase { lists: Vec::new() }
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut lists = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let (list, size) = SignatureList::parse(&bytes[offset..])?;
+            lists.push(list);
+            offset += size;
+        }
+
+        Ok(SignatureDatabase { lists })
+    }
+
+    /// Adds a certificate in a list of its own unless it's already in the database
+    pub fn add_certificate(&mut self, owner: Guid, certificate: &[u8]) {
+        if !self.contains_certificate(certificate) {
+            self.lists.push(SignatureList::x509(owner, certificate));
+        }
+    }
+
+    /// Adds a hash to the first list of its type, creating the list if there isn't one,
+    /// unless it's already in the database
+    pub fn add_hash(&mut self, signature_type: SignatureType, owner: Guid, hash: &[u8]) -> Result<()> {
+        if self.contains_hash(signature_type, hash) {
+            return Ok(());
+        }
+
+        let signature = Signature { owner, data: hash.to_vec() };
+        if let Some(list) = self.lists.iter_mut().find(|l| l.signature_type == signature_type) {
+            return list.push(signature);
+        }
+
+        let mut list = SignatureList::new(signature_type);
+        list.push(signature)?;
+        self.lists.push(list);
+        Ok(())
+    }
+
+    /// Removes a certificate from the database along with any list it leaves empty.
+    /// Returns false if the certificate wasn't in it.
+    pub fn remove_certificate(&mut self, certificate: &[u8]) -> bool {
+        let count = self.lists.len() + self.certificates().count();
+        for list in self.lists.iter_mut().filter(|l| l.signature_type == SignatureType::X509) {
+            list.signatures.retain(|s| s.data != certificate);
+        }
+
+        self.lists.retain(|l| l.signature_type != SignatureType::X509 || !l.signatures.is_empty());
+        count != self.lists.len() + self.certificates().count()
+    }
+
+    pub fn contains_certificate(&self, certificate: &[u8]) -> bool {
+        self.certificates().any(|c| c == certificate)
+    }
+
+    pub fn contains_hash(&self, signature_type: SignatureType, hash: &[u8]) -> bool {
+        self.hashes().any(|(t, h)| t == signature_type && h == hash)
+    }
+
+    /// Serializes the database into the format stored in signature database variables.
+    /// This is the data to sign and pass to `variables::set_authenticated()` when enrolling it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.lists.iter().flat_map(|l| l.to_bytes()).collect()
+    }
+
+    /// All the DER encoded X.509 certificates in the database
+    pub fn certificates<'a>(&'a self) -> impl Iterator<Item=&'a [u8]> + 'a {
+        self.signatures_of(|t| t == SignatureType::X509).map(|(_, s)| &s.data[..])
+    }
+
+    /// All the hashes in the database along with their types
+    pub fn hashes<'a>(&'a self) -> impl Iterator<Item=(SignatureType, &'a [u8])> + 'a {
+        self.signatures_of(|t| t.is_hash()).map(|(t, s)| (t, &s.data[..]))
+    }
+
+    fn signatures_of<'a, P: Fn(SignatureType) -> bool + 'a>(&'a self, predicate: P) -> impl Iterator<Item=(SignatureType, &'a Signature)> + 'a {
+        self.lists.iter()
+            .filter(move |l| predicate(l.signature_type))
+            .flat_map(|l| l.signatures.iter().map(move |s| (l.signature_type, s)))
+    }
+}
+
+// A DER certificate starts with a SEQUENCE tag whose length covers the whole buffer
+pub(crate) fn is_der_sequence(bytes: &[u8]) -> bool {
+    if bytes.len() < 2 || bytes[0] != 0x30 {
+        return false;
+    }
+
+    let (header, length) = match bytes[1] {
+        len @ 0...0x7F => (2, len as usize),
+        0x81 if bytes.len() >= 3 => (3, bytes[2] as usize),
+        0x82 if bytes.len() >= 4 => (4, (bytes[2] as usize) << 8 | bytes[3] as usize),
+        0x83 if bytes.len() >= 5 => (5, (bytes[2] as usize) << 16 | (bytes[3] as usize) << 8 | bytes[4] as usize),
+        _ => return false,
+    };
+
+    header + length == bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi::EFI_GUID;
+
+    #[test]
+    fn parse_sha256_list() {
+        let mut bytes = vec![
+            0x26, 0x16, 0xc4, 0xc1, 0x4c, 0x50, 0x92, 0x40, 0xac, 0xa9, 0x41, 0xf9, 0x36, 0x93, 0x43, 0x28, // EFI_CERT_SHA256_GUID
+            0x7c, 0x00, 0x00, 0x00, // SignatureListSize = 28 + 2 * 48
+            0x00, 0x00, 0x00, 0x00, // SignatureHeaderSize
+            0x30, 0x00, 0x00, 0x00, // SignatureSize = 16 + 32
+        ];
+        for i in 0..2 {
+            bytes.extend_from_slice(&[i; 16]); // Owner
+            bytes.extend_from_slice(&[0xAA; 32]); // Hash
+        }
+
+        let db = SignatureDatabase::parse(&bytes).unwrap();
+        assert_eq!(db.lists.len(), 1);
+        assert_eq!(db.lists[0].signature_type, SignatureType::Sha256);
+        assert_eq!(db.lists[0].signatures.len(), 2);
+        assert_eq!(db.hashes().count(), 2);
+        assert_eq!(db.certificates().count(), 0);
+        assert_eq!(db.lists[0].signatures[1].owner, EFI_GUID(0x01010101, 0x0101, 0x0101, [1; 8]));
+    }
+
+    #[test]
+    fn lists_round_trip() {
+        let list = SignatureList {
+            signature_type: SignatureType::X509,
+            header: Vec::new(),
+            signatures: vec![Signature { owner: EFI_GUID(1, 2, 3, [4; 8]), data: vec![0x30, 0x82, 0x01, 0x00] }],
+        };
+        let db = SignatureDatabase { lists: vec![list.clone(), list] };
+        let bytes = db.to_bytes();
+        assert_eq!(bytes.len(), 2 * (28 + 16 + 4));
+        assert_eq!(SignatureDatabase::parse(&bytes).unwrap(), db);
+    }
+
+    #[test]
+    fn databases_are_built_without_duplicates() {
+        let owner = EFI_GUID(1, 2, 3, [4; 8]);
+        let mut db = SignatureDatabase::new();
+        db.add_hash(SignatureType::Sha256, owner, &[0xAA; 32]).unwrap();
+        db.add_hash(SignatureType::Sha256, owner, &[0xBB; 32]).unwrap();
+        db.add_hash(SignatureType::Sha256, owner, &[0xAA; 32]).unwrap();
+        db.add_certificate(owner, &[0x30, 0x00]);
+        db.add_certificate(owner, &[0x30, 0x01, 0x00]);
+        db.add_certificate(owner, &[0x30, 0x00]);
+        assert_eq!(db.lists.len(), 3);
+        assert_eq!(db.lists[0].signatures.len(), 2);
+        assert!(db.contains_hash(SignatureType::Sha256, &[0xBB; 32]));
+        assert!(!db.contains_hash(SignatureType::Sha1, &[0xBB; 32]));
+        assert_eq!(db.certificates().count(), 2);
+
+        assert_eq!(db.add_hash(SignatureType::Sha256, owner, &[0xCC; 20]).unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+        assert!(SignatureList::hashes(SignatureType::Sha1, owner, &[&[0; 20], &[1; 20]]).unwrap().contains(&[1; 20]));
+        assert!(SignatureList::hashes(SignatureType::X509Sha256, owner, &[&[0; 32]]).is_err());
+
+        assert_eq!(SignatureDatabase::parse(&db.to_bytes()).unwrap(), db);
+
+        assert!(db.remove_certificate(&[0x30, 0x00]));
+        assert!(!db.remove_certificate(&[0x30, 0x00]));
+        assert_eq!(db.lists.len(), 2);
+        assert_eq!(db.certificates().collect::<Vec<_>>(), vec![&[0x30, 0x01, 0x00][..]]);
+    }
+
+    #[test]
+    fn certificates_are_recognized_by_their_der_header() {
+        let mut cert = vec![0x30, 0x82, 0x01, 0x00];
+        cert.extend_from_slice(&[0; 0x100]);
+        assert!(is_der_sequence(&cert));
+        assert!(is_der_sequence(&[0x30, 0x02, 0x05, 0x00]));
+        assert!(!is_der_sequence(&cert[..cert.len() - 1]));
+        assert!(!is_der_sequence(&[0xa1, 0x59, 0xc0, 0xa5]));
+    }
+
+    #[test]
+    fn parse_truncated_list() {
+        assert!(SignatureDatabase::parse(&[0; 10]).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use super::hash::{Hasher, HashAlgorithm};
+use byteorder::{ByteOrder, BigEndian};
+use io;
+use alloc::Vec;
+use Result;
+
+// SHA-1 and SHA-256 in software for firmware without EFI_HASH2_PROTOCOL. Both are Merkle-Damgard
+// constructions over 64 byte blocks with the same padding, so they share everything but the
+// compression function and the state.
+
+const BLOCK_SIZE: usize = 64;
+
+trait Compress: Default {
+    const ALGORITHM: HashAlgorithm;
+    fn compress(&mut self, block: &[u8]);
+    fn output(&self) -> Vec<u8>;
+}
+
+// Buffers partial blocks and pads the message at the end
+struct Engine<C: Compress> {
+    state: C,
+    buf: [u8; BLOCK_SIZE],
+    buf_len: usize,
+    total_len: u64,
+}
+
+impl<C: Compress> Default for Engine<C> {
+    fn default() -> Self {
+        Engine { state: C::default(), buf: [0; BLOCK_SIZE], buf_len: 0, total_len: 0 }
+    }
+}
+
+impl<C: Compress> Engine<C> {
+    fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        if self.buf_len > 0 {
+            let take = ::core::cmp::min(BLOCK_SIZE - self.buf_len, data.len());
+            self.buf[self.buf_len..self.buf_len + take].copy_from_slice(&data[..take]);
+            self.buf_len += take;
+            data = &data[take..];
+            if self.buf_len < BLOCK_SIZE {
+                return;
+            }
+
+            let block = self.buf;
+            self.state.compress(&block);
+            self.buf_len = 0;
+        }
+
+        while data.len() >= BLOCK_SIZE {
+            self.state.compress(&data[..BLOCK_SIZE]);
+            data = &data[BLOCK_SIZE..];
+        }
+
+        self.buf[..data.len()].copy_from_slice(data);
+        self.buf_len = data.len();
+    }
+
+    // Appends a 1 bit, zeros up to 8 bytes short of a block boundary and the message length in bits
+    fn finalize(&mut self) -> Vec<u8> {
+        let bit_len = self.total_len.wrapping_mul(8);
+        let mut padding = [0u8; 2 * BLOCK_SIZE];
+        padding[0] = 0x80;
+        let zeros = (BLOCK_SIZE * 2 - 8 - 1 - self.buf_len) % BLOCK_SIZE;
+        BigEndian::write_u64(&mut padding[1 + zeros..1 + zeros + 8], bit_len);
+        self.update(&padding[..1 + zeros + 8]);
+        debug_assert_eq!(self.buf_len, 0);
+
+        let digest = self.state.output();
+        *self = Self::default();
+        digest
+    }
+}
+
+struct Sha256State([u32; 8]);
+
+impl Default for Sha256State {
+    fn default() -> Self {
+        Sha256State([0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19])
+    }
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+impl Compress for Sha256State {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha256;
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = BigEndian::read_u32(&block[i * 4..]);
+        }
+
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let mut v = self.0;
+        for i in 0..64 {
+            let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+            let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+            let t1 = v[7].wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+            let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+            let t2 = s0.wrapping_add(maj);
+            v = [t1.wrapping_add(t2), v[0], v[1], v[2], v[3].wrapping_add(t1), v[4], v[5], v[6]];
+        }
+
+        for (h, x) in self.0.iter_mut().zip(v.iter()) {
+            *h = h.wrapping_add(*x);
+        }
+    }
+
+    fn output(&self) -> Vec<u8> {
+        words_to_bytes(&self.0)
+    }
+}
+
+struct Sha1State([u32; 5]);
+
+impl Default for Sha1State {
+    fn default() -> Self {
+        Sha1State([0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0])
+    }
+}
+
+impl Compress for Sha1State {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha1;
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = BigEndian::read_u32(&block[i * 4..]);
+        }
+
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let mut v = self.0;
+        for i in 0..80 {
+            let (f, k) = match i {
+                0...19 => ((v[1] & v[2]) | (!v[1] & v[3]), 0x5a827999),
+                20...39 => (v[1] ^ v[2] ^ v[3], 0x6ed9eba1),
+                40...59 => ((v[1] & v[2]) | (v[1] & v[3]) | (v[2] & v[3]), 0x8f1bbcdc),
+                _ => (v[1] ^ v[2] ^ v[3], 0xca62c1d6),
+            };
+
+            let t = v[0].rotate_left(5).wrapping_add(f).wrapping_add(v[4]).wrapping_add(k).wrapping_add(w[i]);
+            v = [t, v[0], v[1].rotate_left(30), v[2], v[3]];
+        }
+
+        for (h, x) in self.0.iter_mut().zip(v.iter()) {
+            *h = h.wrapping_add(*x);
+        }
+    }
+
+    fn output(&self) -> Vec<u8> {
+        words_to_bytes(&self.0)
+    }
+}
+
+fn words_to_bytes(words: &[u32]) -> Vec<u8> {
+    let mut bytes = vec![0u8; words.len() * 4];
+    for (chunk, word) in bytes.chunks_mut(4).zip(words) {
+        BigEndian::write_u32(chunk, *word);
+    }
+
+    bytes
+}
+
+/// SHA-256 computed in software
+#[derive(Default)]
+pub struct Sha256Hasher(Engine<Sha256State>);
+
+/// SHA-1 computed in software
+#[derive(Default)]
+pub struct Sha1Hasher(Engine<Sha1State>);
+
+macro_rules! impl_soft_hasher {
+    ($hasher:ident, $state:ident) => {
+        impl $hasher {
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            /// Hashes a message in one go
+            pub fn digest(data: &[u8]) -> Vec<u8> {
+                let mut hasher = Self::new();
+                hasher.0.update(data);
+                hasher.0.finalize()
+            }
+        }
+
+        impl Hasher for $hasher {
+            fn algorithm(&self) -> HashAlgorithm {
+                $state::ALGORITHM
+            }
+
+            fn update(&mut self, data: &[u8]) -> Result<()> {
+                self.0.update(data);
+                Ok(())
+            }
+
+            fn finalize(&mut self) -> Result<Vec<u8>> {
+                Ok(self.0.finalize())
+            }
+        }
+
+        impl io::Write for $hasher {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.update(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+    }
+}
+
+impl_soft_hasher!(Sha256Hasher, Sha256State);
+impl_soft_hasher!(Sha1Hasher, Sha1State);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> ::alloc::String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    const TWO_BLOCKS: &[u8] = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+
+    #[test]
+    fn sha256_test_vectors() {
+        assert_eq!(hex(&Sha256Hasher::digest(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hex(&Sha256Hasher::digest(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hex(&Sha256Hasher::digest(TWO_BLOCKS)), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+    }
+
+    #[test]
+    fn sha1_test_vectors() {
+        assert_eq!(hex(&Sha1Hasher::digest(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(&Sha1Hasher::digest(TWO_BLOCKS)), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let data = (0..200u32).map(|i| i as u8).collect::<Vec<_>>();
+        let mut hasher = Sha256Hasher::new();
+        for chunk in data.chunks(7) {
+            hasher.update(chunk).unwrap();
+        }
+
+        assert_eq!(hasher.algorithm(), HashAlgorithm::Sha256);
+        assert_eq!(hasher.finalize().unwrap(), Sha256Hasher::digest(&data));
+        // Finalizing starts over
+        assert_eq!(hasher.finalize().unwrap(), Sha256Hasher::digest(b""));
+    }
+}
@@ -0,0 +1,479 @@
+use ffi::tcg2::*;
+use config_table::find_config_table;
+use byteorder::{ByteOrder, LittleEndian, BigEndian};
+use utils::locate_protocol;
+use alloc::{String, Vec};
+use core::{mem, slice};
+use {Result, EfiErrorKind, from_boolean};
+
+// The TPM 2.0 as exposed by EFI_TCG2_PROTOCOL. Measurements go through hash_log_extend_event()
+// which hashes the data into every active PCR bank and appends an event to the firmware's log.
+// The log is in the crypto agile format: a SHA1-style TCG_PCR_EVENT holding the "Spec ID Event03"
+// that lists the digest sizes, followed by TCG_PCR_EVENT2 entries with one digest per bank.
+
+pub const EV_NO_ACTION: u32 = 0x00000003;
+pub const EV_SEPARATOR: u32 = 0x00000004;
+pub const EV_ACTION: u32 = 0x00000005;
+pub const EV_EVENT_TAG: u32 = 0x00000006;
+pub const EV_IPL: u32 = 0x0000000D;
+pub const EV_EFI_VARIABLE_DRIVER_CONFIG: u32 = 0x80000001;
+pub const EV_EFI_BOOT_SERVICES_APPLICATION: u32 = 0x80000003;
+pub const EV_EFI_ACTION: u32 = 0x80000007;
+
+const SPEC_ID_SIGNATURE: &[u8] = b"Spec ID Event03\0";
+const LEGACY_EVENT_HEADER_SIZE: usize = 32; // PCRIndex, EventType, SHA1 digest and EventSize
+const EVENT_HEADER_SIZE: usize = 14; // EFI_TCG2_EVENT_HEADER
+
+flags! {
+    /// A set of hash algorithms, as used for the supported and active PCR banks
+    pub struct HashAlgorithms(u32) {
+        const SHA1 = EFI_TCG2_BOOT_HASH_ALG_SHA1;
+        const SHA256 = EFI_TCG2_BOOT_HASH_ALG_SHA256;
+        const SHA384 = EFI_TCG2_BOOT_HASH_ALG_SHA384;
+        const SHA512 = EFI_TCG2_BOOT_HASH_ALG_SHA512;
+        const SM3_256 = EFI_TCG2_BOOT_HASH_ALG_SM3_256;
+    }
+}
+
+impl HashAlgorithms {
+    /// The TPM_ALG_ID of each algorithm in the set
+    pub fn algorithm_ids(&self) -> Vec<u16> {
+        [(Self::SHA1, TPM_ALG_SHA1), (Self::SHA256, TPM_ALG_SHA256), (Self::SHA384, TPM_ALG_SHA384), (Self::SHA512, TPM_ALG_SHA512), (Self::SM3_256, TPM_ALG_SM3_256)]
+            .iter()
+            .filter(|&&(alg, _)| self.contains(alg))
+            .map(|&(_, id)| id)
+            .collect()
+    }
+}
+
+flags! {
+    /// Flags for `Tcg2::hash_log_extend_event()`
+    pub struct ExtendFlags(u64) {
+        /// Extend the PCRs but don't log the event
+        const EXTEND_ONLY = EFI_TCG2_EXTEND_ONLY;
+        /// The data is a PE/COFF image which is to be hashed the Authenticode way
+        const PE_COFF_IMAGE = PE_COFF_IMAGE;
+    }
+}
+
+/// What the TCG2 protocol and the TPM behind it support
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Capability {
+    /// The protocol version as (major, minor)
+    pub protocol_version: (u8, u8),
+    pub hash_algorithms: HashAlgorithms,
+    pub supported_event_logs: u32,
+    pub tpm_present: bool,
+    pub max_command_size: u32,
+    pub max_response_size: u32,
+    pub manufacturer_id: u32,
+    pub number_of_pcr_banks: u32,
+    pub active_pcr_banks: HashAlgorithms,
+}
+
+impl Capability {
+    /// The TPM vendor's four character code, e.g. "INTC" or "MSFT"
+    pub fn manufacturer(&self) -> String {
+        let mut id = [0u8; 4];
+        BigEndian::write_u32(&mut id, self.manufacturer_id);
+        id.iter().filter(|b| **b != 0).map(|b| *b as char).collect()
+    }
+}
+
+/// A digest of an event for one PCR bank
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    /// The TPM_ALG_ID of the hash algorithm
+    pub algorithm: u16,
+    pub bytes: Vec<u8>,
+}
+
+/// An entry in the TPM event log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub pcr_index: u32,
+    pub event_type: u32,
+    pub digests: Vec<Digest>,
+    pub data: Vec<u8>,
+}
+
+impl Event {
+    /// The digest for the given TPM_ALG_ID, if the event has one
+    pub fn digest(&self, algorithm: u16) -> Option<&[u8]> {
+        self.digests.iter().find(|d| d.algorithm == algorithm).map(|d| &d.bytes[..])
+    }
+}
+
+/// The TPM event log in the crypto agile format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventLog {
+    /// (TPM_ALG_ID, digest size) for each algorithm the log has digests for, from the Spec ID event
+    pub digest_sizes: Vec<(u16, u16)>,
+    /// All events, starting with the Spec ID event. Empty if the firmware hasn't logged anything.
+    pub events: Vec<Event>,
+    /// The firmware ran out of space and dropped events
+    pub truncated: bool,
+}
+
+impl EventLog {
+    /// Parses a whole event log, e.g. one copied out of memory or read from a file
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let (spec_id, mut offset) = parse_legacy_event(bytes)?;
+        let digest_sizes = parse_spec_id(&spec_id.data)?;
+
+        let mut events = vec![spec_id];
+        while offset < bytes.len() {
+            let (event, size) = parse_event2(&bytes[offset..], &digest_sizes)?;
+            events.push(event);
+            offset += size;
+        }
+
+        Ok(Self { digest_sizes, events, truncated: false })
+    }
+}
+
+/// The TCG2 protocol
+pub struct Tcg2 {
+    protocol: *const EFI_TCG2_PROTOCOL,
+}
+
+impl Tcg2 {
+    pub fn get() -> Result<Self> {
+        Ok(Tcg2 { protocol: locate_protocol(&EFI_TCG2_PROTOCOL_GUID)? })
+    }
+
+    pub fn capability(&self) -> Result<Capability> {
+        let mut cap = EFI_TCG2_BOOT_SERVICE_CAPABILITY::default();
+        cap.Size = mem::size_of::<EFI_TCG2_BOOT_SERVICE_CAPABILITY>() as u8;
+        unsafe {
+            ret_on_err!(((*self.protocol).GetCapability)(self.protocol, &mut cap));
+        }
+
+        Ok(Capability {
+            protocol_version: (cap.ProtocolVersion.Major, cap.ProtocolVersion.Minor),
+            hash_algorithms: HashAlgorithms(cap.HashAlgorithmBitmap),
+            supported_event_logs: cap.SupportedEventLogs,
+            tpm_present: from_boolean(cap.TPMPresentFlag),
+            max_command_size: cap.MaxCommandSize,
+            max_response_size: cap.MaxResponseSize,
+            manufacturer_id: cap.ManufacturerID,
+            number_of_pcr_banks: cap.NumberOfPCRBanks,
+            active_pcr_banks: HashAlgorithms(cap.ActivePcrBanks),
+        })
+    }
+
+    pub fn active_pcr_banks(&self) -> Result<HashAlgorithms> {
+        let mut banks = 0;
+        unsafe {
+            ret_on_err!(((*self.protocol).GetActivePcrBanks)(self.protocol, &mut banks));
+        }
+
+        Ok(HashAlgorithms(banks))
+    }
+
+    /// Hashes `data` into the given PCR of every active bank and logs an event of the given type
+    /// carrying `event_data`, e.g. a description of what was measured
+    pub fn hash_log_extend_event(&self, flags: ExtendFlags, pcr_index: u32, event_type: u32, event_data: &[u8], data: &[u8]) -> Result<()> {
+        let event = build_event(pcr_index, event_type, event_data);
+        unsafe {
+            ret_on_err!(((*self.protocol).HashLogExtendEvent)(self.protocol, flags.bits(), data.as_ptr() as u64, data.len() as u64, event.as_ptr() as *const EFI_TCG2_EVENT));
+        }
+
+        Ok(())
+    }
+
+    /// Sends a raw TPM2 command and returns the response, header included
+    pub fn submit_command(&self, command: &[u8]) -> Result<Vec<u8>> {
+        let max_response_size = match self.capability()?.max_response_size {
+            0 => 4096,
+            size => size as usize,
+        };
+
+        let mut response = vec![0u8; max_response_size];
+        unsafe {
+            ret_on_err!(((*self.protocol).SubmitCommand)(self.protocol, command.len() as u32, command.as_ptr(), response.len() as u32, response.as_mut_ptr()));
+        }
+
+        // The response header is a big endian tag, size and response code
+        if response.len() >= 6 {
+            let size = BigEndian::read_u32(&response[2..6]) as usize;
+            if size >= 10 && size <= response.len() {
+                response.truncate(size);
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// The event log so far in the crypto agile (TCG 2.0) format
+    pub fn event_log(&self) -> Result<EventLog> {
+        let (mut location, mut last_entry, mut truncated) = (0u64, 0u64, 0u8);
+        unsafe {
+            ret_on_err!(((*self.protocol).GetEventLog)(self.protocol, EFI_TCG2_EVENT_LOG_FORMAT_TCG_2, &mut location, &mut last_entry, &mut truncated));
+        }
+
+        if location == 0 {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        if last_entry == 0 {
+            return Ok(EventLog { digest_sizes: Vec::new(), events: Vec::new(), truncated: from_boolean(truncated) }); // Nothing logged yet
+        }
+
+        if last_entry < location {
+            return Err(EfiErrorKind::VolumeCorrupted.into());
+        }
+
+        let length = unsafe {
+            if last_entry == location {
+                legacy_event_size_at(location)?
+            } else {
+                let digest_sizes = parse_spec_id(&parse_legacy_event(slice::from_raw_parts(location as *const u8, legacy_event_size_at(location)?))?.0.data)?;
+                (last_entry - location) as usize + event2_size_at(last_entry, &digest_sizes)?
+            }
+        };
+
+        let mut log = EventLog::parse(unsafe { slice::from_raw_parts(location as *const u8, length) })?;
+        log.truncated = from_boolean(truncated);
+        Ok(log)
+    }
+
+    /// The events logged after the event log was first retrieved, which the firmware keeps in the
+    /// final events table as they may be missing from the log returned by `event_log()`
+    pub fn final_events(&self) -> Result<Vec<Event>> {
+        let digest_sizes = self.event_log()?.digest_sizes;
+        if digest_sizes.is_empty() {
+            return Ok(Vec::new()); // Without a Spec ID event the events can't be parsed and there can't be any either
+        }
+
+        let table = find_config_table::<EFI_TCG2_FINAL_EVENTS_TABLE>(&EFI_TCG2_FINAL_EVENTS_TABLE_GUID)
+            .ok_or(EfiErrorKind::NotFound)?;
+
+        let count = unsafe { (*table).NumberOfEvents };
+        let mut address = table as u64 + mem::size_of::<EFI_TCG2_FINAL_EVENTS_TABLE>() as u64;
+        let mut events = Vec::new();
+        for _ in 0..count {
+            let size = unsafe { event2_size_at(address, &digest_sizes)? };
+            let (event, _) = parse_event2(unsafe { slice::from_raw_parts(address as *const u8, size) }, &digest_sizes)?;
+            events.push(event);
+            address += size as u64;
+        }
+
+        Ok(events)
+    }
+}
+
+// An EFI_TCG2_EVENT: the size, the header and then the event data
+fn build_event(pcr_index: u32, event_type: u32, event_data: &[u8]) -> Vec<u8> {
+    let mut event = vec![0u8; 4 + EVENT_HEADER_SIZE];
+    LittleEndian::write_u32(&mut event[0..4], (4 + EVENT_HEADER_SIZE + event_data.len()) as u32);
+    LittleEndian::write_u32(&mut event[4..8], EVENT_HEADER_SIZE as u32);
+    LittleEndian::write_u16(&mut event[8..10], EFI_TCG2_EVENT_HEADER_VERSION);
+    LittleEndian::write_u32(&mut event[10..14], pcr_index);
+    LittleEndian::write_u32(&mut event[14..18], event_type);
+    event.extend_from_slice(event_data);
+    event
+}
+
+// Parses a TCG_PCR_EVENT (the SHA1-only format the log starts with) returning it and its size
+fn parse_legacy_event(bytes: &[u8]) -> Result<(Event, usize)> {
+    if bytes.len() < LEGACY_EVENT_HEADER_SIZE {
+        return Err(EfiErrorKind::VolumeCorrupted.into());
+    }
+
+    let size = LEGACY_EVENT_HEADER_SIZE + LittleEndian::read_u32(&bytes[28..32]) as usize;
+    if size > bytes.len() {
+        return Err(EfiErrorKind::VolumeCorrupted.into());
+    }
+
+    let event = Event {
+        pcr_index: LittleEndian::read_u32(&bytes[0..4]),
+        event_type: LittleEndian::read_u32(&bytes[4..8]),
+        digests: vec![Digest { algorithm: TPM_ALG_SHA1, bytes: bytes[8..28].to_vec() }],
+        data: bytes[LEGACY_EVENT_HEADER_SIZE..size].to_vec(),
+    };
+    Ok((event, size))
+}
+
+// The (algorithm, digest size) list from a TCG_EfiSpecIDEventStruct
+fn parse_spec_id(data: &[u8]) -> Result<Vec<(u16, u16)>> {
+    if data.len() < 28 || &data[..16] != SPEC_ID_SIGNATURE {
+        return Err(EfiErrorKind::Unsupported.into());
+    }
+
+    let count = LittleEndian::read_u32(&data[24..28]) as usize;
+    if data.len() < 28 + count * 4 {
+        return Err(EfiErrorKind::VolumeCorrupted.into());
+    }
+
+    Ok(data[28..28 + count * 4].chunks(4)
+        .map(|c| (LittleEndian::read_u16(&c[0..2]), LittleEndian::read_u16(&c[2..4])))
+        .collect())
+}
+
+// How far into a TCG_PCR_EVENT2 parsing got: either its full size or how many bytes are needed to continue
+#[derive(Debug, PartialEq, Eq)]
+enum Event2Size {
+    Complete(usize),
+    NeedAtLeast(usize),
+}
+
+fn event2_size(bytes: &[u8], digest_sizes: &[(u16, u16)]) -> Result<Event2Size> {
+    if bytes.len() < 12 {
+        return Ok(Event2Size::NeedAtLeast(12));
+    }
+
+    let mut offset = 12;
+    for _ in 0..LittleEndian::read_u32(&bytes[8..12]) {
+        if bytes.len() < offset + 2 {
+            return Ok(Event2Size::NeedAtLeast(offset + 2));
+        }
+
+        let algorithm = LittleEndian::read_u16(&bytes[offset..offset + 2]);
+        let size = digest_sizes.iter()
+            .find(|&&(alg, _)| alg == algorithm)
+            .map(|&(_, size)| size as usize)
+            .ok_or(EfiErrorKind::VolumeCorrupted)?;
+        offset += 2 + size;
+    }
+
+    if bytes.len() < offset + 4 {
+        return Ok(Event2Size::NeedAtLeast(offset + 4));
+    }
+
+    Ok(Event2Size::Complete(offset + 4 + LittleEndian::read_u32(&bytes[offset..offset + 4]) as usize))
+}
+
+fn parse_event2(bytes: &[u8], digest_sizes: &[(u16, u16)]) -> Result<(Event, usize)> {
+    let size = match event2_size(bytes, digest_sizes)? {
+        Event2Size::Complete(size) if size <= bytes.len() => size,
+        _ => return Err(EfiErrorKind::VolumeCorrupted.into()),
+    };
+
+    let mut digests = Vec::new();
+    let mut offset = 12;
+    for _ in 0..LittleEndian::read_u32(&bytes[8..12]) {
+        let algorithm = LittleEndian::read_u16(&bytes[offset..offset + 2]);
+        let digest_size = digest_sizes.iter().find(|&&(alg, _)| alg == algorithm).map_or(0, |&(_, size)| size as usize);
+        digests.push(Digest { algorithm, bytes: bytes[offset + 2..offset + 2 + digest_size].to_vec() });
+        offset += 2 + digest_size;
+    }
+
+    let event = Event {
+        pcr_index: LittleEndian::read_u32(&bytes[0..4]),
+        event_type: LittleEndian::read_u32(&bytes[4..8]),
+        digests,
+        data: bytes[offset + 4..size].to_vec(),
+    };
+    Ok((event, size))
+}
+
+// The size of the TCG_PCR_EVENT at the given address
+unsafe fn legacy_event_size_at(address: u64) -> Result<usize> {
+    let header = slice::from_raw_parts(address as *const u8, LEGACY_EVENT_HEADER_SIZE);
+    Ok(LEGACY_EVENT_HEADER_SIZE + LittleEndian::read_u32(&header[28..32]) as usize)
+}
+
+// The size of the TCG_PCR_EVENT2 at the given address, reading no more of it than needed
+unsafe fn event2_size_at(address: u64, digest_sizes: &[(u16, u16)]) -> Result<usize> {
+    let mut len = 0;
+    loop {
+        match event2_size(slice::from_raw_parts(address as *const u8, len), digest_sizes)? {
+            Event2Size::Complete(size) => return Ok(size),
+            Event2Size::NeedAtLeast(needed) => len = needed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1_SIZE: usize = 20;
+    const SHA256_SIZE: usize = 32;
+
+    fn spec_id_event() -> Vec<u8> {
+        let mut data = SPEC_ID_SIGNATURE.to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 2, 0, 2, 2, 0, 0, 0]);
+        data.extend_from_slice(&[0x04, 0x00, 20, 0, 0x0B, 0x00, 32, 0, 0]);
+
+        let mut event = vec![0u8; LEGACY_EVENT_HEADER_SIZE];
+        LittleEndian::write_u32(&mut event[4..8], EV_NO_ACTION);
+        LittleEndian::write_u32(&mut event[28..32], data.len() as u32);
+        event.extend(data);
+        event
+    }
+
+    fn event2(pcr: u32, event_type: u32, data: &[u8]) -> Vec<u8> {
+        let mut event = vec![0u8; 12];
+        LittleEndian::write_u32(&mut event[0..4], pcr);
+        LittleEndian::write_u32(&mut event[4..8], event_type);
+        LittleEndian::write_u32(&mut event[8..12], 2);
+        event.extend_from_slice(&[0x04, 0x00]);
+        event.extend_from_slice(&[0x11; SHA1_SIZE]);
+        event.extend_from_slice(&[0x0B, 0x00]);
+        event.extend_from_slice(&[0x22; SHA256_SIZE]);
+        let mut size = [0u8; 4];
+        LittleEndian::write_u32(&mut size, data.len() as u32);
+        event.extend_from_slice(&size);
+        event.extend_from_slice(data);
+        event
+    }
+
+    #[test]
+    fn crypto_agile_logs_are_parsed() {
+        let mut log = spec_id_event();
+        log.extend(event2(4, EV_EFI_BOOT_SERVICES_APPLICATION, b"\\EFI\\BOOT\\BOOTX64.EFI"));
+        log.extend(event2(7, EV_SEPARATOR, &[0, 0, 0, 0]));
+
+        let log = EventLog::parse(&log).unwrap();
+        assert_eq!(log.digest_sizes, vec![(TPM_ALG_SHA1, 20), (TPM_ALG_SHA256, 32)]);
+        assert_eq!(log.events.len(), 3);
+        assert_eq!(log.events[0].event_type, EV_NO_ACTION);
+        assert_eq!(log.events[1].pcr_index, 4);
+        assert_eq!(log.events[1].data, b"\\EFI\\BOOT\\BOOTX64.EFI");
+        assert_eq!(log.events[1].digest(TPM_ALG_SHA256), Some(&[0x22; SHA256_SIZE][..]));
+        assert_eq!(log.events[1].digest(TPM_ALG_SHA384), None);
+        assert_eq!(log.events[2].event_type, EV_SEPARATOR);
+    }
+
+    #[test]
+    fn event_sizes_are_found_incrementally() {
+        let event = event2(0, EV_ACTION, b"abc");
+        let sizes = [(TPM_ALG_SHA1, 20), (TPM_ALG_SHA256, 32)];
+        assert_eq!(event2_size(&[], &sizes).unwrap(), Event2Size::NeedAtLeast(12));
+        assert_eq!(event2_size(&event[..12], &sizes).unwrap(), Event2Size::NeedAtLeast(14));
+        assert_eq!(event2_size(&event[..14], &sizes).unwrap(), Event2Size::NeedAtLeast(36));
+        assert_eq!(event2_size(&event[..72], &sizes).unwrap(), Event2Size::Complete(event.len()));
+
+        // Digests for algorithms the Spec ID event doesn't list can't be sized
+        assert_eq!(event2_size(&event, &sizes[..1]).unwrap_err().kind(), EfiErrorKind::VolumeCorrupted);
+        assert_eq!(parse_event2(&event[..event.len() - 1], &sizes).unwrap_err().kind(), EfiErrorKind::VolumeCorrupted);
+    }
+
+    #[test]
+    fn logs_without_a_spec_id_event_are_rejected() {
+        let mut log = spec_id_event();
+        log[LEGACY_EVENT_HEADER_SIZE] = b'X';
+        assert_eq!(EventLog::parse(&log).unwrap_err().kind(), EfiErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn events_are_built_with_a_packed_header() {
+        let event = build_event(9, EV_IPL, b"grub.cfg");
+        assert_eq!(event.len(), 18 + 8);
+        assert_eq!(LittleEndian::read_u32(&event[0..4]), 26);
+        assert_eq!(LittleEndian::read_u32(&event[4..8]), 14);
+        assert_eq!(LittleEndian::read_u16(&event[8..10]), 1);
+        assert_eq!(LittleEndian::read_u32(&event[10..14]), 9);
+        assert_eq!(LittleEndian::read_u32(&event[14..18]), EV_IPL);
+        assert_eq!(&event[18..], b"grub.cfg");
+    }
+
+    #[test]
+    fn algorithms_map_to_tpm_ids() {
+        let algs = HashAlgorithms::SHA1 | HashAlgorithms::SHA256;
+        assert_eq!(algs.algorithm_ids(), vec![TPM_ALG_SHA1, TPM_ALG_SHA256]);
+        assert!(!algs.contains(HashAlgorithms::SHA384));
+    }
+}
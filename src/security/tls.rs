@@ -0,0 +1,54 @@
+use ffi::security::EFI_TLS_CA_CERTIFICATE_GUID;
+use super::signature::{SignatureDatabase, is_der_sequence};
+use variables::{self, Attributes};
+use {Result, EfiErrorKind, Guid};
+
+// The TLS trust store. The firmware's HTTP boot reads its CA certificates from the TlsCaCertificate
+// variable, a sequence of signature lists of DER encoded X.509 certificates, when it sets up a TLS
+// session. Certificates written here are therefore used from the next HTTPS connection on.
+
+pub const TLS_CA_CERTIFICATE_VARIABLE: &str = "TlsCaCertificate";
+
+/// The CA certificates HTTPS connections are verified against. Empty if none were provisioned.
+pub fn ca_certificates() -> Result<SignatureDatabase> {
+    match variables::try_get(TLS_CA_CERTIFICATE_VARIABLE, &EFI_TLS_CA_CERTIFICATE_GUID)? {
+        Some(bytes) => SignatureDatabase::parse(&bytes),
+        None => Ok(SignatureDatabase::new()),
+    }
+}
+
+/// Replaces the CA certificates. An empty database deletes the variable.
+pub fn set_ca_certificates(db: &SignatureDatabase) -> Result<()> {
+    if db.lists.is_empty() {
+        return variables::delete(TLS_CA_CERTIFICATE_VARIABLE, &EFI_TLS_CA_CERTIFICATE_GUID);
+    }
+
+    variables::set(TLS_CA_CERTIFICATE_VARIABLE, &EFI_TLS_CA_CERTIFICATE_GUID, Attributes::NON_VOLATILE | Attributes::BOOTSERVICE_ACCESS, &db.to_bytes())
+}
+
+/// Adds a DER encoded CA certificate unless it's already there.
+/// Fails with `InvalidParameter` if the certificate isn't DER encoded (e.g. if it's PEM).
+pub fn add_ca_certificate(owner: Guid, certificate: &[u8]) -> Result<()> {
+    if !is_der_sequence(certificate) {
+        return Err(EfiErrorKind::InvalidParameter.into());
+    }
+
+    let mut db = ca_certificates()?;
+    if db.contains_certificate(certificate) {
+        return Ok(());
+    }
+
+    db.add_certificate(owner, certificate);
+    set_ca_certificates(&db)
+}
+
+/// Removes a CA certificate. Returns false if it wasn't there.
+pub fn remove_ca_certificate(certificate: &[u8]) -> Result<bool> {
+    let mut db = ca_certificates()?;
+    if !db.remove_certificate(certificate) {
+        return Ok(false);
+    }
+
+    set_ca_certificates(&db)?;
+    Ok(true)
+}
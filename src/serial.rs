@@ -0,0 +1,266 @@
+use ffi::{
+    serial::{
+        EFI_SERIAL_IO_PROTOCOL,
+        EFI_SERIAL_IO_PROTOCOL_GUID,
+        EFI_PARITY_TYPE,
+        EFI_STOP_BITS_TYPE,
+        EFI_SERIAL_CLEAR_TO_SEND,
+        EFI_SERIAL_DATA_SET_READY,
+        EFI_SERIAL_RING_INDICATE,
+        EFI_SERIAL_CARRIER_DETECT,
+        EFI_SERIAL_REQUEST_TO_SEND,
+        EFI_SERIAL_DATA_TERMINAL_READY,
+        EFI_SERIAL_INPUT_BUFFER_EMPTY,
+        EFI_SERIAL_OUTPUT_BUFFER_EMPTY,
+        EFI_SERIAL_HARDWARE_LOOPBACK_ENABLE,
+        EFI_SERIAL_SOFTWARE_LOOPBACK_ENABLE,
+        EFI_SERIAL_HARDWARE_FLOW_CONTROL_ENABLE,
+    },
+    EFI_HANDLE,
+    EFI_TIMEOUT,
+    IsSuccess,
+    UINT32,
+    UINTN,
+    VOID,
+};
+use io;
+use utils::{handles_by_protocol, open_protocol, as_micros_saturating};
+use alloc::Vec;
+use core::{cmp, time::Duration};
+use {Result, EfiErrorKind};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Parity {
+    /// Whatever the device defaults to
+    Default,
+    None,
+    Even,
+    Odd,
+    Mark,
+    Space,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopBits {
+    /// Whatever the device defaults to
+    Default,
+    One,
+    OneFive,
+    Two,
+}
+
+/// The line settings of a serial port
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SerialConfig {
+    /// 0 for the device's default
+    pub baud_rate: u64,
+    /// 0 for the device's default
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    /// Depth of the receive FIFO. 0 for the device's default.
+    pub receive_fifo_depth: u32,
+    /// How long a read or write waits for each character. Zero for the device's default.
+    pub timeout: Duration,
+}
+
+impl SerialConfig {
+    /// 8 data bits, no parity and 1 stop bit (8N1) at the given baud rate
+    pub fn new(baud_rate: u64) -> Self {
+        SerialConfig { baud_rate, data_bits: 8, parity: Parity::None, stop_bits: StopBits::One, receive_fifo_depth: 0, timeout: Duration::from_secs(0) }
+    }
+}
+
+impl Default for SerialConfig {
+    /// 115200 8N1, the usual setting of server consoles
+    fn default() -> Self {
+        SerialConfig::new(115200)
+    }
+}
+
+flags! {
+    /// Modem control and status lines, and loopback/flow control settings.
+    /// Can be combined using the `|` operator.
+    pub struct ControlBits(UINT32) {
+        const CLEAR_TO_SEND = EFI_SERIAL_CLEAR_TO_SEND;
+        const DATA_SET_READY = EFI_SERIAL_DATA_SET_READY;
+        const RING_INDICATE = EFI_SERIAL_RING_INDICATE;
+        const CARRIER_DETECT = EFI_SERIAL_CARRIER_DETECT;
+        const REQUEST_TO_SEND = EFI_SERIAL_REQUEST_TO_SEND;
+        const DATA_TERMINAL_READY = EFI_SERIAL_DATA_TERMINAL_READY;
+        const INPUT_BUFFER_EMPTY = EFI_SERIAL_INPUT_BUFFER_EMPTY;
+        const OUTPUT_BUFFER_EMPTY = EFI_SERIAL_OUTPUT_BUFFER_EMPTY;
+        const HARDWARE_LOOPBACK_ENABLE = EFI_SERIAL_HARDWARE_LOOPBACK_ENABLE;
+        const SOFTWARE_LOOPBACK_ENABLE = EFI_SERIAL_SOFTWARE_LOOPBACK_ENABLE;
+        const HARDWARE_FLOW_CONTROL_ENABLE = EFI_SERIAL_HARDWARE_FLOW_CONTROL_ENABLE;
+    }
+}
+
+/// A serial port (EFI_SERIAL_IO_PROTOCOL).
+/// Reading through `io::Read` blocks until at least one byte arrives. Use `try_read()` to give up after the timeout.
+pub struct SerialPort {
+    handle: EFI_HANDLE,
+    protocol: *mut EFI_SERIAL_IO_PROTOCOL,
+}
+
+impl SerialPort {
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol::<EFI_SERIAL_IO_PROTOCOL>(handle, &EFI_SERIAL_IO_PROTOCOL_GUID)?;
+        Ok(SerialPort { handle, protocol: protocol as *mut _ })
+    }
+
+    /// Opens all serial ports in the system
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_SERIAL_IO_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    /// The first serial port in the system, usually COM1. Fails with `NotFound` if there isn't one.
+    pub fn first() -> Result<Self> {
+        match Self::all()?.into_iter().next() {
+            Some(port) => Ok(port),
+            None => Err(EfiErrorKind::NotFound.into())
+        }
+    }
+
+    /// The handle the port is on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// Resets the hardware
+    pub fn reset(&mut self) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).Reset)(self.protocol));
+        }
+
+        Ok(())
+    }
+
+    /// The current line settings
+    pub fn config(&self) -> SerialConfig {
+        let mode = unsafe { &*(*self.protocol).Mode };
+        let parity = match mode.Parity {
+            1 => Parity::None,
+            2 => Parity::Even,
+            3 => Parity::Odd,
+            4 => Parity::Mark,
+            5 => Parity::Space,
+            _ => Parity::Default
+        };
+
+        let stop_bits = match mode.StopBits {
+            1 => StopBits::One,
+            2 => StopBits::OneFive,
+            3 => StopBits::Two,
+            _ => StopBits::Default
+        };
+
+        SerialConfig {
+            baud_rate: mode.BaudRate,
+            data_bits: mode.DataBits as u8,
+            parity,
+            stop_bits,
+            receive_fifo_depth: mode.ReceiveFifoDepth,
+            timeout: Duration::from_micros(mode.Timeout as u64),
+        }
+    }
+
+    /// Changes the line settings. Fails with `InvalidParameter` if the device doesn't support them.
+    pub fn set_config(&mut self, config: &SerialConfig) -> Result<()> {
+        let parity = match config.parity {
+            Parity::Default => EFI_PARITY_TYPE::DefaultParity,
+            Parity::None => EFI_PARITY_TYPE::NoParity,
+            Parity::Even => EFI_PARITY_TYPE::EvenParity,
+            Parity::Odd => EFI_PARITY_TYPE::OddParity,
+            Parity::Mark => EFI_PARITY_TYPE::MarkParity,
+            Parity::Space => EFI_PARITY_TYPE::SpaceParity,
+        };
+
+        let stop_bits = match config.stop_bits {
+            StopBits::Default => EFI_STOP_BITS_TYPE::DefaultStopBits,
+            StopBits::One => EFI_STOP_BITS_TYPE::OneStopBit,
+            StopBits::OneFive => EFI_STOP_BITS_TYPE::OneFiveStopBits,
+            StopBits::Two => EFI_STOP_BITS_TYPE::TwoStopBits,
+        };
+
+        let timeout = cmp::min(as_micros_saturating(config.timeout), u32::max_value() as u64) as u32;
+        unsafe {
+            ret_on_err!(((*self.protocol).SetAttributes)(self.protocol, config.baud_rate, config.receive_fifo_depth, timeout, parity, config.data_bits, stop_bits));
+        }
+
+        Ok(())
+    }
+
+    /// Changes only the baud rate, keeping the other settings
+    pub fn set_baud_rate(&mut self, baud_rate: u64) -> Result<()> {
+        let mut config = self.config();
+        config.baud_rate = baud_rate;
+        self.set_config(&config)
+    }
+
+    /// The state of the control lines
+    pub fn control(&self) -> Result<ControlBits> {
+        let mut bits: UINT32 = 0;
+        unsafe {
+            ret_on_err!(((*self.protocol).GetControl)(self.protocol, &mut bits));
+        }
+
+        Ok(ControlBits(bits))
+    }
+
+    /// Sets the control lines. Only REQUEST_TO_SEND, DATA_TERMINAL_READY and the loopback and flow control bits can be set.
+    pub fn set_control(&mut self, bits: ControlBits) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).SetControl)(self.protocol, bits.bits()));
+        }
+
+        Ok(())
+    }
+
+    /// Reads whatever arrives before the timeout, which may be nothing
+    pub fn try_read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut size: UINTN = buf.len();
+        let status = unsafe { ((*self.protocol).Read)(self.protocol, &mut size, buf.as_mut_ptr() as *mut VOID) };
+        match status {
+            EFI_TIMEOUT => Ok(size),
+            s if IsSuccess(s) => Ok(size),
+            s => Err(s.into())
+        }
+    }
+
+    /// Writes as much as the device takes before the timeout
+    fn try_write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut size: UINTN = buf.len();
+        let status = unsafe { ((*self.protocol).Write)(self.protocol, &mut size, buf.as_ptr() as *const VOID) };
+        match status {
+            EFI_TIMEOUT if size > 0 => Ok(size),
+            s if IsSuccess(s) => Ok(size),
+            s => Err(s.into())
+        }
+    }
+}
+
+impl io::Read for SerialPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match self.try_read(buf)? {
+                0 => continue,
+                n => return Ok(n)
+            }
+        }
+    }
+}
+
+impl io::Write for SerialPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.try_write(buf).map_err(io::Error::from)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(()) // Every write goes straight to the device
+    }
+}
@@ -0,0 +1,416 @@
+use ffi::{
+    shell::{EFI_SHELL_PROTOCOL, EFI_SHELL_PROTOCOL_GUID, SHELL_FILE_HANDLE},
+    shell_parameters::{EFI_SHELL_PARAMETERS_PROTOCOL, EFI_SHELL_PARAMETERS_PROTOCOL_GUID},
+    media::{EFI_FILE_PROTOCOL, EFI_FILE_INFO_ID},
+    device_path::EFI_DEVICE_PATH_PROTOCOL,
+    runtime_services::EFI_VARIABLE_NON_VOLATILE,
+    EFI_STATUS,
+    EFI_SUCCESS,
+    EFI_BUFFER_TOO_SMALL,
+    CHAR16,
+    VOID,
+    IsSuccess,
+};
+use fs::{Volume, File, OpenMode, FileAttributes};
+use device_path::DevicePath;
+use utils::{as_slice, to_null_terminated_utf16, locate_protocol, open_protocol};
+use boxed::EfiBox;
+use io::{self, Cursor};
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec};
+use core::{ptr, slice};
+use {Result, EfiError, EfiErrorKind, image_handle, to_boolean};
+
+// The UEFI Shell's own services (EFI_SHELL_PROTOCOL). Only present when the image was started from
+// the shell, or the shell is otherwise running.
+
+/// The running shell
+pub struct Shell {
+    protocol: *const EFI_SHELL_PROTOCOL,
+}
+
+impl Shell {
+    /// Fails with `NotFound` when no shell is running
+    pub fn get() -> Result<Self> {
+        Ok(Shell { protocol: locate_protocol(&EFI_SHELL_PROTOCOL_GUID)? })
+    }
+
+    /// The version of the shell specification the shell implements as (major, minor)
+    pub fn version(&self) -> (u32, u32) {
+        unsafe { ((*self.protocol).MajorVersion, (*self.protocol).MinorVersion) }
+    }
+
+    /// Runs a command line as if it were typed at the prompt, in the current environment.
+    /// Fails either if the command couldn't be run or with the status the command itself returned.
+    pub fn execute(&self, command_line: &str) -> Result<()> {
+        self.execute_in(command_line, None)
+    }
+
+    /// Runs a command line with only the given environment variables set
+    pub fn execute_with_env(&self, command_line: &str, env: &[(&str, &str)]) -> Result<()> {
+        self.execute_in(command_line, Some(env))
+    }
+
+    /// The value of an environment variable
+    pub fn env(&self, name: &str) -> Option<String> {
+        let name = to_null_terminated_utf16(name);
+        let value = unsafe { ((*self.protocol).GetEnv)(name.as_ptr()) };
+        string_from_shell(value) // Owned by the shell
+    }
+
+    /// Sets an environment variable. Non-volatile variables survive a reset.
+    /// An empty value deletes the variable.
+    pub fn set_env(&self, name: &str, value: &str, volatile: bool) -> Result<()> {
+        let name = to_null_terminated_utf16(name);
+        let value = to_null_terminated_utf16(value);
+        unsafe {
+            ret_on_err!(((*self.protocol).SetEnv)(name.as_ptr(), value.as_ptr(), to_boolean(volatile)));
+        }
+        Ok(())
+    }
+
+    /// Deletes an environment variable, whether it's volatile or not
+    pub fn remove_env(&self, name: &str) -> Result<()> {
+        self.set_env(name, "", true)
+    }
+
+    /// The names of all environment variables
+    pub fn env_names(&self) -> Vec<String> {
+        let names = unsafe { ((*self.protocol).GetEnv)(ptr::null()) };
+        if names.is_null() {
+            return Vec::new();
+        }
+
+        split_multi_string(unsafe { multi_string_as_slice(names) }) // Owned by the shell
+    }
+
+    /// All environment variables as (name, value) pairs
+    pub fn envs(&self) -> Vec<(String, String)> {
+        self.env_names().into_iter()
+            .filter_map(|name| self.env(&name).map(|value| (name, value)))
+            .collect()
+    }
+
+    /// Whether the environment variable is volatile, i.e. is gone after a reset. Fails with `NotFound` if there's
+    /// no such variable and with `Unsupported` on shells older than 2.1, which don't say.
+    pub fn is_env_volatile(&self, name: &str) -> Result<bool> {
+        let (major, minor) = self.version();
+        if (major, minor) < (2, 1) {
+            return Err(EfiErrorKind::Unsupported.into()); // GetEnvEx() is past the end of older protocols
+        }
+
+        let name = to_null_terminated_utf16(name);
+        let mut attributes = 0;
+        let value = unsafe { ((*self.protocol).GetEnvEx)(name.as_ptr(), &mut attributes) };
+        if value.is_null() {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        Ok(attributes & EFI_VARIABLE_NON_VOLATILE == 0)
+    }
+
+    /// The device path a mapping such as "fs0" or "blk2:" refers to
+    pub fn device_path_from_map(&self, mapping: &str) -> Result<DevicePath> {
+        let mapping = to_null_terminated_utf16(&mapping_name(mapping));
+        let path = unsafe { ((*self.protocol).GetDevicePathFromMap)(mapping.as_ptr()) };
+        if path.is_null() {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        DevicePath::from_bytes(DevicePath::from_ptr(path)?.as_bytes()) // Owned by the shell so copied
+    }
+
+    /// The mappings of a device, separated by ';' if there is more than one e.g. "fs0:;blk1:"
+    pub fn map_from_device_path(&self, path: &DevicePath) -> Result<String> {
+        let mut remaining: *const EFI_DEVICE_PATH_PROTOCOL = path.as_ptr();
+        let map = unsafe { ((*self.protocol).GetMapFromDevicePath)(&mut remaining) };
+        string_from_shell(map).ok_or_else(|| EfiErrorKind::NotFound.into())
+    }
+
+    /// Converts a shell path such as `fs0:\EFI\tool.efi`, or a path relative to the current directory,
+    /// to a device path
+    pub fn device_path_from_file_path(&self, path: &str) -> Result<DevicePath> {
+        let path = to_null_terminated_utf16(path);
+        let raw = unsafe { ((*self.protocol).GetDevicePathFromFilePath)(path.as_ptr()) };
+        if raw.is_null() {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        let raw = unsafe { EfiBox::from_raw(raw) };  // Putting it in a box for proper cleanup on exit
+        DevicePath::from_bytes(DevicePath::from_ptr(raw.as_raw())?.as_bytes())
+    }
+
+    /// Converts a device path to a shell path such as `fs0:\EFI\tool.efi`
+    pub fn file_path_from_device_path(&self, path: &DevicePath) -> Result<String> {
+        let raw = unsafe { ((*self.protocol).GetFilePathFromDevicePath)(path.as_ptr()) };
+        if raw.is_null() {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        let raw = unsafe { EfiBox::from_raw(raw) };  // Putting it in a box for proper cleanup on exit
+        Ok(String::from_utf16_lossy(unsafe { as_slice(raw.as_raw()) }))
+    }
+
+    /// The current directory of a file system mapping, or of the current file system if `None`.
+    /// Includes the mapping e.g. `fs0:\EFI`.
+    pub fn current_dir(&self, mapping: Option<&str>) -> Option<String> {
+        let mapping = mapping.map(|m| to_null_terminated_utf16(&mapping_name(m)));
+        let dir = unsafe { ((*self.protocol).GetCurDir)(mapping.as_ref().map_or(ptr::null(), |m| m.as_ptr())) };
+        string_from_shell(dir)
+    }
+
+    /// Changes the current directory. With a mapping, changes that file system's current directory
+    /// without switching to it. Without one the directory can include a mapping to switch to.
+    pub fn set_current_dir(&self, mapping: Option<&str>, dir: &str) -> Result<()> {
+        let mapping = mapping.map(|m| to_null_terminated_utf16(&mapping_name(m)));
+        let dir = to_null_terminated_utf16(dir);
+        unsafe {
+            ret_on_err!(((*self.protocol).SetCurDir)(mapping.as_ref().map_or(ptr::null(), |m| m.as_ptr()), dir.as_ptr()));
+        }
+        Ok(())
+    }
+
+    /// Opens a file by shell path, resolved the way the shell would: through its mappings and
+    /// relative to its current directory
+    pub fn open(&self, path: &str, mode: OpenMode, attributes: FileAttributes) -> Result<File> {
+        let device_path = self.device_path_from_file_path(path)?;
+        let (volume, path) = Volume::from_device_path(&device_path)?;
+        volume.open(&path, mode, attributes)
+    }
+
+    fn execute_in(&self, command_line: &str, env: Option<&[(&str, &str)]>) -> Result<()> {
+        let command_line = to_null_terminated_utf16(command_line);
+        let env_strings = env.map(|e| e.iter().map(|&(name, value)| to_null_terminated_utf16(&env_entry(name, value))).collect::<Vec<_>>());
+        let env_ptrs = env_strings.as_ref().map(|e| {
+            let mut ptrs = e.iter().map(|s| s.as_ptr()).collect::<Vec<*const CHAR16>>();
+            ptrs.push(ptr::null());
+            ptrs
+        });
+
+        let parent = image_handle();
+        let mut command_status: EFI_STATUS = EFI_SUCCESS;
+        unsafe {
+            ret_on_err!(((*self.protocol).Execute)(&parent, command_line.as_ptr(), env_ptrs.as_ref().map_or(ptr::null(), |e| e.as_ptr()), &mut command_status));
+        }
+
+        if IsSuccess(command_status) { Ok(()) } else { Err(EfiError::from(command_status)) }
+    }
+}
+
+fn string_from_shell(s: *const CHAR16) -> Option<String> {
+    if s.is_null() {
+        None
+    } else {
+        Some(String::from_utf16_lossy(unsafe { as_slice(s) }))
+    }
+}
+
+// A list of null terminated strings ending with an empty one
+unsafe fn multi_string_as_slice<'a>(s: *const CHAR16) -> &'a [CHAR16] {
+    let mut len = 0;
+    while *s.offset(len) != 0 || *s.offset(len + 1) != 0 {
+        len += 1;
+    }
+
+    slice::from_raw_parts(s, len as usize + 1)
+}
+
+fn split_multi_string(s: &[CHAR16]) -> Vec<String> {
+    s.split(|&c| c == 0).filter(|s| !s.is_empty()).map(String::from_utf16_lossy).collect()
+}
+
+// The shell wants mappings with their trailing colon
+fn mapping_name(mapping: &str) -> String {
+    format!("{}:", mapping.trim_right_matches(':'))
+}
+
+fn env_entry(name: &str, value: &str) -> String {
+    format!("{}={}", name, value)
+}
+
+/// What the shell hands an application it starts (EFI_SHELL_PARAMETERS_PROTOCOL): the already split
+/// command line and the standard streams, which may have been redirected to files or pipes
+pub struct ShellParameters {
+    protocol: *const EFI_SHELL_PARAMETERS_PROTOCOL,
+}
+
+impl ShellParameters {
+    /// The parameters of the running image. Fails with `Unsupported` if it wasn't started from the shell.
+    pub fn current() -> Result<Self> {
+        let protocol = open_protocol(image_handle(), &EFI_SHELL_PARAMETERS_PROTOCOL_GUID)?;
+        Ok(ShellParameters { protocol })
+    }
+
+    /// The arguments as split by the shell, starting with the name the image was run as
+    pub fn argv(&self) -> Vec<String> {
+        let (argv, argc) = unsafe { ((*self.protocol).Argv, (*self.protocol).Argc) };
+        if argv.is_null() {
+            return Vec::new();
+        }
+
+        unsafe { slice::from_raw_parts(argv, argc) }.iter()
+            .filter_map(|arg| string_from_shell(*arg))
+            .collect()
+    }
+
+    pub fn stdin(&self) -> ShellFile {
+        ShellFile::new(unsafe { (*self.protocol).StdIn })
+    }
+
+    pub fn stdout(&self) -> ShellFile {
+        ShellFile::new(unsafe { (*self.protocol).StdOut })
+    }
+
+    pub fn stderr(&self) -> ShellFile {
+        ShellFile::new(unsafe { (*self.protocol).StdErr })
+    }
+}
+
+/// One of the shell's standard streams. Text goes through it as UCS-2 like it does in files the
+/// shell redirects to, and is converted to and from UTF-8 at this end.
+pub struct ShellFile {
+    handle: *mut EFI_FILE_PROTOCOL,
+    utf8_buf: Cursor<Vec<u8>>,
+    odd_byte: Option<u8>, // Half a UCS-2 character left over from the last read
+    at_start: bool,
+}
+
+impl ShellFile {
+    fn new(handle: SHELL_FILE_HANDLE) -> Self {
+        ShellFile { handle: handle as *mut EFI_FILE_PROTOCOL, utf8_buf: Cursor::new(Vec::new()), odd_byte: None, at_start: true }
+    }
+
+    /// Whether the stream is a file, rather than the console or a pipe. The shell's console and pipe
+    /// handles don't support GetInfo() while files do.
+    pub fn is_file(&self) -> bool {
+        if self.handle.is_null() {
+            return false;
+        }
+
+        let mut size = 0;
+        let status = unsafe { ((*self.handle).GetInfo)(self.handle, &EFI_FILE_INFO_ID, &mut size, ptr::null_mut()) };
+        status == EFI_BUFFER_TOO_SMALL || IsSuccess(status)
+    }
+}
+
+impl io::Write for ShellFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = match ::alloc::str::from_utf8(buf) {
+            Ok(text) => text,
+            Err(ref e) if e.valid_up_to() == 0 => return Err(io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")),
+            Err(e) => unsafe { ::alloc::str::from_utf8_unchecked(&buf[..e.valid_up_to()]) },
+        };
+
+        let bytes = encode_ucs2(text);
+        let mut size = bytes.len();
+        let status = unsafe { ((*self.handle).Write)(self.handle, &mut size, bytes.as_ptr() as *const VOID) };
+        if !IsSuccess(status) {
+            return Err(EfiError::from(status).into());
+        }
+
+        Ok(text.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(()) // Writes go straight to the handle
+    }
+}
+
+impl io::Read for ShellFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.utf8_buf.position() as usize == self.utf8_buf.get_ref().len() {
+            let mut raw = vec![0u8; 0x1000];
+            let start = match self.odd_byte.take() {
+                Some(b) => { raw[0] = b; 1 },
+                None => 0,
+            };
+
+            let mut size = raw.len() - start;
+            let status = unsafe { ((*self.handle).Read)(self.handle, &mut size, raw[start..].as_mut_ptr() as *mut VOID) };
+            if !IsSuccess(status) {
+                return Err(EfiError::from(status).into());
+            }
+
+            if size == 0 {
+                return Ok(0); // End of file. A dangling half character is dropped.
+            }
+
+            let len = start + size;
+            if len % 2 != 0 {
+                self.odd_byte = Some(raw[len - 1]);
+            }
+
+            let text = decode_ucs2(&raw[..len - len % 2], self.at_start);
+            self.at_start = false;
+            self.utf8_buf = Cursor::new(text.into_bytes());
+        }
+
+        self.utf8_buf.read(buf)
+    }
+}
+
+/// The running image's standard input if the shell redirected it to a file
+pub(crate) fn redirected_stdin() -> Option<ShellFile> {
+    ShellParameters::current().ok().map(|p| p.stdin()).and_then(|f| if f.is_file() { Some(f) } else { None })
+}
+
+/// The running image's standard output if the shell redirected it to a file
+pub(crate) fn redirected_stdout() -> Option<ShellFile> {
+    ShellParameters::current().ok().map(|p| p.stdout()).and_then(|f| if f.is_file() { Some(f) } else { None })
+}
+
+/// The running image's standard error if the shell redirected it to a file
+pub(crate) fn redirected_stderr() -> Option<ShellFile> {
+    ShellParameters::current().ok().map(|p| p.stderr()).and_then(|f| if f.is_file() { Some(f) } else { None })
+}
+
+// UCS-2 little endian with LFs turned into CRLFs as the shell itself writes text
+fn encode_ucs2(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len() * 2);
+    let mut last = 0;
+    for c in text.encode_utf16() {
+        if c == '\n' as u16 && last != '\r' as u16 {
+            bytes.extend_from_slice(&['\r' as u8, 0]);
+        }
+        bytes.extend_from_slice(&[c as u8, (c >> 8) as u8]);
+        last = c;
+    }
+    bytes
+}
+
+// Files the shell creates start with a byte order mark, which is dropped
+fn decode_ucs2(bytes: &[u8], at_start: bool) -> String {
+    let mut chars = bytes.chunks(2).map(|c| LittleEndian::read_u16(c)).collect::<Vec<u16>>();
+    if at_start && chars.first() == Some(&0xFEFF) {
+        chars.remove(0);
+    }
+    String::from_utf16_lossy(&chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mappings_get_a_single_colon() {
+        assert_eq!(mapping_name("fs0"), "fs0:");
+        assert_eq!(mapping_name("blk2:"), "blk2:");
+        assert_eq!(env_entry("path", "fs0:\\efi"), "path=fs0:\\efi");
+    }
+
+    #[test]
+    fn env_names_are_split() {
+        let names = "path\0cwd\0\0".encode_utf16().collect::<Vec<_>>();
+        let slice = unsafe { multi_string_as_slice(names.as_ptr()) };
+        assert_eq!(slice.len(), names.len() - 1);
+        assert_eq!(split_multi_string(slice), vec![String::from("path"), String::from("cwd")]);
+        assert_eq!(split_multi_string(&[0, 0]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn streams_are_ucs2() {
+        assert_eq!(encode_ucs2("a\nb\r\n"), vec![b'a', 0, b'\r', 0, b'\n', 0, b'b', 0, b'\r', 0, b'\n', 0]);
+        assert_eq!(decode_ucs2(&[0xFF, 0xFE, b'h', 0, b'i', 0], true), "hi");
+        assert_eq!(decode_ucs2(&[0xFF, 0xFE, b'h', 0], false), "\u{FEFF}h");
+    }
+}
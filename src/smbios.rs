@@ -0,0 +1,591 @@
+use config_table::{find_config_table, SMBIOS_TABLE_GUID, SMBIOS3_TABLE_GUID};
+use acpi::checksum_valid;
+use utils::guid_from_bytes;
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::{String, Vec};
+use core::{slice, str};
+use {Result, EfiErrorKind, Guid};
+
+// SMBIOS tables as published by the firmware. The entry point (the 32 bit "_SM_" one or the 64 bit
+// "_SM3_" one) comes from the configuration table and points to a packed run of structures. Each
+// structure has a formatted area that starts with a type, length and handle and is followed by a
+// set of NUL terminated strings that the formatted area refers to by 1-based index.
+
+pub const TYPE_BIOS: u8 = 0;
+pub const TYPE_SYSTEM: u8 = 1;
+pub const TYPE_BASEBOARD: u8 = 2;
+pub const TYPE_CHASSIS: u8 = 3;
+pub const TYPE_PROCESSOR: u8 = 4;
+pub const TYPE_MEMORY_DEVICE: u8 = 17;
+pub const TYPE_END_OF_TABLE: u8 = 127;
+
+/// The parts of an SMBIOS entry point needed to find the structure table
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+pub struct EntryPoint {
+    pub major_version: u8,
+    pub minor_version: u8,
+    pub table_address: u64,
+    /// The exact table length for 2.x entry points, the maximum for 3.x ones
+    pub table_length: usize,
+}
+
+impl EntryPoint {
+    /// Parses a "_SM_" or "_SM3_" entry point, checking its checksums
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() >= 24 && &bytes[..5] == b"_SM3_" {
+            let length = bytes[6] as usize;
+            if length < 24 || length > bytes.len() {
+                return Err(EfiErrorKind::BadBufferSize.into());
+            }
+
+            if !checksum_valid(&bytes[..length]) {
+                return Err(EfiErrorKind::CrcError.into());
+            }
+
+            Ok(Self {
+                major_version: bytes[7],
+                minor_version: bytes[8],
+                table_address: LittleEndian::read_u64(&bytes[16..24]),
+                table_length: LittleEndian::read_u32(&bytes[12..16]) as usize,
+            })
+        } else if bytes.len() >= 31 && &bytes[..4] == b"_SM_" {
+            let length = bytes[5] as usize;
+            if length < 31 || length > bytes.len() {
+                return Err(EfiErrorKind::BadBufferSize.into());
+            }
+
+            if !checksum_valid(&bytes[..length]) || &bytes[16..21] != b"_DMI_" || !checksum_valid(&bytes[16..31]) {
+                return Err(EfiErrorKind::CrcError.into());
+            }
+
+            Ok(Self {
+                major_version: bytes[6],
+                minor_version: bytes[7],
+                table_address: LittleEndian::read_u32(&bytes[24..28]) as u64,
+                table_length: LittleEndian::read_u16(&bytes[22..24]) as usize,
+            })
+        } else {
+            Err(EfiErrorKind::NotFound.into())
+        }
+    }
+}
+
+/// One structure from the table
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+pub struct Structure<'a> {
+    pub kind: u8,
+    pub handle: u16,
+    /// The formatted area including the four byte header
+    pub formatted: &'a [u8],
+    #[cfg_attr(feature = "with-serde", serde(skip))]
+    strings: &'a [u8],
+}
+
+impl<'a> Structure<'a> {
+    /// The string with the given 1-based index; 0 means no string
+    pub fn string(&self, index: u8) -> Option<&'a str> {
+        if index == 0 {
+            return None;
+        }
+
+        self.strings().nth(index as usize - 1)
+    }
+
+    /// All the strings after the formatted area
+    pub fn strings(&self) -> impl Iterator<Item = &'a str> {
+        self.strings.split(|b| *b == 0)
+            .take_while(|s| !s.is_empty())
+            .map(|s| str::from_utf8(s).unwrap_or("?"))
+    }
+
+    fn byte(&self, offset: usize) -> Option<u8> {
+        self.formatted.get(offset).cloned()
+    }
+
+    fn word(&self, offset: usize) -> Option<u16> {
+        self.formatted.get(offset..offset + 2).map(LittleEndian::read_u16)
+    }
+
+    fn dword(&self, offset: usize) -> Option<u32> {
+        self.formatted.get(offset..offset + 4).map(LittleEndian::read_u32)
+    }
+
+    fn qword(&self, offset: usize) -> Option<u64> {
+        self.formatted.get(offset..offset + 8).map(LittleEndian::read_u64)
+    }
+
+    // The string referred to by the byte at the given offset, trimmed and with empty ones treated as absent
+    fn string_at(&self, offset: usize) -> Option<String> {
+        self.byte(offset)
+            .and_then(|index| self.string(index))
+            .map(|s| s.trim())
+            .and_then(|s| if s.is_empty() { None } else { Some(s.into()) })
+    }
+}
+
+/// Iterates over the structures in a table, stopping at the end-of-table structure or the first
+/// malformed one
+pub struct Structures<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Structures<'a> {
+    type Item = Structure<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() < 4 {
+            return None;
+        }
+
+        let length = self.data[1] as usize;
+        if length < 4 || length > self.data.len() {
+            self.data = &[];
+            return None;
+        }
+
+        // The strings end with a double NUL; a structure without strings has just the two NULs
+        let rest = &self.data[length..];
+        let strings_end = match rest.windows(2).position(|w| w == [0, 0]) {
+            Some(end) => end + 2,
+            None => {
+                self.data = &[];
+                return None;
+            }
+        };
+
+        let structure = Structure {
+            kind: self.data[0],
+            handle: LittleEndian::read_u16(&self.data[2..4]),
+            formatted: &self.data[..length],
+            strings: &rest[..strings_end],
+        };
+
+        self.data = if structure.kind == TYPE_END_OF_TABLE { &[] } else { &rest[strings_end..] };
+        Some(structure)
+    }
+}
+
+/// Iterates over the structures in a raw structure table
+pub fn structures(table: &[u8]) -> Structures {
+    Structures { data: table }
+}
+
+/// BIOS information (type 0)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+pub struct BiosInfo {
+    pub vendor: Option<String>,
+    pub version: Option<String>,
+    pub release_date: Option<String>,
+    /// The system BIOS release as major.minor, from SMBIOS 2.4 on
+    pub release: Option<(u8, u8)>,
+}
+
+impl BiosInfo {
+    pub fn parse(s: &Structure) -> Self {
+        Self {
+            vendor: s.string_at(0x04),
+            version: s.string_at(0x05),
+            release_date: s.string_at(0x08),
+            release: match (s.byte(0x14), s.byte(0x15)) {
+                (Some(0xFF), Some(0xFF)) | (None, _) | (_, None) => None,
+                (Some(major), Some(minor)) => Some((major, minor)),
+            },
+        }
+    }
+}
+
+/// System information (type 1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+pub struct SystemInfo {
+    pub manufacturer: Option<String>,
+    pub product_name: Option<String>,
+    pub version: Option<String>,
+    pub serial_number: Option<String>,
+    /// None if the firmware reports it as not present or not set
+    pub uuid: Option<Guid>,
+    pub sku_number: Option<String>,
+    pub family: Option<String>,
+}
+
+impl SystemInfo {
+    pub fn parse(s: &Structure) -> Self {
+        // Since SMBIOS 2.6 the first three fields of the UUID are little endian, the same as an EFI_GUID
+        let uuid = s.formatted.get(0x08..0x18)
+            .and_then(|uuid| if uuid_is_set(uuid) { Some(guid_from_bytes(uuid)) } else { None });
+
+        Self {
+            manufacturer: s.string_at(0x04),
+            product_name: s.string_at(0x05),
+            version: s.string_at(0x06),
+            serial_number: s.string_at(0x07),
+            uuid,
+            sku_number: s.string_at(0x19),
+            family: s.string_at(0x1A),
+        }
+    }
+}
+
+// All zeros means the UUID isn't present, all ones that it's present but not set
+fn uuid_is_set(uuid: &[u8]) -> bool {
+    !uuid.iter().all(|b| *b == 0) && !uuid.iter().all(|b| *b == 0xFF)
+}
+
+/// Baseboard information (type 2)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+pub struct BaseboardInfo {
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub version: Option<String>,
+    pub serial_number: Option<String>,
+    pub asset_tag: Option<String>,
+}
+
+impl BaseboardInfo {
+    pub fn parse(s: &Structure) -> Self {
+        Self {
+            manufacturer: s.string_at(0x04),
+            product: s.string_at(0x05),
+            version: s.string_at(0x06),
+            serial_number: s.string_at(0x07),
+            asset_tag: s.string_at(0x08),
+        }
+    }
+}
+
+/// System enclosure or chassis (type 3)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+pub struct ChassisInfo {
+    pub manufacturer: Option<String>,
+    /// E.g. 3 for desktop, 10 for notebook, 23 for rack mount chassis
+    pub chassis_type: u8,
+    pub locked: bool,
+    pub version: Option<String>,
+    pub serial_number: Option<String>,
+    pub asset_tag: Option<String>,
+}
+
+impl ChassisInfo {
+    pub fn parse(s: &Structure) -> Self {
+        let chassis_type = s.byte(0x05).unwrap_or(0);
+        Self {
+            manufacturer: s.string_at(0x04),
+            chassis_type: chassis_type & 0x7F,
+            locked: chassis_type & 0x80 != 0,
+            version: s.string_at(0x06),
+            serial_number: s.string_at(0x07),
+            asset_tag: s.string_at(0x08),
+        }
+    }
+}
+
+/// Processor information (type 4)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+pub struct ProcessorInfo {
+    pub socket: Option<String>,
+    pub processor_type: u8,
+    /// The processor family, taken from the 2.6+ extended field when the original one says so
+    pub family: u16,
+    pub manufacturer: Option<String>,
+    /// The raw processor ID, on x86 the CPUID leaf 1 EAX and EDX values
+    pub id: u64,
+    pub version: Option<String>,
+    pub max_speed_mhz: u16,
+    pub current_speed_mhz: u16,
+    pub populated: bool,
+    pub serial_number: Option<String>,
+    pub asset_tag: Option<String>,
+    pub part_number: Option<String>,
+    pub core_count: Option<u16>,
+    pub cores_enabled: Option<u16>,
+    pub thread_count: Option<u16>,
+}
+
+impl ProcessorInfo {
+    pub fn parse(s: &Structure) -> Self {
+        let family = match s.byte(0x06) {
+            Some(0xFE) => s.word(0x28).unwrap_or(0xFE),
+            family => family.unwrap_or(0) as u16,
+        };
+
+        // The 8 bit counts say 0xFF when the real count is in the 3.0+ 16 bit fields
+        let count = |offset, offset2| match s.byte(offset) {
+            Some(0) | None => None,
+            Some(0xFF) => s.word(offset2).or(Some(0xFF)),
+            Some(count) => Some(count as u16),
+        };
+
+        Self {
+            socket: s.string_at(0x04),
+            processor_type: s.byte(0x05).unwrap_or(0),
+            family,
+            manufacturer: s.string_at(0x07),
+            id: s.qword(0x08).unwrap_or(0),
+            version: s.string_at(0x10),
+            max_speed_mhz: s.word(0x14).unwrap_or(0),
+            current_speed_mhz: s.word(0x16).unwrap_or(0),
+            populated: s.byte(0x18).map_or(false, |status| status & 0x40 != 0),
+            serial_number: s.string_at(0x20),
+            asset_tag: s.string_at(0x21),
+            part_number: s.string_at(0x22),
+            core_count: count(0x23, 0x2A),
+            cores_enabled: count(0x24, 0x2C),
+            thread_count: count(0x25, 0x2E),
+        }
+    }
+}
+
+/// A memory device, i.e. a DIMM slot (type 17)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+pub struct MemoryDevice {
+    /// The installed size in bytes; None if the slot is empty or the size is unknown
+    pub size: Option<u64>,
+    pub form_factor: u8,
+    pub device_locator: Option<String>,
+    pub bank_locator: Option<String>,
+    pub memory_type: u8,
+    /// The maximum speed in MT/s, 0 if unknown
+    pub speed: u16,
+    pub manufacturer: Option<String>,
+    pub serial_number: Option<String>,
+    pub asset_tag: Option<String>,
+    pub part_number: Option<String>,
+    /// The configured speed in MT/s, 0 if unknown
+    pub configured_speed: u16,
+}
+
+impl MemoryDevice {
+    pub fn parse(s: &Structure) -> Self {
+        Self {
+            size: memory_size(s.word(0x0C).unwrap_or(0), s.dword(0x1C)),
+            form_factor: s.byte(0x0E).unwrap_or(0),
+            device_locator: s.string_at(0x10),
+            bank_locator: s.string_at(0x11),
+            memory_type: s.byte(0x12).unwrap_or(0),
+            speed: s.word(0x15).unwrap_or(0),
+            manufacturer: s.string_at(0x17),
+            serial_number: s.string_at(0x18),
+            asset_tag: s.string_at(0x19),
+            part_number: s.string_at(0x1A),
+            configured_speed: s.word(0x20).unwrap_or(0),
+        }
+    }
+
+    pub fn is_populated(&self) -> bool {
+        self.size.is_some()
+    }
+}
+
+// The size field is in MB, or KB if the top bit is set. 0x7FFF means the size is in the extended
+// size field (MB), 0 means no device and 0xFFFF means unknown.
+fn memory_size(size: u16, extended: Option<u32>) -> Option<u64> {
+    const MB: u64 = 1024 * 1024;
+    match size {
+        0 | 0xFFFF => None,
+        0x7FFF => extended.map(|mb| (mb & 0x7FFF_FFFF) as u64 * MB),
+        size if size & 0x8000 != 0 => Some((size & 0x7FFF) as u64 * 1024),
+        size => Some(size as u64 * MB),
+    }
+}
+
+/// The SMBIOS tables the firmware has published
+#[derive(Debug, Copy, Clone)]
+pub struct Smbios {
+    entry_point: EntryPoint,
+    table: &'static [u8],
+}
+
+impl Smbios {
+    /// Locates the entry point through the configuration table, preferring the SMBIOS 3 one
+    pub fn new() -> Result<Self> {
+        let entry_point = find_config_table::<u8>(&SMBIOS3_TABLE_GUID)
+            .or_else(|| find_config_table::<u8>(&SMBIOS_TABLE_GUID))
+            .ok_or(EfiErrorKind::NotFound)?;
+        unsafe { Self::from_entry_point(entry_point as u64) }
+    }
+
+    /// Reads the table through an entry point at the given physical address
+    ///
+    /// # Safety
+    /// The address must point to a readable entry point whose table stays in place for the rest of the program
+    pub unsafe fn from_entry_point(address: u64) -> Result<Self> {
+        let head = slice::from_raw_parts(address as *const u8, 7);
+        let length = if &head[..5] == b"_SM3_" { head[6] } else { head[5] };
+        let entry_point = EntryPoint::parse(slice::from_raw_parts(address as *const u8, length as usize))?;
+        if entry_point.table_address == 0 {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        let table = slice::from_raw_parts(entry_point.table_address as *const u8, entry_point.table_length);
+        Ok(Self { entry_point, table })
+    }
+
+    pub fn entry_point(&self) -> &EntryPoint {
+        &self.entry_point
+    }
+
+    /// The raw structure table
+    pub fn table(&self) -> &'static [u8] {
+        self.table
+    }
+
+    pub fn structures(&self) -> Structures<'static> {
+        structures(self.table)
+    }
+
+    /// The structures of the given type
+    pub fn structures_of_type(&self, kind: u8) -> Vec<Structure<'static>> {
+        self.structures().filter(|s| s.kind == kind).collect()
+    }
+
+    pub fn bios(&self) -> Option<BiosInfo> {
+        self.first(TYPE_BIOS).map(|s| BiosInfo::parse(&s))
+    }
+
+    pub fn system(&self) -> Option<SystemInfo> {
+        self.first(TYPE_SYSTEM).map(|s| SystemInfo::parse(&s))
+    }
+
+    pub fn baseboard(&self) -> Option<BaseboardInfo> {
+        self.first(TYPE_BASEBOARD).map(|s| BaseboardInfo::parse(&s))
+    }
+
+    pub fn chassis(&self) -> Option<ChassisInfo> {
+        self.first(TYPE_CHASSIS).map(|s| ChassisInfo::parse(&s))
+    }
+
+    pub fn processors(&self) -> Vec<ProcessorInfo> {
+        self.structures_of_type(TYPE_PROCESSOR).iter().map(ProcessorInfo::parse).collect()
+    }
+
+    /// All memory device slots, populated or not
+    pub fn memory_devices(&self) -> Vec<MemoryDevice> {
+        self.structures_of_type(TYPE_MEMORY_DEVICE).iter().map(MemoryDevice::parse).collect()
+    }
+
+    fn first(&self, kind: u8) -> Option<Structure<'static>> {
+        self.structures().find(|s| s.kind == kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::guid_to_bytes;
+
+    fn structure(kind: u8, handle: u16, formatted: &[u8], strings: &[&str]) -> Vec<u8> {
+        let mut bytes = vec![kind, (formatted.len() + 4) as u8, handle as u8, (handle >> 8) as u8];
+        bytes.extend_from_slice(formatted);
+        for s in strings {
+            bytes.extend_from_slice(s.as_bytes());
+            bytes.push(0);
+        }
+        if strings.is_empty() {
+            bytes.push(0);
+        }
+        bytes.push(0);
+        bytes
+    }
+
+    fn system() -> Vec<u8> {
+        let mut formatted = vec![1, 2, 0, 3];
+        formatted.extend_from_slice(&[0x78, 0x56, 0x34, 0x12, 0x34, 0x12, 0x78, 0x56, 1, 2, 3, 4, 5, 6, 7, 8]);
+        formatted.extend_from_slice(&[6, 0, 4]);
+        structure(TYPE_SYSTEM, 1, &formatted, &["QEMU", "Standard PC", "  SN123  ", "Virtual"])
+    }
+
+    #[test]
+    fn structures_and_strings_are_walked() {
+        let mut table = structure(TYPE_BIOS, 0, &[1, 2, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 7], &["EDK II", "1.0", "01/01/2024"]);
+        table.extend(system());
+        table.extend(structure(TYPE_END_OF_TABLE, 0xFEFF, &[], &[]));
+        table.extend(structure(TYPE_BASEBOARD, 2, &[], &[]));
+
+        let all = structures(&table).collect::<Vec<_>>();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[1].handle, 1);
+        assert_eq!(all[2].kind, TYPE_END_OF_TABLE);
+        assert_eq!(all[1].strings().collect::<Vec<_>>(), vec!["QEMU", "Standard PC", "  SN123  ", "Virtual"]);
+        assert_eq!(all[1].string(0), None);
+        assert_eq!(all[1].string(5), None);
+
+        let bios = BiosInfo::parse(&all[0]);
+        assert_eq!(bios.vendor, Some("EDK II".into()));
+        assert_eq!(bios.release_date, Some("01/01/2024".into()));
+        assert_eq!(bios.release, Some((2, 7)));
+
+        let system = SystemInfo::parse(&all[1]);
+        assert_eq!(system.manufacturer, Some("QEMU".into()));
+        assert_eq!(system.version, None);
+        assert_eq!(system.serial_number, Some("SN123".into()));
+        assert_eq!(system.family, Some("Virtual".into()));
+        assert_eq!(system.sku_number, None);
+        let uuid = system.uuid.unwrap();
+        assert_eq!((uuid.0, uuid.1, uuid.2), (0x12345678, 0x1234, 0x5678));
+        assert_eq!(guid_to_bytes(&uuid)[8..], [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn truncated_tables_stop_iteration() {
+        let table = system();
+        assert_eq!(structures(&table[..table.len() - 1]).count(), 0);
+        assert_eq!(structures(&[1, 2, 0, 0]).count(), 0);
+    }
+
+    #[test]
+    fn processor_counts_use_the_wide_fields() {
+        let mut formatted = vec![0u8; 0x30 - 4];
+        formatted[0x06 - 4] = 0xFE;
+        formatted[0x28 - 4] = 0x01;
+        formatted[0x28 - 4 + 1] = 0x01;
+        formatted[0x14 - 4] = 0xB8;
+        formatted[0x14 - 4 + 1] = 0x0B;
+        formatted[0x18 - 4] = 0x41;
+        formatted[0x23 - 4] = 0xFF;
+        formatted[0x24 - 4] = 8;
+        formatted[0x2A - 4] = 0x00;
+        formatted[0x2A - 4 + 1] = 0x01;
+        let s = structure(TYPE_PROCESSOR, 4, &formatted, &[]);
+        let cpu = ProcessorInfo::parse(&structures(&s).next().unwrap());
+        assert_eq!(cpu.family, 0x101);
+        assert_eq!(cpu.max_speed_mhz, 3000);
+        assert!(cpu.populated);
+        assert_eq!(cpu.core_count, Some(256));
+        assert_eq!(cpu.cores_enabled, Some(8));
+        assert_eq!(cpu.thread_count, None);
+    }
+
+    #[test]
+    fn memory_sizes_are_decoded() {
+        assert_eq!(memory_size(0, None), None);
+        assert_eq!(memory_size(0xFFFF, None), None);
+        assert_eq!(memory_size(8192, None), Some(8 << 30));
+        assert_eq!(memory_size(0x8000 | 512, None), Some(512 << 10));
+        assert_eq!(memory_size(0x7FFF, Some(65536)), Some(64 << 30));
+    }
+
+    #[test]
+    fn entry_points_are_checked() {
+        let mut sm3 = vec![0u8; 24];
+        sm3[..5].copy_from_slice(b"_SM3_");
+        sm3[6] = 24;
+        sm3[7] = 3;
+        sm3[8] = 2;
+        LittleEndian::write_u32(&mut sm3[12..16], 0x1000);
+        LittleEndian::write_u64(&mut sm3[16..24], 0x7f00_0000);
+        let sum = sm3.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        sm3[5] = 0u8.wrapping_sub(sum);
+        assert_eq!(EntryPoint::parse(&sm3).unwrap(), EntryPoint { major_version: 3, minor_version: 2, table_address: 0x7f00_0000, table_length: 0x1000 });
+
+        sm3[20] = 1;
+        assert_eq!(EntryPoint::parse(&sm3).unwrap_err().kind(), EfiErrorKind::CrcError);
+        assert_eq!(EntryPoint::parse(b"_XX_").unwrap_err().kind(), EfiErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,619 @@
+use ffi::{
+    ata::{
+        EFI_ATA_PASS_THRU_PROTOCOL,
+        EFI_ATA_PASS_THRU_PROTOCOL_GUID,
+        EFI_ATA_PASS_THRU_COMMAND_PACKET,
+        EFI_ATA_COMMAND_BLOCK,
+        EFI_ATA_STATUS_BLOCK,
+        EFI_ATA_PASS_THRU_CMD_PROTOCOL,
+        EFI_ATA_PASS_THRU_PROTOCOL_ATA_NON_DATA,
+        EFI_ATA_PASS_THRU_PROTOCOL_PIO_DATA_IN,
+        EFI_ATA_PASS_THRU_PROTOCOL_PIO_DATA_OUT,
+        EFI_ATA_PASS_THRU_PROTOCOL_DMA,
+        EFI_ATA_PASS_THRU_PROTOCOL_UDMA_DATA_IN,
+        EFI_ATA_PASS_THRU_PROTOCOL_UDMA_DATA_OUT,
+        EFI_ATA_PASS_THRU_LENGTH_BYTES,
+        EFI_ATA_PASS_THRU_LENGTH_SECTOR_COUNT,
+        EFI_ATA_PASS_THRU_LENGTH_NO_DATA_TRANSFER,
+    },
+    device_path::EFI_DEVICE_PATH_PROTOCOL,
+    EFI_HANDLE,
+    EFI_SUCCESS,
+    EFI_NOT_FOUND,
+    EFI_DEVICE_ERROR,
+    IsSuccess,
+    VOID,
+};
+use super::{is_aligned, aligned_bounce_buffer, ascii_field};
+use device_path::DevicePath;
+use events::as_100ns_units;
+use byteorder::{ByteOrder, LittleEndian};
+use utils::{handles_by_protocol, open_protocol};
+use alloc::{Vec, String};
+use core::{ptr, mem, cmp, time::Duration};
+use {Result, EfiErrorKind};
+
+const ATA_SECTOR_SIZE: usize = 512;
+const ATA_DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const ATA_CMD_IDENTIFY_DEVICE: u8 = 0xEC;
+const ATA_CMD_SMART: u8 = 0xB0;
+const SMART_READ_DATA: u8 = 0xD0;
+const SMART_RETURN_STATUS: u8 = 0xDA;
+
+// SMART commands must carry these in LBA mid/high. RETURN STATUS gives them back unchanged if the disk is healthy
+// and as 0xF4/0x2C if some attribute has crossed its threshold.
+const SMART_LBA_MID: u8 = 0x4F;
+const SMART_LBA_HIGH: u8 = 0xC2;
+const SMART_THRESHOLD_EXCEEDED_LBA_MID: u8 = 0xF4;
+const SMART_THRESHOLD_EXCEEDED_LBA_HIGH: u8 = 0x2C;
+
+const SMART_ATTRIBUTE_COUNT: usize = 30;
+const SMART_ATTRIBUTE_SIZE: usize = 12;
+
+pub const SMART_ATTRIBUTE_REALLOCATED_SECTORS: u8 = 5;
+pub const SMART_ATTRIBUTE_POWER_ON_HOURS: u8 = 9;
+pub const SMART_ATTRIBUTE_AIRFLOW_TEMPERATURE: u8 = 190;
+pub const SMART_ATTRIBUTE_TEMPERATURE: u8 = 194;
+pub const SMART_ATTRIBUTE_PENDING_SECTORS: u8 = 197;
+pub const SMART_ATTRIBUTE_UNCORRECTABLE_SECTORS: u8 = 198;
+
+pub const ATA_STATUS_ERR: u8 = 0x01;
+pub const ATA_STATUS_DRQ: u8 = 0x08;
+pub const ATA_STATUS_DF: u8 = 0x20;
+pub const ATA_STATUS_DRDY: u8 = 0x40;
+pub const ATA_STATUS_BSY: u8 = 0x80;
+
+pub const ATA_ERROR_ABRT: u8 = 0x04;
+
+/// A device on an ATA controller
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AtaDevice {
+    pub port: u16,
+    /// 0xFFFF if the device is attached directly to the port
+    pub port_multiplier_port: u16,
+}
+
+/// The ATA protocol used to carry out a command
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AtaProtocol {
+    NonData,
+    PioDataIn,
+    PioDataOut,
+    Dma,
+    UdmaDataIn,
+    UdmaDataOut,
+}
+
+impl AtaProtocol {
+    fn as_raw(&self) -> EFI_ATA_PASS_THRU_CMD_PROTOCOL {
+        match *self {
+            AtaProtocol::NonData => EFI_ATA_PASS_THRU_PROTOCOL_ATA_NON_DATA,
+            AtaProtocol::PioDataIn => EFI_ATA_PASS_THRU_PROTOCOL_PIO_DATA_IN,
+            AtaProtocol::PioDataOut => EFI_ATA_PASS_THRU_PROTOCOL_PIO_DATA_OUT,
+            AtaProtocol::Dma => EFI_ATA_PASS_THRU_PROTOCOL_DMA,
+            AtaProtocol::UdmaDataIn => EFI_ATA_PASS_THRU_PROTOCOL_UDMA_DATA_IN,
+            AtaProtocol::UdmaDataOut => EFI_ATA_PASS_THRU_PROTOCOL_UDMA_DATA_OUT,
+        }
+    }
+}
+
+/// The task file of an ATA command. Constructors are provided for the commands this module uses.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AtaCommand {
+    pub command: u8,
+    pub features: u16,
+    /// 48-bit LBA. Commands that aren't 48-bit only look at the low 24 bits.
+    pub lba: u64,
+    pub sector_count: u16,
+    pub device: u8,
+    pub protocol: AtaProtocol,
+}
+
+impl AtaCommand {
+    pub fn identify_device() -> Self {
+        AtaCommand { command: ATA_CMD_IDENTIFY_DEVICE, features: 0, lba: 0, sector_count: 1, device: 0, protocol: AtaProtocol::PioDataIn }
+    }
+
+    pub fn smart_read_data() -> Self {
+        Self::smart(SMART_READ_DATA, AtaProtocol::PioDataIn)
+    }
+
+    pub fn smart_return_status() -> Self {
+        Self::smart(SMART_RETURN_STATUS, AtaProtocol::NonData)
+    }
+
+    fn smart(feature: u8, protocol: AtaProtocol) -> Self {
+        let sector_count = if protocol == AtaProtocol::NonData { 0 } else { 1 };
+        let lba = (SMART_LBA_HIGH as u64) << 16 | (SMART_LBA_MID as u64) << 8;
+        AtaCommand { command: ATA_CMD_SMART, features: feature as u16, lba, sector_count, device: 0, protocol }
+    }
+}
+
+/// The data phase of an ATA command
+pub enum AtaData<'a> {
+    None,
+    /// Data from the device to the host
+    In(&'a mut [u8]),
+    /// Data from the host to the device
+    Out(&'a [u8]),
+}
+
+/// The registers returned by the device on completion of a command
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct AtaRegisters {
+    /// Combination of the `ATA_STATUS_*` bits
+    pub status: u8,
+    /// Combination of the `ATA_ERROR_*` bits. Only meaningful if `status` has `ATA_STATUS_ERR` set.
+    pub error: u8,
+    pub lba: u64,
+    pub sector_count: u16,
+    pub device: u8,
+}
+
+impl AtaRegisters {
+    fn from_status_block(asb: &EFI_ATA_STATUS_BLOCK) -> Self {
+        let lba = asb.AtaSectorNumber as u64
+            | (asb.AtaCylinderLow as u64) << 8
+            | (asb.AtaCylinderHigh as u64) << 16
+            | (asb.AtaSectorNumberExp as u64) << 24
+            | (asb.AtaCylinderLowExp as u64) << 32
+            | (asb.AtaCylinderHighExp as u64) << 40;
+        AtaRegisters {
+            status: asb.AtaStatus,
+            error: asb.AtaError,
+            lba,
+            sector_count: asb.AtaSectorCount as u16 | (asb.AtaSectorCountExp as u16) << 8,
+            device: asb.AtaDeviceHead,
+        }
+    }
+
+    /// Whether the device reported that the command failed
+    pub fn is_error(&self) -> bool {
+        self.status & (ATA_STATUS_ERR | ATA_STATUS_DF) != 0
+    }
+
+    fn error_kind(&self) -> EfiErrorKind {
+        // Devices abort commands they don't implement or have disabled (e.g. SMART being turned off)
+        if self.status & ATA_STATUS_ERR != 0 && self.error & ATA_ERROR_ABRT != 0 {
+            EfiErrorKind::Unsupported
+        } else {
+            EfiErrorKind::DeviceError
+        }
+    }
+}
+
+/// The parts of the IDENTIFY DEVICE data useful for picking and diagnosing disks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentifyDevice {
+    pub serial_number: String,
+    pub firmware_revision: String,
+    pub model_number: String,
+    pub supports_lba48: bool,
+    /// Number of addressable logical sectors
+    pub sectors: u64,
+    pub logical_sector_size: u32,
+    pub physical_sector_size: u32,
+    /// True for SSDs
+    pub non_rotational: bool,
+    pub smart_supported: bool,
+    pub smart_enabled: bool,
+}
+
+impl IdentifyDevice {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < ATA_SECTOR_SIZE {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        let word = |n: usize| LittleEndian::read_u16(&bytes[n * 2..n * 2 + 2]);
+
+        let supports_lba48 = word(83) & (1 << 10) != 0;
+        let sectors = if supports_lba48 {
+            LittleEndian::read_u64(&bytes[200..208])
+        } else {
+            LittleEndian::read_u32(&bytes[120..124]) as u64
+        };
+
+        // Word 106 is only valid if bit 14 is set and bit 15 clear
+        let sector_size_info = word(106);
+        let sector_size_valid = sector_size_info & 0xC000 == 0x4000;
+        let logical_sector_size = if sector_size_valid && sector_size_info & (1 << 12) != 0 {
+            LittleEndian::read_u32(&bytes[234..238]) * 2 // Words 117-118 give the size in words
+        } else {
+            ATA_SECTOR_SIZE as u32
+        };
+        let physical_sector_size = if sector_size_valid && sector_size_info & (1 << 13) != 0 {
+            logical_sector_size << (sector_size_info & 0xF)
+        } else {
+            logical_sector_size
+        };
+
+        Ok(IdentifyDevice {
+            serial_number: ata_string(&bytes[20..40]),
+            firmware_revision: ata_string(&bytes[46..54]),
+            model_number: ata_string(&bytes[54..94]),
+            supports_lba48,
+            sectors,
+            logical_sector_size,
+            physical_sector_size,
+            non_rotational: word(217) == 1,
+            smart_supported: word(82) & 1 != 0,
+            smart_enabled: word(85) & 1 != 0,
+        })
+    }
+
+    /// The capacity of the device in bytes
+    pub fn capacity(&self) -> u64 {
+        self.sectors * self.logical_sector_size as u64
+    }
+}
+
+/// An entry of the SMART attribute table. The meaning of the values is vendor specific but the
+/// `SMART_ATTRIBUTE_*` ones are widely agreed upon.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SmartAttribute {
+    pub id: u8,
+    pub flags: u16,
+    /// Normalized value. Lower is worse.
+    pub current: u8,
+    /// Lowest normalized value seen so far
+    pub worst: u8,
+    /// 48-bit raw value
+    pub raw: u64,
+}
+
+/// A summary of the health of a disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmartHealth {
+    /// False if the disk reports that some attribute has crossed its failure threshold
+    pub passed: bool,
+    /// In degrees Celsius
+    pub temperature: Option<u8>,
+    pub reallocated_sectors: Option<u64>,
+    pub pending_sectors: Option<u64>,
+    pub power_on_hours: Option<u64>,
+    pub attributes: Vec<SmartAttribute>,
+}
+
+impl SmartHealth {
+    fn new(passed: bool, attributes: Vec<SmartAttribute>) -> Self {
+        let (temperature, reallocated_sectors, pending_sectors, power_on_hours) = {
+            let raw = |id| attributes.iter().find(|a| a.id == id).map(|a| a.raw);
+            (
+                raw(SMART_ATTRIBUTE_TEMPERATURE)
+                    .or_else(|| raw(SMART_ATTRIBUTE_AIRFLOW_TEMPERATURE))
+                    .map(|r| r as u8), // Only the lowest byte is the current temperature
+                raw(SMART_ATTRIBUTE_REALLOCATED_SECTORS),
+                raw(SMART_ATTRIBUTE_PENDING_SECTORS),
+                raw(SMART_ATTRIBUTE_POWER_ON_HOURS).map(|r| r & 0xFFFF_FFFF), // Some vendors keep minutes in the top bytes
+            )
+        };
+
+        SmartHealth { passed, temperature, reallocated_sectors, pending_sectors, power_on_hours, attributes }
+    }
+}
+
+// Parses the attribute table out of the data returned by SMART READ DATA
+fn parse_smart_attributes(bytes: &[u8]) -> Result<Vec<SmartAttribute>> {
+    if bytes.len() < ATA_SECTOR_SIZE {
+        return Err(EfiErrorKind::BadBufferSize.into());
+    }
+
+    // The last byte is a checksum that makes all of them add up to 0
+    if bytes[..ATA_SECTOR_SIZE].iter().fold(0_u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+        return Err(EfiErrorKind::CrcError.into());
+    }
+
+    let table = &bytes[2..2 + SMART_ATTRIBUTE_COUNT * SMART_ATTRIBUTE_SIZE];
+    Ok(table.chunks(SMART_ATTRIBUTE_SIZE)
+        .filter(|e| e[0] != 0)
+        .map(|e| {
+            let mut raw = [0_u8; 8];
+            raw[..6].copy_from_slice(&e[5..11]);
+            SmartAttribute { id: e[0], flags: LittleEndian::read_u16(&e[1..3]), current: e[3], worst: e[4], raw: LittleEndian::read_u64(&raw) }
+        })
+        .collect())
+}
+
+// Interprets the LBA registers returned by SMART RETURN STATUS
+fn smart_status_passed(regs: &AtaRegisters) -> Result<bool> {
+    let mid = (regs.lba >> 8) as u8;
+    let high = (regs.lba >> 16) as u8;
+    match (mid, high) {
+        (SMART_LBA_MID, SMART_LBA_HIGH) => Ok(true),
+        (SMART_THRESHOLD_EXCEEDED_LBA_MID, SMART_THRESHOLD_EXCEEDED_LBA_HIGH) => Ok(false),
+        _ => Err(EfiErrorKind::DeviceError.into())
+    }
+}
+
+// ATA strings store two characters per word with the first one in the high byte
+fn ata_string(bytes: &[u8]) -> String {
+    let mut swapped = Vec::with_capacity(bytes.len());
+    for pair in bytes.chunks(2) {
+        swapped.extend(pair.iter().rev());
+    }
+
+    String::from(ascii_field(&swapped).trim_left())
+}
+
+/// An ATA controller exposed through EFI_ATA_PASS_THRU_PROTOCOL
+pub struct AtaPassthru {
+    handle: EFI_HANDLE,
+    protocol: *const EFI_ATA_PASS_THRU_PROTOCOL,
+    // The data buffer and the command and status blocks all have to meet the controller's alignment requirement
+    bounce: Vec<u8>,
+    acb: Vec<u8>,
+    asb: Vec<u8>,
+    last_registers: Option<AtaRegisters>,
+}
+
+impl AtaPassthru {
+    /// Opens the ATA controller on the given handle
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol::<EFI_ATA_PASS_THRU_PROTOCOL>(handle, &EFI_ATA_PASS_THRU_PROTOCOL_GUID)?;
+
+        Ok(AtaPassthru { handle, protocol, bounce: Vec::new(), acb: Vec::new(), asb: Vec::new(), last_registers: None })
+    }
+
+    /// Opens all ATA controllers in the system
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_ATA_PASS_THRU_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    /// The handle the controller is on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// All devices present on the controller
+    pub fn devices(&self) -> Result<Vec<AtaDevice>> {
+        let mut devices = Vec::new();
+        let mut port = 0xFFFF; // 0xFFFF asks for the first port
+        loop {
+            match unsafe { ((*self.protocol).GetNextPort)(self.protocol, &mut port) } {
+                EFI_SUCCESS => (),
+                EFI_NOT_FOUND => return Ok(devices),
+                s => return Err(s.into())
+            }
+
+            let mut port_multiplier_port = 0xFFFF;
+            loop {
+                match unsafe { ((*self.protocol).GetNextDevice)(self.protocol, port, &mut port_multiplier_port) } {
+                    EFI_SUCCESS => devices.push(AtaDevice { port, port_multiplier_port }),
+                    EFI_NOT_FOUND => break,
+                    s => return Err(s.into())
+                }
+            }
+        }
+    }
+
+    /// The device path of the given device
+    pub fn device_path(&self, device: &AtaDevice) -> Result<DevicePath> {
+        let mut path: *const EFI_DEVICE_PATH_PROTOCOL = ptr::null();
+        unsafe {
+            ret_on_err!(((*self.protocol).BuildDevicePath)(self.protocol, device.port, device.port_multiplier_port, &mut path));
+        }
+
+        DevicePath::from_ptr(path)
+    }
+
+    /// Resets the given device
+    pub fn reset(&self, device: &AtaDevice) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).ResetDevice)(self.protocol, device.port, device.port_multiplier_port));
+        }
+
+        Ok(())
+    }
+
+    /// Sends a command and waits for it to complete. Returns the registers the device completed it with.
+    /// If the device reports an error it's kept for `last_registers()`. A timeout of `None` waits indefinitely.
+    pub fn execute(&mut self, device: &AtaDevice, command: &AtaCommand, mut data: AtaData, timeout: Option<Duration>) -> Result<AtaRegisters> {
+        let io_align = unsafe { (*(*self.protocol).Mode).IoAlign };
+        self.last_registers = None;
+
+        let acb_ptr = aligned_bounce_buffer(&mut self.acb, mem::size_of::<EFI_ATA_COMMAND_BLOCK>(), io_align).as_mut_ptr() as *mut EFI_ATA_COMMAND_BLOCK;
+        let asb_ptr = aligned_bounce_buffer(&mut self.asb, mem::size_of::<EFI_ATA_STATUS_BLOCK>(), io_align).as_mut_ptr() as *mut EFI_ATA_STATUS_BLOCK;
+        unsafe {
+            *acb_ptr = EFI_ATA_COMMAND_BLOCK {
+                AtaCommand: command.command,
+                AtaFeatures: command.features as u8,
+                AtaSectorNumber: command.lba as u8,
+                AtaCylinderLow: (command.lba >> 8) as u8,
+                AtaCylinderHigh: (command.lba >> 16) as u8,
+                AtaDeviceHead: command.device,
+                AtaSectorNumberExp: (command.lba >> 24) as u8,
+                AtaCylinderLowExp: (command.lba >> 32) as u8,
+                AtaCylinderHighExp: (command.lba >> 40) as u8,
+                AtaFeaturesExp: (command.features >> 8) as u8,
+                AtaSectorCount: command.sector_count as u8,
+                AtaSectorCountExp: (command.sector_count >> 8) as u8,
+                ..Default::default()
+            };
+            *asb_ptr = EFI_ATA_STATUS_BLOCK::default();
+        }
+
+        let mut packet = EFI_ATA_PASS_THRU_COMMAND_PACKET {
+            Asb: asb_ptr,
+            Acb: acb_ptr,
+            Timeout: timeout.as_ref().map_or(0, as_100ns_units),
+            InDataBuffer: ptr::null_mut(),
+            OutDataBuffer: ptr::null_mut(),
+            InTransferLength: 0,
+            OutTransferLength: 0,
+            Protocol: command.protocol.as_raw(),
+            Length: EFI_ATA_PASS_THRU_LENGTH_NO_DATA_TRANSFER,
+        };
+
+        let data_len = match data {
+            AtaData::In(ref buf) => buf.len(),
+            AtaData::Out(ref buf) => buf.len(),
+            AtaData::None => 0,
+        };
+        if data_len > u32::max_value() as usize {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        let mut bounced_in = None;
+        match data {
+            AtaData::None => (),
+            AtaData::In(ref mut buf) => {
+                packet.Length = EFI_ATA_PASS_THRU_LENGTH_BYTES | EFI_ATA_PASS_THRU_LENGTH_SECTOR_COUNT;
+                packet.InTransferLength = buf.len() as u32;
+                packet.InDataBuffer = if is_aligned(buf.as_ptr(), io_align) {
+                    buf.as_mut_ptr() as *mut VOID
+                } else {
+                    bounced_in = Some(buf.len());
+                    aligned_bounce_buffer(&mut self.bounce, buf.len(), io_align).as_mut_ptr() as *mut VOID
+                };
+            },
+            AtaData::Out(buf) => {
+                packet.Length = EFI_ATA_PASS_THRU_LENGTH_BYTES | EFI_ATA_PASS_THRU_LENGTH_SECTOR_COUNT;
+                packet.OutTransferLength = buf.len() as u32;
+                packet.OutDataBuffer = if is_aligned(buf.as_ptr(), io_align) {
+                    buf.as_ptr() as *mut VOID
+                } else {
+                    let bounce = aligned_bounce_buffer(&mut self.bounce, buf.len(), io_align);
+                    bounce.copy_from_slice(buf);
+                    bounce.as_mut_ptr() as *mut VOID
+                };
+            },
+        }
+
+        let status = unsafe { ((*self.protocol).PassThru)(self.protocol, device.port, device.port_multiplier_port, &mut packet, ptr::null()) };
+
+        // With a device error the status block tells us what actually went wrong
+        if !IsSuccess(status) && status != EFI_DEVICE_ERROR {
+            return Err(status.into());
+        }
+
+        let registers = AtaRegisters::from_status_block(unsafe { &*asb_ptr });
+        if !IsSuccess(status) || registers.is_error() {
+            self.last_registers = Some(registers);
+            return Err(if registers.is_error() { registers.error_kind() } else { EfiErrorKind::DeviceError }.into());
+        }
+
+        if let AtaData::In(buf) = data {
+            if let Some(len) = bounced_in {
+                let transferred = cmp::min(packet.InTransferLength as usize, len);
+                buf[..transferred].copy_from_slice(&aligned_bounce_buffer(&mut self.bounce, len, io_align)[..transferred]);
+            }
+        }
+
+        Ok(registers)
+    }
+
+    /// The registers of the last command the device failed
+    pub fn last_registers(&self) -> Option<AtaRegisters> {
+        self.last_registers
+    }
+
+    pub fn identify(&mut self, device: &AtaDevice) -> Result<IdentifyDevice> {
+        let mut data = [0_u8; ATA_SECTOR_SIZE];
+        self.execute(device, &AtaCommand::identify_device(), AtaData::In(&mut data), Some(ATA_DEFAULT_TIMEOUT))?;
+        IdentifyDevice::parse(&data)
+    }
+
+    /// The SMART attribute table of the device
+    pub fn smart_attributes(&mut self, device: &AtaDevice) -> Result<Vec<SmartAttribute>> {
+        let mut data = [0_u8; ATA_SECTOR_SIZE];
+        self.execute(device, &AtaCommand::smart_read_data(), AtaData::In(&mut data), Some(ATA_DEFAULT_TIMEOUT))?;
+        parse_smart_attributes(&data)
+    }
+
+    /// The overall SMART status of the device along with the attributes most telling of a failing disk.
+    /// Fails with `Unsupported` if the device doesn't support SMART and `NotStarted` if it's been disabled.
+    pub fn smart_health(&mut self, device: &AtaDevice) -> Result<SmartHealth> {
+        let identify = self.identify(device)?;
+        if !identify.smart_supported {
+            return Err(EfiErrorKind::Unsupported.into());
+        }
+        if !identify.smart_enabled {
+            return Err(EfiErrorKind::NotStarted.into());
+        }
+
+        let registers = self.execute(device, &AtaCommand::smart_return_status(), AtaData::None, Some(ATA_DEFAULT_TIMEOUT))?;
+        let passed = smart_status_passed(&registers)?;
+        let attributes = self.smart_attributes(device)?;
+        Ok(SmartHealth::new(passed, attributes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_ata_string(data: &mut [u8], s: &[u8]) {
+        for (i, c) in s.iter().enumerate() {
+            data[i ^ 1] = *c;
+        }
+    }
+
+    #[test]
+    fn parses_identify_device() {
+        let mut data = [0_u8; 512];
+        put_ata_string(&mut data[20..40], b"        S3Z9NB0K123");
+        put_ata_string(&mut data[46..54], b"RVT04B6Q");
+        put_ata_string(&mut data[54..94], b"Samsung SSD 860 EVO 500GB               ");
+        LittleEndian::write_u16(&mut data[164..166], 1); // Word 82: SMART supported
+        LittleEndian::write_u16(&mut data[166..168], 1 << 10); // Word 83: 48-bit LBA
+        LittleEndian::write_u16(&mut data[170..172], 1); // Word 85: SMART enabled
+        LittleEndian::write_u64(&mut data[200..208], 976773168);
+        LittleEndian::write_u16(&mut data[212..214], 0x6003); // Word 106: 8 logical sectors per physical one
+        LittleEndian::write_u16(&mut data[434..436], 1); // Word 217: non-rotational
+
+        let identify = IdentifyDevice::parse(&data).unwrap();
+        assert_eq!(identify.serial_number, "S3Z9NB0K123");
+        assert_eq!(identify.firmware_revision, "RVT04B6Q");
+        assert_eq!(identify.model_number, "Samsung SSD 860 EVO 500GB");
+        assert!(identify.supports_lba48 && identify.smart_supported && identify.smart_enabled && identify.non_rotational);
+        assert_eq!(identify.sectors, 976773168);
+        assert_eq!(identify.logical_sector_size, 512);
+        assert_eq!(identify.physical_sector_size, 4096);
+        assert_eq!(identify.capacity(), 500107862016);
+        assert!(IdentifyDevice::parse(&data[..256]).is_err());
+    }
+
+    #[test]
+    fn parses_smart_data() {
+        let mut data = [0_u8; 512];
+        let entries: [(u8, u64); 4] = [(5, 8), (9, 0x0012_0000_1234), (194, 0x0028_0014_0025), (197, 1)];
+        for (i, &(id, raw)) in entries.iter().enumerate() {
+            let entry = &mut data[2 + i * 12..2 + (i + 1) * 12];
+            entry[0] = id;
+            entry[3] = 100;
+            entry[4] = 99;
+            let mut raw_bytes = [0_u8; 8];
+            LittleEndian::write_u64(&mut raw_bytes, raw);
+            entry[5..11].copy_from_slice(&raw_bytes[..6]);
+        }
+        assert!(parse_smart_attributes(&data).is_err()); // Checksum's wrong
+
+        let sum = data[..511].iter().fold(0_u8, |sum, b| sum.wrapping_add(*b));
+        data[511] = 0_u8.wrapping_sub(sum);
+        let attributes = parse_smart_attributes(&data).unwrap();
+        assert_eq!(attributes.len(), 4);
+        assert_eq!(attributes[0], SmartAttribute { id: 5, flags: 0, current: 100, worst: 99, raw: 8 });
+
+        let health = SmartHealth::new(true, attributes);
+        assert_eq!(health.temperature, Some(0x25));
+        assert_eq!(health.reallocated_sectors, Some(8));
+        assert_eq!(health.pending_sectors, Some(1));
+        assert_eq!(health.power_on_hours, Some(0x1234));
+    }
+
+    #[test]
+    fn interprets_smart_status() {
+        let ok = AtaRegisters { lba: 0xC24F00, ..Default::default() };
+        let failing = AtaRegisters { lba: 0x2CF400, ..Default::default() };
+        assert_eq!(smart_status_passed(&ok).unwrap(), true);
+        assert_eq!(smart_status_passed(&failing).unwrap(), false);
+        assert!(smart_status_passed(&AtaRegisters::default()).is_err());
+        assert_eq!(AtaCommand::smart_return_status().lba, 0xC24F00);
+
+        let aborted = AtaRegisters { status: ATA_STATUS_DRDY | ATA_STATUS_ERR, error: ATA_ERROR_ABRT, ..Default::default() };
+        assert!(aborted.is_error());
+        assert_eq!(aborted.error_kind(), EfiErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,1362 @@
+use ffi::{
+    media::{
+        EFI_BLOCK_IO_PROTOCOL,
+        EFI_BLOCK_IO_PROTOCOL_GUID,
+        EFI_DISK_IO_PROTOCOL,
+        EFI_DISK_IO_PROTOCOL_GUID,
+        EFI_DISK_IO2_PROTOCOL,
+        EFI_DISK_IO2_PROTOCOL_GUID,
+        EFI_DISK_IO2_TOKEN,
+        EFI_ERASE_BLOCK_PROTOCOL,
+        EFI_ERASE_BLOCK_PROTOCOL_GUID,
+        EFI_PARTITION_INFO_PROTOCOL,
+        EFI_PARTITION_INFO_PROTOCOL_GUID,
+        PARTITION_TYPE_MBR,
+        PARTITION_TYPE_GPT,
+        EFI_RAM_DISK_PROTOCOL,
+        EFI_RAM_DISK_PROTOCOL_GUID,
+        EFI_VIRTUAL_DISK_GUID,
+        EFI_VIRTUAL_CD_GUID,
+        EFI_PERSISTENT_VIRTUAL_DISK_GUID,
+        EFI_PERSISTENT_VIRTUAL_CD_GUID,
+    },
+    device_path::{EFI_DEVICE_PATH_PROTOCOL, MEDIA_DEVICE_PATH, MEDIA_HARDDRIVE_DP, END_DEVICE_PATH_TYPE},
+    boot_services::{
+        EFI_TPL,
+        TPL_CALLBACK,
+        EFI_ALLOCATE_TYPE,
+        EFI_MEMORY_TYPE,
+        EFI_PHYSICAL_ADDRESS,
+        EFI_PAGE_SIZE,
+    },
+    nvme::{
+        EFI_NVM_EXPRESS_PASS_THRU_PROTOCOL,
+        EFI_NVM_EXPRESS_PASS_THRU_PROTOCOL_GUID,
+        EFI_NVM_EXPRESS_PASS_THRU_COMMAND_PACKET,
+        EFI_NVM_EXPRESS_COMMAND,
+        EFI_NVM_EXPRESS_COMPLETION,
+        NVME_ADMIN_QUEUE,
+        NVME_IO_QUEUE,
+    },
+    EFI_GUID,
+    EFI_NOT_FOUND,
+    EFI_HANDLE,
+    EFI_EVENT,
+    EFI_SUCCESS,
+    EFI_NOT_READY,
+    IsSuccess,
+    VOID,
+};
+use events::{Wait, as_100ns_units};
+use fs::{self, Volume};
+use device_path::{self, DevicePath};
+use io::{self, Read, Write, Seek, SeekFrom};
+use image::Len;
+use gpt;
+use mbr;
+use byteorder::{ByteOrder, LittleEndian};
+use utils::{handles_by_protocol, locate_protocol, open_protocol};
+use alloc::{Vec, String, boxed::Box};
+use core::{ptr, mem, slice, cmp, str, cell::Cell, marker::PhantomData, time::Duration};
+use {Result, EfiError, EfiErrorKind, boot_services, to_boolean, from_boolean};
+
+mod scsi;
+mod ata;
+mod security;
+
+pub use self::scsi::*;
+pub use self::ata::*;
+pub use self::security::*;
+
+// Upper limit on the size of the bounce buffer used for transfers to/from unaligned buffers
+const MAX_BOUNCE_BUFFER_SIZE: usize = 64 * 1024;
+// How much is written between calls to the progress callback of the *_with_progress() methods
+const PROGRESS_CHUNK_SIZE: usize = 1024 * 1024;
+
+const NVME_ADMIN_IDENTIFY: u8 = 0x06;
+const NVME_IDENTIFY_CNS_NAMESPACE: u32 = 0x00;
+const NVME_IDENTIFY_CNS_CONTROLLER: u32 = 0x01;
+const NVME_IDENTIFY_DATA_SIZE: usize = 4096;
+const NVME_DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Information about the media in a block device
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MediaInfo {
+    /// Changes every time the media in the device is changed
+    pub media_id: u32,
+    pub removable: bool,
+    pub present: bool,
+    /// Whether the device is a partition of a larger device rather than the whole device
+    pub logical_partition: bool,
+    pub read_only: bool,
+    pub write_caching: bool,
+    /// Size of a block in bytes
+    pub block_size: u32,
+    /// Required alignment of I/O buffers in bytes. 0 or 1 means no requirement.
+    pub io_align: u32,
+    /// Address of the last block on the device
+    pub last_block: u64,
+}
+
+impl MediaInfo {
+    /// Total size of the media in bytes
+    pub fn size(&self) -> u64 {
+        (self.last_block + 1) * self.block_size as u64
+    }
+}
+
+/// A disk, partition or other block device exposed through EFI_BLOCK_IO_PROTOCOL
+pub struct BlockDevice {
+    handle: EFI_HANDLE,
+    protocol: *const EFI_BLOCK_IO_PROTOCOL,
+    erase_block: *const EFI_ERASE_BLOCK_PROTOCOL, // Null if not supported
+    bounce: Vec<u8>,
+}
+
+impl BlockDevice {
+    /// Opens the block device on the given handle
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol::<EFI_BLOCK_IO_PROTOCOL>(handle, &EFI_BLOCK_IO_PROTOCOL_GUID)?;
+        let erase_block = open_protocol(handle, &EFI_ERASE_BLOCK_PROTOCOL_GUID).unwrap_or(ptr::null());
+        Ok(BlockDevice { handle, protocol, erase_block, bounce: Vec::new() })
+    }
+
+    /// Opens all block devices in the system. This includes both whole disks and their partitions.
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_BLOCK_IO_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    /// The handle the device is on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// The current media information. Can change between calls for removable devices.
+    pub fn media(&self) -> MediaInfo {
+        let media = unsafe { &*(*self.protocol).Media };
+        MediaInfo {
+            media_id: media.MediaId,
+            removable: from_boolean(media.RemovableMedia),
+            present: from_boolean(media.MediaPresent),
+            logical_partition: from_boolean(media.LogicalPartition),
+            read_only: from_boolean(media.ReadOnly),
+            write_caching: from_boolean(media.WriteCaching),
+            block_size: media.BlockSize,
+            io_align: media.IoAlign,
+            last_block: media.LastBlock,
+        }
+    }
+
+    /// Reads whole blocks starting at the given LBA. The length of the buffer must be a multiple of the block size.
+    /// Buffers that don't meet the device's alignment requirement are read through an internal bounce buffer.
+    pub fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<()> {
+        let media = self.media();
+        check_len(&media, buf.len())?;
+
+        if is_aligned(buf.as_ptr(), media.io_align) {
+            unsafe {
+                ret_on_err!(((*self.protocol).ReadBlocks)(self.protocol, media.media_id, lba, buf.len(), buf.as_mut_ptr() as *mut VOID));
+            }
+            return Ok(());
+        }
+
+        let chunk_size = bounce_chunk_size(&media);
+        let mut lba = lba;
+        for chunk in buf.chunks_mut(chunk_size) {
+            let protocol = self.protocol;
+            let bounce = aligned_bounce_buffer(&mut self.bounce, chunk.len(), media.io_align);
+            unsafe {
+                ret_on_err!(((*protocol).ReadBlocks)(protocol, media.media_id, lba, bounce.len(), bounce.as_mut_ptr() as *mut VOID));
+            }
+            chunk.copy_from_slice(bounce);
+            lba += (chunk.len() / media.block_size as usize) as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Writes whole blocks starting at the given LBA. The length of the buffer must be a multiple of the block size.
+    /// Buffers that don't meet the device's alignment requirement are written through an internal bounce buffer.
+    pub fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<()> {
+        let media = self.media();
+        check_len(&media, buf.len())?;
+
+        if is_aligned(buf.as_ptr(), media.io_align) {
+            unsafe {
+                ret_on_err!(((*self.protocol).WriteBlocks)(self.protocol, media.media_id, lba, buf.len(), buf.as_ptr() as *const VOID));
+            }
+            return Ok(());
+        }
+
+        let chunk_size = bounce_chunk_size(&media);
+        let mut lba = lba;
+        for chunk in buf.chunks(chunk_size) {
+            let protocol = self.protocol;
+            let bounce = aligned_bounce_buffer(&mut self.bounce, chunk.len(), media.io_align);
+            bounce.copy_from_slice(chunk);
+            unsafe {
+                ret_on_err!(((*protocol).WriteBlocks)(protocol, media.media_id, lba, bounce.len(), bounce.as_ptr() as *const VOID));
+            }
+            lba += (chunk.len() / media.block_size as usize) as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Same as `write_blocks()` but writes in chunks, calling `progress` with the number of bytes written so far
+    /// and the total after each one
+    pub fn write_blocks_with_progress(&mut self, lba: u64, buf: &[u8], progress: &mut FnMut(u64, u64)) -> Result<()> {
+        let media = self.media();
+        check_len(&media, buf.len())?;
+
+        let block_size = media.block_size as usize;
+        let chunk_size = cmp::max(PROGRESS_CHUNK_SIZE / block_size, 1) * block_size;
+        let mut lba = lba;
+        let mut written = 0;
+        progress(0, buf.len() as u64);
+        for chunk in buf.chunks(chunk_size) {
+            self.write_blocks(lba, chunk)?;
+            lba += (chunk.len() / block_size) as u64;
+            written += chunk.len() as u64;
+            progress(written, buf.len() as u64);
+        }
+
+        Ok(())
+    }
+
+    /// Flushes any data cached by the device to the media
+    pub fn flush(&mut self) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).FlushBlocks)(self.protocol));
+        }
+
+        Ok(())
+    }
+
+    /// Resets the device hardware. With `extended_verification` the driver may take longer to verify the device.
+    pub fn reset(&mut self, extended_verification: bool) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).Reset)(self.protocol, to_boolean(extended_verification)));
+        }
+
+        Ok(())
+    }
+
+    /// Whether the device can erase blocks itself through EFI_ERASE_BLOCK_PROTOCOL
+    pub fn supports_erase(&self) -> bool {
+        !self.erase_block.is_null()
+    }
+
+    /// The number of blocks erases should start and end on a multiple of for best performance.
+    /// `None` if the device can't erase blocks itself.
+    pub fn erase_granularity(&self) -> Option<u32> {
+        if self.erase_block.is_null() {
+            None
+        } else {
+            Some(unsafe { (*self.erase_block).EraseLengthGranularity })
+        }
+    }
+
+    /// Erases `count` blocks starting at the given LBA e.g. by issuing a TRIM to an SSD. What erased blocks read back as
+    /// is up to the device. If the device can't erase blocks itself they're overwritten with zeros instead.
+    pub fn erase_blocks(&mut self, lba: u64, count: u64) -> Result<()> {
+        if self.erase_block.is_null() {
+            return self.zero_blocks(lba, count);
+        }
+
+        let media = self.media();
+        let size = check_range(&media, lba, count)?;
+        unsafe {
+            ret_on_err!(((*self.erase_block).EraseBlocks)(self.erase_block, media.media_id, lba, ptr::null_mut(), size)); // No token means a blocking erase
+        }
+
+        Ok(())
+    }
+
+    /// Overwrites `count` blocks starting at the given LBA with zeros
+    pub fn zero_blocks(&mut self, lba: u64, count: u64) -> Result<()> {
+        let media = self.media();
+        let size = check_range(&media, lba, count)?;
+
+        let chunk_size = cmp::min(bounce_chunk_size(&media), size);
+        let protocol = self.protocol;
+        let zeros = aligned_bounce_buffer(&mut self.bounce, chunk_size, media.io_align);
+        for b in zeros.iter_mut() {
+            *b = 0;
+        }
+
+        let mut lba = lba;
+        let mut remaining = size;
+        while remaining > 0 {
+            let len = cmp::min(remaining, chunk_size);
+            unsafe {
+                ret_on_err!(((*protocol).WriteBlocks)(protocol, media.media_id, lba, len, zeros.as_ptr() as *const VOID));
+            }
+            lba += (len / media.block_size as usize) as u64;
+            remaining -= len;
+        }
+
+        Ok(())
+    }
+}
+
+/// Byte-granular access to a disk or partition through EFI_DISK_IO_PROTOCOL.
+/// Also supports asynchronous I/O if the device has EFI_DISK_IO2_PROTOCOL.
+pub struct Disk {
+    block_device: BlockDevice,
+    disk_io: *const EFI_DISK_IO_PROTOCOL,
+    disk_io2: *const EFI_DISK_IO2_PROTOCOL, // Null if not supported
+}
+
+impl Disk {
+    /// Opens the disk on the given handle. The handle must support both EFI_DISK_IO_PROTOCOL and EFI_BLOCK_IO_PROTOCOL.
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let block_device = BlockDevice::from_handle(handle)?;
+        let disk_io = open_protocol(handle, &EFI_DISK_IO_PROTOCOL_GUID)?;
+        let disk_io2 = open_protocol(handle, &EFI_DISK_IO2_PROTOCOL_GUID).unwrap_or(ptr::null());
+        Ok(Disk { block_device, disk_io, disk_io2 })
+    }
+
+    /// The underlying block device
+    pub fn block_device(&mut self) -> &mut BlockDevice {
+        &mut self.block_device
+    }
+
+    /// Whether `read_at_async()` and `write_at_async()` are supported
+    pub fn supports_async(&self) -> bool {
+        !self.disk_io2.is_null()
+    }
+
+    /// Reads from the given byte offset. Neither the offset nor the length have to be block aligned.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let media_id = self.block_device.media().media_id;
+        unsafe {
+            ret_on_err!(((*self.disk_io).ReadDisk)(self.disk_io, media_id, offset, buf.len(), buf.as_mut_ptr() as *mut VOID));
+        }
+
+        Ok(())
+    }
+
+    /// Writes at the given byte offset. Neither the offset nor the length have to be block aligned.
+    pub fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<()> {
+        let media_id = self.block_device.media().media_id;
+        unsafe {
+            ret_on_err!(((*self.disk_io).WriteDisk)(self.disk_io, media_id, offset, buf.len(), buf.as_ptr() as *const VOID));
+        }
+
+        Ok(())
+    }
+
+    /// Same as `write_at()` but writes in chunks, calling `progress` with the number of bytes written so far
+    /// and the total after each one
+    pub fn write_at_with_progress(&mut self, offset: u64, buf: &[u8], progress: &mut FnMut(u64, u64)) -> Result<()> {
+        let mut written = 0;
+        progress(0, buf.len() as u64);
+        for chunk in buf.chunks(PROGRESS_CHUNK_SIZE) {
+            self.write_at(offset + written, chunk)?;
+            written += chunk.len() as u64;
+            progress(written, buf.len() as u64);
+        }
+
+        Ok(())
+    }
+
+    /// Starts reading from the given byte offset without waiting for the read to complete.
+    /// Fails with `Unsupported` if the device does not support asynchronous I/O.
+    pub fn read_at_async<'a>(&'a self, offset: u64, buf: &'a mut [u8]) -> Result<DiskIoRequest<'a>> {
+        let disk_io2 = self.disk_io2()?;
+        let media_id = self.block_device.media().media_id;
+        let mut request = DiskIoRequest::new()?;
+        unsafe {
+            ret_on_err!(((*disk_io2).ReadDiskEx)(disk_io2, media_id, offset, &mut *request.token, buf.len(), buf.as_mut_ptr() as *mut VOID));
+        }
+
+        request.started = true;
+        Ok(request)
+    }
+
+    /// Starts writing at the given byte offset without waiting for the write to complete.
+    /// Fails with `Unsupported` if the device does not support asynchronous I/O.
+    pub fn write_at_async<'a>(&'a self, offset: u64, buf: &'a [u8]) -> Result<DiskIoRequest<'a>> {
+        let disk_io2 = self.disk_io2()?;
+        let media_id = self.block_device.media().media_id;
+        let mut request = DiskIoRequest::new()?;
+        unsafe {
+            ret_on_err!(((*disk_io2).WriteDiskEx)(disk_io2, media_id, offset, &mut *request.token, buf.len(), buf.as_ptr() as *const VOID));
+        }
+
+        request.started = true;
+        Ok(request)
+    }
+
+    /// A stream over the disk starting at offset 0, for use with `io::Read`, `io::Write` and `io::Seek` consumers
+    pub fn stream(&mut self) -> DiskStream {
+        DiskStream { disk: self, position: 0 }
+    }
+
+    fn disk_io2(&self) -> Result<*const EFI_DISK_IO2_PROTOCOL> {
+        if self.disk_io2.is_null() {
+            Err(EfiErrorKind::Unsupported.into())
+        } else {
+            Ok(self.disk_io2)
+        }
+    }
+}
+
+/// A seekable stream of bytes over a disk obtained from `Disk::stream()`.
+/// Reads stop and writes fail with `WriteZero` at the end of the media.
+pub struct DiskStream<'a> {
+    disk: &'a mut Disk,
+    position: u64,
+}
+
+impl<'a> DiskStream<'a> {
+    // Number of bytes from the current position to the end of the media
+    fn remaining(&self) -> u64 {
+        self.disk.block_device.media().size().saturating_sub(self.position)
+    }
+}
+
+impl<'a> Read for DiskStream<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = cmp::min(buf.len() as u64, self.remaining()) as usize;
+        self.disk.read_at(self.position, &mut buf[..len])?;
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl<'a> Write for DiskStream<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = cmp::min(buf.len() as u64, self.remaining()) as usize;
+        let position = self.position;
+        self.disk.write_at(position, &buf[..len])?;
+        self.position += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.disk.block_device().flush().map_err(io::Error::from)
+    }
+}
+
+impl<'a> Seek for DiskStream<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.position = position;
+                return Ok(position);
+            },
+            SeekFrom::End(offset) => (self.disk.block_device.media().size(), offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+
+        let position = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.wrapping_neg() as u64)
+        };
+
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            },
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+        }
+    }
+}
+
+impl<'a> Len for DiskStream<'a> {
+    fn len(&mut self) -> Result<Option<u64>> {
+        Ok(Some(self.disk.block_device.media().size()))
+    }
+}
+
+/// An asynchronous disk read or write in progress. Use the `Wait` trait to wait for it to complete.
+/// The buffer stays borrowed until the request is dropped and dropping an incomplete request blocks until it completes.
+pub struct DiskIoRequest<'a> {
+    token: Box<EFI_DISK_IO2_TOKEN>, // On the heap because the firmware holds on to its address until completion
+    started: bool,
+    // Set once completion has been seen. Both waiting on the event and checking it reset it, so it can't be waited on again.
+    completed: Cell<bool>,
+    _buffer: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> DiskIoRequest<'a> {
+    fn new() -> Result<Self> {
+        let bs = boot_services();
+        let mut event: EFI_EVENT = ptr::null();
+        unsafe {
+            ret_on_err!(((*bs).CreateEvent)(0, TPL_CALLBACK as EFI_TPL, None, ptr::null(), &mut event));
+        }
+
+        Ok(DiskIoRequest { token: Box::new(EFI_DISK_IO2_TOKEN { Event: event, TransactionStatus: EFI_SUCCESS }), started: false, completed: Cell::new(false), _buffer: PhantomData })
+    }
+}
+
+impl<'a> Wait for DiskIoRequest<'a> {
+    /// Waits for the request to complete and returns its outcome
+    fn wait(&self) -> Result<()> {
+        if !self.completed.get() {
+            let bs = boot_services();
+            unsafe {
+                let mut signaled_index = 0;
+                ret_on_err!(((*bs).WaitForEvent)(1, &self.token.Event, &mut signaled_index));
+            }
+            self.completed.set(true);
+        }
+
+        ret_on_err!(self.token.TransactionStatus);
+        Ok(())
+    }
+
+    fn is_signaled(&self) -> Result<bool> {
+        if self.completed.get() {
+            return Ok(true);
+        }
+
+        let bs = boot_services();
+        let status = unsafe { ((*bs).CheckEvent)(self.token.Event) };
+        match status {
+            EFI_SUCCESS => {
+                self.completed.set(true);
+                Ok(true)
+            },
+            EFI_NOT_READY => Ok(false),
+            s => Err(s.into())
+        }
+    }
+}
+
+impl<'a> Drop for DiskIoRequest<'a> {
+    fn drop(&mut self) {
+        // The firmware may still be using the buffer. We must not return before it's done.
+        if self.started && !self.completed.get() {
+            let _ = self.wait();
+        }
+
+        let bs = boot_services();
+        unsafe {
+            ((*bs).CloseEvent)(self.token.Event);
+        }
+    }
+}
+
+/// A whole disk and the partitions on it as returned by `partitions()`
+#[derive(Debug, Clone)]
+pub struct DiskInfo {
+    pub handle: EFI_HANDLE,
+    pub media: MediaInfo,
+    /// Whether there's a file system directly on the disk without a partition table as on some USB sticks
+    pub has_file_system: bool,
+    /// Ordered by partition number
+    pub partitions: Vec<PartitionInfo>,
+}
+
+impl DiskInfo {
+    /// The EFI system partition on the disk if it has one
+    pub fn esp(&self) -> Option<&PartitionInfo> {
+        self.partitions.iter().find(|p| p.is_esp)
+    }
+}
+
+/// A partition as seen by the firmware
+#[derive(Debug, Clone)]
+pub struct PartitionInfo {
+    pub handle: EFI_HANDLE,
+    /// Number of the entry in the partition table starting from 1
+    pub number: u32,
+    /// Address of the first block on the disk
+    pub first_lba: u64,
+    pub block_count: u64,
+    pub kind: PartitionKind,
+    pub is_esp: bool,
+    pub has_file_system: bool,
+}
+
+impl PartitionInfo {
+    /// Opens the file system on the partition
+    pub fn volume(&self) -> Result<Volume> {
+        Volume::from_handle(self.handle)
+    }
+}
+
+/// The partition table entry describing a partition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartitionKind {
+    Gpt(gpt::Partition),
+    Mbr(mbr::Partition),
+    /// Neither the firmware nor the partition table on the disk could tell us
+    Unknown,
+}
+
+/// Lists all disks in the system along with their partitions and which of them have a file system.
+/// Partition details come from EFI_PARTITION_INFO_PROTOCOL where the firmware supports it and otherwise from
+/// reading the GPT or MBR on the disk ourselves.
+pub fn partitions() -> Result<Vec<DiskInfo>> {
+    let file_systems = fs::file_system_handles()?;
+
+    let mut disks = Vec::new();
+    let mut disk_paths = Vec::new();
+    let mut partition_devices = Vec::new();
+    for device in BlockDevice::all()? {
+        // Without a device path there's no way to tell which disk a partition belongs to
+        let path = match device_path::device_path_of(device.handle()) {
+            Ok(path) => path,
+            Err(_) => continue
+        };
+
+        if device.media().logical_partition {
+            partition_devices.push((device, path));
+        } else {
+            disk_paths.push(path);
+            disks.push(device);
+        }
+    }
+
+    let mut tree = disks.iter()
+        .map(|d| DiskInfo { handle: d.handle(), media: d.media(), has_file_system: file_systems.contains(&d.handle()), partitions: Vec::new() })
+        .collect::<Vec<_>>();
+    let mut tables: Vec<Option<PartitionTable>> = disks.iter().map(|_| None).collect();
+
+    for (device, path) in partition_devices {
+        let (parent_path, node) = match split_last_node(path.as_bytes()) {
+            Some(split) => split,
+            None => continue
+        };
+
+        let (number, first_lba, block_count) = match parse_hard_drive_node(node) {
+            Some(hd) => hd,
+            None => continue // E.g. El Torito partitions on CDs
+        };
+
+        let disk_index = match disk_paths.iter().position(|p| split_end_node(p.as_bytes()) == parent_path) {
+            Some(i) => i,
+            None => continue
+        };
+
+        let (kind, is_esp) = match partition_kind_from_firmware(device.handle(), first_lba, block_count) {
+            Some(kind) => kind,
+            None => {
+                if tables[disk_index].is_none() {
+                    tables[disk_index] = Some(PartitionTable::read(&mut disks[disk_index]));
+                }
+                tables[disk_index].as_ref().map_or((PartitionKind::Unknown, false), |t| t.find(first_lba))
+            }
+        };
+
+        tree[disk_index].partitions.push(PartitionInfo {
+            handle: device.handle(),
+            number,
+            first_lba,
+            block_count,
+            kind,
+            is_esp,
+            has_file_system: file_systems.contains(&device.handle()),
+        });
+    }
+
+    for disk in &mut tree {
+        disk.partitions.sort_by_key(|p| p.number);
+    }
+
+    Ok(tree)
+}
+
+// Partition table read from a disk because the firmware doesn't provide EFI_PARTITION_INFO_PROTOCOL
+enum PartitionTable {
+    Gpt(gpt::PartitionTable),
+    Mbr(mbr::PartitionTable),
+    None,
+}
+
+impl PartitionTable {
+    fn read(disk: &mut BlockDevice) -> Self {
+        if let Ok(table) = gpt::PartitionTable::read(disk) {
+            return PartitionTable::Gpt(table);
+        }
+
+        match mbr::PartitionTable::read(disk) {
+            Ok(ref table) if table.is_protective() => PartitionTable::None, // A GPT disk with both tables damaged
+            Ok(table) => PartitionTable::Mbr(table),
+            Err(_) => PartitionTable::None
+        }
+    }
+
+    // The entry of the partition starting at the given block and whether it's an ESP
+    fn find(&self, first_lba: u64) -> (PartitionKind, bool) {
+        match *self {
+            PartitionTable::Gpt(ref table) => table.partitions()
+                .into_iter()
+                .find(|&(_, p)| p.first_lba == first_lba)
+                .map_or((PartitionKind::Unknown, false), |(_, p)| (PartitionKind::Gpt(p.clone()), p.type_guid == gpt::PARTITION_TYPE_EFI_SYSTEM)),
+            PartitionTable::Mbr(ref table) => table.partitions()
+                .into_iter()
+                .find(|p| p.first_lba == first_lba)
+                .map_or((PartitionKind::Unknown, false), |p| (PartitionKind::Mbr(p), p.os_type == mbr::OS_TYPE_EFI_SYSTEM)),
+            PartitionTable::None => (PartitionKind::Unknown, false)
+        }
+    }
+}
+
+fn partition_kind_from_firmware(handle: EFI_HANDLE, first_lba: u64, block_count: u64) -> Option<(PartitionKind, bool)> {
+    let info = open_protocol::<EFI_PARTITION_INFO_PROTOCOL>(handle, &EFI_PARTITION_INFO_PROTOCOL_GUID).ok()?;
+    let info = unsafe { &*info };
+    let is_esp = info.System == 1;
+    let kind = match info.Type {
+        PARTITION_TYPE_GPT => {
+            let entry = unsafe { info.Info.Gpt };
+            let name = entry.PartitionName;
+            let name_len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+            PartitionKind::Gpt(gpt::Partition {
+                type_guid: entry.PartitionTypeGUID,
+                unique_guid: entry.UniquePartitionGUID,
+                first_lba: entry.StartingLBA,
+                last_lba: entry.EndingLBA,
+                attributes: gpt::PartitionAttributes::from_bits(entry.Attributes),
+                name: String::from_utf16_lossy(&name[..name_len]),
+            })
+        },
+        PARTITION_TYPE_MBR => {
+            let record = unsafe { info.Info.Mbr };
+            // The record's LBA is relative to its EBR for logical partitions so we go by the device path instead
+            PartitionKind::Mbr(mbr::Partition { bootable: record.BootIndicator == 0x80, os_type: record.OSIndicator, first_lba, block_count })
+        },
+        _ => PartitionKind::Unknown
+    };
+
+    Some((kind, is_esp))
+}
+
+// Splits a device path into everything before the last node and the last node itself
+fn split_last_node(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let mut offset = 0;
+    let mut last = None;
+    while let Some(len) = device_path::node_len(&bytes[offset..]) {
+        if bytes[offset] == END_DEVICE_PATH_TYPE {
+            break;
+        }
+
+        last = Some((offset, len));
+        offset += len;
+    }
+
+    last.map(|(offset, len)| (&bytes[..offset], &bytes[offset..offset + len]))
+}
+
+// Everything before the end node
+fn split_end_node(bytes: &[u8]) -> &[u8] {
+    match split_last_node(bytes) {
+        Some((before, last)) => &bytes[..before.len() + last.len()],
+        None => &bytes[..0]
+    }
+}
+
+// Returns the partition number, start and size from a HARDDRIVE_DEVICE_PATH node
+fn parse_hard_drive_node(node: &[u8]) -> Option<(u32, u64, u64)> {
+    if node.len() < 42 || node[0] != MEDIA_DEVICE_PATH || node[1] != MEDIA_HARDDRIVE_DP {
+        return None;
+    }
+
+    Some((LittleEndian::read_u32(&node[4..8]), LittleEndian::read_u64(&node[8..16]), LittleEndian::read_u64(&node[16..24])))
+}
+
+/// What a RAM disk holds. Decides how the firmware and the OS treat it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RamDiskKind {
+    /// A raw disk image
+    VirtualDisk,
+    /// An ISO image
+    VirtualCd,
+    /// A raw disk image the OS should keep using after boot
+    PersistentVirtualDisk,
+    /// An ISO image the OS should keep using after boot
+    PersistentVirtualCd,
+}
+
+impl RamDiskKind {
+    fn guid(&self) -> &'static EFI_GUID {
+        match *self {
+            RamDiskKind::VirtualDisk => &EFI_VIRTUAL_DISK_GUID,
+            RamDiskKind::VirtualCd => &EFI_VIRTUAL_CD_GUID,
+            RamDiskKind::PersistentVirtualDisk => &EFI_PERSISTENT_VIRTUAL_DISK_GUID,
+            RamDiskKind::PersistentVirtualCd => &EFI_PERSISTENT_VIRTUAL_CD_GUID,
+        }
+    }
+}
+
+/// A memory buffer exposed as a disk through EFI_RAM_DISK_PROTOCOL e.g. an ISO downloaded over HTTP.
+/// Once registered the firmware produces Block I/O (and a file system if it recognizes one) on it.
+/// The disk is unregistered and its memory freed on drop.
+pub struct RamDisk {
+    protocol: *const EFI_RAM_DISK_PROTOCOL,
+    base: EFI_PHYSICAL_ADDRESS,
+    pages: usize,
+    size: u64,
+    path: DevicePath,
+}
+
+impl RamDisk {
+    /// Copies the data into a new RAM disk
+    pub fn register(data: &[u8], kind: RamDiskKind) -> Result<Self> {
+        let mut reader = io::Cursor::new(data);
+        Self::from_reader(&mut reader, data.len() as u64, kind)
+    }
+
+    /// Reads exactly `size` bytes from the reader into a new RAM disk.
+    /// Lets an image be streamed straight into the disk's memory without buffering it somewhere else first.
+    pub fn from_reader<R: Read>(reader: &mut R, size: u64, kind: RamDiskKind) -> Result<Self> {
+        let bs = boot_services();
+        let protocol: *const EFI_RAM_DISK_PROTOCOL = locate_protocol(&EFI_RAM_DISK_PROTOCOL_GUID)?;
+
+        if size == 0 || size > usize::max_value() as u64 {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        // Reserved memory so that the OS doesn't reuse it if the disk is meant to outlive boot
+        let pages = (size as usize + EFI_PAGE_SIZE - 1) / EFI_PAGE_SIZE;
+        let mut base: EFI_PHYSICAL_ADDRESS = 0;
+        unsafe {
+            ret_on_err!(((*bs).AllocatePages)(EFI_ALLOCATE_TYPE::AllocateAnyPages, EFI_MEMORY_TYPE::EfiReservedMemoryType, pages, &mut base));
+        }
+
+        let free_pages = || unsafe { ((*bs).FreePages)(base, pages); };
+
+        let buf = unsafe { slice::from_raw_parts_mut(base as usize as *mut u8, size as usize) };
+        match io::fill_buf(reader, buf) {
+            Ok(bytes_read) if bytes_read as u64 == size => (),
+            Ok(_) => {
+                free_pages();
+                return Err(EfiErrorKind::EndOfFile.into());
+            },
+            Err(e) => {
+                free_pages();
+                let kind = e.efi_error_kind().unwrap_or(EfiErrorKind::DeviceError);
+                return Err(EfiError::with_source(kind, e).add_context("reading RAM disk contents"));
+            }
+        }
+
+        let mut path: *const EFI_DEVICE_PATH_PROTOCOL = ptr::null();
+        let status = unsafe { ((*protocol).Register)(base, size, kind.guid(), ptr::null(), &mut path) };
+        if !IsSuccess(status) || path.is_null() {
+            free_pages();
+            return Err(if IsSuccess(status) { EfiErrorKind::DeviceError.into() } else { status.into() });
+        }
+
+        // The firmware holds on to the disk now so it has to let go of it before the pages can be freed
+        let path = match DevicePath::from_ptr(path) {
+            Ok(path) => path,
+            Err(e) => {
+                if IsSuccess(unsafe { ((*protocol).Unregister)(path) }) {
+                    free_pages();
+                }
+                return Err(e);
+            }
+        };
+
+        Ok(RamDisk { protocol, base, pages, size, path })
+    }
+
+    /// The device path of the disk. Can be used to boot from it or find its handle.
+    pub fn device_path(&self) -> &DevicePath {
+        &self.path
+    }
+
+    /// The handle on which the firmware produced EFI_BLOCK_IO_PROTOCOL for the disk
+    pub fn handle(&self) -> Result<EFI_HANDLE> {
+        let bs = boot_services();
+        let mut remaining = self.path.as_ptr();
+        let mut handle: EFI_HANDLE = ptr::null_mut();
+        unsafe {
+            ret_on_err!(((*bs).LocateDevicePath)(&EFI_BLOCK_IO_PROTOCOL_GUID, &mut remaining, &mut handle));
+        }
+
+        Ok(handle)
+    }
+
+    /// Size of the disk in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Keeps the disk registered for good, e.g. to boot an OS from it, and returns its device path
+    pub fn into_device_path(self) -> Result<DevicePath> {
+        let path = self.path.try_clone()?;
+        mem::forget(self);
+        Ok(path)
+    }
+}
+
+impl Drop for RamDisk {
+    fn drop(&mut self) {
+        let bs = boot_services();
+        unsafe {
+            // Only free the memory if the firmware's let go of it
+            if IsSuccess(((*self.protocol).Unregister)(self.path.as_ptr())) {
+                ((*bs).FreePages)(self.base, self.pages);
+            }
+        }
+    }
+}
+
+/// Which queue an NVMe command goes to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NvmeQueue {
+    Admin,
+    Io,
+}
+
+/// An NVMe command. The data pointers and PRP lists are taken care of by the firmware.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct NvmeCommand {
+    pub opcode: u8,
+    /// 0 for commands that don't apply to a namespace
+    pub namespace_id: u32,
+    pub cdw2: u32,
+    pub cdw3: u32,
+    pub cdw10: u32,
+    pub cdw11: u32,
+    pub cdw12: u32,
+    pub cdw13: u32,
+    pub cdw14: u32,
+    pub cdw15: u32,
+}
+
+/// The completion queue entry of a command
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct NvmeCompletion {
+    /// Command specific result
+    pub dw0: u32,
+    pub dw1: u32,
+    pub dw2: u32,
+    pub dw3: u32,
+}
+
+impl NvmeCompletion {
+    pub fn status_code(&self) -> u8 {
+        (self.dw3 >> 17) as u8
+    }
+
+    pub fn status_code_type(&self) -> u8 {
+        ((self.dw3 >> 25) & 0x7) as u8
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status_code() == 0 && self.status_code_type() == 0
+    }
+}
+
+/// The parts of the Identify Controller data structure most callers care about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentifyController {
+    pub vendor_id: u16,
+    pub subsystem_vendor_id: u16,
+    pub serial_number: String,
+    pub model_number: String,
+    pub firmware_revision: String,
+    /// Largest transfer as a power of two multiple of the minimum page size. 0 means no limit.
+    pub max_data_transfer_size: u8,
+    pub controller_id: u16,
+    /// Major version in the upper 16 bits, minor in the next 8 and tertiary in the lowest 8
+    pub version: u32,
+    /// Total NVM capacity in bytes (lower 64 bits)
+    pub total_capacity: u64,
+    /// Highest namespace ID the controller supports
+    pub namespace_count: u32,
+}
+
+impl IdentifyController {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < NVME_IDENTIFY_DATA_SIZE {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        Ok(IdentifyController {
+            vendor_id: LittleEndian::read_u16(&bytes[0..2]),
+            subsystem_vendor_id: LittleEndian::read_u16(&bytes[2..4]),
+            serial_number: ascii_field(&bytes[4..24]),
+            model_number: ascii_field(&bytes[24..64]),
+            firmware_revision: ascii_field(&bytes[64..72]),
+            max_data_transfer_size: bytes[77],
+            controller_id: LittleEndian::read_u16(&bytes[78..80]),
+            version: LittleEndian::read_u32(&bytes[80..84]),
+            total_capacity: LittleEndian::read_u64(&bytes[280..288]),
+            namespace_count: LittleEndian::read_u32(&bytes[516..520]),
+        })
+    }
+}
+
+/// One of the block formats a namespace supports
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LbaFormat {
+    /// Bytes of metadata per block
+    pub metadata_size: u16,
+    /// Bytes of data per block
+    pub block_size: u32,
+    /// 0 is best, 3 is degraded
+    pub relative_performance: u8,
+}
+
+/// The parts of the Identify Namespace data structure most callers care about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentifyNamespace {
+    /// Size of the namespace in blocks
+    pub size: u64,
+    /// Blocks that can be allocated
+    pub capacity: u64,
+    /// Blocks currently allocated
+    pub utilization: u64,
+    pub lba_formats: Vec<LbaFormat>,
+    /// Index into `lba_formats` of the format the namespace is formatted with
+    pub formatted_lba_format: usize,
+}
+
+impl IdentifyNamespace {
+    /// Size of a block in bytes in the current format
+    pub fn block_size(&self) -> u32 {
+        self.lba_formats.get(self.formatted_lba_format).map_or(0, |f| f.block_size)
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < NVME_IDENTIFY_DATA_SIZE {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        let no_of_formats = bytes[25] as usize + 1; // The field is 0 based
+        let lba_formats = bytes[128..128 + no_of_formats * 4].chunks(4)
+            .map(|f| LbaFormat {
+                metadata_size: LittleEndian::read_u16(&f[0..2]),
+                block_size: if f[2] < 32 { 1 << f[2] } else { 0 }, // Stored as a power of two
+                relative_performance: f[3] & 0x3,
+            })
+            .collect();
+
+        Ok(IdentifyNamespace {
+            size: LittleEndian::read_u64(&bytes[0..8]),
+            capacity: LittleEndian::read_u64(&bytes[8..16]),
+            utilization: LittleEndian::read_u64(&bytes[16..24]),
+            lba_formats,
+            formatted_lba_format: (bytes[26] & 0xF) as usize,
+        })
+    }
+}
+
+/// An NVMe controller exposed through EFI_NVM_EXPRESS_PASS_THRU_PROTOCOL
+pub struct NvmePassthru {
+    handle: EFI_HANDLE,
+    protocol: *const EFI_NVM_EXPRESS_PASS_THRU_PROTOCOL,
+    bounce: Vec<u8>,
+}
+
+impl NvmePassthru {
+    /// Opens the NVMe controller on the given handle
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol(handle, &EFI_NVM_EXPRESS_PASS_THRU_PROTOCOL_GUID)?;
+        Ok(NvmePassthru { handle, protocol, bounce: Vec::new() })
+    }
+
+    /// Opens all NVMe controllers in the system
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_NVM_EXPRESS_PASS_THRU_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    /// The handle the controller is on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// The NVMe version the controller implements in the same format as `IdentifyController::version`
+    pub fn nvme_version(&self) -> u32 {
+        unsafe { (*(*self.protocol).Mode).NvmeVersion }
+    }
+
+    /// IDs of all active namespaces on the controller
+    pub fn namespaces(&self) -> Result<Vec<u32>> {
+        let mut namespaces = Vec::new();
+        let mut namespace_id = 0xFFFFFFFF; // Asks for the first namespace
+        loop {
+            let status = unsafe { ((*self.protocol).GetNextNamespace)(self.protocol, &mut namespace_id) };
+            match status {
+                EFI_SUCCESS => namespaces.push(namespace_id),
+                EFI_NOT_FOUND => return Ok(namespaces),
+                s => return Err(s.into())
+            }
+        }
+    }
+
+    /// The device path of the given namespace
+    pub fn namespace_device_path(&self, namespace_id: u32) -> Result<DevicePath> {
+        let mut path: *const EFI_DEVICE_PATH_PROTOCOL = ptr::null();
+        unsafe {
+            ret_on_err!(((*self.protocol).BuildDevicePath)(self.protocol, namespace_id, &mut path));
+        }
+
+        DevicePath::from_ptr(path)
+    }
+
+    /// Sends a command and waits for it to complete.
+    /// The data buffer is read from or written to depending on the direction encoded in the opcode.
+    /// Buffers that don't meet the controller's alignment requirement go through an internal bounce buffer.
+    /// A timeout of `None` waits indefinitely. Fails with `DeviceError` if the command completed with an error status.
+    pub fn submit(&mut self, queue: NvmeQueue, command: &NvmeCommand, mut data: Option<&mut [u8]>, timeout: Option<Duration>) -> Result<NvmeCompletion> {
+        let io_align = unsafe { (*(*self.protocol).Mode).IoAlign };
+
+        let mut raw_command = EFI_NVM_EXPRESS_COMMAND {
+            Cdw0: command.opcode as u32,
+            Flags: 0xFF, // All command dwords valid
+            Nsid: command.namespace_id,
+            Cdw2: command.cdw2,
+            Cdw3: command.cdw3,
+            Cdw10: command.cdw10,
+            Cdw11: command.cdw11,
+            Cdw12: command.cdw12,
+            Cdw13: command.cdw13,
+            Cdw14: command.cdw14,
+            Cdw15: command.cdw15,
+        };
+        let mut raw_completion = EFI_NVM_EXPRESS_COMPLETION::default();
+
+        let (data_ptr, data_len, bounced) = match data {
+            Some(ref data) if data.len() > u32::max_value() as usize => return Err(EfiErrorKind::BadBufferSize.into()),
+            Some(ref data) if !is_aligned(data.as_ptr(), io_align) => {
+                let bounce = aligned_bounce_buffer(&mut self.bounce, data.len(), io_align);
+                bounce.copy_from_slice(data); // Host to controller commands need the data and it's harmless for the rest
+                (bounce.as_mut_ptr(), bounce.len(), true)
+            },
+            Some(ref mut data) => (data.as_mut_ptr(), data.len(), false),
+            None => (ptr::null_mut(), 0, false),
+        };
+
+        let mut packet = EFI_NVM_EXPRESS_PASS_THRU_COMMAND_PACKET {
+            CommandTimeout: timeout.as_ref().map_or(0, as_100ns_units),
+            TransferBuffer: data_ptr as *mut VOID,
+            TransferLength: data_len as u32,
+            MetadataBuffer: ptr::null_mut(),
+            MetadataLength: 0,
+            QueueType: if queue == NvmeQueue::Admin { NVME_ADMIN_QUEUE } else { NVME_IO_QUEUE },
+            NvmeCmd: &mut raw_command,
+            NvmeCompletion: &mut raw_completion,
+        };
+
+        let status = unsafe { ((*self.protocol).PassThru)(self.protocol, command.namespace_id, &mut packet, ptr::null()) };
+
+        if bounced {
+            if let Some(data) = data {
+                let len = data.len();
+                data.copy_from_slice(aligned_bounce_buffer(&mut self.bounce, len, io_align));
+            }
+        }
+
+        ret_on_err!(status);
+
+        let completion = NvmeCompletion { dw0: raw_completion.DW0, dw1: raw_completion.DW1, dw2: raw_completion.DW2, dw3: raw_completion.DW3 };
+        if !completion.is_success() {
+            return Err(EfiErrorKind::DeviceError.into());
+        }
+
+        Ok(completion)
+    }
+
+    /// Reads the controller's Identify data
+    pub fn identify_controller(&mut self) -> Result<IdentifyController> {
+        let data = self.identify(0, NVME_IDENTIFY_CNS_CONTROLLER)?;
+        IdentifyController::parse(&data)
+    }
+
+    /// Reads the Identify data of the given namespace
+    pub fn identify_namespace(&mut self, namespace_id: u32) -> Result<IdentifyNamespace> {
+        let data = self.identify(namespace_id, NVME_IDENTIFY_CNS_NAMESPACE)?;
+        IdentifyNamespace::parse(&data)
+    }
+
+    fn identify(&mut self, namespace_id: u32, cns: u32) -> Result<Vec<u8>> {
+        let command = NvmeCommand { opcode: NVME_ADMIN_IDENTIFY, namespace_id, cdw10: cns, ..NvmeCommand::default() };
+        let mut data = vec![0_u8; NVME_IDENTIFY_DATA_SIZE];
+        self.submit(NvmeQueue::Admin, &command, Some(&mut data), Some(NVME_DEFAULT_TIMEOUT))?;
+        Ok(data)
+    }
+}
+
+// Fixed width ASCII fields in NVMe data structures are padded with spaces
+fn ascii_field(bytes: &[u8]) -> String {
+    String::from(str::from_utf8(bytes).unwrap_or("").trim_right_matches(|c| c == ' ' || c == '\0'))
+}
+
+fn check_len(media: &MediaInfo, len: usize) -> Result<()> {
+    if !media.present {
+        return Err(EfiErrorKind::NoMedia.into());
+    }
+
+    if media.block_size == 0 || len % media.block_size as usize != 0 {
+        return Err(EfiErrorKind::BadBufferSize.into());
+    }
+
+    Ok(())
+}
+
+// Checks that the given range of blocks is on the media and returns its size in bytes
+fn check_range(media: &MediaInfo, lba: u64, count: u64) -> Result<usize> {
+    if !media.present {
+        return Err(EfiErrorKind::NoMedia.into());
+    }
+
+    let end = lba.checked_add(count);
+    if count == 0 || media.block_size == 0 || end.map_or(true, |end| end > media.last_block + 1) {
+        return Err(EfiErrorKind::InvalidParameter.into());
+    }
+
+    count.checked_mul(media.block_size as u64)
+        .and_then(|size| if size > usize::max_value() as u64 { None } else { Some(size as usize) })
+        .ok_or_else(|| EfiErrorKind::BadBufferSize.into())
+}
+
+fn is_aligned(ptr: *const u8, align: u32) -> bool {
+    align <= 1 || ptr as usize % align as usize == 0
+}
+
+// Largest multiple of the block size that fits in the bounce buffer (but at least one block)
+fn bounce_chunk_size(media: &MediaInfo) -> usize {
+    let block_size = media.block_size as usize;
+    cmp::max(MAX_BOUNCE_BUFFER_SIZE / block_size, 1) * block_size
+}
+
+// Returns a slice of the given length from the buffer that starts at the given alignment, growing the buffer if needed
+fn aligned_bounce_buffer(buffer: &mut Vec<u8>, len: usize, align: u32) -> &mut [u8] {
+    let align = cmp::max(align as usize, 1);
+    if buffer.len() < len + align {
+        buffer.resize(len + align, 0);
+    }
+
+    let misalignment = buffer.as_ptr() as usize % align;
+    let offset = if misalignment == 0 { 0 } else { align - misalignment };
+    &mut buffer[offset..offset + len]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounce_buffer_is_aligned() {
+        let mut buffer = Vec::new();
+        for &align in &[0, 1, 2, 16, 512, 4096] {
+            let bounce = aligned_bounce_buffer(&mut buffer, 1024, align);
+            assert_eq!(bounce.len(), 1024);
+            assert!(is_aligned(bounce.as_ptr(), align));
+        }
+    }
+
+    #[test]
+    fn erase_range_is_checked() {
+        let media = MediaInfo {
+            media_id: 0,
+            removable: false,
+            present: true,
+            logical_partition: false,
+            read_only: false,
+            write_caching: false,
+            block_size: 512,
+            io_align: 0,
+            last_block: 99,
+        };
+        assert_eq!(check_range(&media, 0, 100).unwrap(), 51200);
+        assert_eq!(check_range(&media, 99, 1).unwrap(), 512);
+        assert!(check_range(&media, 99, 2).is_err());
+        assert!(check_range(&media, 10, 0).is_err());
+        assert!(check_range(&media, u64::max_value(), 2).is_err());
+        assert!(check_range(&MediaInfo { present: false, ..media }, 0, 1).is_err());
+        assert_eq!(check_range(&MediaInfo { block_size: 0, ..media }, 0, 1).unwrap_err().kind(), EfiErrorKind::InvalidParameter);
+    }
+
+    fn hard_drive_node(number: u32, start: u64, size: u64) -> Vec<u8> {
+        let mut node = vec![0_u8; 42];
+        node[0] = MEDIA_DEVICE_PATH;
+        node[1] = MEDIA_HARDDRIVE_DP;
+        LittleEndian::write_u16(&mut node[2..4], 42);
+        LittleEndian::write_u32(&mut node[4..8], number);
+        LittleEndian::write_u64(&mut node[8..16], start);
+        LittleEndian::write_u64(&mut node[16..24], size);
+        node
+    }
+
+    #[test]
+    fn partition_path_splits_into_disk_path_and_hard_drive_node() {
+        let disk = [0x01, 0x01, 0x06, 0x00, 0x00, 0x1F]; // PciRoot-ish node
+        let end = [END_DEVICE_PATH_TYPE, 0xFF, 0x04, 0x00];
+        let hd = hard_drive_node(2, 2048, 4096);
+
+        let mut disk_path = disk.to_vec();
+        disk_path.extend_from_slice(&end);
+        let mut partition_path = disk.to_vec();
+        partition_path.extend_from_slice(&hd);
+        partition_path.extend_from_slice(&end);
+
+        let (parent, node) = split_last_node(&partition_path).unwrap();
+        assert_eq!(parent, split_end_node(&disk_path));
+        assert_eq!(parse_hard_drive_node(node), Some((2, 2048, 4096)));
+        assert_eq!(parse_hard_drive_node(&disk), None);
+        assert_eq!(split_last_node(&end), None);
+    }
+
+    #[test]
+    fn parses_identify_controller() {
+        let mut data = vec![0_u8; NVME_IDENTIFY_DATA_SIZE];
+        LittleEndian::write_u16(&mut data[0..2], 0x144D);
+        data[4..24].copy_from_slice(b"S4EWNX0N123456      ");
+        data[24..64].copy_from_slice(b"Samsung SSD 970 EVO Plus 1TB            ");
+        data[64..72].copy_from_slice(b"2B2QEXM7");
+        data[77] = 9;
+        LittleEndian::write_u32(&mut data[80..84], 0x00010300);
+        LittleEndian::write_u32(&mut data[516..520], 1);
+
+        let controller = IdentifyController::parse(&data).unwrap();
+        assert_eq!(controller.vendor_id, 0x144D);
+        assert_eq!(controller.serial_number, "S4EWNX0N123456");
+        assert_eq!(controller.model_number, "Samsung SSD 970 EVO Plus 1TB");
+        assert_eq!(controller.firmware_revision, "2B2QEXM7");
+        assert_eq!(controller.max_data_transfer_size, 9);
+        assert_eq!(controller.version, 0x00010300);
+        assert_eq!(controller.namespace_count, 1);
+        assert!(IdentifyController::parse(&data[..100]).is_err());
+    }
+
+    #[test]
+    fn parses_identify_namespace() {
+        let mut data = vec![0_u8; NVME_IDENTIFY_DATA_SIZE];
+        LittleEndian::write_u64(&mut data[0..8], 1953525168);
+        LittleEndian::write_u64(&mut data[8..16], 1953525168);
+        data[25] = 1; // Two formats
+        data[26] = 1; // Formatted with the second
+        data[128..132].copy_from_slice(&[0, 0, 9, 2]);
+        data[132..136].copy_from_slice(&[8, 0, 12, 0]);
+
+        let namespace = IdentifyNamespace::parse(&data).unwrap();
+        assert_eq!(namespace.size, 1953525168);
+        assert_eq!(namespace.lba_formats, vec![
+            LbaFormat { metadata_size: 0, block_size: 512, relative_performance: 2 },
+            LbaFormat { metadata_size: 8, block_size: 4096, relative_performance: 0 },
+        ]);
+        assert_eq!(namespace.block_size(), 4096);
+    }
+
+    #[test]
+    fn completion_status_is_decoded() {
+        let success = NvmeCompletion { dw0: 0, dw1: 0, dw2: 0, dw3: 0x0001_0000 }; // Phase tag only
+        assert!(success.is_success());
+        let invalid_field = NvmeCompletion { dw3: 0x02 << 17, ..success };
+        assert_eq!(invalid_field.status_code(), 0x02);
+        assert_eq!(invalid_field.status_code_type(), 0);
+        assert!(!invalid_field.is_success());
+    }
+
+    #[test]
+    fn bounce_chunks_are_whole_blocks() {
+        let mut media = MediaInfo { media_id: 0, removable: false, present: true, logical_partition: false, read_only: false, write_caching: false, block_size: 512, io_align: 0, last_block: 0 };
+        assert_eq!(bounce_chunk_size(&media), MAX_BOUNCE_BUFFER_SIZE);
+        media.block_size = 3000;
+        assert_eq!(bounce_chunk_size(&media) % 3000, 0);
+        media.block_size = 128 * 1024;
+        assert_eq!(bounce_chunk_size(&media), 128 * 1024);
+    }
+}
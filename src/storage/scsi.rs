@@ -0,0 +1,484 @@
+use ffi::{
+    scsi::{
+        EFI_EXT_SCSI_PASS_THRU_PROTOCOL,
+        EFI_EXT_SCSI_PASS_THRU_PROTOCOL_GUID,
+        EFI_EXT_SCSI_PASS_THRU_SCSI_REQUEST_PACKET,
+        EFI_EXT_SCSI_DATA_DIRECTION_READ,
+        EFI_EXT_SCSI_DATA_DIRECTION_WRITE,
+        EFI_EXT_SCSI_STATUS_HOST_ADAPTER_OK,
+        EFI_EXT_SCSI_STATUS_TARGET_GOOD,
+        EFI_EXT_SCSI_STATUS_TARGET_CHECK_CONDITION,
+        TARGET_MAX_BYTES,
+    },
+    device_path::EFI_DEVICE_PATH_PROTOCOL,
+    EFI_HANDLE,
+    EFI_SUCCESS,
+    EFI_NOT_FOUND,
+    EFI_DEVICE_ERROR,
+    IsSuccess,
+    VOID,
+};
+use super::{is_aligned, aligned_bounce_buffer, ascii_field};
+use device_path::DevicePath;
+use events::as_100ns_units;
+use byteorder::{ByteOrder, BigEndian};
+use utils::{handles_by_protocol, open_protocol};
+use alloc::{Vec, String};
+use core::{ptr, slice, cmp, time::Duration};
+use {Result, EfiErrorKind};
+
+const SENSE_DATA_SIZE: usize = 252;
+const INQUIRY_DATA_SIZE: usize = 36;
+const READ_CAPACITY_16_DATA_SIZE: usize = 32;
+const SCSI_DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const OPCODE_TEST_UNIT_READY: u8 = 0x00;
+const OPCODE_INQUIRY: u8 = 0x12;
+const OPCODE_READ_16: u8 = 0x88;
+const OPCODE_WRITE_16: u8 = 0x8A;
+const OPCODE_SERVICE_ACTION_IN_16: u8 = 0x9E;
+const SERVICE_ACTION_READ_CAPACITY_16: u8 = 0x10;
+const FUA_BIT: u8 = 0x08;
+
+pub const SENSE_KEY_NO_SENSE: u8 = 0x0;
+pub const SENSE_KEY_RECOVERED_ERROR: u8 = 0x1;
+pub const SENSE_KEY_NOT_READY: u8 = 0x2;
+pub const SENSE_KEY_MEDIUM_ERROR: u8 = 0x3;
+pub const SENSE_KEY_HARDWARE_ERROR: u8 = 0x4;
+pub const SENSE_KEY_ILLEGAL_REQUEST: u8 = 0x5;
+pub const SENSE_KEY_UNIT_ATTENTION: u8 = 0x6;
+pub const SENSE_KEY_DATA_PROTECT: u8 = 0x7;
+pub const SENSE_KEY_ABORTED_COMMAND: u8 = 0xB;
+
+// Additional sense code for "medium not present"
+const ASC_MEDIUM_NOT_PRESENT: u8 = 0x3A;
+// Additional sense code for "invalid command operation code"
+const ASC_INVALID_COMMAND: u8 = 0x20;
+
+/// A logical unit on a SCSI channel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ScsiTarget {
+    /// Transport specific target address
+    pub target: [u8; TARGET_MAX_BYTES],
+    pub lun: u64,
+}
+
+/// A SCSI Command Descriptor Block.
+/// Constructors are provided for common commands. Anything else can be built from raw bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cdb {
+    bytes: [u8; 16],
+    len: usize,
+}
+
+impl Cdb {
+    /// A CDB made of the given bytes. Fails with `InvalidParameter` if there are more than 16.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.is_empty() || bytes.len() > 16 {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let mut cdb = Cdb { bytes: [0; 16], len: bytes.len() };
+        cdb.bytes[..bytes.len()].copy_from_slice(bytes);
+        Ok(cdb)
+    }
+
+    pub fn test_unit_ready() -> Self {
+        Cdb { bytes: [OPCODE_TEST_UNIT_READY, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], len: 6 }
+    }
+
+    /// Standard INQUIRY returning up to `allocation_length` bytes
+    pub fn inquiry(allocation_length: u16) -> Self {
+        let mut cdb = Cdb { bytes: [0; 16], len: 6 };
+        cdb.bytes[0] = OPCODE_INQUIRY;
+        BigEndian::write_u16(&mut cdb.bytes[3..5], allocation_length);
+        cdb
+    }
+
+    /// READ CAPACITY (16) returning up to `allocation_length` bytes
+    pub fn read_capacity_16(allocation_length: u32) -> Self {
+        let mut cdb = Cdb { bytes: [0; 16], len: 16 };
+        cdb.bytes[0] = OPCODE_SERVICE_ACTION_IN_16;
+        cdb.bytes[1] = SERVICE_ACTION_READ_CAPACITY_16;
+        BigEndian::write_u32(&mut cdb.bytes[10..14], allocation_length);
+        cdb
+    }
+
+    /// READ (16) of `blocks` blocks starting at `lba`
+    pub fn read_16(lba: u64, blocks: u32) -> Self {
+        Self::rw_16(OPCODE_READ_16, lba, blocks)
+    }
+
+    /// WRITE (16) of `blocks` blocks starting at `lba`
+    pub fn write_16(lba: u64, blocks: u32) -> Self {
+        Self::rw_16(OPCODE_WRITE_16, lba, blocks)
+    }
+
+    /// Sets the Force Unit Access bit of a READ (16) or WRITE (16) so the data goes to or comes from the medium
+    /// rather than a cache. Has no effect on other commands.
+    pub fn force_unit_access(mut self, fua: bool) -> Self {
+        if self.bytes[0] == OPCODE_READ_16 || self.bytes[0] == OPCODE_WRITE_16 {
+            if fua { self.bytes[1] |= FUA_BIT } else { self.bytes[1] &= !FUA_BIT }
+        }
+        self
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    fn rw_16(opcode: u8, lba: u64, blocks: u32) -> Self {
+        let mut cdb = Cdb { bytes: [0; 16], len: 16 };
+        cdb.bytes[0] = opcode;
+        BigEndian::write_u64(&mut cdb.bytes[2..10], lba);
+        BigEndian::write_u32(&mut cdb.bytes[10..14], blocks);
+        cdb
+    }
+}
+
+/// The data phase of a SCSI command
+pub enum ScsiData<'a> {
+    None,
+    /// Data from the device to the host
+    In(&'a mut [u8]),
+    /// Data from the host to the device
+    Out(&'a [u8]),
+}
+
+/// The key fields of the sense data returned with a CHECK CONDITION status
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SenseData {
+    /// One of the `SENSE_KEY_*` constants
+    pub key: u8,
+    /// Additional sense code
+    pub asc: u8,
+    /// Additional sense code qualifier
+    pub ascq: u8,
+}
+
+impl SenseData {
+    /// Parses fixed or descriptor format sense data
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.is_empty() {
+            return None;
+        }
+
+        match bytes[0] & 0x7F {
+            0x70 | 0x71 if bytes.len() >= 14 => Some(SenseData { key: bytes[2] & 0xF, asc: bytes[12], ascq: bytes[13] }),
+            0x72 | 0x73 if bytes.len() >= 4 => Some(SenseData { key: bytes[1] & 0xF, asc: bytes[2], ascq: bytes[3] }),
+            _ => None
+        }
+    }
+
+    /// The closest UEFI error for this sense data
+    pub fn error_kind(&self) -> EfiErrorKind {
+        match self.key {
+            SENSE_KEY_NOT_READY if self.asc == ASC_MEDIUM_NOT_PRESENT => EfiErrorKind::NoMedia,
+            SENSE_KEY_NOT_READY => EfiErrorKind::NotReady,
+            SENSE_KEY_ILLEGAL_REQUEST if self.asc == ASC_INVALID_COMMAND => EfiErrorKind::Unsupported,
+            SENSE_KEY_ILLEGAL_REQUEST => EfiErrorKind::InvalidParameter,
+            SENSE_KEY_UNIT_ATTENTION => EfiErrorKind::MediaChanged,
+            SENSE_KEY_DATA_PROTECT => EfiErrorKind::WriteProtected,
+            SENSE_KEY_ABORTED_COMMAND => EfiErrorKind::Aborted,
+            _ => EfiErrorKind::DeviceError
+        }
+    }
+}
+
+/// Standard INQUIRY data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InquiryData {
+    /// 0x00 for disks, 0x05 for CD/DVD drives etc.
+    pub peripheral_device_type: u8,
+    pub removable: bool,
+    pub vendor_id: String,
+    pub product_id: String,
+    pub product_revision: String,
+}
+
+impl InquiryData {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < INQUIRY_DATA_SIZE {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        Ok(InquiryData {
+            peripheral_device_type: bytes[0] & 0x1F,
+            removable: bytes[1] & 0x80 != 0,
+            vendor_id: ascii_field(&bytes[8..16]),
+            product_id: ascii_field(&bytes[16..32]),
+            product_revision: ascii_field(&bytes[32..36]),
+        })
+    }
+}
+
+/// Data returned by READ CAPACITY (16)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Capacity {
+    pub last_lba: u64,
+    pub block_size: u32,
+}
+
+impl Capacity {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 12 {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        Ok(Capacity { last_lba: BigEndian::read_u64(&bytes[0..8]), block_size: BigEndian::read_u32(&bytes[8..12]) })
+    }
+}
+
+/// A SCSI channel exposed through EFI_EXT_SCSI_PASS_THRU_PROTOCOL
+pub struct ScsiPassthru {
+    handle: EFI_HANDLE,
+    protocol: *const EFI_EXT_SCSI_PASS_THRU_PROTOCOL,
+    // The data, CDB and sense buffers all have to meet the controller's alignment requirement
+    bounce: Vec<u8>,
+    cdb: Vec<u8>,
+    sense: Vec<u8>,
+    last_sense: Option<SenseData>,
+}
+
+impl ScsiPassthru {
+    /// Opens the SCSI channel on the given handle
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol::<EFI_EXT_SCSI_PASS_THRU_PROTOCOL>(handle, &EFI_EXT_SCSI_PASS_THRU_PROTOCOL_GUID)?;
+
+        Ok(ScsiPassthru { handle, protocol, bounce: Vec::new(), cdb: Vec::new(), sense: Vec::new(), last_sense: None })
+    }
+
+    /// Opens all SCSI channels in the system
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_EXT_SCSI_PASS_THRU_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    /// The handle the channel is on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    /// All targets and LUNs present on the channel
+    pub fn targets(&self) -> Result<Vec<ScsiTarget>> {
+        let mut targets = Vec::new();
+        let mut target = [0xFF_u8; TARGET_MAX_BYTES]; // All 0xFF asks for the first target
+        let mut lun = 0;
+        loop {
+            let mut target_ptr = target.as_mut_ptr();
+            let status = unsafe { ((*self.protocol).GetNextTargetLun)(self.protocol, &mut target_ptr, &mut lun) };
+            match status {
+                EFI_SUCCESS => {
+                    target.copy_from_slice(unsafe { slice::from_raw_parts(target_ptr, TARGET_MAX_BYTES) });
+                    targets.push(ScsiTarget { target, lun });
+                },
+                EFI_NOT_FOUND => return Ok(targets),
+                s => return Err(s.into())
+            }
+        }
+    }
+
+    /// The device path of the given target
+    pub fn device_path(&self, target: &ScsiTarget) -> Result<DevicePath> {
+        let mut path: *const EFI_DEVICE_PATH_PROTOCOL = ptr::null();
+        unsafe {
+            ret_on_err!(((*self.protocol).BuildDevicePath)(self.protocol, target.target.as_ptr(), target.lun, &mut path));
+        }
+
+        DevicePath::from_ptr(path)
+    }
+
+    /// Resets the given target
+    pub fn reset(&self, target: &ScsiTarget) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).ResetTargetLun)(self.protocol, target.target.as_ptr(), target.lun));
+        }
+
+        Ok(())
+    }
+
+    /// Sends a command and waits for it to complete. Returns the number of bytes transferred.
+    /// If the target reports CHECK CONDITION the sense data is mapped to an error and kept for `last_sense()`.
+    /// A timeout of `None` waits indefinitely.
+    pub fn execute(&mut self, target: &ScsiTarget, cdb: &Cdb, mut data: ScsiData, timeout: Option<Duration>) -> Result<usize> {
+        let io_align = unsafe { (*(*self.protocol).Mode).IoAlign };
+        self.last_sense = None;
+
+        let cdb_buf = aligned_bounce_buffer(&mut self.cdb, cdb.as_bytes().len(), io_align);
+        cdb_buf.copy_from_slice(cdb.as_bytes());
+        let cdb_ptr = cdb_buf.as_mut_ptr();
+        let sense_ptr = aligned_bounce_buffer(&mut self.sense, SENSE_DATA_SIZE, io_align).as_mut_ptr();
+
+        let mut packet = EFI_EXT_SCSI_PASS_THRU_SCSI_REQUEST_PACKET {
+            Timeout: timeout.as_ref().map_or(0, as_100ns_units),
+            InDataBuffer: ptr::null_mut(),
+            OutDataBuffer: ptr::null_mut(),
+            SenseData: sense_ptr as *mut VOID,
+            Cdb: cdb_ptr as *mut VOID,
+            InTransferLength: 0,
+            OutTransferLength: 0,
+            CdbLength: cdb.as_bytes().len() as u8,
+            DataDirection: EFI_EXT_SCSI_DATA_DIRECTION_READ,
+            HostAdapterStatus: 0,
+            TargetStatus: 0,
+            SenseDataLength: SENSE_DATA_SIZE as u8,
+        };
+
+        let data_len = match data {
+            ScsiData::In(ref buf) => buf.len(),
+            ScsiData::Out(ref buf) => buf.len(),
+            ScsiData::None => 0,
+        };
+        if data_len > u32::max_value() as usize {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        let mut bounced_in = None;
+        match data {
+            ScsiData::None => (),
+            ScsiData::In(ref mut buf) => {
+                packet.InTransferLength = buf.len() as u32;
+                packet.InDataBuffer = if is_aligned(buf.as_ptr(), io_align) {
+                    buf.as_mut_ptr() as *mut VOID
+                } else {
+                    bounced_in = Some(buf.len());
+                    aligned_bounce_buffer(&mut self.bounce, buf.len(), io_align).as_mut_ptr() as *mut VOID
+                };
+            },
+            ScsiData::Out(buf) => {
+                packet.OutTransferLength = buf.len() as u32;
+                packet.DataDirection = EFI_EXT_SCSI_DATA_DIRECTION_WRITE;
+                packet.OutDataBuffer = if is_aligned(buf.as_ptr(), io_align) {
+                    buf.as_ptr() as *mut VOID
+                } else {
+                    let bounce = aligned_bounce_buffer(&mut self.bounce, buf.len(), io_align);
+                    bounce.copy_from_slice(buf);
+                    bounce.as_mut_ptr() as *mut VOID
+                };
+            },
+        }
+
+        let status = unsafe { ((*self.protocol).PassThru)(self.protocol, target.target.as_ptr(), target.lun, &mut packet, ptr::null()) };
+
+        // With a device error the statuses and the sense data tell us what actually went wrong
+        if !IsSuccess(status) && status != EFI_DEVICE_ERROR {
+            return Err(status.into());
+        }
+
+        if packet.TargetStatus == EFI_EXT_SCSI_STATUS_TARGET_CHECK_CONDITION {
+            let sense_len = cmp::min(packet.SenseDataLength as usize, SENSE_DATA_SIZE);
+            let sense = SenseData::parse(&aligned_bounce_buffer(&mut self.sense, SENSE_DATA_SIZE, io_align)[..sense_len]);
+            self.last_sense = sense;
+            return Err(sense.map_or(EfiErrorKind::DeviceError, |s| s.error_kind()).into());
+        }
+
+        if !IsSuccess(status) || packet.HostAdapterStatus != EFI_EXT_SCSI_STATUS_HOST_ADAPTER_OK || packet.TargetStatus != EFI_EXT_SCSI_STATUS_TARGET_GOOD {
+            return Err(EfiErrorKind::DeviceError.into());
+        }
+
+        match data {
+            ScsiData::In(buf) => {
+                let transferred = cmp::min(packet.InTransferLength as usize, buf.len());
+                if let Some(len) = bounced_in {
+                    buf[..transferred].copy_from_slice(&aligned_bounce_buffer(&mut self.bounce, len, io_align)[..transferred]);
+                }
+                Ok(transferred)
+            },
+            ScsiData::Out(_) => Ok(packet.OutTransferLength as usize),
+            ScsiData::None => Ok(0),
+        }
+    }
+
+    /// The sense data of the last command that failed with CHECK CONDITION
+    pub fn last_sense(&self) -> Option<SenseData> {
+        self.last_sense
+    }
+
+    /// Whether the target is ready to accept media access commands
+    pub fn test_unit_ready(&mut self, target: &ScsiTarget) -> Result<()> {
+        self.execute(target, &Cdb::test_unit_ready(), ScsiData::None, Some(SCSI_DEFAULT_TIMEOUT)).map(|_| ())
+    }
+
+    pub fn inquiry(&mut self, target: &ScsiTarget) -> Result<InquiryData> {
+        let mut data = [0_u8; INQUIRY_DATA_SIZE];
+        let len = self.execute(target, &Cdb::inquiry(INQUIRY_DATA_SIZE as u16), ScsiData::In(&mut data), Some(SCSI_DEFAULT_TIMEOUT))?;
+        InquiryData::parse(&data[..len])
+    }
+
+    pub fn read_capacity(&mut self, target: &ScsiTarget) -> Result<Capacity> {
+        let mut data = [0_u8; READ_CAPACITY_16_DATA_SIZE];
+        let len = self.execute(target, &Cdb::read_capacity_16(READ_CAPACITY_16_DATA_SIZE as u32), ScsiData::In(&mut data), Some(SCSI_DEFAULT_TIMEOUT))?;
+        Capacity::parse(&data[..len])
+    }
+
+    /// Reads whole blocks starting at the given LBA. The length of the buffer must be a multiple of the block size.
+    pub fn read_blocks(&mut self, target: &ScsiTarget, block_size: u32, lba: u64, buf: &mut [u8]) -> Result<()> {
+        let blocks = block_count(block_size, buf.len())?;
+        self.execute(target, &Cdb::read_16(lba, blocks), ScsiData::In(buf), Some(SCSI_DEFAULT_TIMEOUT)).map(|_| ())
+    }
+
+    /// Writes whole blocks starting at the given LBA. The length of the buffer must be a multiple of the block size.
+    pub fn write_blocks(&mut self, target: &ScsiTarget, block_size: u32, lba: u64, buf: &[u8]) -> Result<()> {
+        let blocks = block_count(block_size, buf.len())?;
+        self.execute(target, &Cdb::write_16(lba, blocks), ScsiData::Out(buf), Some(SCSI_DEFAULT_TIMEOUT)).map(|_| ())
+    }
+}
+
+fn block_count(block_size: u32, len: usize) -> Result<u32> {
+    if block_size == 0 || len % block_size as usize != 0 || len / block_size as usize > u32::max_value() as usize {
+        return Err(EfiErrorKind::BadBufferSize.into());
+    }
+
+    Ok((len / block_size as usize) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cdbs_are_encoded() {
+        assert_eq!(Cdb::inquiry(36).as_bytes(), &[0x12, 0, 0, 0, 36, 0]);
+        assert_eq!(Cdb::read_capacity_16(32).as_bytes(), &[0x9E, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 32, 0, 0]);
+        assert_eq!(Cdb::read_16(0x0102030405060708, 8).force_unit_access(true).as_bytes(), &[0x88, 0x08, 1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 8, 0, 0]);
+        assert_eq!(Cdb::write_16(1, 1).as_bytes()[0], 0x8A);
+        assert_eq!(Cdb::test_unit_ready().force_unit_access(true).as_bytes(), &[0; 6]);
+        assert!(Cdb::from_bytes(&[0; 17]).is_err());
+    }
+
+    #[test]
+    fn sense_data_maps_to_errors() {
+        let mut fixed = [0_u8; 18];
+        fixed[0] = 0x70;
+        fixed[2] = SENSE_KEY_NOT_READY;
+        fixed[12] = ASC_MEDIUM_NOT_PRESENT;
+        let sense = SenseData::parse(&fixed).unwrap();
+        assert_eq!(sense, SenseData { key: SENSE_KEY_NOT_READY, asc: ASC_MEDIUM_NOT_PRESENT, ascq: 0 });
+        assert_eq!(sense.error_kind(), EfiErrorKind::NoMedia);
+
+        let descriptor = [0x72, SENSE_KEY_DATA_PROTECT, 0x27, 0x00];
+        assert_eq!(SenseData::parse(&descriptor).unwrap().error_kind(), EfiErrorKind::WriteProtected);
+
+        assert_eq!(SenseData::parse(&[0x70, 0, 0]), None);
+        assert_eq!(SenseData::parse(&[0x00; 18]), None);
+    }
+
+    #[test]
+    fn parses_inquiry_and_capacity() {
+        let mut inquiry = [0_u8; 36];
+        inquiry[0] = 0x05;
+        inquiry[1] = 0x80;
+        inquiry[8..16].copy_from_slice(b"QEMU    ");
+        inquiry[16..32].copy_from_slice(b"QEMU CD-ROM     ");
+        inquiry[32..36].copy_from_slice(b"2.5+");
+        let inquiry = InquiryData::parse(&inquiry).unwrap();
+        assert_eq!(inquiry.peripheral_device_type, 0x05);
+        assert!(inquiry.removable);
+        assert_eq!(inquiry.vendor_id, "QEMU");
+        assert_eq!(inquiry.product_id, "QEMU CD-ROM");
+        assert_eq!(inquiry.product_revision, "2.5+");
+
+        let capacity = [0, 0, 0, 0, 0, 0x3F, 0xFF, 0xFF, 0, 0, 0x02, 0x00];
+        assert_eq!(Capacity::parse(&capacity).unwrap(), Capacity { last_lba: 0x3FFFFF, block_size: 512 });
+        assert!(Capacity::parse(&capacity[..8]).is_err());
+    }
+}
@@ -0,0 +1,587 @@
+use ffi::{
+    media::{EFI_STORAGE_SECURITY_COMMAND_PROTOCOL, EFI_STORAGE_SECURITY_COMMAND_PROTOCOL_GUID},
+    EFI_HANDLE,
+    VOID,
+};
+use super::{BlockDevice, is_aligned, aligned_bounce_buffer};
+use events::as_100ns_units;
+use byteorder::{ByteOrder, BigEndian};
+use utils::{handles_by_protocol, open_protocol};
+use alloc::Vec;
+use core::{cmp, time::Duration};
+use {Result, EfiErrorKind};
+
+/// Lists the security protocols the device supports
+pub const SECURITY_PROTOCOL_INFORMATION: u8 = 0x00;
+/// TCG storage commands e.g. Opal discovery and sessions
+pub const SECURITY_PROTOCOL_TCG_1: u8 = 0x01;
+/// TCG ComID management
+pub const SECURITY_PROTOCOL_TCG_2: u8 = 0x02;
+
+pub const TCG_FEATURE_TPER: u16 = 0x0001;
+pub const TCG_FEATURE_LOCKING: u16 = 0x0002;
+pub const TCG_FEATURE_GEOMETRY: u16 = 0x0003;
+pub const TCG_FEATURE_ENTERPRISE: u16 = 0x0100;
+pub const TCG_FEATURE_OPAL_V1: u16 = 0x0200;
+pub const TCG_FEATURE_OPAL_V2: u16 = 0x0203;
+pub const TCG_FEATURE_OPALITE: u16 = 0x0301;
+pub const TCG_FEATURE_PYRITE_V1: u16 = 0x0302;
+pub const TCG_FEATURE_PYRITE_V2: u16 = 0x0303;
+pub const TCG_FEATURE_RUBY: u16 = 0x0304;
+
+const SECURITY_DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const TCG_TRANSFER_SIZE: usize = 2048;
+const TCG_LEVEL_0_DISCOVERY_COMID: u16 = 0x0001;
+const LEVEL_0_HEADER_SIZE: usize = 48;
+// Number of times to poll for a response the TPer hasn't finished preparing yet
+const TCG_RESPONSE_POLLS: usize = 100;
+
+const COM_PACKET_HEADER_SIZE: usize = 20;
+const PACKET_HEADER_SIZE: usize = 24;
+const SUB_PACKET_HEADER_SIZE: usize = 12;
+const PAYLOAD_OFFSET: usize = COM_PACKET_HEADER_SIZE + PACKET_HEADER_SIZE + SUB_PACKET_HEADER_SIZE;
+
+const TOKEN_START_LIST: u8 = 0xF0;
+const TOKEN_END_LIST: u8 = 0xF1;
+const TOKEN_START_NAME: u8 = 0xF2;
+const TOKEN_END_NAME: u8 = 0xF3;
+const TOKEN_CALL: u8 = 0xF8;
+const TOKEN_END_OF_DATA: u8 = 0xF9;
+const TOKEN_END_OF_SESSION: u8 = 0xFA;
+const TOKEN_EMPTY: u8 = 0xFF;
+
+const UID_SESSION_MANAGER: [u8; 8] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF];
+const UID_ADMIN_SP: [u8; 8] = [0x00, 0x00, 0x02, 0x05, 0x00, 0x00, 0x00, 0x01];
+const UID_PSID_AUTHORITY: [u8; 8] = [0x00, 0x00, 0x00, 0x09, 0x00, 0x01, 0xFF, 0x01];
+const METHOD_START_SESSION: [u8; 8] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x02];
+const METHOD_SYNC_SESSION: [u8; 8] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x03];
+const METHOD_REVERT: [u8; 8] = [0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x02, 0x02];
+
+// Names of the optional StartSession parameters
+const START_SESSION_HOST_CHALLENGE: u64 = 0;
+const START_SESSION_HOST_SIGNING_AUTHORITY: u64 = 3;
+const HOST_SESSION_ID: u64 = 1;
+
+const METHOD_STATUS_SUCCESS: u64 = 0x00;
+const METHOD_STATUS_NOT_AUTHORIZED: u64 = 0x01;
+const METHOD_STATUS_SP_BUSY: u64 = 0x03;
+const METHOD_STATUS_INVALID_PARAMETER: u64 = 0x0C;
+const METHOD_STATUS_AUTHORITY_LOCKED_OUT: u64 = 0x12;
+
+/// A feature descriptor from the TCG Level 0 discovery response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Level0Feature {
+    /// One of the `TCG_FEATURE_*` constants or a vendor specific code
+    pub code: u16,
+    pub version: u8,
+    pub data: Vec<u8>,
+}
+
+/// The state of the Locking feature
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LockingFeature {
+    pub supported: bool,
+    pub enabled: bool,
+    pub locked: bool,
+    pub media_encryption: bool,
+    pub mbr_enabled: bool,
+    pub mbr_done: bool,
+}
+
+/// The TCG Security Subsystem Classes a device can implement
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SscKind {
+    Enterprise,
+    OpalV1,
+    OpalV2,
+    Opalite,
+    PyriteV1,
+    PyriteV2,
+    Ruby,
+}
+
+/// The Security Subsystem Class a device implements along with the ComIDs used to talk to it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Ssc {
+    pub kind: SscKind,
+    pub base_comid: u16,
+    pub num_comids: u16,
+}
+
+/// The response to a TCG Level 0 discovery, which describes the TCG features of a self-encrypting drive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Level0Discovery {
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub features: Vec<Level0Feature>,
+}
+
+impl Level0Discovery {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < LEVEL_0_HEADER_SIZE {
+            return Err(EfiErrorKind::BadBufferSize.into());
+        }
+
+        // The length doesn't count the length field itself
+        let end = cmp::min(BigEndian::read_u32(&bytes[0..4]) as usize + 4, bytes.len());
+        let mut features = Vec::new();
+        let mut offset = LEVEL_0_HEADER_SIZE;
+        while offset + 4 <= end {
+            let data_len = bytes[offset + 3] as usize;
+            let data_end = offset + 4 + data_len;
+            if data_end > end {
+                return Err(EfiErrorKind::ProtocolError.into());
+            }
+
+            features.push(Level0Feature {
+                code: BigEndian::read_u16(&bytes[offset..offset + 2]),
+                version: bytes[offset + 2] >> 4,
+                data: bytes[offset + 4..data_end].to_vec(),
+            });
+            offset = data_end;
+        }
+
+        Ok(Level0Discovery {
+            major_version: BigEndian::read_u16(&bytes[4..6]),
+            minor_version: BigEndian::read_u16(&bytes[6..8]),
+            features,
+        })
+    }
+
+    /// The descriptor of the feature with the given code
+    pub fn feature(&self, code: u16) -> Option<&Level0Feature> {
+        self.features.iter().find(|f| f.code == code)
+    }
+
+    /// Whether the device is a TCG device at all
+    pub fn has_tper(&self) -> bool {
+        self.feature(TCG_FEATURE_TPER).is_some()
+    }
+
+    pub fn locking(&self) -> Option<LockingFeature> {
+        self.feature(TCG_FEATURE_LOCKING).and_then(|f| f.data.first()).map(|flags| LockingFeature {
+            supported: flags & 0x01 != 0,
+            enabled: flags & 0x02 != 0,
+            locked: flags & 0x04 != 0,
+            media_encryption: flags & 0x08 != 0,
+            mbr_enabled: flags & 0x10 != 0,
+            mbr_done: flags & 0x20 != 0,
+        })
+    }
+
+    /// The Security Subsystem Class the device implements. If there are several the newest one is picked.
+    pub fn ssc(&self) -> Option<Ssc> {
+        let kinds = [
+            (TCG_FEATURE_RUBY, SscKind::Ruby),
+            (TCG_FEATURE_OPAL_V2, SscKind::OpalV2),
+            (TCG_FEATURE_OPALITE, SscKind::Opalite),
+            (TCG_FEATURE_PYRITE_V2, SscKind::PyriteV2),
+            (TCG_FEATURE_PYRITE_V1, SscKind::PyriteV1),
+            (TCG_FEATURE_OPAL_V1, SscKind::OpalV1),
+            (TCG_FEATURE_ENTERPRISE, SscKind::Enterprise),
+        ];
+
+        kinds.iter()
+            .filter_map(|&(code, kind)| self.feature(code).map(|f| (kind, f)))
+            .find(|&(_, f)| f.data.len() >= 4)
+            .map(|(kind, f)| Ssc { kind, base_comid: BigEndian::read_u16(&f.data[0..2]), num_comids: BigEndian::read_u16(&f.data[2..4]) })
+    }
+}
+
+/// A device that accepts security protocol commands (e.g. TCG Opal) through EFI_STORAGE_SECURITY_COMMAND_PROTOCOL
+pub struct StorageSecurity {
+    block_device: BlockDevice,
+    protocol: *const EFI_STORAGE_SECURITY_COMMAND_PROTOCOL,
+    bounce: Vec<u8>,
+}
+
+impl StorageSecurity {
+    /// Opens the device on the given handle. The handle must support both EFI_STORAGE_SECURITY_COMMAND_PROTOCOL and EFI_BLOCK_IO_PROTOCOL.
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let block_device = BlockDevice::from_handle(handle)?;
+        let protocol = open_protocol::<EFI_STORAGE_SECURITY_COMMAND_PROTOCOL>(handle, &EFI_STORAGE_SECURITY_COMMAND_PROTOCOL_GUID)?;
+
+        Ok(StorageSecurity { block_device, protocol, bounce: Vec::new() })
+    }
+
+    /// Opens all devices in the system that accept security protocol commands
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_STORAGE_SECURITY_COMMAND_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    /// The handle the device is on
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.block_device.handle()
+    }
+
+    /// Receives the result of a security protocol command into the buffer. Returns the number of bytes received.
+    /// `protocol_specific` is given in host byte order e.g. a TCG ComID. A timeout of `None` waits indefinitely.
+    pub fn receive(&mut self, protocol_id: u8, protocol_specific: u16, buf: &mut [u8], timeout: Option<Duration>) -> Result<usize> {
+        let media = self.block_device.media();
+        let bounced = !is_aligned(buf.as_ptr(), media.io_align);
+        let payload = if bounced {
+            aligned_bounce_buffer(&mut self.bounce, buf.len(), media.io_align).as_mut_ptr()
+        } else {
+            buf.as_mut_ptr()
+        };
+
+        let mut transferred = 0;
+        unsafe {
+            ret_on_err!(((*self.protocol).ReceiveData)(self.protocol, media.media_id, timeout.as_ref().map_or(0, as_100ns_units), protocol_id, protocol_specific.to_be(), buf.len(), payload as *mut VOID, &mut transferred));
+        }
+
+        let transferred = cmp::min(transferred, buf.len());
+        if bounced {
+            buf[..transferred].copy_from_slice(aligned_bounce_buffer(&mut self.bounce, transferred, media.io_align));
+        }
+
+        Ok(transferred)
+    }
+
+    /// Sends a security protocol command along with its data.
+    /// `protocol_specific` is given in host byte order e.g. a TCG ComID. A timeout of `None` waits indefinitely.
+    pub fn send(&mut self, protocol_id: u8, protocol_specific: u16, data: &[u8], timeout: Option<Duration>) -> Result<()> {
+        let media = self.block_device.media();
+        let payload = if is_aligned(data.as_ptr(), media.io_align) {
+            data.as_ptr()
+        } else {
+            let bounce = aligned_bounce_buffer(&mut self.bounce, data.len(), media.io_align);
+            bounce.copy_from_slice(data);
+            bounce.as_ptr()
+        };
+
+        unsafe {
+            ret_on_err!(((*self.protocol).SendData)(self.protocol, media.media_id, timeout.as_ref().map_or(0, as_100ns_units), protocol_id, protocol_specific.to_be(), data.len(), payload as *const VOID));
+        }
+
+        Ok(())
+    }
+
+    /// The IDs of the security protocols the device supports
+    pub fn supported_protocols(&mut self) -> Result<Vec<u8>> {
+        let mut buf = vec![0_u8; 512];
+        let len = self.receive(SECURITY_PROTOCOL_INFORMATION, 0, &mut buf, Some(SECURITY_DEFAULT_TIMEOUT))?;
+        if len < 8 {
+            return Err(EfiErrorKind::ProtocolError.into());
+        }
+
+        let count = cmp::min(BigEndian::read_u16(&buf[6..8]) as usize, len - 8);
+        Ok(buf[8..8 + count].to_vec())
+    }
+
+    /// Asks the drive which TCG features (Opal, locking etc.) it supports and what state they're in
+    pub fn opal_discovery(&mut self) -> Result<Level0Discovery> {
+        let mut buf = vec![0_u8; TCG_TRANSFER_SIZE];
+        let len = self.receive(SECURITY_PROTOCOL_TCG_1, TCG_LEVEL_0_DISCOVERY_COMID, &mut buf, Some(SECURITY_DEFAULT_TIMEOUT))?;
+        Level0Discovery::parse(&buf[..len])
+    }
+
+    /// Reverts the drive to its factory state using the PSID printed on its label. This cryptographically erases ALL data
+    /// on the drive and resets all its passwords. Fails with `Unsupported` if the drive doesn't implement an SSC that
+    /// supports PSID revert and `AccessDenied` if the PSID is wrong.
+    pub fn psid_revert(&mut self, psid: &[u8]) -> Result<()> {
+        let comid = match self.opal_discovery()?.ssc() {
+            Some(Ssc { kind: SscKind::Enterprise, .. }) | Some(Ssc { kind: SscKind::OpalV1, .. }) | None => return Err(EfiErrorKind::Unsupported.into()),
+            Some(ssc) => ssc.base_comid,
+        };
+
+        let mut start_session = TokenWriter::new();
+        start_session.call(&UID_SESSION_MANAGER, &METHOD_START_SESSION);
+        start_session.uint(HOST_SESSION_ID);
+        start_session.bytes(&UID_ADMIN_SP);
+        start_session.uint(1); // Write session
+        start_session.named(START_SESSION_HOST_CHALLENGE, |w| w.bytes(psid));
+        start_session.named(START_SESSION_HOST_SIGNING_AUTHORITY, |w| w.bytes(&UID_PSID_AUTHORITY));
+        start_session.end_call();
+
+        let response = self.tcg_call(comid, 0, 0, &start_session.into_bytes())?;
+        let (host_session, tper_session) = sync_session_ids(&response)?;
+
+        let mut revert = TokenWriter::new();
+        revert.call(&UID_ADMIN_SP, &METHOD_REVERT);
+        revert.end_call();
+
+        // A successful revert ends the session on its own
+        match self.tcg_call(comid, tper_session, host_session, &revert.into_bytes()) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let _ = self.send(SECURITY_PROTOCOL_TCG_1, comid, &com_packet(comid, tper_session, host_session, &[TOKEN_END_OF_SESSION]), Some(SECURITY_DEFAULT_TIMEOUT));
+                Err(e)
+            }
+        }
+    }
+
+    // Sends a method call and returns the tokens of the response once its status has been checked
+    fn tcg_call(&mut self, comid: u16, tper_session: u32, host_session: u32, payload: &[u8]) -> Result<Vec<Token>> {
+        self.send(SECURITY_PROTOCOL_TCG_1, comid, &com_packet(comid, tper_session, host_session, payload), Some(SECURITY_DEFAULT_TIMEOUT))?;
+
+        let mut buf = vec![0_u8; TCG_TRANSFER_SIZE];
+        for _ in 0..TCG_RESPONSE_POLLS {
+            let len = self.receive(SECURITY_PROTOCOL_TCG_1, comid, &mut buf, Some(SECURITY_DEFAULT_TIMEOUT))?;
+            if let Some(payload) = com_packet_payload(&buf[..len])? {
+                let tokens = decode_tokens(payload)?;
+                check_method_status(&tokens)?;
+                return Ok(tokens);
+            }
+        }
+
+        Err(EfiErrorKind::Timeout.into())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Uint(u64),
+    Bytes(Vec<u8>),
+    Control(u8),
+}
+
+struct TokenWriter(Vec<u8>);
+
+impl TokenWriter {
+    fn new() -> Self {
+        TokenWriter(Vec::new())
+    }
+
+    fn uint(&mut self, value: u64) {
+        if value < 64 {
+            self.0.push(value as u8); // Tiny atom
+            return;
+        }
+
+        let mut bytes = [0_u8; 8];
+        BigEndian::write_u64(&mut bytes, value);
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        self.0.push(0x80 | (8 - skip) as u8); // Short atom
+        self.0.extend_from_slice(&bytes[skip..]);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        if bytes.len() < 16 {
+            self.0.push(0xA0 | bytes.len() as u8); // Short atom
+        } else {
+            self.0.push(0xD0 | (bytes.len() >> 8) as u8 & 0x07); // Medium atom
+            self.0.push(bytes.len() as u8);
+        }
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn control(&mut self, token: u8) {
+        self.0.push(token);
+    }
+
+    fn named<F: FnOnce(&mut Self)>(&mut self, name: u64, value: F) {
+        self.control(TOKEN_START_NAME);
+        self.uint(name);
+        value(self);
+        self.control(TOKEN_END_NAME);
+    }
+
+    // Starts a method call. Arguments follow until `end_call`.
+    fn call(&mut self, invoking_uid: &[u8; 8], method_uid: &[u8; 8]) {
+        self.control(TOKEN_CALL);
+        self.bytes(invoking_uid);
+        self.bytes(method_uid);
+        self.control(TOKEN_START_LIST);
+    }
+
+    fn end_call(&mut self) {
+        self.control(TOKEN_END_LIST);
+        self.control(TOKEN_END_OF_DATA);
+        // The method status list. Always all zeros when sent by the host.
+        self.control(TOKEN_START_LIST);
+        self.uint(0);
+        self.uint(0);
+        self.uint(0);
+        self.control(TOKEN_END_LIST);
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+fn decode_tokens(bytes: &[u8]) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let b = bytes[offset];
+        let (header_len, data_len, is_bytes) = match b {
+            0x00...0x7F => {
+                tokens.push(Token::Uint((b & 0x3F) as u64)); // Tiny atom
+                offset += 1;
+                continue;
+            },
+            0x80...0xBF => (1, (b & 0x0F) as usize, b & 0x20 != 0),
+            0xC0...0xDF if offset + 1 < bytes.len() => (2, ((b & 0x07) as usize) << 8 | bytes[offset + 1] as usize, b & 0x10 != 0),
+            0xE0...0xE3 if offset + 3 < bytes.len() => (4, (bytes[offset + 1] as usize) << 16 | (bytes[offset + 2] as usize) << 8 | bytes[offset + 3] as usize, b & 0x02 != 0),
+            TOKEN_EMPTY => {
+                offset += 1;
+                continue;
+            },
+            0xF0...0xFE => {
+                tokens.push(Token::Control(b));
+                offset += 1;
+                continue;
+            },
+            _ => return Err(EfiErrorKind::ProtocolError.into())
+        };
+
+        let start = offset + header_len;
+        let end = start + data_len;
+        if end > bytes.len() {
+            return Err(EfiErrorKind::ProtocolError.into());
+        }
+
+        let data = &bytes[start..end];
+        if is_bytes {
+            tokens.push(Token::Bytes(data.to_vec()));
+        } else if data_len <= 8 {
+            tokens.push(Token::Uint(data.iter().fold(0, |v, b| v << 8 | *b as u64)));
+        } else {
+            return Err(EfiErrorKind::ProtocolError.into());
+        }
+        offset = end;
+    }
+
+    Ok(tokens)
+}
+
+// Wraps a token stream in a ComPacket, Packet and SubPacket padded to a whole number of 512 byte blocks
+fn com_packet(comid: u16, tper_session: u32, host_session: u32, payload: &[u8]) -> Vec<u8> {
+    let sub_packet_len = payload.len();
+    let padded_sub_packet_len = (sub_packet_len + 3) & !3;
+    let packet_len = SUB_PACKET_HEADER_SIZE + padded_sub_packet_len;
+    let com_packet_len = PACKET_HEADER_SIZE + packet_len;
+    let total_len = (COM_PACKET_HEADER_SIZE + com_packet_len + 511) & !511;
+
+    let mut buf = vec![0_u8; total_len];
+    BigEndian::write_u16(&mut buf[4..6], comid);
+    BigEndian::write_u32(&mut buf[16..20], com_packet_len as u32);
+    BigEndian::write_u32(&mut buf[20..24], tper_session);
+    BigEndian::write_u32(&mut buf[24..28], host_session);
+    BigEndian::write_u32(&mut buf[40..44], packet_len as u32);
+    BigEndian::write_u32(&mut buf[52..56], sub_packet_len as u32);
+    buf[PAYLOAD_OFFSET..PAYLOAD_OFFSET + sub_packet_len].copy_from_slice(payload);
+    buf
+}
+
+// The token stream in a received ComPacket. `None` if the TPer has nothing for us yet.
+fn com_packet_payload(bytes: &[u8]) -> Result<Option<&[u8]>> {
+    if bytes.len() < COM_PACKET_HEADER_SIZE {
+        return Err(EfiErrorKind::ProtocolError.into());
+    }
+
+    let com_packet_len = BigEndian::read_u32(&bytes[16..20]) as usize;
+    if com_packet_len == 0 {
+        return Ok(None);
+    }
+
+    if bytes.len() < PAYLOAD_OFFSET {
+        return Err(EfiErrorKind::ProtocolError.into());
+    }
+
+    let sub_packet_len = BigEndian::read_u32(&bytes[52..56]) as usize;
+    if PAYLOAD_OFFSET + sub_packet_len > bytes.len() {
+        return Err(EfiErrorKind::ProtocolError.into());
+    }
+
+    Ok(Some(&bytes[PAYLOAD_OFFSET..PAYLOAD_OFFSET + sub_packet_len]))
+}
+
+// Checks the status list that follows the end of data token in a method response
+fn check_method_status(tokens: &[Token]) -> Result<()> {
+    let end_of_data = tokens.iter().rposition(|t| *t == Token::Control(TOKEN_END_OF_DATA));
+    let status = end_of_data.and_then(|i| match (tokens.get(i + 1), tokens.get(i + 2)) {
+        (Some(&Token::Control(TOKEN_START_LIST)), Some(&Token::Uint(status))) => Some(status),
+        _ => None
+    });
+
+    match status {
+        Some(METHOD_STATUS_SUCCESS) => Ok(()),
+        Some(METHOD_STATUS_NOT_AUTHORIZED) | Some(METHOD_STATUS_AUTHORITY_LOCKED_OUT) => Err(EfiErrorKind::AccessDenied.into()),
+        Some(METHOD_STATUS_SP_BUSY) => Err(EfiErrorKind::NotReady.into()),
+        Some(METHOD_STATUS_INVALID_PARAMETER) => Err(EfiErrorKind::InvalidParameter.into()),
+        Some(_) => Err(EfiErrorKind::DeviceError.into()),
+        None => Err(EfiErrorKind::ProtocolError.into())
+    }
+}
+
+// Pulls the host and TPer session numbers out of a SyncSession response
+fn sync_session_ids(tokens: &[Token]) -> Result<(u32, u32)> {
+    let method = Token::Bytes(METHOD_SYNC_SESSION.to_vec());
+    let position = tokens.iter().position(|t| *t == method).ok_or(EfiErrorKind::ProtocolError)?;
+    match (tokens.get(position + 1), tokens.get(position + 2), tokens.get(position + 3)) {
+        (Some(&Token::Control(TOKEN_START_LIST)), Some(&Token::Uint(host)), Some(&Token::Uint(tper))) => Ok((host as u32, tper as u32)),
+        _ => Err(EfiErrorKind::ProtocolError.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_level_0_discovery() {
+        let mut data = vec![0_u8; 48];
+        data[4..8].copy_from_slice(&[0, 0, 0, 1]);
+        data.extend_from_slice(&[0x00, 0x01, 0x10, 0x0C, 0x11, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]); // TPer
+        data.extend_from_slice(&[0x00, 0x02, 0x10, 0x0C, 0x0B, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]); // Locking
+        data.extend_from_slice(&[0x02, 0x03, 0x10, 0x10, 0x10, 0x01, 0x00, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]); // Opal v2
+        let len = data.len() as u32 - 4;
+        BigEndian::write_u32(&mut data[0..4], len);
+        data.extend_from_slice(&[0; 16]); // Padding past the length must be ignored
+
+        let discovery = Level0Discovery::parse(&data).unwrap();
+        assert_eq!(discovery.minor_version, 1);
+        assert_eq!(discovery.features.len(), 3);
+        assert!(discovery.has_tper());
+        assert_eq!(discovery.locking(), Some(LockingFeature { supported: true, enabled: true, locked: false, media_encryption: true, mbr_enabled: false, mbr_done: false }));
+        assert_eq!(discovery.ssc(), Some(Ssc { kind: SscKind::OpalV2, base_comid: 0x1001, num_comids: 1 }));
+
+        BigEndian::write_u32(&mut data[0..4], len + 8);
+        let padding = data.len() - 16;
+        data[padding + 3] = 0xFF; // A descriptor that runs past the end
+        assert!(Level0Discovery::parse(&data).is_err());
+    }
+
+    #[test]
+    fn encodes_tokens() {
+        let mut w = TokenWriter::new();
+        w.uint(5);
+        w.uint(0x1234);
+        w.bytes(&[0xAA, 0xBB]);
+        w.bytes(&[0x11; 32]);
+        w.named(3, |w| w.uint(1));
+        let bytes = w.into_bytes();
+        assert_eq!(&bytes[..6], &[0x05, 0x82, 0x12, 0x34, 0xA2, 0xAA]);
+        assert_eq!(&bytes[7..9], &[0xD0, 32]);
+        assert_eq!(&bytes[41..], &[TOKEN_START_NAME, 3, 1, TOKEN_END_NAME]);
+
+        let tokens = decode_tokens(&bytes).unwrap();
+        assert_eq!(tokens[..4].to_vec(), vec![Token::Uint(5), Token::Uint(0x1234), Token::Bytes(vec![0xAA, 0xBB]), Token::Bytes(vec![0x11; 32])]);
+        assert_eq!(tokens[4], Token::Control(TOKEN_START_NAME));
+        assert!(decode_tokens(&[0xA4, 0x01]).is_err());
+    }
+
+    #[test]
+    fn parses_session_responses() {
+        let mut w = TokenWriter::new();
+        w.call(&UID_SESSION_MANAGER, &METHOD_SYNC_SESSION);
+        w.uint(HOST_SESSION_ID);
+        w.uint(0x1000_0001);
+        w.end_call();
+
+        let packet = com_packet(0x1001, 0, 0, &w.into_bytes());
+        assert_eq!(packet.len(), 512);
+        let tokens = decode_tokens(com_packet_payload(&packet).unwrap().unwrap()).unwrap();
+        check_method_status(&tokens).unwrap();
+        assert_eq!(sync_session_ids(&tokens).unwrap(), (1, 0x1000_0001));
+
+        let not_authorized = decode_tokens(&[TOKEN_START_LIST, TOKEN_END_LIST, TOKEN_END_OF_DATA, TOKEN_START_LIST, 0x01, 0, 0, TOKEN_END_LIST]).unwrap();
+        assert_eq!(check_method_status(&not_authorized).unwrap_err().kind(), EfiErrorKind::AccessDenied);
+
+        let empty = vec![0_u8; 512];
+        assert_eq!(com_packet_payload(&empty).unwrap(), None);
+    }
+}
@@ -1,10 +1,502 @@
-use ffi::UINTN;
-use core::time::Duration;
-use {system_table, Result};
+use ffi::{
+    EFI_TIME,
+    EFI_TIME_CAPABILITIES,
+    EFI_TIME_ADJUST_DAYLIGHT,
+    EFI_TIME_IN_DAYLIGHT,
+    EFI_UNSPECIFIED_TIMEZONE,
+    UINTN,
+    BOOLEAN,
+    TRUE,
+    FALSE,
+    timestamp::{EFI_TIMESTAMP_PROTOCOL, EFI_TIMESTAMP_PROTOCOL_GUID, EFI_TIMESTAMP_PROPERTIES},
+};
+use utils::{locate_protocol, as_micros_saturating};
+use core::{ptr, time::Duration, ops::{Add, AddAssign, Sub, SubAssign}};
+use {system_table, boot_services, Result, EfiError, EfiErrorKind, from_boolean};
+#[cfg(not(feature = "runtime-driver"))] use events::{Timer, TimerSchedule, TimerState, EventTpl, Wait};
 
+// The timestamp protocol and its properties, looked up on first use
+static mut TIMESTAMP: Option<Timestamp> = None;
+
+// Timers only fire on the platform's timer tick which is typically 10ms. Shorter sleeps are done with Stall().
+const TIMER_TICK: Duration = Duration::from_millis(10);
+
+/// Sleeps for the given duration by waiting on a timer event which lets the firmware do other work
+/// (e.g. service the network) in the meantime. Durations shorter than a timer tick and sleeps at
+/// raised TPLs, where waiting on events is not allowed, busy-wait with Stall() instead.
 pub fn sleep(dur: Duration) -> Result<()> {
-    let bs = system_table().BootServices;
-    let micros = (dur.as_secs() * 1000_000 + dur.subsec_micros() as u64) as UINTN; // TODO: this cast can be lossy. fix it
-    unsafe { ret_on_err!(((*bs).Stall)(micros)); }
+    if dur < TIMER_TICK {
+        return stall(dur);
+    }
+
+    wait_on_timer(dur)
+}
+
+#[cfg(not(feature = "runtime-driver"))]
+fn wait_on_timer(dur: Duration) -> Result<()> {
+    let result = Timer::create(dur, TimerSchedule::Relative, TimerState::Active, EventTpl::Callback)
+        .and_then(|timer| timer.wait());
+    match result {
+        Err(ref e) if e.kind() == EfiErrorKind::Unsupported => stall(dur), // We're at a raised TPL
+        r => r,
+    }
+}
+
+#[cfg(feature = "runtime-driver")]
+fn wait_on_timer(dur: Duration) -> Result<()> {
+    stall(dur)
+}
+
+fn stall(dur: Duration) -> Result<()> {
+    let bs = boot_services();
+    unsafe { ret_on_err!(((*bs).Stall)(as_micros_saturating(dur) as UINTN)); }
+    Ok(())
+}
+
+/// Wakes up periodically for recurring work, e.g. polling a device or redrawing a status line:
+///
+/// ```ignore
+/// let mut interval = Interval::new(Duration::from_millis(100))?;
+/// loop {
+///     interval.tick()?;
+///     poll();
+/// }
+/// ```
+///
+/// Ticks that are missed because the work took longer than the period are not made up for.
+#[cfg(not(feature = "runtime-driver"))]
+pub struct Interval {
+    timer: Timer,
+    period: Duration,
+}
+
+#[cfg(not(feature = "runtime-driver"))]
+impl Interval {
+    /// Starts an interval whose first tick is one period from now
+    pub fn new(period: Duration) -> Result<Self> {
+        let timer = Timer::create(period, TimerSchedule::Periodic, TimerState::Active, EventTpl::Callback)?;
+        Ok(Interval { timer, period })
+    }
+
+    /// Waits for the next tick. Returns right away if a tick has happened since the last call.
+    pub fn tick(&mut self) -> Result<()> {
+        self.timer.wait()
+    }
+
+    /// Whether a tick has happened since the last call to `tick()` or `is_ready()`. Doesn't wait.
+    pub fn is_ready(&mut self) -> Result<bool> {
+        self.timer.is_signaled()
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Changes the period. The next tick is one new period from now.
+    pub fn set_period(&mut self, period: Duration) -> Result<()> {
+        self.timer.set(period, TimerSchedule::Periodic)?;
+        self.period = period;
+        Ok(())
+    }
+}
+
+/// The platform's timestamp counter as provided by EFI_TIMESTAMP_PROTOCOL
+#[derive(Debug, Copy, Clone)]
+pub struct Timestamp {
+    protocol: *const EFI_TIMESTAMP_PROTOCOL,
+    frequency: u64,
+    end_value: u64,
+}
+
+impl Timestamp {
+    /// Fails with `Unsupported` if the firmware doesn't provide EFI_TIMESTAMP_PROTOCOL
+    pub fn get() -> Result<Self> {
+        unsafe {
+            if let Some(timestamp) = TIMESTAMP {
+                return Ok(timestamp);
+            }
+
+            let protocol: *const EFI_TIMESTAMP_PROTOCOL = locate_protocol(&EFI_TIMESTAMP_PROTOCOL_GUID)
+                .map_err(|_| EfiError::from(EfiErrorKind::Unsupported))?;
+
+            let mut properties = EFI_TIMESTAMP_PROPERTIES::default();
+            ret_on_err!(((*protocol).GetProperties)(&mut properties));
+            if properties.Frequency == 0 {
+                return Err(EfiErrorKind::Unsupported.into());
+            }
+
+            // Some firmware leaves EndValue zero for a counter that never wraps
+            let end_value = if properties.EndValue == 0 { u64::max_value() } else { properties.EndValue };
+            let timestamp = Timestamp { protocol, frequency: properties.Frequency, end_value };
+            TIMESTAMP = Some(timestamp);
+            Ok(timestamp)
+        }
+    }
+
+    /// The current value of the counter
+    pub fn ticks(&self) -> u64 {
+        unsafe { ((*self.protocol).GetTimestamp)() }
+    }
+
+    /// Ticks per second
+    pub fn frequency(&self) -> u64 {
+        self.frequency
+    }
+
+    /// The largest value of the counter after which it wraps around to zero
+    pub fn end_value(&self) -> u64 {
+        self.end_value
+    }
+}
+
+/// Time elapsed as per the platform's timestamp counter, usually since the platform was reset.
+/// Unlike `now()` this is monotonic (until the counter wraps) and cheap to call.
+/// Fails with `Unsupported` if the firmware doesn't provide EFI_TIMESTAMP_PROTOCOL.
+pub fn timestamp() -> Result<Duration> {
+    let timestamp = Timestamp::get()?;
+    Ok(ticks_to_duration(timestamp.ticks(), timestamp.frequency()))
+}
+
+/// The raw value of the platform's timestamp counter
+pub(crate) fn timestamp_ticks() -> Result<u64> {
+    Ok(Timestamp::get()?.ticks())
+}
+
+/// Nanoseconds on the clock `Instant` is based on. The origin is unspecified, usually around reset,
+/// so only differences between two readings are meaningful. The clock doesn't go backwards as long as
+/// it's read at least once per wrap around of the underlying counter (see `Timestamp::end_value()`),
+/// which for the narrowest common counter, a 32-bit HPET, is every five minutes.
+///
+/// Fails with `Unsupported` only if there is no counter to base the clock on.
+pub fn monotonic_nanos() -> Result<u64> {
+    let (clock, frequency) = clock()?;
+    Ok(ticks_to_nanos(read_clock(clock), frequency))
+}
+
+fn ticks_to_duration(ticks: u64, frequency: u64) -> Duration {
+    let nanos = (ticks % frequency) as u128 * 1_000_000_000 / frequency as u128;
+    Duration::new(ticks / frequency, nanos as u32)
+}
+
+fn ticks_to_nanos(ticks: u64, frequency: u64) -> u64 {
+    let nanos = ticks as u128 * 1_000_000_000 / frequency as u128;
+    if nanos > u64::max_value() as u128 { u64::max_value() } else { nanos as u64 }
+}
+
+fn duration_to_ticks(dur: Duration, frequency: u64) -> Option<u64> {
+    let ticks = dur.as_secs() as u128 * frequency as u128 + dur.subsec_nanos() as u128 * frequency as u128 / 1_000_000_000;
+    if ticks > u64::max_value() as u128 { None } else { Some(ticks as u64) }
+}
+
+// The counter Instant is based on
+#[derive(Copy, Clone)]
+enum Clock {
+    Timestamp(Timestamp),
+    #[cfg(target_arch = "x86_64")]
+    Tsc,
+}
+
+// The clock and its frequency, picked on first use
+static mut CLOCK: Option<(Clock, u64)> = None;
+
+#[cfg(target_arch = "x86_64")]
+const TSC_CALIBRATION_MICROS: UINTN = 10_000;
+
+fn clock() -> Result<(Clock, u64)> {
+    unsafe {
+        if let Some(clock) = CLOCK {
+            return Ok(clock);
+        }
+
+        let clock = match Timestamp::get() {
+            Ok(timestamp) => (Clock::Timestamp(timestamp), timestamp.frequency()),
+            Err(e) => fallback_clock(e)?,
+        };
+        CLOCK = Some(clock);
+        Ok(clock)
+    }
+}
+
+// Without the timestamp protocol we calibrate the CPU's time stamp counter against Stall()
+#[cfg(target_arch = "x86_64")]
+fn fallback_clock(_: EfiError) -> Result<(Clock, u64)> {
+    let bs = boot_services();
+    let start = read_tsc();
+    unsafe { ret_on_err!(((*bs).Stall)(TSC_CALIBRATION_MICROS)); }
+    let ticks = read_tsc().wrapping_sub(start);
+    if ticks == 0 {
+        return Err(EfiErrorKind::Unsupported.into());
+    }
+
+    Ok((Clock::Tsc, ticks * 1_000_000 / TSC_CALIBRATION_MICROS as u64))
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn fallback_clock(e: EfiError) -> Result<(Clock, u64)> {
+    Err(e)
+}
+
+#[cfg(target_arch = "x86_64")]
+fn read_tsc() -> u64 {
+    unsafe { ::core::arch::x86_64::_rdtsc() as u64 }
+}
+
+// The last counter value read and what has been added to it for earlier wrap arounds
+static mut WRAPS: (u64, u64) = (0, 0);
+
+// Counters narrower than 64 bits are extended so that the clock keeps going up after they wrap
+fn read_clock(clock: Clock) -> u64 {
+    match clock {
+        Clock::Timestamp(timestamp) if timestamp.end_value() != u64::max_value() => unsafe {
+            extend_counter(timestamp.ticks(), timestamp.end_value(), &mut WRAPS)
+        },
+        Clock::Timestamp(timestamp) => timestamp.ticks(),
+        #[cfg(target_arch = "x86_64")]
+        Clock::Tsc => read_tsc(),
+    }
+}
+
+fn extend_counter(ticks: u64, end_value: u64, wraps: &mut (u64, u64)) -> u64 {
+    let (last, mut offset) = *wraps;
+    if ticks < last {
+        offset = offset.wrapping_add(end_value).wrapping_add(1);
+    }
+
+    *wraps = (ticks, offset);
+    offset.wrapping_add(ticks)
+}
+
+// Only called once an Instant exists which means the clock has been picked
+fn picked_clock() -> (Clock, u64) {
+    unsafe { CLOCK.expect("an Instant was created without a clock") }
+}
+
+/// A point in time as per `EFI_TIMESTAMP_PROTOCOL` or, if the firmware doesn't have it, the CPU's
+/// time stamp counter calibrated against Stall(). Precise to a tick of the counter. Meant for measuring time, e.g. throughput or deadlines. Use `now()` for the calendar time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    ticks: u64,
+}
+
+impl Instant {
+    /// Fails with `Unsupported` only if there is no counter to base instants on
+    pub fn now() -> Result<Instant> {
+        let (clock, _) = clock()?;
+        Ok(Instant { ticks: read_clock(clock) })
+    }
+
+    /// The time elapsed since this instant
+    pub fn elapsed(&self) -> Duration {
+        let (clock, _) = picked_clock();
+        Instant { ticks: read_clock(clock) }.duration_since(*self)
+    }
+
+    /// The time from `earlier` to this instant. Zero if `earlier` is later than this instant.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        let (_, frequency) = picked_clock();
+        ticks_to_duration(self.ticks.saturating_sub(earlier.ticks), frequency)
+    }
+
+    pub fn checked_add(&self, dur: Duration) -> Option<Instant> {
+        let (_, frequency) = picked_clock();
+        duration_to_ticks(dur, frequency)
+            .and_then(|ticks| self.ticks.checked_add(ticks))
+            .map(|ticks| Instant { ticks })
+    }
+
+    pub fn checked_sub(&self, dur: Duration) -> Option<Instant> {
+        let (_, frequency) = picked_clock();
+        duration_to_ticks(dur, frequency)
+            .and_then(|ticks| self.ticks.checked_sub(ticks))
+            .map(|ticks| Instant { ticks })
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, dur: Duration) -> Instant {
+        self.checked_add(dur).expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, dur: Duration) {
+        *self = *self + dur;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, dur: Duration) -> Instant {
+        self.checked_sub(dur).expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, dur: Duration) {
+        *self = *self - dur;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+/// A calendar date and time as kept by the platform's real time clock
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DateTime {
+    /// 1900 - 9999
+    pub year: u16,
+    /// 1 - 12
+    pub month: u8,
+    /// 1 - 31
+    pub day: u8,
+    /// 0 - 23
+    pub hour: u8,
+    /// 0 - 59
+    pub minute: u8,
+    /// 0 - 59
+    pub second: u8,
+    /// 0 - 999,999,999
+    pub nanosecond: u32,
+    /// Offset from UTC in minutes (-1440 to 1440). None means the time is local time.
+    pub time_zone: Option<i16>,
+    /// Whether the time should be adjusted for daylight savings
+    pub adjust_daylight: bool,
+    /// Whether the time is affected by daylight savings
+    pub in_daylight: bool,
+}
+
+impl DateTime {
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
+        DateTime { year, month, day, hour, minute, second, nanosecond: 0, time_zone: None, adjust_daylight: false, in_daylight: false }
+    }
+}
+
+impl From<EFI_TIME> for DateTime {
+    fn from(time: EFI_TIME) -> Self {
+        DateTime {
+            year: time.Year,
+            month: time.Month,
+            day: time.Day,
+            hour: time.Hour,
+            minute: time.Minute,
+            second: time.Second,
+            nanosecond: time.Nanosecond,
+            time_zone: if time.TimeZone as UINTN == EFI_UNSPECIFIED_TIMEZONE { None } else { Some(time.TimeZone) },
+            adjust_daylight: (time.Daylight as UINTN & EFI_TIME_ADJUST_DAYLIGHT) != 0,
+            in_daylight: (time.Daylight as UINTN & EFI_TIME_IN_DAYLIGHT) != 0,
+        }
+    }
+}
+
+impl From<DateTime> for EFI_TIME {
+    fn from(date_time: DateTime) -> Self {
+        let mut daylight = 0;
+        if date_time.adjust_daylight { daylight |= EFI_TIME_ADJUST_DAYLIGHT; }
+        if date_time.in_daylight { daylight |= EFI_TIME_IN_DAYLIGHT; }
+
+        EFI_TIME {
+            Year: date_time.year,
+            Month: date_time.month,
+            Day: date_time.day,
+            Hour: date_time.hour,
+            Minute: date_time.minute,
+            Second: date_time.second,
+            Pad1: 0,
+            Nanosecond: date_time.nanosecond,
+            TimeZone: date_time.time_zone.unwrap_or(EFI_UNSPECIFIED_TIMEZONE as i16),
+            Daylight: daylight as u8,
+            Pad2: 0,
+        }
+    }
+}
+
+/// The current date and time as per the platform's real time clock
+pub fn now() -> Result<DateTime> {
+    let rs = system_table().RuntimeServices;
+    let mut time = EFI_TIME::zero();
+    let mut capabilities = EFI_TIME_CAPABILITIES::zero();
+    unsafe { ret_on_err!(((*rs).GetTime)(&mut time, &mut capabilities)); }
+    Ok(time.into())
+}
+
+
+/// The state of the real time clock's wakeup alarm
+#[derive(Debug, Copy, Clone)]
+pub struct WakeupAlarm {
+    /// Whether the alarm is armed
+    pub enabled: bool,
+    /// Whether the alarm has fired but not yet been acknowledged
+    pub pending: bool,
+    /// The time at which the alarm fires
+    pub time: DateTime,
+}
+
+/// Returns the current state of the wakeup alarm.
+/// Fails with `Unsupported` if the platform does not have a wakeup alarm.
+pub fn wakeup_alarm() -> Result<WakeupAlarm> {
+    let rs = system_table().RuntimeServices;
+    let mut enabled: BOOLEAN = FALSE;
+    let mut pending: BOOLEAN = FALSE;
+    let mut time = EFI_TIME::zero();
+    unsafe { ret_on_err!(((*rs).GetWakeupTime)(&mut enabled, &mut pending, &mut time)); }
+
+    Ok(WakeupAlarm { enabled: from_boolean(enabled), pending: from_boolean(pending), time: time.into() })
+}
+
+/// Arms the wakeup alarm so that the system powers on at the given time, e.g. for scheduled maintenance.
+/// The time is interpreted by the real time clock which usually keeps local time.
+pub fn set_wakeup_alarm(time: &DateTime) -> Result<()> {
+    let rs = system_table().RuntimeServices;
+    let time: EFI_TIME = (*time).into();
+    unsafe { ret_on_err!(((*rs).SetWakeupTime)(TRUE, &time)); }
+    Ok(())
+}
+
+/// Disarms the wakeup alarm
+pub fn clear_wakeup_alarm() -> Result<()> {
+    let rs = system_table().RuntimeServices;
+    unsafe { ret_on_err!(((*rs).SetWakeupTime)(FALSE, ptr::null())); }
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_are_converted_to_durations() {
+        assert_eq!(ticks_to_duration(3_500_000, 1_000_000), Duration::new(3, 500_000_000));
+        assert_eq!(ticks_to_duration(u64::max_value(), 3_000_000_000), Duration::new(6_148_914_691, 236_517_205));
+    }
+
+    #[test]
+    fn durations_are_converted_to_ticks() {
+        assert_eq!(duration_to_ticks(Duration::new(3, 500_000_000), 1_000_000), Some(3_500_000));
+        assert_eq!(duration_to_ticks(Duration::from_millis(1), 3_000_000_000), Some(3_000_000));
+        assert_eq!(duration_to_ticks(Duration::from_secs(u64::max_value()), 2), None);
+    }
+
+    #[test]
+    fn ticks_are_converted_to_nanos() {
+        assert_eq!(ticks_to_nanos(3, 3_000_000_000), 1);
+        assert_eq!(ticks_to_nanos(14_318_180, 14_318_180), 1_000_000_000);
+        assert_eq!(ticks_to_nanos(u64::max_value(), 1), u64::max_value());
+    }
+
+    #[test]
+    fn narrow_counters_are_extended() {
+        let end_value = u32::max_value() as u64;
+        let mut wraps = (0, 0);
+        assert_eq!(extend_counter(end_value - 1, end_value, &mut wraps), end_value - 1);
+        assert_eq!(extend_counter(5, end_value, &mut wraps), end_value + 6);
+        assert_eq!(extend_counter(10, end_value, &mut wraps), end_value + 11);
+        assert_eq!(extend_counter(2, end_value, &mut wraps), 2 * end_value + 4);
+    }
+}
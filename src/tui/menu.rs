@@ -0,0 +1,281 @@
+use console::{self, Console, Key, ForeColor, BackColor};
+use time;
+use alloc::{Vec, String};
+use core::{cmp, time::Duration};
+use Result;
+use super::put_line;
+
+// How often the keyboard is polled while a timeout is running
+const POLL_INTERVAL_MS: u64 = 50;
+
+/// An entry in a `Menu`
+pub struct MenuItem {
+    label: String,
+    help: Option<String>,
+    shortcut: Option<char>,
+}
+
+impl MenuItem {
+    pub fn new(label: &str) -> Self {
+        MenuItem { label: label.into(), help: None, shortcut: None }
+    }
+
+    /// Text shown at the bottom of the screen while the item is highlighted
+    pub fn help(mut self, help: &str) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// A key that chooses the item straight away. Letters match regardless of case.
+    pub fn shortcut(mut self, shortcut: char) -> Self {
+        self.shortcut = Some(shortcut);
+        self
+    }
+}
+
+/// A full screen list of items to choose from with the arrow keys, e.g. a boot menu:
+///
+/// ```ignore
+/// let choice = Menu::new("Boot")
+///     .item(MenuItem::new("Install").help("Installs the OS to the first disk").shortcut('i'))
+///     .item(MenuItem::new("Rescue").shortcut('r'))
+///     .default(0)
+///     .timeout(Duration::from_secs(10))
+///     .show()?;
+/// ```
+///
+/// Up/Down, Home/End and PageUp/PageDown move the highlight, Enter chooses it and Escape cancels.
+/// Any key press stops the timeout.
+pub struct Menu {
+    title: String,
+    items: Vec<MenuItem>,
+    default: usize,
+    timeout: Option<Duration>,
+}
+
+impl Menu {
+    pub fn new(title: &str) -> Self {
+        Menu { title: title.into(), items: Vec::new(), default: 0, timeout: None }
+    }
+
+    pub fn item(mut self, item: MenuItem) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    /// The item highlighted at first and chosen when the timeout runs out
+    pub fn default(mut self, index: usize) -> Self {
+        self.default = index;
+        self
+    }
+
+    /// Chooses the default item if no key is pressed within this time
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Shows the menu on ConOut and returns the index of the chosen item, or None if it was cancelled with Escape.
+    /// The screen is cleared before and after.
+    pub fn show(&self) -> Result<Option<usize>> {
+        let mut console = console::console();
+        let choice = self.run(&mut console);
+
+        // Restore the console even if something failed
+        let _ = console.set_colors(ForeColor::LightGray, BackColor::Black);
+        let _ = console.clear_screen();
+        let _ = console.enable_cursor();
+        choice
+    }
+
+    fn run(&self, console: &mut Console) -> Result<Option<usize>> {
+        if self.items.is_empty() {
+            return Ok(None);
+        }
+
+        let shortcuts: Vec<Option<char>> = self.items.iter().map(|i| i.shortcut).collect();
+        let mut state = MenuState::new(self.items.len(), self.default, shortcuts);
+        let mut remaining = self.timeout;
+
+        console.set_colors(ForeColor::LightGray, BackColor::Black)?;
+        console.clear_screen()?;
+        let _ = console.disable_cursor(); // Not all consoles support hiding the cursor
+
+        let mut last_remaining_secs = None;
+        self.draw(console, &state, remaining)?;
+        loop {
+            let key = match remaining {
+                None => Some(console.read_key()?),
+                Some(left) => {
+                    let key = console.try_read_key()?;
+                    if key.is_none() {
+                        if left == Duration::from_secs(0) {
+                            return Ok(Some(state.selected));
+                        }
+
+                        let step = cmp::min(left, Duration::from_millis(POLL_INTERVAL_MS));
+                        time::sleep(step)?;
+                        remaining = Some(left - step);
+
+                        // Only the countdown changes so only redraw that
+                        let secs = secs_left(remaining.unwrap());
+                        if last_remaining_secs != Some(secs) {
+                            last_remaining_secs = Some(secs);
+                            self.draw_status(console, &state, remaining)?;
+                        }
+                    }
+                    key
+                }
+            };
+
+            if let Some(key) = key {
+                remaining = None;
+                match state.handle_key(key) {
+                    MenuAction::Chosen(index) => return Ok(Some(index)),
+                    MenuAction::Cancelled => return Ok(None),
+                    MenuAction::Moved | MenuAction::Ignored => self.draw(console, &state, remaining)?,
+                }
+            }
+        }
+    }
+
+    fn draw(&self, console: &mut Console, state: &MenuState, remaining: Option<Duration>) -> Result<()> {
+        let (columns, rows) = console.size()?;
+        let width = columns.saturating_sub(1);
+
+        console.set_colors(ForeColor::White, BackColor::Black)?;
+        put_line(console, 0, 0, &self.title, width)?;
+
+        // Scroll the list if it doesn't fit between the title and the status lines
+        let visible = cmp::max(rows.saturating_sub(FIRST_ITEM_ROW + 3) as usize, 1);
+        let first = if state.selected < visible { 0 } else { state.selected + 1 - visible };
+
+        for (row, (index, item)) in self.items.iter().enumerate().skip(first).take(visible).enumerate() {
+            if index == state.selected {
+                console.set_colors(ForeColor::Black, BackColor::LightGray)?;
+            } else {
+                console.set_colors(ForeColor::LightGray, BackColor::Black)?;
+            }
+
+            let text = match item.shortcut {
+                Some(c) => format!("  {}  ({})", item.label, c),
+                None => format!("  {}", item.label),
+            };
+            put_line(console, FIRST_ITEM_ROW + row as u32, 0, &text, width)?;
+        }
+
+        self.draw_status(console, state, remaining)
+    }
+
+    // Draws the help text of the highlighted item and the countdown at the bottom of the screen
+    fn draw_status(&self, console: &mut Console, state: &MenuState, remaining: Option<Duration>) -> Result<()> {
+        let (columns, rows) = console.size()?;
+        let width = columns.saturating_sub(1);
+
+        console.set_colors(ForeColor::LightGray, BackColor::Black)?;
+        let help = self.items[state.selected].help.as_ref().map_or("", |h| h.as_str());
+        put_line(console, rows.saturating_sub(2), 0, help, width)?;
+
+        let status = match remaining {
+            Some(left) => format!("'{}' will be chosen automatically in {}s", self.items[state.selected].label, secs_left(left)),
+            None => String::new(),
+        };
+        put_line(console, rows.saturating_sub(1), 0, &status, width)
+    }
+}
+
+const FIRST_ITEM_ROW: u32 = 2;
+
+// Rounded up so that the countdown shows 1 until it's over
+fn secs_left(remaining: Duration) -> u64 {
+    remaining.as_secs() + if remaining.subsec_nanos() > 0 { 1 } else { 0 }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum MenuAction {
+    Moved,
+    Chosen(usize),
+    Cancelled,
+    Ignored,
+}
+
+// The navigation logic, kept apart from drawing
+struct MenuState {
+    selected: usize,
+    count: usize,
+    shortcuts: Vec<Option<char>>,
+}
+
+// How far PageUp/PageDown move
+const PAGE_SIZE: usize = 10;
+
+impl MenuState {
+    fn new(count: usize, default: usize, shortcuts: Vec<Option<char>>) -> Self {
+        MenuState { selected: cmp::min(default, count.saturating_sub(1)), count, shortcuts }
+    }
+
+    fn handle_key(&mut self, key: Key) -> MenuAction {
+        let last = self.count - 1;
+        let selected = match key {
+            Key::Enter => return MenuAction::Chosen(self.selected),
+            Key::Escape => return MenuAction::Cancelled,
+            Key::Up => if self.selected == 0 { last } else { self.selected - 1 }, // Wraps around
+            Key::Down => if self.selected == last { 0 } else { self.selected + 1 },
+            Key::Home => 0,
+            Key::End => last,
+            Key::PageUp => self.selected.saturating_sub(PAGE_SIZE),
+            Key::PageDown => cmp::min(self.selected + PAGE_SIZE, last),
+            Key::Char(c) => match self.shortcuts.iter().position(|s| s.map_or(false, |s| eq_ignore_case(s, c))) {
+                Some(index) => return MenuAction::Chosen(index),
+                None => return MenuAction::Ignored,
+            },
+            _ => return MenuAction::Ignored,
+        };
+
+        self.selected = selected;
+        MenuAction::Moved
+    }
+}
+
+fn eq_ignore_case(a: char, b: char) -> bool {
+    a == b || (a.is_ascii() && b.is_ascii() && a.to_ascii_lowercase() == b.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrow_keys_move_the_selection() {
+        let mut state = MenuState::new(3, 1, vec![None; 3]);
+        assert_eq!(state.handle_key(Key::Down), MenuAction::Moved);
+        assert_eq!(state.selected, 2);
+        state.handle_key(Key::Down);
+        assert_eq!(state.selected, 0);
+        state.handle_key(Key::Up);
+        assert_eq!(state.selected, 2);
+        state.handle_key(Key::Home);
+        assert_eq!(state.selected, 0);
+        state.handle_key(Key::PageDown);
+        assert_eq!(state.selected, 2);
+        assert_eq!(state.handle_key(Key::Enter), MenuAction::Chosen(2));
+        assert_eq!(state.handle_key(Key::Escape), MenuAction::Cancelled);
+        assert_eq!(state.handle_key(Key::Tab), MenuAction::Ignored);
+    }
+
+    #[test]
+    fn shortcuts_choose_items() {
+        let mut state = MenuState::new(3, 0, vec![Some('i'), None, Some('R')]);
+        assert_eq!(state.handle_key(Key::Char('r')), MenuAction::Chosen(2));
+        assert_eq!(state.handle_key(Key::Char('I')), MenuAction::Chosen(0));
+        assert_eq!(state.handle_key(Key::Char('x')), MenuAction::Ignored);
+    }
+
+    #[test]
+    fn default_is_clamped_and_countdown_rounds_up() {
+        assert_eq!(MenuState::new(2, 5, vec![None; 2]).selected, 1);
+        assert_eq!(secs_left(Duration::from_millis(1500)), 2);
+        assert_eq!(secs_left(Duration::from_secs(3)), 3);
+        assert_eq!(secs_left(Duration::from_secs(0)), 0);
+    }
+}
@@ -0,0 +1,36 @@
+use console::{Console, Position};
+use io::Write;
+use alloc::String;
+use {Result, EfiErrorKind};
+
+// Text mode UI components built on the console
+
+mod menu;
+
+pub use self::menu::{Menu, MenuItem};
+
+// Writes text at the given position padded with spaces or cut off to exactly `width` columns
+fn put_line(console: &mut Console, row: u32, col: u32, text: &str, width: u32) -> Result<()> {
+    console.set_cursor_pos(Position { row, col })?;
+    console.write_all(fit(text, width as usize).as_bytes()).map_err(|_| EfiErrorKind::DeviceError)?;
+    Ok(())
+}
+
+fn fit(text: &str, width: usize) -> String {
+    let mut line: String = text.chars().take(width).collect();
+    let len = line.chars().count();
+    line.extend((len..width).map(|_| ' '));
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_fitted_to_width() {
+        assert_eq!(fit("abc", 5), "abc  ");
+        assert_eq!(fit("abcdef", 4), "abcd");
+        assert_eq!(fit("", 0), "");
+    }
+}
@@ -0,0 +1,304 @@
+//! Null-terminated UCS-2 strings as used by file names, variable names, text output, load options etc.
+
+use core::{fmt, mem, ops::Deref, char, str::FromStr};
+use alloc::{String, Vec, borrow::{Borrow, ToOwned}};
+use ffi::CHAR16;
+use {EfiError, EfiErrorKind};
+
+/// Creates a `CString16` from a string literal. Panics if the literal has characters outside the BMP or nulls.
+///
+/// Unlike the literal itself the result is not static because the conversion happens at runtime.
+#[macro_export]
+macro_rules! ucs2 {
+    ($s:expr) => {
+        $crate::ucs2::CString16::from_literal($s)
+    }
+}
+
+#[derive(Debug, Fail, Copy, Clone, PartialEq)]
+pub enum Ucs2Error {
+    #[fail(display = "The string is not null terminated")]
+    NotNulTerminated,
+    #[fail(display = "The string has a null at position {} before its end", _0)]
+    InteriorNul(usize),
+    #[fail(display = "The character {:?} cannot be represented in UCS-2", _0)]
+    Unrepresentable(char),
+    #[fail(display = "The code unit 0x{:04X} is not a valid character", _0)]
+    InvalidCodeUnit(u16),
+}
+
+const REPLACEMENT_CHARACTER: char = '\u{FFFD}';
+
+impl From<Ucs2Error> for EfiError {
+    fn from(error: Ucs2Error) -> Self {
+        EfiError::with_source(EfiErrorKind::InvalidParameter, error)
+    }
+}
+
+/// A borrowed null-terminated UCS-2 string
+#[derive(PartialEq, Eq)]
+pub struct CStr16([CHAR16]);
+
+impl CStr16 {
+    /// Wraps a buffer which must end in its one and only null
+    pub fn from_u16_with_nul(buf: &[CHAR16]) -> Result<&CStr16, Ucs2Error> {
+        match buf.iter().position(|c| *c == 0) {
+            Some(pos) if pos == buf.len() - 1 => Ok(unsafe { Self::from_u16_with_nul_unchecked(buf) }),
+            Some(pos) => Err(Ucs2Error::InteriorNul(pos)),
+            None => Err(Ucs2Error::NotNulTerminated),
+        }
+    }
+
+    /// Same as `from_u16_with_nul()` but ignores everything after the first null
+    pub fn from_u16_until_nul(buf: &[CHAR16]) -> Result<&CStr16, Ucs2Error> {
+        match buf.iter().position(|c| *c == 0) {
+            Some(pos) => Ok(unsafe { Self::from_u16_with_nul_unchecked(&buf[..pos + 1]) }),
+            None => Err(Ucs2Error::NotNulTerminated),
+        }
+    }
+
+    /// Wraps a buffer without checking that it ends in its one and only null
+    pub unsafe fn from_u16_with_nul_unchecked(buf: &[CHAR16]) -> &CStr16 {
+        mem::transmute(buf)
+    }
+
+    /// Wraps a null-terminated string returned by the firmware
+    pub unsafe fn from_ptr<'a>(ptr: *const CHAR16) -> &'a CStr16 {
+        let len = ::utils::as_slice(ptr).len();
+        Self::from_u16_with_nul_unchecked(::core::slice::from_raw_parts(ptr, len + 1))
+    }
+
+    pub fn as_ptr(&self) -> *const CHAR16 {
+        self.0.as_ptr()
+    }
+
+    /// The characters without the null terminator
+    pub fn as_slice(&self) -> &[CHAR16] {
+        &self.0[..self.0.len() - 1]
+    }
+
+    pub fn as_slice_with_nul(&self) -> &[CHAR16] {
+        &self.0
+    }
+
+    /// The number of characters not counting the null terminator
+    pub fn len(&self) -> usize {
+        self.0.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Converts to a `String` failing on surrogates that are not part of a pair
+    pub fn to_string(&self) -> Result<String, Ucs2Error> {
+        String::from_utf16(self.as_slice()).map_err(|_| Ucs2Error::InvalidCodeUnit(self.unpaired_surrogate()))
+    }
+
+    /// Converts to a `String` replacing unpaired surrogates with U+FFFD
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.as_slice())
+    }
+
+    // The first surrogate that is not part of a pair. Zero if there's none.
+    fn unpaired_surrogate(&self) -> CHAR16 {
+        let chars = self.as_slice();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                0xD800...0xDBFF if i + 1 < chars.len() && chars[i + 1] >= 0xDC00 && chars[i + 1] <= 0xDFFF => i += 2,
+                c @ 0xD800...0xDFFF => return c,
+                _ => i += 1,
+            }
+        }
+        0
+    }
+}
+
+impl fmt::Display for CStr16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string_lossy())
+    }
+}
+
+impl fmt::Debug for CStr16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.to_string_lossy())
+    }
+}
+
+impl AsRef<CStr16> for CStr16 {
+    fn as_ref(&self) -> &CStr16 {
+        self
+    }
+}
+
+impl ToOwned for CStr16 {
+    type Owned = CString16;
+
+    fn to_owned(&self) -> CString16 {
+        CString16(self.0.to_vec())
+    }
+}
+
+/// An owned null-terminated UCS-2 string
+#[derive(Clone, PartialEq, Eq)]
+pub struct CString16(Vec<CHAR16>);
+
+impl CString16 {
+    pub fn new() -> Self {
+        CString16(vec![0])
+    }
+
+    /// Converts failing on characters outside the BMP and on nulls
+    pub fn from_str_strict(s: &str) -> Result<Self, Ucs2Error> {
+        let mut buf = Vec::with_capacity(s.len() + 1);
+        for (i, c) in s.chars().enumerate() {
+            match c as u32 {
+                0 => return Err(Ucs2Error::InteriorNul(i)),
+                c if c > 0xFFFF => return Err(Ucs2Error::Unrepresentable(char::from_u32(c).unwrap_or(REPLACEMENT_CHARACTER))),
+                c => buf.push(c as CHAR16),
+            }
+        }
+        buf.push(0);
+        Ok(CString16(buf))
+    }
+
+    /// Converts replacing characters outside the BMP and nulls with U+FFFD
+    pub fn from_str_lossy(s: &str) -> Self {
+        let mut buf = s.chars()
+            .map(|c| match c as u32 {
+                0 => REPLACEMENT_CHARACTER as CHAR16,
+                c if c > 0xFFFF => REPLACEMENT_CHARACTER as CHAR16,
+                c => c as CHAR16,
+            })
+            .collect::<Vec<_>>();
+        buf.push(0);
+        CString16(buf)
+    }
+
+    /// Takes a buffer which may or may not be null terminated. Fails if there are nulls anywhere else.
+    pub fn from_vec(mut buf: Vec<CHAR16>) -> Result<Self, Ucs2Error> {
+        if buf.last() != Some(&0) {
+            buf.push(0);
+        }
+        CStr16::from_u16_with_nul(&buf)?;
+        Ok(CString16(buf))
+    }
+
+    #[doc(hidden)]
+    pub fn from_literal(s: &str) -> Self {
+        match Self::from_str_strict(s) {
+            Ok(s) => s,
+            Err(e) => panic!("Invalid UCS-2 literal {:?}: {}", s, e),
+        }
+    }
+
+    pub fn as_c_str(&self) -> &CStr16 {
+        unsafe { CStr16::from_u16_with_nul_unchecked(&self.0) }
+    }
+
+    /// The characters followed by the null terminator
+    pub fn into_vec_with_nul(self) -> Vec<CHAR16> {
+        self.0
+    }
+
+    /// Appends the characters of the string, failing the same way as `from_str_strict()` does
+    pub fn push_str(&mut self, s: &str) -> Result<(), Ucs2Error> {
+        let tail = Self::from_str_strict(s)?;
+        self.0.pop();
+        self.0.extend_from_slice(&tail.0);
+        Ok(())
+    }
+}
+
+impl FromStr for CString16 {
+    type Err = Ucs2Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CString16::from_str_strict(s)
+    }
+}
+
+impl Default for CString16 {
+    fn default() -> Self {
+        CString16::new()
+    }
+}
+
+impl Deref for CString16 {
+    type Target = CStr16;
+
+    fn deref(&self) -> &CStr16 {
+        self.as_c_str()
+    }
+}
+
+impl AsRef<CStr16> for CString16 {
+    fn as_ref(&self) -> &CStr16 {
+        self
+    }
+}
+
+impl Borrow<CStr16> for CString16 {
+    fn borrow(&self) -> &CStr16 {
+        self
+    }
+}
+
+impl<'a> From<&'a CStr16> for CString16 {
+    fn from(s: &'a CStr16) -> Self {
+        s.to_owned()
+    }
+}
+
+impl fmt::Display for CString16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_c_str(), f)
+    }
+}
+
+impl fmt::Debug for CString16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_c_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CStr16, CString16, Ucs2Error};
+
+    #[test]
+    fn from_u16_with_nul() {
+        assert_eq!(CStr16::from_u16_with_nul(&[0x41, 0x42, 0]).unwrap().as_slice(), &[0x41, 0x42]);
+        assert_eq!(CStr16::from_u16_with_nul(&[0x41, 0, 0x42, 0]), Err(Ucs2Error::InteriorNul(1)));
+        assert_eq!(CStr16::from_u16_with_nul(&[0x41]), Err(Ucs2Error::NotNulTerminated));
+        assert_eq!(CStr16::from_u16_until_nul(&[0x41, 0, 0x42, 0]).unwrap().len(), 1);
+        assert!(CStr16::from_u16_with_nul(&[]).is_err());
+    }
+
+    #[test]
+    fn strict_and_lossy_conversions() {
+        let s = CString16::from_str_strict("a\u{e9}b").unwrap();
+        assert_eq!(s.as_slice_with_nul(), &[0x61, 0xE9, 0x62, 0]);
+        assert_eq!(s.to_string().unwrap(), "a\u{e9}b");
+        assert_eq!(format!("{}", s), "a\u{e9}b");
+        assert_eq!(CString16::from_str_strict("a\u{1F600}"), Err(Ucs2Error::Unrepresentable('\u{1F600}')));
+        assert_eq!(CString16::from_str_strict("a\0b"), Err(Ucs2Error::InteriorNul(1)));
+        assert_eq!(CString16::from_str_lossy("a\u{1F600}").as_slice(), &[0x61, 0xFFFD]);
+
+        let unpaired = CStr16::from_u16_with_nul(&[0x61, 0xD800, 0]).unwrap();
+        assert_eq!(unpaired.to_string(), Err(Ucs2Error::InvalidCodeUnit(0xD800)));
+        assert_eq!(unpaired.to_string_lossy(), "a\u{FFFD}");
+    }
+
+    #[test]
+    fn owned_strings() {
+        let mut s = ucs2!("ab");
+        s.push_str("c").unwrap();
+        assert_eq!(s.as_slice(), &[0x61, 0x62, 0x63]);
+        assert_eq!(CString16::from_vec(vec![0x61]).unwrap().as_slice_with_nul(), &[0x61, 0]);
+        assert!(CString16::from_vec(vec![0, 0x61]).is_err());
+        assert!(CString16::new().is_empty());
+    }
+}
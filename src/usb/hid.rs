@@ -0,0 +1,552 @@
+use super::{UsbDevice, EndpointDescriptor, TransferType, ControlRequest, REQUEST_IN, REQUEST_STANDARD, REQUEST_CLASS, RECIPIENT_INTERFACE};
+use byteorder::{ByteOrder, LittleEndian};
+use alloc::Vec;
+use core::{cmp, time::Duration};
+use {Result, EfiErrorKind};
+
+// Human interface devices on USB. Keyboards and mice that support the boot protocol can be driven
+// without understanding their report descriptor, which is how this is meant to be used when the
+// firmware's own HID driver hasn't picked a device up. The report descriptor parser is there for
+// everything else.
+
+const HID_CLASS: u8 = 0x03;
+const BOOT_SUBCLASS: u8 = 0x01;
+const KEYBOARD_PROTOCOL: u8 = 0x01;
+const MOUSE_PROTOCOL: u8 = 0x02;
+
+const GET_DESCRIPTOR: u8 = 0x06;
+const HID_DESCRIPTOR_TYPE: u8 = 0x21;
+const REPORT_DESCRIPTOR_TYPE: u8 = 0x22;
+
+const SET_REPORT: u8 = 0x09;
+const SET_IDLE: u8 = 0x0A;
+const SET_PROTOCOL: u8 = 0x0B;
+const OUTPUT_REPORT: u16 = 0x02;
+
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Bounds how many usages a single Usage Minimum/Maximum pair can expand to
+const MAX_USAGE_RANGE: u32 = 1024;
+
+/// An interface of class HID along with its interrupt IN endpoint
+pub struct HidDevice {
+    usb: UsbDevice,
+    endpoint: EndpointDescriptor,
+}
+
+impl HidDevice {
+    /// Fails with `Unsupported` if the interface isn't a HID interface with an interrupt IN endpoint
+    pub fn from_usb(usb: UsbDevice) -> Result<Self> {
+        if usb.interface_descriptor()?.class != HID_CLASS {
+            return Err(EfiErrorKind::Unsupported.into());
+        }
+
+        let endpoint = usb.endpoint_descriptors()?.into_iter()
+            .find(|e| e.is_in() && e.transfer_type == TransferType::Interrupt)
+            .ok_or(EfiErrorKind::Unsupported)?;
+
+        Ok(HidDevice { usb, endpoint })
+    }
+
+    /// Every HID interface in the system
+    pub fn all() -> Result<Vec<Self>> {
+        Ok(UsbDevice::all()?.into_iter().filter_map(|u| Self::from_usb(u).ok()).collect())
+    }
+
+    /// HID interfaces that are boot protocol keyboards
+    pub fn keyboards() -> Result<Vec<Self>> {
+        Ok(Self::all()?.into_iter().filter(|h| h.is_boot_keyboard()).collect())
+    }
+
+    /// HID interfaces that are boot protocol mice
+    pub fn mice() -> Result<Vec<Self>> {
+        Ok(Self::all()?.into_iter().filter(|h| h.is_boot_mouse()).collect())
+    }
+
+    pub fn usb(&self) -> &UsbDevice {
+        &self.usb
+    }
+
+    pub fn is_boot_keyboard(&self) -> bool {
+        self.boot_protocol() == Some(KEYBOARD_PROTOCOL)
+    }
+
+    pub fn is_boot_mouse(&self) -> bool {
+        self.boot_protocol() == Some(MOUSE_PROTOCOL)
+    }
+
+    /// The raw report descriptor, which `parse_report_descriptor` decodes
+    pub fn report_descriptor(&self) -> Result<Vec<u8>> {
+        let interface = self.interface_number()?;
+
+        // The HID descriptor gives the length of the report descriptor
+        let mut hid_descriptor = [0u8; 9];
+        let request = ControlRequest::new(REQUEST_IN | REQUEST_STANDARD | RECIPIENT_INTERFACE, GET_DESCRIPTOR, (HID_DESCRIPTOR_TYPE as u16) << 8, interface);
+        self.usb.control_in(request, &mut hid_descriptor, Some(CONTROL_TIMEOUT))?;
+        if hid_descriptor[1] != HID_DESCRIPTOR_TYPE {
+            return Err(EfiErrorKind::DeviceError.into());
+        }
+
+        let mut descriptor = vec![0u8; LittleEndian::read_u16(&hid_descriptor[7..]) as usize];
+        let request = ControlRequest::new(REQUEST_IN | REQUEST_STANDARD | RECIPIENT_INTERFACE, GET_DESCRIPTOR, (REPORT_DESCRIPTOR_TYPE as u16) << 8, interface);
+        self.usb.control_in(request, &mut descriptor, Some(CONTROL_TIMEOUT))?;
+        Ok(descriptor)
+    }
+
+    /// Switches the device to the boot protocol so that its reports have the fixed boot layout
+    pub fn set_boot_protocol(&self) -> Result<()> {
+        let request = ControlRequest::new(REQUEST_CLASS | RECIPIENT_INTERFACE, SET_PROTOCOL, 0, self.interface_number()?);
+        self.usb.control(request, Some(CONTROL_TIMEOUT))
+    }
+
+    /// Sets how often the device repeats an unchanged report in units of 4ms. 0 only reports changes.
+    pub fn set_idle(&self, duration: u8) -> Result<()> {
+        let request = ControlRequest::new(REQUEST_CLASS | RECIPIENT_INTERFACE, SET_IDLE, (duration as u16) << 8, self.interface_number()?);
+        self.usb.control(request, Some(CONTROL_TIMEOUT))
+    }
+
+    /// Sets a keyboard's lock LEDs
+    pub fn set_leds(&self, leds: Leds) -> Result<()> {
+        let request = ControlRequest::new(REQUEST_CLASS | RECIPIENT_INTERFACE, SET_REPORT, OUTPUT_REPORT << 8, self.interface_number()?);
+        self.usb.control_out(request, &[leds.bits()], Some(CONTROL_TIMEOUT))
+    }
+
+    /// Reads the next report into the buffer. Returns the report's length.
+    /// Fails with `Timeout` if the device has nothing to report in time.
+    pub fn read_report(&self, buf: &mut [u8], timeout: Option<Duration>) -> Result<usize> {
+        self.usb.interrupt_in(self.endpoint.address, buf, timeout)
+    }
+
+    /// Reads the next report of a keyboard in boot protocol mode
+    pub fn read_keyboard(&self, timeout: Option<Duration>) -> Result<KeyboardReport> {
+        let mut buf = self.report_buffer();
+        let len = self.read_report(&mut buf, timeout)?;
+        KeyboardReport::parse(&buf[..len]).ok_or_else(|| EfiErrorKind::DeviceError.into())
+    }
+
+    /// Reads the next report of a mouse in boot protocol mode
+    pub fn read_mouse(&self, timeout: Option<Duration>) -> Result<MouseReport> {
+        let mut buf = self.report_buffer();
+        let len = self.read_report(&mut buf, timeout)?;
+        MouseReport::parse(&buf[..len]).ok_or_else(|| EfiErrorKind::DeviceError.into())
+    }
+
+    fn report_buffer(&self) -> Vec<u8> {
+        vec![0u8; cmp::max(self.endpoint.max_packet_size as usize, 8)]
+    }
+
+    fn interface_number(&self) -> Result<u16> {
+        Ok(self.usb.interface_descriptor()?.interface_number as u16)
+    }
+
+    fn boot_protocol(&self) -> Option<u8> {
+        self.usb.interface_descriptor().ok()
+            .and_then(|i| if i.subclass == BOOT_SUBCLASS { Some(i.protocol) } else { None })
+    }
+}
+
+flags! {
+    /// Modifier keys in a boot keyboard report
+    pub struct Modifiers(u8) {
+        const LEFT_CONTROL = 1 << 0;
+        const LEFT_SHIFT = 1 << 1;
+        const LEFT_ALT = 1 << 2;
+        const LEFT_GUI = 1 << 3;
+        const RIGHT_CONTROL = 1 << 4;
+        const RIGHT_SHIFT = 1 << 5;
+        const RIGHT_ALT = 1 << 6;
+        const RIGHT_GUI = 1 << 7;
+    }
+}
+
+impl Modifiers {
+    /// Either Shift key is down
+    pub fn shift(&self) -> bool {
+        self.0 & (Self::LEFT_SHIFT.0 | Self::RIGHT_SHIFT.0) != 0
+    }
+
+    /// Either Ctrl key is down
+    pub fn control(&self) -> bool {
+        self.0 & (Self::LEFT_CONTROL.0 | Self::RIGHT_CONTROL.0) != 0
+    }
+
+    /// Either Alt key is down
+    pub fn alt(&self) -> bool {
+        self.0 & (Self::LEFT_ALT.0 | Self::RIGHT_ALT.0) != 0
+    }
+}
+
+flags! {
+    /// Keyboard lock LEDs, as sent in a boot keyboard's output report
+    pub struct Leds(u8) {
+        const NUM_LOCK = 1 << 0;
+        const CAPS_LOCK = 1 << 1;
+        const SCROLL_LOCK = 1 << 2;
+        const COMPOSE = 1 << 3;
+        const KANA = 1 << 4;
+    }
+}
+
+/// A boot protocol keyboard report: the modifiers and up to six keys held down, as usages from
+/// the Keyboard/Keypad usage page
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyboardReport {
+    pub modifiers: Modifiers,
+    pub keys: [u8; 6],
+}
+
+impl KeyboardReport {
+    pub fn parse(report: &[u8]) -> Option<Self> {
+        if report.len() < 8 {
+            return None;
+        }
+
+        let mut keys = [0u8; 6];
+        keys.copy_from_slice(&report[2..8]);
+        Some(KeyboardReport { modifiers: Modifiers(report[0]), keys })
+    }
+
+    /// More keys are down than the report can hold. The keys are then all reported as 0x01.
+    pub fn is_rollover(&self) -> bool {
+        self.keys.iter().all(|k| *k == 0x01)
+    }
+
+    /// The keys that are down
+    pub fn pressed(&self) -> Vec<u8> {
+        self.keys.iter().cloned().filter(|k| *k > 0x03).collect()
+    }
+
+    /// The keys that are down now but weren't in `previous`, i.e. the key presses this report is for
+    pub fn pressed_since(&self, previous: &KeyboardReport) -> Vec<u8> {
+        if self.is_rollover() {
+            return Vec::new();
+        }
+
+        self.pressed().into_iter().filter(|k| !previous.keys.contains(k)).collect()
+    }
+}
+
+/// A boot protocol mouse report
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MouseReport {
+    /// Bit 0 is the left button, bit 1 the right and bit 2 the middle
+    pub buttons: u8,
+    pub dx: i8,
+    pub dy: i8,
+    /// Not part of the boot protocol, 0 if the mouse doesn't send it
+    pub wheel: i8,
+}
+
+impl MouseReport {
+    pub fn parse(report: &[u8]) -> Option<Self> {
+        if report.len() < 3 {
+            return None;
+        }
+
+        Some(MouseReport {
+            buttons: report[0],
+            dx: report[1] as i8,
+            dy: report[2] as i8,
+            wheel: report.get(3).map_or(0, |w| *w as i8),
+        })
+    }
+
+    pub fn left(&self) -> bool {
+        self.buttons & 0x01 != 0
+    }
+
+    pub fn right(&self) -> bool {
+        self.buttons & 0x02 != 0
+    }
+
+    pub fn middle(&self) -> bool {
+        self.buttons & 0x04 != 0
+    }
+}
+
+/// The character a Keyboard/Keypad page usage types on a US keyboard, if any
+pub fn usage_to_char(usage: u8, shift: bool) -> Option<char> {
+    const DIGITS: &[u8] = b"1234567890";
+    const SHIFTED_DIGITS: &[u8] = b"!@#$%^&*()";
+    const PUNCTUATION: &[u8] = b"-=[]\\#;'`,./";
+    const SHIFTED_PUNCTUATION: &[u8] = b"_+{}|~:\"~<>?";
+
+    let c = match usage {
+        0x04...0x1D => {
+            let c = b'a' + usage - 0x04;
+            if shift { c.to_ascii_uppercase() } else { c }
+        },
+        0x1E...0x27 => if shift { SHIFTED_DIGITS[(usage - 0x1E) as usize] } else { DIGITS[(usage - 0x1E) as usize] },
+        0x28 => b'\r',
+        0x29 => 0x1B,
+        0x2A => 0x08,
+        0x2B => b'\t',
+        0x2C => b' ',
+        0x2D...0x38 => if shift { SHIFTED_PUNCTUATION[(usage - 0x2D) as usize] } else { PUNCTUATION[(usage - 0x2D) as usize] },
+        _ => return None,
+    };
+
+    Some(c as char)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReportKind {
+    Input,
+    Output,
+    Feature,
+}
+
+/// A field of a report as described by an Input, Output or Feature item of a report descriptor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportField {
+    pub kind: ReportKind,
+    /// 0 when the device doesn't use report IDs
+    pub report_id: u8,
+    /// Offset of the field's first bit from the start of the report, not counting the report ID byte
+    pub bit_offset: u32,
+    /// Size of each element in bits
+    pub size: u32,
+    pub count: u32,
+    /// Usages as (usage page << 16) | usage ID. For array fields these are the usages the
+    /// elements' values index (offset by the logical minimum).
+    pub usages: Vec<u32>,
+    pub logical_min: i32,
+    pub logical_max: i32,
+    /// The main item's data bits
+    pub flags: u32,
+}
+
+impl ReportField {
+    /// Padding, or a value that can't change
+    pub fn is_constant(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+
+    /// Each element is a value of its own usage rather than an index into the usages
+    pub fn is_variable(&self) -> bool {
+        self.flags & 0x02 != 0
+    }
+
+    /// Values are changes since the last report rather than absolute
+    pub fn is_relative(&self) -> bool {
+        self.flags & 0x04 != 0
+    }
+
+    /// Extracts element `index` from a report (without its report ID byte), sign extended if the
+    /// logical minimum is negative
+    pub fn value(&self, report: &[u8], index: u32) -> Option<i32> {
+        if index >= self.count || self.size == 0 || self.size > 32 {
+            return None;
+        }
+
+        let start = self.bit_offset + index * self.size;
+        let mut value: u32 = 0;
+        for bit in 0..self.size {
+            let pos = start + bit;
+            let byte = *report.get((pos / 8) as usize)?;
+            value |= (((byte >> (pos % 8)) & 1) as u32) << bit;
+        }
+
+        if self.logical_min < 0 && self.size < 32 {
+            Some(sign_extend(value, self.size))
+        } else {
+            Some(value as i32)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct GlobalState {
+    usage_page: u16,
+    logical_min: i32,
+    logical_max: i32,
+    report_size: u32,
+    report_id: u8,
+    report_count: u32,
+}
+
+/// Decodes a report descriptor into the fields of the reports it describes. Collections are
+/// flattened away, physical ranges and units are ignored.
+pub fn parse_report_descriptor(descriptor: &[u8]) -> Result<Vec<ReportField>> {
+    let mut fields = Vec::new();
+    let mut global = GlobalState::default();
+    let mut global_stack = Vec::new();
+    let mut usages: Vec<u32> = Vec::new();
+    let mut usage_min = None;
+    let mut offsets: Vec<(ReportKind, u8, u32)> = Vec::new();
+
+    let mut i = 0;
+    while i < descriptor.len() {
+        let prefix = descriptor[i];
+        if prefix == 0xFE {
+            // Long item: skip its data, no long items are defined
+            let len = *descriptor.get(i + 1).ok_or(EfiErrorKind::InvalidParameter)? as usize;
+            i += 3 + len;
+            continue;
+        }
+
+        let size = match prefix & 0x03 { 3 => 4, s => s as usize };
+        let data = descriptor.get(i + 1..i + 1 + size).ok_or(EfiErrorKind::InvalidParameter)?;
+        let value = data.iter().rev().fold(0u32, |v, b| (v << 8) | *b as u32);
+        let signed = if size == 0 { 0 } else { sign_extend(value, size as u32 * 8) };
+        i += 1 + size;
+
+        match (prefix >> 2) & 0x03 {
+            // Main
+            0 => {
+                let kind = match prefix >> 4 {
+                    0x8 => Some(ReportKind::Input),
+                    0x9 => Some(ReportKind::Output),
+                    0xB => Some(ReportKind::Feature),
+                    _ => None, // Collections
+                };
+
+                if let Some(kind) = kind {
+                    let offset = match offsets.iter().position(|o| o.0 == kind && o.1 == global.report_id) {
+                        Some(pos) => pos,
+                        None => {
+                            offsets.push((kind, global.report_id, 0));
+                            offsets.len() - 1
+                        }
+                    };
+
+                    let usage_page = global.usage_page as u32;
+                    fields.push(ReportField {
+                        kind,
+                        report_id: global.report_id,
+                        bit_offset: offsets[offset].2,
+                        size: global.report_size,
+                        count: global.report_count,
+                        usages: usages.iter().map(|u| if *u > 0xFFFF { *u } else { (usage_page << 16) | *u }).collect(),
+                        logical_min: global.logical_min,
+                        logical_max: global.logical_max,
+                        flags: value,
+                    });
+                    offsets[offset].2 += global.report_size * global.report_count;
+                }
+
+                // Local items only apply to the main item that follows them
+                usages.clear();
+                usage_min = None;
+            },
+            // Global
+            1 => match prefix >> 4 {
+                0x0 => global.usage_page = value as u16,
+                0x1 => global.logical_min = signed,
+                0x2 => global.logical_max = if global.logical_min >= 0 { value as i32 } else { signed },
+                0x7 => global.report_size = value,
+                0x8 => global.report_id = value as u8,
+                0x9 => global.report_count = value,
+                0xA => global_stack.push(global),
+                0xB => global = global_stack.pop().ok_or(EfiErrorKind::InvalidParameter)?,
+                _ => {},
+            },
+            // Local
+            2 => match prefix >> 4 {
+                0x0 => usages.push(value),
+                0x1 => usage_min = Some(value),
+                0x2 => {
+                    let min = usage_min.take().ok_or(EfiErrorKind::InvalidParameter)?;
+                    if value < min || value - min >= MAX_USAGE_RANGE {
+                        return Err(EfiErrorKind::InvalidParameter.into());
+                    }
+                    usages.extend(min..value + 1);
+                },
+                _ => {},
+            },
+            _ => {},
+        }
+    }
+
+    Ok(fields)
+}
+
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The boot keyboard report descriptor from appendix B of the HID specification
+    const BOOT_KEYBOARD: &[u8] = &[
+        0x05, 0x01, 0x09, 0x06, 0xA1, 0x01, 0x05, 0x07, 0x19, 0xE0, 0x29, 0xE7, 0x15, 0x00, 0x25, 0x01,
+        0x75, 0x01, 0x95, 0x08, 0x81, 0x02, 0x95, 0x01, 0x75, 0x08, 0x81, 0x01, 0x95, 0x05, 0x75, 0x01,
+        0x05, 0x08, 0x19, 0x01, 0x29, 0x05, 0x91, 0x02, 0x95, 0x01, 0x75, 0x03, 0x91, 0x01, 0x95, 0x06,
+        0x75, 0x08, 0x15, 0x00, 0x25, 0x65, 0x05, 0x07, 0x19, 0x00, 0x29, 0x65, 0x81, 0x00, 0xC0,
+    ];
+
+    #[test]
+    fn boot_keyboard_descriptor_is_parsed() {
+        let fields = parse_report_descriptor(BOOT_KEYBOARD).unwrap();
+        assert_eq!(fields.len(), 5);
+
+        let modifiers = &fields[0];
+        assert_eq!((modifiers.kind, modifiers.bit_offset, modifiers.size, modifiers.count), (ReportKind::Input, 0, 1, 8));
+        assert!(modifiers.is_variable() && !modifiers.is_constant());
+        assert_eq!(modifiers.usages.first(), Some(&0x0007_00E0));
+        assert_eq!(modifiers.usages.len(), 8);
+
+        assert!(fields[1].is_constant());
+
+        let leds = &fields[2];
+        assert_eq!((leds.kind, leds.bit_offset, leds.count), (ReportKind::Output, 0, 5));
+        assert_eq!(leds.usages[0], 0x0008_0001);
+        assert_eq!(fields[3].bit_offset, 5);
+
+        let keys = &fields[4];
+        assert_eq!((keys.kind, keys.bit_offset, keys.size, keys.count), (ReportKind::Input, 16, 8, 6));
+        assert!(!keys.is_variable());
+        assert_eq!((keys.logical_min, keys.logical_max), (0, 0x65));
+
+        let report = [0x02, 0x00, 0x04, 0x05, 0, 0, 0, 0];
+        assert_eq!(modifiers.value(&report, 1), Some(1));
+        assert_eq!(keys.value(&report, 1), Some(0x05));
+        assert_eq!(keys.value(&report, 6), None);
+    }
+
+    #[test]
+    fn signed_values_and_push_pop_are_handled() {
+        // Usage Page (Generic Desktop), Push, Logical Min (-127), Logical Max (127), Report Size (8),
+        // Report Count (2), Report ID (3), Usage X, Usage Y, Input (Data, Var, Rel), Pop, Input
+        let descriptor = [0x05, 0x01, 0xA4, 0x15, 0x81, 0x25, 0x7F, 0x75, 0x08, 0x95, 0x02, 0x85, 0x03,
+                          0x09, 0x30, 0x09, 0x31, 0x81, 0x06, 0xB4, 0x81, 0x00];
+        let fields = parse_report_descriptor(&descriptor).unwrap();
+        assert_eq!(fields[0].report_id, 3);
+        assert_eq!((fields[0].logical_min, fields[0].logical_max), (-127, 127));
+        assert!(fields[0].is_relative());
+        assert_eq!(fields[0].usages, vec![0x0001_0030, 0x0001_0031]);
+        assert_eq!(fields[0].value(&[0xFF, 0x05], 0), Some(-1));
+        assert_eq!(fields[0].value(&[0xFF, 0x05], 1), Some(5));
+        assert_eq!((fields[1].report_id, fields[1].size), (0, 0));
+
+        assert!(parse_report_descriptor(&[0xB4]).is_err());
+        assert!(parse_report_descriptor(&[0x26, 0x01]).is_err());
+    }
+
+    #[test]
+    fn boot_reports_are_decoded() {
+        let previous = KeyboardReport::parse(&[0, 0, 0x04, 0, 0, 0, 0, 0]).unwrap();
+        let report = KeyboardReport::parse(&[0x20, 0, 0x04, 0x05, 0, 0, 0, 0]).unwrap();
+        assert!(report.modifiers.shift() && !report.modifiers.control());
+        assert_eq!(report.pressed_since(&previous), vec![0x05]);
+        assert!(KeyboardReport::parse(&[0, 0, 1, 1, 1, 1, 1, 1]).unwrap().is_rollover());
+        assert!(KeyboardReport::parse(&[0; 4]).is_none());
+
+        let mouse = MouseReport::parse(&[0x05, 0xFE, 0x03]).unwrap();
+        assert!(mouse.left() && mouse.middle() && !mouse.right());
+        assert_eq!((mouse.dx, mouse.dy, mouse.wheel), (-2, 3, 0));
+    }
+
+    #[test]
+    fn usages_map_to_us_characters() {
+        assert_eq!(usage_to_char(0x04, false), Some('a'));
+        assert_eq!(usage_to_char(0x1D, true), Some('Z'));
+        assert_eq!(usage_to_char(0x1E, true), Some('!'));
+        assert_eq!(usage_to_char(0x27, false), Some('0'));
+        assert_eq!(usage_to_char(0x38, true), Some('?'));
+        assert_eq!(usage_to_char(0x3A, false), None);
+    }
+}
@@ -0,0 +1,459 @@
+use ffi::{
+    usb_io::{
+        EFI_USB_IO_PROTOCOL,
+        EFI_USB_IO_PROTOCOL_GUID,
+        EFI_USB_DATA_DIRECTION,
+        EFI_USB_DEVICE_REQUEST,
+        EFI_USB_DEVICE_DESCRIPTOR,
+        EFI_USB_CONFIG_DESCRIPTOR,
+        EFI_USB_INTERFACE_DESCRIPTOR,
+        EFI_USB_ENDPOINT_DESCRIPTOR,
+        EFI_USB_ERR_TIMEOUT,
+    },
+    EFI_HANDLE,
+    EFI_STATUS,
+    IsSuccess,
+    CHAR16,
+    VOID,
+};
+use device_path::DevicePath;
+use device_path::device_path_of;
+use utils::{as_slice, handles_by_protocol, open_protocol};
+use boxed::EfiBox;
+use alloc::{String, Vec};
+use core::{ptr, mem, slice, cmp, time::Duration};
+use {Result, EfiError, EfiErrorKind};
+
+pub mod hid;
+
+// USB devices through EFI_USB_IO_PROTOCOL, which the USB bus driver installs on a handle for every
+// interface of every device it enumerates. So a composite device shows up as several UsbDevices,
+// each seeing the same device and configuration descriptors but its own interface and endpoints.
+
+/// Direction bit of `ControlRequest::request_type`: device to host
+pub const REQUEST_IN: u8 = 0x80;
+/// Type bits of `ControlRequest::request_type`
+pub const REQUEST_STANDARD: u8 = 0x00;
+pub const REQUEST_CLASS: u8 = 0x20;
+pub const REQUEST_VENDOR: u8 = 0x40;
+/// Recipient bits of `ControlRequest::request_type`
+pub const RECIPIENT_DEVICE: u8 = 0x00;
+pub const RECIPIENT_INTERFACE: u8 = 0x01;
+pub const RECIPIENT_ENDPOINT: u8 = 0x02;
+
+// The language ID used for strings when a device supports it
+const LANGUAGE_EN_US: u16 = 0x0409;
+
+/// The setup packet of a control transfer, without the length which comes from the data buffer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ControlRequest {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+}
+
+impl ControlRequest {
+    pub fn new(request_type: u8, request: u8, value: u16, index: u16) -> Self {
+        ControlRequest { request_type, request, value, index }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DeviceDescriptor {
+    /// USB specification version in BCD e.g. 0x0200 for 2.0
+    pub usb_version: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub max_packet_size0: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Device release number in BCD
+    pub device_version: u16,
+    /// String descriptor indices, 0 if the device has no such string
+    pub manufacturer_index: u8,
+    pub product_index: u8,
+    pub serial_number_index: u8,
+    pub num_configurations: u8,
+}
+
+impl From<EFI_USB_DEVICE_DESCRIPTOR> for DeviceDescriptor {
+    fn from(d: EFI_USB_DEVICE_DESCRIPTOR) -> Self {
+        DeviceDescriptor {
+            usb_version: d.BcdUSB,
+            class: d.DeviceClass,
+            subclass: d.DeviceSubClass,
+            protocol: d.DeviceProtocol,
+            max_packet_size0: d.MaxPacketSize0,
+            vendor_id: d.IdVendor,
+            product_id: d.IdProduct,
+            device_version: d.BcdDevice,
+            manufacturer_index: d.StrManufacturer,
+            product_index: d.StrProduct,
+            serial_number_index: d.StrSerialNumber,
+            num_configurations: d.NumConfigurations,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ConfigDescriptor {
+    /// Length of the configuration with all its interface, endpoint and class descriptors
+    pub total_length: u16,
+    pub num_interfaces: u8,
+    pub configuration_value: u8,
+    pub configuration_index: u8,
+    pub self_powered: bool,
+    pub remote_wakeup: bool,
+    /// Maximum power draw in mA
+    pub max_power_ma: u16,
+}
+
+impl From<EFI_USB_CONFIG_DESCRIPTOR> for ConfigDescriptor {
+    fn from(d: EFI_USB_CONFIG_DESCRIPTOR) -> Self {
+        ConfigDescriptor {
+            total_length: d.TotalLength,
+            num_interfaces: d.NumInterfaces,
+            configuration_value: d.ConfigurationValue,
+            configuration_index: d.Configuration,
+            self_powered: d.Attributes & 0x40 != 0,
+            remote_wakeup: d.Attributes & 0x20 != 0,
+            max_power_ma: d.MaxPower as u16 * 2, // In units of 2mA
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InterfaceDescriptor {
+    pub interface_number: u8,
+    pub alternate_setting: u8,
+    pub num_endpoints: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub interface_index: u8,
+}
+
+impl From<EFI_USB_INTERFACE_DESCRIPTOR> for InterfaceDescriptor {
+    fn from(d: EFI_USB_INTERFACE_DESCRIPTOR) -> Self {
+        InterfaceDescriptor {
+            interface_number: d.InterfaceNumber,
+            alternate_setting: d.AlternateSetting,
+            num_endpoints: d.NumEndpoints,
+            class: d.InterfaceClass,
+            subclass: d.InterfaceSubClass,
+            protocol: d.InterfaceProtocol,
+            interface_index: d.Interface,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransferType {
+    Control,
+    Isochronous,
+    Bulk,
+    Interrupt,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EndpointDescriptor {
+    /// Endpoint number with the direction in bit 7, as passed to the transfer methods
+    pub address: u8,
+    pub transfer_type: TransferType,
+    pub max_packet_size: u16,
+    /// Polling interval for interrupt and isochronous endpoints in frames or microframes
+    pub interval: u8,
+}
+
+impl EndpointDescriptor {
+    /// Whether data flows from the device to the host
+    pub fn is_in(&self) -> bool {
+        self.address & 0x80 != 0
+    }
+
+    pub fn number(&self) -> u8 {
+        self.address & 0x0F
+    }
+}
+
+impl From<EFI_USB_ENDPOINT_DESCRIPTOR> for EndpointDescriptor {
+    fn from(d: EFI_USB_ENDPOINT_DESCRIPTOR) -> Self {
+        let transfer_type = match d.Attributes & 0x03 {
+            0 => TransferType::Control,
+            1 => TransferType::Isochronous,
+            2 => TransferType::Bulk,
+            _ => TransferType::Interrupt,
+        };
+
+        EndpointDescriptor {
+            address: d.EndpointAddress,
+            transfer_type,
+            max_packet_size: d.MaxPacketSize & 0x07FF, // Upper bits are additional transactions per microframe
+            interval: d.Interval,
+        }
+    }
+}
+
+/// One interface of a USB device. Timeouts of `None` wait until the transfer completes or fails.
+pub struct UsbDevice {
+    handle: EFI_HANDLE,
+    protocol: *const EFI_USB_IO_PROTOCOL,
+}
+
+impl UsbDevice {
+    pub fn from_handle(handle: EFI_HANDLE) -> Result<Self> {
+        let protocol = open_protocol::<EFI_USB_IO_PROTOCOL>(handle, &EFI_USB_IO_PROTOCOL_GUID)?;
+
+        Ok(UsbDevice { handle, protocol })
+    }
+
+    /// Every USB interface in the system
+    pub fn all() -> Result<Vec<Self>> {
+        handles_by_protocol(&EFI_USB_IO_PROTOCOL_GUID)?.into_iter().map(Self::from_handle).collect()
+    }
+
+    pub fn handle(&self) -> EFI_HANDLE {
+        self.handle
+    }
+
+    pub fn device_path(&self) -> Result<DevicePath> {
+        device_path_of(self.handle)
+    }
+
+    pub fn device_descriptor(&self) -> Result<DeviceDescriptor> {
+        let mut descriptor: EFI_USB_DEVICE_DESCRIPTOR = unsafe { mem::zeroed() };
+        unsafe {
+            ret_on_err!(((*self.protocol).UsbGetDeviceDescriptor)(self.protocol, &mut descriptor));
+        }
+        Ok(descriptor.into())
+    }
+
+    /// The descriptor of the active configuration
+    pub fn config_descriptor(&self) -> Result<ConfigDescriptor> {
+        let mut descriptor: EFI_USB_CONFIG_DESCRIPTOR = unsafe { mem::zeroed() };
+        unsafe {
+            ret_on_err!(((*self.protocol).UsbGetConfigDescriptor)(self.protocol, &mut descriptor));
+        }
+        Ok(descriptor.into())
+    }
+
+    /// The descriptor of this interface
+    pub fn interface_descriptor(&self) -> Result<InterfaceDescriptor> {
+        let mut descriptor: EFI_USB_INTERFACE_DESCRIPTOR = unsafe { mem::zeroed() };
+        unsafe {
+            ret_on_err!(((*self.protocol).UsbGetInterfaceDescriptor)(self.protocol, &mut descriptor));
+        }
+        Ok(descriptor.into())
+    }
+
+    /// The descriptors of this interface's endpoints, not including the default control endpoint
+    pub fn endpoint_descriptors(&self) -> Result<Vec<EndpointDescriptor>> {
+        let count = self.interface_descriptor()?.num_endpoints;
+        (0..count).map(|index| {
+            let mut descriptor: EFI_USB_ENDPOINT_DESCRIPTOR = unsafe { mem::zeroed() };
+            unsafe {
+                ret_on_err!(((*self.protocol).UsbGetEndpointDescriptor)(self.protocol, index, &mut descriptor));
+            }
+            Ok(descriptor.into())
+        }).collect()
+    }
+
+    /// The language IDs the device has strings in
+    pub fn supported_languages(&self) -> Result<Vec<u16>> {
+        let mut table: *mut u16 = ptr::null_mut();
+        let mut size: u16 = 0;
+        unsafe {
+            ret_on_err!(((*self.protocol).UsbGetSupportedLanguages)(self.protocol, &mut table, &mut size));
+        }
+
+        if table.is_null() {
+            return Ok(Vec::new());
+        }
+
+        // The table belongs to the bus driver so it's copied rather than freed
+        Ok(unsafe { slice::from_raw_parts(table, size as usize / 2) }.to_vec())
+    }
+
+    /// The string with the given descriptor index in US English, or the device's first language
+    /// if it doesn't have English strings
+    pub fn string(&self, index: u8) -> Result<String> {
+        if index == 0 {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        let languages = self.supported_languages()?;
+        let language = if languages.contains(&LANGUAGE_EN_US) {
+            LANGUAGE_EN_US
+        } else {
+            *languages.first().ok_or_else(|| EfiError::from(EfiErrorKind::NotFound))?
+        };
+
+        self.string_in(language, index)
+    }
+
+    /// The string with the given descriptor index in the given language
+    pub fn string_in(&self, language: u16, index: u8) -> Result<String> {
+        let mut string: *mut CHAR16 = ptr::null_mut();
+        unsafe {
+            ret_on_err!(((*self.protocol).UsbGetStringDescriptor)(self.protocol, language, index, &mut string));
+        }
+
+        if string.is_null() {
+            return Err(EfiErrorKind::NotFound.into());
+        }
+
+        let string = unsafe { EfiBox::from_raw(string) };  // Putting it in a box for proper cleanup on exit
+        Ok(String::from_utf16_lossy(unsafe { as_slice(string.as_raw()) }))
+    }
+
+    /// A control transfer with no data stage
+    pub fn control(&self, request: ControlRequest, timeout: Option<Duration>) -> Result<()> {
+        self.control_transfer(request, EFI_USB_DATA_DIRECTION::EfiUsbNoData, ptr::null_mut(), 0, timeout)
+    }
+
+    /// A control transfer reading into `buf`. The request type should have `REQUEST_IN` set.
+    pub fn control_in(&self, request: ControlRequest, buf: &mut [u8], timeout: Option<Duration>) -> Result<()> {
+        self.control_transfer(request, EFI_USB_DATA_DIRECTION::EfiUsbDataIn, buf.as_mut_ptr(), buf.len(), timeout)
+    }
+
+    /// A control transfer writing `data`
+    pub fn control_out(&self, request: ControlRequest, data: &[u8], timeout: Option<Duration>) -> Result<()> {
+        self.control_transfer(request, EFI_USB_DATA_DIRECTION::EfiUsbDataOut, data.as_ptr() as *mut u8, data.len(), timeout)
+    }
+
+    /// Reads from a bulk IN endpoint. Returns the number of bytes received.
+    pub fn bulk_in(&self, endpoint: u8, buf: &mut [u8], timeout: Option<Duration>) -> Result<usize> {
+        self.bulk_transfer(endpoint | 0x80, buf.as_mut_ptr(), buf.len(), timeout)
+    }
+
+    /// Writes to a bulk OUT endpoint. Returns the number of bytes sent.
+    pub fn bulk_out(&self, endpoint: u8, data: &[u8], timeout: Option<Duration>) -> Result<usize> {
+        self.bulk_transfer(endpoint & 0x7F, data.as_ptr() as *mut u8, data.len(), timeout)
+    }
+
+    /// Reads from an interrupt IN endpoint. Returns the number of bytes received.
+    pub fn interrupt_in(&self, endpoint: u8, buf: &mut [u8], timeout: Option<Duration>) -> Result<usize> {
+        self.interrupt_transfer(endpoint | 0x80, buf.as_mut_ptr(), buf.len(), timeout)
+    }
+
+    /// Writes to an interrupt OUT endpoint. Returns the number of bytes sent.
+    pub fn interrupt_out(&self, endpoint: u8, data: &[u8], timeout: Option<Duration>) -> Result<usize> {
+        self.interrupt_transfer(endpoint & 0x7F, data.as_ptr() as *mut u8, data.len(), timeout)
+    }
+
+    /// Resets the port the device is attached to and reconfigures it. Other interfaces of the device are
+    /// affected too.
+    pub fn reset_port(&self) -> Result<()> {
+        unsafe {
+            ret_on_err!(((*self.protocol).UsbPortReset)(self.protocol));
+        }
+        Ok(())
+    }
+
+    fn control_transfer(&self, request: ControlRequest, direction: EFI_USB_DATA_DIRECTION, data: *mut u8, len: usize, timeout: Option<Duration>) -> Result<()> {
+        if len > u16::max_value() as usize {
+            return Err(EfiErrorKind::InvalidParameter.into());
+        }
+
+        let request = EFI_USB_DEVICE_REQUEST {
+            RequestType: request.request_type,
+            Request: request.request,
+            Value: request.value,
+            Index: request.index,
+            Length: len as u16,
+        };
+
+        let mut usb_status = 0;
+        let status = unsafe {
+            ((*self.protocol).UsbControlTransfer)(self.protocol, &request, direction, timeout_ms(timeout) as u32, data as *mut VOID, len, &mut usb_status)
+        };
+
+        transfer_result(status, usb_status)
+    }
+
+    fn bulk_transfer(&self, endpoint: u8, data: *mut u8, len: usize, timeout: Option<Duration>) -> Result<usize> {
+        let mut transferred = len;
+        let mut usb_status = 0;
+        let status = unsafe {
+            ((*self.protocol).UsbBulkTransfer)(self.protocol, endpoint, data as *mut VOID, &mut transferred, timeout_ms(timeout), &mut usb_status)
+        };
+
+        transfer_result(status, usb_status)?;
+        Ok(cmp::min(transferred, len))
+    }
+
+    fn interrupt_transfer(&self, endpoint: u8, data: *mut u8, len: usize, timeout: Option<Duration>) -> Result<usize> {
+        let mut transferred = len;
+        let mut usb_status = 0;
+        let status = unsafe {
+            ((*self.protocol).UsbSyncInterruptTransfer)(self.protocol, endpoint, data as *mut VOID, &mut transferred, timeout_ms(timeout), &mut usb_status)
+        };
+
+        transfer_result(status, usb_status)?;
+        Ok(cmp::min(transferred, len))
+    }
+}
+
+// Timeouts are in milliseconds with 0 meaning wait forever, so anything shorter than a millisecond is
+// rounded up rather than turning into an infinite wait
+fn timeout_ms(timeout: Option<Duration>) -> usize {
+    match timeout {
+        None => 0,
+        Some(t) => {
+            let ms = t.as_secs().saturating_mul(1000).saturating_add((t.subsec_nanos() as u64 + 999_999) / 1_000_000);
+            cmp::min(cmp::max(ms, 1), u32::max_value() as u64) as usize
+        }
+    }
+}
+
+// Transfers fail with EFI_DEVICE_ERROR for most USB level errors, so a timeout is only visible in the
+// USB status
+fn transfer_result(status: EFI_STATUS, usb_status: u32) -> Result<()> {
+    if IsSuccess(status) {
+        Ok(())
+    } else if usb_status & EFI_USB_ERR_TIMEOUT != 0 {
+        Err(EfiErrorKind::Timeout.into())
+    } else {
+        Err(EfiError::from(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi::{EFI_DEVICE_ERROR, EFI_SUCCESS};
+    use ffi::usb_io::EFI_USB_ERR_STALL;
+
+    #[test]
+    fn timeouts_are_converted_to_milliseconds() {
+        assert_eq!(timeout_ms(None), 0);
+        assert_eq!(timeout_ms(Some(Duration::from_secs(2))), 2000);
+        assert_eq!(timeout_ms(Some(Duration::from_micros(1))), 1);
+        assert_eq!(timeout_ms(Some(Duration::from_micros(1500))), 2);
+        assert_eq!(timeout_ms(Some(Duration::from_secs(0))), 1);
+    }
+
+    #[test]
+    fn usb_status_refines_transfer_errors() {
+        assert!(transfer_result(EFI_SUCCESS, 0).is_ok());
+        assert_eq!(transfer_result(EFI_DEVICE_ERROR, EFI_USB_ERR_TIMEOUT).unwrap_err().kind(), EfiErrorKind::Timeout);
+        assert_eq!(transfer_result(EFI_DEVICE_ERROR, EFI_USB_ERR_STALL).unwrap_err().kind(), EfiErrorKind::DeviceError);
+    }
+
+    #[test]
+    fn endpoint_descriptors_are_decoded() {
+        let raw = EFI_USB_ENDPOINT_DESCRIPTOR { Length: 7, DescriptorType: 5, EndpointAddress: 0x81, Attributes: 0x03, MaxPacketSize: 0x0808, Interval: 10 };
+        let endpoint = EndpointDescriptor::from(raw);
+        assert!(endpoint.is_in());
+        assert_eq!(endpoint.number(), 1);
+        assert_eq!(endpoint.transfer_type, TransferType::Interrupt);
+        assert_eq!(endpoint.max_packet_size, 8);
+
+        let raw = EFI_USB_CONFIG_DESCRIPTOR { Length: 9, DescriptorType: 2, TotalLength: 32, NumInterfaces: 1, ConfigurationValue: 1, Configuration: 0, Attributes: 0xC0, MaxPower: 50 };
+        let config = ConfigDescriptor::from(raw);
+        assert!(config.self_powered && !config.remote_wakeup);
+        assert_eq!(config.max_power_ma, 100);
+    }
+}
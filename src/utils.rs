@@ -1,25 +1,30 @@
 // TODO: Write a proc macro called derive(TupleWrapper) which automaticlly impls Wrapper trait for any tuple struct wrapping types
-use ffi::CHAR16;
-use core::{self, mem, slice, fmt};
-use {EfiError, EfiErrorKind};
-use alloc::str;
+use ffi::{boot_services::{EFI_LOCATE_SEARCH_TYPE, EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL}, CHAR16, EFI_GUID, EFI_HANDLE, VOID};
+use boxed::EfiBox;
+use byteorder::{ByteOrder, LittleEndian};
+use core::{slice, fmt, mem, ptr, time::Duration};
+use {EfiError, EfiErrorKind, boot_services, image_handle};
+use alloc::{str, String, Vec};
 
 pub trait Wrapper {
     type Inner;
     fn inner_ptr(&self) -> *const Self::Inner;
 }
 
+#[cfg(feature = "net")]
 pub fn to_ptr<'a, W: Wrapper>(value: Option<&'a W>) -> *const W::Inner {
-    value.map_or(core::ptr::null(), |v| v.inner_ptr())
+    value.map_or(::core::ptr::null(), |v| v.inner_ptr())
 }
 
 
 // TODO: In rust an Option<*T> is represented the same way as *T
 // So we can use Options directly instead of using this method
+#[cfg(feature = "net")]
 pub fn to_opt<'a, P, R>(ptr: *const P) -> Option<&'a R> {
-    unsafe { ptr.as_ref().map(|p| mem::transmute(p)) }  
+    unsafe { ptr.as_ref().map(|p| ::core::mem::transmute(p)) }  
 }
 
+#[cfg(feature = "net")]
 macro_rules! impl_wrapper {
     ($wrapper: ty, $inner: ty) => {
         impl ::utils::Wrapper for $wrapper {
@@ -32,7 +37,20 @@ macro_rules! impl_wrapper {
     };
 }
 
+// Returns on error statuses only. Warnings mean the operation was carried out so they fall through.
+// Call sites where a warning is of interest to the caller use to_res_with_warning() instead.
 macro_rules! ret_on_err {
+    ($e:expr) => {
+        let status: ::ffi::EFI_STATUS = $e;
+        if $crate::ffi::IsError(status) {
+            return Err($crate::EfiError::from(status));
+        }
+    }
+}
+
+// Returns on anything but success, warnings included. For the few call sites where a warning means
+// the result can't be used, e.g. EFI_WARN_BUFFER_TOO_SMALL for output that was truncated.
+macro_rules! ret_unless_success {
     ($e:expr) => {
         let status: ::ffi::EFI_STATUS = $e;
         if !$crate::ffi::IsSuccess(status) {
@@ -41,6 +59,61 @@ macro_rules! ret_on_err {
     }
 }
 
+// Declares a set of bit flags: a wrapper around the raw bits with the named flags as associated constants,
+// combinable with `|`. Bits listed after `ignoring` aren't flags (e.g. the valid bit of a shift state) and
+// are dropped by from_bits(). Methods specific to a set go in a separate impl block next to it.
+macro_rules! flags {
+    (
+        $(#[$attr:meta])*
+        pub struct $name:ident($bits:ty) $(ignoring $ignored:ident)* {
+            $(
+                $(#[$flag_attr:meta])*
+                const $flag:ident = $value:expr;
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        pub struct $name($bits);
+
+        impl $name {
+            $(
+                $(#[$flag_attr])*
+                pub const $flag: $name = $name($value);
+            )*
+
+            pub fn empty() -> Self {
+                $name(0)
+            }
+
+            pub fn from_bits(bits: $bits) -> Self {
+                $name(bits $(& !$ignored)*)
+            }
+
+            pub fn bits(&self) -> $bits {
+                self.0
+            }
+
+            pub fn contains(&self, other: $name) -> bool {
+                self.0 & other.0 == other.0
+            }
+        }
+
+        impl ::core::ops::BitOr for $name {
+            type Output = Self;
+            fn bitor(self, rhs: Self) -> Self {
+                $name(self.0 | rhs.0)
+            }
+        }
+
+        impl ::core::ops::BitOrAssign for $name {
+            fn bitor_assign(&mut self, rhs: Self) {
+                self.0 |= rhs.0;
+            }
+        }
+    };
+}
+
 pub unsafe fn as_slice<'a>(s: *const CHAR16) -> &'a [CHAR16] {
     let mut len = 0;
     let mut temp = s;
@@ -52,6 +125,124 @@ pub unsafe fn as_slice<'a>(s: *const CHAR16) -> &'a [CHAR16] {
     slice::from_raw_parts(s, len)
 }
 
+/// Converts the given str to a null-terminated UCS-2 buffer as expected by most UEFI APIs.
+/// Code points outside the BMP are encoded as surrogate pairs which UEFI will not render properly
+/// but there's not much else we can do about them.
+pub fn to_null_terminated_utf16(s: &str) -> Vec<CHAR16> {
+    let mut utf16_buf = s.encode_utf16().collect::<Vec<_>>();
+    utf16_buf.push(0); // Adding null terminator
+    utf16_buf
+}
+
+/// Converts the given str to a null-terminated ASCII buffer as used for language codes and
+/// performance record names. Characters that aren't ASCII, and nulls that would cut the string
+/// short, are replaced by '?'.
+pub fn to_null_terminated_ascii(s: &str) -> Vec<u8> {
+    let mut bytes = s.chars().map(|c| if c.is_ascii() && c != '\0' { c as u8 } else { b'?' }).collect::<Vec<_>>();
+    bytes.push(0);
+    bytes
+}
+
+/// Splits a list of RFC 4646 language codes separated by ';' as returned by HII and
+/// EFI_COMPONENT_NAME2_PROTOCOL. Empty entries are dropped.
+pub fn split_languages(languages: &str) -> Vec<String> {
+    languages.split(';').filter(|l| !l.is_empty()).map(String::from).collect()
+}
+
+// Lets the serde impls of types that are written out as strings (addresses, GUIDs) parse them back with FromStr
+#[cfg(feature = "with-serde")]
+pub fn deserialize_from_str<'de, T: str::FromStr, D: ::serde::Deserializer<'de>>(deserializer: D, expecting: &'static str) -> Result<T, D::Error> {
+    use serde::de::{self, Visitor, Unexpected};
+    use core::marker::PhantomData;
+
+    struct FromStrVisitor<T>(PhantomData<T>, &'static str);
+
+    impl<'de, T: str::FromStr> Visitor<'de> for FromStrVisitor<T> {
+        type Value = T;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(self.1)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+            v.parse().map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+        }
+    }
+
+    deserializer.deserialize_str(FromStrVisitor(PhantomData, expecting))
+}
+
+/// The duration in microseconds as taken by Stall(), timer-less timeouts and the like.
+/// Durations too long to fit are capped at u64::MAX rather than wrapping around.
+pub fn as_micros_saturating(dur: Duration) -> u64 {
+    dur.as_secs().saturating_mul(1_000_000).saturating_add(dur.subsec_micros() as u64)
+}
+
+/// The on-the-wire (little endian) representation of a GUID as found in variables, signature lists, partition tables etc.
+pub fn guid_to_bytes(guid: &EFI_GUID) -> [u8; 16] {
+    let mut bytes = [0_u8; 16];
+    LittleEndian::write_u32(&mut bytes[0..4], guid.0);
+    LittleEndian::write_u16(&mut bytes[4..6], guid.1);
+    LittleEndian::write_u16(&mut bytes[6..8], guid.2);
+    bytes[8..16].copy_from_slice(&guid.3);
+    bytes
+}
+
+/// Reads a GUID from the first 16 bytes of the given buffer. Panics if the buffer is shorter than that.
+pub fn guid_from_bytes(bytes: &[u8]) -> EFI_GUID {
+    let mut data4 = [0_u8; 8];
+    data4.copy_from_slice(&bytes[8..16]);
+    EFI_GUID(LittleEndian::read_u32(&bytes[0..4]), LittleEndian::read_u16(&bytes[4..6]), LittleEndian::read_u16(&bytes[6..8]), data4)
+}
+
+/// The handles of all the devices that support the given protocol
+pub fn handles_by_protocol(guid: &EFI_GUID) -> ::Result<Vec<EFI_HANDLE>> {
+    let bs = boot_services();
+    let mut no_of_handles = 0;
+    let mut handle_buf: *const EFI_HANDLE = ptr::null_mut();
+    unsafe {
+        ret_on_err!(((*bs).LocateHandleBuffer)(EFI_LOCATE_SEARCH_TYPE::ByProtocol, guid, ptr::null() as *const VOID, &mut no_of_handles, &mut handle_buf));
+    }
+
+    if no_of_handles == 0 || handle_buf.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let handle_buf = unsafe { EfiBox::from_raw(handle_buf as *mut EFI_HANDLE) };  // Putting it in a box for proper cleanup on exit
+    let handles = unsafe { slice::from_raw_parts(handle_buf.as_raw() as *const EFI_HANDLE, no_of_handles) };
+    Ok(handles.to_vec())
+}
+
+/// The first instance of the given protocol found. Fails with `NotFound` if there's none.
+pub fn locate_protocol<T>(guid: &EFI_GUID) -> ::Result<*const T> {
+    let bs = boot_services();
+    let protocol: *const T = ptr::null();
+    unsafe {
+        ret_on_err!(((*bs).LocateProtocol)(guid, ptr::null(), mem::transmute(&protocol)));
+    }
+
+    if protocol.is_null() {
+        return Err(EfiErrorKind::NotFound.into());
+    }
+
+    Ok(protocol)
+}
+
+/// The given protocol on the given handle, opened by the running image. Fails with `Unsupported` if the handle doesn't have it.
+pub fn open_protocol<T>(handle: EFI_HANDLE, guid: &EFI_GUID) -> ::Result<*const T> {
+    let bs = boot_services();
+    let protocol: *const T = ptr::null();
+    unsafe {
+        ret_on_err!(((*bs).OpenProtocol)(handle, guid, mem::transmute(&protocol), image_handle(), ptr::null(), EFI_OPEN_PROTOCOL_BY_HANDLE_PROTOCOL));
+    }
+
+    if protocol.is_null() {
+        return Err(EfiErrorKind::Unsupported.into());
+    }
+
+    Ok(protocol)
+}
+
 #[derive(Debug)]
 pub struct NullTerminatedAsciiStr<'a> {
     buffer: &'a [u8]
@@ -0,0 +1,719 @@
+// A compact, self-describing binary encoding for serde data model values. It is a subset of CBOR (RFC 7049):
+// integers, floats, booleans, null, byte and text strings, arrays and maps. The serde data model is mapped onto it as follows:
+//
+// - unit, unit structs and `None` are null. `Some(v)` is just `v`
+// - newtype structs are their contents
+// - sequences and tuples are arrays, maps are maps
+// - structs are maps keyed by field name so that fields can be added later with `#[serde(default)]`
+// - unit variants are the variant index, other variants are a single entry map from the variant index to the contents
+//
+// Since Some(v) is indistinguishable from v, nested options such as Option<Option<T>> do not round trip.
+
+use serde::{ser, de, Serialize, Deserialize};
+use byteorder::{ByteOrder, BigEndian};
+use alloc::{String, Vec};
+use core::{fmt, str};
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_SIMPLE: u8 = 7;
+
+const INFO_INDEFINITE: u8 = 31;
+
+const FALSE: u8 = 0xF4;
+const TRUE: u8 = 0xF5;
+const NULL: u8 = 0xF6;
+const FLOAT32: u8 = 0xFA;
+const FLOAT64: u8 = 0xFB;
+const BREAK: u8 = 0xFF;
+
+// Corrupted input must not be able to blow the (small) firmware stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, PartialEq)]
+pub enum CodecError {
+    UnexpectedEnd,
+    Malformed,
+    TrailingBytes,
+    TooDeep,
+    Custom(String),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CodecError::UnexpectedEnd => write!(f, "Unexpected end of input"),
+            CodecError::Malformed => write!(f, "Malformed input"),
+            CodecError::TrailingBytes => write!(f, "Trailing bytes after the value"),
+            CodecError::TooDeep => write!(f, "Value nested too deeply"),
+            CodecError::Custom(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl ser::Error for CodecError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CodecError::Custom(format!("{}", msg))
+    }
+}
+
+impl de::Error for CodecError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CodecError::Custom(format!("{}", msg))
+    }
+}
+
+pub fn to_bytes<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>, CodecError> {
+    let mut encoder = Encoder { output: Vec::new() };
+    value.serialize(&mut encoder)?;
+    Ok(encoder.output)
+}
+
+pub fn from_bytes<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, CodecError> {
+    let mut decoder = Decoder { input: bytes, depth: 0 };
+    let value = T::deserialize(&mut decoder)?;
+    if !decoder.input.is_empty() {
+        return Err(CodecError::TrailingBytes);
+    }
+
+    Ok(value)
+}
+
+struct Encoder {
+    output: Vec<u8>,
+}
+
+impl Encoder {
+    fn write_head(&mut self, major: u8, value: u64) {
+        let major = major << 5;
+        if value < 24 {
+            self.output.push(major | value as u8);
+        } else if value <= u8::max_value() as u64 {
+            self.output.push(major | 24);
+            self.output.push(value as u8);
+        } else if value <= u16::max_value() as u64 {
+            let mut buf = [0_u8; 2];
+            BigEndian::write_u16(&mut buf, value as u16);
+            self.output.push(major | 25);
+            self.output.extend_from_slice(&buf);
+        } else if value <= u32::max_value() as u64 {
+            let mut buf = [0_u8; 4];
+            BigEndian::write_u32(&mut buf, value as u32);
+            self.output.push(major | 26);
+            self.output.extend_from_slice(&buf);
+        } else {
+            let mut buf = [0_u8; 8];
+            BigEndian::write_u64(&mut buf, value);
+            self.output.push(major | 27);
+            self.output.extend_from_slice(&buf);
+        }
+    }
+
+    // Containers of unknown length are written with the indefinite length encoding and terminated with a break
+    fn begin_container(&mut self, major: u8, len: Option<usize>) -> Compound {
+        match len {
+            Some(len) => {
+                self.write_head(major, len as u64);
+                Compound { encoder: self, indefinite: false }
+            },
+            None => {
+                self.output.push(major << 5 | INFO_INDEFINITE);
+                Compound { encoder: self, indefinite: true }
+            }
+        }
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Encoder {
+    type Ok = ();
+    type Error = CodecError;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), CodecError> {
+        self.output.push(if v { TRUE } else { FALSE });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), CodecError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), CodecError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), CodecError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), CodecError> {
+        if v < 0 {
+            self.write_head(MAJOR_NEGATIVE, !v as u64); // !v == -1 - v
+        } else {
+            self.write_head(MAJOR_UNSIGNED, v as u64);
+        }
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), CodecError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), CodecError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), CodecError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), CodecError> {
+        self.write_head(MAJOR_UNSIGNED, v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), CodecError> {
+        let mut buf = [0_u8; 4];
+        BigEndian::write_f32(&mut buf, v);
+        self.output.push(FLOAT32);
+        self.output.extend_from_slice(&buf);
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), CodecError> {
+        let mut buf = [0_u8; 8];
+        BigEndian::write_f64(&mut buf, v);
+        self.output.push(FLOAT64);
+        self.output.extend_from_slice(&buf);
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), CodecError> {
+        let mut buf = [0_u8; 4];
+        self.serialize_str(v.encode_utf8(&mut buf))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), CodecError> {
+        self.write_head(MAJOR_TEXT, v.len() as u64);
+        self.output.extend_from_slice(v.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), CodecError> {
+        self.write_head(MAJOR_BYTES, v.len() as u64);
+        self.output.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), CodecError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), CodecError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), CodecError> {
+        self.output.push(NULL);
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), CodecError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, _variant: &'static str) -> Result<(), CodecError> {
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<(), CodecError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, variant_index: u32, _variant: &'static str, value: &T) -> Result<(), CodecError> {
+        self.write_head(MAJOR_MAP, 1);
+        self.write_head(MAJOR_UNSIGNED, variant_index as u64);
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'a>, CodecError> {
+        Ok(self.begin_container(MAJOR_ARRAY, len))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a>, CodecError> {
+        Ok(self.begin_container(MAJOR_ARRAY, Some(len)))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a>, CodecError> {
+        Ok(self.begin_container(MAJOR_ARRAY, Some(len)))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, variant_index: u32, _variant: &'static str, len: usize) -> Result<Compound<'a>, CodecError> {
+        self.write_head(MAJOR_MAP, 1);
+        self.write_head(MAJOR_UNSIGNED, variant_index as u64);
+        Ok(self.begin_container(MAJOR_ARRAY, Some(len)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound<'a>, CodecError> {
+        Ok(self.begin_container(MAJOR_MAP, len))
+    }
+
+    // The field count is not known up front because of #[serde(skip_serializing_if)]
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>, CodecError> {
+        Ok(self.begin_container(MAJOR_MAP, None))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, variant_index: u32, _variant: &'static str, _len: usize) -> Result<Compound<'a>, CodecError> {
+        self.write_head(MAJOR_MAP, 1);
+        self.write_head(MAJOR_UNSIGNED, variant_index as u64);
+        Ok(self.begin_container(MAJOR_MAP, None))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct Compound<'a> {
+    encoder: &'a mut Encoder,
+    indefinite: bool,
+}
+
+impl<'a> Compound<'a> {
+    fn end(self) -> Result<(), CodecError> {
+        if self.indefinite {
+            self.encoder.output.push(BREAK);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeSeq for Compound<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CodecError> {
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Compound::end(self)
+    }
+}
+
+impl<'a> ser::SerializeTuple for Compound<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CodecError> {
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Compound::end(self)
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for Compound<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CodecError> {
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Compound::end(self)
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for Compound<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CodecError> {
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Compound::end(self)
+    }
+}
+
+impl<'a> ser::SerializeMap for Compound<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), CodecError> {
+        key.serialize(&mut *self.encoder)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CodecError> {
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Compound::end(self)
+    }
+}
+
+impl<'a> ser::SerializeStruct for Compound<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), CodecError> {
+        ser::Serializer::serialize_str(&mut *self.encoder, key)?;
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Compound::end(self)
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for Compound<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), CodecError> {
+        ser::Serializer::serialize_str(&mut *self.encoder, key)?;
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Compound::end(self)
+    }
+}
+
+struct Decoder<'de> {
+    input: &'de [u8],
+    depth: usize,
+}
+
+impl<'de> Decoder<'de> {
+    fn peek(&self) -> Result<u8, CodecError> {
+        self.input.first().cloned().ok_or(CodecError::UnexpectedEnd)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'de [u8], CodecError> {
+        if self.input.len() < len {
+            return Err(CodecError::UnexpectedEnd);
+        }
+
+        let (taken, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    // Returns the major type and the argument. The argument is None for indefinite lengths.
+    fn read_head(&mut self) -> Result<(u8, Option<u64>), CodecError> {
+        let initial = self.take(1)?[0];
+        let major = initial >> 5;
+        let value = match initial & 0x1F {
+            info @ 0...23 => Some(info as u64),
+            24 => Some(self.take(1)?[0] as u64),
+            25 => Some(BigEndian::read_u16(self.take(2)?) as u64),
+            26 => Some(BigEndian::read_u32(self.take(4)?) as u64),
+            27 => Some(BigEndian::read_u64(self.take(8)?)),
+            INFO_INDEFINITE if major == MAJOR_ARRAY || major == MAJOR_MAP => None,
+            _ => return Err(CodecError::Malformed),
+        };
+
+        Ok((major, value))
+    }
+
+    fn read_len(&mut self, len: u64) -> Result<usize, CodecError> {
+        // Every element takes at least one byte so a length longer than the input cannot be genuine.
+        // Checking it here stops corrupted lengths from causing huge allocations.
+        if len > self.input.len() as u64 {
+            return Err(CodecError::UnexpectedEnd);
+        }
+        Ok(len as usize)
+    }
+
+    // Consumes the break that ends an indefinite length container if it is next
+    fn at_break(&mut self) -> Result<bool, CodecError> {
+        if self.peek()? == BREAK {
+            self.input = &self.input[1..];
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), CodecError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(CodecError::TooDeep);
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for &'a mut Decoder<'de> {
+    type Error = CodecError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let initial = self.peek()?;
+        if initial >> 5 == MAJOR_SIMPLE {
+            self.input = &self.input[1..];
+            return match initial {
+                FALSE => visitor.visit_bool(false),
+                TRUE => visitor.visit_bool(true),
+                NULL => visitor.visit_unit(),
+                FLOAT32 => visitor.visit_f32(BigEndian::read_f32(self.take(4)?)),
+                FLOAT64 => visitor.visit_f64(BigEndian::read_f64(self.take(8)?)),
+                _ => Err(CodecError::Malformed),
+            };
+        }
+
+        match self.read_head()? {
+            (MAJOR_UNSIGNED, Some(value)) => visitor.visit_u64(value),
+            (MAJOR_NEGATIVE, Some(value)) => {
+                if value > i64::max_value() as u64 {
+                    return Err(CodecError::Malformed);
+                }
+                visitor.visit_i64(!(value as i64))
+            },
+            (MAJOR_BYTES, Some(len)) => {
+                let len = self.read_len(len)?;
+                visitor.visit_borrowed_bytes(self.take(len)?)
+            },
+            (MAJOR_TEXT, Some(len)) => {
+                let len = self.read_len(len)?;
+                let text = str::from_utf8(self.take(len)?).map_err(|_| CodecError::Malformed)?;
+                visitor.visit_borrowed_str(text)
+            },
+            (MAJOR_ARRAY, len) => {
+                let remaining = match len { Some(len) => Some(self.read_len(len)?), None => None };
+                self.enter()?;
+                let value = visitor.visit_seq(Elements { decoder: self, remaining })?;
+                self.leave();
+                Ok(value)
+            },
+            (MAJOR_MAP, len) => {
+                let remaining = match len { Some(len) => Some(self.read_len(len)?), None => None };
+                self.enter()?;
+                let value = visitor.visit_map(Elements { decoder: self, remaining })?;
+                self.leave();
+                Ok(value)
+            },
+            _ => Err(CodecError::Malformed),
+        }
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        if self.peek()? == NULL {
+            self.input = &self.input[1..];
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, CodecError> {
+        let major = self.peek()? >> 5;
+        if major == MAJOR_UNSIGNED {
+            return visitor.visit_enum(Variant { decoder: self, unit: true });
+        }
+
+        match self.read_head()? {
+            (MAJOR_MAP, Some(1)) => {
+                self.enter()?;
+                let value = visitor.visit_enum(Variant { decoder: self, unit: false })?;
+                self.leave();
+                Ok(value)
+            },
+            _ => Err(CodecError::Malformed),
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+// Access to the elements of an array or the entries of a map
+struct Elements<'a, 'de: 'a> {
+    decoder: &'a mut Decoder<'de>,
+    remaining: Option<usize>, // None for indefinite length
+}
+
+impl<'a, 'de> Elements<'a, 'de> {
+    fn has_next(&mut self) -> Result<bool, CodecError> {
+        match self.remaining {
+            Some(0) => Ok(false),
+            Some(ref mut remaining) => {
+                *remaining -= 1;
+                Ok(true)
+            },
+            None => Ok(!self.decoder.at_break()?),
+        }
+    }
+}
+
+impl<'a, 'de> de::SeqAccess<'de> for Elements<'a, 'de> {
+    type Error = CodecError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, CodecError> {
+        if !self.has_next()? {
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.decoder).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining
+    }
+}
+
+impl<'a, 'de> de::MapAccess<'de> for Elements<'a, 'de> {
+    type Error = CodecError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, CodecError> {
+        if !self.has_next()? {
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.decoder).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, CodecError> {
+        seed.deserialize(&mut *self.decoder)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining
+    }
+}
+
+// An enum variant. Unit variants are a bare index, all others a single entry map whose key is the index.
+struct Variant<'a, 'de: 'a> {
+    decoder: &'a mut Decoder<'de>,
+    unit: bool,
+}
+
+impl<'a, 'de> de::EnumAccess<'de> for Variant<'a, 'de> {
+    type Error = CodecError;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), CodecError> {
+        let index = seed.deserialize(&mut *self.decoder)?;
+        Ok((index, self))
+    }
+}
+
+impl<'a, 'de> de::VariantAccess<'de> for Variant<'a, 'de> {
+    type Error = CodecError;
+
+    fn unit_variant(self) -> Result<(), CodecError> {
+        if self.unit { Ok(()) } else { Err(CodecError::Malformed) }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, CodecError> {
+        if self.unit {
+            return Err(CodecError::Malformed);
+        }
+        seed.deserialize(self.decoder)
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, CodecError> {
+        if self.unit {
+            return Err(CodecError::Malformed);
+        }
+        de::Deserializer::deserialize_any(self.decoder, visitor)
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, CodecError> {
+        if self.unit {
+            return Err(CodecError::Malformed);
+        }
+        de::Deserializer::deserialize_any(self.decoder, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{String, Vec};
+
+    #[test]
+    fn integers_use_shortest_encoding() {
+        assert_eq!(to_bytes(&10_u64).unwrap(), [0x0A]);
+        assert_eq!(to_bytes(&500_u32).unwrap(), [0x19, 0x01, 0xF4]);
+        assert_eq!(to_bytes(&-1_i32).unwrap(), [0x20]);
+        assert_eq!(to_bytes(&-500_i32).unwrap(), [0x39, 0x01, 0xF3]);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let value: Vec<Option<String>> = vec![Some("boot".into()), None, Some(String::new())];
+        let bytes = to_bytes(&value).unwrap();
+        assert_eq!(from_bytes::<Vec<Option<String>>>(&bytes).unwrap(), value);
+
+        assert_eq!(from_bytes::<u64>(&to_bytes(&u64::max_value()).unwrap()).unwrap(), u64::max_value());
+        assert_eq!(from_bytes::<bool>(&to_bytes(&true).unwrap()).unwrap(), true);
+    }
+
+    #[test]
+    fn indefinite_length_array() {
+        assert_eq!(from_bytes::<Vec<u64>>(&[0x9F, 0x01, 0x02, 0xFF]).unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(from_bytes::<u64>(&[0x01, 0x02]).unwrap_err(), CodecError::TrailingBytes);
+        assert_eq!(from_bytes::<String>(&[0x65, b'a']).unwrap_err(), CodecError::UnexpectedEnd);
+        assert_eq!(from_bytes::<Vec<u64>>(&[0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]).unwrap_err(), CodecError::UnexpectedEnd);
+        assert_eq!(from_bytes::<Vec<u64>>(&[0x1F]).unwrap_err(), CodecError::Malformed);
+    }
+
+    #[test]
+    fn guids_are_text() {
+        let guid: ::Guid = guid!("C12A7328-F81F-11D2-BA4B-00A0C93EC93B");
+        let bytes = to_bytes(&guid).unwrap();
+        assert_eq!(from_bytes::<String>(&bytes).unwrap(), "C12A7328-F81F-11D2-BA4B-00A0C93EC93B");
+        assert_eq!(from_bytes::<::Guid>(&bytes).unwrap(), guid);
+        assert!(from_bytes::<::Guid>(&to_bytes("C12A7328").unwrap()).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "dns", not(feature = "runtime-driver")))]
+    fn addresses_and_dns_names_are_text() {
+        use net::{IpAddr, SocketAddr, dns::Name};
+
+        let ip: IpAddr = "10.0.2.15".parse().unwrap();
+        let bytes = to_bytes(&ip).unwrap();
+        assert_eq!(from_bytes::<String>(&bytes).unwrap(), "10.0.2.15");
+        assert_eq!(from_bytes::<IpAddr>(&bytes).unwrap(), ip);
+        let addr: SocketAddr = "10.0.2.2:53".parse().unwrap();
+        assert_eq!(from_bytes::<SocketAddr>(&to_bytes(&addr).unwrap()).unwrap(), addr);
+        assert!(from_bytes::<IpAddr>(&to_bytes("10.0.2").unwrap()).is_err());
+
+        let packet = b"\x07example\x03com\x00\x03www\xc0\x00";
+        let name = Name::scan(&packet[13..], packet).unwrap();
+        assert_eq!(from_bytes::<String>(&to_bytes(&name).unwrap()).unwrap(), "www.example.com");
+    }
+}
@@ -0,0 +1,258 @@
+use ffi::{
+    runtime_services::{
+        EFI_VARIABLE_NON_VOLATILE,
+        EFI_VARIABLE_BOOTSERVICE_ACCESS,
+        EFI_VARIABLE_RUNTIME_ACCESS,
+        EFI_VARIABLE_HARDWARE_ERROR_RECORD,
+        EFI_VARIABLE_AUTHENTICATED_WRITE_ACCESS,
+        EFI_VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS,
+        EFI_VARIABLE_APPEND_WRITE,
+    },
+    security::{
+        EFI_CERT_TYPE_PKCS7_GUID,
+        WIN_CERT_TYPE_EFI_GUID,
+        WIN_CERT_REVISION_2_0,
+    },
+    EFI_BUFFER_TOO_SMALL,
+    EFI_NOT_FOUND,
+    UINT32,
+    UINT64,
+    UINTN,
+    VOID,
+};
+use core::ptr;
+use alloc::Vec;
+use utils::{to_null_terminated_utf16, guid_to_bytes};
+use byteorder::{ByteOrder, LittleEndian};
+use time::DateTime;
+use {system_table, Result, Guid, EfiErrorKind};
+#[cfg(feature = "with-serde")] use serde::{Serialize, de::DeserializeOwned};
+
+#[cfg(feature = "with-serde")] mod codec;
+
+flags! {
+    /// Attributes of a UEFI variable.
+    /// Can be combined using the `|` operator e.g. `Attributes::NON_VOLATILE | Attributes::BOOTSERVICE_ACCESS`
+    pub struct Attributes(UINT32) {
+        const NON_VOLATILE = EFI_VARIABLE_NON_VOLATILE;
+        const BOOTSERVICE_ACCESS = EFI_VARIABLE_BOOTSERVICE_ACCESS;
+        const RUNTIME_ACCESS = EFI_VARIABLE_RUNTIME_ACCESS;
+        const HARDWARE_ERROR_RECORD = EFI_VARIABLE_HARDWARE_ERROR_RECORD;
+        const AUTHENTICATED_WRITE_ACCESS = EFI_VARIABLE_AUTHENTICATED_WRITE_ACCESS;
+        const TIME_BASED_AUTHENTICATED_WRITE_ACCESS = EFI_VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS;
+        const APPEND_WRITE = EFI_VARIABLE_APPEND_WRITE;
+    }
+}
+
+/// Statistics about the storage available for variables of a given set of attributes
+#[derive(Debug, Copy, Clone)]
+pub struct StorageInfo {
+    /// Total size of the storage available for variables with the given attributes
+    pub max_storage: u64,
+    /// Size of the storage still available for variables with the given attributes
+    pub remaining_storage: u64,
+    /// Maximum size of an individual variable with the given attributes
+    pub max_variable_size: u64,
+}
+
+impl StorageInfo {
+    /// Whether a variable whose data (plus name) is `size` bytes long can still be stored.
+    /// Useful to check capacity before writing large variables instead of failing midway.
+    pub fn can_store(&self, size: usize) -> bool {
+        let size = size as u64;
+        size <= self.max_variable_size && size <= self.remaining_storage
+    }
+}
+
+/// Returns information about the storage for variables with the given attributes
+pub fn storage_info(attributes: Attributes) -> Result<StorageInfo> {
+    let rs = system_table().RuntimeServices;
+    let mut max_storage: UINT64 = 0;
+    let mut remaining_storage: UINT64 = 0;
+    let mut max_variable_size: UINT64 = 0;
+
+    unsafe {
+        ret_on_err!(((*rs).QueryVariableInfo)(attributes.bits(), &mut max_storage, &mut remaining_storage, &mut max_variable_size));
+    }
+
+    Ok(StorageInfo { max_storage, remaining_storage, max_variable_size })
+}
+
+
+/// Reads the value of the given variable
+pub fn get(name: &str, vendor_guid: &Guid) -> Result<Vec<u8>> {
+    get_with_attributes(name, vendor_guid).map(|(data, _)| data)
+}
+
+/// Reads the value of the given variable along with its attributes
+pub fn get_with_attributes(name: &str, vendor_guid: &Guid) -> Result<(Vec<u8>, Attributes)> {
+    let rs = system_table().RuntimeServices;
+    let name = to_null_terminated_utf16(name);
+    let mut attributes: UINT32 = 0;
+    let mut data_size: UINTN = 0;
+
+    // First call with a zero sized buffer to find out the size of the data
+    let status = unsafe { ((*rs).GetVariable)(name.as_ptr(), vendor_guid, &mut attributes, &mut data_size, ptr::null_mut()) };
+    if status != EFI_BUFFER_TOO_SMALL {
+        ret_on_err!(status);
+        return Ok((Vec::new(), Attributes(attributes))); // Variable exists but has no data
+    }
+
+    let mut data = vec![0_u8; data_size];
+    unsafe {
+        ret_on_err!(((*rs).GetVariable)(name.as_ptr(), vendor_guid, &mut attributes, &mut data_size, data.as_mut_ptr() as *mut VOID));
+    }
+
+    data.truncate(data_size);
+    Ok((data, Attributes(attributes)))
+}
+
+/// Same as `get()` except that it returns `None` if the variable does not exist
+pub fn try_get(name: &str, vendor_guid: &Guid) -> Result<Option<Vec<u8>>> {
+    match get(name, vendor_guid) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) => if e.kind() == EfiErrorKind::NotFound { Ok(None) } else { Err(e) }
+    }
+}
+
+/// Creates or updates the given variable.
+pub fn set(name: &str, vendor_guid: &Guid, attributes: Attributes, data: &[u8]) -> Result<()> {
+    let rs = system_table().RuntimeServices;
+    let name = to_null_terminated_utf16(name);
+    unsafe {
+        ret_on_err!(((*rs).SetVariable)(name.as_ptr(), vendor_guid, attributes.bits(), data.len(), data.as_ptr() as *const VOID));
+    }
+
+    Ok(())
+}
+
+/// Deletes the given variable. Succeeds even if the variable does not exist.
+pub fn delete(name: &str, vendor_guid: &Guid) -> Result<()> {
+    let rs = system_table().RuntimeServices;
+    let name = to_null_terminated_utf16(name);
+    let status = unsafe { ((*rs).SetVariable)(name.as_ptr(), vendor_guid, 0, 0, ptr::null()) };
+    if status == EFI_NOT_FOUND {
+        return Ok(());
+    }
+
+    ret_on_err!(status);
+    Ok(())
+}
+
+/// Serializes the value and writes it to the given non-volatile, boot services only variable.
+/// The encoding is a compact self-describing binary format (a subset of CBOR) that tolerates
+/// fields being added to structs later as long as they are marked `#[serde(default)]`.
+#[cfg(feature = "with-serde")]
+pub fn store<T: Serialize>(name: &str, vendor_guid: &Guid, value: &T) -> Result<()> {
+    let data = codec::to_bytes(value).map_err(|_| ::EfiError::from(EfiErrorKind::InvalidParameter))?;
+    set(name, vendor_guid, Attributes::NON_VOLATILE | Attributes::BOOTSERVICE_ACCESS, &data)
+}
+
+/// Reads back a value written with `store()`.
+/// Fails with `NotFound` if the variable does not exist and `VolumeCorrupted` if its contents cannot be decoded as `T`.
+#[cfg(feature = "with-serde")]
+pub fn load<T: DeserializeOwned>(name: &str, vendor_guid: &Guid) -> Result<T> {
+    let data = get(name, vendor_guid)?;
+    codec::from_bytes(&data).map_err(|_| EfiErrorKind::VolumeCorrupted.into())
+}
+
+// Size of WIN_CERTIFICATE_UEFI_GUID minus the cert data
+const WIN_CERTIFICATE_UEFI_GUID_HEADER_SIZE: usize = 24;
+
+/// The data that must be signed to authorize a time-based authenticated write (EFI_VARIABLE_AUTHENTICATION_2).
+/// The caller is expected to create a detached PKCS#7 SignedData over it with the appropriate key
+/// (e.g. the PK for writing KEK) and pass the signature to `set_authenticated()` along with the same arguments.
+/// `TIME_BASED_AUTHENTICATED_WRITE_ACCESS` is added to the attributes if not already present, as `set_authenticated()` does.
+pub fn authenticated_write_signable_data(name: &str, vendor_guid: &Guid, attributes: Attributes, timestamp: &DateTime, data: &[u8]) -> Vec<u8> {
+    let attributes = authenticated_attributes(attributes);
+    let mut signable = Vec::new();
+    for c in name.encode_utf16() { // The name without null terminator
+        let mut buf = [0_u8; 2];
+        LittleEndian::write_u16(&mut buf, c);
+        signable.extend_from_slice(&buf);
+    }
+
+    signable.extend_from_slice(&guid_to_bytes(vendor_guid));
+
+    let mut attributes_buf = [0_u8; 4];
+    LittleEndian::write_u32(&mut attributes_buf, attributes.bits());
+    signable.extend_from_slice(&attributes_buf);
+
+    signable.extend_from_slice(&auth_timestamp_bytes(timestamp));
+    signable.extend_from_slice(data);
+    signable
+}
+
+/// Builds an EFI_VARIABLE_AUTHENTICATION_2 descriptor with the given PKCS#7 signature followed by the variable data.
+/// This is the buffer to be passed to SetVariable() for a time-based authenticated write.
+/// Also the format of the .auth files generated by tools such as sign-efi-sig-list.
+pub fn authenticated_payload(timestamp: &DateTime, pkcs7_signature: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(16 + WIN_CERTIFICATE_UEFI_GUID_HEADER_SIZE + pkcs7_signature.len() + data.len());
+    payload.extend_from_slice(&auth_timestamp_bytes(timestamp));
+
+    let mut win_cert_header = [0_u8; 8];
+    LittleEndian::write_u32(&mut win_cert_header[0..4], (WIN_CERTIFICATE_UEFI_GUID_HEADER_SIZE + pkcs7_signature.len()) as u32);
+    LittleEndian::write_u16(&mut win_cert_header[4..6], WIN_CERT_REVISION_2_0);
+    LittleEndian::write_u16(&mut win_cert_header[6..8], WIN_CERT_TYPE_EFI_GUID);
+    payload.extend_from_slice(&win_cert_header);
+    payload.extend_from_slice(&guid_to_bytes(&EFI_CERT_TYPE_PKCS7_GUID));
+    payload.extend_from_slice(pkcs7_signature);
+
+    payload.extend_from_slice(data);
+    payload
+}
+
+/// Performs a time-based authenticated write of the given variable.
+/// `TIME_BASED_AUTHENTICATED_WRITE_ACCESS` is added to the attributes if not already present.
+/// The signature must have been created over `authenticated_write_signable_data()` with the same arguments.
+pub fn set_authenticated(name: &str, vendor_guid: &Guid, attributes: Attributes, timestamp: &DateTime, pkcs7_signature: &[u8], data: &[u8]) -> Result<()> {
+    set(name, vendor_guid, authenticated_attributes(attributes), &authenticated_payload(timestamp, pkcs7_signature, data))
+}
+
+// The attributes of an authenticated write. The signed attributes have to match the written ones exactly.
+fn authenticated_attributes(attributes: Attributes) -> Attributes {
+    attributes | Attributes::TIME_BASED_AUTHENTICATED_WRITE_ACCESS
+}
+
+// EFI_TIME as it appears in EFI_VARIABLE_AUTHENTICATION_2.
+// The spec requires Pad1, Nanosecond, TimeZone, Daylight and Pad2 to be zero.
+fn auth_timestamp_bytes(timestamp: &DateTime) -> [u8; 16] {
+    let mut bytes = [0_u8; 16];
+    LittleEndian::write_u16(&mut bytes[0..2], timestamp.year);
+    bytes[2] = timestamp.month;
+    bytes[3] = timestamp.day;
+    bytes[4] = timestamp.hour;
+    bytes[5] = timestamp.minute;
+    bytes[6] = timestamp.second;
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi::EFI_GUID;
+
+    #[test]
+    fn authenticated_payload_layout() {
+        let timestamp = DateTime::new(2018, 4, 1, 10, 20, 30);
+        let payload = authenticated_payload(&timestamp, &[0xAA, 0xBB], &[0x01]);
+        assert_eq!(payload.len(), 16 + 24 + 2 + 1);
+        assert_eq!(&payload[0..8], &[0xE2, 0x07, 4, 1, 10, 20, 30, 0]);
+        assert_eq!(&payload[16..24], &[26, 0, 0, 0, 0x00, 0x02, 0xF1, 0x0E]);
+        assert_eq!(&payload[24..40], &guid_to_bytes(&EFI_CERT_TYPE_PKCS7_GUID));
+        assert_eq!(&payload[40..], &[0xAA, 0xBB, 0x01]);
+    }
+
+    #[test]
+    fn signable_data_layout() {
+        let timestamp = DateTime::new(2018, 4, 1, 10, 20, 30);
+        let attributes = Attributes::NON_VOLATILE | Attributes::TIME_BASED_AUTHENTICATED_WRITE_ACCESS;
+        let guid = EFI_GUID(0x01020304, 0x0506, 0x0708, [9, 10, 11, 12, 13, 14, 15, 16]);
+        let signable = authenticated_write_signable_data("db", &guid, attributes, &timestamp, &[0xFF]);
+        assert_eq!(&signable[0..4], &[b'd', 0, b'b', 0]);
+        assert_eq!(&signable[4..20], &[4, 3, 2, 1, 6, 5, 8, 7, 9, 10, 11, 12, 13, 14, 15, 16]);
+        assert_eq!(&signable[20..24], &[0x21, 0, 0, 0]);
+        assert_eq!(signable.len(), 4 + 16 + 4 + 16 + 1);
+        assert_eq!(authenticated_write_signable_data("db", &guid, Attributes::NON_VOLATILE, &timestamp, &[0xFF]), signable);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,181 @@
+use ffi::{
+    EFI_EVENT,
+    EFI_STATUS,
+    EFI_SUCCESS,
+    UINTN,
+    VOID,
+    boot_services::{EVT_TIMER, EVT_NOTIFY_SIGNAL, TPL_CALLBACK, EFI_TIMER_DELAY},
+};
+use events::as_100ns_units;
+use alloc::boxed::Box;
+use core::{cmp, ptr, time::Duration};
+use {boot_services, Result};
+
+// The firmware watchdog resets the platform if it isn't re-armed or disabled in time. The boot manager arms it
+// for 5 minutes before starting a boot option, so an application that runs longer, e.g. an installer writing
+// gigabytes, has to deal with it. The firmware can't be asked for the current setting, so the crate keeps track
+// of the timeout it last set and otherwise assumes the boot manager's.
+
+/// The timeout the boot manager arms the watchdog with before starting a boot option
+pub const BOOT_MANAGER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+// Codes up to 0xFFFF are reserved for the firmware
+const WATCHDOG_CODE: u64 = 0x1_0000;
+
+// The timeout last set through this module. None means disabled.
+static mut TIMEOUT: Option<Duration> = Some(BOOT_MANAGER_TIMEOUT);
+
+/// The firmware watchdog timer
+pub struct Watchdog;
+
+impl Watchdog {
+    /// Arms the watchdog to reset the platform once the timeout passes. The timeout is rounded up to whole seconds.
+    pub fn set(timeout: Duration) -> Result<()> {
+        set_watchdog(seconds(timeout))?;
+        unsafe { TIMEOUT = Some(timeout); }
+        Ok(())
+    }
+
+    /// Stops the watchdog from resetting the platform
+    pub fn disable() -> Result<()> {
+        set_watchdog(0)?;
+        unsafe { TIMEOUT = None; }
+        Ok(())
+    }
+
+    /// Re-arms the watchdog with the timeout it was last set to, starting the countdown again.
+    /// Does nothing if it's disabled.
+    pub fn refresh() -> Result<()> {
+        match Self::timeout() {
+            Some(timeout) => Self::set(timeout),
+            None => Ok(()),
+        }
+    }
+
+    /// The timeout last set through this module, or the boot manager's if none has been. `None` if disabled.
+    pub fn timeout() -> Option<Duration> {
+        unsafe { TIMEOUT }
+    }
+
+    /// Arms the watchdog with the given timeout and keeps re-arming it from a periodic timer until the returned
+    /// guard is dropped, at which point the previous setting is restored (with its countdown starting over).
+    ///
+    /// ```ignore
+    /// {
+    ///     let _watchdog = Watchdog::scoped(Duration::from_secs(60))?;
+    ///     io::copy(&mut image, &mut disk)?;
+    /// }
+    /// ```
+    ///
+    /// The timer runs at TPL_CALLBACK so the watchdog still goes off if the firmware stops dispatching
+    /// timers, e.g. because something hangs at a raised TPL, but not if the operation loops forever at
+    /// the application's TPL.
+    pub fn scoped(timeout: Duration) -> Result<ScopedWatchdog> {
+        let previous = Self::timeout();
+        let seconds = Box::new(seconds(timeout));
+        Self::set(timeout)?;
+
+        let bs = boot_services();
+        let mut event: EFI_EVENT = ptr::null();
+        let context = &*seconds as *const UINTN as *const VOID;
+        // Re-arming twice per timeout leaves plenty of slack for the timer to be late
+        let period = cmp::max(timeout / 2, Duration::from_secs(1));
+        unsafe {
+            let status = ((*bs).CreateEvent)(EVT_TIMER | EVT_NOTIFY_SIGNAL, TPL_CALLBACK, Some(rearm_watchdog), context, &mut event);
+            if status != EFI_SUCCESS {
+                let _ = restore(previous);
+                return Err(status.into());
+            }
+
+            let status = ((*bs).SetTimer)(event, EFI_TIMER_DELAY::TimerPeriodic, as_100ns_units(&period));
+            if status != EFI_SUCCESS {
+                ((*bs).CloseEvent)(event);
+                let _ = restore(previous);
+                return Err(status.into());
+            }
+        }
+
+        Ok(ScopedWatchdog { event, previous, _seconds: seconds })
+    }
+}
+
+/// Keeps the watchdog armed while it's alive. See `Watchdog::scoped()`.
+pub struct ScopedWatchdog {
+    event: EFI_EVENT,
+    previous: Option<Duration>,
+    _seconds: Box<UINTN>, // The timer's context. On the heap so that it stays put when the guard moves.
+}
+
+impl Drop for ScopedWatchdog {
+    fn drop(&mut self) {
+        let bs = boot_services();
+        unsafe {
+            ((*bs).CloseEvent)(self.event); // Closing the event cancels the timer
+        }
+
+        let _ = restore(self.previous); // Nothing to be done if it fails
+    }
+}
+
+fn restore(timeout: Option<Duration>) -> Result<()> {
+    match timeout {
+        Some(timeout) => Watchdog::set(timeout),
+        None => Watchdog::disable(),
+    }
+}
+
+extern "win64" fn rearm_watchdog(_event: EFI_EVENT, context: *const VOID) -> EFI_STATUS {
+    let seconds = unsafe { *(context as *const UINTN) };
+    let _ = set_watchdog(seconds); // Can't report failures from here
+    EFI_SUCCESS
+}
+
+fn set_watchdog(seconds: UINTN) -> Result<()> {
+    let bs = boot_services();
+    unsafe {
+        ret_on_err!(((*bs).SetWatchdogTimer)(seconds, WATCHDOG_CODE, 0, ptr::null()));
+    }
+
+    Ok(())
+}
+
+// Whole seconds, rounded up so that a non-zero timeout doesn't disable the watchdog
+fn seconds(timeout: Duration) -> UINTN {
+    let seconds = timeout.as_secs() + if timeout.subsec_nanos() > 0 { 1 } else { 0 };
+    cmp::min(seconds, UINTN::max_value() as u64) as UINTN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeouts_round_up_to_seconds() {
+        assert_eq!(seconds(Duration::from_secs(60)), 60);
+        assert_eq!(seconds(Duration::from_millis(1)), 1);
+        assert_eq!(seconds(Duration::from_millis(1500)), 2);
+        assert_eq!(seconds(Duration::from_secs(0)), 0);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn scoped_watchdog_keeps_rearming_and_restores() {
+        use mock::install;
+        use time;
+
+        let env = install();
+        Watchdog::set(Duration::from_secs(600)).unwrap();
+        {
+            let _watchdog = Watchdog::scoped(Duration::from_secs(60)).unwrap();
+            assert_eq!(env.watchdog(), Some(Duration::from_secs(60)));
+            time::sleep(Duration::from_secs(10 * 60)).unwrap();
+            assert!(env.watchdog().unwrap() >= Duration::from_secs(30));
+        }
+        assert_eq!(env.watchdog(), Some(Duration::from_secs(600)));
+
+        Watchdog::disable().unwrap();
+        drop(Watchdog::scoped(Duration::from_secs(60)).unwrap());
+        assert_eq!(env.watchdog(), None);
+        assert_eq!(Watchdog::timeout(), None);
+    }
+}